@@ -1,25 +1,37 @@
-use classic_terraswap::querier::{query_balance, query_pair_info_from_pair};
+use classic_terraswap::querier::{
+    query_balance, query_pair_info_from_pair, query_token_allowance, query_token_info,
+};
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    coin, to_binary, Addr, Binary, Coin, CosmosMsg, Deps, DepsMut, Env, MessageInfo, Reply,
-    ReplyOn, Response, StdError, StdResult, SubMsg, WasmMsg,
+    coin, instantiate2_address, to_binary, to_vec, Addr, Api, Binary, Coin, CosmosMsg, Decimal,
+    Deps, DepsMut, Empty, Env, Event, MessageInfo, Reply, ReplyOn, Response, StdError, StdResult,
+    Storage, SubMsg, SubMsgResult, Uint128, WasmMsg,
 };
 use cw2::set_contract_version;
 use cw20::Cw20ExecuteMsg;
 
 use crate::response::MsgInstantiateContractResponse;
 use crate::state::{
-    add_allow_native_token, pair_key, read_pairs, Config, TmpPairInfo, ALLOW_NATIVE_TOKENS, CONFIG,
-    PAIRS, TMP_PAIR_INFO,
+    add_allow_native_token, add_pair_stats, build_asset_index, count_pairs, index_pair,
+    read_asset_denylist, read_creators, read_duplicate_pairs, read_native_token_decimals,
+    read_pair_entries, read_pair_infos_raw, read_pairs, read_pairs_by_asset, remove_pair_stats,
+    unindex_pair, Config, OwnershipProposal, RecentPair, TmpPairInfo, ALLOW_NATIVE_TOKENS,
+    ASSET_DENYLIST, CONFIG, CREATORS, LAST_REPLY_ID, LP_TOKEN_PAIRS, OWNERSHIP_PROPOSAL, PAIRS,
+    PAIR_COUNT, PAIR_COUNT_CURSOR, PAIR_GENERATIONS, RECENT_PAIRS, TMP_PAIR_INFOS,
 };
 
 use classic_bindings::{TerraMsg, TerraQuery};
 
-use classic_terraswap::asset::{Asset, AssetInfo, AssetInfoRaw, PairInfo, PairInfoRaw};
+use classic_terraswap::asset::{
+    pair_key, Asset, AssetInfo, AssetInfoRaw, PairCreation, PairInfo, PairInfoRaw,
+};
 use classic_terraswap::factory::{
-    ConfigResponse, ExecuteMsg, InstantiateMsg, MigrateMsg, NativeTokenDecimalsResponse,
-    PairsResponse, QueryMsg,
+    AssetDenylistResponse, ConfigResponse, CreatorsResponse, ExecuteMsg, InstantiateMsg,
+    MigrateMsg, NativeTokenDecimalsResponse, OrderBy, PairFeeBounds, PairInitParams, PairsResponse,
+    PredictPairAddressResponse, QueryMsg, RecentPairResponse, RouterBootstrapResponse,
+    StatsResponse, ValidatePairResponse, DEFAULT_MAX_REFERRAL_BPS, MAX_ASSET_DECIMALS,
+    MAX_REFERRAL_BPS_LIMIT,
 };
 use classic_terraswap::pair::{
     ExecuteMsg as PairExecuteMsg, InstantiateMsg as PairInstantiateMsg,
@@ -27,6 +39,7 @@ use classic_terraswap::pair::{
 };
 use classic_terraswap::util::migrate_version;
 use protobuf::Message;
+use sha2::{Digest, Sha256};
 
 // version info for migration info
 const CONTRACT_NAME: &str = "crates.io:terraswap-factory";
@@ -34,6 +47,14 @@ const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
 const CREATE_PAIR_REPLY_ID: u64 = 1;
 
+const DEFAULT_NATIVE_DECIMALS: u8 = 6;
+
+/// The most pairs a `migrate_pairs` or `report_duplicate_pairs` batch reads
+const MAX_MIGRATE_LIMIT: u32 = 30;
+
+/// The longest an ownership proposal can stay claimable, a week
+const MAX_OWNERSHIP_PROPOSAL_EXPIRES_IN: u64 = 7 * 24 * 60 * 60;
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
     deps: DepsMut<TerraQuery>,
@@ -47,9 +68,15 @@ pub fn instantiate(
         owner: deps.api.addr_canonicalize(info.sender.as_str())?,
         token_code_id: msg.token_code_id,
         pair_code_id: msg.pair_code_id,
+        max_referral_bps: DEFAULT_MAX_REFERRAL_BPS,
+        permissioned: false,
+        pair_creation_fee: None,
+        fee_collector: None,
+        pair_fee_bounds: None,
     };
 
     CONFIG.save(deps.storage, &config)?;
+    PAIR_COUNT.save(deps.storage, &0)?;
 
     Ok(Response::new())
 }
@@ -66,18 +93,102 @@ pub fn execute(
             owner,
             token_code_id,
             pair_code_id,
-        } => execute_update_config(deps, env, info, owner, token_code_id, pair_code_id),
-        ExecuteMsg::CreatePair { assets } => execute_create_pair(deps, env, info, assets),
+            max_referral_bps,
+            permissioned,
+        } => execute_update_config(
+            deps,
+            env,
+            info,
+            owner,
+            token_code_id,
+            pair_code_id,
+            max_referral_bps,
+            permissioned,
+        ),
+        ExecuteMsg::CreatePair {
+            assets,
+            allow_unregistered_decimals,
+            pair_params,
+        } => execute_create_pair(
+            deps,
+            env,
+            info,
+            assets,
+            allow_unregistered_decimals.unwrap_or(false),
+            pair_params,
+            None,
+            None,
+        ),
+        ExecuteMsg::CreatePairAndProvide {
+            asset_infos,
+            assets,
+            slippage_tolerance,
+            receiver,
+        } => execute_create_pair_and_provide(
+            deps,
+            env,
+            info,
+            asset_infos,
+            assets,
+            slippage_tolerance,
+            receiver,
+        ),
         ExecuteMsg::AddNativeTokenDecimals { denom, decimals } => {
             execute_add_native_token_decimals(deps, env, info, denom, decimals)
         }
         ExecuteMsg::MigratePair { contract, code_id } => {
             execute_migrate_pair(deps, env, info, contract, code_id)
         }
+        ExecuteMsg::MigratePairs {
+            new_code_id,
+            start_after,
+            limit,
+            msg,
+        } => execute_migrate_pairs(deps, env, info, new_code_id, start_after, limit, msg),
+        ExecuteMsg::UpdatePairsAdmin {
+            new_admin,
+            start_after,
+            limit,
+        } => execute_update_pairs_admin(deps, env, info, new_admin, start_after, limit),
+        ExecuteMsg::ReportDuplicatePairs { start_after, limit } => {
+            execute_report_duplicate_pairs(deps, info, start_after, limit)
+        }
+        ExecuteMsg::UpdatePairDecimals {
+            asset_infos,
+            asset_decimals,
+        } => execute_update_pair_decimals(deps, info, asset_infos, asset_decimals),
+        ExecuteMsg::DeregisterPair { asset_infos } => {
+            execute_deregister_pair(deps, info, asset_infos)
+        }
+        ExecuteMsg::UpdateCreators { add, remove } => {
+            execute_update_creators(deps, info, add, remove)
+        }
+        ExecuteMsg::UpdateAssetDenylist { add, remove } => {
+            execute_update_asset_denylist(deps, info, add, remove)
+        }
+        ExecuteMsg::UpdatePairCreationFee {
+            pair_creation_fee,
+            fee_collector,
+        } => execute_update_pair_creation_fee(deps, info, pair_creation_fee, fee_collector),
+        ExecuteMsg::UpdatePairFeeBounds { pair_fee_bounds } => {
+            execute_update_pair_fee_bounds(deps, info, pair_fee_bounds)
+        }
+        ExecuteMsg::BuildAssetIndex { limit } => execute_build_asset_index(deps, info, limit),
+        ExecuteMsg::ProposeNewOwner { owner, expires_in } => {
+            execute_propose_new_owner(deps, env, info, owner, expires_in)
+        }
+        ExecuteMsg::DropOwnershipProposal {} => execute_drop_ownership_proposal(deps, info),
+        ExecuteMsg::ClaimOwnership {} => execute_claim_ownership(deps, env, info),
+        ExecuteMsg::CountPairs { limit } => execute_count_pairs(deps, info, limit),
+        ExecuteMsg::RefreshPair { asset_infos } => execute_refresh_pair(deps, info, asset_infos),
+        ExecuteMsg::RepairRegistry { start_after, limit } => {
+            execute_repair_registry(deps, info, start_after, limit)
+        }
     }
 }
 
 // Only owner can execute it
+#[allow(clippy::too_many_arguments)]
 pub fn execute_update_config(
     deps: DepsMut<TerraQuery>,
     _env: Env,
@@ -85,6 +196,8 @@ pub fn execute_update_config(
     owner: Option<String>,
     token_code_id: Option<u64>,
     pair_code_id: Option<u64>,
+    max_referral_bps: Option<u16>,
+    permissioned: Option<bool>,
 ) -> StdResult<Response<TerraMsg>> {
     let mut config: Config = CONFIG.load(deps.storage)?;
 
@@ -100,6 +213,10 @@ pub fn execute_update_config(
         config.owner = deps.api.addr_canonicalize(&owner)?;
     }
 
+    if token_code_id == Some(0) || pair_code_id == Some(0) {
+        return Err(StdError::generic_err("code ids must not be zero"));
+    }
+
     if let Some(token_code_id) = token_code_id {
         config.token_code_id = token_code_id;
     }
@@ -108,43 +225,103 @@ pub fn execute_update_config(
         config.pair_code_id = pair_code_id;
     }
 
+    if let Some(max_referral_bps) = max_referral_bps {
+        if max_referral_bps > MAX_REFERRAL_BPS_LIMIT {
+            return Err(StdError::generic_err(format!(
+                "max_referral_bps must not exceed {}",
+                MAX_REFERRAL_BPS_LIMIT
+            )));
+        }
+
+        config.max_referral_bps = max_referral_bps;
+    }
+
+    if let Some(permissioned) = permissioned {
+        config.permissioned = permissioned;
+    }
+
     CONFIG.save(deps.storage, &config)?;
 
     Ok(Response::new().add_attribute("action", "update_config"))
 }
 
 // Anyone can execute it to create swap pair
+#[allow(clippy::too_many_arguments)]
 pub fn execute_create_pair(
     deps: DepsMut<TerraQuery>,
     env: Env,
     info: MessageInfo,
     assets: [Asset; 2],
+    allow_unregistered_decimals: bool,
+    pair_params: Option<PairInitParams>,
+    slippage_tolerance: Option<Decimal>,
+    receiver: Option<Addr>,
 ) -> StdResult<Response<TerraMsg>> {
     let config: Config = CONFIG.load(deps.storage)?;
 
-    if assets[0].info == assets[1].info {
+    let asset_infos = [
+        assets[0].info.validate(deps.api)?,
+        assets[1].info.validate(deps.api)?,
+    ];
+    if asset_infos[0] == asset_infos[1] {
         return Err(StdError::generic_err("same asset"));
     }
 
-    let asset_1_decimal = match assets[0]
-        .info
-        .query_decimals(env.contract.address.clone(), &deps.querier)
-    {
+    // a permissioned factory only takes pairs of the owner and the creators
+    let is_owner = deps.api.addr_canonicalize(info.sender.as_str())? == config.owner;
+    if config.permissioned && !is_owner && !CREATORS.has(deps.storage, &info.sender) {
+        return Err(StdError::generic_err("unauthorized"));
+    }
+
+    // only the owner can skip the native decimals registry
+    if allow_unregistered_decimals && !is_owner {
+        return Err(StdError::generic_err("unauthorized"));
+    }
+
+    let pair_params = pair_params.unwrap_or(PairInitParams {
+        fee_config: None,
+        admin: None,
+        max_spread_cap: None,
+    });
+    if !is_owner {
+        assert_pair_params(&config, &pair_params)?;
+    }
+
+    for asset_info in asset_infos.iter() {
+        let asset_info = asset_info.inner();
+        if ASSET_DENYLIST.has(deps.storage, asset_info.to_raw(deps.api)?.as_bytes()) {
+            return Err(StdError::generic_err(format!(
+                "asset {} is denylisted",
+                asset_info
+            )));
+        }
+    }
+
+    let fee_messages = collect_pair_creation_fee(deps.as_ref(), &config, &info, &assets)?;
+
+    let asset_1_decimal = match query_asset_decimals(
+        deps.as_ref(),
+        &env,
+        asset_infos[0].inner(),
+        allow_unregistered_decimals,
+    ) {
         Ok(decimal) => decimal,
         Err(_) => return Err(StdError::generic_err("asset1 is invalid")),
     };
 
-    let asset_2_decimal = match assets[1]
-        .info
-        .query_decimals(env.contract.address.clone(), &deps.querier)
-    {
+    let asset_2_decimal = match query_asset_decimals(
+        deps.as_ref(),
+        &env,
+        asset_infos[1].inner(),
+        allow_unregistered_decimals,
+    ) {
         Ok(decimal) => decimal,
         Err(_) => return Err(StdError::generic_err("asset2 is invalid")),
     };
 
     let raw_assets = [assets[0].to_raw(deps.api)?, assets[1].to_raw(deps.api)?];
 
-    let asset_infos = [assets[0].info.clone(), assets[1].info.clone()];
+    let asset_infos = asset_infos.map(AssetInfo::from);
     let raw_infos = [
         asset_infos[0].to_raw(deps.api)?,
         asset_infos[1].to_raw(deps.api)?,
@@ -152,30 +329,43 @@ pub fn execute_create_pair(
 
     let asset_decimals = [asset_1_decimal, asset_2_decimal];
 
+    // the key is sorted, so a pair is found for either order of its assets
     let pair_key = pair_key(&raw_infos);
-    if let Ok(Some(_)) = PAIRS.may_load(deps.storage, &pair_key) {
-        return Err(StdError::generic_err("Pair already exists"));
+    if let Some(pair_info) = PAIRS.may_load(deps.storage, &pair_key)? {
+        return Err(StdError::generic_err(format!(
+            "pair already exists: {}",
+            deps.api.addr_humanize(&pair_info.contract_addr)?
+        )));
     }
 
-    TMP_PAIR_INFO.save(
+    // each creation has its own reply id, so its temporary info can not be overwritten
+    let reply_id = LAST_REPLY_ID
+        .may_load(deps.storage)?
+        .map_or(CREATE_PAIR_REPLY_ID, |reply_id| reply_id + 1);
+    LAST_REPLY_ID.save(deps.storage, &reply_id)?;
+    TMP_PAIR_INFOS.save(
         deps.storage,
+        reply_id,
         &TmpPairInfo {
             pair_key,
             assets: raw_assets,
             asset_decimals,
             sender: info.sender,
+            slippage_tolerance,
+            receiver,
         },
     )?;
 
     Ok(Response::new()
         .add_attributes(vec![
             ("action", "create_pair"),
-            ("pair", &format!("{}-{}", assets[0].info, assets[1].info)),
+            ("pair", &format!("{}-{}", asset_infos[0], asset_infos[1])),
         ])
+        .add_messages(fee_messages)
         .add_submessage(SubMsg {
-            id: CREATE_PAIR_REPLY_ID,
+            id: reply_id,
             gas_limit: None,
-            msg: CosmosMsg::Wasm(WasmMsg::Instantiate {
+            msg: CosmosMsg::Wasm(WasmMsg::Instantiate2 {
                 code_id: config.pair_code_id,
                 funds: vec![],
                 admin: Some(env.contract.address.to_string()),
@@ -184,12 +374,231 @@ pub fn execute_create_pair(
                     asset_infos,
                     token_code_id: config.token_code_id,
                     asset_decimals,
+                    lp_fee_rate: pair_params
+                        .fee_config
+                        .as_ref()
+                        .map(|fee_config| fee_config.lp_fee_rate),
+                    protocol_fee_rate: pair_params
+                        .fee_config
+                        .as_ref()
+                        .map(|fee_config| fee_config.protocol_fee_rate),
+                    protocol_fee_collector: pair_params
+                        .fee_config
+                        .and_then(|fee_config| fee_config.protocol_fee_collector),
+                    pair_admin: pair_params.admin,
+                    max_spread_cap: pair_params.max_spread_cap,
                 })?,
+                salt: pair_salt(deps.storage, &raw_infos)?,
             }),
-            reply_on: ReplyOn::Success,
+            // a failed instantiation is replied to tell an address collision apart
+            reply_on: ReplyOn::Always,
         }))
 }
 
+pub fn execute_create_pair_and_provide(
+    deps: DepsMut<TerraQuery>,
+    env: Env,
+    info: MessageInfo,
+    asset_infos: [AssetInfo; 2],
+    assets: [Asset; 2],
+    slippage_tolerance: Option<Decimal>,
+    receiver: Option<String>,
+) -> StdResult<Response<TerraMsg>> {
+    if !asset_infos
+        .iter()
+        .all(|asset_info| assets.iter().any(|asset| asset.info.equal(asset_info)))
+    {
+        return Err(StdError::generic_err("assets must match asset_infos"));
+    }
+
+    if assets.iter().any(|asset| asset.amount.is_zero()) {
+        return Err(StdError::generic_err("provided assets must not be zero"));
+    }
+
+    let config: Config = CONFIG.load(deps.storage)?;
+    for asset in assets.iter() {
+        match &asset.info {
+            AssetInfo::NativeToken { denom } => {
+                // the creation fee is sent along in the same denom
+                let fee = match &config.pair_creation_fee {
+                    Some(fee) if config.fee_collector.is_some() && fee.info.equal(&asset.info) => {
+                        fee.amount
+                    }
+                    _ => Uint128::zero(),
+                };
+                let sent = info
+                    .funds
+                    .iter()
+                    .filter(|coin| &coin.denom == denom)
+                    .map(|coin| coin.amount)
+                    .sum::<Uint128>();
+                if sent.checked_sub(fee).ok() != Some(asset.amount) {
+                    return Err(StdError::generic_err(
+                        "Native token balance mismatch between the argument and the transferred",
+                    ));
+                }
+            }
+            AssetInfo::Token { contract_addr } => {
+                let allowance = query_token_allowance(
+                    &deps.querier,
+                    deps.api.addr_validate(contract_addr)?,
+                    info.sender.clone(),
+                    env.contract.address.clone(),
+                )?;
+                if allowance.allowance < asset.amount || allowance.expires.is_expired(&env.block) {
+                    return Err(StdError::generic_err(format!(
+                        "insufficient allowance of {} to the factory",
+                        contract_addr
+                    )));
+                }
+            }
+        }
+    }
+
+    let receiver = receiver
+        .map(|receiver| deps.api.addr_validate(&receiver))
+        .transpose()?;
+
+    execute_create_pair(
+        deps,
+        env,
+        info,
+        assets,
+        false,
+        None,
+        slippage_tolerance,
+        receiver,
+    )
+}
+
+/// Other creators than the owner can only set fees within the pair fee bounds
+/// and no admin or protocol fee collector
+fn assert_pair_params(config: &Config, pair_params: &PairInitParams) -> StdResult<()> {
+    if pair_params.admin.is_some() {
+        return Err(StdError::generic_err(
+            "only the owner can set the pair admin",
+        ));
+    }
+
+    if let Some(fee_config) = &pair_params.fee_config {
+        if fee_config.protocol_fee_collector.is_some() {
+            return Err(StdError::generic_err(
+                "only the owner can set the protocol fee collector",
+            ));
+        }
+
+        let bounds = config
+            .pair_fee_bounds
+            .as_ref()
+            .ok_or_else(|| StdError::generic_err("only the owner can set the pair fees"))?;
+        let fee_rate = fee_config.lp_fee_rate + fee_config.protocol_fee_rate;
+        if fee_rate < bounds.min_fee_rate || fee_rate > bounds.max_fee_rate {
+            return Err(StdError::generic_err(format!(
+                "pair fee rate must be between {} and {}",
+                bounds.min_fee_rate, bounds.max_fee_rate
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// The instantiate2 salt of a pair, derived from its sorted asset infos and, once
+/// a pair of the assets was deregistered, the number of deregistrations
+fn pair_salt(storage: &dyn Storage, asset_infos: &[AssetInfoRaw; 2]) -> StdResult<Binary> {
+    let mut preimage = pair_key(asset_infos);
+    let generation = PAIR_GENERATIONS
+        .may_load(storage, &preimage)?
+        .unwrap_or_default();
+    if generation > 0 {
+        preimage.extend_from_slice(&generation.to_be_bytes());
+    }
+
+    Ok(Binary::from(Sha256::digest(preimage).to_vec()))
+}
+
+/// The address instantiate2 derives for a pair of `asset_infos` created by this factory
+fn predict_pair_address(
+    deps: Deps<TerraQuery>,
+    env: &Env,
+    code_id: u64,
+    asset_infos: &[AssetInfoRaw; 2],
+) -> StdResult<Addr> {
+    let checksum = deps.querier.query_wasm_code_info(code_id)?.checksum;
+    let creator = deps.api.addr_canonicalize(env.contract.address.as_str())?;
+    let contract_addr = instantiate2_address(
+        checksum.as_slice(),
+        &creator,
+        pair_salt(deps.storage, asset_infos)?.as_slice(),
+    )
+    .map_err(|e| StdError::generic_err(e.to_string()))?;
+
+    deps.api.addr_humanize(&contract_addr)
+}
+
+/// Sends the pair creation fee to the fee collector and refunds the rest of its denom,
+/// beyond the native amounts provided to the pair
+fn collect_pair_creation_fee(
+    deps: Deps<TerraQuery>,
+    config: &Config,
+    info: &MessageInfo,
+    assets: &[Asset; 2],
+) -> StdResult<Vec<CosmosMsg<TerraMsg>>> {
+    let (fee, fee_collector) = match (&config.pair_creation_fee, &config.fee_collector) {
+        (Some(fee), Some(fee_collector)) if !fee.amount.is_zero() => (fee, fee_collector),
+        _ => return Ok(vec![]),
+    };
+
+    let sent = match &fee.info {
+        AssetInfo::NativeToken { denom } => info
+            .funds
+            .iter()
+            .filter(|coin| &coin.denom == denom)
+            .map(|coin| coin.amount)
+            .sum::<Uint128>(),
+        AssetInfo::Token { .. } => Uint128::zero(),
+    };
+    let provided = assets
+        .iter()
+        .filter(|asset| asset.info.equal(&fee.info))
+        .map(|asset| asset.amount)
+        .sum::<Uint128>();
+    let excess = sent
+        .checked_sub(provided)
+        .ok()
+        .and_then(|sent| sent.checked_sub(fee.amount).ok())
+        .ok_or_else(|| StdError::generic_err(format!("pair creation fee of {} required", fee)))?;
+
+    let mut messages = vec![fee
+        .clone()
+        .into_msg(&deps.querier, deps.api.addr_humanize(fee_collector)?)?];
+    if !excess.is_zero() {
+        let refund = Asset {
+            info: fee.info.clone(),
+            amount: excess,
+        };
+        messages.push(refund.into_msg(&deps.querier, info.sender.clone())?);
+    }
+
+    Ok(messages)
+}
+
+/// The decimals of a token, or of a native denom from the registry. Unregistered
+/// denoms are read with the default decimals when allowed
+fn query_asset_decimals(
+    deps: Deps<TerraQuery>,
+    env: &Env,
+    asset_info: &AssetInfo,
+    allow_unregistered_decimals: bool,
+) -> StdResult<u8> {
+    match asset_info.query_decimals(env.contract.address.clone(), &deps.querier) {
+        Err(_) if allow_unregistered_decimals && asset_info.is_native_token() => {
+            Ok(DEFAULT_NATIVE_DECIMALS)
+        }
+        res => res,
+    }
+}
+
 pub fn execute_add_native_token_decimals(
     deps: DepsMut<TerraQuery>,
     env: Env,
@@ -240,137 +649,86 @@ pub fn execute_migrate_pair(
         Response::new().add_message(CosmosMsg::Wasm(WasmMsg::Migrate {
             contract_addr: contract,
             new_code_id: code_id,
-            msg: to_binary(&PairMigrateMsg {})?,
+            msg: to_binary(&PairMigrateMsg {
+                update_liquidity_token: false,
+                liquidity_token_code_id: None,
+            })?,
         })),
     )
 }
 
-/// This just stores the result for future query
-#[cfg_attr(not(feature = "library"), entry_point)]
-pub fn reply(deps: DepsMut<TerraQuery>, env: Env, msg: Reply) -> StdResult<Response<TerraMsg>> {
-    if msg.id != CREATE_PAIR_REPLY_ID {
-        return Err(StdError::generic_err("invalid reply msg"));
-    }
-
-    let tmp_pair_info = TMP_PAIR_INFO.load(deps.storage)?;
-
-    let res: MsgInstantiateContractResponse =
-        Message::parse_from_bytes(msg.result.unwrap().data.unwrap().as_slice()).map_err(|_| {
-            StdError::parse_err("MsgInstantiateContractResponse", "failed to parse data")
-        })?;
-
-    let pair_contract = res.get_contract_address();
-    let pair_info = query_pair_info_from_pair(&deps.querier, Addr::unchecked(pair_contract))?;
+// Only owner can execute it
+pub fn execute_migrate_pairs(
+    deps: DepsMut<TerraQuery>,
+    env: Env,
+    info: MessageInfo,
+    new_code_id: u64,
+    start_after: Option<[AssetInfo; 2]>,
+    limit: u32,
+    msg: Binary,
+) -> StdResult<Response<TerraMsg>> {
+    let config: Config = CONFIG.load(deps.storage)?;
 
-    let raw_infos = [
-        tmp_pair_info.assets[0].info.clone(),
-        tmp_pair_info.assets[1].info.clone(),
-    ];
+    // permission check
+    if deps.api.addr_canonicalize(info.sender.as_str())? != config.owner {
+        return Err(StdError::generic_err("unauthorized"));
+    }
 
-    PAIRS.save(
-        deps.storage,
-        &tmp_pair_info.pair_key,
-        &PairInfoRaw {
-            liquidity_token: deps.api.addr_canonicalize(&pair_info.liquidity_token)?,
-            contract_addr: deps.api.addr_canonicalize(pair_contract)?,
-            asset_infos: raw_infos,
-            asset_decimals: tmp_pair_info.asset_decimals,
-        },
-    )?;
+    let start_after = if let Some(start_after) = start_after {
+        Some([
+            start_after[0].to_raw(deps.api)?,
+            start_after[1].to_raw(deps.api)?,
+        ])
+    } else {
+        None
+    };
+    let limit = limit.min(MAX_MIGRATE_LIMIT) as usize;
+    let pairs = read_pair_infos_raw(deps.storage, start_after, limit)?;
 
+    // pairs the factory can not migrate are listed instead of failing the batch
     let mut messages: Vec<CosmosMsg<TerraMsg>> = vec![];
-    if !tmp_pair_info.assets[0].amount.is_zero() || !tmp_pair_info.assets[1].amount.is_zero() {
-        let assets = [
-            tmp_pair_info.assets[0].to_normal(deps.api)?,
-            tmp_pair_info.assets[1].to_normal(deps.api)?,
-        ];
-        let mut funds: Vec<Coin> = vec![];
-        for asset in tmp_pair_info.assets.iter() {
-            if let AssetInfoRaw::NativeToken { denom, .. } = &asset.info {
-                funds.push(coin(asset.amount.u128(), denom.to_string()));
-            } else if let AssetInfoRaw::Token { contract_addr } = &asset.info {
-                let contract_addr = deps.api.addr_humanize(contract_addr)?.to_string();
-                messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
-                    contract_addr: contract_addr.to_string(),
-                    msg: to_binary(&Cw20ExecuteMsg::IncreaseAllowance {
-                        spender: pair_contract.to_string(),
-                        amount: asset.amount,
-                        expires: None,
-                    })?,
-                    funds: vec![],
-                }));
-                messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
-                    contract_addr,
-                    msg: to_binary(&Cw20ExecuteMsg::TransferFrom {
-                        owner: tmp_pair_info.sender.to_string(),
-                        recipient: env.contract.address.to_string(),
-                        amount: asset.amount,
-                    })?,
-                    funds: vec![],
-                }));
-            }
+    let mut skipped: Vec<String> = vec![];
+    for pair_info in pairs.iter() {
+        let contract_addr = deps.api.addr_humanize(&pair_info.contract_addr)?;
+        if !is_factory_admin(deps.as_ref(), &env, &contract_addr) {
+            skipped.push(contract_addr.to_string());
+            continue;
         }
 
-        funds.sort_by(|a, b| a.denom.cmp(&b.denom));
-        messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
-            contract_addr: pair_contract.to_string(),
-            msg: to_binary(&PairExecuteMsg::ProvideLiquidity {
-                assets,
-                receiver: Some(tmp_pair_info.sender.to_string()),
-                deadline: None,
-                slippage_tolerance: None,
-            })?,
-            funds,
+        messages.push(CosmosMsg::Wasm(WasmMsg::Migrate {
+            contract_addr: contract_addr.to_string(),
+            new_code_id,
+            msg: msg.clone(),
         }));
     }
 
     Ok(Response::new()
         .add_attributes(vec![
-            ("pair_contract_addr", pair_contract),
-            ("liquidity_token_addr", pair_info.liquidity_token.as_str()),
+            ("action", "migrate_pairs"),
+            ("migrated", &messages.len().to_string()),
+            ("skipped", &skipped.join(",")),
+            ("last_pair", &last_pair(deps.api, &pairs)?),
         ])
         .add_messages(messages))
 }
 
-#[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps<TerraQuery>, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
-    match msg {
-        QueryMsg::Config {} => to_binary(&query_config(deps)?),
-        QueryMsg::Pair { asset_infos } => to_binary(&query_pair(deps, asset_infos)?),
-        QueryMsg::Pairs { start_after, limit } => {
-            to_binary(&query_pairs(deps, start_after, limit)?)
-        }
-        QueryMsg::NativeTokenDecimals { denom } => {
-            to_binary(&query_native_token_decimal(deps, denom)?)
-        }
-    }
-}
-
-pub fn query_config(deps: Deps<TerraQuery>) -> StdResult<ConfigResponse> {
-    let state: Config = CONFIG.load(deps.storage)?;
-    let resp = ConfigResponse {
-        owner: deps.api.addr_humanize(&state.owner)?.to_string(),
-        token_code_id: state.token_code_id,
-        pair_code_id: state.pair_code_id,
-    };
-
-    Ok(resp)
-}
+// Only owner can execute it
+pub fn execute_update_pairs_admin(
+    deps: DepsMut<TerraQuery>,
+    env: Env,
+    info: MessageInfo,
+    new_admin: String,
+    start_after: Option<[AssetInfo; 2]>,
+    limit: u32,
+) -> StdResult<Response<TerraMsg>> {
+    let config: Config = CONFIG.load(deps.storage)?;
 
-pub fn query_pair(deps: Deps<TerraQuery>, asset_infos: [AssetInfo; 2]) -> StdResult<PairInfo> {
-    let pair_key = pair_key(&[
-        asset_infos[0].to_raw(deps.api)?,
-        asset_infos[1].to_raw(deps.api)?,
-    ]);
-    let pair_info: PairInfoRaw = PAIRS.load(deps.storage, &pair_key)?;
-    pair_info.to_normal(deps.api)
-}
+    // permission check
+    if deps.api.addr_canonicalize(info.sender.as_str())? != config.owner {
+        return Err(StdError::generic_err("unauthorized"));
+    }
 
-pub fn query_pairs(
-    deps: Deps<TerraQuery>,
-    start_after: Option<[AssetInfo; 2]>,
-    limit: Option<u32>,
-) -> StdResult<PairsResponse> {
+    let new_admin = deps.api.addr_validate(&new_admin)?;
     let start_after = if let Some(start_after) = start_after {
         Some([
             start_after[0].to_raw(deps.api)?,
@@ -379,29 +737,1076 @@ pub fn query_pairs(
     } else {
         None
     };
+    let limit = limit.min(MAX_MIGRATE_LIMIT) as usize;
+    let pairs = read_pair_infos_raw(deps.storage, start_after, limit)?;
 
-    let pairs: Vec<PairInfo> = read_pairs(deps.storage, deps.api, start_after, limit)?;
-    let resp = PairsResponse { pairs };
+    // pairs the factory can not hand over are listed instead of failing the batch
+    let mut messages: Vec<CosmosMsg<TerraMsg>> = vec![];
+    let mut skipped: Vec<String> = vec![];
+    for pair_info in pairs.iter() {
+        let contract_addr = deps.api.addr_humanize(&pair_info.contract_addr)?;
+        if !is_factory_admin(deps.as_ref(), &env, &contract_addr) {
+            skipped.push(contract_addr.to_string());
+            continue;
+        }
 
-    Ok(resp)
+        messages.push(CosmosMsg::Wasm(WasmMsg::UpdateAdmin {
+            contract_addr: contract_addr.to_string(),
+            admin: new_admin.to_string(),
+        }));
+    }
+
+    Ok(Response::new()
+        .add_attributes(vec![
+            ("action", "update_pairs_admin"),
+            ("new_admin", new_admin.as_str()),
+            ("updated", &messages.len().to_string()),
+            ("skipped", &skipped.join(",")),
+            ("last_pair", &last_pair(deps.api, &pairs)?),
+        ])
+        .add_messages(messages))
 }
 
-pub fn query_native_token_decimal(
-    deps: Deps<TerraQuery>,
-    denom: String,
-) -> StdResult<NativeTokenDecimalsResponse> {
-    let decimals = ALLOW_NATIVE_TOKENS.load(deps.storage, denom.as_bytes())?;
+/// Whether the factory is the wasm admin of a contract
+fn is_factory_admin(deps: Deps<TerraQuery>, env: &Env, contract_addr: &Addr) -> bool {
+    let admin = deps
+        .querier
+        .query_wasm_contract_info(contract_addr.to_string())
+        .ok()
+        .and_then(|contract_info| contract_info.admin);
+    admin.as_deref() == Some(env.contract.address.as_str())
+}
 
-    Ok(NativeTokenDecimalsResponse { decimals })
+/// The asset infos of the last pair of a batch as json, to continue the batch after it,
+/// empty once every pair is processed
+fn last_pair(api: &dyn Api, pairs: &[PairInfoRaw]) -> StdResult<String> {
+    match pairs.last() {
+        Some(pair_info) => Ok(String::from_utf8(to_vec(&[
+            pair_info.asset_infos[0].to_normal(api)?,
+            pair_info.asset_infos[1].to_normal(api)?,
+        ])?)?),
+        None => Ok("".to_string()),
+    }
 }
 
-const TARGET_CONTRACT_VERSION: &str = "0.0.0";
-#[cfg_attr(not(feature = "library"), entry_point)]
-pub fn migrate(
+// Only owner can execute it
+pub fn execute_report_duplicate_pairs(
     deps: DepsMut<TerraQuery>,
-    _env: Env,
-    _msg: MigrateMsg,
+    info: MessageInfo,
+    start_after: Option<Binary>,
+    limit: Option<u32>,
 ) -> StdResult<Response<TerraMsg>> {
+    let config: Config = CONFIG.load(deps.storage)?;
+
+    // permission check
+    if deps.api.addr_canonicalize(info.sender.as_str())? != config.owner {
+        return Err(StdError::generic_err("unauthorized"));
+    }
+
+    let limit = limit.unwrap_or(MAX_MIGRATE_LIMIT).min(MAX_MIGRATE_LIMIT) as usize;
+    let (duplicates, last_key) =
+        read_duplicate_pairs(deps.storage, start_after.map(|key| key.to_vec()), limit)?;
+    let duplicates = duplicates
+        .iter()
+        .map(|pair_info| {
+            Ok(deps
+                .api
+                .addr_humanize(&pair_info.contract_addr)?
+                .to_string())
+        })
+        .collect::<StdResult<Vec<String>>>()?;
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "report_duplicate_pairs".to_string()),
+        ("duplicates", duplicates.join(",")),
+        (
+            "last_key",
+            last_key
+                .map(|key| Binary(key).to_base64())
+                .unwrap_or_default(),
+        ),
+    ]))
+}
+
+// Only owner can execute it
+pub fn execute_count_pairs(
+    deps: DepsMut<TerraQuery>,
+    info: MessageInfo,
+    limit: Option<u32>,
+) -> StdResult<Response<TerraMsg>> {
+    let config: Config = CONFIG.load(deps.storage)?;
+
+    // permission check
+    if deps.api.addr_canonicalize(info.sender.as_str())? != config.owner {
+        return Err(StdError::generic_err("unauthorized"));
+    }
+
+    let (counted, complete) = count_pairs(deps.storage, limit)?;
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "count_pairs"),
+        ("counted", &counted.to_string()),
+        ("complete", &complete.to_string()),
+    ]))
+}
+
+/// The decimals of a live pair in the order of the registered assets,
+/// none when the pair holds other assets
+fn live_asset_decimals(registered: &[AssetInfo; 2], live: &PairInfo) -> Option<[u8; 2]> {
+    if live.asset_infos == *registered {
+        Some(live.asset_decimals)
+    } else if live.asset_infos[0] == registered[1] && live.asset_infos[1] == registered[0] {
+        Some([live.asset_decimals[1], live.asset_decimals[0]])
+    } else {
+        None
+    }
+}
+
+// Only owner can execute it to resync a registered pair with its contract
+pub fn execute_refresh_pair(
+    deps: DepsMut<TerraQuery>,
+    info: MessageInfo,
+    asset_infos: [AssetInfo; 2],
+) -> StdResult<Response<TerraMsg>> {
+    let config: Config = CONFIG.load(deps.storage)?;
+
+    // permission check
+    if deps.api.addr_canonicalize(info.sender.as_str())? != config.owner {
+        return Err(StdError::generic_err("unauthorized"));
+    }
+
+    let pair_key = pair_key(&[
+        asset_infos[0].to_raw(deps.api)?,
+        asset_infos[1].to_raw(deps.api)?,
+    ]);
+    let mut pair_info_raw: PairInfoRaw = PAIRS.load(deps.storage, &pair_key)?;
+    let registered = pair_info_raw.to_normal(deps.api)?;
+    let live = query_pair_info_from_pair(
+        &deps.querier,
+        Addr::unchecked(registered.contract_addr.as_str()),
+    )?;
+    let asset_decimals = live_asset_decimals(&registered.asset_infos, &live)
+        .ok_or_else(|| StdError::generic_err("pair assets do not match the registry"))?;
+
+    // the new liquidity token must be a token
+    let liquidity_token = deps.api.addr_validate(&live.liquidity_token)?;
+    query_token_info(&deps.querier, liquidity_token.clone())?;
+
+    unindex_pair(deps.storage, &pair_key, &pair_info_raw);
+    pair_info_raw.liquidity_token = deps.api.addr_canonicalize(liquidity_token.as_str())?;
+    pair_info_raw.asset_decimals = asset_decimals;
+    PAIRS.save(deps.storage, &pair_key, &pair_info_raw)?;
+    index_pair(deps.storage, &pair_key, &pair_info_raw)?;
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "refresh_pair"),
+        ("pair", &format!("{}-{}", asset_infos[0], asset_infos[1])),
+        ("liquidity_token", liquidity_token.as_str()),
+        (
+            "asset_decimals",
+            &format!("{}, {}", asset_decimals[0], asset_decimals[1]),
+        ),
+    ]))
+}
+
+/// How a registry entry compares with its live pair
+enum RegistryRepair {
+    Unchanged,
+    Repaired(PairInfoRaw),
+    /// The live pair holds the assets of another key, the entry is left as it is
+    Mismatched,
+}
+
+/// The pair info of a registry entry rewritten from its live pair. The entry keeps its key,
+/// so the live assets must still belong to it, in either order
+fn repair_pair_info(
+    api: &dyn Api,
+    key: &[u8],
+    registered: &PairInfoRaw,
+    live: &PairInfo,
+) -> StdResult<RegistryRepair> {
+    let live_asset_infos = [
+        live.asset_infos[0].to_raw(api)?,
+        live.asset_infos[1].to_raw(api)?,
+    ];
+    if pair_key(&live_asset_infos) != key {
+        return Ok(RegistryRepair::Mismatched);
+    }
+
+    let mut pair_info = registered.clone();
+    pair_info.liquidity_token = api.addr_canonicalize(&live.liquidity_token)?;
+    let registered_asset_infos = [
+        registered.asset_infos[0].to_normal(api)?,
+        registered.asset_infos[1].to_normal(api)?,
+    ];
+    match live_asset_decimals(&registered_asset_infos, live) {
+        Some(asset_decimals) => pair_info.asset_decimals = asset_decimals,
+        // the entry recorded other assets under the key of the live ones
+        None => {
+            pair_info.asset_infos = live_asset_infos;
+            pair_info.asset_decimals = live.asset_decimals;
+        }
+    }
+
+    Ok(if pair_info == *registered {
+        RegistryRepair::Unchanged
+    } else {
+        RegistryRepair::Repaired(pair_info)
+    })
+}
+
+// Only owner can execute it to resync a batch of registered pairs with their contracts
+pub fn execute_repair_registry(
+    deps: DepsMut<TerraQuery>,
+    info: MessageInfo,
+    start_after: Option<Binary>,
+    limit: Option<u32>,
+) -> StdResult<Response<TerraMsg>> {
+    let config: Config = CONFIG.load(deps.storage)?;
+
+    // permission check
+    if deps.api.addr_canonicalize(info.sender.as_str())? != config.owner {
+        return Err(StdError::generic_err("unauthorized"));
+    }
+
+    let limit = limit.unwrap_or(MAX_MIGRATE_LIMIT).min(MAX_MIGRATE_LIMIT) as usize;
+    let (pairs, last_key) =
+        read_pair_entries(deps.storage, start_after.map(|key| key.to_vec()), limit)?;
+
+    // pairs which do not answer with a token as liquidity token, or hold the assets of
+    // another key, are listed instead of failing the batch
+    let mut repaired: Vec<String> = vec![];
+    let mut skipped: Vec<String> = vec![];
+    let mut mismatched: Vec<String> = vec![];
+    for (key, pair_info_raw) in pairs {
+        let contract_addr = deps.api.addr_humanize(&pair_info_raw.contract_addr)?;
+        let live = match query_pair_info_from_pair(&deps.querier, contract_addr.clone()) {
+            Ok(live)
+                if query_token_info(&deps.querier, Addr::unchecked(&live.liquidity_token))
+                    .is_ok() =>
+            {
+                live
+            }
+            _ => {
+                skipped.push(contract_addr.to_string());
+                continue;
+            }
+        };
+
+        match repair_pair_info(deps.api, &key, &pair_info_raw, &live)? {
+            RegistryRepair::Unchanged => {}
+            RegistryRepair::Repaired(repaired_info) => {
+                unindex_pair(deps.storage, &key, &pair_info_raw);
+                PAIRS.save(deps.storage, &key, &repaired_info)?;
+                index_pair(deps.storage, &key, &repaired_info)?;
+                repaired.push(Binary(key).to_base64());
+            }
+            RegistryRepair::Mismatched => mismatched.push(contract_addr.to_string()),
+        }
+    }
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "repair_registry".to_string()),
+        ("repaired", repaired.join(",")),
+        ("skipped", skipped.join(",")),
+        ("mismatched", mismatched.join(",")),
+        (
+            "last_key",
+            last_key
+                .map(|key| Binary(key).to_base64())
+                .unwrap_or_default(),
+        ),
+    ]))
+}
+
+// Only owner can execute it to correct the decimals of a pair
+pub fn execute_update_pair_decimals(
+    deps: DepsMut<TerraQuery>,
+    info: MessageInfo,
+    asset_infos: [AssetInfo; 2],
+    asset_decimals: [u8; 2],
+) -> StdResult<Response<TerraMsg>> {
+    let config: Config = CONFIG.load(deps.storage)?;
+
+    // permission check
+    if deps.api.addr_canonicalize(info.sender.as_str())? != config.owner {
+        return Err(StdError::generic_err("unauthorized"));
+    }
+
+    if asset_decimals
+        .iter()
+        .any(|decimals| *decimals > MAX_ASSET_DECIMALS)
+    {
+        return Err(StdError::generic_err(format!(
+            "asset decimals must not exceed {}",
+            MAX_ASSET_DECIMALS
+        )));
+    }
+
+    let raw_infos = [
+        asset_infos[0].to_raw(deps.api)?,
+        asset_infos[1].to_raw(deps.api)?,
+    ];
+    let pair_key = pair_key(&raw_infos);
+    let mut pair_info: PairInfoRaw = PAIRS.load(deps.storage, &pair_key)?;
+
+    // the decimals are given in the order of `asset_infos`
+    pair_info.asset_decimals = if pair_info.asset_infos[0].equal(&raw_infos[0]) {
+        asset_decimals
+    } else {
+        [asset_decimals[1], asset_decimals[0]]
+    };
+    PAIRS.save(deps.storage, &pair_key, &pair_info)?;
+
+    Ok(Response::new()
+        .add_message(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: deps
+                .api
+                .addr_humanize(&pair_info.contract_addr)?
+                .to_string(),
+            msg: to_binary(&PairExecuteMsg::UpdateAssetDecimals {
+                asset_decimals: pair_info.asset_decimals,
+            })?,
+            funds: vec![],
+        }))
+        .add_attributes(vec![
+            ("action", "update_pair_decimals"),
+            ("pair", &format!("{}-{}", asset_infos[0], asset_infos[1])),
+            (
+                "asset_decimals",
+                &format!(
+                    "{}, {}",
+                    pair_info.asset_decimals[0], pair_info.asset_decimals[1]
+                ),
+            ),
+        ]))
+}
+
+pub fn execute_deregister_pair(
+    deps: DepsMut<TerraQuery>,
+    info: MessageInfo,
+    asset_infos: [AssetInfo; 2],
+) -> StdResult<Response<TerraMsg>> {
+    let config: Config = CONFIG.load(deps.storage)?;
+
+    // permission check
+    if deps.api.addr_canonicalize(info.sender.as_str())? != config.owner {
+        return Err(StdError::generic_err("unauthorized"));
+    }
+
+    let pair_key = pair_key(&[
+        asset_infos[0].to_raw(deps.api)?,
+        asset_infos[1].to_raw(deps.api)?,
+    ]);
+    let pair_info: PairInfoRaw = PAIRS.load(deps.storage, &pair_key)?;
+    PAIRS.remove(deps.storage, &pair_key);
+    unindex_pair(deps.storage, &pair_key, &pair_info);
+    remove_pair_stats(deps.storage, &pair_key)?;
+    // the next pair of the assets is instantiated at a new address
+    PAIR_GENERATIONS.update(deps.storage, &pair_key, |generation| -> StdResult<_> {
+        Ok(generation.unwrap_or_default() + 1)
+    })?;
+
+    let pair = format!("{}-{}", asset_infos[0], asset_infos[1]);
+    let pair_contract_addr = deps.api.addr_humanize(&pair_info.contract_addr)?;
+
+    Ok(Response::new()
+        .add_attributes(vec![
+            ("action", "deregister_pair"),
+            ("pair", &pair),
+            ("pair_contract_addr", pair_contract_addr.as_str()),
+        ])
+        .add_event(
+            Event::new("terraswap_deregister_pair")
+                .add_attribute("pair", &pair)
+                .add_attribute("pair_contract_addr", pair_contract_addr.as_str()),
+        ))
+}
+
+// Only owner can execute it
+pub fn execute_update_creators(
+    deps: DepsMut<TerraQuery>,
+    info: MessageInfo,
+    add: Vec<String>,
+    remove: Vec<String>,
+) -> StdResult<Response<TerraMsg>> {
+    let config: Config = CONFIG.load(deps.storage)?;
+
+    // permission check
+    if deps.api.addr_canonicalize(info.sender.as_str())? != config.owner {
+        return Err(StdError::generic_err("unauthorized"));
+    }
+
+    for creator in add.iter() {
+        CREATORS.save(deps.storage, &deps.api.addr_validate(creator)?, &Empty {})?;
+    }
+    for creator in remove.iter() {
+        CREATORS.remove(deps.storage, &deps.api.addr_validate(creator)?);
+    }
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "update_creators"),
+        ("added", &add.join(",")),
+        ("removed", &remove.join(",")),
+    ]))
+}
+
+// Only owner can execute it
+pub fn execute_propose_new_owner(
+    deps: DepsMut<TerraQuery>,
+    env: Env,
+    info: MessageInfo,
+    owner: String,
+    expires_in: u64,
+) -> StdResult<Response<TerraMsg>> {
+    let config: Config = CONFIG.load(deps.storage)?;
+
+    // permission check
+    if deps.api.addr_canonicalize(info.sender.as_str())? != config.owner {
+        return Err(StdError::generic_err("unauthorized"));
+    }
+
+    if expires_in > MAX_OWNERSHIP_PROPOSAL_EXPIRES_IN {
+        return Err(StdError::generic_err(format!(
+            "expires_in must not exceed {} seconds",
+            MAX_OWNERSHIP_PROPOSAL_EXPIRES_IN
+        )));
+    }
+
+    let proposal = OwnershipProposal {
+        owner: deps.api.addr_validate(&owner)?,
+        expires_at: env.block.time.plus_seconds(expires_in).seconds(),
+    };
+    OWNERSHIP_PROPOSAL.save(deps.storage, &proposal)?;
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "propose_new_owner"),
+        ("owner", proposal.owner.as_str()),
+        ("expires_at", &proposal.expires_at.to_string()),
+    ]))
+}
+
+// Only owner can execute it
+pub fn execute_drop_ownership_proposal(
+    deps: DepsMut<TerraQuery>,
+    info: MessageInfo,
+) -> StdResult<Response<TerraMsg>> {
+    let config: Config = CONFIG.load(deps.storage)?;
+
+    // permission check
+    if deps.api.addr_canonicalize(info.sender.as_str())? != config.owner {
+        return Err(StdError::generic_err("unauthorized"));
+    }
+
+    OWNERSHIP_PROPOSAL.remove(deps.storage);
+
+    Ok(Response::new().add_attribute("action", "drop_ownership_proposal"))
+}
+
+// Only the proposed owner can execute it
+pub fn execute_claim_ownership(
+    deps: DepsMut<TerraQuery>,
+    env: Env,
+    info: MessageInfo,
+) -> StdResult<Response<TerraMsg>> {
+    let proposal = OWNERSHIP_PROPOSAL
+        .may_load(deps.storage)?
+        .ok_or_else(|| StdError::generic_err("no ownership proposal"))?;
+
+    if info.sender != proposal.owner {
+        return Err(StdError::generic_err("unauthorized"));
+    }
+
+    if env.block.time.seconds() >= proposal.expires_at {
+        return Err(StdError::generic_err("ownership proposal expired"));
+    }
+
+    let mut config: Config = CONFIG.load(deps.storage)?;
+    config.owner = deps.api.addr_canonicalize(proposal.owner.as_str())?;
+    CONFIG.save(deps.storage, &config)?;
+    OWNERSHIP_PROPOSAL.remove(deps.storage);
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "claim_ownership"),
+        ("owner", proposal.owner.as_str()),
+    ]))
+}
+
+// Only owner can execute it
+pub fn execute_update_asset_denylist(
+    deps: DepsMut<TerraQuery>,
+    info: MessageInfo,
+    add: Vec<AssetInfo>,
+    remove: Vec<AssetInfo>,
+) -> StdResult<Response<TerraMsg>> {
+    let config: Config = CONFIG.load(deps.storage)?;
+
+    // permission check
+    if deps.api.addr_canonicalize(info.sender.as_str())? != config.owner {
+        return Err(StdError::generic_err("unauthorized"));
+    }
+
+    for asset_info in add.iter() {
+        let raw_info = asset_info.to_raw(deps.api)?;
+        ASSET_DENYLIST.save(deps.storage, raw_info.as_bytes(), &raw_info)?;
+    }
+    for asset_info in remove.iter() {
+        ASSET_DENYLIST.remove(deps.storage, asset_info.to_raw(deps.api)?.as_bytes());
+    }
+
+    let join = |asset_infos: &[AssetInfo]| {
+        asset_infos
+            .iter()
+            .map(|asset_info| asset_info.to_string())
+            .collect::<Vec<String>>()
+            .join(",")
+    };
+    Ok(Response::new().add_attributes(vec![
+        ("action", "update_asset_denylist"),
+        ("added", &join(&add)),
+        ("removed", &join(&remove)),
+    ]))
+}
+
+// Only owner can execute it
+pub fn execute_update_pair_creation_fee(
+    deps: DepsMut<TerraQuery>,
+    info: MessageInfo,
+    pair_creation_fee: Option<Asset>,
+    fee_collector: Option<String>,
+) -> StdResult<Response<TerraMsg>> {
+    let mut config: Config = CONFIG.load(deps.storage)?;
+
+    // permission check
+    if deps.api.addr_canonicalize(info.sender.as_str())? != config.owner {
+        return Err(StdError::generic_err("unauthorized"));
+    }
+
+    if let Some(pair_creation_fee) = &pair_creation_fee {
+        if !pair_creation_fee.is_native_token() {
+            return Err(StdError::generic_err(
+                "pair_creation_fee must be a native token",
+            ));
+        }
+        if fee_collector.is_none() {
+            return Err(StdError::generic_err(
+                "pair_creation_fee requires a fee_collector",
+            ));
+        }
+    }
+
+    config.fee_collector = fee_collector
+        .map(|fee_collector| deps.api.addr_canonicalize(&fee_collector))
+        .transpose()?;
+    config.pair_creation_fee = pair_creation_fee;
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "update_pair_creation_fee"),
+        (
+            "pair_creation_fee",
+            &config
+                .pair_creation_fee
+                .map_or_else(|| "none".to_string(), |fee| fee.to_string()),
+        ),
+    ]))
+}
+
+// Only owner can execute it
+pub fn execute_update_pair_fee_bounds(
+    deps: DepsMut<TerraQuery>,
+    info: MessageInfo,
+    pair_fee_bounds: Option<PairFeeBounds>,
+) -> StdResult<Response<TerraMsg>> {
+    let mut config: Config = CONFIG.load(deps.storage)?;
+
+    // permission check
+    if deps.api.addr_canonicalize(info.sender.as_str())? != config.owner {
+        return Err(StdError::generic_err("unauthorized"));
+    }
+
+    if let Some(bounds) = &pair_fee_bounds {
+        if bounds.min_fee_rate > bounds.max_fee_rate {
+            return Err(StdError::generic_err(
+                "min_fee_rate must not exceed max_fee_rate",
+            ));
+        }
+    }
+
+    config.pair_fee_bounds = pair_fee_bounds;
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "update_pair_fee_bounds"),
+        (
+            "pair_fee_bounds",
+            &config.pair_fee_bounds.map_or_else(
+                || "none".to_string(),
+                |bounds| format!("{}-{}", bounds.min_fee_rate, bounds.max_fee_rate),
+            ),
+        ),
+    ]))
+}
+
+// Only owner can execute it
+pub fn execute_build_asset_index(
+    deps: DepsMut<TerraQuery>,
+    info: MessageInfo,
+    limit: Option<u32>,
+) -> StdResult<Response<TerraMsg>> {
+    let config: Config = CONFIG.load(deps.storage)?;
+
+    // permission check
+    if deps.api.addr_canonicalize(info.sender.as_str())? != config.owner {
+        return Err(StdError::generic_err("unauthorized"));
+    }
+
+    let (indexed, complete) = build_asset_index(deps.storage, limit)?;
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "build_asset_index"),
+        ("indexed", &indexed.to_string()),
+        ("complete", &complete.to_string()),
+    ]))
+}
+
+/// This just stores the result for future query
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn reply(deps: DepsMut<TerraQuery>, env: Env, msg: Reply) -> StdResult<Response<TerraMsg>> {
+    let tmp_pair_info = match TMP_PAIR_INFOS.may_load(deps.storage, msg.id)? {
+        Some(tmp_pair_info) => tmp_pair_info,
+        None => return Err(StdError::generic_err("invalid reply msg")),
+    };
+    TMP_PAIR_INFOS.remove(deps.storage, msg.id);
+
+    let data = match msg.result {
+        SubMsgResult::Ok(res) => res.data,
+        // a deregistered pair still holds the address of its assets
+        SubMsgResult::Err(err) if is_address_collision(&err) => {
+            let config: Config = CONFIG.load(deps.storage)?;
+            let asset_infos = [
+                tmp_pair_info.assets[0].info.clone(),
+                tmp_pair_info.assets[1].info.clone(),
+            ];
+            let contract_addr =
+                predict_pair_address(deps.as_ref(), &env, config.pair_code_id, &asset_infos)?;
+            return Err(StdError::generic_err(format!(
+                "pair already exists: {}",
+                contract_addr
+            )));
+        }
+        SubMsgResult::Err(err) => return Err(StdError::generic_err(err)),
+    };
+
+    let res: MsgInstantiateContractResponse = Message::parse_from_bytes(data.unwrap().as_slice())
+        .map_err(|_| {
+        StdError::parse_err("MsgInstantiateContractResponse", "failed to parse data")
+    })?;
+
+    let pair_contract = res.get_contract_address();
+    let pair_info = query_pair_info_from_pair(&deps.querier, Addr::unchecked(pair_contract))?;
+
+    let raw_infos = [
+        tmp_pair_info.assets[0].info.clone(),
+        tmp_pair_info.assets[1].info.clone(),
+    ];
+
+    let pair_info_raw = PairInfoRaw {
+        liquidity_token: deps.api.addr_canonicalize(&pair_info.liquidity_token)?,
+        contract_addr: deps.api.addr_canonicalize(pair_contract)?,
+        asset_infos: raw_infos,
+        asset_decimals: tmp_pair_info.asset_decimals,
+        created_at: Some(PairCreation {
+            height: env.block.height,
+            time: env.block.time.seconds(),
+        }),
+    };
+    PAIRS.save(deps.storage, &tmp_pair_info.pair_key, &pair_info_raw)?;
+    index_pair(deps.storage, &tmp_pair_info.pair_key, &pair_info_raw)?;
+    add_pair_stats(
+        deps.storage,
+        &tmp_pair_info.pair_key,
+        RecentPair {
+            contract_addr: Addr::unchecked(pair_contract),
+            created_at: env.block.time.seconds(),
+        },
+    )?;
+
+    let mut messages: Vec<CosmosMsg<TerraMsg>> = vec![];
+    if !tmp_pair_info.assets[0].amount.is_zero() || !tmp_pair_info.assets[1].amount.is_zero() {
+        let assets = [
+            tmp_pair_info.assets[0].to_normal(deps.api)?,
+            tmp_pair_info.assets[1].to_normal(deps.api)?,
+        ];
+        let mut funds: Vec<Coin> = vec![];
+        for asset in tmp_pair_info.assets.iter() {
+            if let AssetInfoRaw::NativeToken { denom, .. } = &asset.info {
+                funds.push(coin(asset.amount.u128(), denom.to_string()));
+            } else if let AssetInfoRaw::Token { contract_addr } = &asset.info {
+                let contract_addr = deps.api.addr_humanize(contract_addr)?.to_string();
+                messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+                    contract_addr: contract_addr.to_string(),
+                    msg: to_binary(&Cw20ExecuteMsg::IncreaseAllowance {
+                        spender: pair_contract.to_string(),
+                        amount: asset.amount,
+                        expires: None,
+                    })?,
+                    funds: vec![],
+                }));
+                messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+                    contract_addr,
+                    msg: to_binary(&Cw20ExecuteMsg::TransferFrom {
+                        owner: tmp_pair_info.sender.to_string(),
+                        recipient: env.contract.address.to_string(),
+                        amount: asset.amount,
+                    })?,
+                    funds: vec![],
+                }));
+            }
+        }
+
+        funds.sort_by(|a, b| a.denom.cmp(&b.denom));
+        messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: pair_contract.to_string(),
+            msg: to_binary(&PairExecuteMsg::ProvideLiquidity {
+                assets,
+                receiver: Some(
+                    tmp_pair_info
+                        .receiver
+                        .unwrap_or_else(|| tmp_pair_info.sender.clone())
+                        .to_string(),
+                ),
+                deadline: None,
+                slippage_tolerance: tmp_pair_info.slippage_tolerance,
+                min_lp_to_receive: None,
+            })?,
+            funds,
+        }));
+    }
+
+    let asset_infos = [
+        pair_info_raw.asset_infos[0].to_normal(deps.api)?,
+        pair_info_raw.asset_infos[1].to_normal(deps.api)?,
+    ];
+    let event = Event::new("terraswap_pair_created").add_attributes(vec![
+        ("pair_contract", pair_contract.to_string()),
+        ("liquidity_token", pair_info.liquidity_token.clone()),
+        ("asset0", asset_infos[0].to_string()),
+        ("asset1", asset_infos[1].to_string()),
+        (
+            "decimals",
+            format!(
+                "{},{}",
+                tmp_pair_info.asset_decimals[0], tmp_pair_info.asset_decimals[1]
+            ),
+        ),
+        ("creator", tmp_pair_info.sender.to_string()),
+    ]);
+
+    Ok(Response::new()
+        .add_attributes(vec![
+            ("pair_contract_addr", pair_contract),
+            ("liquidity_token_addr", pair_info.liquidity_token.as_str()),
+        ])
+        .add_event(event)
+        .add_messages(messages))
+}
+
+/// Whether an instantiate2 error is the duplicate address error of wasmd,
+/// which is redacted to its error code in replies
+fn is_address_collision(err: &str) -> bool {
+    err.contains("duplicate")
+        || err.contains("already exists")
+        || err.ends_with("codespace: wasm, code: 22")
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(deps: Deps<TerraQuery>, env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::Config {} => to_binary(&query_config(deps)?),
+        QueryMsg::Pair { asset_infos } => to_binary(&query_pair(deps, asset_infos)?),
+        QueryMsg::Pairs {
+            start_after,
+            limit,
+            order,
+        } => to_binary(&query_pairs(deps, start_after, limit, order)?),
+        QueryMsg::PairByLpToken { lp_token } => to_binary(&query_pair_by_lp_token(deps, lp_token)?),
+        QueryMsg::PairsByAsset {
+            asset_info,
+            start_after,
+            limit,
+        } => to_binary(&query_pairs_by_asset(deps, asset_info, start_after, limit)?),
+        QueryMsg::NativeTokenDecimals { denom } => {
+            to_binary(&query_native_token_decimal(deps, denom)?)
+        }
+        QueryMsg::Creators { start_after, limit } => {
+            to_binary(&query_creators(deps, start_after, limit)?)
+        }
+        QueryMsg::AssetDenylist { start_after, limit } => {
+            to_binary(&query_asset_denylist(deps, start_after, limit)?)
+        }
+        QueryMsg::PredictPairAddress { asset_infos } => {
+            to_binary(&query_predict_pair_address(deps, env, asset_infos)?)
+        }
+        QueryMsg::Stats {} => to_binary(&query_stats(deps)?),
+        QueryMsg::ValidatePair { asset_infos } => {
+            to_binary(&query_validate_pair(deps, asset_infos)?)
+        }
+        QueryMsg::RouterBootstrap {} => to_binary(&query_router_bootstrap(deps, env)?),
+    }
+}
+
+pub fn query_config(deps: Deps<TerraQuery>) -> StdResult<ConfigResponse> {
+    let state: Config = CONFIG.load(deps.storage)?;
+    let resp = ConfigResponse {
+        owner: deps.api.addr_humanize(&state.owner)?.to_string(),
+        token_code_id: state.token_code_id,
+        pair_code_id: state.pair_code_id,
+        max_referral_bps: state.max_referral_bps,
+        permissioned: state.permissioned,
+        pair_creation_fee: state.pair_creation_fee,
+        fee_collector: state
+            .fee_collector
+            .map(|fee_collector| deps.api.addr_humanize(&fee_collector))
+            .transpose()?
+            .map(|fee_collector| fee_collector.to_string()),
+        pending_owner: OWNERSHIP_PROPOSAL
+            .may_load(deps.storage)?
+            .map(|proposal| proposal.owner.to_string()),
+        pair_fee_bounds: state.pair_fee_bounds,
+    };
+
+    Ok(resp)
+}
+
+pub fn query_pair(deps: Deps<TerraQuery>, asset_infos: [AssetInfo; 2]) -> StdResult<PairInfo> {
+    let pair_key = pair_key(&[
+        asset_infos[0].to_raw(deps.api)?,
+        asset_infos[1].to_raw(deps.api)?,
+    ]);
+    let pair_info: PairInfoRaw = PAIRS.load(deps.storage, &pair_key)?;
+    pair_info.to_normal(deps.api)
+}
+
+pub fn query_predict_pair_address(
+    deps: Deps<TerraQuery>,
+    env: Env,
+    asset_infos: [AssetInfo; 2],
+) -> StdResult<PredictPairAddressResponse> {
+    let config: Config = CONFIG.load(deps.storage)?;
+    let raw_infos = [
+        asset_infos[0].to_raw(deps.api)?,
+        asset_infos[1].to_raw(deps.api)?,
+    ];
+    let contract_addr = predict_pair_address(deps, &env, config.pair_code_id, &raw_infos)?;
+
+    Ok(PredictPairAddressResponse {
+        contract_addr: contract_addr.to_string(),
+    })
+}
+
+pub fn query_stats(deps: Deps<TerraQuery>) -> StdResult<StatsResponse> {
+    let recent_pairs = RECENT_PAIRS
+        .may_load(deps.storage)?
+        .unwrap_or_default()
+        .into_iter()
+        .rev()
+        .map(|recent_pair| RecentPairResponse {
+            contract_addr: recent_pair.contract_addr.to_string(),
+            created_at: recent_pair.created_at,
+        })
+        .collect();
+
+    Ok(StatsResponse {
+        pair_count: PAIR_COUNT.may_load(deps.storage)?.unwrap_or_default(),
+        pair_count_complete: !PAIR_COUNT_CURSOR.exists(deps.storage),
+        recent_pairs,
+    })
+}
+
+pub fn query_router_bootstrap(
+    deps: Deps<TerraQuery>,
+    env: Env,
+) -> StdResult<RouterBootstrapResponse> {
+    let config: Config = CONFIG.load(deps.storage)?;
+
+    Ok(RouterBootstrapResponse {
+        factory: env.contract.address.to_string(),
+        pair_code_id: config.pair_code_id,
+        token_code_id: config.token_code_id,
+        native_token_decimals: read_native_token_decimals(deps.storage)?,
+        pair_count: PAIR_COUNT.may_load(deps.storage)?.unwrap_or_default(),
+        pair_count_complete: !PAIR_COUNT_CURSOR.exists(deps.storage),
+    })
+}
+
+pub fn query_validate_pair(
+    deps: Deps<TerraQuery>,
+    asset_infos: [AssetInfo; 2],
+) -> StdResult<ValidatePairResponse> {
+    let registered = query_pair(deps, asset_infos)?;
+
+    let mut mismatches: Vec<String> = vec![];
+    match query_pair_info_from_pair(
+        &deps.querier,
+        Addr::unchecked(registered.contract_addr.as_str()),
+    ) {
+        Ok(live) => {
+            if live.contract_addr != registered.contract_addr {
+                mismatches.push(format!(
+                    "contract_addr: registered {}, live {}",
+                    registered.contract_addr, live.contract_addr
+                ));
+            }
+            if live.liquidity_token != registered.liquidity_token {
+                mismatches.push(format!(
+                    "liquidity_token: registered {}, live {}",
+                    registered.liquidity_token, live.liquidity_token
+                ));
+            }
+            match live_asset_decimals(&registered.asset_infos, &live) {
+                Some(asset_decimals) if asset_decimals != registered.asset_decimals => mismatches
+                    .push(format!(
+                        "asset_decimals: registered {:?}, live {:?}",
+                        registered.asset_decimals, asset_decimals
+                    )),
+                Some(_) => {}
+                None => mismatches.push(format!(
+                    "asset_infos: registered {}-{}, live {}-{}",
+                    registered.asset_infos[0],
+                    registered.asset_infos[1],
+                    live.asset_infos[0],
+                    live.asset_infos[1]
+                )),
+            }
+        }
+        Err(err) => mismatches.push(format!("pair query failed: {}", err)),
+    }
+
+    if let Err(err) = query_token_info(
+        &deps.querier,
+        Addr::unchecked(registered.liquidity_token.as_str()),
+    ) {
+        mismatches.push(format!("liquidity token query failed: {}", err));
+    }
+
+    Ok(ValidatePairResponse {
+        registered,
+        live_matches: mismatches.is_empty(),
+        mismatches,
+    })
+}
+
+pub fn query_creators(
+    deps: Deps<TerraQuery>,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<CreatorsResponse> {
+    let start_after = start_after
+        .map(|start_after| deps.api.addr_validate(&start_after))
+        .transpose()?;
+    let creators = read_creators(deps.storage, start_after, limit)?;
+
+    Ok(CreatorsResponse { creators })
+}
+
+pub fn query_asset_denylist(
+    deps: Deps<TerraQuery>,
+    start_after: Option<AssetInfo>,
+    limit: Option<u32>,
+) -> StdResult<AssetDenylistResponse> {
+    let start_after = start_after
+        .map(|start_after| start_after.to_raw(deps.api))
+        .transpose()?;
+    let assets = read_asset_denylist(deps.storage, deps.api, start_after, limit)?;
+
+    Ok(AssetDenylistResponse { assets })
+}
+
+pub fn query_pair_by_lp_token(deps: Deps<TerraQuery>, lp_token: String) -> StdResult<PairInfo> {
+    let lp_token = deps.api.addr_canonicalize(&lp_token)?;
+    let pair_key = LP_TOKEN_PAIRS
+        .may_load(deps.storage, lp_token.as_slice())?
+        .ok_or_else(|| StdError::generic_err("no pair for the liquidity token"))?;
+    let pair_info: PairInfoRaw = PAIRS.load(deps.storage, &pair_key)?;
+
+    pair_info.to_normal(deps.api)
+}
+
+pub fn query_pairs_by_asset(
+    deps: Deps<TerraQuery>,
+    asset_info: AssetInfo,
+    start_after: Option<[AssetInfo; 2]>,
+    limit: Option<u32>,
+) -> StdResult<PairsResponse> {
+    let start_after = if let Some(start_after) = start_after {
+        Some([
+            start_after[0].to_raw(deps.api)?,
+            start_after[1].to_raw(deps.api)?,
+        ])
+    } else {
+        None
+    };
+
+    let pairs: Vec<PairInfo> = read_pairs_by_asset(
+        deps.storage,
+        deps.api,
+        &asset_info.to_raw(deps.api)?,
+        start_after,
+        limit,
+    )?;
+    let resp = PairsResponse { pairs };
+
+    Ok(resp)
+}
+
+pub fn query_pairs(
+    deps: Deps<TerraQuery>,
+    start_after: Option<[AssetInfo; 2]>,
+    limit: Option<u32>,
+    order: Option<OrderBy>,
+) -> StdResult<PairsResponse> {
+    let start_after = if let Some(start_after) = start_after {
+        Some([
+            start_after[0].to_raw(deps.api)?,
+            start_after[1].to_raw(deps.api)?,
+        ])
+    } else {
+        None
+    };
+
+    let pairs: Vec<PairInfo> = read_pairs(deps.storage, deps.api, start_after, limit, order)?;
+    let resp = PairsResponse { pairs };
+
+    Ok(resp)
+}
+
+pub fn query_native_token_decimal(
+    deps: Deps<TerraQuery>,
+    denom: String,
+) -> StdResult<NativeTokenDecimalsResponse> {
+    let decimals = ALLOW_NATIVE_TOKENS.load(deps.storage, denom.as_bytes())?;
+
+    Ok(NativeTokenDecimalsResponse { decimals })
+}
+
+const TARGET_CONTRACT_VERSION: &str = "0.0.0";
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn migrate(
+    deps: DepsMut<TerraQuery>,
+    _env: Env,
+    _msg: MigrateMsg,
+) -> StdResult<Response<TerraMsg>> {
+    // pair creations are replied to before the migration, so the single temporary info
+    // of older factories is left over
+    deps.storage.remove(b"tmp_pair_info");
+
+    // the pairs registered before the pair count are counted with `count_pairs`
+    if !PAIR_COUNT.exists(deps.storage) {
+        PAIR_COUNT.save(deps.storage, &0)?;
+        PAIR_COUNT_CURSOR.save(deps.storage, &vec![])?;
+    }
+
     migrate_version(
         deps,
         TARGET_CONTRACT_VERSION,
@@ -8,7 +8,8 @@
 #![allow(unused_attributes)]
 #![cfg_attr(rustfmt, rustfmt::skip)]
 
-#![allow(box_pointers)]
+#![allow(unused_parens)]
+#![allow(mismatched_lifetime_syntaxes)]
 #![allow(dead_code)]
 #![allow(missing_docs)]
 #![allow(non_camel_case_types)]
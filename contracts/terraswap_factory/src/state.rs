@@ -1,8 +1,13 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use classic_terraswap::asset::{AssetInfoRaw, AssetRaw, PairInfo, PairInfoRaw};
-use cosmwasm_std::{Addr, Api, CanonicalAddr, Order, StdResult, Storage};
+use classic_terraswap::asset::{
+    pair_key, Asset, AssetInfo, AssetInfoRaw, AssetRaw, PairInfo, PairInfoRaw,
+};
+use classic_terraswap::factory::{
+    default_max_referral_bps, NativeTokenDecimalsItem, OrderBy, PairFeeBounds,
+};
+use cosmwasm_std::{Addr, Api, CanonicalAddr, Decimal, Empty, Order, StdResult, Storage};
 use cw_storage_plus::{Bound, Item, Map};
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
@@ -10,42 +15,86 @@ pub struct Config {
     pub owner: CanonicalAddr,
     pub pair_code_id: u64,
     pub token_code_id: u64,
+    /// Factories from before the referral cap use the default
+    #[serde(default = "default_max_referral_bps")]
+    pub max_referral_bps: u16,
+    /// Only the owner and the creators can create pairs
+    #[serde(default)]
+    pub permissioned: bool,
+    /// Native fee of a pair creation, sent to the fee collector
+    #[serde(default)]
+    pub pair_creation_fee: Option<Asset>,
+    #[serde(default)]
+    pub fee_collector: Option<CanonicalAddr>,
+    /// Fee range of the pairs created by other creators than the owner
+    #[serde(default)]
+    pub pair_fee_bounds: Option<PairFeeBounds>,
 }
 
 pub const CONFIG: Item<Config> = Item::new("config");
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct OwnershipProposal {
+    pub owner: Addr,
+    /// Block time in seconds the proposal can no longer be claimed at
+    pub expires_at: u64,
+}
+
+pub const OWNERSHIP_PROPOSAL: Item<OwnershipProposal> = Item::new("ownership_proposal");
+/// Addresses allowed to create pairs while the factory is permissioned
+pub const CREATORS: Map<&Addr, Empty> = Map::new("creators");
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 pub struct TmpPairInfo {
     pub pair_key: Vec<u8>,
     pub assets: [AssetRaw; 2],
     pub asset_decimals: [u8; 2],
     pub sender: Addr,
+    /// Slippage tolerance of the liquidity provided to the new pair
+    #[serde(default)]
+    pub slippage_tolerance: Option<Decimal>,
+    /// Receiver of the liquidity tokens, the sender by default
+    #[serde(default)]
+    pub receiver: Option<Addr>,
 }
 
-pub const TMP_PAIR_INFO: Item<TmpPairInfo> = Item::new("tmp_pair_info");
+/// Pair creations waiting for their reply, keyed by the reply id
+pub const TMP_PAIR_INFOS: Map<u64, TmpPairInfo> = Map::new("tmp_pair_infos");
+/// The reply id of the last pair creation
+pub const LAST_REPLY_ID: Item<u64> = Item::new("last_reply_id");
 pub const PAIRS: Map<&[u8], PairInfoRaw> = Map::new("pair_info");
-
-pub fn pair_key(asset_infos: &[AssetInfoRaw; 2]) -> Vec<u8> {
-    let mut asset_infos = asset_infos.to_vec();
-    asset_infos.sort_by(|a, b| a.as_bytes().cmp(b.as_bytes()));
-
-    [asset_infos[0].as_bytes(), asset_infos[1].as_bytes()].concat()
-}
+/// The number of times each pair key was deregistered, salting the instantiate2
+/// address of the next pair of the assets apart from the deregistered ones
+pub const PAIR_GENERATIONS: Map<&[u8], u32> = Map::new("pair_generations");
 
 // settings for pagination
 const MAX_LIMIT: u32 = 30;
 const DEFAULT_LIMIT: u32 = 10;
+/// The max limit of the pairs query
+const MAX_PAIRS_LIMIT: u32 = 100;
 pub fn read_pairs(
     storage: &dyn Storage,
     api: &dyn Api,
     start_after: Option<[AssetInfoRaw; 2]>,
     limit: Option<u32>,
+    order: Option<OrderBy>,
 ) -> StdResult<Vec<PairInfo>> {
-    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
-    let start = calc_range_start(start_after).map(Bound::ExclusiveRaw);
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_PAIRS_LIMIT) as usize;
+    let (start, end, order) = match order.unwrap_or(OrderBy::Ascending) {
+        OrderBy::Ascending => (
+            calc_range_start(start_after).map(Bound::ExclusiveRaw),
+            None,
+            Order::Ascending,
+        ),
+        OrderBy::Descending => (
+            None,
+            start_after.map(|asset_infos| Bound::ExclusiveRaw(pair_key(&asset_infos))),
+            Order::Descending,
+        ),
+    };
 
     PAIRS
-        .range(storage, start, None, Order::Ascending)
+        .range(storage, start, end, order)
         .take(limit)
         .map(|item| {
             let (_, v) = item?;
@@ -54,6 +103,292 @@ pub fn read_pairs(
         .collect::<StdResult<Vec<PairInfo>>>()
 }
 
+pub fn read_creators(
+    storage: &dyn Storage,
+    start_after: Option<Addr>,
+    limit: Option<u32>,
+) -> StdResult<Vec<String>> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.as_ref().map(Bound::exclusive);
+
+    CREATORS
+        .keys(storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| Ok(item?.to_string()))
+        .collect::<StdResult<Vec<String>>>()
+}
+
+/// Assets no new pair can be created with, keyed by the raw asset info
+pub const ASSET_DENYLIST: Map<&[u8], AssetInfoRaw> = Map::new("asset_denylist");
+
+pub fn read_asset_denylist(
+    storage: &dyn Storage,
+    api: &dyn Api,
+    start_after: Option<AssetInfoRaw>,
+    limit: Option<u32>,
+) -> StdResult<Vec<AssetInfo>> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(|asset_info| Bound::ExclusiveRaw(asset_info.as_bytes().to_vec()));
+
+    ASSET_DENYLIST
+        .range(storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item?.1.to_normal(api))
+        .collect::<StdResult<Vec<AssetInfo>>>()
+}
+
+/// Pairs of each asset, keyed by the asset and the pair key
+pub const ASSET_PAIRS: Map<(&[u8], &[u8]), Empty> = Map::new("asset_pairs");
+/// The pair key of each liquidity token, keyed by its canonical address
+pub const LP_TOKEN_PAIRS: Map<&[u8], Vec<u8>> = Map::new("lp_token_pairs");
+/// The last pair key indexed by its assets in `build_asset_index`
+pub const ASSET_INDEX_CURSOR: Item<Vec<u8>> = Item::new("asset_index_cursor");
+/// The last pair key indexed by its liquidity token in `build_asset_index`,
+/// apart from the asset cursor which may have completed before this index existed
+pub const LP_TOKEN_INDEX_CURSOR: Item<Vec<u8>> = Item::new("lp_token_index_cursor");
+
+/// Indexes a pair by its assets and its liquidity token
+pub fn index_pair(
+    storage: &mut dyn Storage,
+    pair_key: &[u8],
+    pair_info: &PairInfoRaw,
+) -> StdResult<()> {
+    index_pair_assets(storage, pair_key, pair_info)?;
+    index_pair_lp_token(storage, pair_key, pair_info)
+}
+
+fn index_pair_assets(
+    storage: &mut dyn Storage,
+    pair_key: &[u8],
+    pair_info: &PairInfoRaw,
+) -> StdResult<()> {
+    for asset_info in pair_info.asset_infos.iter() {
+        ASSET_PAIRS.save(storage, (asset_info.as_bytes(), pair_key), &Empty {})?;
+    }
+
+    Ok(())
+}
+
+fn index_pair_lp_token(
+    storage: &mut dyn Storage,
+    pair_key: &[u8],
+    pair_info: &PairInfoRaw,
+) -> StdResult<()> {
+    LP_TOKEN_PAIRS.save(
+        storage,
+        pair_info.liquidity_token.as_slice(),
+        &pair_key.to_vec(),
+    )
+}
+
+pub fn unindex_pair(storage: &mut dyn Storage, pair_key: &[u8], pair_info: &PairInfoRaw) {
+    for asset_info in pair_info.asset_infos.iter() {
+        ASSET_PAIRS.remove(storage, (asset_info.as_bytes(), pair_key));
+    }
+    LP_TOKEN_PAIRS.remove(storage, pair_info.liquidity_token.as_slice());
+}
+
+const MAX_INDEX_LIMIT: u32 = 100;
+const DEFAULT_INDEX_LIMIT: u32 = 30;
+/// Indexes the next registered pairs after each index cursor, returns the most pairs
+/// indexed by either index and whether all pairs are indexed by both
+pub fn build_asset_index(
+    storage: &mut dyn Storage,
+    limit: Option<u32>,
+) -> StdResult<(usize, bool)> {
+    let limit = limit.unwrap_or(DEFAULT_INDEX_LIMIT).min(MAX_INDEX_LIMIT) as usize;
+    let (assets_indexed, assets_complete) =
+        index_next_pairs(storage, &ASSET_INDEX_CURSOR, limit, index_pair_assets)?;
+    let (lp_tokens_indexed, lp_tokens_complete) =
+        index_next_pairs(storage, &LP_TOKEN_INDEX_CURSOR, limit, index_pair_lp_token)?;
+
+    Ok((
+        assets_indexed.max(lp_tokens_indexed),
+        assets_complete && lp_tokens_complete,
+    ))
+}
+
+/// Indexes the next `limit` registered pairs after the cursor, and moves the cursor
+fn index_next_pairs(
+    storage: &mut dyn Storage,
+    cursor: &Item<Vec<u8>>,
+    limit: usize,
+    index: fn(&mut dyn Storage, &[u8], &PairInfoRaw) -> StdResult<()>,
+) -> StdResult<(usize, bool)> {
+    let start = cursor.may_load(storage)?.map(Bound::ExclusiveRaw);
+
+    let pairs = PAIRS
+        .range(storage, start, None, Order::Ascending)
+        .take(limit)
+        .collect::<StdResult<Vec<(Vec<u8>, PairInfoRaw)>>>()?;
+    for (pair_key, pair_info) in pairs.iter() {
+        index(storage, pair_key, pair_info)?;
+    }
+    if let Some((pair_key, _)) = pairs.last() {
+        cursor.save(storage, pair_key)?;
+    }
+
+    Ok((pairs.len(), pairs.len() < limit))
+}
+
+pub fn read_pairs_by_asset(
+    storage: &dyn Storage,
+    api: &dyn Api,
+    asset_info: &AssetInfoRaw,
+    start_after: Option<[AssetInfoRaw; 2]>,
+    limit: Option<u32>,
+) -> StdResult<Vec<PairInfo>> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(|asset_infos| Bound::ExclusiveRaw(pair_key(&asset_infos)));
+
+    ASSET_PAIRS
+        .prefix(asset_info.as_bytes())
+        .keys(storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|pair_key| PAIRS.load(storage, &pair_key?)?.to_normal(api))
+        .collect::<StdResult<Vec<PairInfo>>>()
+}
+
+/// The number of registered pairs, up to the count cursor while the count is backfilled
+pub const PAIR_COUNT: Item<u64> = Item::new("pair_count");
+/// The last pair key counted by `count_pairs`, removed once every pair is counted
+pub const PAIR_COUNT_CURSOR: Item<Vec<u8>> = Item::new("pair_count_cursor");
+/// The most recently created pairs, the oldest first
+pub const RECENT_PAIRS: Item<Vec<RecentPair>> = Item::new("recent_pairs");
+const MAX_RECENT_PAIRS: usize = 10;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct RecentPair {
+    pub contract_addr: Addr,
+    /// Block time in seconds the pair was created at
+    pub created_at: u64,
+}
+
+/// Whether a pair is included in the pair count, the pairs after the count cursor
+/// are counted by `count_pairs` instead
+fn is_counted(storage: &dyn Storage, pair_key: &[u8]) -> StdResult<bool> {
+    Ok(match PAIR_COUNT_CURSOR.may_load(storage)? {
+        Some(cursor) => pair_key <= cursor.as_slice(),
+        None => true,
+    })
+}
+
+/// Counts a registered pair and records its creation
+pub fn add_pair_stats(
+    storage: &mut dyn Storage,
+    pair_key: &[u8],
+    recent_pair: RecentPair,
+) -> StdResult<()> {
+    if is_counted(storage, pair_key)? {
+        let count = PAIR_COUNT.may_load(storage)?.unwrap_or_default();
+        PAIR_COUNT.save(storage, &(count + 1))?;
+    }
+
+    let mut recent_pairs = RECENT_PAIRS.may_load(storage)?.unwrap_or_default();
+    recent_pairs.push(recent_pair);
+    if recent_pairs.len() > MAX_RECENT_PAIRS {
+        recent_pairs.remove(0);
+    }
+    RECENT_PAIRS.save(storage, &recent_pairs)
+}
+
+/// Uncounts a deregistered pair
+pub fn remove_pair_stats(storage: &mut dyn Storage, pair_key: &[u8]) -> StdResult<()> {
+    if is_counted(storage, pair_key)? {
+        let count = PAIR_COUNT.may_load(storage)?.unwrap_or_default();
+        PAIR_COUNT.save(storage, &count.saturating_sub(1))?;
+    }
+
+    Ok(())
+}
+
+/// Counts the next registered pairs after the count cursor, returns the number counted
+/// and whether all pairs are counted
+pub fn count_pairs(storage: &mut dyn Storage, limit: Option<u32>) -> StdResult<(usize, bool)> {
+    let cursor = match PAIR_COUNT_CURSOR.may_load(storage)? {
+        Some(cursor) => cursor,
+        None => return Ok((0, true)),
+    };
+    let limit = limit.unwrap_or(DEFAULT_INDEX_LIMIT).min(MAX_INDEX_LIMIT) as usize;
+
+    let keys = PAIRS
+        .keys(
+            storage,
+            Some(Bound::ExclusiveRaw(cursor)),
+            None,
+            Order::Ascending,
+        )
+        .take(limit)
+        .collect::<StdResult<Vec<Vec<u8>>>>()?;
+    let count = PAIR_COUNT.may_load(storage)?.unwrap_or_default();
+    PAIR_COUNT.save(storage, &(count + keys.len() as u64))?;
+
+    let complete = keys.len() < limit;
+    if complete {
+        PAIR_COUNT_CURSOR.remove(storage);
+    } else if let Some(key) = keys.last() {
+        PAIR_COUNT_CURSOR.save(storage, key)?;
+    }
+
+    Ok((keys.len(), complete))
+}
+
+/// Registered pairs after `start_after`, in the order of the `pairs` query
+pub fn read_pair_infos_raw(
+    storage: &dyn Storage,
+    start_after: Option<[AssetInfoRaw; 2]>,
+    limit: usize,
+) -> StdResult<Vec<PairInfoRaw>> {
+    let start = calc_range_start(start_after).map(Bound::ExclusiveRaw);
+
+    PAIRS
+        .range(storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| Ok(item?.1))
+        .collect::<StdResult<Vec<PairInfoRaw>>>()
+}
+
+/// Pairs registered under a key other than the sorted key of their assets after the raw key
+/// `start_after`, with the last key read when there are more pairs to read
+pub fn read_duplicate_pairs(
+    storage: &dyn Storage,
+    start_after: Option<Vec<u8>>,
+    limit: usize,
+) -> StdResult<(Vec<PairInfoRaw>, Option<Vec<u8>>)> {
+    let (pairs, last_key) = read_pair_entries(storage, start_after, limit)?;
+    let duplicates = pairs
+        .into_iter()
+        .filter(|(key, pair_info)| *key != pair_key(&pair_info.asset_infos))
+        .map(|(_, pair_info)| pair_info)
+        .collect();
+
+    Ok((duplicates, last_key))
+}
+
+/// A registered pair with its raw key
+pub type PairEntry = (Vec<u8>, PairInfoRaw);
+
+/// Registered pairs with their raw keys after the raw key `start_after`, with the last key
+/// read when there are more pairs to read
+pub fn read_pair_entries(
+    storage: &dyn Storage,
+    start_after: Option<Vec<u8>>,
+    limit: usize,
+) -> StdResult<(Vec<PairEntry>, Option<Vec<u8>>)> {
+    let start = start_after.map(Bound::ExclusiveRaw);
+
+    let pairs = PAIRS
+        .range(storage, start, None, Order::Ascending)
+        .take(limit)
+        .collect::<StdResult<Vec<PairEntry>>>()?;
+    let last_key = match pairs.last() {
+        Some((key, _)) if pairs.len() == limit => Some(key.clone()),
+        _ => None,
+    };
+
+    Ok((pairs, last_key))
+}
+
 // this will set the first key after the provided key, by appending a 1 byte
 fn calc_range_start(start_after: Option<[AssetInfoRaw; 2]>) -> Option<Vec<u8>> {
     start_after.map(|asset_infos| {
@@ -79,6 +414,22 @@ pub fn add_allow_native_token(
     ALLOW_NATIVE_TOKENS.save(storage, denom.as_bytes(), &decimals)
 }
 
+/// Every registered native denom with its decimals, by denom
+pub fn read_native_token_decimals(
+    storage: &dyn Storage,
+) -> StdResult<Vec<NativeTokenDecimalsItem>> {
+    ALLOW_NATIVE_TOKENS
+        .range(storage, None, None, Order::Ascending)
+        .map(|item| {
+            let (denom, decimals) = item?;
+            Ok(NativeTokenDecimalsItem {
+                denom: String::from_utf8(denom)?,
+                decimals,
+            })
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod allow_native_token {
 
@@ -1,24 +1,41 @@
-use classic_bindings::TerraQuery;
+use classic_bindings::{TerraMsg, TerraQuery};
 
-use crate::contract::{execute, instantiate, query, reply};
+use crate::contract::{execute, instantiate, migrate, query, reply};
+use crate::response::MsgInstantiateContractResponse;
 use classic_terraswap::mock_querier::{mock_dependencies, WasmMockQuerier};
 
-use crate::state::{pair_key, TmpPairInfo, TMP_PAIR_INFO};
+use crate::state::{
+    TmpPairInfo, LP_TOKEN_INDEX_CURSOR, LP_TOKEN_PAIRS, PAIRS, PAIR_COUNT, TMP_PAIR_INFOS,
+};
 
-use classic_terraswap::asset::{Asset, AssetInfo, PairInfo};
+use classic_terraswap::asset::{
+    pair_key, Asset, AssetInfo, AssetInfoRaw, PairCreation, PairInfo, PairInfoRaw,
+};
 use classic_terraswap::factory::{
-    ConfigResponse, ExecuteMsg, InstantiateMsg, NativeTokenDecimalsResponse, QueryMsg,
+    AssetDenylistResponse, ConfigResponse, CreatorsResponse, ExecuteMsg, InstantiateMsg,
+    MigrateMsg, NativeTokenDecimalsItem, NativeTokenDecimalsResponse, OrderBy, PairFeeBounds,
+    PairFeeParams, PairInitParams, PairsResponse, PredictPairAddressResponse, QueryMsg,
+    RecentPairResponse, RouterBootstrapResponse, StatsResponse, ValidatePairResponse,
 };
 use classic_terraswap::pair::{
     ExecuteMsg as PairExecuteMsg, InstantiateMsg as PairInstantiateMsg,
     MigrateMsg as PairMigrateMsg,
 };
-use cosmwasm_std::testing::{mock_env, mock_info, MockApi, MockStorage, MOCK_CONTRACT_ADDR};
+use cosmwasm_std::testing::{
+    mock_env, mock_info, MockApi, MockQuerier, MockStorage, MOCK_CONTRACT_ADDR,
+};
 use cosmwasm_std::{
-    attr, coin, coins, from_binary, to_binary, Addr, CosmosMsg, OwnedDeps, Reply, ReplyOn,
-    Response, StdError, SubMsg, SubMsgResponse, SubMsgResult, Uint128, WasmMsg,
+    attr, coin, coins, from_binary, instantiate2_address, to_binary, to_vec, Addr, Api, BankMsg,
+    Binary, CanonicalAddr, CosmosMsg, Decimal, Env, Event, HexBinary, OwnedDeps,
+    RecoverPubkeyError, Reply, ReplyOn, Response, StdError, StdResult, Storage, SubMsg,
+    SubMsgResponse, SubMsgResult, Uint128, VerificationError, WasmMsg,
 };
+use cw2::set_contract_version;
 use cw20::Cw20ExecuteMsg;
+use protobuf::Message;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::marker::PhantomData;
 
 #[test]
 fn proper_initialization() {
@@ -38,6 +55,7 @@ fn proper_initialization() {
     let config_res: ConfigResponse = from_binary(&query_res).unwrap();
     assert_eq!(123u64, config_res.token_code_id);
     assert_eq!(321u64, config_res.pair_code_id);
+    assert_eq!(50u16, config_res.max_referral_bps);
     assert_eq!("addr0000".to_string(), config_res.owner);
 }
 
@@ -61,6 +79,8 @@ fn update_config() {
         owner: Some("addr0001".to_string()),
         pair_code_id: None,
         token_code_id: None,
+        max_referral_bps: None,
+        permissioned: None,
     };
 
     let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
@@ -80,6 +100,8 @@ fn update_config() {
         owner: None,
         pair_code_id: Some(100u64),
         token_code_id: Some(200u64),
+        max_referral_bps: Some(30u16),
+        permissioned: None,
     };
 
     let res = execute(deps.as_mut(), env, info, msg).unwrap();
@@ -90,8 +112,38 @@ fn update_config() {
     let config_res: ConfigResponse = from_binary(&query_res).unwrap();
     assert_eq!(200u64, config_res.token_code_id);
     assert_eq!(100u64, config_res.pair_code_id);
+    assert_eq!(30u16, config_res.max_referral_bps);
     assert_eq!("addr0001".to_string(), config_res.owner);
 
+    // code ids are not zero
+    let info = mock_info("addr0001", &[]);
+    let msg = ExecuteMsg::UpdateConfig {
+        owner: None,
+        pair_code_id: Some(0u64),
+        token_code_id: None,
+        max_referral_bps: None,
+        permissioned: None,
+    };
+    let res = execute(deps.as_mut(), mock_env(), info, msg);
+    assert_eq!(res, Err(StdError::generic_err("code ids must not be zero")));
+
+    // the referral cap is bounded
+    let info = mock_info("addr0001", &[]);
+    let msg = ExecuteMsg::UpdateConfig {
+        owner: None,
+        pair_code_id: None,
+        token_code_id: None,
+        max_referral_bps: Some(1_001u16),
+        permissioned: None,
+    };
+    let res = execute(deps.as_mut(), mock_env(), info, msg);
+    assert_eq!(
+        res,
+        Err(StdError::generic_err(
+            "max_referral_bps must not exceed 1000"
+        ))
+    );
+
     // Unauthorized err
     let env = mock_env();
     let info = mock_info("addr0000", &[]);
@@ -99,6 +151,8 @@ fn update_config() {
         owner: None,
         pair_code_id: None,
         token_code_id: None,
+        max_referral_bps: None,
+        permissioned: None,
     };
 
     let res = execute(deps.as_mut(), env, info, msg);
@@ -108,6 +162,146 @@ fn update_config() {
     }
 }
 
+#[test]
+fn transfer_ownership() {
+    let mut deps = mock_dependencies(&[]);
+    deps = init(deps);
+
+    let query_config = |deps: &OwnedDeps<MockStorage, MockApi, WasmMockQuerier, TerraQuery>| {
+        from_binary::<ConfigResponse>(
+            &query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap(),
+        )
+        .unwrap()
+    };
+    let propose = |owner: &str| ExecuteMsg::ProposeNewOwner {
+        owner: owner.to_string(),
+        expires_in: 100u64,
+    };
+
+    let info = mock_info("addr0001", &[]);
+    assert_eq!(
+        execute(deps.as_mut(), mock_env(), info, propose("addr0001")),
+        Err(StdError::generic_err("unauthorized"))
+    );
+
+    let info = mock_info("addr0001", &[]);
+    assert_eq!(
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::ClaimOwnership {}
+        ),
+        Err(StdError::generic_err("no ownership proposal"))
+    );
+
+    // the proposal expires within a week
+    let owner_info = mock_info("addr0000", &[]);
+    assert_eq!(
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            owner_info.clone(),
+            ExecuteMsg::ProposeNewOwner {
+                owner: "addr0001".to_string(),
+                expires_in: u64::MAX,
+            }
+        ),
+        Err(StdError::generic_err(
+            "expires_in must not exceed 604800 seconds"
+        ))
+    );
+
+    // a new proposal replaces the previous one
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        owner_info.clone(),
+        propose("addr0001"),
+    )
+    .unwrap();
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        owner_info.clone(),
+        propose("addr0002"),
+    )
+    .unwrap();
+    assert_eq!(
+        query_config(&deps).pending_owner,
+        Some("addr0002".to_string())
+    );
+
+    let info = mock_info("addr0001", &[]);
+    assert_eq!(
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::ClaimOwnership {}
+        ),
+        Err(StdError::generic_err("unauthorized"))
+    );
+
+    // the proposal can not be claimed once expired
+    let mut env = mock_env();
+    env.block.time = env.block.time.plus_seconds(100);
+    let info = mock_info("addr0002", &[]);
+    assert_eq!(
+        execute(
+            deps.as_mut(),
+            env,
+            info.clone(),
+            ExecuteMsg::ClaimOwnership {}
+        ),
+        Err(StdError::generic_err("ownership proposal expired"))
+    );
+
+    // the owner can drop the proposal
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        owner_info.clone(),
+        ExecuteMsg::DropOwnershipProposal {},
+    )
+    .unwrap();
+    assert_eq!(query_config(&deps).pending_owner, None);
+    assert_eq!(
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            ExecuteMsg::ClaimOwnership {}
+        ),
+        Err(StdError::generic_err("no ownership proposal"))
+    );
+
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        owner_info.clone(),
+        propose("addr0002"),
+    )
+    .unwrap();
+    let mut env = mock_env();
+    env.block.time = env.block.time.plus_seconds(99);
+    let res = execute(deps.as_mut(), env, info, ExecuteMsg::ClaimOwnership {}).unwrap();
+    assert_eq!(
+        res.attributes,
+        vec![attr("action", "claim_ownership"), attr("owner", "addr0002")]
+    );
+
+    let config = query_config(&deps);
+    assert_eq!(config.owner, "addr0002".to_string());
+    assert_eq!(config.pending_owner, None);
+
+    // the previous owner lost its privileges
+    assert_eq!(
+        execute(deps.as_mut(), mock_env(), owner_info, propose("addr0000")),
+        Err(StdError::generic_err("unauthorized"))
+    );
+}
+
 fn init(
     mut deps: OwnedDeps<MockStorage, MockApi, WasmMockQuerier, TerraQuery>,
 ) -> OwnedDeps<MockStorage, MockApi, WasmMockQuerier, TerraQuery> {
@@ -129,6 +323,16 @@ fn init(
     deps
 }
 
+// the salt of a pair is the sha256 hash of its sorted raw asset infos
+fn pair_salt(asset_infos: &[AssetInfo; 2]) -> Binary {
+    let api = MockApi::default();
+    let raw_infos = [
+        asset_infos[0].to_raw(&api).unwrap(),
+        asset_infos[1].to_raw(&api).unwrap(),
+    ];
+    Binary::from(Sha256::digest(pair_key(&raw_infos)).to_vec())
+}
+
 #[test]
 fn create_pair() {
     let mut deps = mock_dependencies(&[coin(10u128, "uusd".to_string())]);
@@ -152,6 +356,8 @@ fn create_pair() {
 
     let msg = ExecuteMsg::CreatePair {
         assets: assets.clone(),
+        allow_unregistered_decimals: None,
+        pair_params: None,
     };
 
     let env = mock_env();
@@ -169,8 +375,8 @@ fn create_pair() {
         vec![SubMsg {
             id: 1,
             gas_limit: None,
-            reply_on: ReplyOn::Success,
-            msg: WasmMsg::Instantiate {
+            reply_on: ReplyOn::Always,
+            msg: WasmMsg::Instantiate2 {
                 msg: to_binary(&PairInstantiateMsg {
                     asset_infos: [
                         AssetInfo::NativeToken {
@@ -181,13 +387,26 @@ fn create_pair() {
                         }
                     ],
                     token_code_id: 123u64,
-                    asset_decimals: [6u8, 8u8]
+                    asset_decimals: [6u8, 8u8],
+                    lp_fee_rate: None,
+                    protocol_fee_rate: None,
+                    protocol_fee_collector: None,
+                    pair_admin: None,
+                    max_spread_cap: None,
                 })
                 .unwrap(),
                 code_id: 321u64,
                 funds: vec![],
                 label: "pair".to_string(),
                 admin: Some(MOCK_CONTRACT_ADDR.to_string()),
+                salt: pair_salt(&[
+                    AssetInfo::NativeToken {
+                        denom: "uusd".to_string(),
+                    },
+                    AssetInfo::Token {
+                        contract_addr: "asset0001".to_string(),
+                    },
+                ]),
             }
             .into()
         },]
@@ -204,11 +423,13 @@ fn create_pair() {
     ];
 
     assert_eq!(
-        TMP_PAIR_INFO.load(&deps.storage).unwrap(),
+        TMP_PAIR_INFOS.load(&deps.storage, 1).unwrap(),
         TmpPairInfo {
             assets: raw_assets,
             pair_key: pair_key(&raw_infos),
             sender: Addr::unchecked("addr0000"),
+            slippage_tolerance: None,
+            receiver: None,
             asset_decimals: [6u8, 8u8]
         }
     );
@@ -218,12 +439,21 @@ fn create_pair() {
 fn create_pair_native_token_and_ibc_token() {
     let mut deps = mock_dependencies(&[
         coin(10u128, "uusd".to_string()),
-        coin(10u128, "ibc/HASH".to_string()),
+        coin(
+            10u128,
+            "ibc/0471F1C4E7AFD3F07702BEF6DC365268D64570F7C1FDC98EA6098DD6DE59817B".to_string(),
+        ),
     ]);
     deps = init(deps);
     deps.querier.with_terraswap_factory(
         &[],
-        &[("uusd".to_string(), 6u8), ("ibc/HASH".to_string(), 6u8)],
+        &[
+            ("uusd".to_string(), 6u8),
+            (
+                "ibc/0471F1C4E7AFD3F07702BEF6DC365268D64570F7C1FDC98EA6098DD6DE59817B".to_string(),
+                6u8,
+            ),
+        ],
     );
 
     let assets = [
@@ -235,7 +465,8 @@ fn create_pair_native_token_and_ibc_token() {
         },
         Asset {
             info: AssetInfo::NativeToken {
-                denom: "ibc/HASH".to_string(),
+                denom: "ibc/0471F1C4E7AFD3F07702BEF6DC365268D64570F7C1FDC98EA6098DD6DE59817B"
+                    .to_string(),
             },
             amount: Uint128::zero(),
         },
@@ -243,6 +474,8 @@ fn create_pair_native_token_and_ibc_token() {
 
     let msg = ExecuteMsg::CreatePair {
         assets: assets.clone(),
+        allow_unregistered_decimals: None,
+        pair_params: None,
     };
 
     let env = mock_env();
@@ -250,32 +483,51 @@ fn create_pair_native_token_and_ibc_token() {
     let res = execute(deps.as_mut(), env, info, msg).unwrap();
     assert_eq!(
         res.attributes,
-        vec![attr("action", "create_pair"), attr("pair", "uusd-ibc/HASH")]
+        vec![
+            attr("action", "create_pair"),
+            attr(
+                "pair",
+                "uusd-ibc/0471F1C4E7AFD3F07702BEF6DC365268D64570F7C1FDC98EA6098DD6DE59817B"
+            )
+        ]
     );
     assert_eq!(
         res.messages,
         vec![SubMsg {
             id: 1,
             gas_limit: None,
-            reply_on: ReplyOn::Success,
-            msg: WasmMsg::Instantiate {
+            reply_on: ReplyOn::Always,
+            msg: WasmMsg::Instantiate2 {
                 msg: to_binary(&PairInstantiateMsg {
                     asset_infos: [
                         AssetInfo::NativeToken {
                             denom: "uusd".to_string(),
                         },
                         AssetInfo::NativeToken {
-                            denom: "ibc/HASH".to_string(),
+                            denom: "ibc/0471F1C4E7AFD3F07702BEF6DC365268D64570F7C1FDC98EA6098DD6DE59817B".to_string(),
                         }
                     ],
                     token_code_id: 123u64,
-                    asset_decimals: [6u8, 6u8]
+                    asset_decimals: [6u8, 6u8],
+                    lp_fee_rate: None,
+                    protocol_fee_rate: None,
+                    protocol_fee_collector: None,
+                    pair_admin: None,
+                    max_spread_cap: None,
                 })
                 .unwrap(),
                 code_id: 321u64,
                 funds: vec![],
                 label: "pair".to_string(),
                 admin: Some(MOCK_CONTRACT_ADDR.to_string()),
+                salt: pair_salt(&[
+                    AssetInfo::NativeToken {
+                        denom: "uusd".to_string(),
+                    },
+                    AssetInfo::NativeToken {
+                        denom: "ibc/0471F1C4E7AFD3F07702BEF6DC365268D64570F7C1FDC98EA6098DD6DE59817B".to_string(),
+                    },
+                ]),
             }
             .into()
         },]
@@ -292,59 +544,253 @@ fn create_pair_native_token_and_ibc_token() {
     ];
 
     assert_eq!(
-        TMP_PAIR_INFO.load(&deps.storage).unwrap(),
+        TMP_PAIR_INFOS.load(&deps.storage, 1).unwrap(),
         TmpPairInfo {
             assets: raw_assets,
             pair_key: pair_key(&raw_infos),
             sender: Addr::unchecked("addr0000"),
+            slippage_tolerance: None,
+            receiver: None,
             asset_decimals: [6u8, 6u8]
         }
     );
 }
 
 #[test]
-fn fail_to_create_same_pair() {
-    let mut deps = mock_dependencies(&[coin(10u128, "uusd".to_string())]);
+fn create_pair_with_pair_params() {
+    let mut deps = mock_dependencies(&[]);
     deps = init(deps);
+    deps.querier
+        .with_terraswap_factory(&[], &[("uusd".to_string(), 6u8)]);
 
-    let assets = [
-        Asset {
-            info: AssetInfo::NativeToken {
-                denom: "uusd".to_string(),
-            },
-            amount: Uint128::zero(),
+    let native = |denom: &str| Asset {
+        info: AssetInfo::NativeToken {
+            denom: denom.to_string(),
         },
-        Asset {
-            info: AssetInfo::NativeToken {
-                denom: "uusd".to_string(),
+        amount: Uint128::zero(),
+    };
+    let create_pair = |denom: &str, pair_params: PairInitParams| ExecuteMsg::CreatePair {
+        assets: [native("uusd"), native(denom)],
+        allow_unregistered_decimals: Some(true),
+        pair_params: Some(pair_params),
+    };
+    // other creators can not skip the decimals registry
+    let create_token_pair = |pair_params: PairInitParams| ExecuteMsg::CreatePair {
+        assets: [
+            native("uusd"),
+            Asset {
+                info: AssetInfo::Token {
+                    contract_addr: "asset0001".to_string(),
+                },
+                amount: Uint128::zero(),
             },
-            amount: Uint128::zero(),
-        },
+        ],
+        allow_unregistered_decimals: None,
+        pair_params: Some(pair_params),
+    };
+    let instantiate_msg = |res: Response<TerraMsg>| match &res.messages[0].msg {
+        CosmosMsg::Wasm(WasmMsg::Instantiate2 { msg, .. }) => {
+            from_binary::<PairInstantiateMsg>(msg).unwrap()
+        }
+        _ => panic!("DO NOT ENTER HERE"),
+    };
+    let fee_config = |lp_fee_rate: u64, protocol_fee_collector: Option<&str>| PairFeeParams {
+        lp_fee_rate: Decimal::permille(lp_fee_rate),
+        protocol_fee_rate: Decimal::permille(1),
+        protocol_fee_collector: protocol_fee_collector.map(|c| c.to_string()),
+    };
+
+    // the owner sets any params
+    let owner_info = mock_info("addr0000", &[]);
+    let pair_params = PairInitParams {
+        fee_config: Some(fee_config(20, Some("collector0000"))),
+        admin: Some("admin0000".to_string()),
+        max_spread_cap: Some(Decimal::percent(5)),
+    };
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        owner_info.clone(),
+        create_pair("uluna", pair_params),
+    )
+    .unwrap();
+    let msg = instantiate_msg(res);
+    assert_eq!(msg.lp_fee_rate, Some(Decimal::permille(20)));
+    assert_eq!(msg.protocol_fee_rate, Some(Decimal::permille(1)));
+    assert_eq!(
+        msg.protocol_fee_collector,
+        Some("collector0000".to_string())
+    );
+    assert_eq!(msg.pair_admin, Some("admin0000".to_string()));
+    assert_eq!(msg.max_spread_cap, Some(Decimal::percent(5)));
+
+    // other creators are limited
+    let info = mock_info("addr0001", &[]);
+    let restricted = [
+        (
+            PairInitParams {
+                fee_config: None,
+                admin: Some("addr0001".to_string()),
+                max_spread_cap: None,
+            },
+            "only the owner can set the pair admin",
+        ),
+        (
+            PairInitParams {
+                fee_config: Some(fee_config(2, Some("addr0001"))),
+                admin: None,
+                max_spread_cap: None,
+            },
+            "only the owner can set the protocol fee collector",
+        ),
+        (
+            PairInitParams {
+                fee_config: Some(fee_config(2, None)),
+                admin: None,
+                max_spread_cap: None,
+            },
+            "only the owner can set the pair fees",
+        ),
     ];
+    for (pair_params, err) in restricted {
+        assert_eq!(
+            execute(
+                deps.as_mut(),
+                mock_env(),
+                info.clone(),
+                create_token_pair(pair_params)
+            ),
+            Err(StdError::generic_err(err))
+        );
+    }
 
-    let msg = ExecuteMsg::CreatePair { assets };
+    let msg = ExecuteMsg::UpdatePairFeeBounds {
+        pair_fee_bounds: Some(PairFeeBounds {
+            min_fee_rate: Decimal::permille(2),
+            max_fee_rate: Decimal::percent(1),
+        }),
+    };
+    assert_eq!(
+        execute(deps.as_mut(), mock_env(), info.clone(), msg.clone()),
+        Err(StdError::generic_err("unauthorized"))
+    );
+    execute(deps.as_mut(), mock_env(), owner_info.clone(), msg).unwrap();
+    let config: ConfigResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
+    assert_eq!(
+        config.pair_fee_bounds,
+        Some(PairFeeBounds {
+            min_fee_rate: Decimal::permille(2),
+            max_fee_rate: Decimal::percent(1),
+        })
+    );
 
-    let env = mock_env();
-    let info = mock_info("addr0000", &[]);
+    let msg = ExecuteMsg::UpdatePairFeeBounds {
+        pair_fee_bounds: Some(PairFeeBounds {
+            min_fee_rate: Decimal::percent(2),
+            max_fee_rate: Decimal::percent(1),
+        }),
+    };
+    assert_eq!(
+        execute(deps.as_mut(), mock_env(), owner_info, msg),
+        Err(StdError::generic_err(
+            "min_fee_rate must not exceed max_fee_rate"
+        ))
+    );
 
-    match execute(deps.as_mut(), env, info, msg).unwrap_err() {
-        StdError::GenericErr { msg, .. } => assert_eq!(msg, "same asset".to_string()),
-        _ => panic!("Must return generic error"),
+    for lp_fee_rate in [0, 10] {
+        let pair_params = PairInitParams {
+            fee_config: Some(fee_config(lp_fee_rate, None)),
+            admin: None,
+            max_spread_cap: None,
+        };
+        assert_eq!(
+            execute(
+                deps.as_mut(),
+                mock_env(),
+                info.clone(),
+                create_token_pair(pair_params)
+            ),
+            Err(StdError::generic_err(
+                "pair fee rate must be between 0.002 and 0.01"
+            ))
+        );
     }
+
+    let pair_params = PairInitParams {
+        fee_config: Some(fee_config(9, None)),
+        admin: None,
+        max_spread_cap: Some(Decimal::percent(10)),
+    };
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        create_token_pair(pair_params),
+    )
+    .unwrap();
+    let msg = instantiate_msg(res);
+    assert_eq!(msg.lp_fee_rate, Some(Decimal::permille(9)));
+    assert_eq!(msg.protocol_fee_collector, None);
+    assert_eq!(msg.pair_admin, None);
+    assert_eq!(msg.max_spread_cap, Some(Decimal::percent(10)));
 }
 
 #[test]
-fn fail_to_create_pair_with_unknown_denom() {
-    let mut deps = mock_dependencies(&[coin(10u128, "uusd".to_string())]);
+fn fail_to_create_pair_with_invalid_asset() {
+    let mut deps = mock_dependencies(&[]);
     deps = init(deps);
 
-    deps.querier
-        .with_terraswap_factory(&[], &[("uusd".to_string(), 6u8)]);
+    let create_pair = |asset_info: AssetInfo| ExecuteMsg::CreatePair {
+        assets: [
+            Asset {
+                info: AssetInfo::NativeToken {
+                    denom: "uusd".to_string(),
+                },
+                amount: Uint128::zero(),
+            },
+            Asset {
+                info: asset_info,
+                amount: Uint128::zero(),
+            },
+        ],
+        allow_unregistered_decimals: Some(true),
+        pair_params: None,
+    };
+    let info = mock_info("addr0000", &[]);
+
+    let msg = create_pair(AssetInfo::NativeToken {
+        denom: "".to_string(),
+    });
+    assert_eq!(
+        execute(deps.as_mut(), mock_env(), info.clone(), msg),
+        Err(StdError::parse_err("AssetInfo", "empty denom"))
+    );
+    let msg = create_pair(AssetInfo::NativeToken {
+        denom: "ibc/hash".to_string(),
+    });
+    assert_eq!(
+        execute(deps.as_mut(), mock_env(), info.clone(), msg),
+        Err(StdError::parse_err(
+            "AssetInfo",
+            "invalid ibc denom: ibc/hash"
+        ))
+    );
+    let msg = create_pair(AssetInfo::Token {
+        contract_addr: "ASSET0001".to_string(),
+    });
+    assert!(execute(deps.as_mut(), mock_env(), info, msg).is_err());
+}
+
+#[test]
+fn fail_to_create_same_pair() {
+    let mut deps = mock_dependencies(&[coin(10u128, "uusd".to_string())]);
+    deps = init(deps);
 
     let assets = [
         Asset {
             info: AssetInfo::NativeToken {
-                denom: "uxxx".to_string(),
+                denom: "uusd".to_string(),
             },
             amount: Uint128::zero(),
         },
@@ -356,70 +802,441 @@ fn fail_to_create_pair_with_unknown_denom() {
         },
     ];
 
-    let msg = ExecuteMsg::CreatePair { assets };
+    let msg = ExecuteMsg::CreatePair {
+        assets,
+        allow_unregistered_decimals: None,
+        pair_params: None,
+    };
 
     let env = mock_env();
     let info = mock_info("addr0000", &[]);
 
     match execute(deps.as_mut(), env, info, msg).unwrap_err() {
-        StdError::GenericErr { msg, .. } => assert_eq!(msg, "asset1 is invalid".to_string()),
+        StdError::GenericErr { msg, .. } => assert_eq!(msg, "same asset".to_string()),
         _ => panic!("Must return generic error"),
     }
 }
 
 #[test]
-fn fail_to_create_pair_with_unknown_token() {
+fn fail_to_create_reversed_pair() {
     let mut deps = mock_dependencies(&[coin(10u128, "uusd".to_string())]);
     deps = init(deps);
 
-    deps.querier
-        .with_terraswap_factory(&[], &[("uluna".to_string(), 6u8)]);
-
-    let assets = [
-        Asset {
-            info: AssetInfo::NativeToken {
-                denom: "uluna".to_string(),
-            },
-            amount: Uint128::zero(),
+    let asset_infos = [
+        AssetInfo::NativeToken {
+            denom: "uusd".to_string(),
         },
-        Asset {
-            info: AssetInfo::Token {
-                contract_addr: "terra123".to_string(),
-            },
-            amount: Uint128::zero(),
+        AssetInfo::NativeToken {
+            denom: "uluna".to_string(),
         },
     ];
+    let raw_infos = [
+        asset_infos[0].to_raw(deps.as_ref().api).unwrap(),
+        asset_infos[1].to_raw(deps.as_ref().api).unwrap(),
+    ];
+    let pair_info = PairInfoRaw {
+        asset_infos: raw_infos.clone(),
+        contract_addr: deps.as_ref().api.addr_canonicalize("pair0000").unwrap(),
+        liquidity_token: deps
+            .as_ref()
+            .api
+            .addr_canonicalize("liquidity0000")
+            .unwrap(),
+        asset_decimals: [6u8, 6u8],
+        created_at: None,
+    };
+    PAIRS
+        .save(deps.as_mut().storage, &pair_key(&raw_infos), &pair_info)
+        .unwrap();
 
-    let msg = ExecuteMsg::CreatePair { assets };
+    let reversed = [asset_infos[1].clone(), asset_infos[0].clone()];
+    for asset_infos in [asset_infos, reversed] {
+        let msg = ExecuteMsg::CreatePair {
+            assets: [
+                Asset {
+                    info: asset_infos[0].clone(),
+                    amount: Uint128::zero(),
+                },
+                Asset {
+                    info: asset_infos[1].clone(),
+                    amount: Uint128::zero(),
+                },
+            ],
+            allow_unregistered_decimals: Some(true),
+            pair_params: None,
+        };
+        let info = mock_info("addr0000", &[]);
+        assert_eq!(
+            execute(deps.as_mut(), mock_env(), info, msg),
+            Err(StdError::generic_err("pair already exists: pair0000"))
+        );
+
+        let pair: PairInfo =
+            from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Pair { asset_infos }).unwrap())
+                .unwrap();
+        assert_eq!(pair.contract_addr, "pair0000".to_string());
+    }
+}
 
-    let env = mock_env();
-    let info = mock_info("addr0000", &[]);
+#[test]
+fn report_duplicate_pairs() {
+    let mut deps = mock_dependencies(&[coin(10u128, "uusd".to_string())]);
+    deps = init(deps);
 
-    match execute(deps.as_mut(), env, info, msg).unwrap_err() {
-        StdError::GenericErr { msg, .. } => assert_eq!(msg, "asset2 is invalid".to_string()),
-        _ => panic!("Must return generic error"),
+    let native = |denom: &str| AssetInfo::NativeToken {
+        denom: denom.to_string(),
+    };
+    // the second pair is stored under the unsorted key of its assets
+    let pairs = [
+        [native("uluna"), native("uusd")],
+        [native("uusd"), native("uluna")],
+        [native("ukrw"), native("uusd")],
+    ];
+    for (i, asset_infos) in pairs.iter().enumerate() {
+        let raw_infos = [
+            asset_infos[0].to_raw(deps.as_ref().api).unwrap(),
+            asset_infos[1].to_raw(deps.as_ref().api).unwrap(),
+        ];
+        let pair_info = PairInfoRaw {
+            asset_infos: raw_infos.clone(),
+            contract_addr: deps
+                .as_ref()
+                .api
+                .addr_canonicalize(&format!("pair000{}", i))
+                .unwrap(),
+            liquidity_token: deps
+                .as_ref()
+                .api
+                .addr_canonicalize(&format!("liquidity000{}", i))
+                .unwrap(),
+            asset_decimals: [6u8, 6u8],
+            created_at: None,
+        };
+        let key = [raw_infos[0].as_bytes(), raw_infos[1].as_bytes()].concat();
+        PAIRS.save(deps.as_mut().storage, &key, &pair_info).unwrap();
     }
+
+    let msg = ExecuteMsg::ReportDuplicatePairs {
+        start_after: None,
+        limit: Some(2),
+    };
+    let info = mock_info("noadmin", &[]);
+    assert_eq!(
+        execute(deps.as_mut(), mock_env(), info, msg.clone()),
+        Err(StdError::generic_err("unauthorized"))
+    );
+
+    // the keys are ordered ukrw-uusd, uluna-uusd, uusd-uluna
+    let info = mock_info("addr0000", &[]);
+    let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+    assert_eq!(res.attributes[1], attr("duplicates", ""));
+    let last_key = Binary::from(b"ulunauusd".to_vec());
+    assert_eq!(res.attributes[2], attr("last_key", last_key.to_base64()));
+
+    let msg = ExecuteMsg::ReportDuplicatePairs {
+        start_after: Some(last_key),
+        limit: Some(2),
+    };
+    let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    assert_eq!(
+        res.attributes,
+        vec![
+            attr("action", "report_duplicate_pairs"),
+            attr("duplicates", "pair0001"),
+            attr("last_key", ""),
+        ]
+    );
 }
 
 #[test]
-fn reply_only_create_pair() {
+fn repair_registry() {
     let mut deps = mock_dependencies(&[]);
+    deps = init(deps);
 
-    deps.querier.with_token_balances(&[(
-        &MOCK_CONTRACT_ADDR.to_string(),
-        &[
-            (&"asset0000".to_string(), &Uint128::from(100u128)),
-            (&"asset0001".to_string(), &Uint128::from(100u128)),
-        ],
-    )]);
+    let native = |denom: &str| AssetInfo::NativeToken {
+        denom: denom.to_string(),
+    };
+    let live_pairs = [
+        (
+            [native("uusd"), native("uluna")],
+            "pair0000",
+            "liquidity0000",
+        ),
+        (
+            [native("uusd"), native("ukrw")],
+            "pair0001",
+            "liquidity0001",
+        ),
+        (
+            [native("uluna"), native("ukrw")],
+            "pair0002",
+            "liquidity0002",
+        ),
+    ];
+    for (asset_infos, pair_contract, liquidity_token) in live_pairs.iter() {
+        deps.querier.with_pair_info(
+            pair_contract,
+            &PairInfo {
+                asset_infos: asset_infos.clone(),
+                contract_addr: pair_contract.to_string(),
+                liquidity_token: liquidity_token.to_string(),
+                asset_decimals: [6u8, 6u8],
+                created_at: None,
+            },
+        );
+        let denoms = [asset_infos[0].to_string(), asset_infos[1].to_string()];
+        create_native_pair(
+            &mut deps,
+            mock_env(),
+            [&denoms[0], &denoms[1]],
+            pair_contract,
+        );
+    }
+    deps.querier.with_token_balances(&[
+        (
+            &"liquidity0000".to_string(),
+            &[(&"addr0000".to_string(), &Uint128::zero())],
+        ),
+        (
+            &"liquidity0001".to_string(),
+            &[(&"addr0000".to_string(), &Uint128::zero())],
+        ),
+    ]);
 
-    let assets = [
-        Asset {
-            info: AssetInfo::Token {
-                contract_addr: "asset0000".to_string(),
+    // an older factory recorded no liquidity token for uusd-ukrw and other assets
+    // for uusd-uluna
+    let mut corrupt = |key: &[u8], edit: &dyn Fn(&mut PairInfoRaw)| {
+        let mut pair_info = PAIRS.load(deps.as_ref().storage, key).unwrap();
+        edit(&mut pair_info);
+        PAIRS.save(deps.as_mut().storage, key, &pair_info).unwrap();
+    };
+    corrupt(b"ukrwuusd", &|pair_info| {
+        pair_info.liquidity_token = CanonicalAddr::from(vec![])
+    });
+    corrupt(b"ulunauusd", &|pair_info| {
+        pair_info.asset_infos = [
+            AssetInfoRaw::NativeToken {
+                denom: "uusd".to_string(),
             },
-            amount: Uint128::zero(),
-        },
+            AssetInfoRaw::NativeToken {
+                denom: "ukrw".to_string(),
+            },
+        ];
+        pair_info.asset_decimals = [8u8, 8u8];
+    });
+
+    let msg = ExecuteMsg::RepairRegistry {
+        start_after: None,
+        limit: Some(2),
+    };
+    assert_eq!(
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("addr0001", &[]),
+            msg.clone()
+        ),
+        Err(StdError::generic_err("unauthorized"))
+    );
+
+    // the keys are ordered ukrw-uluna, ukrw-uusd, uluna-uusd, the liquidity token of
+    // ukrw-uluna is not a token
+    let info = mock_info("addr0000", &[]);
+    let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+    let last_key = Binary::from(b"ukrwuusd".to_vec());
+    assert_eq!(
+        res.attributes,
+        vec![
+            attr("action", "repair_registry"),
+            attr("repaired", last_key.to_base64()),
+            attr("skipped", "pair0002"),
+            attr("mismatched", ""),
+            attr("last_key", last_key.to_base64()),
+        ]
+    );
+    let pair: PairInfo = from_binary(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::PairByLpToken {
+                lp_token: "liquidity0001".to_string(),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(pair.contract_addr, "pair0001".to_string());
+
+    // the batch continues after the cursor
+    let msg = ExecuteMsg::RepairRegistry {
+        start_after: Some(last_key),
+        limit: Some(2),
+    };
+    let res = execute(deps.as_mut(), mock_env(), info.clone(), msg.clone()).unwrap();
+    assert_eq!(
+        res.attributes,
+        vec![
+            attr("action", "repair_registry"),
+            attr("repaired", Binary::from(b"ulunauusd".to_vec()).to_base64()),
+            attr("skipped", ""),
+            attr("mismatched", ""),
+            attr("last_key", ""),
+        ]
+    );
+    let pair_info = PAIRS.load(deps.as_ref().storage, b"ulunauusd").unwrap();
+    assert_eq!(
+        pair_info.to_normal(deps.as_ref().api).unwrap().asset_infos,
+        [native("uusd"), native("uluna")]
+    );
+    assert_eq!(pair_info.asset_decimals, [6u8, 6u8]);
+
+    // repaired entries are left untouched
+    let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+    assert_eq!(res.attributes[1], attr("repaired", ""));
+
+    // a pair holding the assets of another key is reported, its entry is not rewritten
+    deps.querier.with_pair_info(
+        "pair0001",
+        &PairInfo {
+            asset_infos: [native("uusd"), native("uluna")],
+            contract_addr: "pair0001".to_string(),
+            liquidity_token: "liquidity0001".to_string(),
+            asset_decimals: [6u8, 6u8],
+            created_at: None,
+        },
+    );
+    let before = PAIRS.load(deps.as_ref().storage, b"ukrwuusd").unwrap();
+    let msg = ExecuteMsg::RepairRegistry {
+        start_after: None,
+        limit: None,
+    };
+    let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    assert_eq!(res.attributes[1], attr("repaired", ""));
+    assert_eq!(res.attributes[3], attr("mismatched", "pair0001"));
+    assert_eq!(
+        PAIRS.load(deps.as_ref().storage, b"ukrwuusd").unwrap(),
+        before
+    );
+}
+
+#[test]
+fn fail_to_create_pair_with_unknown_denom() {
+    let mut deps = mock_dependencies(&[coin(10u128, "uusd".to_string())]);
+    deps = init(deps);
+
+    deps.querier
+        .with_terraswap_factory(&[], &[("uusd".to_string(), 6u8)]);
+
+    let assets = [
+        Asset {
+            info: AssetInfo::NativeToken {
+                denom: "uxxx".to_string(),
+            },
+            amount: Uint128::zero(),
+        },
+        Asset {
+            info: AssetInfo::NativeToken {
+                denom: "uusd".to_string(),
+            },
+            amount: Uint128::zero(),
+        },
+    ];
+
+    let msg = ExecuteMsg::CreatePair {
+        assets: assets.clone(),
+        allow_unregistered_decimals: None,
+        pair_params: None,
+    };
+
+    let env = mock_env();
+    let info = mock_info("addr0000", &[]);
+
+    match execute(deps.as_mut(), env, info, msg).unwrap_err() {
+        StdError::GenericErr { msg, .. } => assert_eq!(msg, "asset1 is invalid".to_string()),
+        _ => panic!("Must return generic error"),
+    }
+
+    // only the owner can create it, with the default decimals
+    let msg = ExecuteMsg::CreatePair {
+        assets,
+        allow_unregistered_decimals: Some(true),
+        pair_params: None,
+    };
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0001", &[]),
+        msg.clone(),
+    );
+    assert_eq!(res, Err(StdError::generic_err("unauthorized")));
+
+    let res = execute(deps.as_mut(), mock_env(), mock_info("addr0000", &[]), msg).unwrap();
+    match &res.messages[0].msg {
+        CosmosMsg::Wasm(WasmMsg::Instantiate2 { msg, .. }) => {
+            let msg: PairInstantiateMsg = from_binary(msg).unwrap();
+            assert_eq!(msg.asset_decimals, [6u8, 6u8]);
+        }
+        _ => panic!("Must instantiate the pair"),
+    }
+}
+
+#[test]
+fn fail_to_create_pair_with_unknown_token() {
+    let mut deps = mock_dependencies(&[coin(10u128, "uusd".to_string())]);
+    deps = init(deps);
+
+    deps.querier
+        .with_terraswap_factory(&[], &[("uluna".to_string(), 6u8)]);
+
+    let assets = [
+        Asset {
+            info: AssetInfo::NativeToken {
+                denom: "uluna".to_string(),
+            },
+            amount: Uint128::zero(),
+        },
+        Asset {
+            info: AssetInfo::Token {
+                contract_addr: "terra123".to_string(),
+            },
+            amount: Uint128::zero(),
+        },
+    ];
+
+    let msg = ExecuteMsg::CreatePair {
+        assets,
+        allow_unregistered_decimals: None,
+        pair_params: None,
+    };
+
+    let env = mock_env();
+    let info = mock_info("addr0000", &[]);
+
+    match execute(deps.as_mut(), env, info, msg).unwrap_err() {
+        StdError::GenericErr { msg, .. } => assert_eq!(msg, "asset2 is invalid".to_string()),
+        _ => panic!("Must return generic error"),
+    }
+}
+
+#[test]
+fn reply_only_create_pair() {
+    let mut deps = mock_dependencies(&[]);
+
+    deps.querier.with_token_balances(&[(
+        &MOCK_CONTRACT_ADDR.to_string(),
+        &[
+            (&"asset0000".to_string(), &Uint128::from(100u128)),
+            (&"asset0001".to_string(), &Uint128::from(100u128)),
+        ],
+    )]);
+
+    let assets = [
+        Asset {
+            info: AssetInfo::Token {
+                contract_addr: "asset0000".to_string(),
+            },
+            amount: Uint128::zero(),
+        },
         Asset {
             info: AssetInfo::Token {
                 contract_addr: "asset0001".to_string(),
@@ -439,13 +1256,16 @@ fn reply_only_create_pair() {
     ];
 
     let pair_key = pair_key(&raw_infos);
-    TMP_PAIR_INFO
+    TMP_PAIR_INFOS
         .save(
             &mut deps.storage,
+            1,
             &TmpPairInfo {
                 assets: raw_assets,
                 pair_key,
                 sender: Addr::unchecked("addr0000"),
+                slippage_tolerance: None,
+                receiver: None,
                 asset_decimals: [8u8, 8u8],
             },
         )
@@ -473,10 +1293,11 @@ fn reply_only_create_pair() {
         &[(
             &"0000".to_string(),
             &PairInfo {
-                asset_infos,
+                asset_infos: asset_infos.clone(),
                 contract_addr: "0000".to_string(),
                 liquidity_token: "liquidity0000".to_string(),
                 asset_decimals: [8u8, 8u8],
+                created_at: None,
             },
         )],
         &[],
@@ -490,6 +1311,47 @@ fn reply_only_create_pair() {
         res.attributes[1],
         attr("liquidity_token_addr", "liquidity0000")
     );
+
+    // the registered pair is indexed by both of its assets
+    for asset_info in asset_infos.clone() {
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::PairsByAsset {
+                asset_info,
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap();
+        let pairs = from_binary::<PairsResponse>(&res).unwrap().pairs;
+        assert_eq!(pairs.len(), 1);
+        assert_eq!(pairs[0].contract_addr, "0000");
+    }
+
+    // and by its liquidity token
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::PairByLpToken {
+            lp_token: "liquidity0000".to_string(),
+        },
+    )
+    .unwrap();
+    let pair_info: PairInfo = from_binary(&res).unwrap();
+    assert_eq!(pair_info.contract_addr, "0000");
+    assert_eq!(pair_info.asset_infos, asset_infos);
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::PairByLpToken {
+            lp_token: "liquidity0001".to_string(),
+        },
+    );
+    assert_eq!(
+        res,
+        Err(StdError::generic_err("no pair for the liquidity token"))
+    );
 }
 
 #[test]
@@ -530,13 +1392,16 @@ fn reply_create_pair_with_provide() {
     ];
 
     let pair_key = pair_key(&raw_infos);
-    TMP_PAIR_INFO
+    TMP_PAIR_INFOS
         .save(
             &mut deps.storage,
+            1,
             &TmpPairInfo {
                 assets: raw_assets,
                 pair_key,
                 sender: Addr::unchecked("addr0000"),
+                slippage_tolerance: None,
+                receiver: None,
                 asset_decimals: [18u8, 8u8],
             },
         )
@@ -568,6 +1433,7 @@ fn reply_create_pair_with_provide() {
                 contract_addr: "pair0000".to_string(),
                 liquidity_token: "liquidity0000".to_string(),
                 asset_decimals: [18u8, 8u8],
+                created_at: None,
             },
         )],
         &[("uluna".to_string(), 18u8)],
@@ -623,6 +1489,7 @@ fn reply_create_pair_with_provide() {
                     receiver: Some("addr0000".to_string()),
                     deadline: None,
                     slippage_tolerance: None,
+                    min_lp_to_receive: None,
                 })
                 .unwrap(),
                 funds: coins(100u128, "uluna".to_string()),
@@ -638,6 +1505,143 @@ fn reply_create_pair_with_provide() {
     );
 }
 
+#[test]
+fn create_pair_and_provide() {
+    let mut deps = mock_dependencies(&[]);
+    deps = init(deps);
+    deps.querier
+        .with_terraswap_factory(&[], &[("uusd".to_string(), 6u8)]);
+
+    let asset_infos = [
+        AssetInfo::NativeToken {
+            denom: "uusd".to_string(),
+        },
+        AssetInfo::Token {
+            contract_addr: "asset0001".to_string(),
+        },
+    ];
+    let assets = [
+        Asset {
+            info: asset_infos[0].clone(),
+            amount: Uint128::from(100u128),
+        },
+        Asset {
+            info: asset_infos[1].clone(),
+            amount: Uint128::from(200u128),
+        },
+    ];
+    let msg = ExecuteMsg::CreatePairAndProvide {
+        asset_infos: asset_infos.clone(),
+        assets: assets.clone(),
+        slippage_tolerance: Some(Decimal::percent(1)),
+        receiver: Some("addr0002".to_string()),
+    };
+
+    let mismatched_msg = ExecuteMsg::CreatePairAndProvide {
+        asset_infos: [
+            asset_infos[0].clone(),
+            AssetInfo::NativeToken {
+                denom: "uluna".to_string(),
+            },
+        ],
+        assets: assets.clone(),
+        slippage_tolerance: None,
+        receiver: None,
+    };
+    let info = mock_info("addr0001", &coins(100u128, "uusd"));
+    assert_eq!(
+        execute(deps.as_mut(), mock_env(), info.clone(), mismatched_msg),
+        Err(StdError::generic_err("assets must match asset_infos"))
+    );
+
+    // the cw20 side needs an allowance to the factory
+    assert_eq!(
+        execute(deps.as_mut(), mock_env(), info.clone(), msg.clone()),
+        Err(StdError::generic_err(
+            "insufficient allowance of asset0001 to the factory"
+        ))
+    );
+    deps.querier.with_token_allowance(
+        "asset0001",
+        "addr0001",
+        MOCK_CONTRACT_ADDR,
+        Uint128::from(199u128),
+    );
+    assert_eq!(
+        execute(deps.as_mut(), mock_env(), info.clone(), msg.clone()),
+        Err(StdError::generic_err(
+            "insufficient allowance of asset0001 to the factory"
+        ))
+    );
+    deps.querier.with_token_allowance(
+        "asset0001",
+        "addr0001",
+        MOCK_CONTRACT_ADDR,
+        Uint128::from(200u128),
+    );
+
+    // the native side is sent along
+    let underpaid_info = mock_info("addr0001", &coins(99u128, "uusd"));
+    assert_eq!(
+        execute(deps.as_mut(), mock_env(), underpaid_info, msg.clone()),
+        Err(StdError::generic_err(
+            "Native token balance mismatch between the argument and the transferred"
+        ))
+    );
+
+    let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    assert_eq!(res.messages.len(), 1);
+    assert_eq!(res.messages[0].reply_on, ReplyOn::Always);
+
+    let mut data = MsgInstantiateContractResponse::new();
+    data.set_contract_address("pair0000".to_string());
+    let reply_msg = Reply {
+        id: 1,
+        result: SubMsgResult::Ok(SubMsgResponse {
+            events: vec![],
+            data: Some(data.write_to_bytes().unwrap().into()),
+        }),
+    };
+    let res = reply(deps.as_mut(), mock_env(), reply_msg).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(WasmMsg::Execute {
+                contract_addr: "asset0001".to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::IncreaseAllowance {
+                    spender: "pair0000".to_string(),
+                    amount: Uint128::from(200u128),
+                    expires: None,
+                })
+                .unwrap(),
+                funds: vec![],
+            }),
+            SubMsg::new(WasmMsg::Execute {
+                contract_addr: "asset0001".to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::TransferFrom {
+                    owner: "addr0001".to_string(),
+                    recipient: MOCK_CONTRACT_ADDR.to_string(),
+                    amount: Uint128::from(200u128),
+                })
+                .unwrap(),
+                funds: vec![],
+            }),
+            SubMsg::new(WasmMsg::Execute {
+                contract_addr: "pair0000".to_string(),
+                msg: to_binary(&PairExecuteMsg::ProvideLiquidity {
+                    assets,
+                    receiver: Some("addr0002".to_string()),
+                    deadline: None,
+                    slippage_tolerance: Some(Decimal::percent(1)),
+                    min_lp_to_receive: None,
+                })
+                .unwrap(),
+                funds: coins(100u128, "uusd"),
+            }),
+        ]
+    );
+}
+
 #[test]
 fn failed_reply_with_unknown_id() {
     let mut deps = mock_dependencies(&[]);
@@ -657,180 +1661,2108 @@ fn failed_reply_with_unknown_id() {
     assert_eq!(res, Err(StdError::generic_err("invalid reply msg")))
 }
 
-#[test]
-fn normal_add_allow_native_token() {
-    let mut deps = mock_dependencies(&[coin(1u128, "uluna".to_string())]);
-    deps = init(deps);
+// humanizes the 32 byte instantiate2 addresses the mock api rejects as hex strings
+struct Instantiate2Api(MockApi);
 
-    let msg = ExecuteMsg::AddNativeTokenDecimals {
-        denom: "uluna".to_string(),
-        decimals: 6u8,
-    };
+impl Api for Instantiate2Api {
+    fn addr_validate(&self, human: &str) -> StdResult<Addr> {
+        self.0.addr_validate(human)
+    }
 
-    let info = mock_info("addr0000", &[]);
+    fn addr_canonicalize(&self, human: &str) -> StdResult<CanonicalAddr> {
+        self.0.addr_canonicalize(human)
+    }
 
-    assert_eq!(
-        execute(deps.as_mut(), mock_env(), info, msg).unwrap(),
-        Response::new().add_attributes(vec![
-            ("action", "add_allow_native_token"),
-            ("denom", "uluna"),
-            ("decimals", "6"),
-        ])
-    );
+    fn addr_humanize(&self, canonical: &CanonicalAddr) -> StdResult<Addr> {
+        if canonical.len() == 32 {
+            return Ok(Addr::unchecked(
+                HexBinary::from(canonical.as_slice()).to_hex(),
+            ));
+        }
+        self.0.addr_humanize(canonical)
+    }
 
-    let res = query(
-        deps.as_ref(),
-        mock_env(),
-        QueryMsg::NativeTokenDecimals {
+    fn secp256k1_verify(
+        &self,
+        message_hash: &[u8],
+        signature: &[u8],
+        public_key: &[u8],
+    ) -> Result<bool, VerificationError> {
+        self.0.secp256k1_verify(message_hash, signature, public_key)
+    }
+
+    fn secp256k1_recover_pubkey(
+        &self,
+        message_hash: &[u8],
+        signature: &[u8],
+        recovery_param: u8,
+    ) -> Result<Vec<u8>, RecoverPubkeyError> {
+        self.0
+            .secp256k1_recover_pubkey(message_hash, signature, recovery_param)
+    }
+
+    fn ed25519_verify(
+        &self,
+        message: &[u8],
+        signature: &[u8],
+        public_key: &[u8],
+    ) -> Result<bool, VerificationError> {
+        self.0.ed25519_verify(message, signature, public_key)
+    }
+
+    fn ed25519_batch_verify(
+        &self,
+        messages: &[&[u8]],
+        signatures: &[&[u8]],
+        public_keys: &[&[u8]],
+    ) -> Result<bool, VerificationError> {
+        self.0
+            .ed25519_batch_verify(messages, signatures, public_keys)
+    }
+
+    fn debug(&self, message: &str) {
+        self.0.debug(message)
+    }
+}
+
+#[test]
+fn predict_pair_address() {
+    let mut deps = OwnedDeps {
+        storage: MockStorage::default(),
+        api: Instantiate2Api(MockApi::default()),
+        querier: WasmMockQuerier::new(MockQuerier::new(&[(MOCK_CONTRACT_ADDR, &[])])),
+        custom_query_type: PhantomData::<TerraQuery>,
+    };
+    let msg = InstantiateMsg {
+        pair_code_id: 321u64,
+        token_code_id: 123u64,
+    };
+    let info = mock_info("addr0000", &[]);
+    instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+    let asset_infos = [
+        AssetInfo::NativeToken {
+            denom: "uusd".to_string(),
+        },
+        AssetInfo::NativeToken {
             denom: "uluna".to_string(),
         },
+    ];
+    let predict = |deps: &OwnedDeps<MockStorage, Instantiate2Api, WasmMockQuerier, TerraQuery>,
+                   asset_infos: [AssetInfo; 2]| {
+        from_binary::<PredictPairAddressResponse>(
+            &query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::PredictPairAddress { asset_infos },
+            )
+            .unwrap(),
+        )
+        .unwrap()
+        .contract_addr
+    };
+    let predicted = predict(&deps, asset_infos.clone());
+
+    // the address does not depend on the order of the assets
+    let reversed = [asset_infos[1].clone(), asset_infos[0].clone()];
+    assert_eq!(predict(&deps, reversed), predicted);
+
+    let msg = ExecuteMsg::CreatePair {
+        assets: [
+            Asset {
+                info: asset_infos[0].clone(),
+                amount: Uint128::zero(),
+            },
+            Asset {
+                info: asset_infos[1].clone(),
+                amount: Uint128::zero(),
+            },
+        ],
+        allow_unregistered_decimals: Some(true),
+        pair_params: None,
+    };
+    let res = execute(deps.as_mut(), mock_env(), info, msg.clone()).unwrap();
+
+    // the chain derives the address from the checksum of the code, the creator and the salt
+    let salt = match &res.messages[0].msg {
+        CosmosMsg::Wasm(WasmMsg::Instantiate2 { code_id, salt, .. }) => {
+            assert_eq!(*code_id, 321u64);
+            salt.clone()
+        }
+        _ => panic!("DO NOT ENTER HERE"),
+    };
+    let checksum = deps
+        .as_ref()
+        .querier
+        .query_wasm_code_info(321u64)
+        .unwrap()
+        .checksum;
+    let creator = deps.api.addr_canonicalize(MOCK_CONTRACT_ADDR).unwrap();
+    let created = deps
+        .api
+        .addr_humanize(
+            &instantiate2_address(checksum.as_slice(), &creator, salt.as_slice()).unwrap(),
+        )
+        .unwrap();
+    assert_eq!(created.to_string(), predicted);
+
+    let mut data = MsgInstantiateContractResponse::new();
+    data.set_contract_address(created.to_string());
+    let reply_msg = Reply {
+        id: 1,
+        result: SubMsgResult::Ok(SubMsgResponse {
+            events: vec![],
+            data: Some(data.write_to_bytes().unwrap().into()),
+        }),
+    };
+    reply(deps.as_mut(), mock_env(), reply_msg).unwrap();
+
+    let pair_info: PairInfo = from_binary(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Pair {
+                asset_infos: asset_infos.clone(),
+            },
+        )
+        .unwrap(),
     )
     .unwrap();
-    let res: NativeTokenDecimalsResponse = from_binary(&res).unwrap();
-    assert_eq!(6u8, res.decimals)
-}
-
-#[test]
-fn failed_add_allow_native_token_with_non_admin() {
-    let mut deps = mock_dependencies(&[coin(1u128, "uluna".to_string())]);
-    deps = init(deps);
+    assert_eq!(pair_info.contract_addr, predicted);
 
-    let msg = ExecuteMsg::AddNativeTokenDecimals {
-        denom: "uluna".to_string(),
-        decimals: 6u8,
+    // a pair created again after the deregistration gets a new address
+    let deregister_msg = ExecuteMsg::DeregisterPair {
+        asset_infos: asset_infos.clone(),
     };
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        deregister_msg,
+    )
+    .unwrap();
+    let repredicted = predict(&deps, asset_infos);
+    assert_ne!(repredicted, predicted);
 
-    let info = mock_info("noadmin", &[]);
+    let res = execute(deps.as_mut(), mock_env(), mock_info("addr0000", &[]), msg).unwrap();
+    let salt = match &res.messages[0].msg {
+        CosmosMsg::Wasm(WasmMsg::Instantiate2 { salt, .. }) => salt.clone(),
+        _ => panic!("DO NOT ENTER HERE"),
+    };
+    let created = deps
+        .api
+        .addr_humanize(
+            &instantiate2_address(checksum.as_slice(), &creator, salt.as_slice()).unwrap(),
+        )
+        .unwrap();
+    assert_eq!(created.to_string(), repredicted);
 
+    // an address collision is reported as an existing pair
+    let reply_msg = Reply {
+        id: res.messages[0].id,
+        result: SubMsgResult::Err("codespace: wasm, code: 22".to_string()),
+    };
     assert_eq!(
-        execute(deps.as_mut(), mock_env(), info, msg),
-        Err(StdError::generic_err("unauthorized"))
+        reply(deps.as_mut(), mock_env(), reply_msg),
+        Err(StdError::generic_err(format!(
+            "pair already exists: {}",
+            repredicted
+        )))
     );
 }
 
 #[test]
-fn failed_add_allow_native_token_with_zero_factory_balance() {
-    let mut deps = mock_dependencies(&[coin(0u128, "uluna".to_string())]);
+fn pair_created_event() {
+    let mut deps = mock_dependencies(&[]);
     deps = init(deps);
+    deps.querier
+        .with_terraswap_factory(&[], &[("uusd".to_string(), 6u8)]);
 
-    let msg = ExecuteMsg::AddNativeTokenDecimals {
-        denom: "uluna".to_string(),
-        decimals: 6u8,
+    let create_pair = |asset_infos: [AssetInfo; 2], allow_unregistered_decimals: Option<bool>| {
+        ExecuteMsg::CreatePair {
+            assets: asset_infos.map(|info| Asset {
+                info,
+                amount: Uint128::zero(),
+            }),
+            allow_unregistered_decimals,
+            pair_params: None,
+        }
+    };
+    let reply_msg = |id: u64, pair_contract: &str| {
+        let mut data = MsgInstantiateContractResponse::new();
+        data.set_contract_address(pair_contract.to_string());
+        Reply {
+            id,
+            result: SubMsgResult::Ok(SubMsgResponse {
+                events: vec![],
+                data: Some(data.write_to_bytes().unwrap().into()),
+            }),
+        }
     };
 
-    let info = mock_info("addr0000", &[]);
+    // both pairs are created before either is replied to
+    let msg = create_pair(
+        [
+            AssetInfo::NativeToken {
+                denom: "uusd".to_string(),
+            },
+            AssetInfo::NativeToken {
+                denom: "uluna".to_string(),
+            },
+        ],
+        Some(true),
+    );
+    let res = execute(deps.as_mut(), mock_env(), mock_info("addr0000", &[]), msg).unwrap();
+    let first_id = res.messages[0].id;
+    let msg = create_pair(
+        [
+            AssetInfo::NativeToken {
+                denom: "uusd".to_string(),
+            },
+            AssetInfo::Token {
+                contract_addr: "asset0001".to_string(),
+            },
+        ],
+        None,
+    );
+    let res = execute(deps.as_mut(), mock_env(), mock_info("addr0001", &[]), msg).unwrap();
+    let second_id = res.messages[0].id;
+    assert_ne!(first_id, second_id);
 
+    let res = reply(deps.as_mut(), mock_env(), reply_msg(second_id, "pair0001")).unwrap();
     assert_eq!(
-        execute(deps.as_mut(), mock_env(), info, msg),
-        Err(StdError::generic_err(
-            "a balance greater than zero is required by the factory for verification",
-        ))
+        res.events,
+        vec![Event::new("terraswap_pair_created").add_attributes(vec![
+            attr("pair_contract", "pair0001"),
+            attr("liquidity_token", "liquidity0000"),
+            attr("asset0", "uusd"),
+            attr("asset1", "asset0001"),
+            attr("decimals", "6,8"),
+            attr("creator", "addr0001"),
+        ])]
     );
-}
 
-#[test]
-fn append_add_allow_native_token_with_already_exist_token() {
-    let mut deps = mock_dependencies(&[coin(1u128, "uluna".to_string())]);
-    deps = init(deps);
+    let res = reply(deps.as_mut(), mock_env(), reply_msg(first_id, "pair0000")).unwrap();
+    assert_eq!(
+        res.events,
+        vec![Event::new("terraswap_pair_created").add_attributes(vec![
+            attr("pair_contract", "pair0000"),
+            attr("liquidity_token", "liquidity0000"),
+            attr("asset0", "uusd"),
+            attr("asset1", "uluna"),
+            attr("decimals", "6,6"),
+            attr("creator", "addr0000"),
+        ])]
+    );
 
-    let msg = ExecuteMsg::AddNativeTokenDecimals {
-        denom: "uluna".to_string(),
+    // the temporary infos are removed after the reply
+    assert!(TMP_PAIR_INFOS.is_empty(&deps.storage));
+    assert_eq!(
+        reply(deps.as_mut(), mock_env(), reply_msg(first_id, "pair0000")),
+        Err(StdError::generic_err("invalid reply msg"))
+    );
+}
 
-        decimals: 6u8,
+// creates a pair of native denoms, replied with the given pair address
+fn create_native_pair(
+    deps: &mut OwnedDeps<MockStorage, MockApi, WasmMockQuerier, TerraQuery>,
+    env: Env,
+    denoms: [&str; 2],
+    pair_contract: &str,
+) {
+    let msg = ExecuteMsg::CreatePair {
+        assets: [
+            Asset {
+                info: AssetInfo::NativeToken {
+                    denom: denoms[0].to_string(),
+                },
+                amount: Uint128::zero(),
+            },
+            Asset {
+                info: AssetInfo::NativeToken {
+                    denom: denoms[1].to_string(),
+                },
+                amount: Uint128::zero(),
+            },
+        ],
+        allow_unregistered_decimals: Some(true),
+        pair_params: None,
     };
+    let res = execute(deps.as_mut(), env.clone(), mock_info("addr0000", &[]), msg).unwrap();
 
-    let info = mock_info("addr0000", &[]);
+    let mut data = MsgInstantiateContractResponse::new();
+    data.set_contract_address(pair_contract.to_string());
+    let reply_msg = Reply {
+        id: res.messages[0].id,
+        result: SubMsgResult::Ok(SubMsgResponse {
+            events: vec![],
+            data: Some(data.write_to_bytes().unwrap().into()),
+        }),
+    };
+    reply(deps.as_mut(), env, reply_msg).unwrap();
+}
 
-    execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+fn query_stats(
+    deps: &OwnedDeps<MockStorage, MockApi, WasmMockQuerier, TerraQuery>,
+) -> StatsResponse {
+    from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Stats {}).unwrap()).unwrap()
+}
 
-    let res = query(
-        deps.as_ref(),
-        mock_env(),
-        QueryMsg::NativeTokenDecimals {
-            denom: "uluna".to_string(),
-        },
-    )
-    .unwrap();
-    let res: NativeTokenDecimalsResponse = from_binary(&res).unwrap();
-    assert_eq!(6u8, res.decimals);
+#[test]
+fn pair_stats() {
+    let mut deps = mock_dependencies(&[]);
+    deps = init(deps);
 
-    let msg = ExecuteMsg::AddNativeTokenDecimals {
-        denom: "uluna".to_string(),
-        decimals: 7u8,
-    };
+    let mut env = mock_env();
+    create_native_pair(&mut deps, env.clone(), ["uusd", "uluna"], "pair0000");
+    env.block.time = env.block.time.plus_seconds(10);
+    create_native_pair(&mut deps, env.clone(), ["uusd", "ukrw"], "pair0001");
 
-    execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    let stats = query_stats(&deps);
+    assert_eq!(stats.pair_count, 2);
+    assert!(stats.pair_count_complete);
+    assert_eq!(
+        stats.recent_pairs,
+        vec![
+            RecentPairResponse {
+                contract_addr: "pair0001".to_string(),
+                created_at: env.block.time.seconds(),
+            },
+            RecentPairResponse {
+                contract_addr: "pair0000".to_string(),
+                created_at: mock_env().block.time.seconds(),
+            },
+        ]
+    );
 
-    let res = query(
-        deps.as_ref(),
-        mock_env(),
-        QueryMsg::NativeTokenDecimals {
-            denom: "uluna".to_string(),
-        },
-    )
-    .unwrap();
-    let res: NativeTokenDecimalsResponse = from_binary(&res).unwrap();
-    assert_eq!(7u8, res.decimals)
+    let msg = ExecuteMsg::DeregisterPair {
+        asset_infos: [
+            AssetInfo::NativeToken {
+                denom: "uluna".to_string(),
+            },
+            AssetInfo::NativeToken {
+                denom: "uusd".to_string(),
+            },
+        ],
+    };
+    execute(deps.as_mut(), mock_env(), mock_info("addr0000", &[]), msg).unwrap();
+    assert_eq!(query_stats(&deps).pair_count, 1);
+
+    create_native_pair(&mut deps, env.clone(), ["uluna", "uusd"], "pair0002");
+    let stats = query_stats(&deps);
+    assert_eq!(stats.pair_count, 2);
+    assert_eq!(stats.recent_pairs.len(), 3);
+    assert_eq!(stats.recent_pairs[0].contract_addr, "pair0002".to_string());
+
+    // only the latest creations are kept
+    for i in 0..10 {
+        create_native_pair(
+            &mut deps,
+            env.clone(),
+            ["uusd", &format!("denom{}", i)],
+            "pair0003",
+        );
+    }
+    let stats = query_stats(&deps);
+    assert_eq!(stats.pair_count, 12);
+    assert_eq!(stats.recent_pairs.len(), 10);
 }
 
 #[test]
-fn normal_migrate_pair() {
-    let mut deps = mock_dependencies(&[coin(1u128, "uluna".to_string())]);
+fn router_bootstrap() {
+    let mut deps = mock_dependencies(&[coin(1u128, "uluna"), coin(1u128, "uusd")]);
     deps = init(deps);
 
-    let msg = ExecuteMsg::MigratePair {
-        code_id: Some(123u64),
-        contract: "contract0000".to_string(),
-    };
-
-    let info = mock_info("addr0000", &[]);
+    for (denom, decimals) in [("uusd", 6u8), ("uluna", 8u8)] {
+        let msg = ExecuteMsg::AddNativeTokenDecimals {
+            denom: denom.to_string(),
+            decimals,
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("addr0000", &[]), msg).unwrap();
+    }
+    create_native_pair(&mut deps, mock_env(), ["uusd", "uluna"], "pair0000");
 
+    let res: RouterBootstrapResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::RouterBootstrap {}).unwrap())
+            .unwrap();
     assert_eq!(
-        execute(deps.as_mut(), mock_env(), info, msg).unwrap(),
-        Response::new().add_message(CosmosMsg::Wasm(WasmMsg::Migrate {
-            contract_addr: "contract0000".to_string(),
-            new_code_id: 123u64,
-            msg: to_binary(&PairMigrateMsg {}).unwrap(),
-        })),
+        res,
+        RouterBootstrapResponse {
+            factory: MOCK_CONTRACT_ADDR.to_string(),
+            pair_code_id: 321u64,
+            token_code_id: 123u64,
+            native_token_decimals: vec![
+                NativeTokenDecimalsItem {
+                    denom: "uluna".to_string(),
+                    decimals: 8u8,
+                },
+                NativeTokenDecimalsItem {
+                    denom: "uusd".to_string(),
+                    decimals: 6u8,
+                },
+            ],
+            pair_count: 1,
+            pair_count_complete: true,
+        }
     );
 }
 
 #[test]
-fn normal_migrate_pair_with_none_code_id_will_config_code_id() {
-    let mut deps = mock_dependencies(&[coin(1u128, "uluna".to_string())]);
+fn query_pairs_in_order() {
+    let mut deps = mock_dependencies(&[]);
     deps = init(deps);
 
-    let msg = ExecuteMsg::MigratePair {
-        code_id: None,
-        contract: "contract0000".to_string(),
+    let mut env = mock_env();
+    create_native_pair(&mut deps, env.clone(), ["uusd", "uluna"], "pair0000");
+    env.block.height += 1;
+    env.block.time = env.block.time.plus_seconds(5);
+    create_native_pair(&mut deps, env.clone(), ["uusd", "ukrw"], "pair0001");
+    create_native_pair(&mut deps, env.clone(), ["ukrw", "uluna"], "pair0002");
+
+    let query_pairs = |deps: &OwnedDeps<MockStorage, MockApi, WasmMockQuerier, TerraQuery>,
+                       start_after: Option<[&str; 2]>,
+                       limit: u32,
+                       order: Option<OrderBy>| {
+        from_binary::<PairsResponse>(
+            &query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::Pairs {
+                    start_after: start_after.map(|denoms| {
+                        denoms.map(|denom| AssetInfo::NativeToken {
+                            denom: denom.to_string(),
+                        })
+                    }),
+                    limit: Some(limit),
+                    order,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap()
+        .pairs
+    };
+    let addrs = |pairs: Vec<PairInfo>| {
+        pairs
+            .into_iter()
+            .map(|pair| pair.contract_addr)
+            .collect::<Vec<String>>()
     };
 
-    let info = mock_info("addr0000", &[]);
+    // the keys are ordered ukrw-uluna, ukrw-uusd, uluna-uusd
+    let pairs = query_pairs(&deps, None, 3, None);
+    assert_eq!(
+        pairs[2].created_at,
+        Some(PairCreation {
+            height: mock_env().block.height,
+            time: mock_env().block.time.seconds(),
+        })
+    );
+    assert_eq!(
+        pairs[0].created_at,
+        Some(PairCreation {
+            height: env.block.height,
+            time: env.block.time.seconds(),
+        })
+    );
+    assert_eq!(addrs(pairs), vec!["pair0002", "pair0001", "pair0000"]);
+    assert_eq!(
+        addrs(query_pairs(
+            &deps,
+            Some(["ukrw", "uluna"]),
+            3,
+            Some(OrderBy::Ascending)
+        )),
+        vec!["pair0001", "pair0000"]
+    );
+    assert_eq!(
+        addrs(query_pairs(&deps, None, 2, Some(OrderBy::Descending))),
+        vec!["pair0000", "pair0001"]
+    );
+    assert_eq!(
+        addrs(query_pairs(
+            &deps,
+            Some(["uusd", "ukrw"]),
+            3,
+            Some(OrderBy::Descending)
+        )),
+        vec!["pair0002"]
+    );
+    assert_eq!(
+        query_pairs(&deps, Some(["ukrw", "uluna"]), 3, Some(OrderBy::Descending)),
+        vec![]
+    );
 
+    // the limit is clamped
+    for i in 0..100 {
+        create_native_pair(
+            &mut deps,
+            env.clone(),
+            ["uusd", &format!("denom{:03}", i)],
+            &format!("pair1{:03}", i),
+        );
+    }
+    assert_eq!(query_pairs(&deps, None, 200, None).len(), 100);
     assert_eq!(
-        execute(deps.as_mut(), mock_env(), info, msg).unwrap(),
-        Response::new().add_message(CosmosMsg::Wasm(WasmMsg::Migrate {
-            contract_addr: "contract0000".to_string(),
-            new_code_id: 321u64,
-            msg: to_binary(&PairMigrateMsg {}).unwrap(),
-        })),
+        query_pairs(&deps, None, 200, Some(OrderBy::Descending)).len(),
+        100
     );
 }
 
 #[test]
-fn failed_migrate_pair_with_no_admin() {
-    let mut deps = mock_dependencies(&[coin(1u128, "uluna".to_string())]);
+fn validate_and_refresh_pair() {
+    let mut deps = mock_dependencies(&[]);
     deps = init(deps);
+    create_native_pair(&mut deps, mock_env(), ["uusd", "uluna"], "pair0000");
 
-    let msg = ExecuteMsg::MigratePair {
-        code_id: None,
-        contract: "contract0000".to_string(),
+    let asset_infos = [
+        AssetInfo::NativeToken {
+            denom: "uusd".to_string(),
+        },
+        AssetInfo::NativeToken {
+            denom: "uluna".to_string(),
+        },
+    ];
+    let live_pair = |asset_infos: [AssetInfo; 2], liquidity_token: &str| PairInfo {
+        asset_infos,
+        contract_addr: "pair0000".to_string(),
+        liquidity_token: liquidity_token.to_string(),
+        asset_decimals: [6u8, 6u8],
+        created_at: None,
+    };
+    let validate = |deps: &OwnedDeps<MockStorage, MockApi, WasmMockQuerier, TerraQuery>| {
+        from_binary::<ValidatePairResponse>(
+            &query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::ValidatePair {
+                    asset_infos: asset_infos.clone(),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap()
+    };
+    let refresh_msg = ExecuteMsg::RefreshPair {
+        asset_infos: asset_infos.clone(),
     };
 
-    let info = mock_info("noadmin", &[]);
+    deps.querier
+        .with_pair_info("pair0000", &live_pair(asset_infos.clone(), "liquidity0000"));
+    deps.querier.with_token_balances(&[(
+        &"liquidity0000".to_string(),
+        &[(&"addr0000".to_string(), &Uint128::zero())],
+    )]);
+    let res = validate(&deps);
+    assert!(res.live_matches);
+    assert_eq!(res.mismatches, Vec::<String>::new());
+    assert_eq!(res.registered.liquidity_token, "liquidity0000".to_string());
 
+    // the pair has moved to another liquidity token
+    deps.querier
+        .with_pair_info("pair0000", &live_pair(asset_infos.clone(), "liquidity0001"));
+    let res = validate(&deps);
+    assert!(!res.live_matches);
     assert_eq!(
-        execute(deps.as_mut(), mock_env(), info, msg),
-        Err(StdError::generic_err("unauthorized")),
+        res.mismatches,
+        vec!["liquidity_token: registered liquidity0000, live liquidity0001".to_string()]
+    );
+
+    assert_eq!(
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("addr0001", &[]),
+            refresh_msg.clone()
+        ),
+        Err(StdError::generic_err("unauthorized"))
+    );
+    // the new liquidity token is not a token yet
+    assert!(execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        refresh_msg.clone()
+    )
+    .is_err());
+
+    deps.querier.with_token_balances(&[(
+        &"liquidity0001".to_string(),
+        &[(&"addr0000".to_string(), &Uint128::zero())],
+    )]);
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        refresh_msg.clone(),
+    )
+    .unwrap();
+    assert_eq!(
+        res.attributes,
+        vec![
+            attr("action", "refresh_pair"),
+            attr("pair", "uusd-uluna"),
+            attr("liquidity_token", "liquidity0001"),
+            attr("asset_decimals", "6, 6"),
+        ]
+    );
+    let res = validate(&deps);
+    assert!(res.live_matches);
+    assert_eq!(res.registered.liquidity_token, "liquidity0001".to_string());
+    let pair_info: PairInfo = from_binary(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::PairByLpToken {
+                lp_token: "liquidity0001".to_string(),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(pair_info.contract_addr, "pair0000".to_string());
+    assert_eq!(
+        query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::PairByLpToken {
+                lp_token: "liquidity0000".to_string(),
+            },
+        ),
+        Err(StdError::generic_err("no pair for the liquidity token"))
+    );
+
+    // a pair of other assets is not refreshed
+    let other_assets = [
+        AssetInfo::NativeToken {
+            denom: "uusd".to_string(),
+        },
+        AssetInfo::NativeToken {
+            denom: "ukrw".to_string(),
+        },
+    ];
+    deps.querier
+        .with_pair_info("pair0000", &live_pair(other_assets, "liquidity0001"));
+    let res = validate(&deps);
+    assert_eq!(
+        res.mismatches,
+        vec!["asset_infos: registered uusd-uluna, live uusd-ukrw".to_string()]
+    );
+    assert_eq!(
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("addr0000", &[]),
+            refresh_msg
+        ),
+        Err(StdError::generic_err(
+            "pair assets do not match the registry"
+        ))
+    );
+}
+
+#[test]
+fn count_pairs_after_migration() {
+    let mut deps = mock_dependencies(&[]);
+    deps = init(deps);
+
+    // a factory from before the pair count with registered pairs
+    let native = |denom: &str| AssetInfo::NativeToken {
+        denom: denom.to_string(),
+    };
+    for (i, asset_infos) in [
+        [native("uluna"), native("uusd")],
+        [native("ukrw"), native("uluna")],
+        [native("ukrw"), native("uusd")],
+    ]
+    .iter()
+    .enumerate()
+    {
+        let raw_infos = [
+            asset_infos[0].to_raw(deps.as_ref().api).unwrap(),
+            asset_infos[1].to_raw(deps.as_ref().api).unwrap(),
+        ];
+        let pair_info = PairInfoRaw {
+            asset_infos: raw_infos.clone(),
+            contract_addr: deps
+                .as_ref()
+                .api
+                .addr_canonicalize(&format!("pair000{}", i))
+                .unwrap(),
+            liquidity_token: deps
+                .as_ref()
+                .api
+                .addr_canonicalize(&format!("liquidity000{}", i))
+                .unwrap(),
+            asset_decimals: [6u8, 6u8],
+            created_at: None,
+        };
+        PAIRS
+            .save(deps.as_mut().storage, &pair_key(&raw_infos), &pair_info)
+            .unwrap();
+    }
+    PAIR_COUNT.remove(deps.as_mut().storage);
+    set_contract_version(
+        deps.as_mut().storage,
+        "crates.io:terraswap-factory",
+        "0.0.0",
+    )
+    .unwrap();
+
+    migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+    let stats = query_stats(&deps);
+    assert_eq!(stats.pair_count, 0);
+    assert!(!stats.pair_count_complete);
+
+    let info = mock_info("addr0000", &[]);
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        ExecuteMsg::CountPairs { limit: Some(2) },
+    )
+    .unwrap();
+    assert_eq!(
+        res.attributes,
+        vec![
+            attr("action", "count_pairs"),
+            attr("counted", "2"),
+            attr("complete", "false"),
+        ]
+    );
+
+    // the keys are ordered ukrw-uluna, ukrw-uusd, uluna-uusd, the pairs created or
+    // deregistered after the cursor are left to the count
+    create_native_pair(&mut deps, mock_env(), ["uusd", "uwon"], "pair0003");
+    let msg = ExecuteMsg::DeregisterPair {
+        asset_infos: [native("uluna"), native("uusd")],
+    };
+    execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+    create_native_pair(&mut deps, mock_env(), ["uaud", "uusd"], "pair0004");
+    assert_eq!(query_stats(&deps).pair_count, 3);
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        ExecuteMsg::CountPairs { limit: Some(2) },
+    )
+    .unwrap();
+    assert_eq!(res.attributes[1], attr("counted", "1"));
+    assert_eq!(res.attributes[2], attr("complete", "true"));
+
+    let stats = query_stats(&deps);
+    assert_eq!(stats.pair_count, 4);
+    assert!(stats.pair_count_complete);
+
+    assert_eq!(
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("addr0001", &[]),
+            ExecuteMsg::CountPairs { limit: None },
+        ),
+        Err(StdError::generic_err("unauthorized"))
+    );
+}
+
+#[test]
+fn normal_add_allow_native_token() {
+    let mut deps = mock_dependencies(&[coin(1u128, "uluna".to_string())]);
+    deps = init(deps);
+
+    let msg = ExecuteMsg::AddNativeTokenDecimals {
+        denom: "uluna".to_string(),
+        decimals: 6u8,
+    };
+
+    let info = mock_info("addr0000", &[]);
+
+    assert_eq!(
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap(),
+        Response::new().add_attributes(vec![
+            ("action", "add_allow_native_token"),
+            ("denom", "uluna"),
+            ("decimals", "6"),
+        ])
+    );
+
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::NativeTokenDecimals {
+            denom: "uluna".to_string(),
+        },
+    )
+    .unwrap();
+    let res: NativeTokenDecimalsResponse = from_binary(&res).unwrap();
+    assert_eq!(6u8, res.decimals)
+}
+
+#[test]
+fn failed_add_allow_native_token_with_non_admin() {
+    let mut deps = mock_dependencies(&[coin(1u128, "uluna".to_string())]);
+    deps = init(deps);
+
+    let msg = ExecuteMsg::AddNativeTokenDecimals {
+        denom: "uluna".to_string(),
+        decimals: 6u8,
+    };
+
+    let info = mock_info("noadmin", &[]);
+
+    assert_eq!(
+        execute(deps.as_mut(), mock_env(), info, msg),
+        Err(StdError::generic_err("unauthorized"))
+    );
+}
+
+#[test]
+fn failed_add_allow_native_token_with_zero_factory_balance() {
+    let mut deps = mock_dependencies(&[coin(0u128, "uluna".to_string())]);
+    deps = init(deps);
+
+    let msg = ExecuteMsg::AddNativeTokenDecimals {
+        denom: "uluna".to_string(),
+        decimals: 6u8,
+    };
+
+    let info = mock_info("addr0000", &[]);
+
+    assert_eq!(
+        execute(deps.as_mut(), mock_env(), info, msg),
+        Err(StdError::generic_err(
+            "a balance greater than zero is required by the factory for verification",
+        ))
+    );
+}
+
+#[test]
+fn append_add_allow_native_token_with_already_exist_token() {
+    let mut deps = mock_dependencies(&[coin(1u128, "uluna".to_string())]);
+    deps = init(deps);
+
+    let msg = ExecuteMsg::AddNativeTokenDecimals {
+        denom: "uluna".to_string(),
+
+        decimals: 6u8,
+    };
+
+    let info = mock_info("addr0000", &[]);
+
+    execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::NativeTokenDecimals {
+            denom: "uluna".to_string(),
+        },
+    )
+    .unwrap();
+    let res: NativeTokenDecimalsResponse = from_binary(&res).unwrap();
+    assert_eq!(6u8, res.decimals);
+
+    let msg = ExecuteMsg::AddNativeTokenDecimals {
+        denom: "uluna".to_string(),
+        decimals: 7u8,
+    };
+
+    execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::NativeTokenDecimals {
+            denom: "uluna".to_string(),
+        },
+    )
+    .unwrap();
+    let res: NativeTokenDecimalsResponse = from_binary(&res).unwrap();
+    assert_eq!(7u8, res.decimals)
+}
+
+#[test]
+fn normal_migrate_pair() {
+    let mut deps = mock_dependencies(&[coin(1u128, "uluna".to_string())]);
+    deps = init(deps);
+
+    let msg = ExecuteMsg::MigratePair {
+        code_id: Some(123u64),
+        contract: "contract0000".to_string(),
+    };
+
+    let info = mock_info("addr0000", &[]);
+
+    assert_eq!(
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap(),
+        Response::new().add_message(CosmosMsg::Wasm(WasmMsg::Migrate {
+            contract_addr: "contract0000".to_string(),
+            new_code_id: 123u64,
+            msg: to_binary(&PairMigrateMsg {
+                update_liquidity_token: false,
+                liquidity_token_code_id: None,
+            })
+            .unwrap(),
+        })),
+    );
+}
+
+#[test]
+fn normal_migrate_pair_with_none_code_id_will_config_code_id() {
+    let mut deps = mock_dependencies(&[coin(1u128, "uluna".to_string())]);
+    deps = init(deps);
+
+    let msg = ExecuteMsg::MigratePair {
+        code_id: None,
+        contract: "contract0000".to_string(),
+    };
+
+    let info = mock_info("addr0000", &[]);
+
+    assert_eq!(
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap(),
+        Response::new().add_message(CosmosMsg::Wasm(WasmMsg::Migrate {
+            contract_addr: "contract0000".to_string(),
+            new_code_id: 321u64,
+            msg: to_binary(&PairMigrateMsg {
+                update_liquidity_token: false,
+                liquidity_token_code_id: None,
+            })
+            .unwrap(),
+        })),
+    );
+}
+
+#[test]
+fn failed_migrate_pair_with_no_admin() {
+    let mut deps = mock_dependencies(&[coin(1u128, "uluna".to_string())]);
+    deps = init(deps);
+
+    let msg = ExecuteMsg::MigratePair {
+        code_id: None,
+        contract: "contract0000".to_string(),
+    };
+
+    let info = mock_info("noadmin", &[]);
+
+    assert_eq!(
+        execute(deps.as_mut(), mock_env(), info, msg),
+        Err(StdError::generic_err("unauthorized")),
+    );
+}
+
+#[test]
+fn migrate_pairs() {
+    let mut deps = mock_dependencies(&[coin(1u128, "uluna".to_string())]);
+    deps = init(deps);
+
+    let native = |denom: &str| AssetInfo::NativeToken {
+        denom: denom.to_string(),
+    };
+    // the keys are ordered ukrw-uluna, ukrw-uusd, uluna-uusd
+    let pairs = [
+        [native("ukrw"), native("uluna")],
+        [native("ukrw"), native("uusd")],
+        [native("uluna"), native("uusd")],
+    ];
+    for (i, asset_infos) in pairs.iter().enumerate() {
+        let raw_infos = [
+            asset_infos[0].to_raw(deps.as_ref().api).unwrap(),
+            asset_infos[1].to_raw(deps.as_ref().api).unwrap(),
+        ];
+        let pair_info = PairInfoRaw {
+            asset_infos: raw_infos.clone(),
+            contract_addr: deps
+                .as_ref()
+                .api
+                .addr_canonicalize(&format!("pair000{}", i))
+                .unwrap(),
+            liquidity_token: deps
+                .as_ref()
+                .api
+                .addr_canonicalize(&format!("liquidity000{}", i))
+                .unwrap(),
+            asset_decimals: [6u8, 6u8],
+            created_at: None,
+        };
+        PAIRS
+            .save(deps.as_mut().storage, &pair_key(&raw_infos), &pair_info)
+            .unwrap();
+    }
+    deps.querier.with_contract_admins(&[
+        ("pair0000", Some(MOCK_CONTRACT_ADDR.to_string())),
+        ("pair0001", Some("someone".to_string())),
+        ("pair0002", None),
+    ]);
+
+    let migrate_msg = to_binary(&PairMigrateMsg {
+        update_liquidity_token: false,
+        liquidity_token_code_id: None,
+    })
+    .unwrap();
+    let msg = ExecuteMsg::MigratePairs {
+        new_code_id: 456u64,
+        start_after: None,
+        limit: 2,
+        msg: migrate_msg.clone(),
+    };
+
+    let info = mock_info("noadmin", &[]);
+    assert_eq!(
+        execute(deps.as_mut(), mock_env(), info, msg.clone()),
+        Err(StdError::generic_err("unauthorized")),
+    );
+
+    // only the pair administrated by the factory is migrated
+    let info = mock_info("addr0000", &[]);
+    let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Wasm(WasmMsg::Migrate {
+            contract_addr: "pair0000".to_string(),
+            new_code_id: 456u64,
+            msg: migrate_msg.clone(),
+        }))]
+    );
+    assert_eq!(
+        res.attributes,
+        vec![
+            attr("action", "migrate_pairs"),
+            attr("migrated", "1"),
+            attr("skipped", "pair0001"),
+            attr(
+                "last_pair",
+                String::from_utf8(to_vec(&pairs[1]).unwrap()).unwrap()
+            ),
+        ]
+    );
+
+    // continue from the last processed pair
+    let msg = ExecuteMsg::MigratePairs {
+        new_code_id: 456u64,
+        start_after: Some(pairs[1].clone()),
+        limit: 2,
+        msg: migrate_msg.clone(),
+    };
+    let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+    assert!(res.messages.is_empty());
+    assert_eq!(
+        res.attributes,
+        vec![
+            attr("action", "migrate_pairs"),
+            attr("migrated", "0"),
+            attr("skipped", "pair0002"),
+            attr(
+                "last_pair",
+                String::from_utf8(to_vec(&pairs[2]).unwrap()).unwrap()
+            ),
+        ]
+    );
+
+    // nothing is left to migrate
+    let msg = ExecuteMsg::MigratePairs {
+        new_code_id: 456u64,
+        start_after: Some(pairs[2].clone()),
+        limit: 2,
+        msg: migrate_msg,
+    };
+    let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    assert!(res.messages.is_empty());
+    assert_eq!(res.attributes[1], attr("migrated", "0"));
+    assert_eq!(res.attributes[3], attr("last_pair", ""));
+}
+
+#[test]
+fn update_pairs_admin() {
+    let mut deps = mock_dependencies(&[]);
+    deps = init(deps);
+
+    let native = |denom: &str| AssetInfo::NativeToken {
+        denom: denom.to_string(),
+    };
+    // the keys are ordered ukrw-uluna, ukrw-uusd, uluna-uusd
+    let pairs = [
+        [native("ukrw"), native("uluna")],
+        [native("ukrw"), native("uusd")],
+        [native("uluna"), native("uusd")],
+    ];
+    for (i, asset_infos) in pairs.iter().enumerate() {
+        let raw_infos = [
+            asset_infos[0].to_raw(deps.as_ref().api).unwrap(),
+            asset_infos[1].to_raw(deps.as_ref().api).unwrap(),
+        ];
+        let pair_info = PairInfoRaw {
+            asset_infos: raw_infos.clone(),
+            contract_addr: deps
+                .as_ref()
+                .api
+                .addr_canonicalize(&format!("pair000{}", i))
+                .unwrap(),
+            liquidity_token: deps
+                .as_ref()
+                .api
+                .addr_canonicalize(&format!("liquidity000{}", i))
+                .unwrap(),
+            asset_decimals: [6u8, 6u8],
+            created_at: None,
+        };
+        PAIRS
+            .save(deps.as_mut().storage, &pair_key(&raw_infos), &pair_info)
+            .unwrap();
+    }
+    deps.querier.with_contract_admins(&[
+        ("pair0000", Some(MOCK_CONTRACT_ADDR.to_string())),
+        ("pair0001", Some("someone".to_string())),
+        ("pair0002", Some(MOCK_CONTRACT_ADDR.to_string())),
+    ]);
+
+    let msg = ExecuteMsg::UpdatePairsAdmin {
+        new_admin: "factory0001".to_string(),
+        start_after: None,
+        limit: 2,
+    };
+    assert_eq!(
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("noadmin", &[]),
+            msg.clone()
+        ),
+        Err(StdError::generic_err("unauthorized")),
+    );
+
+    // the pair administrated by someone else is skipped
+    let info = mock_info("addr0000", &[]);
+    let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Wasm(WasmMsg::UpdateAdmin {
+            contract_addr: "pair0000".to_string(),
+            admin: "factory0001".to_string(),
+        }))]
+    );
+    assert_eq!(
+        res.attributes,
+        vec![
+            attr("action", "update_pairs_admin"),
+            attr("new_admin", "factory0001"),
+            attr("updated", "1"),
+            attr("skipped", "pair0001"),
+            attr(
+                "last_pair",
+                String::from_utf8(to_vec(&pairs[1]).unwrap()).unwrap()
+            ),
+        ]
+    );
+
+    // continue from the last processed pair
+    let msg = ExecuteMsg::UpdatePairsAdmin {
+        new_admin: "factory0001".to_string(),
+        start_after: Some(pairs[1].clone()),
+        limit: 2,
+    };
+    let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Wasm(WasmMsg::UpdateAdmin {
+            contract_addr: "pair0002".to_string(),
+            admin: "factory0001".to_string(),
+        }))]
+    );
+    assert_eq!(res.attributes[2], attr("updated", "1"));
+    assert_eq!(res.attributes[3], attr("skipped", ""));
+    assert_eq!(
+        res.attributes[4],
+        attr(
+            "last_pair",
+            String::from_utf8(to_vec(&pairs[2]).unwrap()).unwrap()
+        )
+    );
+
+    // nothing is left to update
+    let msg = ExecuteMsg::UpdatePairsAdmin {
+        new_admin: "factory0001".to_string(),
+        start_after: Some(pairs[2].clone()),
+        limit: 2,
+    };
+    let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    assert!(res.messages.is_empty());
+    assert_eq!(res.attributes[4], attr("last_pair", ""));
+}
+
+#[test]
+fn update_pair_decimals() {
+    let mut deps = mock_dependencies(&[coin(1u128, "uluna".to_string())]);
+    deps = init(deps);
+
+    let asset_infos = [
+        AssetInfo::NativeToken {
+            denom: "uusd".to_string(),
+        },
+        AssetInfo::Token {
+            contract_addr: "asset0000".to_string(),
+        },
+    ];
+    let raw_infos = [
+        asset_infos[0].to_raw(deps.as_ref().api).unwrap(),
+        asset_infos[1].to_raw(deps.as_ref().api).unwrap(),
+    ];
+
+    // a pair stored before the decimals were recorded
+    let pair_key = pair_key(&raw_infos);
+    #[derive(Serialize)]
+    struct LegacyPairInfoRaw {
+        asset_infos: [AssetInfoRaw; 2],
+        contract_addr: CanonicalAddr,
+        liquidity_token: CanonicalAddr,
+    }
+    let legacy_pair_info = LegacyPairInfoRaw {
+        asset_infos: raw_infos.clone(),
+        contract_addr: deps.as_ref().api.addr_canonicalize("pair0000").unwrap(),
+        liquidity_token: deps
+            .as_ref()
+            .api
+            .addr_canonicalize("liquidity0000")
+            .unwrap(),
+    };
+    deps.storage
+        .set(&PAIRS.key(&pair_key), &to_vec(&legacy_pair_info).unwrap());
+
+    let query_pair = |deps: &OwnedDeps<MockStorage, MockApi, WasmMockQuerier, TerraQuery>| {
+        from_binary::<PairInfo>(
+            &query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::Pair {
+                    asset_infos: asset_infos.clone(),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap()
+    };
+    assert_eq!(query_pair(&deps).asset_decimals, [6u8, 6u8]);
+
+    // the decimals are given in the order of the asset infos of the message
+    let msg = ExecuteMsg::UpdatePairDecimals {
+        asset_infos: [asset_infos[1].clone(), asset_infos[0].clone()],
+        asset_decimals: [8u8, 6u8],
+    };
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("noadmin", &[]),
+        msg.clone(),
+    );
+    assert_eq!(res, Err(StdError::generic_err("unauthorized")));
+
+    let res = execute(deps.as_mut(), mock_env(), mock_info("addr0000", &[]), msg).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "pair0000".to_string(),
+            msg: to_binary(&PairExecuteMsg::UpdateAssetDecimals {
+                asset_decimals: [6u8, 8u8],
+            })
+            .unwrap(),
+            funds: vec![],
+        }))]
+    );
+    assert_eq!(query_pair(&deps).asset_decimals, [6u8, 8u8]);
+
+    // decimals are bounded
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        ExecuteMsg::UpdatePairDecimals {
+            asset_infos,
+            asset_decimals: [19u8, 6u8],
+        },
+    );
+    assert_eq!(
+        res,
+        Err(StdError::generic_err("asset decimals must not exceed 18"))
+    );
+}
+
+#[test]
+fn deregister_pair() {
+    let mut deps = mock_dependencies(&[coin(1u128, "uluna".to_string())]);
+    deps = init(deps);
+
+    let native = |denom: &str| AssetInfo::NativeToken {
+        denom: denom.to_string(),
+    };
+    let pairs = [
+        [native("uluna"), native("uusd")],
+        [native("uluna"), native("ukrw")],
+        [native("ukrw"), native("uusd")],
+    ];
+    for (i, asset_infos) in pairs.iter().enumerate() {
+        let raw_infos = [
+            asset_infos[0].to_raw(deps.as_ref().api).unwrap(),
+            asset_infos[1].to_raw(deps.as_ref().api).unwrap(),
+        ];
+        let pair_info = PairInfoRaw {
+            asset_infos: raw_infos.clone(),
+            contract_addr: deps
+                .as_ref()
+                .api
+                .addr_canonicalize(&format!("pair000{}", i))
+                .unwrap(),
+            liquidity_token: deps
+                .as_ref()
+                .api
+                .addr_canonicalize(&format!("liquidity000{}", i))
+                .unwrap(),
+            asset_decimals: [6u8, 6u8],
+            created_at: None,
+        };
+        PAIRS
+            .save(deps.as_mut().storage, &pair_key(&raw_infos), &pair_info)
+            .unwrap();
+    }
+    let query_pairs = |deps: &OwnedDeps<MockStorage, MockApi, WasmMockQuerier, TerraQuery>,
+                       start_after: Option<[AssetInfo; 2]>| {
+        from_binary::<PairsResponse>(
+            &query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::Pairs {
+                    start_after,
+                    limit: Some(1),
+                    order: None,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap()
+        .pairs
+        .into_iter()
+        .map(|pair| pair.contract_addr)
+        .collect::<Vec<String>>()
+    };
+    // the keys are ordered ukrw-uluna, ukrw-uusd, uluna-uusd
+    assert_eq!(
+        query_pairs(&deps, Some(pairs[1].clone())),
+        vec!["pair0002".to_string()]
+    );
+
+    let msg = ExecuteMsg::DeregisterPair {
+        asset_infos: pairs[2].clone(),
+    };
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("noadmin", &[]),
+        msg.clone(),
+    );
+    assert_eq!(res, Err(StdError::generic_err("unauthorized")));
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        msg.clone(),
+    )
+    .unwrap();
+    assert_eq!(
+        res.attributes,
+        vec![
+            attr("action", "deregister_pair"),
+            attr("pair", "ukrw-uusd"),
+            attr("pair_contract_addr", "pair0002"),
+        ]
+    );
+    assert_eq!(
+        res.events,
+        vec![Event::new("terraswap_deregister_pair")
+            .add_attribute("pair", "ukrw-uusd")
+            .add_attribute("pair_contract_addr", "pair0002")]
+    );
+
+    // the pair is no longer found and the listing skips it
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::Pair {
+            asset_infos: pairs[2].clone(),
+        },
+    );
+    assert!(res.is_err());
+    assert_eq!(
+        query_pairs(&deps, Some(pairs[1].clone())),
+        vec!["pair0000".to_string()]
+    );
+
+    let res = execute(deps.as_mut(), mock_env(), mock_info("addr0000", &[]), msg);
+    assert!(res.is_err());
+}
+
+#[test]
+fn create_pair_with_updated_code_ids() {
+    let mut deps = mock_dependencies(&[coin(10u128, "uusd".to_string())]);
+    deps = init(deps);
+    deps.querier
+        .with_terraswap_factory(&[], &[("uusd".to_string(), 6u8)]);
+
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        ExecuteMsg::UpdateConfig {
+            owner: None,
+            pair_code_id: Some(654u64),
+            token_code_id: Some(456u64),
+            max_referral_bps: None,
+            permissioned: None,
+        },
+    )
+    .unwrap();
+
+    let msg = ExecuteMsg::CreatePair {
+        assets: [
+            Asset {
+                info: AssetInfo::NativeToken {
+                    denom: "uusd".to_string(),
+                },
+                amount: Uint128::zero(),
+            },
+            Asset {
+                info: AssetInfo::Token {
+                    contract_addr: "asset0001".to_string(),
+                },
+                amount: Uint128::zero(),
+            },
+        ],
+        allow_unregistered_decimals: None,
+        pair_params: None,
+    };
+    let res = execute(deps.as_mut(), mock_env(), mock_info("addr0000", &[]), msg).unwrap();
+    match &res.messages[0].msg {
+        CosmosMsg::Wasm(WasmMsg::Instantiate2 { code_id, msg, .. }) => {
+            assert_eq!(*code_id, 654u64);
+            let msg: PairInstantiateMsg = from_binary(msg).unwrap();
+            assert_eq!(msg.token_code_id, 456u64);
+        }
+        _ => panic!("Must instantiate the pair"),
+    }
+}
+
+#[test]
+fn asset_denylist() {
+    let mut deps = mock_dependencies(&[]);
+    deps = init(deps);
+    deps.querier.with_token_balances(&[(
+        &"asset0000".to_string(),
+        &[(&MOCK_CONTRACT_ADDR.to_string(), &Uint128::from(100u128))],
+    )]);
+
+    let token = AssetInfo::Token {
+        contract_addr: "asset0000".to_string(),
+    };
+    let native = |denom: &str| AssetInfo::NativeToken {
+        denom: denom.to_string(),
+    };
+    let create_pair = |asset_infos: [AssetInfo; 2]| ExecuteMsg::CreatePair {
+        assets: [
+            Asset {
+                info: asset_infos[0].clone(),
+                amount: Uint128::zero(),
+            },
+            Asset {
+                info: asset_infos[1].clone(),
+                amount: Uint128::zero(),
+            },
+        ],
+        allow_unregistered_decimals: Some(true),
+        pair_params: None,
+    };
+
+    let msg = ExecuteMsg::UpdateAssetDenylist {
+        add: vec![token.clone(), native("uluna")],
+        remove: vec![],
+    };
+    assert_eq!(
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("addr0001", &[]),
+            msg.clone()
+        ),
+        Err(StdError::generic_err("unauthorized"))
+    );
+
+    let info = mock_info("addr0000", &[]);
+    execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+    let res: AssetDenylistResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::AssetDenylist {
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(res.assets.len(), 2);
+    assert!(res.assets.contains(&token) && res.assets.contains(&native("uluna")));
+    let res: AssetDenylistResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::AssetDenylist {
+                start_after: Some(res.assets[0].clone()),
+                limit: None,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(res.assets.len(), 1);
+
+    // either asset of the pair is checked
+    assert_eq!(
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            create_pair([native("uusd"), token.clone()]),
+        ),
+        Err(StdError::generic_err("asset asset0000 is denylisted"))
+    );
+    assert_eq!(
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            create_pair([native("uluna"), native("uusd")]),
+        ),
+        Err(StdError::generic_err("asset uluna is denylisted"))
+    );
+
+    // removing an asset enables the creation again
+    let msg = ExecuteMsg::UpdateAssetDenylist {
+        add: vec![],
+        remove: vec![token.clone()],
+    };
+    execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        create_pair([native("uusd"), token]),
+    )
+    .unwrap();
+    assert_eq!(
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            create_pair([native("uusd"), native("uluna")]),
+        ),
+        Err(StdError::generic_err("asset uluna is denylisted"))
+    );
+}
+
+#[test]
+fn permissioned_pair_creation() {
+    let mut deps = mock_dependencies(&[coin(10u128, "uusd".to_string())]);
+    deps = init(deps);
+    deps.querier
+        .with_terraswap_factory(&[], &[("uusd".to_string(), 6u8)]);
+
+    let create_pair = ExecuteMsg::CreatePair {
+        assets: [
+            Asset {
+                info: AssetInfo::NativeToken {
+                    denom: "uusd".to_string(),
+                },
+                amount: Uint128::zero(),
+            },
+            Asset {
+                info: AssetInfo::Token {
+                    contract_addr: "asset0001".to_string(),
+                },
+                amount: Uint128::zero(),
+            },
+        ],
+        allow_unregistered_decimals: None,
+        pair_params: None,
+    };
+    let permissioned = |permissioned: bool| ExecuteMsg::UpdateConfig {
+        owner: None,
+        token_code_id: None,
+        pair_code_id: None,
+        max_referral_bps: None,
+        permissioned: Some(permissioned),
+    };
+    let query_creators = |deps: &OwnedDeps<MockStorage, MockApi, WasmMockQuerier, TerraQuery>,
+                          start_after: Option<&str>| {
+        from_binary::<CreatorsResponse>(
+            &query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::Creators {
+                    start_after: start_after.map(|s| s.to_string()),
+                    limit: Some(2),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap()
+        .creators
+    };
+
+    // open by default
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0001", &[]),
+        create_pair.clone(),
+    )
+    .unwrap();
+
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        permissioned(true),
+    )
+    .unwrap();
+    let res = query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap();
+    assert!(from_binary::<ConfigResponse>(&res).unwrap().permissioned);
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0001", &[]),
+        create_pair.clone(),
+    );
+    assert_eq!(res, Err(StdError::generic_err("unauthorized")));
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        create_pair.clone(),
+    )
+    .unwrap();
+
+    // only the owner updates the creators
+    let update_creators = ExecuteMsg::UpdateCreators {
+        add: vec![
+            "addr0003".to_string(),
+            "addr0001".to_string(),
+            "addr0002".to_string(),
+        ],
+        remove: vec![],
+    };
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0001", &[]),
+        update_creators.clone(),
+    );
+    assert_eq!(res, Err(StdError::generic_err("unauthorized")));
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        update_creators,
+    )
+    .unwrap();
+    assert_eq!(
+        query_creators(&deps, None),
+        vec!["addr0001".to_string(), "addr0002".to_string()]
+    );
+    assert_eq!(
+        query_creators(&deps, Some("addr0002")),
+        vec!["addr0003".to_string()]
+    );
+
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0001", &[]),
+        create_pair.clone(),
+    )
+    .unwrap();
+
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        ExecuteMsg::UpdateCreators {
+            add: vec![],
+            remove: vec!["addr0001".to_string()],
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        query_creators(&deps, None),
+        vec!["addr0002".to_string(), "addr0003".to_string()]
+    );
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0001", &[]),
+        create_pair.clone(),
+    );
+    assert_eq!(res, Err(StdError::generic_err("unauthorized")));
+
+    // turning it off opens creation to everyone again
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        permissioned(false),
+    )
+    .unwrap();
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0001", &[]),
+        create_pair,
+    )
+    .unwrap();
+}
+
+#[test]
+fn pair_creation_fee() {
+    let mut deps = mock_dependencies(&[coin(10u128, "uusd".to_string())]);
+    deps = init(deps);
+    deps.querier
+        .with_terraswap_factory(&[], &[("uusd".to_string(), 6u8)]);
+
+    let uusd = |amount: u128| Asset {
+        info: AssetInfo::NativeToken {
+            denom: "uusd".to_string(),
+        },
+        amount: Uint128::from(amount),
+    };
+    let create_pair = |provided: u128| ExecuteMsg::CreatePair {
+        assets: [
+            uusd(provided),
+            Asset {
+                info: AssetInfo::Token {
+                    contract_addr: "asset0001".to_string(),
+                },
+                amount: Uint128::zero(),
+            },
+        ],
+        allow_unregistered_decimals: None,
+        pair_params: None,
+    };
+    let update_fee = |pair_creation_fee: Option<Asset>, fee_collector: Option<&str>| {
+        ExecuteMsg::UpdatePairCreationFee {
+            pair_creation_fee,
+            fee_collector: fee_collector.map(|s| s.to_string()),
+        }
+    };
+    let bank_send = |to_address: &str, amount: u128| {
+        SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+            to_address: to_address.to_string(),
+            amount: coins(amount, "uusd"),
+        }))
+    };
+
+    // only the owner sets a native fee with a collector
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0001", &[]),
+        update_fee(Some(uusd(100)), Some("collector0000")),
+    );
+    assert_eq!(res, Err(StdError::generic_err("unauthorized")));
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        update_fee(Some(uusd(100)), None),
+    );
+    assert_eq!(
+        res,
+        Err(StdError::generic_err(
+            "pair_creation_fee requires a fee_collector"
+        ))
+    );
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        update_fee(
+            Some(Asset {
+                info: AssetInfo::Token {
+                    contract_addr: "asset0001".to_string(),
+                },
+                amount: Uint128::from(100u128),
+            }),
+            Some("collector0000"),
+        ),
+    );
+    assert_eq!(
+        res,
+        Err(StdError::generic_err(
+            "pair_creation_fee must be a native token"
+        ))
+    );
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        update_fee(Some(uusd(100)), Some("collector0000")),
+    )
+    .unwrap();
+    let res = query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap();
+    let config: ConfigResponse = from_binary(&res).unwrap();
+    assert_eq!(config.pair_creation_fee, Some(uusd(100)));
+    assert_eq!(config.fee_collector, Some("collector0000".to_string()));
+
+    // underpayment
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0001", &coins(99, "uusd")),
+        create_pair(0),
+    );
+    assert_eq!(
+        res,
+        Err(StdError::generic_err(
+            "pair creation fee of 100uusd required"
+        ))
+    );
+
+    // exact fee
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0001", &coins(100, "uusd")),
+        create_pair(0),
+    )
+    .unwrap();
+    assert_eq!(res.messages.len(), 2);
+    assert_eq!(res.messages[0], bank_send("collector0000", 100));
+
+    // overpayment is refunded
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0001", &coins(150, "uusd")),
+        create_pair(0),
+    )
+    .unwrap();
+    assert_eq!(res.messages.len(), 3);
+    assert_eq!(res.messages[0], bank_send("collector0000", 100));
+    assert_eq!(res.messages[1], bank_send("addr0001", 50));
+
+    // the native amount provided to the pair is not part of the fee
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0001", &coins(1_000, "uusd")),
+        create_pair(1_000),
+    );
+    assert_eq!(
+        res,
+        Err(StdError::generic_err(
+            "pair creation fee of 100uusd required"
+        ))
+    );
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0001", &coins(1_100, "uusd")),
+        create_pair(1_000),
+    )
+    .unwrap();
+    assert_eq!(res.messages.len(), 2);
+    assert_eq!(res.messages[0], bank_send("collector0000", 100));
+
+    // zero-fee mode
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        update_fee(None, None),
+    )
+    .unwrap();
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0001", &[]),
+        create_pair(0),
+    )
+    .unwrap();
+    assert_eq!(res.messages.len(), 1);
+}
+
+#[test]
+fn pairs_by_asset() {
+    let mut deps = mock_dependencies(&[]);
+    deps = init(deps);
+
+    let native = |denom: &str| AssetInfo::NativeToken {
+        denom: denom.to_string(),
+    };
+    // pairs registered before the index, in key order
+    let pairs = [
+        [native("ukrw"), native("uluna")],
+        [native("ukrw"), native("uusd")],
+        [native("uluna"), native("umnt")],
+        [native("uluna"), native("uusd")],
+    ];
+    for (i, asset_infos) in pairs.iter().enumerate() {
+        let raw_infos = [
+            asset_infos[0].to_raw(deps.as_ref().api).unwrap(),
+            asset_infos[1].to_raw(deps.as_ref().api).unwrap(),
+        ];
+        let pair_info = PairInfoRaw {
+            asset_infos: raw_infos.clone(),
+            contract_addr: deps
+                .as_ref()
+                .api
+                .addr_canonicalize(&format!("pair000{}", i))
+                .unwrap(),
+            liquidity_token: deps
+                .as_ref()
+                .api
+                .addr_canonicalize(&format!("liquidity000{}", i))
+                .unwrap(),
+            asset_decimals: [6u8, 6u8],
+            created_at: None,
+        };
+        PAIRS
+            .save(deps.as_mut().storage, &pair_key(&raw_infos), &pair_info)
+            .unwrap();
+    }
+    let query_pairs_by_asset =
+        |deps: &OwnedDeps<MockStorage, MockApi, WasmMockQuerier, TerraQuery>,
+         start_after: Option<[AssetInfo; 2]>,
+         limit: Option<u32>| {
+            from_binary::<PairsResponse>(
+                &query(
+                    deps.as_ref(),
+                    mock_env(),
+                    QueryMsg::PairsByAsset {
+                        asset_info: native("uluna"),
+                        start_after,
+                        limit,
+                    },
+                )
+                .unwrap(),
+            )
+            .unwrap()
+            .pairs
+            .into_iter()
+            .map(|pair| pair.contract_addr)
+            .collect::<Vec<String>>()
+        };
+    let query_pair_by_lp_token =
+        |deps: &OwnedDeps<MockStorage, MockApi, WasmMockQuerier, TerraQuery>| {
+            query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::PairByLpToken {
+                    lp_token: "liquidity0003".to_string(),
+                },
+            )
+            .map(|res| from_binary::<PairInfo>(&res).unwrap().contract_addr)
+        };
+    assert!(query_pairs_by_asset(&deps, None, None).is_empty());
+    assert!(query_pair_by_lp_token(&deps).is_err());
+
+    // only the owner builds the index, in passes
+    let build = |limit: u32| ExecuteMsg::BuildAssetIndex { limit: Some(limit) };
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0001", &[]),
+        build(3),
+    );
+    assert_eq!(res, Err(StdError::generic_err("unauthorized")));
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        build(3),
+    )
+    .unwrap();
+    assert_eq!(res.attributes[1], attr("indexed", "3"));
+    assert_eq!(res.attributes[2], attr("complete", "false"));
+    assert_eq!(
+        query_pairs_by_asset(&deps, None, None),
+        vec!["pair0000".to_string(), "pair0002".to_string()]
+    );
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        build(3),
+    )
+    .unwrap();
+    assert_eq!(res.attributes[1], attr("indexed", "1"));
+    assert_eq!(res.attributes[2], attr("complete", "true"));
+    assert_eq!(query_pair_by_lp_token(&deps), Ok("pair0003".to_string()));
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        build(3),
+    )
+    .unwrap();
+    assert_eq!(res.attributes[1], attr("indexed", "0"));
+
+    // the liquidity tokens are backfilled on their own cursor,
+    // for factories whose asset index completed before the liquidity token index existed
+    for i in 0..4 {
+        let lp_token = deps
+            .as_ref()
+            .api
+            .addr_canonicalize(&format!("liquidity000{}", i))
+            .unwrap();
+        LP_TOKEN_PAIRS.remove(deps.as_mut().storage, lp_token.as_slice());
+    }
+    LP_TOKEN_INDEX_CURSOR.remove(deps.as_mut().storage);
+    assert!(query_pair_by_lp_token(&deps).is_err());
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        build(4),
+    )
+    .unwrap();
+    assert_eq!(res.attributes[1], attr("indexed", "4"));
+    assert_eq!(res.attributes[2], attr("complete", "false"));
+    assert_eq!(query_pair_by_lp_token(&deps), Ok("pair0003".to_string()));
+
+    // paginated by the pair asset infos
+    assert_eq!(
+        query_pairs_by_asset(&deps, None, Some(2)),
+        vec!["pair0000".to_string(), "pair0002".to_string()]
+    );
+    assert_eq!(
+        query_pairs_by_asset(&deps, Some(pairs[2].clone()), Some(2)),
+        vec!["pair0003".to_string()]
+    );
+
+    // a deregistered pair leaves the index
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        ExecuteMsg::DeregisterPair {
+            asset_infos: pairs[2].clone(),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        query_pairs_by_asset(&deps, Some(pairs[0].clone()), None),
+        vec!["pair0003".to_string()]
     );
 }
@@ -1,29 +1,48 @@
 use crate::error::ContractError;
 use crate::response::MsgInstantiateContractResponse;
-use crate::state::PAIR_INFO;
+use crate::state::{
+    accrue_protocol_fee, add_deposit, read_commit_config, read_deposits, read_fee_config,
+    read_pool_status, read_protocol_fees, read_reserves, read_unpooled_balances,
+    save_price_accumulators, take_deposits, track_commission, update_price_accumulators,
+    Commitment, LiquidityTokenSnapshot, PriceAccumulators, COMMITMENTS, COMMIT_CONFIG, FACTORY,
+    FEE_CONFIG, FEE_TRACKER, LIQUIDITY_TOKEN_SNAPSHOT, MAX_SPREAD_CAP, MAX_TRADE_RATIO,
+    OBSERVATION_SLOTS, PAIR_ADMIN, PAIR_INFO, POOL_STATUS, PRICE_ACCUMULATORS, PRICE_OBSERVATIONS,
+    PROTOCOL_FEES, RESERVES,
+};
 
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 
 use cosmwasm_std::{
     from_binary, to_binary, Addr, Binary, CanonicalAddr, CosmosMsg, Decimal, Decimal256, Deps,
-    DepsMut, Env, MessageInfo, Reply, ReplyOn, Response, StdError, StdResult, SubMsg, Uint128,
-    Uint256, WasmMsg,
+    DepsMut, Empty, Env, Event, MessageInfo, QuerierWrapper, Reply, ReplyOn, Response, StdError,
+    StdResult, SubMsg, Uint128, Uint256, WasmMsg,
 };
 
 use classic_bindings::{TerraMsg, TerraQuery};
 
-use classic_terraswap::asset::{Asset, AssetInfo, PairInfo, PairInfoRaw};
+use classic_terraswap::asset::{Asset, AssetInfo, AssetInfoRaw, PairInfo, PairInfoRaw};
+use classic_terraswap::factory::{
+    ConfigResponse as FactoryConfigResponse, QueryMsg as FactoryQueryMsg, MAX_ASSET_DECIMALS,
+};
 use classic_terraswap::pair::{
-    Cw20HookMsg, ExecuteMsg, InstantiateMsg, MigrateMsg, PoolResponse, QueryMsg,
-    ReverseSimulationResponse, SimulationResponse,
+    compute_offer_amount, compute_swap, BeliefPriceDirection, CommitConfig, CommitSwapParams,
+    CommitmentResponse, ConfigResponse, CumulativePricesResponse, Cw20HookMsg, ExecuteMsg,
+    FeeConfig, FeeStatsResponse, InstantiateMsg, MigrateMsg, ObservedTwapResponse,
+    OfferComputation, PoolResponse, PoolStatus, QueryMsg, Referral, ReverseSimulationBatchResponse,
+    ReverseSimulationResponse, SimulateProvideLiquidityResponse,
+    SimulateProvideLiquiditySingleResponse, SimulationBatchResponse, SimulationResponse,
+    SwapComputation,
+};
+use classic_terraswap::querier::{compute_reverse_tax, compute_tax, query_token_info};
+use classic_terraswap::token::{
+    liquidity_token_symbol, InstantiateMarketingInfo, InstantiateMsg as TokenInstantiateMsg,
 };
-use classic_terraswap::querier::query_token_info;
-use classic_terraswap::token::InstantiateMsg as TokenInstantiateMsg;
 use classic_terraswap::util::{assert_deadline, migrate_version};
 use cw2::set_contract_version;
-use cw20::{Cw20ExecuteMsg, Cw20ReceiveMsg, MinterResponse};
+use cw20::{Cw20ExecuteMsg, Cw20QueryMsg, Cw20ReceiveMsg, Logo, MinterResponse};
 use protobuf::Message;
+use sha2::{Digest, Sha256};
 use std::cmp::Ordering;
 use std::convert::TryInto;
 use std::ops::Mul;
@@ -33,21 +52,54 @@ const CONTRACT_NAME: &str = "crates.io:terraswap-pair";
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
 const INSTANTIATE_REPLY_ID: u64 = 1;
+const LIQUIDITY_TOKEN_MIGRATE_REPLY_ID: u64 = 2;
 
-/// Commission rate == 0.3%
-const COMMISSION_RATE: u64 = 3;
+/// Total commission rate <= 1%
+const MAX_TOTAL_FEE_RATE: u64 = 1;
 
 const MINIMUM_LIQUIDITY_AMOUNT: u128 = 1_000;
 
+/// The most amounts a batch simulation takes
+const MAX_SIMULATION_BATCH: usize = 50;
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
     deps: DepsMut<TerraQuery>,
     env: Env,
-    _info: MessageInfo,
+    info: MessageInfo,
     msg: InstantiateMsg,
-) -> StdResult<Response<TerraMsg>> {
+) -> Result<Response<TerraMsg>, ContractError> {
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
 
+    let default_fee_config = FeeConfig::default();
+    let fee_config = FeeConfig {
+        lp_fee_rate: msg.lp_fee_rate.unwrap_or(default_fee_config.lp_fee_rate),
+        protocol_fee_rate: msg
+            .protocol_fee_rate
+            .unwrap_or(default_fee_config.protocol_fee_rate),
+        protocol_fee_collector: msg
+            .protocol_fee_collector
+            .map(|collector| deps.api.addr_validate(&collector))
+            .transpose()?,
+        fee_tiers: vec![],
+    };
+    assert_fee_config(&fee_config)?;
+
+    FEE_CONFIG.save(deps.storage, &fee_config)?;
+    // the instantiating factory's owner manages the fee config
+    FACTORY.save(deps.storage, &info.sender)?;
+    if let Some(pair_admin) = msg.pair_admin {
+        PAIR_ADMIN.save(deps.storage, &deps.api.addr_validate(&pair_admin)?)?;
+    }
+    if let Some(max_spread_cap) = msg.max_spread_cap {
+        MAX_SPREAD_CAP.save(deps.storage, &max_spread_cap)?;
+    }
+    save_price_accumulators(
+        deps.storage,
+        &PriceAccumulators::new(env.block.time.seconds()),
+    )?;
+    RESERVES.save(deps.storage, &[Uint128::zero(), Uint128::zero()])?;
+
     let pair_info: &PairInfoRaw = &PairInfoRaw {
         contract_addr: deps.api.addr_canonicalize(env.contract.address.as_str())?,
         liquidity_token: CanonicalAddr::from(vec![]),
@@ -56,24 +108,35 @@ pub fn instantiate(
             msg.asset_infos[1].to_raw(deps.api)?,
         ],
         asset_decimals: msg.asset_decimals,
+        created_at: None,
     };
 
     PAIR_INFO.save(deps.storage, pair_info)?;
 
+    let symbol = liquidity_token_symbol_of(&deps.querier, &msg.asset_infos)?;
+
     Ok(Response::new().add_submessage(SubMsg {
         // Create LP token
         msg: WasmMsg::Instantiate {
-            admin: None,
+            // the pair can migrate its liquidity token
+            admin: Some(env.contract.address.to_string()),
             code_id: msg.token_code_id,
             msg: to_binary(&TokenInstantiateMsg {
                 name: "terraswap liquidity token".to_string(),
-                symbol: "uLP".to_string(),
+                symbol,
                 decimals: 6,
                 initial_balances: vec![],
                 mint: Some(MinterResponse {
                     minter: env.contract.address.to_string(),
                     cap: None,
                 }),
+                // the pair markets its liquidity token until it hands that over
+                marketing: Some(InstantiateMarketingInfo {
+                    project: None,
+                    description: None,
+                    marketing: Some(env.contract.address.to_string()),
+                    logo: None,
+                }),
             })?,
             funds: vec![],
             label: "lp".to_string(),
@@ -99,6 +162,7 @@ pub fn execute(
             receiver,
             deadline,
             slippage_tolerance,
+            min_lp_to_receive,
         } => provide_liquidity(
             deps,
             env,
@@ -107,18 +171,52 @@ pub fn execute(
             receiver,
             deadline,
             slippage_tolerance,
+            min_lp_to_receive,
+            false,
         ),
+        ExecuteMsg::ProvideLiquidityFromDeposits {
+            slippage_tolerance,
+            min_lp_to_receive,
+        } => {
+            provide_liquidity_from_deposits(deps, env, info, slippage_tolerance, min_lp_to_receive)
+        }
+        ExecuteMsg::RefundDeposits {} => refund_deposits(deps, info),
+        ExecuteMsg::RescueFunds {
+            asset_info,
+            amount,
+            to,
+        } => rescue_funds(deps, info, asset_info, amount, to),
+        ExecuteMsg::CommitSwap { hash } => commit_swap(deps, env, info, hash),
+        ExecuteMsg::RevealSwap { params, salt } => {
+            if !params.offer_asset.is_native_token() {
+                return Err(ContractError::Unauthorized {});
+            }
+
+            assert_sent_funds(&info, std::slice::from_ref(&params.offer_asset))?;
+
+            reveal_swap(deps, env, info.sender, params, salt)
+        }
+        ExecuteMsg::CancelCommitment {} => cancel_commitment(deps, info),
+        ExecuteMsg::UpdateCommitConfig {
+            min_delay_blocks,
+            expiry_blocks,
+        } => update_commit_config(deps, info, min_delay_blocks, expiry_blocks),
         ExecuteMsg::Swap {
             offer_asset,
             belief_price,
+            belief_price_direction,
             max_spread,
             to,
             deadline,
+            to_msg,
+            referral,
         } => {
             if !offer_asset.is_native_token() {
                 return Err(ContractError::Unauthorized {});
             }
 
+            assert_sent_funds(&info, std::slice::from_ref(&offer_asset))?;
+
             let to_addr = if let Some(to_addr) = to {
                 Some(deps.api.addr_validate(&to_addr)?)
             } else {
@@ -128,327 +226,1546 @@ pub fn execute(
             swap(
                 deps,
                 env,
-                info.clone(),
                 info.sender,
                 offer_asset,
                 belief_price,
+                belief_price_direction,
                 max_spread,
                 to_addr,
                 deadline,
+                to_msg,
+                None,
+                referral,
             )
         }
-    }
-}
-
-pub fn receive_cw20(
-    deps: DepsMut<TerraQuery>,
-    env: Env,
-    info: MessageInfo,
-    cw20_msg: Cw20ReceiveMsg,
-) -> Result<Response<TerraMsg>, ContractError> {
-    let contract_addr = info.sender.clone();
-
-    match from_binary(&cw20_msg.msg) {
-        Ok(Cw20HookMsg::Swap {
-            belief_price,
-            max_spread,
+        ExecuteMsg::SwapExactOut {
+            ask_asset,
+            max_offer_amount,
             to,
             deadline,
-        }) => {
-            // only asset contract can execute this message
-            let mut authorized: bool = false;
-            let config: PairInfoRaw = PAIR_INFO.load(deps.storage)?;
-            let pools: [Asset; 2] =
-                config.query_pools(&deps.querier, deps.api, env.contract.address.clone())?;
-            for pool in pools.iter() {
-                if let AssetInfo::Token { contract_addr, .. } = &pool.info {
-                    if contract_addr == &info.sender {
-                        authorized = true;
-                    }
-                }
-            }
-
-            if !authorized {
+        } => {
+            // the offer asset is the other asset of the pair
+            let pair_info: PairInfo = PAIR_INFO.load(deps.storage)?.to_normal(deps.api)?;
+            let offer_asset = Asset {
+                info: if ask_asset.info.equal(&pair_info.asset_infos[0]) {
+                    pair_info.asset_infos[1].clone()
+                } else {
+                    pair_info.asset_infos[0].clone()
+                },
+                amount: max_offer_amount,
+            };
+            if !offer_asset.is_native_token() {
                 return Err(ContractError::Unauthorized {});
             }
 
+            assert_sent_funds(&info, std::slice::from_ref(&offer_asset))?;
+
             let to_addr = if let Some(to_addr) = to {
-                Some(deps.api.addr_validate(to_addr.as_str())?)
+                Some(deps.api.addr_validate(&to_addr)?)
             } else {
                 None
             };
 
-            swap(
+            swap_exact_out(
                 deps,
                 env,
-                info,
-                Addr::unchecked(cw20_msg.sender),
-                Asset {
-                    info: AssetInfo::Token {
-                        contract_addr: contract_addr.to_string(),
-                    },
-                    amount: cw20_msg.amount,
-                },
-                belief_price,
-                max_spread,
+                info.sender,
+                offer_asset,
+                ask_asset,
                 to_addr,
                 deadline,
             )
         }
-        Ok(Cw20HookMsg::WithdrawLiquidity {
-            min_assets,
+        ExecuteMsg::ProvideLiquiditySingle {
+            asset,
+            min_lp_to_receive,
             deadline,
-        }) => {
-            let config: PairInfoRaw = PAIR_INFO.load(deps.storage)?;
-            if deps.api.addr_canonicalize(info.sender.as_str())? != config.liquidity_token {
+        } => {
+            if !asset.is_native_token() {
                 return Err(ContractError::Unauthorized {});
             }
 
-            let sender_addr = deps.api.addr_validate(cw20_msg.sender.as_str())?;
-            withdraw_liquidity(
+            provide_liquidity_single(
                 deps,
                 env,
-                info,
-                sender_addr,
-                cw20_msg.amount,
-                min_assets,
+                info.clone(),
+                info.sender,
+                asset,
+                min_lp_to_receive,
                 deadline,
             )
         }
-        Err(err) => Err(ContractError::Std(err)),
+        ExecuteMsg::UpdateFeeConfig {
+            lp_fee_rate,
+            protocol_fee_rate,
+            protocol_fee_collector,
+        } => update_fee_config(
+            deps,
+            info,
+            lp_fee_rate,
+            protocol_fee_rate,
+            protocol_fee_collector,
+        ),
+        ExecuteMsg::UpdateFeeTiers { fee_tiers } => update_fee_tiers(deps, info, fee_tiers),
+        ExecuteMsg::UpdateAdmin { admin } => update_admin(deps, info, admin),
+        ExecuteMsg::UpdateMaxSpreadCap { max_spread_cap } => {
+            update_max_spread_cap(deps, info, max_spread_cap)
+        }
+        ExecuteMsg::UpdateMaxTradeRatio { max_trade_ratio } => {
+            update_max_trade_ratio(deps, info, max_trade_ratio)
+        }
+        ExecuteMsg::SetPoolStatus {
+            swaps_paused,
+            deposits_paused,
+        } => set_pool_status(deps, info, swaps_paused, deposits_paused),
+        ExecuteMsg::Skim { to } => skim(deps, env, info, to),
+        ExecuteMsg::Sync {} => sync(deps, env),
+        ExecuteMsg::UpdateLiquidityTokenMarketing {
+            project,
+            description,
+            marketing,
+            logo,
+        } => update_liquidity_token_marketing(deps, info, project, description, marketing, logo),
+        ExecuteMsg::CollectProtocolFees {} => collect_protocol_fees(deps),
+        ExecuteMsg::UpdateAssetDecimals { asset_decimals } => {
+            update_asset_decimals(deps, info, asset_decimals)
+        }
     }
 }
 
-/// This just stores the result for future query
-#[cfg_attr(not(feature = "library"), entry_point)]
-pub fn reply(deps: DepsMut<TerraQuery>, _env: Env, msg: Reply) -> StdResult<Response<TerraMsg>> {
-    if msg.id != INSTANTIATE_REPLY_ID {
-        return Err(StdError::generic_err("invalid reply msg"));
+/// The pair has no owner of its own, the owner of its factory configures it
+fn assert_factory_owner(deps: Deps<TerraQuery>, info: &MessageInfo) -> Result<(), ContractError> {
+    let factory = FACTORY.load(deps.storage)?;
+    let factory_config: FactoryConfigResponse = deps
+        .querier
+        .query_wasm_smart(factory, &FactoryQueryMsg::Config {})?;
+    if info.sender != factory_config.owner {
+        return Err(ContractError::Unauthorized {});
     }
 
-    let data = msg.result.unwrap().data.unwrap();
-    let res: MsgInstantiateContractResponse =
-        Message::parse_from_bytes(data.as_slice()).map_err(|_| {
-            StdError::parse_err("MsgInstantiateContractResponse", "failed to parse data")
-        })?;
-    let liquidity_token = res.get_contract_address();
+    Ok(())
+}
 
-    let api = deps.api;
-    PAIR_INFO.update(deps.storage, |mut meta| -> StdResult<_> {
-        meta.liquidity_token = api.addr_canonicalize(liquidity_token)?;
-        Ok(meta)
-    })?;
+/// The pair admin manages the pair besides the factory owner
+fn assert_pair_admin(deps: Deps<TerraQuery>, info: &MessageInfo) -> Result<(), ContractError> {
+    if PAIR_ADMIN.may_load(deps.storage)? == Some(info.sender.clone()) {
+        return Ok(());
+    }
 
-    Ok(Response::new().add_attribute("liquidity_token_addr", liquidity_token))
+    assert_factory_owner(deps, info)
 }
 
-/// CONTRACT - should approve contract to use the amount of token
-pub fn provide_liquidity(
+/// Replaces the pair admin, `None` removes it
+pub fn update_admin(
     deps: DepsMut<TerraQuery>,
-    env: Env,
     info: MessageInfo,
-    assets: [Asset; 2],
-    receiver: Option<String>,
-    deadline: Option<u64>,
-    slippage_tolerance: Option<Decimal>,
+    admin: Option<String>,
 ) -> Result<Response<TerraMsg>, ContractError> {
-    assert_deadline(env.block.time.seconds(), deadline)?;
-
-    for asset in assets.iter() {
-        asset.assert_sent_native_token_balance(&info)?;
+    assert_pair_admin(deps.as_ref(), &info)?;
+
+    let admin = admin
+        .map(|admin| deps.api.addr_validate(&admin))
+        .transpose()?;
+    match &admin {
+        Some(admin) => PAIR_ADMIN.save(deps.storage, admin)?,
+        None => PAIR_ADMIN.remove(deps.storage),
     }
 
-    let pair_info: PairInfoRaw = PAIR_INFO.load(deps.storage)?;
-    let mut pools: [Asset; 2] =
-        pair_info.query_pools(&deps.querier, deps.api, env.contract.address.clone())?;
-    let deposits: [Uint128; 2] = [
-        assets
-            .iter()
-            .find(|a| a.info.equal(&pools[0].info))
-            .map(|a| a.amount)
-            .expect("Wrong asset info is given"),
-        assets
-            .iter()
-            .find(|a| a.info.equal(&pools[1].info))
-            .map(|a| a.amount)
-            .expect("Wrong asset info is given"),
-    ];
+    Ok(Response::new().add_attributes(vec![
+        ("action", "update_admin"),
+        (
+            "admin",
+            &admin.map_or_else(|| "none".to_string(), |admin| admin.to_string()),
+        ),
+    ]))
+}
 
-    let mut messages: Vec<CosmosMsg<TerraMsg>> = vec![];
-    for (i, pool) in pools.iter_mut().enumerate() {
-        if pool.is_native_token() {
-            // If the asset is native token, balance is already increased
-            // To calculated properly we should subtract user deposit from the pool
-            pool.amount = pool.amount.checked_sub(deposits[i])?;
-        }
-    }
+/// The symbol of the liquidity token of `asset_infos`, from the native denoms and
+/// token symbols
+fn liquidity_token_symbol_of(
+    querier: &QuerierWrapper<TerraQuery>,
+    asset_infos: &[AssetInfo; 2],
+) -> StdResult<String> {
+    let labels = asset_infos
+        .iter()
+        .map(|asset_info| match asset_info {
+            AssetInfo::NativeToken { denom } => Ok(denom.clone()),
+            AssetInfo::Token { contract_addr } => {
+                Ok(query_token_info(querier, Addr::unchecked(contract_addr))?.symbol)
+            }
+        })
+        .collect::<StdResult<Vec<String>>>()?;
+
+    Ok(liquidity_token_symbol([&labels[0], &labels[1]]))
+}
+
+/// Updates the marketing info and logo of the liquidity token, which the pair markets.
+/// `marketing` hands the marketing over to another address
+pub fn update_liquidity_token_marketing(
+    deps: DepsMut<TerraQuery>,
+    info: MessageInfo,
+    project: Option<String>,
+    description: Option<String>,
+    marketing: Option<String>,
+    logo: Option<Logo>,
+) -> Result<Response<TerraMsg>, ContractError> {
+    assert_pair_admin(deps.as_ref(), &info)?;
 
+    let pair_info: PairInfoRaw = PAIR_INFO.load(deps.storage)?;
     let liquidity_token = deps.api.addr_humanize(&pair_info.liquidity_token)?;
-    let total_share = query_token_info(&deps.querier, liquidity_token)?.total_supply;
-    let share = if total_share.is_zero() {
-        // Initial share = collateral amount
-        let deposit0: Uint256 = deposits[0].into();
-        let deposit1: Uint256 = deposits[1].into();
-        let share: Uint128 = match (Decimal256::from_ratio(deposit0.mul(deposit1), 1u8).sqrt()
-            * Uint256::from(1u8))
-        .try_into()
-        {
-            Ok(share) => share,
-            Err(e) => return Err(ContractError::ConversionOverflowError(e)),
-        };
 
-        // the initial liquidity is deducted by MINIMUM_LIQUIDITY_AMOUNT
-        // to protect a pair from malicious provision blocking
+    let mut messages: Vec<CosmosMsg<TerraMsg>> = vec![];
+    if project.is_some() || description.is_some() || marketing.is_some() {
         messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
-            contract_addr: deps
-                .api
-                .addr_humanize(&pair_info.liquidity_token)?
-                .to_string(),
-            msg: to_binary(&Cw20ExecuteMsg::Mint {
-                recipient: env.contract.address.to_string(),
-                amount: MINIMUM_LIQUIDITY_AMOUNT.into(),
+            contract_addr: liquidity_token.to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::UpdateMarketing {
+                project,
+                description,
+                marketing,
             })?,
             funds: vec![],
         }));
-
-        share
-            .checked_sub(MINIMUM_LIQUIDITY_AMOUNT.into())
-            .map_err(|_| ContractError::MinimumLiquidityAmountError {
-                min_lp_token: MINIMUM_LIQUIDITY_AMOUNT.to_string(),
-                given_lp: share.to_string(),
-            })?
-    } else {
-        // min(1, 2)
-        // 1. sqrt(deposit_0 * exchange_rate_0_to_1 * deposit_0) * (total_share / sqrt(pool_0 * pool_1))
-        // == deposit_0 * total_share / pool_0
-        // 2. sqrt(deposit_1 * exchange_rate_1_to_0 * deposit_1) * (total_share / sqrt(pool_1 * pool_1))
-        // == deposit_1 * total_share / pool_1
-        std::cmp::min(
-            deposits[0].multiply_ratio(total_share, pools[0].amount),
-            deposits[1].multiply_ratio(total_share, pools[1].amount),
-        )
-    };
-
-    // prevent providing free token
-    if share.is_zero() {
-        return Err(ContractError::InvalidZeroAmount {});
+    }
+    if let Some(logo) = logo {
+        messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: liquidity_token.to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::UploadLogo(logo))?,
+            funds: vec![],
+        }));
     }
 
-    // refund of remaining native token & desired of token
-    let mut refund_assets: Vec<Asset> = vec![];
-    for (i, pool) in pools.iter().enumerate() {
-        let desired_amount = match total_share.is_zero() {
-            true => deposits[i],
-            false => {
-                let mut desired_amount = pool.amount.multiply_ratio(share, total_share);
-                if desired_amount.multiply_ratio(total_share, share) != pool.amount {
-                    desired_amount += Uint128::from(1u8);
-                }
-
-                desired_amount
-            }
-        };
-
-        let remain_amount = deposits[i] - desired_amount;
-        if let Some(slippage_tolerance) = slippage_tolerance {
-            if remain_amount > deposits[i] * slippage_tolerance {
-                return Err(ContractError::MaxSlippageAssertion {});
-            }
-        }
-        refund_assets.push(Asset {
-            info: pool.info.clone(),
-            amount: remain_amount,
-        });
+    Ok(Response::new().add_messages(messages).add_attributes(vec![
+        ("action", "update_liquidity_token_marketing"),
+        ("liquidity_token", liquidity_token.as_str()),
+    ]))
+}
 
-        if let AssetInfo::NativeToken { denom, .. } = &pool.info {
-            if !remain_amount.is_zero() {
-                let msg = Asset {
-                    amount: remain_amount,
-                    info: AssetInfo::NativeToken {
-                        denom: denom.to_string(),
-                    },
-                }
-                .into_msg(&deps.querier, info.sender.clone())?;
+/// Corrects the decimals of the pair assets, sent by the factory on
+/// `update_pair_decimals` or by the factory owner
+pub fn update_asset_decimals(
+    deps: DepsMut<TerraQuery>,
+    info: MessageInfo,
+    asset_decimals: [u8; 2],
+) -> Result<Response<TerraMsg>, ContractError> {
+    if info.sender != FACTORY.load(deps.storage)? {
+        assert_factory_owner(deps.as_ref(), &info)?;
+    }
 
-                messages.push(msg);
-            }
-        } else if let AssetInfo::Token { contract_addr, .. } = &pool.info {
-            messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
-                contract_addr: contract_addr.to_string(),
-                msg: to_binary(&Cw20ExecuteMsg::TransferFrom {
-                    owner: info.sender.to_string(),
-                    recipient: env.contract.address.to_string(),
-                    amount: desired_amount,
-                })?,
-                funds: vec![],
-            }));
-        }
+    if asset_decimals
+        .iter()
+        .any(|decimals| *decimals > MAX_ASSET_DECIMALS)
+    {
+        return Err(ContractError::InvalidAssetDecimals {});
     }
 
-    // mint LP token to sender
-    let receiver = receiver.unwrap_or_else(|| info.sender.to_string());
-    messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
-        contract_addr: deps
-            .api
-            .addr_humanize(&pair_info.liquidity_token)?
-            .to_string(),
-        msg: to_binary(&Cw20ExecuteMsg::Mint {
-            recipient: receiver.to_string(),
-            amount: share,
-        })?,
-        funds: vec![],
-    }));
+    let mut pair_info: PairInfoRaw = PAIR_INFO.load(deps.storage)?;
+    pair_info.asset_decimals = asset_decimals;
+    PAIR_INFO.save(deps.storage, &pair_info)?;
 
-    Ok(Response::new().add_messages(messages).add_attributes(vec![
-        ("action", "provide_liquidity"),
-        ("sender", info.sender.as_str()),
-        ("receiver", receiver.as_str()),
-        ("assets", &format!("{}, {}", assets[0], assets[1])),
-        ("share", &share.to_string()),
+    Ok(Response::new().add_attributes(vec![
+        ("action", "update_asset_decimals"),
         (
-            "refund_assets",
-            &format!("{}, {}", refund_assets[0], refund_assets[1]),
+            "asset_decimals",
+            &format!("{}, {}", asset_decimals[0], asset_decimals[1]),
         ),
     ]))
 }
 
-pub fn withdraw_liquidity(
+/// Sends assets which are not part of the pair, sent to it by mistake
+pub fn rescue_funds(
     deps: DepsMut<TerraQuery>,
-    env: Env,
-    _info: MessageInfo,
-    sender: Addr,
+    info: MessageInfo,
+    asset_info: AssetInfo,
     amount: Uint128,
-    min_assets: Option<[Asset; 2]>,
-    deadline: Option<u64>,
+    to: String,
 ) -> Result<Response<TerraMsg>, ContractError> {
-    assert_deadline(env.block.time.seconds(), deadline)?;
+    assert_pair_admin(deps.as_ref(), &info)?;
 
+    let to = deps.api.addr_validate(&to)?;
     let pair_info: PairInfoRaw = PAIR_INFO.load(deps.storage)?;
-    let liquidity_addr: Addr = deps.api.addr_humanize(&pair_info.liquidity_token)?;
-
-    let pools: [Asset; 2] = pair_info.query_pools(&deps.querier, deps.api, env.contract.address)?;
-    let total_share: Uint128 = query_token_info(&deps.querier, liquidity_addr)?.total_supply;
+    let raw_info = asset_info.to_raw(deps.api)?;
+    let is_liquidity_token = matches!(
+        &raw_info,
+        AssetInfoRaw::Token { contract_addr } if *contract_addr == pair_info.liquidity_token
+    );
+    if is_liquidity_token || pair_info.asset_infos.iter().any(|a| a.equal(&raw_info)) {
+        return Err(ContractError::PairAssetRescue {});
+    }
+
+    let asset = Asset {
+        info: asset_info,
+        amount,
+    };
+
+    Ok(Response::new()
+        .add_message(asset.clone().into_msg(&deps.querier, to.clone())?)
+        .add_attributes(vec![
+            ("action", "rescue_funds"),
+            ("to", to.as_str()),
+            ("asset", &asset.to_string()),
+        ]))
+}
+
+pub fn update_fee_config(
+    deps: DepsMut<TerraQuery>,
+    info: MessageInfo,
+    lp_fee_rate: Decimal,
+    protocol_fee_rate: Decimal,
+    protocol_fee_collector: Option<String>,
+) -> Result<Response<TerraMsg>, ContractError> {
+    assert_pair_admin(deps.as_ref(), &info)?;
+
+    let fee_config = FeeConfig {
+        lp_fee_rate,
+        protocol_fee_rate,
+        protocol_fee_collector: protocol_fee_collector
+            .map(|collector| deps.api.addr_validate(&collector))
+            .transpose()?,
+        // the tiers are kept and must still charge at least the new rates
+        fee_tiers: read_fee_config(deps.storage)?.fee_tiers,
+    };
+    assert_fee_config(&fee_config)?;
+
+    FEE_CONFIG.save(deps.storage, &fee_config)?;
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "update_fee_config"),
+        ("lp_fee_rate", &lp_fee_rate.to_string()),
+        ("protocol_fee_rate", &protocol_fee_rate.to_string()),
+    ]))
+}
+
+pub fn update_fee_tiers(
+    deps: DepsMut<TerraQuery>,
+    info: MessageInfo,
+    fee_tiers: Vec<(Uint128, Decimal)>,
+) -> Result<Response<TerraMsg>, ContractError> {
+    assert_pair_admin(deps.as_ref(), &info)?;
+
+    let fee_config = FeeConfig {
+        fee_tiers,
+        ..read_fee_config(deps.storage)?
+    };
+    assert_fee_config(&fee_config)?;
+
+    FEE_CONFIG.save(deps.storage, &fee_config)?;
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "update_fee_tiers"),
+        ("fee_tiers", &fee_config.fee_tiers.len().to_string()),
+    ]))
+}
+
+pub fn update_max_spread_cap(
+    deps: DepsMut<TerraQuery>,
+    info: MessageInfo,
+    max_spread_cap: Option<Decimal>,
+) -> Result<Response<TerraMsg>, ContractError> {
+    assert_factory_owner(deps.as_ref(), &info)?;
+
+    match max_spread_cap {
+        Some(max_spread_cap) => MAX_SPREAD_CAP.save(deps.storage, &max_spread_cap)?,
+        None => MAX_SPREAD_CAP.remove(deps.storage),
+    }
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "update_max_spread_cap"),
+        (
+            "max_spread_cap",
+            &max_spread_cap.map_or_else(|| "none".to_string(), |cap| cap.to_string()),
+        ),
+    ]))
+}
+
+pub fn update_max_trade_ratio(
+    deps: DepsMut<TerraQuery>,
+    info: MessageInfo,
+    max_trade_ratio: Option<Decimal>,
+) -> Result<Response<TerraMsg>, ContractError> {
+    assert_pair_admin(deps.as_ref(), &info)?;
+
+    match max_trade_ratio {
+        Some(ratio) if ratio.is_zero() || ratio > Decimal::one() => {
+            return Err(ContractError::InvalidMaxTradeRatio {})
+        }
+        Some(ratio) => MAX_TRADE_RATIO.save(deps.storage, &ratio)?,
+        None => MAX_TRADE_RATIO.remove(deps.storage),
+    }
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "update_max_trade_ratio"),
+        (
+            "max_trade_ratio",
+            &max_trade_ratio.map_or_else(|| "none".to_string(), |ratio| ratio.to_string()),
+        ),
+    ]))
+}
+
+pub fn set_pool_status(
+    deps: DepsMut<TerraQuery>,
+    info: MessageInfo,
+    swaps_paused: bool,
+    deposits_paused: bool,
+) -> Result<Response<TerraMsg>, ContractError> {
+    assert_pair_admin(deps.as_ref(), &info)?;
+
+    POOL_STATUS.save(
+        deps.storage,
+        &PoolStatus {
+            swaps_paused,
+            deposits_paused,
+        },
+    )?;
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "set_pool_status"),
+        ("swaps_paused", &swaps_paused.to_string()),
+        ("deposits_paused", &deposits_paused.to_string()),
+    ]))
+}
+
+fn assert_fee_config(fee_config: &FeeConfig) -> Result<(), ContractError> {
+    if fee_config.total_fee_rate() > Decimal::percent(MAX_TOTAL_FEE_RATE) {
+        return Err(ContractError::FeeRateTooHigh {});
+    }
+
+    if !fee_config.protocol_fee_rate.is_zero() && fee_config.protocol_fee_collector.is_none() {
+        return Err(ContractError::ProtocolFeeCollectorRequired {});
+    }
+
+    // every tier charges more than the one below it, starting from the total fee rate
+    let mut previous = (Uint128::zero(), fee_config.total_fee_rate());
+    for (threshold, commission_rate) in fee_config.fee_tiers.iter().copied() {
+        if threshold <= previous.0 || commission_rate < previous.1 {
+            return Err(ContractError::InvalidFeeTiers {});
+        }
+        if commission_rate > Decimal::percent(MAX_TOTAL_FEE_RATE) {
+            return Err(ContractError::FeeRateTooHigh {});
+        }
+        previous = (threshold, commission_rate);
+    }
+
+    Ok(())
+}
+
+pub fn receive_cw20(
+    deps: DepsMut<TerraQuery>,
+    env: Env,
+    info: MessageInfo,
+    cw20_msg: Cw20ReceiveMsg,
+) -> Result<Response<TerraMsg>, ContractError> {
+    let contract_addr = info.sender.clone();
+
+    match from_binary(&cw20_msg.msg) {
+        Ok(Cw20HookMsg::Swap {
+            belief_price,
+            belief_price_direction,
+            max_spread,
+            to,
+            deadline,
+            to_msg,
+            referral,
+        }) => {
+            assert_asset_token(deps.as_ref(), &info.sender)?;
+
+            let to_addr = if let Some(to_addr) = to {
+                Some(deps.api.addr_validate(to_addr.as_str())?)
+            } else {
+                None
+            };
+
+            swap(
+                deps,
+                env,
+                Addr::unchecked(cw20_msg.sender),
+                Asset {
+                    info: AssetInfo::Token {
+                        contract_addr: contract_addr.to_string(),
+                    },
+                    amount: cw20_msg.amount,
+                },
+                belief_price,
+                belief_price_direction,
+                max_spread,
+                to_addr,
+                deadline,
+                to_msg,
+                None,
+                referral,
+            )
+        }
+        Ok(Cw20HookMsg::SwapExactOut {
+            ask_asset,
+            to,
+            deadline,
+        }) => {
+            assert_asset_token(deps.as_ref(), &info.sender)?;
+
+            let to_addr = if let Some(to_addr) = to {
+                Some(deps.api.addr_validate(to_addr.as_str())?)
+            } else {
+                None
+            };
+
+            swap_exact_out(
+                deps,
+                env,
+                Addr::unchecked(cw20_msg.sender),
+                Asset {
+                    info: AssetInfo::Token {
+                        contract_addr: contract_addr.to_string(),
+                    },
+                    amount: cw20_msg.amount,
+                },
+                ask_asset,
+                to_addr,
+                deadline,
+            )
+        }
+        Ok(Cw20HookMsg::RevealSwap { params, salt }) => {
+            // the committed offer is the sent amount of the sending token
+            let offer_asset = Asset {
+                info: AssetInfo::Token {
+                    contract_addr: contract_addr.to_string(),
+                },
+                amount: cw20_msg.amount,
+            };
+            if params.offer_asset != offer_asset {
+                return Err(ContractError::AssetMismatch {});
+            }
+
+            assert_asset_token(deps.as_ref(), &info.sender)?;
+
+            reveal_swap(deps, env, Addr::unchecked(cw20_msg.sender), params, salt)
+        }
+        Ok(Cw20HookMsg::ProvideLiquiditySingle {
+            min_lp_to_receive,
+            deadline,
+        }) => {
+            assert_asset_token(deps.as_ref(), &info.sender)?;
+
+            provide_liquidity_single(
+                deps,
+                env,
+                info,
+                Addr::unchecked(cw20_msg.sender),
+                Asset {
+                    info: AssetInfo::Token {
+                        contract_addr: contract_addr.to_string(),
+                    },
+                    amount: cw20_msg.amount,
+                },
+                min_lp_to_receive,
+                deadline,
+            )
+        }
+        Ok(Cw20HookMsg::DepositForLiquidity {}) => {
+            let index = assert_asset_token(deps.as_ref(), &info.sender)?;
+
+            let sender = deps.api.addr_validate(&cw20_msg.sender)?;
+            add_deposit(deps.storage, &sender, index, cw20_msg.amount)?;
+
+            Ok(Response::new().add_attributes(vec![
+                ("action", "deposit_for_liquidity"),
+                ("sender", sender.as_str()),
+                ("asset", &format!("{}{}", cw20_msg.amount, contract_addr)),
+            ]))
+        }
+        Ok(Cw20HookMsg::WithdrawLiquidity {
+            min_assets,
+            deadline,
+            receiver,
+        }) => {
+            assert_liquidity_token(deps.as_ref(), &info.sender)?;
+
+            let sender_addr = deps.api.addr_validate(cw20_msg.sender.as_str())?;
+            let receiver_addr = validate_receiver(deps.as_ref(), receiver, &sender_addr)?;
+            withdraw_liquidity(
+                deps,
+                env,
+                info,
+                sender_addr,
+                receiver_addr,
+                cw20_msg.amount,
+                min_assets,
+                deadline,
+            )
+        }
+        Ok(Cw20HookMsg::WithdrawLiquiditySingle {
+            ask_asset_info,
+            min_receive,
+            deadline,
+            receiver,
+        }) => {
+            assert_liquidity_token(deps.as_ref(), &info.sender)?;
+
+            let sender_addr = deps.api.addr_validate(cw20_msg.sender.as_str())?;
+            let receiver_addr = validate_receiver(deps.as_ref(), receiver, &sender_addr)?;
+            withdraw_liquidity_single(
+                deps,
+                env,
+                info,
+                sender_addr,
+                receiver_addr,
+                cw20_msg.amount,
+                ask_asset_info,
+                min_receive,
+                deadline,
+            )
+        }
+        Err(err) => Err(ContractError::Std(err)),
+    }
+}
+
+/// Only the cw20 assets of the pair can send the hooks offering an asset, a token
+/// calling with a spoofed hook has no reserves to swap or deposit against.
+/// Returns the index of the asset
+fn assert_asset_token(deps: Deps<TerraQuery>, sender: &Addr) -> Result<usize, ContractError> {
+    let pair_info: PairInfoRaw = PAIR_INFO.load(deps.storage)?;
+    let sender = deps.api.addr_canonicalize(sender.as_str())?;
+    pair_info
+        .asset_infos
+        .iter()
+        .position(|asset_info| {
+            matches!(asset_info, AssetInfoRaw::Token { contract_addr } if *contract_addr == sender)
+        })
+        .ok_or(ContractError::Unauthorized {})
+}
+
+/// Only the liquidity token can send the hooks withdrawing liquidity
+fn assert_liquidity_token(deps: Deps<TerraQuery>, sender: &Addr) -> Result<(), ContractError> {
+    let pair_info: PairInfoRaw = PAIR_INFO.load(deps.storage)?;
+    if deps.api.addr_canonicalize(sender.as_str())? != pair_info.liquidity_token {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    Ok(())
+}
+
+/// The receiver of a withdrawal, the sender of the liquidity token by default.
+/// An operator withdrawing with `send_from` sends the refund to the owner with it
+fn validate_receiver(
+    deps: Deps<TerraQuery>,
+    receiver: Option<String>,
+    sender: &Addr,
+) -> StdResult<Addr> {
+    receiver.map_or_else(
+        || Ok(sender.clone()),
+        |receiver| deps.api.addr_validate(&receiver),
+    )
+}
+
+/// This just stores the result for future query
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn reply(
+    deps: DepsMut<TerraQuery>,
+    _env: Env,
+    msg: Reply,
+) -> Result<Response<TerraMsg>, ContractError> {
+    if msg.id == LIQUIDITY_TOKEN_MIGRATE_REPLY_ID {
+        return assert_liquidity_token_intact(deps);
+    }
+    if msg.id != INSTANTIATE_REPLY_ID {
+        return Err(ContractError::InvalidReply {});
+    }
+
+    let data = msg.result.unwrap().data.unwrap();
+    let res: MsgInstantiateContractResponse =
+        Message::parse_from_bytes(data.as_slice()).map_err(|_| {
+            StdError::parse_err("MsgInstantiateContractResponse", "failed to parse data")
+        })?;
+    let liquidity_token = res.get_contract_address();
+
+    let api = deps.api;
+    PAIR_INFO.update(deps.storage, |mut meta| -> StdResult<_> {
+        meta.liquidity_token = api.addr_canonicalize(liquidity_token)?;
+        Ok(meta)
+    })?;
+
+    Ok(Response::new().add_attribute("liquidity_token_addr", liquidity_token))
+}
+
+fn query_liquidity_token_snapshot(
+    deps: Deps<TerraQuery>,
+    liquidity_token: &Addr,
+) -> StdResult<LiquidityTokenSnapshot> {
+    Ok(LiquidityTokenSnapshot {
+        total_supply: query_token_info(&deps.querier, liquidity_token.clone())?.total_supply,
+        minter: deps
+            .querier
+            .query_wasm_smart(liquidity_token, &Cw20QueryMsg::Minter {})?,
+    })
+}
+
+/// Aborts the migration of the liquidity token when it changed the supply or the minter
+fn assert_liquidity_token_intact(
+    deps: DepsMut<TerraQuery>,
+) -> Result<Response<TerraMsg>, ContractError> {
+    let pair_info: PairInfoRaw = PAIR_INFO.load(deps.storage)?;
+    let liquidity_token = deps.api.addr_humanize(&pair_info.liquidity_token)?;
+    let snapshot = LIQUIDITY_TOKEN_SNAPSHOT.load(deps.storage)?;
+    LIQUIDITY_TOKEN_SNAPSHOT.remove(deps.storage);
+
+    if query_liquidity_token_snapshot(deps.as_ref(), &liquidity_token)? != snapshot {
+        return Err(ContractError::LiquidityTokenChanged {});
+    }
+
+    Ok(Response::new().add_attribute("liquidity_token_intact", "true"))
+}
+
+/// The pools of the pair at the tracked reserves
+fn read_pools(deps: Deps<TerraQuery>, pair_info: &PairInfoRaw) -> StdResult<[Asset; 2]> {
+    let reserves = read_reserves(deps.storage)?;
+
+    Ok([
+        Asset {
+            info: pair_info.asset_infos[0].to_normal(deps.api)?,
+            amount: reserves[0],
+        },
+        Asset {
+            info: pair_info.asset_infos[1].to_normal(deps.api)?,
+            amount: reserves[1],
+        },
+    ])
+}
+
+/// Sends the tokens sent to the pair directly, the balances above the reserves
+pub fn skim(
+    deps: DepsMut<TerraQuery>,
+    env: Env,
+    info: MessageInfo,
+    to: Option<String>,
+) -> Result<Response<TerraMsg>, ContractError> {
+    let to = match to {
+        Some(to) => deps.api.addr_validate(&to)?,
+        None => info.sender,
+    };
+
+    let pair_info: PairInfoRaw = PAIR_INFO.load(deps.storage)?;
+    let pools: [Asset; 2] = read_pools(deps.as_ref(), &pair_info)?;
+    let balances: [Asset; 2] =
+        pair_info.query_pools(&deps.querier, deps.api, env.contract.address.clone())?;
+
+    let unpooled_balances = read_unpooled_balances(deps.storage)?;
+
+    let excess_assets: Vec<Asset> = balances
+        .iter()
+        .zip(pools.iter())
+        .zip(unpooled_balances.iter())
+        .map(|((balance, pool), unpooled_balance)| Asset {
+            info: balance.info.clone(),
+            amount: balance
+                .amount
+                .saturating_sub(pool.amount)
+                .saturating_sub(*unpooled_balance),
+        })
+        .collect();
+
+    let mut messages: Vec<CosmosMsg<TerraMsg>> = vec![];
+    for excess_asset in excess_assets.iter() {
+        if !excess_asset.amount.is_zero() {
+            messages.push(excess_asset.clone().into_msg(&deps.querier, to.clone())?);
+        }
+    }
+
+    Ok(Response::new().add_messages(messages).add_attributes(vec![
+        ("action", "skim"),
+        ("to", to.as_str()),
+        (
+            "assets",
+            &format!("{}, {}", excess_assets[0], excess_assets[1]),
+        ),
+    ]))
+}
+
+/// Sets the reserves to the balances of the pair
+pub fn sync(deps: DepsMut<TerraQuery>, env: Env) -> Result<Response<TerraMsg>, ContractError> {
+    let pair_info: PairInfoRaw = PAIR_INFO.load(deps.storage)?;
+    let pools: [Asset; 2] = read_pools(deps.as_ref(), &pair_info)?;
+    let balances: [Asset; 2] =
+        pair_info.query_pools(&deps.querier, deps.api, env.contract.address)?;
+
+    let unpooled_balances = read_unpooled_balances(deps.storage)?;
+
+    update_price_accumulators(
+        deps.storage,
+        [pools[0].amount, pools[1].amount],
+        env.block.time.seconds(),
+    )?;
+
+    // the accrued protocol fees and the escrowed deposits are held apart from the reserves
+    let reserves = [
+        balances[0].amount.saturating_sub(unpooled_balances[0]),
+        balances[1].amount.saturating_sub(unpooled_balances[1]),
+    ];
+    RESERVES.save(deps.storage, &reserves)?;
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "sync"),
+        (
+            "reserves",
+            &format!(
+                "{}{}, {}{}",
+                reserves[0], balances[0].info, reserves[1], balances[1].info
+            ),
+        ),
+    ]))
+}
+
+/// Sends the accrued protocol fees to the protocol fee collector
+pub fn collect_protocol_fees(
+    deps: DepsMut<TerraQuery>,
+) -> Result<Response<TerraMsg>, ContractError> {
+    let pair_info: PairInfoRaw = PAIR_INFO.load(deps.storage)?;
+    let pair_info: PairInfo = pair_info.to_normal(deps.api)?;
+    let protocol_fees = read_protocol_fees(deps.storage)?;
+    let fee_assets: Vec<Asset> = pair_info
+        .asset_infos
+        .iter()
+        .zip(protocol_fees.iter())
+        .map(|(info, amount)| Asset {
+            info: info.clone(),
+            amount: *amount,
+        })
+        .collect();
+
+    let mut messages: Vec<CosmosMsg<TerraMsg>> = vec![];
+    let collector = read_fee_config(deps.storage)?.protocol_fee_collector;
+    if let Some(collector) = &collector {
+        for fee_asset in fee_assets.iter() {
+            if !fee_asset.amount.is_zero() {
+                messages.push(
+                    fee_asset
+                        .clone()
+                        .into_msg(&deps.querier, collector.clone())?,
+                );
+            }
+        }
+    } else if fee_assets.iter().any(|a| !a.amount.is_zero()) {
+        return Err(ContractError::ProtocolFeeCollectorRequired {});
+    }
+
+    PROTOCOL_FEES.save(deps.storage, &[Uint128::zero(); 2])?;
+
+    Ok(Response::new().add_messages(messages).add_attributes(vec![
+        ("action", "collect_protocol_fees"),
+        (
+            "collector",
+            collector.as_ref().map(|c| c.as_str()).unwrap_or_default(),
+        ),
+        ("assets", &format!("{}, {}", fee_assets[0], fee_assets[1])),
+    ]))
+}
+
+/// CONTRACT - should approve contract to use the amount of token,
+/// unless the tokens are `escrowed` by the pair already
+#[allow(clippy::too_many_arguments)]
+pub fn provide_liquidity(
+    deps: DepsMut<TerraQuery>,
+    env: Env,
+    info: MessageInfo,
+    assets: [Asset; 2],
+    receiver: Option<String>,
+    deadline: Option<u64>,
+    slippage_tolerance: Option<Decimal>,
+    min_lp_to_receive: Option<Uint128>,
+    escrowed: bool,
+) -> Result<Response<TerraMsg>, ContractError> {
+    assert_deadline(env.block.time.seconds(), deadline)?;
+
+    if read_pool_status(deps.storage)?.deposits_paused {
+        return Err(ContractError::DepositsPaused {});
+    }
+
+    // LP tokens can be minted to another address, the deposits are still taken from the sender
+    let receiver = match receiver {
+        Some(receiver) => deps.api.addr_validate(&receiver)?,
+        None => info.sender.clone(),
+    };
+
+    assert_sent_funds(&info, &assets)?;
+    match (assets[0].amount.is_zero(), assets[1].amount.is_zero()) {
+        (true, true) => return Err(ContractError::ZeroDeposits {}),
+        (true, false) | (false, true) => return Err(ContractError::OneSidedDeposit {}),
+        _ => {}
+    }
+
+    let pair_info: PairInfoRaw = PAIR_INFO.load(deps.storage)?;
+    let pools: [Asset; 2] = read_pools(deps.as_ref(), &pair_info)?;
+    let assets = normalize_assets(&pools, &assets)?;
+    // the burn tax is charged to the sender on top, the whole amount is credited
+    let deposits = [assets[0].amount, assets[1].amount];
+
+    let mut messages: Vec<CosmosMsg<TerraMsg>> = vec![];
+    update_price_accumulators(
+        deps.storage,
+        [pools[0].amount, pools[1].amount],
+        env.block.time.seconds(),
+    )?;
+
+    let liquidity_token = deps.api.addr_humanize(&pair_info.liquidity_token)?;
+    let total_share = query_token_info(&deps.querier, liquidity_token)?.total_supply;
+    let Provision {
+        share,
+        locked_share,
+        used_amounts,
+    } = compute_provide(
+        [pools[0].amount, pools[1].amount],
+        deposits,
+        total_share,
+        slippage_tolerance,
+    )?;
+
+    // the locked share is minted to the pair itself and can never be withdrawn
+    if !locked_share.is_zero() {
+        messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: deps
+                .api
+                .addr_humanize(&pair_info.liquidity_token)?
+                .to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Mint {
+                recipient: env.contract.address.to_string(),
+                amount: locked_share,
+            })?,
+            funds: vec![],
+        }));
+    }
+
+    // refund of remaining native token & desired of token
+    let mut refund_assets: Vec<Asset> = vec![];
+    let mut reserves = [Uint128::zero(); 2];
+    for (i, pool) in pools.iter().enumerate() {
+        let desired_amount = used_amounts[i];
+        let remain_amount = deposits[i] - desired_amount;
+        reserves[i] = pool.amount.checked_add(desired_amount)?;
+        refund_assets.push(Asset {
+            info: pool.info.clone(),
+            amount: remain_amount,
+        });
+
+        if let AssetInfo::NativeToken { denom, .. } = &pool.info {
+            if !remain_amount.is_zero() {
+                let msg = Asset {
+                    amount: remain_amount,
+                    info: AssetInfo::NativeToken {
+                        denom: denom.to_string(),
+                    },
+                }
+                .into_msg(&deps.querier, info.sender.clone())?;
+
+                messages.push(msg);
+            }
+        } else if let (true, AssetInfo::Token { contract_addr, .. }) = (escrowed, &pool.info) {
+            // the escrowed excess is sent back
+            if !remain_amount.is_zero() {
+                messages.push(
+                    Asset {
+                        amount: remain_amount,
+                        info: AssetInfo::Token {
+                            contract_addr: contract_addr.to_string(),
+                        },
+                    }
+                    .into_msg(&deps.querier, info.sender.clone())?,
+                );
+            }
+        } else if let AssetInfo::Token { contract_addr, .. } = &pool.info {
+            messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: contract_addr.to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::TransferFrom {
+                    owner: info.sender.to_string(),
+                    recipient: env.contract.address.to_string(),
+                    amount: desired_amount,
+                })?,
+                funds: vec![],
+            }));
+        }
+    }
+
+    assert_min_lp_to_receive(share, min_lp_to_receive)?;
+    RESERVES.save(deps.storage, &reserves)?;
+
+    // mint LP token to receiver
+    messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: deps
+            .api
+            .addr_humanize(&pair_info.liquidity_token)?
+            .to_string(),
+        msg: to_binary(&Cw20ExecuteMsg::Mint {
+            recipient: receiver.to_string(),
+            amount: share,
+        })?,
+        funds: vec![],
+    }));
+
+    Ok(Response::new().add_messages(messages).add_attributes(vec![
+        ("action", "provide_liquidity"),
+        ("sender", info.sender.as_str()),
+        ("receiver", receiver.as_str()),
+        ("assets", &format!("{}, {}", assets[0], assets[1])),
+        (
+            "deposits",
+            &format!(
+                "{}{}, {}{}",
+                deposits[0], pools[0].info, deposits[1], pools[1].info
+            ),
+        ),
+        ("share", &share.to_string()),
+        (
+            "refund_assets",
+            &format!("{}, {}", refund_assets[0], refund_assets[1]),
+        ),
+        ("locked_share", &locked_share.to_string()),
+    ]))
+}
+
+/// Provides the deposits escrowed by the sender together with the attached native funds
+pub fn provide_liquidity_from_deposits(
+    deps: DepsMut<TerraQuery>,
+    env: Env,
+    info: MessageInfo,
+    slippage_tolerance: Option<Decimal>,
+    min_lp_to_receive: Option<Uint128>,
+) -> Result<Response<TerraMsg>, ContractError> {
+    let pair_info: PairInfo = PAIR_INFO.load(deps.storage)?.to_normal(deps.api)?;
+    for coin in info.funds.iter() {
+        if !pair_info
+            .asset_infos
+            .iter()
+            .any(|a| matches!(a, AssetInfo::NativeToken { denom } if *denom == coin.denom))
+        {
+            return Err(ContractError::AssetMismatch {});
+        }
+    }
+
+    let deposits = take_deposits(deps.storage, &info.sender)?;
+    let mut assets: Vec<Asset> = vec![];
+    for (asset_info, deposit) in pair_info.asset_infos.iter().zip(deposits.iter()) {
+        let amount = match asset_info {
+            AssetInfo::NativeToken { denom } => info
+                .funds
+                .iter()
+                .find(|coin| coin.denom == *denom)
+                .map(|coin| coin.amount)
+                .unwrap_or_default(),
+            AssetInfo::Token { .. } => *deposit,
+        };
+        assets.push(Asset {
+            info: asset_info.clone(),
+            amount,
+        });
+    }
+
+    provide_liquidity(
+        deps,
+        env,
+        info,
+        [assets[0].clone(), assets[1].clone()],
+        None,
+        None,
+        slippage_tolerance,
+        min_lp_to_receive,
+        true,
+    )
+}
+
+/// Sends the deposits escrowed by the sender back
+pub fn refund_deposits(
+    deps: DepsMut<TerraQuery>,
+    info: MessageInfo,
+) -> Result<Response<TerraMsg>, ContractError> {
+    let pair_info: PairInfo = PAIR_INFO.load(deps.storage)?.to_normal(deps.api)?;
+    let deposits = take_deposits(deps.storage, &info.sender)?;
+    let refund_assets: Vec<Asset> = pair_info
+        .asset_infos
+        .iter()
+        .zip(deposits.iter())
+        .map(|(info, amount)| Asset {
+            info: info.clone(),
+            amount: *amount,
+        })
+        .collect();
+
+    let mut messages: Vec<CosmosMsg<TerraMsg>> = vec![];
+    for refund_asset in refund_assets.iter() {
+        if !refund_asset.amount.is_zero() {
+            messages.push(
+                refund_asset
+                    .clone()
+                    .into_msg(&deps.querier, info.sender.clone())?,
+            );
+        }
+    }
+
+    Ok(Response::new().add_messages(messages).add_attributes(vec![
+        ("action", "refund_deposits"),
+        ("sender", info.sender.as_str()),
+        (
+            "refund_assets",
+            &format!("{}, {}", refund_assets[0], refund_assets[1]),
+        ),
+    ]))
+}
+
+/// Orders the provided `assets` as the pools, whichever order they are given in.
+/// Every pool must be provided exactly once.
+fn normalize_assets(pools: &[Asset; 2], assets: &[Asset]) -> Result<[Asset; 2], ContractError> {
+    if assets.len() != 2 || assets[0].info.equal(&assets[1].info) {
+        return Err(ContractError::AssetMismatch {});
+    }
+
+    let find = |pool: &Asset| {
+        assets
+            .iter()
+            .find(|a| a.info.equal(&pool.info))
+            .cloned()
+            .ok_or(ContractError::AssetMismatch {})
+    };
+
+    Ok([find(&pools[0])?, find(&pools[1])?])
+}
+
+struct Provision {
+    /// the share minted to the receiver
+    share: Uint128,
+    /// the share locked in the pair by the first provision
+    locked_share: Uint128,
+    /// the part of the deposits taken into the pools, the rest is refunded
+    used_amounts: [Uint128; 2],
+}
+
+/// The share minted for `deposits` to `pools`, shared by `provide_liquidity`
+/// and its simulation
+fn compute_provide(
+    pools: [Uint128; 2],
+    deposits: [Uint128; 2],
+    total_share: Uint128,
+    slippage_tolerance: Option<Decimal>,
+) -> Result<Provision, ContractError> {
+    let mut locked_share = Uint128::zero();
+    let share = if total_share.is_zero() {
+        // Initial share = collateral amount
+        let deposit0: Uint256 = deposits[0].into();
+        let deposit1: Uint256 = deposits[1].into();
+        let share: Uint128 = match (Decimal256::from_ratio(deposit0.mul(deposit1), 1u8).sqrt()
+            * Uint256::from(1u8))
+        .try_into()
+        {
+            Ok(share) => share,
+            Err(e) => return Err(ContractError::ConversionOverflowError(e)),
+        };
+
+        // the initial liquidity is deducted by MINIMUM_LIQUIDITY_AMOUNT
+        // to protect a pair from malicious provision blocking,
+        // the first provider must receive a share on top of it
+        if share <= Uint128::from(MINIMUM_LIQUIDITY_AMOUNT) {
+            return Err(ContractError::MinimumLiquidityAmountError {
+                min_lp_token: MINIMUM_LIQUIDITY_AMOUNT.to_string(),
+                given_lp: share.to_string(),
+            });
+        }
+        locked_share = MINIMUM_LIQUIDITY_AMOUNT.into();
+
+        // share = sqrt(deposit_0 * deposit_1) - MINIMUM_LIQUIDITY_AMOUNT
+        share.checked_sub(locked_share)?
+    } else {
+        // min(1, 2)
+        // 1. sqrt(deposit_0 * exchange_rate_0_to_1 * deposit_0) * (total_share / sqrt(pool_0 * pool_1))
+        // == deposit_0 * total_share / pool_0
+        // 2. sqrt(deposit_1 * exchange_rate_1_to_0 * deposit_1) * (total_share / sqrt(pool_1 * pool_1))
+        // == deposit_1 * total_share / pool_1
+        std::cmp::min(
+            deposits[0].multiply_ratio(total_share, pools[0]),
+            deposits[1].multiply_ratio(total_share, pools[1]),
+        )
+    };
+
+    // prevent providing free token
+    if share.is_zero() {
+        return Err(ContractError::InvalidZeroAmount {});
+    }
+
+    let mut used_amounts = [Uint128::zero(); 2];
+    for (i, pool) in pools.iter().enumerate() {
+        used_amounts[i] = match total_share.is_zero() {
+            true => deposits[i],
+            false => {
+                let mut desired_amount = pool.multiply_ratio(share, total_share);
+                if desired_amount.multiply_ratio(total_share, share) != *pool {
+                    desired_amount += Uint128::from(1u8);
+                }
+
+                desired_amount
+            }
+        };
+
+        let remain_amount = deposits[i].checked_sub(used_amounts[i])?;
+        if let Some(slippage_tolerance) = slippage_tolerance {
+            if remain_amount > deposits[i] * slippage_tolerance {
+                return Err(ContractError::MaxSlippageAssertion {});
+            }
+        }
+    }
+
+    Ok(Provision {
+        share,
+        locked_share,
+        used_amounts,
+    })
+}
+
+fn assert_min_lp_to_receive(
+    share: Uint128,
+    min_lp_to_receive: Option<Uint128>,
+) -> Result<(), ContractError> {
+    if let Some(min_lp_to_receive) = min_lp_to_receive {
+        if share < min_lp_to_receive {
+            return Err(ContractError::MinLpToReceiveAssertion {
+                minted: share.to_string(),
+                min_lp_to_receive: min_lp_to_receive.to_string(),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Provides liquidity with one asset of the pair. The optimal part of the deposit
+/// is swapped to the other asset in the pool itself and both sides are provided,
+/// the rounding dust is refunded.
+pub fn provide_liquidity_single(
+    deps: DepsMut<TerraQuery>,
+    env: Env,
+    info: MessageInfo,
+    sender: Addr,
+    asset: Asset,
+    min_lp_to_receive: Option<Uint128>,
+    deadline: Option<u64>,
+) -> Result<Response<TerraMsg>, ContractError> {
+    assert_deadline(env.block.time.seconds(), deadline)?;
+
+    // the deposit is partly swapped
+    let pool_status = read_pool_status(deps.storage)?;
+    if pool_status.deposits_paused {
+        return Err(ContractError::DepositsPaused {});
+    }
+    if pool_status.swaps_paused {
+        return Err(ContractError::SwapsPaused {});
+    }
+
+    assert_sent_funds(&info, std::slice::from_ref(&asset))?;
+
+    let pair_info: PairInfoRaw = PAIR_INFO.load(deps.storage)?;
+    let pools: [Asset; 2] = read_pools(deps.as_ref(), &pair_info)?;
+
+    let (offer_index, ask_index) = if asset.info.equal(&pools[0].info) {
+        (0, 1)
+    } else if asset.info.equal(&pools[1].info) {
+        (1, 0)
+    } else {
+        return Err(ContractError::AssetMismatch {});
+    };
+    let offer_pool = pools[offer_index].clone();
+    let ask_pool = pools[ask_index].clone();
+
+    update_price_accumulators(
+        deps.storage,
+        [pools[0].amount, pools[1].amount],
+        env.block.time.seconds(),
+    )?;
+
+    let liquidity_token = deps.api.addr_humanize(&pair_info.liquidity_token)?;
+    let total_share = query_token_info(&deps.querier, liquidity_token.clone())?.total_supply;
+    let fee_config = read_fee_config(deps.storage)?;
+    let provision = compute_provide_single(
+        &offer_pool,
+        &ask_pool,
+        asset.amount,
+        total_share,
+        &fee_config,
+    )?;
+
+    assert_min_lp_to_receive(provision.share, min_lp_to_receive)?;
+
+    // the deposit stays in the pool but for the refund,
+    // the swap return is provided back but for the refund and the protocol fee
+    let mut reserves = [Uint128::zero(); 2];
+    reserves[offer_index] = offer_pool
+        .amount
+        .checked_add(asset.amount)?
+        .checked_sub(provision.refund_assets[0].amount)?;
+    reserves[ask_index] = ask_pool
+        .amount
+        .checked_sub(provision.protocol_fee_amount)?
+        .checked_sub(provision.refund_assets[1].amount)?;
+    RESERVES.save(deps.storage, &reserves)?;
+
+    accrue_protocol_fee(deps.storage, ask_index, provision.protocol_fee_amount)?;
+    track_commission(
+        deps.storage,
+        ask_index,
+        provision.commission_amount,
+        env.block.time.seconds(),
+    )?;
+
+    let mut messages: Vec<CosmosMsg<TerraMsg>> = vec![];
+
+    for refund_asset in provision.refund_assets.iter() {
+        if !refund_asset.amount.is_zero() {
+            messages.push(
+                refund_asset
+                    .clone()
+                    .into_msg(&deps.querier, sender.clone())?,
+            );
+        }
+    }
+
+    messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: liquidity_token.to_string(),
+        msg: to_binary(&Cw20ExecuteMsg::Mint {
+            recipient: sender.to_string(),
+            amount: provision.share,
+        })?,
+        funds: vec![],
+    }));
+
+    Ok(Response::new().add_messages(messages).add_attributes(vec![
+        ("action", "provide_liquidity_single"),
+        ("sender", sender.as_str()),
+        ("asset", &asset.to_string()),
+        ("swap_amount", &provision.swap_amount.to_string()),
+        ("return_amount", &provision.return_amount.to_string()),
+        ("share", &provision.share.to_string()),
+        (
+            "refund_assets",
+            &format!(
+                "{}, {}",
+                provision.refund_assets[0], provision.refund_assets[1]
+            ),
+        ),
+        (
+            "commission_amount",
+            &provision.commission_amount.to_string(),
+        ),
+        (
+            "protocol_fee_amount",
+            &provision.protocol_fee_amount.to_string(),
+        ),
+    ]))
+}
+
+struct SingleSidedProvision {
+    swap_amount: Uint128,
+    return_amount: Uint128,
+    spread_amount: Uint128,
+    commission_amount: Uint128,
+    protocol_fee_amount: Uint128,
+    share: Uint128,
+    /// refunds of the offer and the ask asset
+    refund_assets: [Asset; 2],
+}
+
+fn compute_provide_single(
+    offer_pool: &Asset,
+    ask_pool: &Asset,
+    offer_amount: Uint128,
+    total_share: Uint128,
+    fee_config: &FeeConfig,
+) -> Result<SingleSidedProvision, ContractError> {
+    if total_share.is_zero() || offer_pool.amount.is_zero() || ask_pool.amount.is_zero() {
+        return Err(ContractError::NoLiquidity {});
+    }
+
+    // the swap amount depends on the rate and the tier on the swap amount,
+    // raise the rate until the tier of the swap amount charges it
+    let mut commission_rate = fee_config.total_fee_rate();
+    let mut swap_amount =
+        compute_single_sided_swap_amount(offer_pool.amount, offer_amount, commission_rate)?;
+    while fee_config.commission_rate(offer_pool.amount, swap_amount) > commission_rate {
+        commission_rate = fee_config.commission_rate(offer_pool.amount, swap_amount);
+        swap_amount =
+            compute_single_sided_swap_amount(offer_pool.amount, offer_amount, commission_rate)?;
+    }
+    let SwapComputation {
+        return_amount,
+        spread_amount,
+        commission_amount,
+    } = compute_swap(
+        offer_pool.amount,
+        ask_pool.amount,
+        swap_amount,
+        commission_rate,
+    )?;
+    let protocol_fee_amount = compute_protocol_fee(fee_config, commission_amount);
+
+    // provide the rest of the deposit and the swap return to the pools after the swap
+    let deposits = [offer_amount.checked_sub(swap_amount)?, return_amount];
+    let pools = [
+        offer_pool.amount.checked_add(swap_amount)?,
+        ask_pool
+            .amount
+            .checked_sub(return_amount)?
+            .checked_sub(protocol_fee_amount)?,
+    ];
+    let share = std::cmp::min(
+        deposits[0].multiply_ratio(total_share, pools[0]),
+        deposits[1].multiply_ratio(total_share, pools[1]),
+    );
+
+    // prevent providing free token
+    if share.is_zero() {
+        return Err(ContractError::InvalidZeroAmount {});
+    }
+
+    let refund_amounts: Vec<Uint128> = pools
+        .iter()
+        .zip(deposits.iter())
+        .map(|(pool, deposit)| {
+            let mut desired_amount = pool.multiply_ratio(share, total_share);
+            if desired_amount.multiply_ratio(total_share, share) != *pool {
+                desired_amount += Uint128::from(1u8);
+            }
+
+            deposit.saturating_sub(desired_amount)
+        })
+        .collect();
+
+    Ok(SingleSidedProvision {
+        swap_amount,
+        return_amount,
+        spread_amount,
+        commission_amount,
+        protocol_fee_amount,
+        share,
+        refund_assets: [
+            Asset {
+                info: offer_pool.info.clone(),
+                amount: refund_amounts[0],
+            },
+            Asset {
+                info: ask_pool.info.clone(),
+                amount: refund_amounts[1],
+            },
+        ],
+    })
+}
+
+/// Returns the part of a single-sided deposit to swap so the rest of the deposit
+/// and the swap return match the pool ratio after the swap.
+///
+/// With the commission rate f taken from the return and kept in the pool,
+/// (deposit - x) / (offer_pool + x) == return / (ask_pool - return) gives
+/// x^2 + ((2 - f) * offer_pool - f * deposit) * x - deposit * offer_pool == 0
+fn compute_single_sided_swap_amount(
+    offer_pool: Uint128,
+    deposit: Uint128,
+    commission_rate: Decimal,
+) -> StdResult<Uint128> {
+    let offer_pool: Uint256 = offer_pool.into();
+    let deposit: Uint256 = deposit.into();
+    let commission_rate: Decimal256 = commission_rate.into();
+
+    let b_positive = offer_pool * (Decimal256::from_ratio(2u8, 1u8) - commission_rate);
+    let b_negative = deposit * commission_rate;
+    let c = deposit * offer_pool;
+
+    let (b, b_is_positive) = if b_positive >= b_negative {
+        (b_positive - b_negative, true)
+    } else {
+        (b_negative - b_positive, false)
+    };
+    let root = isqrt(
+        b.checked_mul(b)?
+            .checked_add(c.checked_mul(Uint256::from(4u8))?)?,
+    );
+    let swap_amount = if b_is_positive {
+        (root - b) / Uint256::from(2u8)
+    } else {
+        (root + b) / Uint256::from(2u8)
+    };
 
-    let share_ratio: Decimal = Decimal::from_ratio(amount, total_share);
-    let refund_assets: Vec<Asset> = pools
+    Ok(std::cmp::min(swap_amount, deposit).try_into()?)
+}
+
+/// Integer square root, rounded down
+fn isqrt(n: Uint256) -> Uint256 {
+    if n < Uint256::from(2u8) {
+        return n;
+    }
+
+    // Newton's method from an initial guess above the root
+    let mut x = n;
+    let mut y = (x + Uint256::one()) / Uint256::from(2u8);
+    while y < x {
+        x = y;
+        y = (x + n / x) / Uint256::from(2u8);
+    }
+
+    x
+}
+
+/// Returns the assets `amount` LP tokens are worth, floored as they are refunded on withdrawal
+fn get_share_in_assets(pools: &[Asset; 2], amount: Uint128, total_share: Uint128) -> Vec<Asset> {
+    let share_ratio: Decimal = if total_share.is_zero() {
+        Decimal::zero()
+    } else {
+        Decimal::from_ratio(amount, total_share)
+    };
+
+    pools
         .iter()
         .map(|a| Asset {
             info: a.info.clone(),
             amount: a.amount * share_ratio,
         })
-        .collect();
+        .collect()
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn withdraw_liquidity(
+    deps: DepsMut<TerraQuery>,
+    env: Env,
+    _info: MessageInfo,
+    sender: Addr,
+    receiver: Addr,
+    amount: Uint128,
+    min_assets: Option<Vec<Asset>>,
+    deadline: Option<u64>,
+) -> Result<Response<TerraMsg>, ContractError> {
+    assert_deadline(env.block.time.seconds(), deadline)?;
+
+    let pair_info: PairInfoRaw = PAIR_INFO.load(deps.storage)?;
+    let liquidity_addr: Addr = deps.api.addr_humanize(&pair_info.liquidity_token)?;
+
+    let pools: [Asset; 2] = read_pools(deps.as_ref(), &pair_info)?;
+    let total_share: Uint128 = query_token_info(&deps.querier, liquidity_addr)?.total_supply;
+
+    update_price_accumulators(
+        deps.storage,
+        [pools[0].amount, pools[1].amount],
+        env.block.time.seconds(),
+    )?;
+
+    let refund_assets: Vec<Asset> = get_share_in_assets(&pools, amount, total_share);
 
     assert_minimum_assets(refund_assets.to_vec(), min_assets)?;
 
+    RESERVES.save(
+        deps.storage,
+        &[
+            pools[0].amount.checked_sub(refund_assets[0].amount)?,
+            pools[1].amount.checked_sub(refund_assets[1].amount)?,
+        ],
+    )?;
+
     // update pool info
     Ok(Response::new()
         .add_messages(vec![
             refund_assets[0]
                 .clone()
-                .into_msg(&deps.querier, sender.clone())?,
+                .into_msg(&deps.querier, receiver.clone())?,
             refund_assets[1]
                 .clone()
-                .into_msg(&deps.querier, sender.clone())?,
+                .into_msg(&deps.querier, receiver.clone())?,
             // burn liquidity token
             CosmosMsg::Wasm(WasmMsg::Execute {
                 contract_addr: deps
@@ -467,51 +1784,203 @@ pub fn withdraw_liquidity(
                 "refund_assets",
                 &format!("{}, {}", refund_assets[0], refund_assets[1]),
             ),
+            ("receiver", receiver.as_str()),
         ]))
 }
 
+/// Withdraws liquidity into `ask_asset_info` only. The refund of the other asset
+/// is swapped to the ask asset against the pools left after the withdrawal,
+/// paying the regular commission to the remaining LPs.
+#[allow(clippy::too_many_arguments)]
+pub fn withdraw_liquidity_single(
+    deps: DepsMut<TerraQuery>,
+    env: Env,
+    _info: MessageInfo,
+    sender: Addr,
+    receiver: Addr,
+    amount: Uint128,
+    ask_asset_info: AssetInfo,
+    min_receive: Option<Uint128>,
+    deadline: Option<u64>,
+) -> Result<Response<TerraMsg>, ContractError> {
+    assert_deadline(env.block.time.seconds(), deadline)?;
+
+    // the other refund is swapped, a symmetric withdrawal stays open
+    if read_pool_status(deps.storage)?.swaps_paused {
+        return Err(ContractError::SwapsPaused {});
+    }
+
+    let pair_info: PairInfoRaw = PAIR_INFO.load(deps.storage)?;
+    let liquidity_addr: Addr = deps.api.addr_humanize(&pair_info.liquidity_token)?;
+
+    let pools: [Asset; 2] = read_pools(deps.as_ref(), &pair_info)?;
+    let total_share: Uint128 =
+        query_token_info(&deps.querier, liquidity_addr.clone())?.total_supply;
+
+    let (ask_index, offer_index) = if ask_asset_info.equal(&pools[0].info) {
+        (0, 1)
+    } else if ask_asset_info.equal(&pools[1].info) {
+        (1, 0)
+    } else {
+        return Err(ContractError::AssetMismatch {});
+    };
+
+    update_price_accumulators(
+        deps.storage,
+        [pools[0].amount, pools[1].amount],
+        env.block.time.seconds(),
+    )?;
+
+    let refund_assets: Vec<Asset> = get_share_in_assets(&pools, amount, total_share);
+
+    // swap the offer refund against the pools left after the withdrawal
+    let offer_refund = refund_assets[offer_index].clone();
+    let offer_pool = pools[offer_index].amount.checked_sub(offer_refund.amount)?;
+    let ask_pool = pools[ask_index]
+        .amount
+        .checked_sub(refund_assets[ask_index].amount)?;
+    let fee_config = read_fee_config(deps.storage)?;
+    let commission_rate = fee_config.commission_rate(offer_pool, offer_refund.amount);
+    let (return_amount, commission_amount) =
+        if offer_refund.amount.is_zero() || offer_pool.is_zero() || ask_pool.is_zero() {
+            (Uint128::zero(), Uint128::zero())
+        } else {
+            let swap = compute_swap(offer_pool, ask_pool, offer_refund.amount, commission_rate)?;
+            (swap.return_amount, swap.commission_amount)
+        };
+
+    // the offer refund valued at the price before the withdrawal, after the commission;
+    // a swap returning nothing for it is worse than the symmetric withdrawal
+    let refund_worth = if offer_refund.amount.is_zero() {
+        Uint128::zero()
+    } else {
+        offer_refund
+            .amount
+            .multiply_ratio(pools[ask_index].amount, pools[offer_index].amount)
+            * (Decimal::one() - commission_rate)
+    };
+    if return_amount.is_zero() && !refund_worth.is_zero() {
+        return Err(ContractError::WithdrawSingleWorseThanSymmetric {});
+    }
+    let swap_amount = offer_refund.amount;
+
+    let protocol_fee_amount = compute_protocol_fee(&fee_config, commission_amount);
+
+    let return_asset = Asset {
+        info: ask_asset_info,
+        amount: refund_assets[ask_index].amount.checked_add(return_amount)?,
+    };
+    if let Some(min_receive) = min_receive {
+        if return_asset.amount < min_receive {
+            return Err(ContractError::MinAmountAssertion {
+                min_asset: Asset {
+                    info: return_asset.info.clone(),
+                    amount: min_receive,
+                }
+                .to_string(),
+                asset: return_asset.to_string(),
+            });
+        }
+    }
+
+    // the swapped offer refund stays in the pool
+    let mut reserves = [Uint128::zero(); 2];
+    reserves[ask_index] = pools[ask_index]
+        .amount
+        .checked_sub(return_asset.amount)?
+        .checked_sub(protocol_fee_amount)?;
+    reserves[offer_index] = pools[offer_index].amount;
+    RESERVES.save(deps.storage, &reserves)?;
+    accrue_protocol_fee(deps.storage, ask_index, protocol_fee_amount)?;
+    track_commission(
+        deps.storage,
+        ask_index,
+        commission_amount,
+        env.block.time.seconds(),
+    )?;
+
+    let mut messages: Vec<CosmosMsg<TerraMsg>> = vec![];
+    if !return_asset.amount.is_zero() {
+        messages.push(
+            return_asset
+                .clone()
+                .into_msg(&deps.querier, receiver.clone())?,
+        );
+    }
+
+    // burn liquidity token
+    messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: liquidity_addr.to_string(),
+        msg: to_binary(&Cw20ExecuteMsg::Burn { amount })?,
+        funds: vec![],
+    }));
+
+    Ok(Response::new().add_messages(messages).add_attributes(vec![
+        ("action", "withdraw_liquidity_single"),
+        ("sender", sender.as_str()),
+        ("withdrawn_share", &amount.to_string()),
+        (
+            "refund_assets",
+            &format!("{}, {}", refund_assets[0], refund_assets[1]),
+        ),
+        ("swap_amount", &swap_amount.to_string()),
+        ("return_asset", &return_asset.to_string()),
+        ("commission_amount", &commission_amount.to_string()),
+        ("protocol_fee_amount", &protocol_fee_amount.to_string()),
+        ("receiver", receiver.as_str()),
+    ]))
+}
+
 // CONTRACT - a user must do token approval
 #[allow(clippy::too_many_arguments)]
 pub fn swap(
     deps: DepsMut<TerraQuery>,
     env: Env,
-    info: MessageInfo,
     sender: Addr,
     offer_asset: Asset,
     belief_price: Option<Decimal>,
+    belief_price_direction: Option<BeliefPriceDirection>,
     max_spread: Option<Decimal>,
     to: Option<Addr>,
     deadline: Option<u64>,
+    to_msg: Option<Binary>,
+    ask_amount: Option<Uint128>,
+    referral: Option<Referral>,
 ) -> Result<Response<TerraMsg>, ContractError> {
     assert_deadline(env.block.time.seconds(), deadline)?;
 
-    offer_asset.assert_sent_native_token_balance(&info)?;
+    if offer_asset.amount.is_zero() {
+        return Err(ContractError::ZeroOfferAmount {});
+    }
+
+    if read_pool_status(deps.storage)?.swaps_paused {
+        return Err(ContractError::SwapsPaused {});
+    }
+
+    // the hook message is delivered to the `to` contract only
+    if to_msg.is_some() && to.is_none() {
+        return Err(ContractError::ToMsgWithoutTo {});
+    }
+
+    let referral = validate_referral(deps.as_ref(), referral)?;
 
     let pair_info: PairInfoRaw = PAIR_INFO.load(deps.storage)?;
 
-    let pools: [Asset; 2] = pair_info.query_pools(&deps.querier, deps.api, env.contract.address)?;
+    let pools: [Asset; 2] = read_pools(deps.as_ref(), &pair_info)?;
 
     let offer_pool: Asset;
     let ask_pool: Asset;
 
     let offer_decimal: u8;
     let ask_decimal: u8;
-    // If the asset balance is already increased
-    // To calculated properly we should subtract user deposit from the pool
     if offer_asset.info.equal(&pools[0].info) {
-        offer_pool = Asset {
-            amount: pools[0].amount.checked_sub(offer_asset.amount)?,
-            info: pools[0].info.clone(),
-        };
+        offer_pool = pools[0].clone();
         ask_pool = pools[1].clone();
 
         offer_decimal = pair_info.asset_decimals[0];
         ask_decimal = pair_info.asset_decimals[1];
     } else if offer_asset.info.equal(&pools[1].info) {
-        offer_pool = Asset {
-            amount: pools[1].amount.checked_sub(offer_asset.amount)?,
-            info: pools[1].info.clone(),
-        };
+        offer_pool = pools[1].clone();
         ask_pool = pools[0].clone();
 
         offer_decimal = pair_info.asset_decimals[1];
@@ -519,16 +1988,74 @@ pub fn swap(
     } else {
         return Err(ContractError::AssetMismatch {});
     }
+    assert_liquidity(offer_pool.amount, ask_pool.amount)?;
+
+    update_price_accumulators(
+        deps.storage,
+        [pools[0].amount, pools[1].amount],
+        env.block.time.seconds(),
+    )?;
 
+    let fee_config = read_fee_config(deps.storage)?;
     let offer_amount = offer_asset.amount;
-    let (return_amount, spread_amount, commission_amount) =
-        compute_swap(offer_pool.amount, ask_pool.amount, offer_amount)?;
+    let SwapComputation {
+        return_amount,
+        spread_amount,
+        commission_amount,
+    } = compute_swap(
+        offer_pool.amount,
+        ask_pool.amount,
+        offer_amount,
+        fee_config.commission_rate(offer_pool.amount, offer_amount),
+    )?;
+
+    // an exact-out swap returns the ask amount only, the rounding surplus stays in the pool
+    let (return_amount, spread_amount) = match ask_amount {
+        Some(ask_amount) => (
+            ask_amount,
+            spread_amount.checked_add(return_amount.checked_sub(ask_amount)?)?,
+        ),
+        None => (return_amount, spread_amount),
+    };
+
+    if let Some(limit) = trade_limit(MAX_TRADE_RATIO.may_load(deps.storage)?, ask_pool.amount) {
+        if return_amount > limit {
+            return Err(ContractError::TradeLimitExceeded {
+                limit: limit.to_string(),
+                amount: return_amount.to_string(),
+            });
+        }
+    }
 
     let return_asset = Asset {
         info: ask_pool.info.clone(),
         amount: return_amount,
     };
 
+    // the pool cap bounds the max spread, and applies when none is given
+    let max_spread_cap = MAX_SPREAD_CAP.may_load(deps.storage)?;
+    let (max_spread, max_spread_clamped) = match (max_spread, max_spread_cap) {
+        (Some(max_spread), Some(cap)) if max_spread > cap => (Some(cap), true),
+        (None, Some(cap)) => (Some(cap), true),
+        (max_spread, _) => (max_spread, false),
+    };
+
+    // belief prices are checked as offer per ask, whole units
+    let belief_price = match (belief_price, belief_price_direction) {
+        (Some(belief_price), _) if belief_price.is_zero() => {
+            return Err(ContractError::ZeroBeliefPrice {})
+        }
+        (Some(belief_price), Some(BeliefPriceDirection::AskPerOffer)) => {
+            Some(Decimal::one() / belief_price)
+        }
+        (belief_price, _) => belief_price,
+    };
+    let expected_return = belief_price
+        .map(|belief_price| {
+            compute_expected_return(offer_amount, belief_price, offer_decimal, ask_decimal)
+        })
+        .transpose()?;
+
     // check max spread limit if exist
     assert_max_spread(
         belief_price,
@@ -540,42 +2067,478 @@ pub fn swap(
         ask_decimal,
     )?;
 
-    // compute tax
-    let tax_amount = return_asset.compute_tax(&deps.querier)?;
-    let receiver = to.unwrap_or_else(|| sender.clone());
+    // the referral cut is taken from the return before it is sent
+    let referral_amount = referral.as_ref().map_or_else(Uint128::zero, |(_, bps)| {
+        compute_referral_amount(return_amount, *bps)
+    });
+    let return_asset = Asset {
+        info: ask_pool.info.clone(),
+        amount: return_amount.checked_sub(referral_amount)?,
+    };
+
+    // the offer is added to the pool, the return and the protocol fee leave it
+    let protocol_fee_amount = compute_protocol_fee(&fee_config, commission_amount);
+    let offer_reserve = offer_pool.amount.checked_add(offer_amount)?;
+    let ask_reserve = ask_pool
+        .amount
+        .checked_sub(return_amount)?
+        .checked_sub(protocol_fee_amount)?;
+    let ask_index = if offer_asset.info.equal(&pools[0].info) {
+        RESERVES.save(deps.storage, &[offer_reserve, ask_reserve])?;
+        1
+    } else {
+        RESERVES.save(deps.storage, &[ask_reserve, offer_reserve])?;
+        0
+    };
+
+    // the lp share of the commission stays in the pool,
+    // the protocol share accrues until it is collected
+    accrue_protocol_fee(deps.storage, ask_index, protocol_fee_amount)?;
+    track_commission(
+        deps.storage,
+        ask_index,
+        commission_amount,
+        env.block.time.seconds(),
+    )?;
+
+    // compute tax
+    let tax_amount = return_asset.compute_tax(&deps.querier)?;
+    let receiver = to.unwrap_or_else(|| sender.clone());
+
+    let mut messages: Vec<CosmosMsg<TerraMsg>> = vec![];
+    if !return_asset.amount.is_zero() {
+        messages.push(return_asset.clone().into_taxed_send_msg(
+            &deps.querier,
+            receiver.clone(),
+            to_msg,
+        )?);
+    }
+    if let (Some((referral_addr, _)), false) = (&referral, referral_amount.is_zero()) {
+        messages.push(
+            Asset {
+                info: ask_pool.info.clone(),
+                amount: referral_amount,
+            }
+            .into_msg(&deps.querier, referral_addr.clone())?,
+        );
+    }
+
+    let swap_event = Event::new("terraswap_swap").add_attributes(vec![
+        ("sender", sender.to_string()),
+        ("receiver", receiver.to_string()),
+        ("offer_asset", offer_asset.info.to_string()),
+        ("ask_asset", ask_pool.info.to_string()),
+        ("offer_amount", offer_amount.to_string()),
+        ("return_amount", return_amount.to_string()),
+        ("spread_amount", spread_amount.to_string()),
+        ("commission_amount", commission_amount.to_string()),
+        ("tax_amount", tax_amount.to_string()),
+    ]);
+
+    // send collateral token from the contract to a user
+    let mut response = Response::new()
+        .add_messages(messages)
+        .add_event(swap_event)
+        .add_attributes(vec![
+            ("action", "swap"),
+            ("sender", sender.as_str()),
+            ("receiver", receiver.as_str()),
+            ("offer_asset", &offer_asset.info.to_string()),
+            ("ask_asset", &ask_pool.info.to_string()),
+            ("offer_amount", &offer_amount.to_string()),
+            ("return_amount", &return_amount.to_string()),
+            ("tax_amount", &tax_amount.to_string()),
+            ("spread_amount", &spread_amount.to_string()),
+            ("commission_amount", &commission_amount.to_string()),
+            ("protocol_fee_amount", &protocol_fee_amount.to_string()),
+            (
+                "price",
+                &display_price(return_amount, ask_decimal, offer_amount, offer_decimal).to_string(),
+            ),
+            (
+                "pool_price",
+                &display_price(ask_reserve, ask_decimal, offer_reserve, offer_decimal).to_string(),
+            ),
+        ]);
+    if max_spread_clamped {
+        response = response.add_attribute("max_spread_clamped", "true");
+    }
+    if let Some(expected_return) = expected_return {
+        response = response.add_attribute("expected_return", expected_return.to_string());
+    }
+    if let Some((referral_addr, _)) = referral {
+        response = response.add_attributes(vec![
+            ("referral_address", referral_addr.to_string()),
+            ("referral_amount", referral_amount.to_string()),
+            ("net_return_amount", return_asset.amount.to_string()),
+        ]);
+    }
+
+    Ok(response)
+}
+
+/// Validates the referral address and its cut against the cap of the factory
+fn validate_referral(
+    deps: Deps<TerraQuery>,
+    referral: Option<Referral>,
+) -> Result<Option<(Addr, u16)>, ContractError> {
+    let referral = match referral {
+        Some(referral) => referral,
+        None => return Ok(None),
+    };
+
+    let factory_config: FactoryConfigResponse = deps
+        .querier
+        .query_wasm_smart(FACTORY.load(deps.storage)?, &FactoryQueryMsg::Config {})?;
+    if referral.bps > factory_config.max_referral_bps {
+        return Err(ContractError::ReferralBpsTooHigh {
+            max_referral_bps: factory_config.max_referral_bps,
+        });
+    }
+
+    Ok(Some((
+        deps.api.addr_validate(&referral.address)?,
+        referral.bps,
+    )))
+}
+
+/// The return of `offer_amount` at an offer per ask `belief_price` in whole units,
+/// in the ask asset
+fn compute_expected_return(
+    offer_amount: Uint128,
+    belief_price: Decimal,
+    offer_decimal: u8,
+    ask_decimal: u8,
+) -> StdResult<Uint128> {
+    let belief_price: Decimal256 = belief_price.into();
+    let offer_amount = Uint256::from(offer_amount)
+        .checked_mul(Uint256::from(10u8).checked_pow(ask_decimal.into())?)?;
+    let expected_return = offer_amount.multiply_ratio(
+        Decimal256::one().atomics(),
+        belief_price
+            .atomics()
+            .checked_mul(Uint256::from(10u8).checked_pow(offer_decimal.into())?)?,
+    );
+
+    Ok(expected_return.try_into()?)
+}
+
+/// The referral cut of a return, rounded down in favor of the receiver
+fn compute_referral_amount(return_amount: Uint128, bps: u16) -> Uint128 {
+    return_amount.multiply_ratio(bps, 10_000u16)
+}
+
+/// The commitment of a swap, the hex encoded sha256 of the JSON params,
+/// the salt and the sender
+pub fn commit_swap_hash(params: &CommitSwapParams, salt: &str, sender: &Addr) -> StdResult<String> {
+    let mut hasher = Sha256::new();
+    hasher.update(to_binary(params)?.as_slice());
+    hasher.update(salt.as_bytes());
+    hasher.update(sender.as_bytes());
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+pub fn commit_swap(
+    deps: DepsMut<TerraQuery>,
+    env: Env,
+    info: MessageInfo,
+    hash: String,
+) -> Result<Response<TerraMsg>, ContractError> {
+    COMMITMENTS.save(
+        deps.storage,
+        &info.sender,
+        &Commitment {
+            hash: hash.clone(),
+            height: env.block.height,
+        },
+    )?;
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "commit_swap"),
+        ("sender", info.sender.as_str()),
+        ("hash", &hash),
+    ]))
+}
+
+/// Executes the committed swap of `sender` at the reserves of the reveal,
+/// a commitment is revealed once
+pub fn reveal_swap(
+    deps: DepsMut<TerraQuery>,
+    env: Env,
+    sender: Addr,
+    params: CommitSwapParams,
+    salt: String,
+) -> Result<Response<TerraMsg>, ContractError> {
+    let commitment = COMMITMENTS
+        .may_load(deps.storage, &sender)?
+        .ok_or(ContractError::CommitmentNotFound {})?;
+    let commit_config = read_commit_config(deps.storage)?;
+
+    let reveal_height = commitment.height + commit_config.min_delay_blocks;
+    if env.block.height < reveal_height {
+        return Err(ContractError::CommitmentTooEarly { reveal_height });
+    }
+    if env.block.height > commitment.height + commit_config.expiry_blocks {
+        return Err(ContractError::CommitmentExpired {});
+    }
+    if commit_swap_hash(&params, &salt, &sender)? != commitment.hash {
+        return Err(ContractError::CommitmentMismatch {});
+    }
+    COMMITMENTS.remove(deps.storage, &sender);
+
+    let to_addr = if let Some(to_addr) = params.to {
+        Some(deps.api.addr_validate(&to_addr)?)
+    } else {
+        None
+    };
+
+    let response = swap(
+        deps,
+        env,
+        sender,
+        params.offer_asset,
+        params.belief_price,
+        None,
+        params.max_spread,
+        to_addr,
+        None,
+        None,
+        None,
+        None,
+    )?;
+
+    Ok(response.add_attribute("commitment", commitment.hash))
+}
+
+pub fn cancel_commitment(
+    deps: DepsMut<TerraQuery>,
+    info: MessageInfo,
+) -> Result<Response<TerraMsg>, ContractError> {
+    if !COMMITMENTS.has(deps.storage, &info.sender) {
+        return Err(ContractError::CommitmentNotFound {});
+    }
+    COMMITMENTS.remove(deps.storage, &info.sender);
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "cancel_commitment"),
+        ("sender", info.sender.as_str()),
+    ]))
+}
+
+pub fn update_commit_config(
+    deps: DepsMut<TerraQuery>,
+    info: MessageInfo,
+    min_delay_blocks: u64,
+    expiry_blocks: u64,
+) -> Result<Response<TerraMsg>, ContractError> {
+    assert_pair_admin(deps.as_ref(), &info)?;
+
+    if min_delay_blocks == 0 || min_delay_blocks > expiry_blocks {
+        return Err(ContractError::InvalidCommitConfig {});
+    }
+    COMMIT_CONFIG.save(
+        deps.storage,
+        &CommitConfig {
+            min_delay_blocks,
+            expiry_blocks,
+        },
+    )?;
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "update_commit_config"),
+        ("min_delay_blocks", &min_delay_blocks.to_string()),
+        ("expiry_blocks", &expiry_blocks.to_string()),
+    ]))
+}
+
+/// Swaps the least offer that returns exactly `ask_asset`, computed with the reverse
+/// simulation, and refunds the rest of `max_offer_asset`
+pub fn swap_exact_out(
+    mut deps: DepsMut<TerraQuery>,
+    env: Env,
+    sender: Addr,
+    max_offer_asset: Asset,
+    ask_asset: Asset,
+    to: Option<Addr>,
+    deadline: Option<u64>,
+) -> Result<Response<TerraMsg>, ContractError> {
+    let pair_info: PairInfoRaw = PAIR_INFO.load(deps.storage)?;
+    let pools: [Asset; 2] = read_pools(deps.as_ref(), &pair_info)?;
+
+    let (offer_pool, ask_pool) = if max_offer_asset.info.equal(&pools[0].info) {
+        (&pools[0], &pools[1])
+    } else if max_offer_asset.info.equal(&pools[1].info) {
+        (&pools[1], &pools[0])
+    } else {
+        return Err(ContractError::AssetMismatch {});
+    };
+    if !ask_asset.info.equal(&ask_pool.info) {
+        return Err(ContractError::AssetMismatch {});
+    }
+    assert_liquidity(offer_pool.amount, ask_pool.amount)?;
+
+    // a native ask is sent with the tax deducted, the swap returns the tax on top
+    let return_amount = match &ask_asset.info {
+        AssetInfo::NativeToken { denom } => {
+            let mut return_amount = ask_asset.amount.checked_add(compute_reverse_tax(
+                &deps.querier,
+                ask_asset.amount,
+                denom.to_string(),
+            )?)?;
+            while return_amount.checked_sub(compute_tax(
+                &deps.querier,
+                return_amount,
+                denom.to_string(),
+            )?)? < ask_asset.amount
+            {
+                return_amount += Uint128::one();
+            }
+            return_amount
+        }
+        AssetInfo::Token { .. } => ask_asset.amount,
+    };
+
+    let OfferComputation { offer_amount, .. } = compute_tiered_offer_amount(
+        &read_fee_config(deps.storage)?,
+        offer_pool.amount,
+        ask_pool.amount,
+        return_amount,
+    )?;
+
+    // the whole attached offer is credited, only the spent part must fit in it
+    if offer_amount > max_offer_asset.amount {
+        return Err(ContractError::MaxOfferAmountAssertion {
+            offer_amount: offer_amount.to_string(),
+            max_offer_amount: max_offer_asset.amount.to_string(),
+        });
+    }
+
+    let response = swap(
+        deps.branch(),
+        env,
+        sender.clone(),
+        Asset {
+            info: max_offer_asset.info.clone(),
+            amount: offer_amount,
+        },
+        None,
+        None,
+        None,
+        to,
+        deadline,
+        None,
+        Some(return_amount),
+        None,
+    )?;
 
+    let refund = Asset {
+        info: max_offer_asset.info,
+        amount: max_offer_asset.amount.checked_sub(offer_amount)?,
+    };
     let mut messages: Vec<CosmosMsg<TerraMsg>> = vec![];
-    if !return_amount.is_zero() {
-        messages.push(return_asset.into_msg(&deps.querier, receiver.clone())?);
+    if !refund.amount.is_zero() {
+        messages.push(refund.clone().into_msg(&deps.querier, sender)?);
     }
 
-    // 1. send collateral token from the contract to a user
-    // 2. send inactive commission to collector
-    Ok(Response::new().add_messages(messages).add_attributes(vec![
-        ("action", "swap"),
-        ("sender", sender.as_str()),
-        ("receiver", receiver.as_str()),
-        ("offer_asset", &offer_asset.info.to_string()),
-        ("ask_asset", &ask_pool.info.to_string()),
-        ("offer_amount", &offer_amount.to_string()),
-        ("return_amount", &return_amount.to_string()),
-        ("tax_amount", &tax_amount.to_string()),
-        ("spread_amount", &spread_amount.to_string()),
-        ("commission_amount", &commission_amount.to_string()),
+    Ok(response.add_messages(messages).add_attributes(vec![
+        ("offer_spent", offer_amount.to_string()),
+        ("refund", refund.amount.to_string()),
     ]))
 }
 
+/// Returns the price of the offer asset in the ask asset in whole units of both,
+/// `ask_amount / 10^ask_decimal` over `offer_amount / 10^offer_decimal`
+fn display_price(
+    ask_amount: Uint128,
+    ask_decimal: u8,
+    offer_amount: Uint128,
+    offer_decimal: u8,
+) -> Decimal256 {
+    let scale = |amount: Uint128, decimal: u8| {
+        Uint256::from(10u8)
+            .checked_pow(decimal.into())
+            .and_then(|unit| Uint256::from(amount).checked_mul(unit))
+    };
+
+    match (
+        scale(ask_amount, offer_decimal),
+        scale(offer_amount, ask_decimal),
+    ) {
+        (Ok(ask_amount), Ok(offer_amount)) => {
+            Decimal256::checked_from_ratio(ask_amount, offer_amount).unwrap_or_default()
+        }
+        _ => Decimal256::zero(),
+    }
+}
+
+/// Returns the protocol share of the commission, rounded down in favor of the pool
+fn compute_protocol_fee(fee_config: &FeeConfig, commission_amount: Uint128) -> Uint128 {
+    let total_fee_rate = fee_config.total_fee_rate();
+    if total_fee_rate.is_zero() {
+        return Uint128::zero();
+    }
+
+    commission_amount.multiply_ratio(
+        fee_config.protocol_fee_rate.atomics(),
+        total_fee_rate.atomics(),
+    )
+}
+
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps<TerraQuery>, _env: Env, msg: QueryMsg) -> Result<Binary, ContractError> {
+pub fn query(deps: Deps<TerraQuery>, env: Env, msg: QueryMsg) -> Result<Binary, ContractError> {
     match msg {
         QueryMsg::Pair {} => Ok(to_binary(&query_pair_info(deps)?)?),
         QueryMsg::Pool {} => Ok(to_binary(&query_pool(deps)?)?),
-        QueryMsg::Simulation { offer_asset } => {
-            Ok(to_binary(&query_simulation(deps, offer_asset)?)?)
-        }
-        QueryMsg::ReverseSimulation { ask_asset } => {
-            Ok(to_binary(&query_reverse_simulation(deps, ask_asset)?)?)
+        QueryMsg::Share { amount } => Ok(to_binary(&query_share(deps, amount)?)?),
+        QueryMsg::Deposits { address } => Ok(to_binary(&query_deposits(deps, address)?)?),
+        QueryMsg::Simulation {
+            offer_asset,
+            referral,
+        } => Ok(to_binary(&query_simulation(deps, offer_asset, referral)?)?),
+        QueryMsg::ReverseSimulation {
+            ask_asset,
+            referral,
+        } => Ok(to_binary(&query_reverse_simulation(
+            deps, ask_asset, referral,
+        )?)?),
+        QueryMsg::SimulationBatch {
+            offer_asset_info,
+            amounts,
+        } => Ok(to_binary(&query_simulation_batch(
+            deps,
+            offer_asset_info,
+            amounts,
+        )?)?),
+        QueryMsg::ReverseSimulationBatch {
+            ask_asset_info,
+            amounts,
+        } => Ok(to_binary(&query_reverse_simulation_batch(
+            deps,
+            ask_asset_info,
+            amounts,
+        )?)?),
+        QueryMsg::FeeConfig {} => Ok(to_binary(&read_fee_config(deps.storage)?)?),
+        QueryMsg::FeeStats {} => Ok(to_binary(&query_fee_stats(deps)?)?),
+        QueryMsg::Config {} => Ok(to_binary(&query_config(deps)?)?),
+        QueryMsg::PoolStatus {} => Ok(to_binary(&read_pool_status(deps.storage)?)?),
+        QueryMsg::Commitment { address } => Ok(to_binary(&query_commitment(deps, address)?)?),
+        QueryMsg::CommitConfig {} => Ok(to_binary(&read_commit_config(deps.storage)?)?),
+        QueryMsg::CumulativePrices {} => Ok(to_binary(&query_cumulative_prices(deps, env)?)?),
+        QueryMsg::ObservedTwap { window_seconds } => {
+            Ok(to_binary(&query_observed_twap(deps, env, window_seconds)?)?)
         }
+        QueryMsg::SimulateProvideLiquiditySingle { asset } => Ok(to_binary(
+            &query_simulate_provide_liquidity_single(deps, asset)?,
+        )?),
+        QueryMsg::SimulateProvideLiquidity {
+            assets,
+            slippage_tolerance,
+        } => Ok(to_binary(&query_simulate_provide_liquidity(
+            deps,
+            assets,
+            slippage_tolerance,
+        )?)?),
     }
 }
 
@@ -588,8 +2551,7 @@ pub fn query_pair_info(deps: Deps<TerraQuery>) -> Result<PairInfo, ContractError
 
 pub fn query_pool(deps: Deps<TerraQuery>) -> Result<PoolResponse, ContractError> {
     let pair_info: PairInfoRaw = PAIR_INFO.load(deps.storage)?;
-    let contract_addr = deps.api.addr_humanize(&pair_info.contract_addr)?;
-    let assets: [Asset; 2] = pair_info.query_pools(&deps.querier, deps.api, contract_addr)?;
+    let assets: [Asset; 2] = read_pools(deps, &pair_info)?;
     let total_share: Uint128 = query_token_info(
         &deps.querier,
         deps.api.addr_humanize(&pair_info.liquidity_token)?,
@@ -604,161 +2566,514 @@ pub fn query_pool(deps: Deps<TerraQuery>) -> Result<PoolResponse, ContractError>
     Ok(resp)
 }
 
-pub fn query_simulation(
+pub fn query_fee_stats(deps: Deps<TerraQuery>) -> Result<FeeStatsResponse, ContractError> {
+    let pair_info: PairInfoRaw = PAIR_INFO.load(deps.storage)?;
+    let reserves: [Asset; 2] = read_pools(deps, &pair_info)?;
+    let (cumulative_fees, tracked_since) = match FEE_TRACKER.may_load(deps.storage)? {
+        Some(fee_tracker) => (fee_tracker.cumulative_fees, Some(fee_tracker.tracked_since)),
+        None => ([Uint128::zero(), Uint128::zero()], None),
+    };
+
+    Ok(FeeStatsResponse {
+        cumulative_fees: [
+            Asset {
+                info: reserves[0].info.clone(),
+                amount: cumulative_fees[0],
+            },
+            Asset {
+                info: reserves[1].info.clone(),
+                amount: cumulative_fees[1],
+            },
+        ],
+        tracked_since,
+        reserves,
+    })
+}
+
+/// Returns the assets `amount` LP tokens are worth at the current reserves
+pub fn query_share(deps: Deps<TerraQuery>, amount: Uint128) -> Result<Vec<Asset>, ContractError> {
+    let pair_info: PairInfoRaw = PAIR_INFO.load(deps.storage)?;
+    let pools: [Asset; 2] = read_pools(deps, &pair_info)?;
+    let total_share: Uint128 = query_token_info(
+        &deps.querier,
+        deps.api.addr_humanize(&pair_info.liquidity_token)?,
+    )?
+    .total_supply;
+
+    Ok(get_share_in_assets(&pools, amount, total_share))
+}
+
+pub fn query_deposits(
     deps: Deps<TerraQuery>,
-    offer_asset: Asset,
-) -> Result<SimulationResponse, ContractError> {
+    address: String,
+) -> Result<[Asset; 2], ContractError> {
+    let pair_info: PairInfo = PAIR_INFO.load(deps.storage)?.to_normal(deps.api)?;
+    let deposits = read_deposits(deps.storage, &deps.api.addr_validate(&address)?)?;
+
+    Ok([
+        Asset {
+            info: pair_info.asset_infos[0].clone(),
+            amount: deposits[0],
+        },
+        Asset {
+            info: pair_info.asset_infos[1].clone(),
+            amount: deposits[1],
+        },
+    ])
+}
+
+/// Returns the accumulators brought up to the current block with the current reserves
+pub fn query_cumulative_prices(
+    deps: Deps<TerraQuery>,
+    env: Env,
+) -> Result<CumulativePricesResponse, ContractError> {
+    let pair_info: PairInfoRaw = PAIR_INFO.load(deps.storage)?;
+    let assets: [Asset; 2] = read_pools(deps, &pair_info)?;
+
+    let block_time = env.block.time.seconds();
+    let mut accumulators = PRICE_ACCUMULATORS
+        .may_load(deps.storage)?
+        .unwrap_or_else(|| PriceAccumulators::new(block_time));
+    accumulators.accumulate([assets[0].amount, assets[1].amount], block_time);
+
+    Ok(CumulativePricesResponse {
+        assets,
+        price0_cumulative_last: accumulators.price0_cumulative_last,
+        price1_cumulative_last: accumulators.price1_cumulative_last,
+        block_time_last: accumulators.block_time_last,
+    })
+}
+
+/// Averages the prices since the oldest observation within the last `window_seconds`
+pub fn query_observed_twap(
+    deps: Deps<TerraQuery>,
+    env: Env,
+    window_seconds: u64,
+) -> Result<ObservedTwapResponse, ContractError> {
+    let current = query_cumulative_prices(deps, env)?;
+    let window_start = current.block_time_last.saturating_sub(window_seconds);
+
+    let mut oldest: Option<PriceAccumulators> = None;
+    for slot in 0..OBSERVATION_SLOTS {
+        if let Some(observation) = PRICE_OBSERVATIONS.may_load(deps.storage, slot)? {
+            if observation.block_time_last >= window_start
+                && observation.block_time_last < current.block_time_last
+                && oldest
+                    .as_ref()
+                    .map(|oldest| observation.block_time_last < oldest.block_time_last)
+                    .unwrap_or(true)
+            {
+                oldest = Some(observation);
+            }
+        }
+    }
+    let oldest = oldest.ok_or(ContractError::InsufficientPriceHistory { window_seconds })?;
+
+    let elapsed = Uint256::from(current.block_time_last - oldest.block_time_last);
+    let scale = Uint256::from(1_000_000_000_000_000_000u128);
+    Ok(ObservedTwapResponse {
+        price0_average: Decimal256::from_ratio(
+            current
+                .price0_cumulative_last
+                .wrapping_sub(oldest.price0_cumulative_last),
+            elapsed * scale,
+        ),
+        price1_average: Decimal256::from_ratio(
+            current
+                .price1_cumulative_last
+                .wrapping_sub(oldest.price1_cumulative_last),
+            elapsed * scale,
+        ),
+        observed_since: oldest.block_time_last,
+    })
+}
+
+pub fn query_commitment(
+    deps: Deps<TerraQuery>,
+    address: String,
+) -> Result<Option<CommitmentResponse>, ContractError> {
+    let address = deps.api.addr_validate(&address)?;
+    let commitment = COMMITMENTS.may_load(deps.storage, &address)?;
+
+    Ok(commitment.map(|commitment| CommitmentResponse {
+        hash: commitment.hash,
+        height: commitment.height,
+    }))
+}
+
+pub fn query_config(deps: Deps<TerraQuery>) -> Result<ConfigResponse, ContractError> {
+    Ok(ConfigResponse {
+        factory: FACTORY.load(deps.storage)?,
+        pair_admin: PAIR_ADMIN.may_load(deps.storage)?,
+        max_spread_cap: MAX_SPREAD_CAP.may_load(deps.storage)?,
+        max_trade_ratio: MAX_TRADE_RATIO.may_load(deps.storage)?,
+    })
+}
+
+pub fn query_simulate_provide_liquidity_single(
+    deps: Deps<TerraQuery>,
+    asset: Asset,
+) -> Result<SimulateProvideLiquiditySingleResponse, ContractError> {
     let pair_info: PairInfoRaw = PAIR_INFO.load(deps.storage)?;
 
-    let contract_addr = deps.api.addr_humanize(&pair_info.contract_addr)?;
-    let pools: [Asset; 2] = pair_info.query_pools(&deps.querier, deps.api, contract_addr)?;
+    let pools: [Asset; 2] = read_pools(deps, &pair_info)?;
 
     let offer_pool: Asset;
     let ask_pool: Asset;
-    if offer_asset.info.equal(&pools[0].info) {
+    if asset.info.equal(&pools[0].info) {
         offer_pool = pools[0].clone();
         ask_pool = pools[1].clone();
-    } else if offer_asset.info.equal(&pools[1].info) {
+    } else if asset.info.equal(&pools[1].info) {
         offer_pool = pools[1].clone();
         ask_pool = pools[0].clone();
     } else {
         return Err(ContractError::AssetMismatch {});
     }
 
-    let (return_amount, spread_amount, commission_amount) =
-        compute_swap(offer_pool.amount, ask_pool.amount, offer_asset.amount)?;
+    let total_share = query_token_info(
+        &deps.querier,
+        deps.api.addr_humanize(&pair_info.liquidity_token)?,
+    )?
+    .total_supply;
+    let provision = compute_provide_single(
+        &offer_pool,
+        &ask_pool,
+        asset.amount,
+        total_share,
+        &read_fee_config(deps.storage)?,
+    )?;
 
-    Ok(SimulationResponse {
-        return_amount,
-        spread_amount,
-        commission_amount,
+    let price_impact = if provision.spread_amount.is_zero() {
+        Decimal::zero()
+    } else {
+        Decimal::from_ratio(
+            provision.spread_amount,
+            provision.return_amount + provision.spread_amount,
+        )
+    };
+
+    Ok(SimulateProvideLiquiditySingleResponse {
+        lp_amount: provision.share,
+        swap_amount: provision.swap_amount,
+        price_impact,
+    })
+}
+
+pub fn query_simulate_provide_liquidity(
+    deps: Deps<TerraQuery>,
+    assets: Vec<Asset>,
+    slippage_tolerance: Option<Decimal>,
+) -> Result<SimulateProvideLiquidityResponse, ContractError> {
+    let pair_info: PairInfoRaw = PAIR_INFO.load(deps.storage)?;
+    let pools: [Asset; 2] = read_pools(deps, &pair_info)?;
+    let assets = normalize_assets(&pools, &assets)?;
+    let deposits = [assets[0].amount, assets[1].amount];
+
+    let total_share = query_token_info(
+        &deps.querier,
+        deps.api.addr_humanize(&pair_info.liquidity_token)?,
+    )?
+    .total_supply;
+    let provision = compute_provide(
+        [pools[0].amount, pools[1].amount],
+        deposits,
+        total_share,
+        slippage_tolerance,
+    )?;
+
+    let mut used_amounts: Vec<Asset> = vec![];
+    let mut refund_amounts: Vec<Asset> = vec![];
+    for (i, pool) in pools.iter().enumerate() {
+        used_amounts.push(Asset {
+            info: pool.info.clone(),
+            amount: provision.used_amounts[i],
+        });
+        refund_amounts.push(Asset {
+            info: pool.info.clone(),
+            amount: deposits[i].checked_sub(provision.used_amounts[i])?,
+        });
+    }
+
+    Ok(SimulateProvideLiquidityResponse {
+        lp_minted: provision.share,
+        used_amounts,
+        refund_amounts,
     })
 }
 
+pub fn query_simulation(
+    deps: Deps<TerraQuery>,
+    offer_asset: Asset,
+    referral: Option<Referral>,
+) -> Result<SimulationResponse, ContractError> {
+    let referral = validate_referral(deps, referral)?;
+    let (offer_pool, ask_pool) = read_swap_pools(deps, &offer_asset.info)?;
+
+    simulate_swap(
+        &read_fee_config(deps.storage)?,
+        MAX_TRADE_RATIO.may_load(deps.storage)?,
+        offer_pool.amount,
+        ask_pool.amount,
+        offer_asset.amount,
+        referral.map(|(_, bps)| bps),
+    )
+}
+
 pub fn query_reverse_simulation(
     deps: Deps<TerraQuery>,
     ask_asset: Asset,
+    referral: Option<Referral>,
 ) -> Result<ReverseSimulationResponse, ContractError> {
-    let pair_info: PairInfoRaw = PAIR_INFO.load(deps.storage)?;
+    let referral = validate_referral(deps, referral)?;
+    let (ask_pool, offer_pool) = read_swap_pools(deps, &ask_asset.info)?;
+
+    simulate_reverse_swap(
+        &read_fee_config(deps.storage)?,
+        MAX_TRADE_RATIO.may_load(deps.storage)?,
+        offer_pool.amount,
+        ask_pool.amount,
+        ask_asset.amount,
+        referral.map(|(_, bps)| bps),
+    )
+}
 
-    let contract_addr = deps.api.addr_humanize(&pair_info.contract_addr)?;
-    let pools: [Asset; 2] = pair_info.query_pools(&deps.querier, deps.api, contract_addr)?;
+pub fn query_simulation_batch(
+    deps: Deps<TerraQuery>,
+    offer_asset_info: AssetInfo,
+    amounts: Vec<Uint128>,
+) -> Result<SimulationBatchResponse, ContractError> {
+    assert_simulation_batch(&amounts)?;
+    let (offer_pool, ask_pool) = read_swap_pools(deps, &offer_asset_info)?;
+    let fee_config = read_fee_config(deps.storage)?;
+    let max_trade_ratio = MAX_TRADE_RATIO.may_load(deps.storage)?;
+
+    let simulations = amounts
+        .into_iter()
+        .map(|amount| {
+            simulate_swap(
+                &fee_config,
+                max_trade_ratio,
+                offer_pool.amount,
+                ask_pool.amount,
+                amount,
+                None,
+            )
+        })
+        .collect::<Result<Vec<_>, _>>()?;
 
-    let offer_pool: Asset;
-    let ask_pool: Asset;
-    if ask_asset.info.equal(&pools[0].info) {
-        ask_pool = pools[0].clone();
-        offer_pool = pools[1].clone();
-    } else if ask_asset.info.equal(&pools[1].info) {
-        ask_pool = pools[1].clone();
-        offer_pool = pools[0].clone();
-    } else {
-        return Err(ContractError::AssetMismatch {});
-    }
+    Ok(SimulationBatchResponse { simulations })
+}
 
-    let (offer_amount, spread_amount, commission_amount) =
-        compute_offer_amount(offer_pool.amount, ask_pool.amount, ask_asset.amount)?;
+pub fn query_reverse_simulation_batch(
+    deps: Deps<TerraQuery>,
+    ask_asset_info: AssetInfo,
+    amounts: Vec<Uint128>,
+) -> Result<ReverseSimulationBatchResponse, ContractError> {
+    assert_simulation_batch(&amounts)?;
+    let (ask_pool, offer_pool) = read_swap_pools(deps, &ask_asset_info)?;
+    let fee_config = read_fee_config(deps.storage)?;
+    let max_trade_ratio = MAX_TRADE_RATIO.may_load(deps.storage)?;
+
+    let simulations = amounts
+        .into_iter()
+        .map(|amount| {
+            simulate_reverse_swap(
+                &fee_config,
+                max_trade_ratio,
+                offer_pool.amount,
+                ask_pool.amount,
+                amount,
+                None,
+            )
+        })
+        .collect::<Result<Vec<_>, _>>()?;
 
-    Ok(ReverseSimulationResponse {
-        offer_amount,
-        spread_amount,
-        commission_amount,
-    })
+    Ok(ReverseSimulationBatchResponse { simulations })
 }
 
-fn compute_swap(
-    offer_pool: Uint128,
-    ask_pool: Uint128,
-    offer_amount: Uint128,
-) -> StdResult<(Uint128, Uint128, Uint128)> {
-    let offer_pool: Uint256 = offer_pool.into();
-    let ask_pool: Uint256 = ask_pool.into();
-    let offer_amount: Uint256 = offer_amount.into();
+fn assert_simulation_batch(amounts: &[Uint128]) -> Result<(), ContractError> {
+    if amounts.len() > MAX_SIMULATION_BATCH {
+        return Err(ContractError::SimulationBatchTooLarge {
+            max: MAX_SIMULATION_BATCH,
+        });
+    }
 
-    let commission_rate = Decimal256::permille(COMMISSION_RATE);
+    Ok(())
+}
+
+/// The pool of `asset_info` and the other pool of the pair
+fn read_swap_pools(
+    deps: Deps<TerraQuery>,
+    asset_info: &AssetInfo,
+) -> Result<(Asset, Asset), ContractError> {
+    let pair_info: PairInfoRaw = PAIR_INFO.load(deps.storage)?;
+    let [pool0, pool1]: [Asset; 2] = read_pools(deps, &pair_info)?;
 
-    // offer => ask
-    // ask_amount = (ask_pool - cp / (offer_pool + offer_amount)) * (1 - commission_rate)
-    let return_amount: Uint256 = (ask_pool * offer_amount) / (offer_pool + offer_amount);
+    if asset_info.equal(&pool0.info) {
+        Ok((pool0, pool1))
+    } else if asset_info.equal(&pool1.info) {
+        Ok((pool1, pool0))
+    } else {
+        Err(ContractError::AssetMismatch {})
+    }
+}
 
-    // calculate spread & commission
-    let spread_amount: Uint256 =
-        (offer_amount * Decimal256::from_ratio(ask_pool, offer_pool)) - return_amount;
-    let mut commission_amount: Uint256 = return_amount * commission_rate;
-    if return_amount != (commission_amount * (Decimal256::one() / commission_rate)) {
-        commission_amount += Uint256::from(1u128);
+/// Checks the native funds sent match the native `assets` exactly, summing the coins of a
+/// denom, and that no other denom is sent. A zero amount must not come with funds
+fn assert_sent_funds(info: &MessageInfo, assets: &[Asset]) -> Result<(), ContractError> {
+    for coin in info.funds.iter() {
+        if !assets.iter().any(
+            |asset| matches!(&asset.info, AssetInfo::NativeToken { denom } if *denom == coin.denom),
+        ) {
+            return Err(ContractError::UnexpectedFunds {
+                denom: coin.denom.clone(),
+            });
+        }
     }
 
-    // commission will be absorbed to pool
-    let return_amount: Uint256 = return_amount - commission_amount;
+    for asset in assets.iter() {
+        if let AssetInfo::NativeToken { denom } = &asset.info {
+            let sent: Uint128 = info
+                .funds
+                .iter()
+                .filter(|coin| coin.denom == *denom)
+                .map(|coin| coin.amount)
+                .sum();
+            if asset.amount.is_zero() && !sent.is_zero() {
+                return Err(ContractError::ZeroAmountWithFunds {
+                    denom: denom.to_string(),
+                });
+            }
+            if sent != asset.amount {
+                return Err(ContractError::NativeFundsMismatch {
+                    denom: denom.to_string(),
+                    declared: asset.amount,
+                    sent,
+                });
+            }
+        }
+    }
 
-    Ok((
-        return_amount.try_into()?,
-        spread_amount.try_into()?,
-        commission_amount.try_into()?,
-    ))
+    Ok(())
 }
 
-#[test]
-fn test_compute_swap_with_huge_pool_variance() {
-    let offer_pool = Uint128::from(395451850234u128);
-    let ask_pool = Uint128::from(317u128);
+/// Swaps need both reserves, an empty one is left by a fresh pair or a full withdrawal
+fn assert_liquidity(offer_pool: Uint128, ask_pool: Uint128) -> Result<(), ContractError> {
+    if offer_pool.is_zero() || ask_pool.is_zero() {
+        return Err(ContractError::NoLiquidity {});
+    }
 
-    assert_eq!(
-        compute_swap(offer_pool, ask_pool, Uint128::from(1u128))
-            .unwrap()
-            .0,
-        Uint128::zero()
-    );
+    Ok(())
 }
 
-fn compute_offer_amount(
+/// Simulates a swap of `offer_amount` against loaded pools, reporting the return
+/// after the referral cut as the swap sends it
+fn simulate_swap(
+    fee_config: &FeeConfig,
+    max_trade_ratio: Option<Decimal>,
     offer_pool: Uint128,
     ask_pool: Uint128,
-    ask_amount: Uint128,
-) -> StdResult<(Uint128, Uint128, Uint128)> {
-    let offer_pool: Uint256 = offer_pool.into();
-    let ask_pool: Uint256 = ask_pool.into();
-    let ask_amount: Uint256 = ask_amount.into();
-
-    let commission_rate = Decimal256::permille(COMMISSION_RATE);
+    offer_amount: Uint128,
+    referral_bps: Option<u16>,
+) -> Result<SimulationResponse, ContractError> {
+    assert_liquidity(offer_pool, ask_pool)?;
 
-    // ask => offer
-    // offer_amount = cp / (ask_pool - ask_amount / (1 - commission_rate)) - offer_pool
-    let cp: Uint256 = offer_pool * ask_pool;
+    let SwapComputation {
+        return_amount,
+        spread_amount,
+        commission_amount,
+    } = compute_swap(
+        offer_pool,
+        ask_pool,
+        offer_amount,
+        fee_config.commission_rate(offer_pool, offer_amount),
+    )?;
 
-    let one_minus_commission = Decimal256::one() - commission_rate;
-    let inv_one_minus_commission = Decimal256::one() / one_minus_commission;
-    let mut before_commission_deduction: Uint256 = ask_amount * inv_one_minus_commission;
-    if before_commission_deduction * one_minus_commission != ask_amount {
-        before_commission_deduction += Uint256::one();
-    }
+    let exceeds_trade_limit = exceeds_trade_limit(max_trade_ratio, ask_pool, return_amount);
+    let return_amount = match referral_bps {
+        Some(bps) => return_amount.checked_sub(compute_referral_amount(return_amount, bps))?,
+        None => return_amount,
+    };
 
-    let after_ask_pool = ask_pool - before_commission_deduction;
-    let mut after_offer_pool = Uint256::one().multiply_ratio(cp, after_ask_pool);
+    Ok(SimulationResponse {
+        return_amount,
+        spread_amount,
+        commission_amount,
+        exceeds_trade_limit,
+    })
+}
 
-    if after_offer_pool * (ask_pool - before_commission_deduction) != cp {
-        after_offer_pool += Uint256::one();
+/// Simulates the least offer returning `ask_amount` against loaded pools, after
+/// the referral cut
+fn simulate_reverse_swap(
+    fee_config: &FeeConfig,
+    max_trade_ratio: Option<Decimal>,
+    offer_pool: Uint128,
+    ask_pool: Uint128,
+    ask_amount: Uint128,
+    referral_bps: Option<u16>,
+) -> Result<ReverseSimulationResponse, ContractError> {
+    assert_liquidity(offer_pool, ask_pool)?;
+
+    // the least return that leaves the ask amount after the referral cut
+    let mut return_amount = ask_amount;
+    if let Some(bps) = referral_bps {
+        let after_referral = |amount: Uint128| amount - compute_referral_amount(amount, bps);
+        return_amount = ask_amount.multiply_ratio(10_000u16, 10_000u16 - bps);
+        while after_referral(return_amount) < ask_amount {
+            return_amount += Uint128::one();
+        }
+        while !return_amount.is_zero()
+            && after_referral(return_amount - Uint128::one()) >= ask_amount
+        {
+            return_amount -= Uint128::one();
+        }
     }
 
-    let offer_amount: Uint256 = after_offer_pool - offer_pool;
+    let OfferComputation {
+        offer_amount,
+        spread_amount,
+        commission_amount,
+    } = compute_tiered_offer_amount(fee_config, offer_pool, ask_pool, return_amount)?;
 
-    let before_spread_deduction: Uint256 =
-        offer_amount * Decimal256::from_ratio(ask_pool, offer_pool);
+    Ok(ReverseSimulationResponse {
+        offer_amount,
+        spread_amount,
+        commission_amount,
+        exceeds_trade_limit: exceeds_trade_limit(max_trade_ratio, ask_pool, return_amount),
+    })
+}
 
-    let spread_amount = if before_spread_deduction > before_commission_deduction {
-        before_spread_deduction - before_commission_deduction
-    } else {
-        Uint256::zero()
-    };
+/// The most a single swap may return out of `ask_pool`, if the max trade ratio is set
+fn trade_limit(max_trade_ratio: Option<Decimal>, ask_pool: Uint128) -> Option<Uint128> {
+    max_trade_ratio.map(|ratio| ask_pool * ratio)
+}
 
-    let commission_amount = before_commission_deduction - ask_amount;
+fn exceeds_trade_limit(
+    max_trade_ratio: Option<Decimal>,
+    ask_pool: Uint128,
+    return_amount: Uint128,
+) -> bool {
+    trade_limit(max_trade_ratio, ask_pool).is_some_and(|limit| return_amount > limit)
+}
 
-    Ok((
-        offer_amount.try_into()?,
-        spread_amount.try_into()?,
-        commission_amount.try_into()?,
-    ))
+/// `compute_offer_amount` at the tier the offer falls in. A higher rate only raises
+/// the offer, so the rate is raised until the tier of the offer charges it.
+fn compute_tiered_offer_amount(
+    fee_config: &FeeConfig,
+    offer_pool: Uint128,
+    ask_pool: Uint128,
+    ask_amount: Uint128,
+) -> StdResult<OfferComputation> {
+    let mut commission_rate = fee_config.total_fee_rate();
+    loop {
+        let offer = compute_offer_amount(offer_pool, ask_pool, ask_amount, commission_rate)?;
+        let tier_rate = fee_config.commission_rate(offer_pool, offer.offer_amount);
+        if tier_rate <= commission_rate {
+            return Ok(offer);
+        }
+        commission_rate = tier_rate;
+    }
 }
 
 /// If `belief_price` and `max_spread` both are given,
@@ -834,32 +3149,30 @@ pub fn assert_max_spread(
     Ok(())
 }
 
+/// Each of the up to two `min_assets` must match a refunded asset,
+/// refunded assets without a minimum are unconstrained
 pub fn assert_minimum_assets(
     assets: Vec<Asset>,
-    min_assets: Option<[Asset; 2]>,
+    min_assets: Option<Vec<Asset>>,
 ) -> Result<(), ContractError> {
     if let Some(min_assets) = min_assets {
+        if min_assets.len() > 2
+            || min_assets
+                .iter()
+                .any(|min_asset| !assets.iter().any(|asset| asset.info == min_asset.info))
+        {
+            return Err(ContractError::AssetMismatch {});
+        }
+
         min_assets.iter().try_for_each(|min_asset| {
-            match assets.iter().find(|asset| asset.info == min_asset.info) {
-                Some(asset) => {
-                    if asset.amount.cmp(&min_asset.amount).is_lt() {
-                        return Err(ContractError::MinAmountAssertion {
-                            min_asset: min_asset.to_string(),
-                            asset: asset.to_string(),
-                        });
-                    }
-                }
-                None => {
+            if let Some(asset) = assets.iter().find(|asset| asset.info == min_asset.info) {
+                if asset.amount.cmp(&min_asset.amount).is_lt() {
                     return Err(ContractError::MinAmountAssertion {
                         min_asset: min_asset.to_string(),
-                        asset: Asset {
-                            info: min_asset.info.clone(),
-                            amount: Uint128::zero(),
-                        }
-                        .to_string(),
-                    })
+                        asset: asset.to_string(),
+                    });
                 }
-            };
+            }
 
             Ok(())
         })?;
@@ -871,16 +3184,92 @@ pub fn assert_minimum_assets(
 const TARGET_CONTRACT_VERSION: &str = "0.1.1";
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn migrate(
-    deps: DepsMut<TerraQuery>,
-    _env: Env,
-    _msg: MigrateMsg,
+    mut deps: DepsMut<TerraQuery>,
+    env: Env,
+    msg: MigrateMsg,
 ) -> Result<Response<TerraMsg>, ContractError> {
     migrate_version(
-        deps,
+        deps.branch(),
         TARGET_CONTRACT_VERSION,
         CONTRACT_NAME,
         CONTRACT_VERSION,
     )?;
 
+    // pairs are instantiated by the factory, which is kept as their admin
+    if FACTORY.may_load(deps.storage)?.is_none() {
+        if let Some(admin) = deps
+            .querier
+            .query_wasm_contract_info(env.contract.address.clone())?
+            .admin
+        {
+            FACTORY.save(deps.storage, &Addr::unchecked(admin))?;
+        }
+    }
+
+    // pairs from before the decimals were recorded are backfilled with 6,
+    // wrong ones are corrected with `update_asset_decimals`
+    let pair_info: PairInfoRaw = PAIR_INFO.load(deps.storage)?;
+    PAIR_INFO.save(deps.storage, &pair_info)?;
+
+    // the reserves of pairs from before they were tracked are their balances
+    if RESERVES.may_load(deps.storage)?.is_none() {
+        let pair_info: PairInfoRaw = PAIR_INFO.load(deps.storage)?;
+        let balances: [Asset; 2] =
+            pair_info.query_pools(&deps.querier, deps.api, env.contract.address.clone())?;
+        RESERVES.save(deps.storage, &[balances[0].amount, balances[1].amount])?;
+    }
+
+    if msg.update_liquidity_token {
+        return migrate_liquidity_token(deps, env, msg.liquidity_token_code_id);
+    }
+
     Ok(Response::default())
 }
+
+/// Migrates the liquidity token to `new_code_id` or the token code id of the factory.
+/// Its supply and minter are recorded to be checked in the reply
+fn migrate_liquidity_token(
+    deps: DepsMut<TerraQuery>,
+    env: Env,
+    new_code_id: Option<u64>,
+) -> Result<Response<TerraMsg>, ContractError> {
+    let pair_info: PairInfoRaw = PAIR_INFO.load(deps.storage)?;
+    let liquidity_token = deps.api.addr_humanize(&pair_info.liquidity_token)?;
+
+    // liquidity tokens instantiated without the pair as their admin can't be migrated
+    let admin = deps
+        .querier
+        .query_wasm_contract_info(liquidity_token.clone())?
+        .admin;
+    if admin != Some(env.contract.address.to_string()) {
+        return Err(ContractError::LiquidityTokenAdminRequired {});
+    }
+
+    let new_code_id = match new_code_id {
+        Some(new_code_id) => new_code_id,
+        None => {
+            let factory_config: FactoryConfigResponse = deps
+                .querier
+                .query_wasm_smart(FACTORY.load(deps.storage)?, &FactoryQueryMsg::Config {})?;
+            factory_config.token_code_id
+        }
+    };
+
+    let snapshot = query_liquidity_token_snapshot(deps.as_ref(), &liquidity_token)?;
+    LIQUIDITY_TOKEN_SNAPSHOT.save(deps.storage, &snapshot)?;
+
+    Ok(Response::new()
+        .add_submessage(SubMsg::reply_on_success(
+            WasmMsg::Migrate {
+                contract_addr: liquidity_token.to_string(),
+                new_code_id,
+                msg: to_binary(&Empty {})?,
+            },
+            LIQUIDITY_TOKEN_MIGRATE_REPLY_ID,
+        ))
+        .add_attributes(vec![
+            ("action", "update_liquidity_token"),
+            ("liquidity_token", liquidity_token.as_str()),
+            ("new_code_id", &new_code_id.to_string()),
+        ]))
+}
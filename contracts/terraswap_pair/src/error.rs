@@ -1,4 +1,5 @@
-use cosmwasm_std::{ConversionOverflowError, OverflowError, StdError};
+use classic_terraswap::util::DeadlineError;
+use cosmwasm_std::{ConversionOverflowError, OverflowError, StdError, Uint128};
 use thiserror::Error;
 
 #[derive(Error, Debug, PartialEq)]
@@ -12,24 +13,133 @@ pub enum ContractError {
     #[error("{0}")]
     ConversionOverflowError(#[from] ConversionOverflowError),
 
+    #[error("{0}")]
+    Deadline(#[from] DeadlineError),
+
     #[error("Unauthorized")]
     Unauthorized {},
 
     #[error("Invalid zero amount")]
     InvalidZeroAmount {},
 
+    #[error("Offer amount must not be zero")]
+    ZeroOfferAmount {},
+
+    #[error("Both deposits are zero")]
+    ZeroDeposits {},
+
+    #[error("One deposit is zero; provide both assets or use provide_liquidity_single")]
+    OneSidedDeposit {},
+
+    #[error("{denom} is declared with a zero amount but was sent")]
+    ZeroAmountWithFunds { denom: String },
+
+    #[error("{denom} is declared with {declared} but {sent} was sent")]
+    NativeFundsMismatch {
+        denom: String,
+        declared: Uint128,
+        sent: Uint128,
+    },
+
+    #[error("{denom} was sent but is not declared")]
+    UnexpectedFunds { denom: String },
+
     #[error("Max spread assertion")]
     MaxSpreadAssertion {},
 
+    #[error("pair has no liquidity; provide liquidity before swapping")]
+    NoLiquidity {},
+
+    #[error("to_msg requires a to address")]
+    ToMsgWithoutTo {},
+
+    #[error("Swaps are paused")]
+    SwapsPaused {},
+
+    #[error("Deposits are paused")]
+    DepositsPaused {},
+
     #[error("Asset mismatch")]
     AssetMismatch {},
 
     #[error("Min amount assertion ({min_asset} > {asset})")]
     MinAmountAssertion { min_asset: String, asset: String },
 
+    #[error("The swap returns nothing for the other refund; withdraw both assets instead")]
+    WithdrawSingleWorseThanSymmetric {},
+
     #[error("Max slippage assertion")]
     MaxSlippageAssertion {},
 
+    #[error("minted LP {minted} less than minimum {min_lp_to_receive}")]
+    MinLpToReceiveAssertion {
+        minted: String,
+        min_lp_to_receive: String,
+    },
+
+    #[error("return {amount} exceeds the trade limit {limit} of the ask reserve")]
+    TradeLimitExceeded { limit: String, amount: String },
+
+    #[error("Max trade ratio must be above 0 and at most 1")]
+    InvalidMaxTradeRatio {},
+
+    #[error("asset decimals must not exceed 18")]
+    InvalidAssetDecimals {},
+
+    #[error("offer {offer_amount} exceeds the max offer {max_offer_amount}")]
+    MaxOfferAmountAssertion {
+        offer_amount: String,
+        max_offer_amount: String,
+    },
+
+    #[error("No price observation within the last {window_seconds} seconds")]
+    InsufficientPriceHistory { window_seconds: u64 },
+
+    #[error("The liquidity token must have the pair as its admin to be migrated")]
+    LiquidityTokenAdminRequired {},
+
+    #[error("Pair assets and the liquidity token can not be rescued")]
+    PairAssetRescue {},
+
+    #[error("No commitment found for the sender")]
+    CommitmentNotFound {},
+
+    #[error("The commitment can be revealed from height {reveal_height}")]
+    CommitmentTooEarly { reveal_height: u64 },
+
+    #[error("The commitment has expired")]
+    CommitmentExpired {},
+
+    #[error("The revealed swap does not match the commitment")]
+    CommitmentMismatch {},
+
+    #[error("min_delay_blocks must be at least 1 and not exceed expiry_blocks")]
+    InvalidCommitConfig {},
+
+    #[error("A batch simulation takes at most {max} amounts")]
+    SimulationBatchTooLarge { max: usize },
+
+    #[error("Belief price must not be zero")]
+    ZeroBeliefPrice {},
+
+    #[error("Referral cut must not exceed {max_referral_bps} bps")]
+    ReferralBpsTooHigh { max_referral_bps: u16 },
+
+    #[error("Total fee rate must not exceed 1%")]
+    FeeRateTooHigh {},
+
+    #[error("Fee tiers must raise the commission rate with strictly increasing thresholds")]
+    InvalidFeeTiers {},
+
+    #[error("Protocol fee collector is required for a protocol fee")]
+    ProtocolFeeCollectorRequired {},
+
+    #[error("invalid reply msg")]
+    InvalidReply {},
+
+    #[error("Liquidity token migration changed the total supply or the minter")]
+    LiquidityTokenChanged {},
+
     #[error("More initial liquidity needed ({min_lp_token} > {given_lp})")]
     MinimumLiquidityAmountError {
         min_lp_token: String,
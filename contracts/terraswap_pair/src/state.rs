@@ -1,4 +1,225 @@
 use classic_terraswap::asset::PairInfoRaw;
-use cw_storage_plus::Item;
+use classic_terraswap::pair::{CommitConfig, FeeConfig, PoolStatus};
+use cosmwasm_std::{Addr, Decimal, StdResult, Storage, Uint128, Uint256};
+use cw20::MinterResponse;
+use cw_storage_plus::{Item, Map};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 
 pub const PAIR_INFO: Item<PairInfoRaw> = Item::new("pair_info");
+pub const FACTORY: Item<Addr> = Item::new("factory");
+pub const FEE_CONFIG: Item<FeeConfig> = Item::new("fee_config");
+pub const MAX_SPREAD_CAP: Item<Decimal> = Item::new("max_spread_cap");
+pub const MAX_TRADE_RATIO: Item<Decimal> = Item::new("max_trade_ratio");
+pub const PAIR_ADMIN: Item<Addr> = Item::new("pair_admin");
+pub const POOL_STATUS: Item<PoolStatus> = Item::new("pool_status");
+pub const RESERVES: Item<[Uint128; 2]> = Item::new("reserves");
+pub const PROTOCOL_FEES: Item<[Uint128; 2]> = Item::new("protocol_fees");
+pub const DEPOSITS: Map<&Addr, [Uint128; 2]> = Map::new("deposits");
+pub const DEPOSIT_TOTALS: Item<[Uint128; 2]> = Item::new("deposit_totals");
+pub const PRICE_ACCUMULATORS: Item<PriceAccumulators> = Item::new("price_accumulators");
+/// Ring buffer of the accumulators, one observation per period slot
+pub const PRICE_OBSERVATIONS: Map<u64, PriceAccumulators> = Map::new("price_observations");
+pub const LIQUIDITY_TOKEN_SNAPSHOT: Item<LiquidityTokenSnapshot> =
+    Item::new("liquidity_token_snapshot");
+pub const COMMIT_CONFIG: Item<CommitConfig> = Item::new("commit_config");
+/// The pending swap commitment of each committer
+pub const COMMITMENTS: Map<&Addr, Commitment> = Map::new("commitments");
+pub const FEE_TRACKER: Item<FeeTracker> = Item::new("fee_tracker");
+
+/// Seconds between two price observations
+pub const OBSERVATION_PERIOD: u64 = 3600;
+/// Number of observations kept, the oldest is overwritten
+pub const OBSERVATION_SLOTS: u64 = 24;
+
+/// Pairs instantiated before the fee config existed use the default commission
+pub fn read_fee_config(storage: &dyn Storage) -> StdResult<FeeConfig> {
+    Ok(FEE_CONFIG.may_load(storage)?.unwrap_or_default())
+}
+
+/// Reserves in the order of the pair asset infos. They are tracked apart from
+/// the balances, so tokens sent to the pair directly don't move the price
+pub fn read_reserves(storage: &dyn Storage) -> StdResult<[Uint128; 2]> {
+    Ok(RESERVES.may_load(storage)?.unwrap_or_default())
+}
+
+/// Protocol fees accrued since the last collection, in the order of the pair asset infos.
+/// They are held by the pair apart from the reserves
+pub fn read_protocol_fees(storage: &dyn Storage) -> StdResult<[Uint128; 2]> {
+    Ok(PROTOCOL_FEES.may_load(storage)?.unwrap_or_default())
+}
+
+pub fn accrue_protocol_fee(
+    storage: &mut dyn Storage,
+    index: usize,
+    amount: Uint128,
+) -> StdResult<()> {
+    let mut protocol_fees = read_protocol_fees(storage)?;
+    protocol_fees[index] = protocol_fees[index].checked_add(amount)?;
+    PROTOCOL_FEES.save(storage, &protocol_fees)
+}
+
+/// Commission charged by the swaps since `tracked_since`, in the order of the pair asset infos
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct FeeTracker {
+    pub cumulative_fees: [Uint128; 2],
+    pub tracked_since: u64,
+}
+
+/// Adds the commission of a swap, the first tracked swap starts the tracking
+pub fn track_commission(
+    storage: &mut dyn Storage,
+    index: usize,
+    amount: Uint128,
+    block_time: u64,
+) -> StdResult<()> {
+    let mut fee_tracker = FEE_TRACKER.may_load(storage)?.unwrap_or(FeeTracker {
+        cumulative_fees: [Uint128::zero(), Uint128::zero()],
+        tracked_since: block_time,
+    });
+    fee_tracker.cumulative_fees[index] = fee_tracker.cumulative_fees[index].saturating_add(amount);
+    FEE_TRACKER.save(storage, &fee_tracker)
+}
+
+/// Tokens escrowed by `addr` for a provision, in the order of the pair asset infos
+pub fn read_deposits(storage: &dyn Storage, addr: &Addr) -> StdResult<[Uint128; 2]> {
+    Ok(DEPOSITS.may_load(storage, addr)?.unwrap_or_default())
+}
+
+pub fn add_deposit(
+    storage: &mut dyn Storage,
+    addr: &Addr,
+    index: usize,
+    amount: Uint128,
+) -> StdResult<()> {
+    let mut deposits = read_deposits(storage, addr)?;
+    deposits[index] = deposits[index].checked_add(amount)?;
+    DEPOSITS.save(storage, addr, &deposits)?;
+
+    let mut deposit_totals = DEPOSIT_TOTALS.may_load(storage)?.unwrap_or_default();
+    deposit_totals[index] = deposit_totals[index].checked_add(amount)?;
+    DEPOSIT_TOTALS.save(storage, &deposit_totals)
+}
+
+/// Removes and returns the deposits of `addr`
+pub fn take_deposits(storage: &mut dyn Storage, addr: &Addr) -> StdResult<[Uint128; 2]> {
+    let deposits = read_deposits(storage, addr)?;
+    DEPOSITS.remove(storage, addr);
+
+    let mut deposit_totals = DEPOSIT_TOTALS.may_load(storage)?.unwrap_or_default();
+    deposit_totals[0] = deposit_totals[0].checked_sub(deposits[0])?;
+    deposit_totals[1] = deposit_totals[1].checked_sub(deposits[1])?;
+    DEPOSIT_TOTALS.save(storage, &deposit_totals)?;
+
+    Ok(deposits)
+}
+
+/// Balances the pair holds apart from the reserves, the accrued protocol fees
+/// and the escrowed deposits
+pub fn read_unpooled_balances(storage: &dyn Storage) -> StdResult<[Uint128; 2]> {
+    let protocol_fees = read_protocol_fees(storage)?;
+    let deposit_totals = DEPOSIT_TOTALS.may_load(storage)?.unwrap_or_default();
+
+    Ok([
+        protocol_fees[0].checked_add(deposit_totals[0])?,
+        protocol_fees[1].checked_add(deposit_totals[1])?,
+    ])
+}
+
+/// Pools are active until paused
+pub fn read_pool_status(storage: &dyn Storage) -> StdResult<PoolStatus> {
+    Ok(POOL_STATUS.may_load(storage)?.unwrap_or_default())
+}
+
+pub fn read_commit_config(storage: &dyn Storage) -> StdResult<CommitConfig> {
+    Ok(COMMIT_CONFIG.may_load(storage)?.unwrap_or_default())
+}
+
+/// A swap hash committed at `height`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct Commitment {
+    pub hash: String,
+    pub height: u64,
+}
+
+/// The supply and minter of the liquidity token before its migration,
+/// checked against the migrated token in the reply
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LiquidityTokenSnapshot {
+    pub total_supply: Uint128,
+    pub minter: Option<MinterResponse>,
+}
+
+/// Time weighted sums of the pool prices, scaled by 10^18.
+/// price0 is the price of asset 0 in asset 1, price1 the inverse.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct PriceAccumulators {
+    pub price0_cumulative_last: Uint256,
+    pub price1_cumulative_last: Uint256,
+    pub block_time_last: u64,
+}
+
+impl PriceAccumulators {
+    pub fn new(block_time: u64) -> Self {
+        PriceAccumulators {
+            price0_cumulative_last: Uint256::zero(),
+            price1_cumulative_last: Uint256::zero(),
+            block_time_last: block_time,
+        }
+    }
+
+    /// Adds the prices of `reserves` held since `block_time_last` up to `block_time`.
+    /// The sums wrap on overflow, consumers take wrapping differences.
+    pub fn accumulate(&mut self, reserves: [Uint128; 2], block_time: u64) {
+        let time_elapsed = block_time.saturating_sub(self.block_time_last);
+        if time_elapsed > 0 && !reserves[0].is_zero() && !reserves[1].is_zero() {
+            let scale = Uint256::from(1_000_000_000_000_000_000u128);
+            let reserve0 = Uint256::from(reserves[0]);
+            let reserve1 = Uint256::from(reserves[1]);
+            let time_elapsed = Uint256::from(time_elapsed);
+
+            self.price0_cumulative_last = self
+                .price0_cumulative_last
+                .wrapping_add((reserve1 * scale / reserve0).wrapping_mul(time_elapsed));
+            self.price1_cumulative_last = self
+                .price1_cumulative_last
+                .wrapping_add((reserve0 * scale / reserve1).wrapping_mul(time_elapsed));
+        }
+
+        self.block_time_last = std::cmp::max(self.block_time_last, block_time);
+    }
+}
+
+/// Accumulates the prices of the reserves before the current action
+pub fn update_price_accumulators(
+    storage: &mut dyn Storage,
+    reserves: [Uint128; 2],
+    block_time: u64,
+) -> StdResult<()> {
+    // pairs instantiated before the accumulators existed start from the first action
+    let mut accumulators = PRICE_ACCUMULATORS
+        .may_load(storage)?
+        .unwrap_or_else(|| PriceAccumulators::new(block_time));
+    accumulators.accumulate(reserves, block_time);
+
+    save_price_accumulators(storage, &accumulators)
+}
+
+/// Saves the accumulators and observes them once per period,
+/// writing a single slot of the ring buffer at most
+pub fn save_price_accumulators(
+    storage: &mut dyn Storage,
+    accumulators: &PriceAccumulators,
+) -> StdResult<()> {
+    let period = accumulators.block_time_last / OBSERVATION_PERIOD;
+    let slot = period % OBSERVATION_SLOTS;
+    let observed = PRICE_OBSERVATIONS
+        .may_load(storage, slot)?
+        .map(|observation| observation.block_time_last / OBSERVATION_PERIOD == period)
+        .unwrap_or(false);
+    if !observed {
+        PRICE_OBSERVATIONS.save(storage, slot, accumulators)?;
+    }
+
+    PRICE_ACCUMULATORS.save(storage, accumulators)
+}
@@ -1,28 +1,41 @@
+#![allow(clippy::get_first)]
+
 use crate::contract::{
-    assert_max_spread, assert_minimum_assets, execute, instantiate, query_pair_info, query_pool,
-    query_reverse_simulation, query_simulation, reply,
+    assert_max_spread, assert_minimum_assets, commit_swap_hash, execute, instantiate, migrate,
+    query, query_pair_info, query_pool, query_reverse_simulation, query_simulation, reply,
 };
 use crate::error::ContractError;
-use classic_bindings::TerraMsg;
-use classic_terraswap::mock_querier::mock_dependencies;
+use crate::state::{
+    track_commission, FeeTracker, PriceAccumulators, FEE_TRACKER, PROTOCOL_FEES, RESERVES,
+};
+use classic_bindings::{TerraMsg, TerraQuery};
+use classic_terraswap::mock_querier::{mock_dependencies, WasmMockQuerier};
 use std::str::FromStr;
 
 use classic_terraswap::asset::{Asset, AssetInfo, PairInfo};
 use classic_terraswap::pair::{
-    Cw20HookMsg, ExecuteMsg, InstantiateMsg, PoolResponse, ReverseSimulationResponse,
-    SimulationResponse,
+    BeliefPriceDirection, CommitConfig, CommitSwapParams, CommitmentResponse, ConfigResponse,
+    CumulativePricesResponse, Cw20HookMsg, ExecuteMsg, FeeConfig, FeeStatsResponse, InstantiateMsg,
+    MigrateMsg, ObservedTwapResponse, PoolResponse, PoolStatus, QueryMsg, Referral,
+    ReverseSimulationBatchResponse, ReverseSimulationResponse, SimulateProvideLiquidityResponse,
+    SimulateProvideLiquiditySingleResponse, SimulationBatchResponse, SimulationResponse,
 };
-use classic_terraswap::token::InstantiateMsg as TokenInstantiateMsg;
-use cosmwasm_std::testing::{mock_env, mock_info, MOCK_CONTRACT_ADDR};
+use classic_terraswap::token::{InstantiateMarketingInfo, InstantiateMsg as TokenInstantiateMsg};
+use classic_terraswap::util::DeadlineError;
+use cosmwasm_std::testing::{mock_env, mock_info, MockApi, MockStorage, MOCK_CONTRACT_ADDR};
 use cosmwasm_std::{
-    attr, to_binary, BankMsg, Coin, CosmosMsg, Decimal, Reply, ReplyOn, Response, StdError, SubMsg,
-    SubMsgResponse, SubMsgResult, Uint128, WasmMsg,
+    attr, from_binary, to_binary, Addr, BankMsg, Binary, Coin, CosmosMsg, Decimal, Decimal256,
+    Deps, Empty, Env, Event, OwnedDeps, Reply, ReplyOn, Response, SubMsg, SubMsgResponse,
+    SubMsgResult, Uint128, Uint256, WasmMsg,
 };
-use cw20::{Cw20ExecuteMsg, Cw20ReceiveMsg, MinterResponse};
+use cw2::set_contract_version;
+use cw20::{Cw20ExecuteMsg, Cw20ReceiveMsg, Logo, MinterResponse};
 
 #[test]
 fn proper_initialization() {
     let mut deps = mock_dependencies(&[]);
+    deps.querier
+        .with_token_balances(&[(&"asset0000".to_string(), &[])]);
 
     let msg = InstantiateMsg {
         asset_infos: [
@@ -35,6 +48,11 @@ fn proper_initialization() {
         ],
         token_code_id: 10u64,
         asset_decimals: [6u8, 8u8],
+        lp_fee_rate: None,
+        protocol_fee_rate: None,
+        protocol_fee_collector: None,
+        pair_admin: None,
+        max_spread_cap: Some(Decimal::percent(5)),
     };
 
     // we can just call .unwrap() to assert this was a success
@@ -48,18 +66,24 @@ fn proper_initialization() {
                 code_id: 10u64,
                 msg: to_binary(&TokenInstantiateMsg {
                     name: "terraswap liquidity token".to_string(),
-                    symbol: "uLP".to_string(),
+                    symbol: "uLP-uusd-mAA".to_string(),
                     decimals: 6,
                     initial_balances: vec![],
                     mint: Some(MinterResponse {
                         minter: MOCK_CONTRACT_ADDR.to_string(),
                         cap: None,
                     }),
+                    marketing: Some(InstantiateMarketingInfo {
+                        project: None,
+                        description: None,
+                        marketing: Some(MOCK_CONTRACT_ADDR.to_string()),
+                        logo: None,
+                    }),
                 })
                 .unwrap(),
                 funds: vec![],
                 label: "lp".to_string(),
-                admin: None,
+                admin: Some(MOCK_CONTRACT_ADDR.to_string()),
             }
             .into(),
             gas_limit: None,
@@ -98,6 +122,11 @@ fn proper_initialization() {
             }
         ]
     );
+
+    // the spread cap is set at creation
+    let config: ConfigResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
+    assert_eq!(config.max_spread_cap, Some(Decimal::percent(5)));
 }
 
 #[test]
@@ -126,6 +155,11 @@ fn provide_liquidity() {
         ],
         token_code_id: 10u64,
         asset_decimals: [6u8, 8u8],
+        lp_fee_rate: None,
+        protocol_fee_rate: None,
+        protocol_fee_collector: None,
+        pair_admin: None,
+        max_spread_cap: None,
     };
 
     let env = mock_env();
@@ -168,6 +202,7 @@ fn provide_liquidity() {
         receiver: None,
         deadline: None,
         slippage_tolerance: None,
+        min_lp_to_receive: None,
     };
     let env = mock_env();
     let info = mock_info(
@@ -210,6 +245,7 @@ fn provide_liquidity() {
         receiver: None,
         deadline: None,
         slippage_tolerance: None,
+        min_lp_to_receive: None,
     };
 
     let env = mock_env();
@@ -286,6 +322,8 @@ fn provide_liquidity() {
         ),
     ]);
 
+    set_reserves(&mut deps, [200, 200]);
+
     let msg = ExecuteMsg::ProvideLiquidity {
         assets: [
             Asset {
@@ -304,6 +342,7 @@ fn provide_liquidity() {
         receiver: Some("staking0000".to_string()), // try changing receiver
         deadline: None,
         slippage_tolerance: Some(Decimal::from_str("0.005").unwrap()),
+        min_lp_to_receive: None,
     };
 
     let env = mock_env();
@@ -343,6 +382,8 @@ fn provide_liquidity() {
         ),
     ]);
 
+    set_reserves(&mut deps, [200, 200]);
+
     let msg = ExecuteMsg::ProvideLiquidity {
         assets: [
             Asset {
@@ -361,6 +402,7 @@ fn provide_liquidity() {
         receiver: Some("staking0000".to_string()), // try changing receiver
         deadline: None,
         slippage_tolerance: Some(Decimal::from_str("0.05").unwrap()),
+        min_lp_to_receive: None,
     };
 
     let env = mock_env();
@@ -401,6 +443,39 @@ fn provide_liquidity() {
             funds: vec![],
         }))
     );
+    assert!(res.attributes.contains(&attr("sender", "addr0000")));
+    assert!(res.attributes.contains(&attr("receiver", "staking0000")));
+
+    // invalid receiver
+    let msg = ExecuteMsg::ProvideLiquidity {
+        assets: [
+            Asset {
+                info: AssetInfo::Token {
+                    contract_addr: "asset0000".to_string(),
+                },
+                amount: Uint128::from(100u128),
+            },
+            Asset {
+                info: AssetInfo::NativeToken {
+                    denom: "uusd".to_string(),
+                },
+                amount: Uint128::from(100u128),
+            },
+        ],
+        receiver: Some("s".to_string()),
+        deadline: None,
+        slippage_tolerance: None,
+        min_lp_to_receive: None,
+    };
+    let info = mock_info(
+        "addr0000",
+        &[Coin {
+            denom: "uusd".to_string(),
+            amount: Uint128::from(100u128),
+        }],
+    );
+    let res = execute(deps.as_mut(), mock_env(), info, msg);
+    assert!(matches!(res, Err(ContractError::Std(_))));
 
     // check wrong argument
     let msg = ExecuteMsg::ProvideLiquidity {
@@ -421,6 +496,7 @@ fn provide_liquidity() {
         receiver: None,
         deadline: None,
         slippage_tolerance: Some(Decimal::from_str("0.005").unwrap()),
+        min_lp_to_receive: None,
     };
 
     let env = mock_env();
@@ -432,13 +508,14 @@ fn provide_liquidity() {
         }],
     );
     let res = execute(deps.as_mut(), env, info, msg).unwrap_err();
-    match res {
-        ContractError::Std(StdError::GenericErr { msg, .. }) => assert_eq!(
-            msg,
-            "Native token balance mismatch between the argument and the transferred".to_string()
-        ),
-        _ => panic!("Must return generic error"),
-    }
+    assert_eq!(
+        res,
+        ContractError::NativeFundsMismatch {
+            denom: "uusd".to_string(),
+            declared: Uint128::from(50u128),
+            sent: Uint128::from(100u128),
+        }
+    );
 
     // initialize token balance to 1:1
     deps.querier.with_balance(&[(
@@ -460,6 +537,8 @@ fn provide_liquidity() {
         ),
     ]);
 
+    set_reserves(&mut deps, [100, 100]);
+
     // successfully provide liquidity, and refund remain asset
     let msg = ExecuteMsg::ProvideLiquidity {
         assets: [
@@ -479,6 +558,7 @@ fn provide_liquidity() {
         receiver: None,
         deadline: None,
         slippage_tolerance: Some(Decimal::from_str("0.05").unwrap()),
+        min_lp_to_receive: None,
     };
 
     let env = mock_env();
@@ -552,6 +632,11 @@ fn withdraw_liquidity() {
         ],
         token_code_id: 10u64,
         asset_decimals: [6u8, 8u8],
+        lp_fee_rate: None,
+        protocol_fee_rate: None,
+        protocol_fee_collector: None,
+        pair_admin: None,
+        max_spread_cap: None,
     };
 
     let env = mock_env();
@@ -574,6 +659,7 @@ fn withdraw_liquidity() {
     };
 
     let _res = reply(deps.as_mut(), mock_env(), reply_msg).unwrap();
+    set_reserves(&mut deps, [100, 100]);
 
     // withdraw liquidity
     let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
@@ -581,6 +667,7 @@ fn withdraw_liquidity() {
         msg: to_binary(&Cw20HookMsg::WithdrawLiquidity {
             min_assets: None,
             deadline: None,
+            receiver: None,
         })
         .unwrap(),
         amount: Uint128::from(100u128),
@@ -638,10 +725,11 @@ fn withdraw_liquidity() {
     );
 
     // withdraw liquidity with assert min_assets
+    set_reserves(&mut deps, [100, 100]);
     let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
         sender: "addr0000".to_string(),
         msg: to_binary(&Cw20HookMsg::WithdrawLiquidity {
-            min_assets: Some([
+            min_assets: Some(vec![
                 Asset {
                     info: AssetInfo::NativeToken {
                         denom: "uusd".to_string(),
@@ -656,6 +744,7 @@ fn withdraw_liquidity() {
                 },
             ]),
             deadline: None,
+            receiver: None,
         })
         .unwrap(),
         amount: Uint128::from(100u128),
@@ -679,6 +768,7 @@ fn withdraw_liquidity() {
         msg: to_binary(&Cw20HookMsg::WithdrawLiquidity {
             min_assets: None,
             deadline: Some(100u64),
+            receiver: None,
         })
         .unwrap(),
         amount: Uint128::from(100u128),
@@ -686,10 +776,13 @@ fn withdraw_liquidity() {
 
     let env = mock_env();
     let info = mock_info("liquidity0000", &[]);
-    let err = execute(deps.as_mut(), env, info, msg).unwrap_err();
+    let err = execute(deps.as_mut(), env.clone(), info, msg).unwrap_err();
     assert_eq!(
         err,
-        ContractError::Std(StdError::generic_err("Expired deadline"))
+        ContractError::Deadline(DeadlineError {
+            deadline: 100,
+            block_time: env.block.time.seconds(),
+        })
     )
 }
 
@@ -709,7 +802,7 @@ fn failed_reply_with_unknown_id() {
         },
     );
 
-    assert_eq!(res, Err(StdError::generic_err("invalid reply msg")))
+    assert_eq!(res, Err(ContractError::InvalidReply {}))
 }
 
 #[test]
@@ -752,6 +845,11 @@ fn try_native_to_token() {
         ],
         token_code_id: 10u64,
         asset_decimals: [6u8, 8u8],
+        lp_fee_rate: None,
+        protocol_fee_rate: None,
+        protocol_fee_collector: None,
+        pair_admin: None,
+        max_spread_cap: None,
     };
 
     let env = mock_env();
@@ -774,6 +872,10 @@ fn try_native_to_token() {
     };
 
     let _res = reply(deps.as_mut(), mock_env(), reply_msg).unwrap();
+    set_reserves(
+        &mut deps,
+        [collateral_pool_amount.u128(), asset_pool_amount.u128()],
+    );
 
     // normal swap
     let msg = ExecuteMsg::Swap {
@@ -784,9 +886,12 @@ fn try_native_to_token() {
             amount: offer_amount,
         },
         belief_price: None,
+        belief_price_direction: None,
         max_spread: None,
         to: None,
         deadline: None,
+        to_msg: None,
+        referral: None,
     };
     let env = mock_env();
     let info = mock_info(
@@ -812,14 +917,11 @@ fn try_native_to_token() {
         .unwrap();
     let expected_tax_amount = Uint128::zero(); // no tax for token
 
-    // check simulation res
-    deps.querier.with_balance(&[(
-        &MOCK_CONTRACT_ADDR.to_string(),
-        vec![Coin {
-            denom: "uusd".to_string(),
-            amount: collateral_pool_amount, /* user deposit must be pre-applied */
-        }],
-    )]);
+    // check simulation res with the reserves before the swap
+    set_reserves(
+        &mut deps,
+        [collateral_pool_amount.u128(), asset_pool_amount.u128()],
+    );
 
     let simulation_res: SimulationResponse = query_simulation(
         deps.as_ref(),
@@ -829,6 +931,7 @@ fn try_native_to_token() {
             },
             amount: offer_amount,
         },
+        None,
     )
     .unwrap();
     assert_eq!(expected_return_amount, simulation_res.return_amount);
@@ -844,6 +947,7 @@ fn try_native_to_token() {
             },
             amount: expected_return_amount,
         },
+        None,
     )
     .unwrap();
 
@@ -877,9 +981,27 @@ fn try_native_to_token() {
             attr("tax_amount", expected_tax_amount.to_string()),
             attr("spread_amount", expected_spread_amount.to_string()),
             attr("commission_amount", expected_commission_amount.to_string()),
+            attr("protocol_fee_amount", "0"),
+            attr("price", "0.006330158726666666"),
+            attr("pool_price", "0.006047770219365079"),
         ]
     );
 
+    assert_eq!(
+        res.events,
+        vec![Event::new("terraswap_swap").add_attributes(vec![
+            attr("sender", "addr0000"),
+            attr("receiver", "addr0000"),
+            attr("offer_asset", "uusd"),
+            attr("ask_asset", "asset0000"),
+            attr("offer_amount", offer_amount.to_string()),
+            attr("return_amount", expected_return_amount.to_string()),
+            attr("spread_amount", expected_spread_amount.to_string()),
+            attr("commission_amount", expected_commission_amount.to_string()),
+            attr("tax_amount", expected_tax_amount.to_string()),
+        ])]
+    );
+
     assert_eq!(
         &SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
             contract_addr: "asset0000".to_string(),
@@ -935,6 +1057,11 @@ fn try_token_to_native() {
         ],
         token_code_id: 10u64,
         asset_decimals: [8u8, 8u8],
+        lp_fee_rate: None,
+        protocol_fee_rate: None,
+        protocol_fee_collector: None,
+        pair_admin: None,
+        max_spread_cap: None,
     };
 
     let env = mock_env();
@@ -957,6 +1084,10 @@ fn try_token_to_native() {
     };
 
     let _res = reply(deps.as_mut(), mock_env(), reply_msg).unwrap();
+    set_reserves(
+        &mut deps,
+        [collateral_pool_amount.u128(), asset_pool_amount.u128()],
+    );
 
     // unauthorized access; can not execute swap directly for token swap
     let msg = ExecuteMsg::Swap {
@@ -967,9 +1098,12 @@ fn try_token_to_native() {
             amount: offer_amount,
         },
         belief_price: None,
+        belief_price_direction: None,
         max_spread: None,
         to: None,
         deadline: None,
+        to_msg: None,
+        referral: None,
     };
     let env = mock_env();
     let info = mock_info("addr0000", &[]);
@@ -985,9 +1119,12 @@ fn try_token_to_native() {
         amount: offer_amount,
         msg: to_binary(&Cw20HookMsg::Swap {
             belief_price: None,
+            belief_price_direction: None,
             max_spread: None,
             to: None,
             deadline: None,
+            to_msg: None,
+            referral: None,
         })
         .unwrap(),
     });
@@ -1017,18 +1154,11 @@ fn try_token_to_native() {
             )
             .unwrap(),
     );
-    // check simulation res
-    // return asset token balance as normal
-    deps.querier.with_token_balances(&[
-        (
-            &"liquidity0000".to_string(),
-            &[(&MOCK_CONTRACT_ADDR.to_string(), &total_share)],
-        ),
-        (
-            &"asset0000".to_string(),
-            &[(&MOCK_CONTRACT_ADDR.to_string(), &(asset_pool_amount))],
-        ),
-    ]);
+    // check simulation res with the reserves before the swap
+    set_reserves(
+        &mut deps,
+        [collateral_pool_amount.u128(), asset_pool_amount.u128()],
+    );
 
     let simulation_res: SimulationResponse = query_simulation(
         deps.as_ref(),
@@ -1038,6 +1168,7 @@ fn try_token_to_native() {
                 contract_addr: "asset0000".to_string(),
             },
         },
+        None,
     )
     .unwrap();
     assert_eq!(expected_return_amount, simulation_res.return_amount);
@@ -1053,6 +1184,7 @@ fn try_token_to_native() {
                 denom: "uusd".to_string(),
             },
         },
+        None,
     )
     .unwrap();
     assert!(
@@ -1085,9 +1217,27 @@ fn try_token_to_native() {
             attr("tax_amount", expected_tax_amount.to_string()),
             attr("spread_amount", expected_spread_amount.to_string()),
             attr("commission_amount", expected_commission_amount.to_string()),
+            attr("protocol_fee_amount", "0"),
+            attr("price", "0.633015872666666666"),
+            attr("pool_price", "0.604777021936507936"),
         ]
     );
 
+    assert_eq!(
+        res.events,
+        vec![Event::new("terraswap_swap").add_attributes(vec![
+            attr("sender", "addr0000"),
+            attr("receiver", "addr0000"),
+            attr("offer_asset", "asset0000"),
+            attr("ask_asset", "uusd"),
+            attr("offer_amount", offer_amount.to_string()),
+            attr("return_amount", expected_return_amount.to_string()),
+            attr("spread_amount", expected_spread_amount.to_string()),
+            attr("commission_amount", expected_commission_amount.to_string()),
+            attr("tax_amount", expected_tax_amount.to_string()),
+        ])]
+    );
+
     assert_eq!(
         &SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
             to_address: "addr0000".to_string(),
@@ -1107,9 +1257,12 @@ fn try_token_to_native() {
         amount: offer_amount,
         msg: to_binary(&Cw20HookMsg::Swap {
             belief_price: None,
+            belief_price_direction: None,
             max_spread: None,
             to: None,
             deadline: None,
+            to_msg: None,
+            referral: None,
         })
         .unwrap(),
     });
@@ -1356,6 +1509,11 @@ fn test_query_pool() {
         ],
         token_code_id: 10u64,
         asset_decimals: [6u8, 8u8],
+        lp_fee_rate: None,
+        protocol_fee_rate: None,
+        protocol_fee_collector: None,
+        pair_admin: None,
+        max_spread_cap: None,
     };
 
     let env = mock_env();
@@ -1379,6 +1537,10 @@ fn test_query_pool() {
 
     let _res = reply(deps.as_mut(), mock_env(), reply_msg).unwrap();
 
+    // the pool reports the reserves synced to the balances
+    let msg = ExecuteMsg::Sync {};
+    let _res = execute(deps.as_mut(), mock_env(), mock_info("addr0000", &[]), msg).unwrap();
+
     let res: PoolResponse = query_pool(deps.as_ref()).unwrap();
 
     assert_eq!(
@@ -1418,7 +1580,7 @@ fn test_assert_minimum_assets_with_equals() {
         },
     ];
 
-    let minimum_assets = Some([
+    let minimum_assets = Some(vec![
         Asset {
             info: AssetInfo::NativeToken {
                 denom: "uluna".to_string(),
@@ -1453,7 +1615,7 @@ fn test_assert_minimum_assets_with_normal() {
         },
     ];
 
-    let minimum_assets = Some([
+    let minimum_assets = Some(vec![
         Asset {
             info: AssetInfo::NativeToken {
                 denom: "uluna".to_string(),
@@ -1488,7 +1650,7 @@ fn test_assert_minimum_assets_with_less_all() {
         },
     ];
 
-    let minimum_assets = Some([
+    let minimum_assets = Some(vec![
         Asset {
             info: AssetInfo::NativeToken {
                 denom: "uluna".to_string(),
@@ -1530,7 +1692,7 @@ fn test_assert_minimum_assets_with_less_second_asset() {
         },
     ];
 
-    let minimum_assets = Some([
+    let minimum_assets = Some(vec![
         Asset {
             info: AssetInfo::NativeToken {
                 denom: "uluna".to_string(),
@@ -1572,7 +1734,7 @@ fn test_assert_minimum_assets_with_less_first_asset() {
         },
     ];
 
-    let minimum_assets = Some([
+    let minimum_assets = Some(vec![
         Asset {
             info: AssetInfo::NativeToken {
                 denom: "uluna".to_string(),
@@ -1614,7 +1776,7 @@ fn test_assert_minimum_assets_with_unsorted_less_first_asset() {
         },
     ];
 
-    let minimum_assets = Some([
+    let minimum_assets = Some(vec![
         Asset {
             info: AssetInfo::NativeToken {
                 denom: "uusd".to_string(),
@@ -1656,7 +1818,7 @@ fn test_assert_minimum_assets_with_unknown_asset() {
         },
     ];
 
-    let minimum_assets = Some([
+    let minimum_assets = Some(vec![
         Asset {
             info: AssetInfo::NativeToken {
                 denom: "ukrw".to_string(),
@@ -1672,11 +1834,4673 @@ fn test_assert_minimum_assets_with_unknown_asset() {
     ]);
 
     let err = assert_minimum_assets(assets, minimum_assets).unwrap_err();
+    assert_eq!(err, ContractError::AssetMismatch {})
+}
+
+#[test]
+fn test_assert_minimum_assets_with_one_asset() {
+    let assets = vec![
+        Asset {
+            info: AssetInfo::NativeToken {
+                denom: "uluna".to_string(),
+            },
+            amount: Uint128::from(1u128),
+        },
+        Asset {
+            info: AssetInfo::NativeToken {
+                denom: "uusd".to_string(),
+            },
+            amount: Uint128::from(1u128),
+        },
+    ];
+
+    // uluna is unconstrained
+    let minimum_assets = Some(vec![Asset {
+        info: AssetInfo::NativeToken {
+            denom: "uusd".to_string(),
+        },
+        amount: Uint128::from(1u128),
+    }]);
+    assert_minimum_assets(assets.clone(), minimum_assets).unwrap();
+
+    let minimum_assets = Some(vec![Asset {
+        info: AssetInfo::NativeToken {
+            denom: "uusd".to_string(),
+        },
+        amount: Uint128::from(2u128),
+    }]);
+    let err = assert_minimum_assets(assets.clone(), minimum_assets).unwrap_err();
     assert_eq!(
         err,
         ContractError::MinAmountAssertion {
-            min_asset: "1ukrw".to_string(),
-            asset: "0ukrw".to_string()
+            min_asset: "2uusd".to_string(),
+            asset: "1uusd".to_string()
         }
-    )
+    );
+
+    // an empty list constrains nothing
+    assert_minimum_assets(assets, Some(vec![])).unwrap();
+}
+
+#[test]
+fn test_assert_minimum_assets_with_too_many_assets() {
+    let assets = vec![
+        Asset {
+            info: AssetInfo::NativeToken {
+                denom: "uluna".to_string(),
+            },
+            amount: Uint128::from(1u128),
+        },
+        Asset {
+            info: AssetInfo::NativeToken {
+                denom: "uusd".to_string(),
+            },
+            amount: Uint128::from(1u128),
+        },
+    ];
+    let minimum_assets = Some(vec![
+        assets[0].clone(),
+        assets[1].clone(),
+        assets[0].clone(),
+    ]);
+    let err = assert_minimum_assets(assets, minimum_assets).unwrap_err();
+    assert_eq!(err, ContractError::AssetMismatch {})
+}
+
+#[test]
+fn update_fee_config() {
+    let mut deps = mock_dependencies(&[]);
+    deps.querier
+        .with_token_balances(&[(&"asset0000".to_string(), &[])]);
+
+    let msg = InstantiateMsg {
+        asset_infos: [
+            AssetInfo::NativeToken {
+                denom: "uusd".to_string(),
+            },
+            AssetInfo::Token {
+                contract_addr: "asset0000".to_string(),
+            },
+        ],
+        token_code_id: 10u64,
+        asset_decimals: [6u8, 8u8],
+        lp_fee_rate: Some(Decimal::permille(8)),
+        protocol_fee_rate: Some(Decimal::permille(3)),
+        protocol_fee_collector: Some("collector0000".to_string()),
+        pair_admin: None,
+        max_spread_cap: None,
+    };
+
+    // total fee rate is capped at 1%
+    let info = mock_info("factory0000", &[]);
+    let res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg.clone());
+    assert_eq!(res, Err(ContractError::FeeRateTooHigh {}));
+
+    let msg = InstantiateMsg {
+        lp_fee_rate: None,
+        protocol_fee_rate: None,
+        protocol_fee_collector: None,
+        pair_admin: None,
+        max_spread_cap: None,
+        ..msg
+    };
+    let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    // defaults to 0.3% all kept in the pool
+    let fee_config: FeeConfig =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::FeeConfig {}).unwrap()).unwrap();
+    assert_eq!(
+        fee_config,
+        FeeConfig {
+            lp_fee_rate: Decimal::permille(3),
+            protocol_fee_rate: Decimal::zero(),
+            protocol_fee_collector: None,
+            fee_tiers: vec![],
+        }
+    );
+
+    // only the factory owner can update the fee config
+    let msg = ExecuteMsg::UpdateFeeConfig {
+        lp_fee_rate: Decimal::permille(2),
+        protocol_fee_rate: Decimal::permille(1),
+        protocol_fee_collector: Some("collector0000".to_string()),
+    };
+    let info = mock_info("factory0000", &[]);
+    let res = execute(deps.as_mut(), mock_env(), info, msg.clone());
+    assert_eq!(res, Err(ContractError::Unauthorized {}));
+
+    let info = mock_info("owner0000", &[]);
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        ExecuteMsg::UpdateFeeConfig {
+            lp_fee_rate: Decimal::permille(2),
+            protocol_fee_rate: Decimal::permille(1),
+            protocol_fee_collector: None,
+        },
+    );
+    assert_eq!(res, Err(ContractError::ProtocolFeeCollectorRequired {}));
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        ExecuteMsg::UpdateFeeConfig {
+            lp_fee_rate: Decimal::permille(7),
+            protocol_fee_rate: Decimal::from_str("0.003000000000000001").unwrap(),
+            protocol_fee_collector: Some("collector0000".to_string()),
+        },
+    );
+    assert_eq!(res, Err(ContractError::FeeRateTooHigh {}));
+
+    let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    let fee_config: FeeConfig =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::FeeConfig {}).unwrap()).unwrap();
+    assert_eq!(
+        fee_config,
+        FeeConfig {
+            lp_fee_rate: Decimal::permille(2),
+            protocol_fee_rate: Decimal::permille(1),
+            protocol_fee_collector: Some(Addr::unchecked("collector0000")),
+            fee_tiers: vec![],
+        }
+    );
+}
+
+#[test]
+fn swap_with_protocol_fee() {
+    let total_share = Uint128::from(30000000000u128);
+    let asset_pool_amount = Uint128::from(20000000000u128);
+    let collateral_pool_amount = Uint128::from(30000000000u128);
+    let offer_amount = Uint128::from(1500000000u128);
+
+    let mut deps = mock_dependencies(&[Coin {
+        denom: "uusd".to_string(),
+        amount: collateral_pool_amount + offer_amount, /* user deposit must be pre-applied */
+    }]);
+
+    deps.querier.with_tax(
+        Decimal::zero(),
+        &[(&"uusd".to_string(), &Uint128::from(1000000u128))],
+    );
+
+    deps.querier.with_token_balances(&[
+        (
+            &"liquidity0000".to_string(),
+            &[(&MOCK_CONTRACT_ADDR.to_string(), &total_share)],
+        ),
+        (
+            &"asset0000".to_string(),
+            &[(&MOCK_CONTRACT_ADDR.to_string(), &asset_pool_amount)],
+        ),
+    ]);
+
+    // 1% commission split in half between the pool and the protocol
+    let msg = InstantiateMsg {
+        asset_infos: [
+            AssetInfo::NativeToken {
+                denom: "uusd".to_string(),
+            },
+            AssetInfo::Token {
+                contract_addr: "asset0000".to_string(),
+            },
+        ],
+        token_code_id: 10u64,
+        asset_decimals: [6u8, 8u8],
+        lp_fee_rate: Some(Decimal::permille(5)),
+        protocol_fee_rate: Some(Decimal::permille(5)),
+        protocol_fee_collector: Some("collector0000".to_string()),
+        pair_admin: None,
+        max_spread_cap: None,
+    };
+
+    let info = mock_info("factory0000", &[]);
+    let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    // store liquidity token
+    let reply_msg = Reply {
+        id: 1,
+        result: SubMsgResult::Ok(SubMsgResponse {
+            events: vec![],
+            data: Some(
+                vec![
+                    10, 13, 108, 105, 113, 117, 105, 100, 105, 116, 121, 48, 48, 48, 48,
+                ]
+                .into(),
+            ),
+        }),
+    };
+
+    let _res = reply(deps.as_mut(), mock_env(), reply_msg).unwrap();
+    set_reserves(
+        &mut deps,
+        [collateral_pool_amount.u128(), asset_pool_amount.u128()],
+    );
+
+    let msg = ExecuteMsg::Swap {
+        offer_asset: Asset {
+            info: AssetInfo::NativeToken {
+                denom: "uusd".to_string(),
+            },
+            amount: offer_amount,
+        },
+        belief_price: None,
+        belief_price_direction: None,
+        max_spread: None,
+        to: None,
+        deadline: None,
+        to_msg: None,
+        referral: None,
+    };
+    let info = mock_info(
+        "addr0000",
+        &[Coin {
+            denom: "uusd".to_string(),
+            amount: offer_amount,
+        }],
+    );
+    let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    // 952.380952 = 20000 - 20000 * 30000 / (30000 + 1500)
+    // 9.523810 = 952.380952 * 1%, round up
+    let expected_commission_amount = Uint128::from(9_523_810u128);
+    let expected_return_amount = Uint128::from(942_857_142u128);
+    // 4.761905 = 9.523810 * 0.5% / 1%
+    let expected_protocol_fee_amount = Uint128::from(4_761_905u128);
+
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "asset0000".to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: "addr0000".to_string(),
+                amount: expected_return_amount,
+            })
+            .unwrap(),
+            funds: vec![],
+        })),]
+    );
+    assert!(res.attributes.contains(&attr(
+        "commission_amount",
+        expected_commission_amount.to_string()
+    )));
+    assert!(res.attributes.contains(&attr(
+        "protocol_fee_amount",
+        expected_protocol_fee_amount.to_string()
+    )));
+
+    // the protocol fee accrues until it is collected
+    assert_eq!(
+        PROTOCOL_FEES.load(deps.as_ref().storage).unwrap(),
+        [Uint128::zero(), expected_protocol_fee_amount]
+    );
+
+    // simulations use the total fee rate
+    set_reserves(
+        &mut deps,
+        [collateral_pool_amount.u128(), asset_pool_amount.u128()],
+    );
+
+    let simulation_res: SimulationResponse = query_simulation(
+        deps.as_ref(),
+        Asset {
+            info: AssetInfo::NativeToken {
+                denom: "uusd".to_string(),
+            },
+            amount: offer_amount,
+        },
+        None,
+    )
+    .unwrap();
+    assert_eq!(expected_return_amount, simulation_res.return_amount);
+    assert_eq!(expected_commission_amount, simulation_res.commission_amount);
+
+    let reverse_simulation_res: ReverseSimulationResponse = query_reverse_simulation(
+        deps.as_ref(),
+        Asset {
+            info: AssetInfo::Token {
+                contract_addr: "asset0000".to_string(),
+            },
+            amount: expected_return_amount,
+        },
+        None,
+    )
+    .unwrap();
+    assert!(
+        (offer_amount.u128() as i128 - reverse_simulation_res.offer_amount.u128() as i128).abs()
+            < 3i128
+    );
+    assert!(
+        (expected_commission_amount.u128() as i128
+            - reverse_simulation_res.commission_amount.u128() as i128)
+            .abs()
+            < 3i128
+    );
+}
+
+#[test]
+fn cumulative_prices_twap() {
+    let asset_pool_amount = Uint128::from(1_000_000_000u128);
+    let collateral_pool_amount = Uint128::from(2_000_000_000u128);
+    let offer_amount = Uint128::from(1_000_000u128);
+
+    let mut deps = mock_dependencies(&[Coin {
+        denom: "uusd".to_string(),
+        amount: collateral_pool_amount,
+    }]);
+    deps.querier.with_tax(
+        Decimal::zero(),
+        &[(&"uusd".to_string(), &Uint128::from(1000000u128))],
+    );
+    deps.querier.with_token_balances(&[(
+        &"asset0000".to_string(),
+        &[(&MOCK_CONTRACT_ADDR.to_string(), &asset_pool_amount)],
+    )]);
+
+    let msg = InstantiateMsg {
+        asset_infos: [
+            AssetInfo::NativeToken {
+                denom: "uusd".to_string(),
+            },
+            AssetInfo::Token {
+                contract_addr: "asset0000".to_string(),
+            },
+        ],
+        token_code_id: 10u64,
+        asset_decimals: [6u8, 6u8],
+        lp_fee_rate: None,
+        protocol_fee_rate: None,
+        protocol_fee_collector: None,
+        pair_admin: None,
+        max_spread_cap: None,
+    };
+
+    let mut env = mock_env();
+    let start_time = env.block.time;
+    let info = mock_info("addr0000", &[]);
+    let _res = instantiate(deps.as_mut(), env.clone(), info, msg).unwrap();
+    set_reserves(
+        &mut deps,
+        [collateral_pool_amount.u128(), asset_pool_amount.u128()],
+    );
+
+    let query_cumulative_prices = |deps: Deps<TerraQuery>, env: Env| -> CumulativePricesResponse {
+        from_binary(&query(deps, env, QueryMsg::CumulativePrices {}).unwrap()).unwrap()
+    };
+
+    let res = query_cumulative_prices(deps.as_ref(), env.clone());
+    assert_eq!(res.price0_cumulative_last, Uint256::zero());
+    assert_eq!(res.price1_cumulative_last, Uint256::zero());
+    assert_eq!(res.block_time_last, start_time.seconds());
+
+    // swap 100 seconds later, the pre-swap price 0.5 is accumulated
+    env.block.time = start_time.plus_seconds(100);
+    let msg = ExecuteMsg::Swap {
+        offer_asset: Asset {
+            info: AssetInfo::NativeToken {
+                denom: "uusd".to_string(),
+            },
+            amount: offer_amount,
+        },
+        belief_price: None,
+        belief_price_direction: None,
+        max_spread: None,
+        to: None,
+        deadline: None,
+        to_msg: None,
+        referral: None,
+    };
+    let info = mock_info(
+        "addr0000",
+        &[Coin {
+            denom: "uusd".to_string(),
+            amount: offer_amount,
+        }],
+    );
+    let _res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+    // the reserves after the swap quote 0.125
+    set_reserves(&mut deps, [4_000_000_000, 500_000_000]);
+
+    // no time elapsed since the swap
+    let first = query_cumulative_prices(deps.as_ref(), env.clone());
+    assert_eq!(
+        first.price0_cumulative_last,
+        Uint256::from(50_000_000_000_000_000_000u128)
+    );
+    assert_eq!(
+        first.price1_cumulative_last,
+        Uint256::from(200_000_000_000_000_000_000u128)
+    );
+    assert_eq!(first.block_time_last, start_time.seconds() + 100);
+
+    env.block.time = start_time.plus_seconds(300);
+    let second = query_cumulative_prices(deps.as_ref(), env);
+    assert_eq!(second.assets[0].amount, Uint128::from(4_000_000_000u128));
+    assert_eq!(second.block_time_last, start_time.seconds() + 300);
+
+    // twap over the 200 seconds between both snapshots
+    let elapsed = Uint256::from(second.block_time_last - first.block_time_last);
+    let scale = Uint256::from(1_000_000_000_000_000_000u128);
+    let twap0 = Decimal256::from_ratio(
+        second
+            .price0_cumulative_last
+            .wrapping_sub(first.price0_cumulative_last),
+        elapsed * scale,
+    );
+    let twap1 = Decimal256::from_ratio(
+        second
+            .price1_cumulative_last
+            .wrapping_sub(first.price1_cumulative_last),
+        elapsed * scale,
+    );
+    assert_eq!(twap0, Decimal256::from_str("0.125").unwrap());
+    assert_eq!(twap1, Decimal256::from_str("8").unwrap());
+
+    // twap over the 300 seconds since instantiation
+    let twap0 = Decimal256::from_ratio(
+        second.price0_cumulative_last,
+        Uint256::from(300u128) * scale,
+    );
+    assert_eq!(twap0, Decimal256::from_str("0.25").unwrap());
+}
+
+#[test]
+fn price_accumulators_wrap_on_overflow() {
+    let mut accumulators = PriceAccumulators {
+        price0_cumulative_last: Uint256::MAX,
+        price1_cumulative_last: Uint256::MAX - Uint256::from(1u128),
+        block_time_last: 1000,
+    };
+
+    // 1:1 price for 10 seconds adds 10^19 to both sums
+    accumulators.accumulate([Uint128::from(5u128), Uint128::from(5u128)], 1010);
+    assert_eq!(
+        accumulators.price0_cumulative_last,
+        Uint256::from(9_999_999_999_999_999_999u128)
+    );
+    assert_eq!(
+        accumulators.price1_cumulative_last,
+        Uint256::from(9_999_999_999_999_999_998u128)
+    );
+    assert_eq!(accumulators.block_time_last, 1010);
+
+    // the wrapping difference still yields the accumulated price
+    assert_eq!(
+        accumulators
+            .price0_cumulative_last
+            .wrapping_sub(Uint256::MAX),
+        Uint256::from(10_000_000_000_000_000_000u128)
+    );
+
+    // zero elapsed time and empty reserves leave the sums unchanged
+    let before = accumulators.clone();
+    accumulators.accumulate([Uint128::from(5u128), Uint128::from(7u128)], 1010);
+    assert_eq!(accumulators, before);
+    accumulators.accumulate([Uint128::zero(), Uint128::from(7u128)], 1020);
+    assert_eq!(
+        accumulators.price0_cumulative_last,
+        before.price0_cumulative_last
+    );
+    assert_eq!(accumulators.block_time_last, 1020);
+}
+
+#[test]
+fn provide_liquidity_share_inflation() {
+    let mut deps = mock_dependencies(&[Coin {
+        denom: "uusd".to_string(),
+        amount: Uint128::from(1_000u128),
+    }]);
+
+    deps.querier.with_token_balances(&[
+        (
+            &"liquidity0000".to_string(),
+            &[(&MOCK_CONTRACT_ADDR.to_string(), &Uint128::zero())],
+        ),
+        (&"asset0000".to_string(), &[]),
+    ]);
+
+    let msg = InstantiateMsg {
+        asset_infos: [
+            AssetInfo::NativeToken {
+                denom: "uusd".to_string(),
+            },
+            AssetInfo::Token {
+                contract_addr: "asset0000".to_string(),
+            },
+        ],
+        token_code_id: 10u64,
+        asset_decimals: [6u8, 6u8],
+        lp_fee_rate: None,
+        protocol_fee_rate: None,
+        protocol_fee_collector: None,
+        pair_admin: None,
+        max_spread_cap: None,
+    };
+
+    let info = mock_info("addr0000", &[]);
+    let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    // store liquidity token
+    let reply_msg = Reply {
+        id: 1,
+        result: SubMsgResult::Ok(SubMsgResponse {
+            events: vec![],
+            data: Some(
+                vec![
+                    10, 13, 108, 105, 113, 117, 105, 100, 105, 116, 121, 48, 48, 48, 48,
+                ]
+                .into(),
+            ),
+        }),
+    };
+
+    let _res = reply(deps.as_mut(), mock_env(), reply_msg).unwrap();
+
+    let provide_msg = |amount: u128| ExecuteMsg::ProvideLiquidity {
+        assets: [
+            Asset {
+                info: AssetInfo::Token {
+                    contract_addr: "asset0000".to_string(),
+                },
+                amount: Uint128::from(amount),
+            },
+            Asset {
+                info: AssetInfo::NativeToken {
+                    denom: "uusd".to_string(),
+                },
+                amount: Uint128::from(amount),
+            },
+        ],
+        receiver: None,
+        deadline: None,
+        slippage_tolerance: None,
+        min_lp_to_receive: None,
+    };
+
+    // a first provide covering exactly the lock leaves no share to the provider
+    let info = mock_info("attacker0000", &[Coin::new(1_000u128, "uusd")]);
+    let res = execute(deps.as_mut(), mock_env(), info, provide_msg(1_000u128));
+    assert_eq!(
+        res,
+        Err(ContractError::MinimumLiquidityAmountError {
+            min_lp_token: "1000".to_string(),
+            given_lp: "1000".to_string(),
+        })
+    );
+
+    // the smallest first provide, 1000 lp tokens are locked in the pair
+    deps.querier.with_balance(&[(
+        &MOCK_CONTRACT_ADDR.to_string(),
+        vec![Coin::new(1_001u128, "uusd")],
+    )]);
+    let info = mock_info("attacker0000", &[Coin::new(1_001u128, "uusd")]);
+    let res = execute(deps.as_mut(), mock_env(), info, provide_msg(1_001u128)).unwrap();
+    assert_eq!(
+        res.messages.first(),
+        Some(&SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "liquidity0000".to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Mint {
+                recipient: MOCK_CONTRACT_ADDR.to_string(),
+                amount: Uint128::from(1_000u128),
+            })
+            .unwrap(),
+            funds: vec![],
+        })))
+    );
+    assert!(res.attributes.contains(&attr("share", "1")));
+    assert!(res.attributes.contains(&attr("locked_share", "1000")));
+
+    // the attacker inflates the share price by transferring tokens directly to the pair
+    // and syncing the reserves to the balances
+    let donation = 1_000_000_000u128;
+    let deposit = 1_000_000_000u128;
+    deps.querier.with_balance(&[(
+        &MOCK_CONTRACT_ADDR.to_string(),
+        vec![Coin::new(1_001u128 + donation, "uusd")],
+    )]);
+    deps.querier.with_token_balances(&[
+        (
+            &"liquidity0000".to_string(),
+            &[
+                (&MOCK_CONTRACT_ADDR.to_string(), &Uint128::from(1_000u128)),
+                (&"attacker0000".to_string(), &Uint128::from(1u128)),
+            ],
+        ),
+        (
+            &"asset0000".to_string(),
+            &[(
+                &MOCK_CONTRACT_ADDR.to_string(),
+                &Uint128::from(1_001u128 + donation),
+            )],
+        ),
+    ]);
+    let info = mock_info("attacker0000", &[]);
+    execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Sync {}).unwrap();
+
+    // without the lock the victim's share would round down to zero,
+    // with it the victim keeps almost all of the deposit value
+    let info = mock_info("addr0000", &[Coin::new(deposit, "uusd")]);
+    let res = execute(deps.as_mut(), mock_env(), info, provide_msg(deposit)).unwrap();
+    assert!(res.attributes.contains(&attr("share", "1000")));
+    assert!(res.attributes.contains(&attr("locked_share", "0")));
+
+    let pool = 1_001u128 + donation + deposit;
+    let victim_claim = Uint128::from(pool).multiply_ratio(1_000u128, 2_001u128);
+    assert!(victim_claim >= Uint128::from(deposit).multiply_ratio(999u128, 1_000u128));
+}
+
+#[test]
+fn provide_liquidity_with_min_lp_to_receive() {
+    let mut deps = mock_dependencies(&[Coin {
+        denom: "uusd".to_string(),
+        amount: Uint128::from(100u128 + 200u128), /* user deposit must be pre-applied */
+    }]);
+
+    deps.querier.with_token_balances(&[
+        (
+            &"liquidity0000".to_string(),
+            &[(&MOCK_CONTRACT_ADDR.to_string(), &Uint128::from(100u128))],
+        ),
+        (
+            &"asset0000".to_string(),
+            &[(&MOCK_CONTRACT_ADDR.to_string(), &Uint128::from(100u128))],
+        ),
+    ]);
+
+    let msg = InstantiateMsg {
+        asset_infos: [
+            AssetInfo::NativeToken {
+                denom: "uusd".to_string(),
+            },
+            AssetInfo::Token {
+                contract_addr: "asset0000".to_string(),
+            },
+        ],
+        token_code_id: 10u64,
+        asset_decimals: [6u8, 8u8],
+        lp_fee_rate: None,
+        protocol_fee_rate: None,
+        protocol_fee_collector: None,
+        pair_admin: None,
+        max_spread_cap: None,
+    };
+
+    let info = mock_info("addr0000", &[]);
+    let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    // store liquidity token
+    let reply_msg = Reply {
+        id: 1,
+        result: SubMsgResult::Ok(SubMsgResponse {
+            events: vec![],
+            data: Some(
+                vec![
+                    10, 13, 108, 105, 113, 117, 105, 100, 105, 116, 121, 48, 48, 48, 48,
+                ]
+                .into(),
+            ),
+        }),
+    };
+
+    let _res = reply(deps.as_mut(), mock_env(), reply_msg).unwrap();
+    set_reserves(&mut deps, [100, 100]);
+
+    // the unbalanced deposit mints by the lesser ratio, 100 asset0000 => 100 LP,
+    // and the extra 100uusd is refunded within the slippage tolerance
+    let provide_msg = |min_lp_to_receive: u128| ExecuteMsg::ProvideLiquidity {
+        assets: [
+            Asset {
+                info: AssetInfo::Token {
+                    contract_addr: "asset0000".to_string(),
+                },
+                amount: Uint128::from(100u128),
+            },
+            Asset {
+                info: AssetInfo::NativeToken {
+                    denom: "uusd".to_string(),
+                },
+                amount: Uint128::from(200u128),
+            },
+        ],
+        receiver: None,
+        deadline: None,
+        slippage_tolerance: Some(Decimal::percent(50)),
+        min_lp_to_receive: Some(Uint128::from(min_lp_to_receive)),
+    };
+    let info = mock_info("addr0000", &[Coin::new(200u128, "uusd")]);
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        provide_msg(101u128),
+    );
+    match res {
+        Err(err) => {
+            assert_eq!(
+                err,
+                ContractError::MinLpToReceiveAssertion {
+                    minted: "100".to_string(),
+                    min_lp_to_receive: "101".to_string(),
+                }
+            );
+            assert_eq!(err.to_string(), "minted LP 100 less than minimum 101");
+        }
+        _ => panic!("Must return MinLpToReceiveAssertion"),
+    }
+
+    let res = execute(deps.as_mut(), mock_env(), info, provide_msg(100u128)).unwrap();
+    assert_eq!(
+        res.messages.last(),
+        Some(&SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "liquidity0000".to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Mint {
+                recipient: "addr0000".to_string(),
+                amount: Uint128::from(100u128),
+            })
+            .unwrap(),
+            funds: vec![],
+        })))
+    );
+    assert!(res
+        .attributes
+        .contains(&attr("refund_assets", "100uusd, 0asset0000")));
+}
+
+#[test]
+fn provide_liquidity_in_either_order() {
+    let uusd = Asset {
+        info: AssetInfo::NativeToken {
+            denom: "uusd".to_string(),
+        },
+        amount: Uint128::from(3_000_000u128),
+    };
+    let token = Asset {
+        info: AssetInfo::Token {
+            contract_addr: "asset0000".to_string(),
+        },
+        amount: Uint128::from(1_000_000u128),
+    };
+
+    let provide = |assets: [Asset; 2]| {
+        let mut deps = single_sided_pool(0, 0);
+        let simulation: SimulateProvideLiquidityResponse = from_binary(
+            &query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::SimulateProvideLiquidity {
+                    assets: assets.to_vec(),
+                    slippage_tolerance: None,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+
+        let info = mock_info("addr0000", &[Coin::new(3_000_000u128, "uusd")]);
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::ProvideLiquidity {
+                assets,
+                receiver: None,
+                deadline: None,
+                slippage_tolerance: None,
+                min_lp_to_receive: None,
+            },
+        )
+        .unwrap();
+
+        (
+            simulation,
+            res,
+            RESERVES.load(deps.as_ref().storage).unwrap(),
+        )
+    };
+
+    // the assets are matched against the pools, not taken in the given order
+    let (simulation, res, reserves) = provide([uusd.clone(), token.clone()]);
+    let (swapped_simulation, swapped_res, swapped_reserves) =
+        provide([token.clone(), uusd.clone()]);
+    assert_eq!(simulation, swapped_simulation);
+    assert_eq!(res, swapped_res);
+    assert_eq!(reserves, swapped_reserves);
+
+    assert_eq!(simulation.lp_minted, Uint128::from(1_000_000u128));
+    assert_eq!(
+        reserves,
+        [
+            Uint128::from(1_001_000_000u128),
+            Uint128::from(1_001_000_000u128)
+        ]
+    );
+    assert!(res
+        .attributes
+        .contains(&attr("assets", "3000000uusd, 1000000asset0000")));
+    assert!(res
+        .attributes
+        .contains(&attr("refund_assets", "2000000uusd, 0asset0000")));
+
+    // an asset given twice leaves the other pool unprovided
+    let mut deps = single_sided_pool(0, 0);
+    let info = mock_info("addr0000", &[Coin::new(3_000_000u128, "uusd")]);
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::ProvideLiquidity {
+            assets: [uusd.clone(), uusd.clone()],
+            receiver: None,
+            deadline: None,
+            slippage_tolerance: None,
+            min_lp_to_receive: None,
+        },
+    );
+    assert_eq!(res, Err(ContractError::AssetMismatch {}));
+
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::SimulateProvideLiquidity {
+            assets: vec![token.clone(), token],
+            slippage_tolerance: None,
+        },
+    );
+    assert_eq!(res, Err(ContractError::AssetMismatch {}));
+}
+
+#[test]
+fn zero_amounts_rejected() {
+    let uusd = |amount: u128| Asset {
+        info: AssetInfo::NativeToken {
+            denom: "uusd".to_string(),
+        },
+        amount: Uint128::from(amount),
+    };
+    let token = |amount: u128| Asset {
+        info: AssetInfo::Token {
+            contract_addr: "asset0000".to_string(),
+        },
+        amount: Uint128::from(amount),
+    };
+    let provide = |assets: [Asset; 2], funds: &[Coin]| {
+        let mut deps = single_sided_pool(0, 0);
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("addr0000", funds),
+            ExecuteMsg::ProvideLiquidity {
+                assets,
+                receiver: None,
+                deadline: None,
+                slippage_tolerance: None,
+                min_lp_to_receive: None,
+            },
+        )
+    };
+
+    let funds = [Coin::new(1_000u128, "uusd")];
+    assert_eq!(
+        provide([uusd(0), token(0)], &[]),
+        Err(ContractError::ZeroDeposits {})
+    );
+    assert_eq!(
+        provide([uusd(1_000), token(0)], &funds),
+        Err(ContractError::OneSidedDeposit {})
+    );
+    assert_eq!(
+        provide([uusd(0), token(1_000)], &[]),
+        Err(ContractError::OneSidedDeposit {})
+    );
+    assert_eq!(
+        provide([token(1_000), uusd(0)], &[]),
+        Err(ContractError::OneSidedDeposit {})
+    );
+    assert_eq!(
+        provide([uusd(0), token(1_000)], &funds),
+        Err(ContractError::ZeroAmountWithFunds {
+            denom: "uusd".to_string()
+        })
+    );
+    assert!(provide([uusd(1_000), token(1_000)], &funds).is_ok());
+
+    let swap = |offer_asset: Asset, funds: &[Coin]| {
+        let mut deps = single_sided_pool(0, 0);
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("addr0000", funds),
+            ExecuteMsg::Swap {
+                offer_asset,
+                belief_price: None,
+                belief_price_direction: None,
+                max_spread: None,
+                to: None,
+                deadline: None,
+                to_msg: None,
+                referral: None,
+            },
+        )
+    };
+    assert_eq!(swap(uusd(0), &[]), Err(ContractError::ZeroOfferAmount {}));
+    assert_eq!(
+        swap(uusd(0), &funds),
+        Err(ContractError::ZeroAmountWithFunds {
+            denom: "uusd".to_string()
+        })
+    );
+
+    let mut deps = single_sided_pool(0, 0);
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("asset0000", &[]),
+        ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: "addr0000".to_string(),
+            amount: Uint128::zero(),
+            msg: to_binary(&Cw20HookMsg::Swap {
+                belief_price: None,
+                belief_price_direction: None,
+                max_spread: None,
+                to: None,
+                deadline: None,
+                to_msg: None,
+                referral: None,
+            })
+            .unwrap(),
+        }),
+    );
+    assert_eq!(res, Err(ContractError::ZeroOfferAmount {}));
+}
+
+#[test]
+fn receive_from_spoofed_token() {
+    let mut deps = single_sided_pool(0, 0);
+    let swap_hook = to_binary(&Cw20HookMsg::Swap {
+        belief_price: None,
+        belief_price_direction: None,
+        max_spread: None,
+        to: None,
+        deadline: None,
+        to_msg: None,
+        referral: None,
+    })
+    .unwrap();
+    let withdraw_hook = to_binary(&Cw20HookMsg::WithdrawLiquidity {
+        min_assets: None,
+        deadline: None,
+        receiver: None,
+    })
+    .unwrap();
+    let mut receive = |token: &str, msg: &Binary| {
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(token, &[]),
+            ExecuteMsg::Receive(Cw20ReceiveMsg {
+                sender: "addr0000".to_string(),
+                amount: Uint128::from(1_000u128),
+                msg: msg.clone(),
+            }),
+        )
+    };
+
+    // a token which is not an asset of the pair swaps nothing
+    assert_eq!(
+        receive("spoofed0000", &swap_hook),
+        Err(ContractError::Unauthorized {})
+    );
+    assert_eq!(
+        receive("liquidity0000", &swap_hook),
+        Err(ContractError::Unauthorized {})
+    );
+    assert!(receive("asset0000", &swap_hook).is_ok());
+
+    // only the liquidity token withdraws
+    assert_eq!(
+        receive("spoofed0000", &withdraw_hook),
+        Err(ContractError::Unauthorized {})
+    );
+    assert_eq!(
+        receive("asset0000", &withdraw_hook),
+        Err(ContractError::Unauthorized {})
+    );
+    assert!(receive("liquidity0000", &withdraw_hook).is_ok());
+}
+
+#[test]
+fn provide_liquidity_with_expired_deadline() {
+    let mut deps = mock_dependencies(&[]);
+    deps.querier
+        .with_token_balances(&[(&"asset0000".to_string(), &[])]);
+
+    let msg = InstantiateMsg {
+        asset_infos: [
+            AssetInfo::NativeToken {
+                denom: "uusd".to_string(),
+            },
+            AssetInfo::Token {
+                contract_addr: "asset0000".to_string(),
+            },
+        ],
+        token_code_id: 10u64,
+        asset_decimals: [6u8, 8u8],
+        lp_fee_rate: None,
+        protocol_fee_rate: None,
+        protocol_fee_collector: None,
+        pair_admin: None,
+        max_spread_cap: None,
+    };
+
+    let info = mock_info("addr0000", &[]);
+    let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    // a deadline just before the current block time is expired
+    let env = mock_env();
+    for deadline in [100u64, env.block.time.seconds() - 1] {
+        let msg = ExecuteMsg::ProvideLiquidity {
+            assets: [
+                Asset {
+                    info: AssetInfo::Token {
+                        contract_addr: "asset0000".to_string(),
+                    },
+                    amount: Uint128::from(100u128),
+                },
+                Asset {
+                    info: AssetInfo::NativeToken {
+                        denom: "uusd".to_string(),
+                    },
+                    amount: Uint128::from(100u128),
+                },
+            ],
+            receiver: None,
+            deadline: Some(deadline),
+            slippage_tolerance: None,
+            min_lp_to_receive: None,
+        };
+        let info = mock_info("addr0000", &[Coin::new(100u128, "uusd")]);
+        let err = execute(deps.as_mut(), env.clone(), info, msg).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::Deadline(DeadlineError {
+                deadline,
+                block_time: env.block.time.seconds(),
+            })
+        );
+    }
+}
+
+/// Sets the tracked reserves of uusd and asset0000
+fn set_reserves(
+    deps: &mut OwnedDeps<MockStorage, MockApi, WasmMockQuerier, TerraQuery>,
+    reserves: [u128; 2],
+) {
+    RESERVES
+        .save(
+            deps.as_mut().storage,
+            &[Uint128::from(reserves[0]), Uint128::from(reserves[1])],
+        )
+        .unwrap();
+}
+
+/// A 1:1 pool of 1000uusd and 1000asset0000 with 1000 LP tokens, the balances
+/// already include `uusd_deposit` and `token_deposit`
+fn single_sided_pool(
+    uusd_deposit: u128,
+    token_deposit: u128,
+) -> OwnedDeps<MockStorage, MockApi, WasmMockQuerier, TerraQuery> {
+    let mut deps = mock_dependencies(&[Coin::new(1_000_000_000u128 + uusd_deposit, "uusd")]);
+    deps.querier.with_tax(
+        Decimal::zero(),
+        &[(&"uusd".to_string(), &Uint128::from(1000000u128))],
+    );
+    deps.querier.with_token_balances(&[
+        (
+            &"liquidity0000".to_string(),
+            &[(
+                &MOCK_CONTRACT_ADDR.to_string(),
+                &Uint128::from(1_000_000_000u128),
+            )],
+        ),
+        (
+            &"asset0000".to_string(),
+            &[(
+                &MOCK_CONTRACT_ADDR.to_string(),
+                &Uint128::from(1_000_000_000u128 + token_deposit),
+            )],
+        ),
+    ]);
+
+    let msg = InstantiateMsg {
+        asset_infos: [
+            AssetInfo::NativeToken {
+                denom: "uusd".to_string(),
+            },
+            AssetInfo::Token {
+                contract_addr: "asset0000".to_string(),
+            },
+        ],
+        token_code_id: 10u64,
+        asset_decimals: [6u8, 6u8],
+        lp_fee_rate: None,
+        protocol_fee_rate: None,
+        protocol_fee_collector: None,
+        pair_admin: None,
+        max_spread_cap: None,
+    };
+    let info = mock_info("addr0000", &[]);
+    let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    // store liquidity token
+    let reply_msg = Reply {
+        id: 1,
+        result: SubMsgResult::Ok(SubMsgResponse {
+            events: vec![],
+            data: Some(
+                vec![
+                    10, 13, 108, 105, 113, 117, 105, 100, 105, 116, 121, 48, 48, 48, 48,
+                ]
+                .into(),
+            ),
+        }),
+    };
+    let _res = reply(deps.as_mut(), mock_env(), reply_msg).unwrap();
+    set_reserves(&mut deps, [1_000_000_000, 1_000_000_000]);
+
+    deps
+}
+
+/// Swaps `swap_amount` of 100uusd then provides the rest with the swap return,
+/// returning the minted LP tokens
+fn swap_then_provide(swap_amount: u128) -> Uint128 {
+    let deposit = 100_000_000u128;
+    let mut deps = single_sided_pool(swap_amount, 0);
+
+    let msg = ExecuteMsg::Swap {
+        offer_asset: Asset {
+            info: AssetInfo::NativeToken {
+                denom: "uusd".to_string(),
+            },
+            amount: Uint128::from(swap_amount),
+        },
+        belief_price: None,
+        belief_price_direction: None,
+        max_spread: None,
+        to: None,
+        deadline: None,
+        to_msg: None,
+        referral: None,
+    };
+    let info = mock_info("addr0000", &[Coin::new(swap_amount, "uusd")]);
+    let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    let return_amount = res
+        .attributes
+        .iter()
+        .find(|attr| attr.key == "return_amount")
+        .map(|attr| Uint128::from_str(&attr.value).unwrap())
+        .unwrap();
+
+    // the commission stays in the pool
+    deps.querier.with_balance(&[(
+        &MOCK_CONTRACT_ADDR.to_string(),
+        vec![Coin::new(1_000_000_000u128 + deposit, "uusd")],
+    )]);
+    deps.querier.with_token_balances(&[
+        (
+            &"liquidity0000".to_string(),
+            &[(
+                &MOCK_CONTRACT_ADDR.to_string(),
+                &Uint128::from(1_000_000_000u128),
+            )],
+        ),
+        (
+            &"asset0000".to_string(),
+            &[(
+                &MOCK_CONTRACT_ADDR.to_string(),
+                &(Uint128::from(1_000_000_000u128) - return_amount),
+            )],
+        ),
+    ]);
+
+    let msg = ExecuteMsg::ProvideLiquidity {
+        assets: [
+            Asset {
+                info: AssetInfo::Token {
+                    contract_addr: "asset0000".to_string(),
+                },
+                amount: return_amount,
+            },
+            Asset {
+                info: AssetInfo::NativeToken {
+                    denom: "uusd".to_string(),
+                },
+                amount: Uint128::from(deposit - swap_amount),
+            },
+        ],
+        receiver: None,
+        deadline: None,
+        slippage_tolerance: None,
+        min_lp_to_receive: None,
+    };
+    let info = mock_info("addr0000", &[Coin::new(deposit - swap_amount, "uusd")]);
+    let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    res.attributes
+        .iter()
+        .find(|attr| attr.key == "share")
+        .map(|attr| Uint128::from_str(&attr.value).unwrap())
+        .unwrap()
+}
+
+#[test]
+fn provide_liquidity_single() {
+    let deposit = 100_000_000u128;
+    let mut deps = single_sided_pool(0, 0);
+
+    // 48.885753uusd of the deposit is swapped to 46.467497asset0000
+    let expected_swap_amount = Uint128::from(48_885_753u128);
+    let expected_share = Uint128::from(48_731_948u128);
+
+    // the simulation does not expect the deposit in the pool
+    let res: SimulateProvideLiquiditySingleResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::SimulateProvideLiquiditySingle {
+                asset: Asset {
+                    info: AssetInfo::NativeToken {
+                        denom: "uusd".to_string(),
+                    },
+                    amount: Uint128::from(deposit),
+                },
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        res,
+        SimulateProvideLiquiditySingleResponse {
+            lp_amount: expected_share,
+            swap_amount: expected_swap_amount,
+            price_impact: Decimal::from_str("0.04674100900852627").unwrap(),
+        }
+    );
+
+    deps.querier.with_balance(&[(
+        &MOCK_CONTRACT_ADDR.to_string(),
+        vec![Coin::new(1_000_000_000u128 + deposit, "uusd")],
+    )]);
+    let msg = ExecuteMsg::ProvideLiquiditySingle {
+        asset: Asset {
+            info: AssetInfo::NativeToken {
+                denom: "uusd".to_string(),
+            },
+            amount: Uint128::from(deposit),
+        },
+        min_lp_to_receive: Some(expected_share + Uint128::from(1u8)),
+        deadline: None,
+    };
+    let info = mock_info("addr0000", &[Coin::new(deposit, "uusd")]);
+    let res = execute(deps.as_mut(), mock_env(), info.clone(), msg);
+    assert_eq!(
+        res,
+        Err(ContractError::MinLpToReceiveAssertion {
+            minted: "48731948".to_string(),
+            min_lp_to_receive: "48731949".to_string(),
+        })
+    );
+
+    let msg = ExecuteMsg::ProvideLiquiditySingle {
+        asset: Asset {
+            info: AssetInfo::NativeToken {
+                denom: "uusd".to_string(),
+            },
+            amount: Uint128::from(deposit),
+        },
+        min_lp_to_receive: Some(expected_share),
+        deadline: None,
+    };
+    let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    // the rounding dust is refunded
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+                to_address: "addr0000".to_string(),
+                amount: vec![Coin::new(1u128, "uusd")],
+            })),
+            SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "liquidity0000".to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::Mint {
+                    recipient: "addr0000".to_string(),
+                    amount: expected_share,
+                })
+                .unwrap(),
+                funds: vec![],
+            })),
+        ]
+    );
+    assert!(res
+        .attributes
+        .contains(&attr("swap_amount", expected_swap_amount.to_string())));
+
+    // the same as swapping the optimal amount then providing,
+    // and more than swapping half of the deposit
+    assert_eq!(
+        swap_then_provide(expected_swap_amount.u128()),
+        expected_share
+    );
+    assert!(swap_then_provide(deposit / 2) < expected_share);
+}
+
+#[test]
+fn provide_liquidity_single_with_token() {
+    let deposit = 100_000_000u128;
+    let mut deps = single_sided_pool(0, deposit);
+
+    let hook_msg = |sender: &str| {
+        (
+            mock_info(sender, &[]),
+            ExecuteMsg::Receive(Cw20ReceiveMsg {
+                sender: "addr0000".to_string(),
+                amount: Uint128::from(deposit),
+                msg: to_binary(&Cw20HookMsg::ProvideLiquiditySingle {
+                    min_lp_to_receive: None,
+                    deadline: None,
+                })
+                .unwrap(),
+            }),
+        )
+    };
+
+    // only the pair tokens can provide
+    let (info, msg) = hook_msg("asset0001");
+    let res = execute(deps.as_mut(), mock_env(), info, msg);
+    assert_eq!(res, Err(ContractError::Unauthorized {}));
+
+    // the pool is symmetric, the same share as the native deposit
+    let (info, msg) = hook_msg("asset0000");
+    let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "asset0000".to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: "addr0000".to_string(),
+                    amount: Uint128::from(1u128),
+                })
+                .unwrap(),
+                funds: vec![],
+            })),
+            SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "liquidity0000".to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::Mint {
+                    recipient: "addr0000".to_string(),
+                    amount: Uint128::from(48_731_948u128),
+                })
+                .unwrap(),
+                funds: vec![],
+            })),
+        ]
+    );
+
+    // the native variant does not take tokens
+    let msg = ExecuteMsg::ProvideLiquiditySingle {
+        asset: Asset {
+            info: AssetInfo::Token {
+                contract_addr: "asset0000".to_string(),
+            },
+            amount: Uint128::from(deposit),
+        },
+        min_lp_to_receive: None,
+        deadline: None,
+    };
+    let res = execute(deps.as_mut(), mock_env(), mock_info("addr0000", &[]), msg);
+    assert_eq!(res, Err(ContractError::Unauthorized {}));
+}
+
+#[test]
+fn provide_liquidity_single_to_empty_pool() {
+    let mut deps = single_sided_pool(100u128, 0);
+    deps.querier.with_token_balances(&[
+        (
+            &"liquidity0000".to_string(),
+            &[(&MOCK_CONTRACT_ADDR.to_string(), &Uint128::zero())],
+        ),
+        (&"asset0000".to_string(), &[]),
+    ]);
+
+    let msg = ExecuteMsg::ProvideLiquiditySingle {
+        asset: Asset {
+            info: AssetInfo::NativeToken {
+                denom: "uusd".to_string(),
+            },
+            amount: Uint128::from(100u128),
+        },
+        min_lp_to_receive: None,
+        deadline: None,
+    };
+    let info = mock_info("addr0000", &[Coin::new(100u128, "uusd")]);
+    let res = execute(deps.as_mut(), mock_env(), info, msg);
+    assert_eq!(res, Err(ContractError::NoLiquidity {}));
+}
+
+#[test]
+fn withdraw_liquidity_single() {
+    let mut deps = single_sided_pool(0, 0);
+
+    let withdraw_msg = |amount: u128, ask_asset_info: AssetInfo, min_receive: Option<u128>| {
+        ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: "addr0000".to_string(),
+            msg: to_binary(&Cw20HookMsg::WithdrawLiquiditySingle {
+                ask_asset_info,
+                min_receive: min_receive.map(Uint128::from),
+                deadline: None,
+                receiver: None,
+            })
+            .unwrap(),
+            amount: Uint128::from(amount),
+        })
+    };
+    let uusd = AssetInfo::NativeToken {
+        denom: "uusd".to_string(),
+    };
+
+    // only the liquidity token can withdraw
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("asset0000", &[]),
+        withdraw_msg(100_000_000, uusd.clone(), None),
+    );
+    assert_eq!(res, Err(ContractError::Unauthorized {}));
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("liquidity0000", &[]),
+        withdraw_msg(
+            100_000_000,
+            AssetInfo::NativeToken {
+                denom: "ukrw".to_string(),
+            },
+            None,
+        ),
+    );
+    assert_eq!(res, Err(ContractError::AssetMismatch {}));
+
+    // 100uusd and 100asset0000 are refunded,
+    // 100asset0000 is swapped to 89.729999uusd against the 900:900 pool left
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("liquidity0000", &[]),
+        withdraw_msg(100_000_000, uusd.clone(), Some(189_730_000)),
+    );
+    assert_eq!(
+        res,
+        Err(ContractError::MinAmountAssertion {
+            min_asset: "189730000uusd".to_string(),
+            asset: "189729999uusd".to_string(),
+        })
+    );
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("liquidity0000", &[]),
+        withdraw_msg(100_000_000, uusd.clone(), Some(189_729_999)),
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+                to_address: "addr0000".to_string(),
+                amount: vec![Coin::new(189_729_999u128, "uusd")],
+            })),
+            SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "liquidity0000".to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::Burn {
+                    amount: Uint128::from(100_000_000u128),
+                })
+                .unwrap(),
+                funds: vec![],
+            })),
+        ]
+    );
+    assert!(res.attributes.contains(&attr("swap_amount", "100000000")));
+
+    // the same as withdrawing then swapping the token refund
+    let mut deps = single_sided_pool(0, 0);
+    set_reserves(&mut deps, [900_000_000, 900_000_000]);
+    deps.querier.with_balance(&[(
+        &MOCK_CONTRACT_ADDR.to_string(),
+        vec![Coin::new(900_000_000u128, "uusd")],
+    )]);
+    deps.querier.with_token_balances(&[
+        (
+            &"liquidity0000".to_string(),
+            &[(
+                &MOCK_CONTRACT_ADDR.to_string(),
+                &Uint128::from(900_000_000u128),
+            )],
+        ),
+        (
+            &"asset0000".to_string(),
+            &[(
+                &MOCK_CONTRACT_ADDR.to_string(),
+                &Uint128::from(1_000_000_000u128),
+            )],
+        ),
+    ]);
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "addr0000".to_string(),
+        amount: Uint128::from(100_000_000u128),
+        msg: to_binary(&Cw20HookMsg::Swap {
+            belief_price: None,
+            belief_price_direction: None,
+            max_spread: None,
+            to: None,
+            deadline: None,
+            to_msg: None,
+            referral: None,
+        })
+        .unwrap(),
+    });
+    let res = execute(deps.as_mut(), mock_env(), mock_info("asset0000", &[]), msg).unwrap();
+    assert!(res.attributes.contains(&attr("return_amount", "89729999")));
+}
+
+#[test]
+fn withdraw_liquidity_single_small_share() {
+    let mut deps = single_sided_pool(0, 0);
+
+    // a 0.1% withdrawal refunds 1uusd and 1asset0000,
+    // 1asset0000 is swapped to 0.996002uusd against the 999:999 pool left
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "addr0000".to_string(),
+        msg: to_binary(&Cw20HookMsg::WithdrawLiquiditySingle {
+            ask_asset_info: AssetInfo::NativeToken {
+                denom: "uusd".to_string(),
+            },
+            min_receive: Some(Uint128::from(1_996_002u128)),
+            deadline: None,
+            receiver: None,
+        })
+        .unwrap(),
+        amount: Uint128::from(1_000_000u128),
+    });
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("liquidity0000", &[]),
+        msg,
+    )
+    .unwrap();
+    assert!(res.attributes.contains(&attr("swap_amount", "1000000")));
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+                to_address: "addr0000".to_string(),
+                amount: vec![Coin::new(1_996_002u128, "uusd")],
+            })),
+            SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "liquidity0000".to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::Burn {
+                    amount: Uint128::from(1_000_000u128),
+                })
+                .unwrap(),
+                funds: vec![],
+            })),
+        ]
+    );
+}
+
+#[test]
+fn withdraw_liquidity_single_worse_than_symmetric() {
+    let withdraw_msg = |amount: u128| {
+        ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: "addr0000".to_string(),
+            msg: to_binary(&Cw20HookMsg::WithdrawLiquiditySingle {
+                ask_asset_info: AssetInfo::NativeToken {
+                    denom: "uusd".to_string(),
+                },
+                min_receive: None,
+                deadline: None,
+                receiver: None,
+            })
+            .unwrap(),
+            amount: Uint128::from(amount),
+        })
+    };
+
+    // 2asset0000 are worth 1uusd after the commission but the swap returns nothing
+    let mut deps = single_sided_pool(0, 0);
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("liquidity0000", &[]),
+        withdraw_msg(2),
+    );
+    assert_eq!(res, Err(ContractError::WithdrawSingleWorseThanSymmetric {}));
+
+    // nothing is left to swap against
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("liquidity0000", &[]),
+        withdraw_msg(1_000_000_000),
+    );
+    assert_eq!(res, Err(ContractError::WithdrawSingleWorseThanSymmetric {}));
+}
+
+#[test]
+fn swap_with_to_msg() {
+    let hook = to_binary(&"hook").unwrap();
+    let return_amount = |res: &Response<TerraMsg>| {
+        res.attributes
+            .iter()
+            .find(|attr| attr.key == "return_amount")
+            .map(|attr| Uint128::from_str(&attr.value).unwrap())
+            .unwrap()
+    };
+
+    // the token return is sent to the contract with the hook
+    let mut deps = single_sided_pool(1_000_000, 0);
+    let msg = ExecuteMsg::Swap {
+        offer_asset: Asset {
+            info: AssetInfo::NativeToken {
+                denom: "uusd".to_string(),
+            },
+            amount: Uint128::from(1_000_000u128),
+        },
+        belief_price: None,
+        belief_price_direction: None,
+        max_spread: None,
+        to: Some("receiver0000".to_string()),
+        deadline: None,
+        to_msg: Some(hook.clone()),
+        referral: None,
+    };
+    let info = mock_info("addr0000", &[Coin::new(1_000_000u128, "uusd")]);
+    let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "asset0000".to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Send {
+                contract: "receiver0000".to_string(),
+                amount: return_amount(&res),
+                msg: hook.clone(),
+            })
+            .unwrap(),
+            funds: vec![],
+        }))]
+    );
+
+    // the native return is attached to the hook execution
+    let mut deps = single_sided_pool(0, 1_000_000);
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "addr0000".to_string(),
+        amount: Uint128::from(1_000_000u128),
+        msg: to_binary(&Cw20HookMsg::Swap {
+            belief_price: None,
+            belief_price_direction: None,
+            max_spread: None,
+            to: Some("receiver0000".to_string()),
+            deadline: None,
+            to_msg: Some(hook.clone()),
+            referral: None,
+        })
+        .unwrap(),
+    });
+    let res = execute(deps.as_mut(), mock_env(), mock_info("asset0000", &[]), msg).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "receiver0000".to_string(),
+            msg: hook.clone(),
+            funds: vec![Coin::new(return_amount(&res).u128(), "uusd")],
+        }))]
+    );
+
+    // the hook needs a receiver
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "addr0000".to_string(),
+        amount: Uint128::from(1_000_000u128),
+        msg: to_binary(&Cw20HookMsg::Swap {
+            belief_price: None,
+            belief_price_direction: None,
+            max_spread: None,
+            to: None,
+            deadline: None,
+            to_msg: Some(hook),
+            referral: None,
+        })
+        .unwrap(),
+    });
+    let res = execute(deps.as_mut(), mock_env(), mock_info("asset0000", &[]), msg);
+    assert_eq!(res, Err(ContractError::ToMsgWithoutTo {}));
+}
+
+#[test]
+fn swap_with_max_spread_cap() {
+    let swap_msg = |amount: u128, max_spread: Option<&str>| {
+        (
+            mock_info("addr0000", &[Coin::new(amount, "uusd")]),
+            ExecuteMsg::Swap {
+                offer_asset: Asset {
+                    info: AssetInfo::NativeToken {
+                        denom: "uusd".to_string(),
+                    },
+                    amount: Uint128::from(amount),
+                },
+                belief_price: None,
+                belief_price_direction: None,
+                max_spread: max_spread.map(|max_spread| Decimal::from_str(max_spread).unwrap()),
+                to: None,
+                deadline: None,
+                to_msg: None,
+                referral: None,
+            },
+        )
+    };
+    let clamped = attr("max_spread_clamped", "true");
+
+    // without a cap the swap passes through with a 9% spread
+    let mut deps = single_sided_pool(100_000_000, 0);
+    let (info, msg) = swap_msg(100_000_000, None);
+    let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    assert!(!res.attributes.contains(&clamped));
+
+    // only the factory owner can set the cap
+    let msg = ExecuteMsg::UpdateMaxSpreadCap {
+        max_spread_cap: Some(Decimal::percent(1)),
+    };
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        msg.clone(),
+    );
+    assert_eq!(res, Err(ContractError::Unauthorized {}));
+    execute(deps.as_mut(), mock_env(), mock_info("owner0000", &[]), msg).unwrap();
+
+    let res: ConfigResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
+    assert_eq!(
+        res,
+        ConfigResponse {
+            factory: Addr::unchecked("addr0000"),
+            pair_admin: None,
+            max_spread_cap: Some(Decimal::percent(1)),
+            max_trade_ratio: None,
+        }
+    );
+
+    // the cap applies without a max spread and above it
+    let (info, msg) = swap_msg(100_000_000, None);
+    let res = execute(deps.as_mut(), mock_env(), info, msg);
+    assert_eq!(res, Err(ContractError::MaxSpreadAssertion {}));
+    let (info, msg) = swap_msg(100_000_000, Some("0.5"));
+    let res = execute(deps.as_mut(), mock_env(), info, msg);
+    assert_eq!(res, Err(ContractError::MaxSpreadAssertion {}));
+
+    // a 0.5% spread is within the cap
+    let mut deps = single_sided_pool(5_000_000, 0);
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("owner0000", &[]),
+        ExecuteMsg::UpdateMaxSpreadCap {
+            max_spread_cap: Some(Decimal::percent(1)),
+        },
+    )
+    .unwrap();
+    let (info, msg) = swap_msg(5_000_000, Some("0.5"));
+    let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    assert!(res.attributes.contains(&clamped));
+
+    // a max spread below the cap is kept
+    let (info, msg) = swap_msg(5_000_000, Some("0.004"));
+    let res = execute(deps.as_mut(), mock_env(), info, msg);
+    assert_eq!(res, Err(ContractError::MaxSpreadAssertion {}));
+    let (info, msg) = swap_msg(5_000_000, Some("0.0099"));
+    let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    assert!(!res.attributes.contains(&clamped));
+
+    // the cap can be removed
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("owner0000", &[]),
+        ExecuteMsg::UpdateMaxSpreadCap {
+            max_spread_cap: None,
+        },
+    )
+    .unwrap();
+    let res: ConfigResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
+    assert_eq!(res.max_spread_cap, None);
+}
+
+#[test]
+fn swap_with_max_trade_ratio() {
+    let exact_out = |ask_amount: u128| {
+        (
+            mock_info("addr0000", &[Coin::new(20_000_000u128, "uusd")]),
+            ExecuteMsg::SwapExactOut {
+                ask_asset: Asset {
+                    info: AssetInfo::Token {
+                        contract_addr: "asset0000".to_string(),
+                    },
+                    amount: Uint128::from(ask_amount),
+                },
+                max_offer_amount: Uint128::from(20_000_000u128),
+                to: None,
+                deadline: None,
+            },
+        )
+    };
+    // 1% of the 1_000_000_000 ask reserve
+    let limited_pool = || {
+        let mut deps = single_sided_pool(20_000_000, 0);
+        let msg = ExecuteMsg::UpdateMaxTradeRatio {
+            max_trade_ratio: Some(Decimal::percent(1)),
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("owner0000", &[]), msg).unwrap();
+        deps
+    };
+
+    // only the factory owner or the pair admin can set the ratio, within (0, 1]
+    let mut deps = single_sided_pool(0, 0);
+    let msg = |ratio: u64| ExecuteMsg::UpdateMaxTradeRatio {
+        max_trade_ratio: Some(Decimal::percent(ratio)),
+    };
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        msg(1),
+    );
+    assert_eq!(res, Err(ContractError::Unauthorized {}));
+    for ratio in [0, 101] {
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("owner0000", &[]),
+            msg(ratio),
+        );
+        assert_eq!(res, Err(ContractError::InvalidMaxTradeRatio {}));
+    }
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("owner0000", &[]),
+        msg(1),
+    )
+    .unwrap();
+    assert!(res.attributes.contains(&attr("max_trade_ratio", "0.01")));
+    let res: ConfigResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
+    assert_eq!(res.max_trade_ratio, Some(Decimal::percent(1)));
+
+    // below and at the limit
+    for ask_amount in [9_999_999u128, 10_000_000u128] {
+        let mut deps = limited_pool();
+        let res: ReverseSimulationResponse = query_reverse_simulation(
+            deps.as_ref(),
+            Asset {
+                info: AssetInfo::Token {
+                    contract_addr: "asset0000".to_string(),
+                },
+                amount: Uint128::from(ask_amount),
+            },
+            None,
+        )
+        .unwrap();
+        assert!(!res.exceeds_trade_limit);
+
+        let (info, msg) = exact_out(ask_amount);
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    }
+
+    // above the limit the simulation warns and the swap is rejected
+    let mut deps = limited_pool();
+    let res: ReverseSimulationResponse = query_reverse_simulation(
+        deps.as_ref(),
+        Asset {
+            info: AssetInfo::Token {
+                contract_addr: "asset0000".to_string(),
+            },
+            amount: Uint128::from(10_000_001u128),
+        },
+        None,
+    )
+    .unwrap();
+    assert!(res.exceeds_trade_limit);
+
+    let (info, msg) = exact_out(10_000_001);
+    let res = execute(deps.as_mut(), mock_env(), info, msg);
+    assert_eq!(
+        res,
+        Err(ContractError::TradeLimitExceeded {
+            limit: "10000000".to_string(),
+            amount: "10000001".to_string(),
+        })
+    );
+
+    let offer_asset = Asset {
+        info: AssetInfo::NativeToken {
+            denom: "uusd".to_string(),
+        },
+        amount: Uint128::from(20_000_000u128),
+    };
+    let res: SimulationResponse =
+        query_simulation(deps.as_ref(), offer_asset.clone(), None).unwrap();
+    assert!(res.exceeds_trade_limit);
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[Coin::new(20_000_000u128, "uusd")]),
+        ExecuteMsg::Swap {
+            offer_asset,
+            belief_price: None,
+            belief_price_direction: None,
+            max_spread: None,
+            to: None,
+            deadline: None,
+            to_msg: None,
+            referral: None,
+        },
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::TradeLimitExceeded {
+            limit: "10000000".to_string(),
+            amount: res.return_amount.to_string(),
+        }
+    );
+
+    // removing the ratio lifts the limit
+    let msg = ExecuteMsg::UpdateMaxTradeRatio {
+        max_trade_ratio: None,
+    };
+    execute(deps.as_mut(), mock_env(), mock_info("owner0000", &[]), msg).unwrap();
+    let (info, msg) = exact_out(10_000_001);
+    execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+}
+
+#[test]
+fn set_pool_status() {
+    let mut deps = single_sided_pool(1_000_000, 0);
+
+    let set_status = |sender: &str, swaps_paused: bool, deposits_paused: bool| {
+        (
+            mock_info(sender, &[]),
+            ExecuteMsg::SetPoolStatus {
+                swaps_paused,
+                deposits_paused,
+            },
+        )
+    };
+    let swap = || {
+        (
+            mock_info("addr0000", &[Coin::new(1_000_000u128, "uusd")]),
+            ExecuteMsg::Swap {
+                offer_asset: Asset {
+                    info: AssetInfo::NativeToken {
+                        denom: "uusd".to_string(),
+                    },
+                    amount: Uint128::from(1_000_000u128),
+                },
+                belief_price: None,
+                belief_price_direction: None,
+                max_spread: None,
+                to: None,
+                deadline: None,
+                to_msg: None,
+                referral: None,
+            },
+        )
+    };
+    let provide = || {
+        (
+            mock_info("addr0000", &[Coin::new(1_000_000u128, "uusd")]),
+            ExecuteMsg::ProvideLiquiditySingle {
+                asset: Asset {
+                    info: AssetInfo::NativeToken {
+                        denom: "uusd".to_string(),
+                    },
+                    amount: Uint128::from(1_000_000u128),
+                },
+                min_lp_to_receive: None,
+                deadline: None,
+            },
+        )
+    };
+    let withdraw = |msg: Cw20HookMsg| {
+        (
+            mock_info("liquidity0000", &[]),
+            ExecuteMsg::Receive(Cw20ReceiveMsg {
+                sender: "addr0000".to_string(),
+                amount: Uint128::from(1_000_000u128),
+                msg: to_binary(&msg).unwrap(),
+            }),
+        )
+    };
+    let withdraw_liquidity = || {
+        withdraw(Cw20HookMsg::WithdrawLiquidity {
+            min_assets: None,
+            deadline: None,
+            receiver: None,
+        })
+    };
+    let withdraw_liquidity_single = || {
+        withdraw(Cw20HookMsg::WithdrawLiquiditySingle {
+            ask_asset_info: AssetInfo::NativeToken {
+                denom: "uusd".to_string(),
+            },
+            min_receive: None,
+            deadline: None,
+            receiver: None,
+        })
+    };
+
+    // only the factory owner or the pair admin can pause
+    let (info, msg) = set_status("addr0000", true, true);
+    let res = execute(deps.as_mut(), mock_env(), info, msg);
+    assert_eq!(res, Err(ContractError::Unauthorized {}));
+
+    // swaps paused
+    let (info, msg) = set_status("owner0000", true, false);
+    let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    assert_eq!(
+        res.attributes,
+        vec![
+            attr("action", "set_pool_status"),
+            attr("swaps_paused", "true"),
+            attr("deposits_paused", "false"),
+        ]
+    );
+    let res: PoolStatus =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::PoolStatus {}).unwrap()).unwrap();
+    assert_eq!(
+        res,
+        PoolStatus {
+            swaps_paused: true,
+            deposits_paused: false,
+        }
+    );
+
+    let (info, msg) = swap();
+    let res = execute(deps.as_mut(), mock_env(), info, msg);
+    assert_eq!(res, Err(ContractError::SwapsPaused {}));
+    let (info, msg) = provide();
+    let res = execute(deps.as_mut(), mock_env(), info, msg);
+    assert_eq!(res, Err(ContractError::SwapsPaused {}));
+    let (info, msg) = withdraw_liquidity_single();
+    let res = execute(deps.as_mut(), mock_env(), info, msg);
+    assert_eq!(res, Err(ContractError::SwapsPaused {}));
+    let (info, msg) = withdraw_liquidity();
+    execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    // deposits paused
+    let (info, msg) = set_status("owner0000", false, true);
+    execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    let (info, msg) = swap();
+    execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    let (info, msg) = provide();
+    let res = execute(deps.as_mut(), mock_env(), info, msg);
+    assert_eq!(res, Err(ContractError::DepositsPaused {}));
+    let msg = ExecuteMsg::ProvideLiquidity {
+        assets: [
+            Asset {
+                info: AssetInfo::Token {
+                    contract_addr: "asset0000".to_string(),
+                },
+                amount: Uint128::from(1_000_000u128),
+            },
+            Asset {
+                info: AssetInfo::NativeToken {
+                    denom: "uusd".to_string(),
+                },
+                amount: Uint128::from(1_000_000u128),
+            },
+        ],
+        receiver: None,
+        deadline: None,
+        slippage_tolerance: None,
+        min_lp_to_receive: None,
+    };
+    let info = mock_info("addr0000", &[Coin::new(1_000_000u128, "uusd")]);
+    let res = execute(deps.as_mut(), mock_env(), info, msg);
+    assert_eq!(res, Err(ContractError::DepositsPaused {}));
+    let (info, msg) = withdraw_liquidity_single();
+    execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    let (info, msg) = withdraw_liquidity();
+    execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    // unpaused
+    let (info, msg) = set_status("owner0000", false, false);
+    execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    let (info, msg) = provide();
+    execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+}
+
+#[test]
+fn set_pool_status_by_pair_admin() {
+    let mut deps = mock_dependencies(&[]);
+    deps.querier
+        .with_token_balances(&[(&"asset0000".to_string(), &[])]);
+
+    let msg = InstantiateMsg {
+        asset_infos: [
+            AssetInfo::NativeToken {
+                denom: "uusd".to_string(),
+            },
+            AssetInfo::Token {
+                contract_addr: "asset0000".to_string(),
+            },
+        ],
+        token_code_id: 10u64,
+        asset_decimals: [6u8, 8u8],
+        lp_fee_rate: None,
+        protocol_fee_rate: None,
+        protocol_fee_collector: None,
+        pair_admin: Some("admin0000".to_string()),
+        max_spread_cap: None,
+    };
+    let info = mock_info("factory0000", &[]);
+    let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    let res: ConfigResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
+    assert_eq!(res.pair_admin, Some(Addr::unchecked("admin0000")));
+
+    let msg = ExecuteMsg::SetPoolStatus {
+        swaps_paused: true,
+        deposits_paused: true,
+    };
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        msg.clone(),
+    );
+    assert_eq!(res, Err(ContractError::Unauthorized {}));
+    execute(deps.as_mut(), mock_env(), mock_info("admin0000", &[]), msg).unwrap();
+
+    let res: PoolStatus =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::PoolStatus {}).unwrap()).unwrap();
+    assert_eq!(
+        res,
+        PoolStatus {
+            swaps_paused: true,
+            deposits_paused: true,
+        }
+    );
+}
+
+#[test]
+fn skim_and_sync() {
+    // 5uusd and 3asset0000 were transferred to the pair outside of any handler
+    let mut deps = single_sided_pool(5_000_000, 3_000_000);
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        ExecuteMsg::Skim {
+            to: Some("addr0001".to_string()),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+                to_address: "addr0001".to_string(),
+                amount: vec![Coin::new(5_000_000u128, "uusd")],
+            })),
+            SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "asset0000".to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: "addr0001".to_string(),
+                    amount: Uint128::from(3_000_000u128),
+                })
+                .unwrap(),
+                funds: vec![],
+            })),
+        ]
+    );
+
+    // the stray transfers are not part of the pool
+    let res: PoolResponse = query_pool(deps.as_ref()).unwrap();
+    assert_eq!(res.assets[0].amount, Uint128::from(1_000_000_000u128));
+    assert_eq!(res.assets[1].amount, Uint128::from(1_000_000_000u128));
+
+    // sync donates them to the liquidity providers instead
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        ExecuteMsg::Sync {},
+    )
+    .unwrap();
+    assert_eq!(res.attributes[0], attr("action", "sync"));
+
+    let res: PoolResponse = query_pool(deps.as_ref()).unwrap();
+    assert_eq!(res.assets[0].amount, Uint128::from(1_005_000_000u128));
+    assert_eq!(res.assets[1].amount, Uint128::from(1_003_000_000u128));
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        ExecuteMsg::Skim { to: None },
+    )
+    .unwrap();
+    assert!(res.messages.is_empty());
+}
+
+#[test]
+fn query_share() {
+    let mut deps = single_sided_pool(0, 0);
+    set_reserves(&mut deps, [1_000_000_007, 999_999_991]);
+
+    let res: Vec<Asset> = from_binary(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Share {
+                amount: Uint128::from(333_333_333u128),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(res[0].amount, Uint128::from(333_333_335u128));
+    assert_eq!(res[1].amount, Uint128::from(333_333_330u128));
+
+    // the share is exactly what a withdrawal of the same amount refunds
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "addr0000".to_string(),
+        msg: to_binary(&Cw20HookMsg::WithdrawLiquidity {
+            min_assets: None,
+            deadline: None,
+            receiver: None,
+        })
+        .unwrap(),
+        amount: Uint128::from(333_333_333u128),
+    });
+    let withdraw_res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("liquidity0000", &[]),
+        msg,
+    )
+    .unwrap();
+    assert!(withdraw_res
+        .attributes
+        .contains(&attr("refund_assets", format!("{}, {}", res[0], res[1]))));
+
+    // no liquidity is worth nothing
+    deps.querier
+        .with_token_balances(&[(&"liquidity0000".to_string(), &[])]);
+    let res: Vec<Asset> = from_binary(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Share {
+                amount: Uint128::from(100u128),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(res[0].amount, Uint128::zero());
+    assert_eq!(res[1].amount, Uint128::zero());
+}
+
+#[test]
+fn provide_and_withdraw_with_tax() {
+    let mut deps = single_sided_pool(100_000_000, 100_000_000);
+    deps.querier.with_tax(
+        Decimal::permille(5),
+        &[(&"uusd".to_string(), &Uint128::from(1_000_000u128))],
+    );
+
+    let provide_msg = |amount: u128| ExecuteMsg::ProvideLiquidity {
+        assets: [
+            Asset {
+                info: AssetInfo::NativeToken {
+                    denom: "uusd".to_string(),
+                },
+                amount: Uint128::from(amount),
+            },
+            Asset {
+                info: AssetInfo::Token {
+                    contract_addr: "asset0000".to_string(),
+                },
+                amount: Uint128::from(amount),
+            },
+        ],
+        receiver: None,
+        deadline: None,
+        slippage_tolerance: None,
+        min_lp_to_receive: None,
+    };
+
+    // the tax is charged to the sender on top of the attached coins,
+    // the share is minted for the whole deposit the pair received
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[Coin::new(100_000_000u128, "uusd")]),
+        provide_msg(100_000_000),
+    )
+    .unwrap();
+    assert!(res
+        .attributes
+        .contains(&attr("deposits", "100000000uusd, 100000000asset0000")));
+    assert!(res.attributes.contains(&attr("share", "100000000")));
+    assert_eq!(
+        res.messages[0],
+        SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "asset0000".to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::TransferFrom {
+                owner: "addr0000".to_string(),
+                recipient: MOCK_CONTRACT_ADDR.to_string(),
+                amount: Uint128::from(100_000_000u128),
+            })
+            .unwrap(),
+            funds: vec![],
+        }))
+    );
+
+    // the reserves count what the pair received
+    let res: PoolResponse = query_pool(deps.as_ref()).unwrap();
+    assert_eq!(res.assets[0].amount, Uint128::from(1_100_000_000u128));
+    assert_eq!(res.assets[1].amount, Uint128::from(1_100_000_000u128));
+
+    // the whole pool is withdrawn with the refund transfer taxed
+    set_reserves(&mut deps, [1_100_000_000, 1_100_000_000]);
+    deps.querier.with_balance(&[(
+        &MOCK_CONTRACT_ADDR.to_string(),
+        vec![Coin::new(1_100_000_000u128, "uusd")],
+    )]);
+    deps.querier.with_token_balances(&[
+        (
+            &"liquidity0000".to_string(),
+            &[(&"addr0000".to_string(), &Uint128::from(1_100_000_000u128))],
+        ),
+        (
+            &"asset0000".to_string(),
+            &[(
+                &MOCK_CONTRACT_ADDR.to_string(),
+                &Uint128::from(1_100_000_000u128),
+            )],
+        ),
+    ]);
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "addr0000".to_string(),
+        msg: to_binary(&Cw20HookMsg::WithdrawLiquidity {
+            min_assets: None,
+            deadline: None,
+            receiver: None,
+        })
+        .unwrap(),
+        amount: Uint128::from(1_100_000_000u128),
+    });
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("liquidity0000", &[]),
+        msg,
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages[0],
+        SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+            to_address: "addr0000".to_string(),
+            amount: vec![Coin::new(1_099_000_000u128, "uusd")],
+        }))
+    );
+
+    let res: PoolResponse = query_pool(deps.as_ref()).unwrap();
+    assert_eq!(res.assets[0].amount, Uint128::zero());
+    assert_eq!(res.assets[1].amount, Uint128::zero());
+}
+
+#[test]
+fn collect_protocol_fees() {
+    let mut deps = single_sided_pool(0, 0);
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("owner0000", &[]),
+        ExecuteMsg::UpdateFeeConfig {
+            lp_fee_rate: Decimal::permille(2),
+            protocol_fee_rate: Decimal::permille(1),
+            protocol_fee_collector: Some("collector0000".to_string()),
+        },
+    )
+    .unwrap();
+
+    let share = |deps: &OwnedDeps<MockStorage, MockApi, WasmMockQuerier, TerraQuery>| {
+        from_binary::<Vec<Asset>>(
+            &query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::Share {
+                    amount: Uint128::from(100_000_000u128),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap()
+    };
+
+    // protocol fees accrue in the ask asset of every swap
+    for _ in 0..2 {
+        let msg = ExecuteMsg::Swap {
+            offer_asset: Asset {
+                info: AssetInfo::NativeToken {
+                    denom: "uusd".to_string(),
+                },
+                amount: Uint128::from(10_000_000u128),
+            },
+            belief_price: None,
+            belief_price_direction: None,
+            max_spread: None,
+            to: None,
+            deadline: None,
+            to_msg: None,
+            referral: None,
+        };
+        let info = mock_info("addr0000", &[Coin::new(10_000_000u128, "uusd")]);
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        assert_eq!(res.messages.len(), 1);
+    }
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "addr0000".to_string(),
+        amount: Uint128::from(10_000_000u128),
+        msg: to_binary(&Cw20HookMsg::Swap {
+            belief_price: None,
+            belief_price_direction: None,
+            max_spread: None,
+            to: None,
+            deadline: None,
+            to_msg: None,
+            referral: None,
+        })
+        .unwrap(),
+    });
+    execute(deps.as_mut(), mock_env(), mock_info("asset0000", &[]), msg).unwrap();
+
+    let protocol_fees = PROTOCOL_FEES.load(deps.as_ref().storage).unwrap();
+    assert_eq!(
+        protocol_fees,
+        [Uint128::from(10_298u128), Uint128::from(19_608u128)]
+    );
+
+    // the pair holds the reserves and the pending fees
+    let reserves = RESERVES.load(deps.as_ref().storage).unwrap();
+    let res: PoolResponse = query_pool(deps.as_ref()).unwrap();
+    assert_eq!(res.assets[0].amount, reserves[0]);
+    assert_eq!(res.assets[1].amount, reserves[1]);
+    deps.querier.with_balance(&[(
+        &MOCK_CONTRACT_ADDR.to_string(),
+        vec![Coin::new((reserves[0] + protocol_fees[0]).u128(), "uusd")],
+    )]);
+    deps.querier.with_token_balances(&[
+        (
+            &"liquidity0000".to_string(),
+            &[(
+                &MOCK_CONTRACT_ADDR.to_string(),
+                &Uint128::from(1_000_000_000u128),
+            )],
+        ),
+        (
+            &"asset0000".to_string(),
+            &[(
+                &MOCK_CONTRACT_ADDR.to_string(),
+                &(reserves[1] + protocol_fees[1]),
+            )],
+        ),
+    ]);
+
+    // the pending fees are neither skimmed nor synced into the reserves
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        ExecuteMsg::Skim { to: None },
+    )
+    .unwrap();
+    assert!(res.messages.is_empty());
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        ExecuteMsg::Sync {},
+    )
+    .unwrap();
+    assert_eq!(RESERVES.load(deps.as_ref().storage).unwrap(), reserves);
+
+    // anyone can send the fees to the collector
+    let share_before = share(&deps);
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0001", &[]),
+        ExecuteMsg::CollectProtocolFees {},
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+                to_address: "collector0000".to_string(),
+                amount: vec![Coin::new(protocol_fees[0].u128(), "uusd")],
+            })),
+            SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "asset0000".to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: "collector0000".to_string(),
+                    amount: protocol_fees[1],
+                })
+                .unwrap(),
+                funds: vec![],
+            })),
+        ]
+    );
+    assert_eq!(
+        PROTOCOL_FEES.load(deps.as_ref().storage).unwrap(),
+        [Uint128::zero(); 2]
+    );
+
+    // the value of liquidity does not depend on the pending fees
+    assert_eq!(share(&deps), share_before);
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0001", &[]),
+        ExecuteMsg::CollectProtocolFees {},
+    )
+    .unwrap();
+    assert!(res.messages.is_empty());
+}
+
+#[test]
+fn swap_price_with_asset_decimals() {
+    let mut deps = mock_dependencies(&[Coin::new(1_000_000_000u128, "uusd")]);
+    deps.querier
+        .with_token_balances(&[(&"asset0000".to_string(), &[])]);
+    deps.querier.with_tax(
+        Decimal::zero(),
+        &[(&"uusd".to_string(), &Uint128::from(1000000u128))],
+    );
+
+    // 1000uusd of 6 decimals and 1000asset0000 of 8 decimals
+    let msg = InstantiateMsg {
+        asset_infos: [
+            AssetInfo::NativeToken {
+                denom: "uusd".to_string(),
+            },
+            AssetInfo::Token {
+                contract_addr: "asset0000".to_string(),
+            },
+        ],
+        token_code_id: 10u64,
+        asset_decimals: [6u8, 8u8],
+        lp_fee_rate: None,
+        protocol_fee_rate: None,
+        protocol_fee_collector: None,
+        pair_admin: None,
+        max_spread_cap: None,
+    };
+    instantiate(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("factory0000", &[]),
+        msg,
+    )
+    .unwrap();
+
+    // store liquidity token
+    let reply_msg = Reply {
+        id: 1,
+        result: SubMsgResult::Ok(SubMsgResponse {
+            events: vec![],
+            data: Some(
+                vec![
+                    10, 13, 108, 105, 113, 117, 105, 100, 105, 116, 121, 48, 48, 48, 48,
+                ]
+                .into(),
+            ),
+        }),
+    };
+    reply(deps.as_mut(), mock_env(), reply_msg).unwrap();
+    set_reserves(&mut deps, [1_000_000_000, 100_000_000_000]);
+
+    let pair_info: PairInfo =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Pair {}).unwrap()).unwrap();
+    assert_eq!(pair_info.asset_decimals, [6u8, 8u8]);
+
+    // prices are given in whole units of both assets
+    let msg = ExecuteMsg::Swap {
+        offer_asset: Asset {
+            info: AssetInfo::NativeToken {
+                denom: "uusd".to_string(),
+            },
+            amount: Uint128::from(1_000_000u128),
+        },
+        belief_price: None,
+        belief_price_direction: None,
+        max_spread: None,
+        to: None,
+        deadline: None,
+        to_msg: None,
+        referral: None,
+    };
+    let info = mock_info("addr0000", &[Coin::new(1_000_000u128, "uusd")]);
+    let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    // 0.99600398 = 0.99600398asset0000 / 1uusd
+    assert!(res.attributes.contains(&attr("price", "0.99600398")));
+    assert!(res
+        .attributes
+        .contains(&attr("pool_price", "0.998005990029970029")));
+
+    // the factory and its owner correct the decimals
+    let msg = ExecuteMsg::UpdateAssetDecimals {
+        asset_decimals: [6u8, 6u8],
+    };
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        msg.clone(),
+    );
+    assert_eq!(res, Err(ContractError::Unauthorized {}));
+    execute(deps.as_mut(), mock_env(), mock_info("owner0000", &[]), msg).unwrap();
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("factory0000", &[]),
+        ExecuteMsg::UpdateAssetDecimals {
+            asset_decimals: [6u8, 8u8],
+        },
+    )
+    .unwrap();
+
+    let pair_info: PairInfo =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Pair {}).unwrap()).unwrap();
+    assert_eq!(pair_info.asset_decimals, [6u8, 8u8]);
+
+    // decimals are bounded
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("factory0000", &[]),
+        ExecuteMsg::UpdateAssetDecimals {
+            asset_decimals: [6u8, 19u8],
+        },
+    );
+    assert_eq!(res, Err(ContractError::InvalidAssetDecimals {}));
+}
+
+#[test]
+fn provide_liquidity_from_deposits() {
+    let mut deps = mock_dependencies(&[]);
+    deps.querier.with_token_balances(&[
+        (
+            &"liquidity0000".to_string(),
+            &[(
+                &MOCK_CONTRACT_ADDR.to_string(),
+                &Uint128::from(1_000_000_000u128),
+            )],
+        ),
+        (&"asset0000".to_string(), &[]),
+        (&"asset0001".to_string(), &[]),
+    ]);
+
+    let msg = InstantiateMsg {
+        asset_infos: [
+            AssetInfo::Token {
+                contract_addr: "asset0000".to_string(),
+            },
+            AssetInfo::Token {
+                contract_addr: "asset0001".to_string(),
+            },
+        ],
+        token_code_id: 10u64,
+        asset_decimals: [6u8, 6u8],
+        lp_fee_rate: None,
+        protocol_fee_rate: None,
+        protocol_fee_collector: None,
+        pair_admin: None,
+        max_spread_cap: None,
+    };
+    instantiate(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("factory0000", &[]),
+        msg,
+    )
+    .unwrap();
+
+    // store liquidity token
+    let reply_msg = Reply {
+        id: 1,
+        result: SubMsgResult::Ok(SubMsgResponse {
+            events: vec![],
+            data: Some(
+                vec![
+                    10, 13, 108, 105, 113, 117, 105, 100, 105, 116, 121, 48, 48, 48, 48,
+                ]
+                .into(),
+            ),
+        }),
+    };
+    reply(deps.as_mut(), mock_env(), reply_msg).unwrap();
+    set_reserves(&mut deps, [1_000_000_000, 1_000_000_000]);
+
+    let deposit = |deps: &mut OwnedDeps<MockStorage, MockApi, WasmMockQuerier, TerraQuery>,
+                   token: &str,
+                   amount: u128| {
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(token, &[]),
+            ExecuteMsg::Receive(Cw20ReceiveMsg {
+                sender: "addr0000".to_string(),
+                amount: Uint128::from(amount),
+                msg: to_binary(&Cw20HookMsg::DepositForLiquidity {}).unwrap(),
+            }),
+        )
+    };
+    let query_deposits = |deps: &OwnedDeps<MockStorage, MockApi, WasmMockQuerier, TerraQuery>| {
+        let res: [Asset; 2] = from_binary(
+            &query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::Deposits {
+                    address: "addr0000".to_string(),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        [res[0].amount.u128(), res[1].amount.u128()]
+    };
+
+    // only the pair tokens are escrowed
+    assert_eq!(
+        deposit(&mut deps, "asset0002", 100_000_000),
+        Err(ContractError::Unauthorized {})
+    );
+    deposit(&mut deps, "asset0000", 100_000_000).unwrap();
+    deposit(&mut deps, "asset0001", 100_000_000).unwrap();
+    deposit(&mut deps, "asset0001", 50_000_000).unwrap();
+    assert_eq!(query_deposits(&deps), [100_000_000, 150_000_000]);
+
+    // the escrowed tokens are not skimmed
+    deps.querier.with_token_balances(&[
+        (
+            &"liquidity0000".to_string(),
+            &[(
+                &MOCK_CONTRACT_ADDR.to_string(),
+                &Uint128::from(1_000_000_000u128),
+            )],
+        ),
+        (
+            &"asset0000".to_string(),
+            &[(
+                &MOCK_CONTRACT_ADDR.to_string(),
+                &Uint128::from(1_100_000_000u128),
+            )],
+        ),
+        (
+            &"asset0001".to_string(),
+            &[(
+                &MOCK_CONTRACT_ADDR.to_string(),
+                &Uint128::from(1_150_000_000u128),
+            )],
+        ),
+    ]);
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0001", &[]),
+        ExecuteMsg::Skim { to: None },
+    )
+    .unwrap();
+    assert!(res.messages.is_empty());
+
+    // native funds are not part of the pair
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[Coin::new(100u128, "uusd")]),
+        ExecuteMsg::ProvideLiquidityFromDeposits {
+            slippage_tolerance: None,
+            min_lp_to_receive: None,
+        },
+    );
+    assert_eq!(res, Err(ContractError::AssetMismatch {}));
+
+    // the matched deposits are provided and the excess is sent back
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        ExecuteMsg::ProvideLiquidityFromDeposits {
+            slippage_tolerance: None,
+            min_lp_to_receive: Some(Uint128::from(100_000_000u128)),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "asset0001".to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: "addr0000".to_string(),
+                    amount: Uint128::from(50_000_000u128),
+                })
+                .unwrap(),
+                funds: vec![],
+            })),
+            SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "liquidity0000".to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::Mint {
+                    recipient: "addr0000".to_string(),
+                    amount: Uint128::from(100_000_000u128),
+                })
+                .unwrap(),
+                funds: vec![],
+            })),
+        ]
+    );
+    assert_eq!(query_deposits(&deps), [0, 0]);
+    let res: PoolResponse = query_pool(deps.as_ref()).unwrap();
+    assert_eq!(res.assets[0].amount, Uint128::from(1_100_000_000u128));
+    assert_eq!(res.assets[1].amount, Uint128::from(1_100_000_000u128));
+
+    // nothing is left to provide
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        ExecuteMsg::ProvideLiquidityFromDeposits {
+            slippage_tolerance: None,
+            min_lp_to_receive: None,
+        },
+    );
+    assert_eq!(res, Err(ContractError::ZeroDeposits {}));
+
+    // deposits can be taken back
+    deposit(&mut deps, "asset0000", 30_000_000).unwrap();
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        ExecuteMsg::RefundDeposits {},
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "asset0000".to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: "addr0000".to_string(),
+                amount: Uint128::from(30_000_000u128),
+            })
+            .unwrap(),
+            funds: vec![],
+        }))]
+    );
+    assert_eq!(query_deposits(&deps), [0, 0]);
+}
+
+#[test]
+fn swap_exact_out() {
+    let attr_amount = |res: &Response<TerraMsg>, key: &str| {
+        res.attributes
+            .iter()
+            .find(|attr| attr.key == key)
+            .map(|attr| Uint128::from_str(&attr.value).unwrap())
+            .unwrap()
+    };
+
+    // native offer
+    let mut deps = single_sided_pool(0, 0);
+    let msg = |max_offer_amount: u128| ExecuteMsg::SwapExactOut {
+        ask_asset: Asset {
+            info: AssetInfo::Token {
+                contract_addr: "asset0000".to_string(),
+            },
+            amount: Uint128::from(10_000_000u128),
+        },
+        max_offer_amount: Uint128::from(max_offer_amount),
+        to: None,
+        deadline: None,
+    };
+
+    let info = mock_info("addr0000", &[Coin::new(1_000_000u128, "uusd")]);
+    let res = execute(deps.as_mut(), mock_env(), info, msg(1_000_000));
+    assert_eq!(
+        res,
+        Err(ContractError::MaxOfferAmountAssertion {
+            offer_amount: "10131714".to_string(),
+            max_offer_amount: "1000000".to_string(),
+        })
+    );
+
+    let info = mock_info("addr0000", &[Coin::new(20_000_000u128, "uusd")]);
+    let res = execute(deps.as_mut(), mock_env(), info, msg(20_000_000)).unwrap();
+    let offer_spent = attr_amount(&res, "offer_spent");
+    assert_eq!(offer_spent, Uint128::from(10_131_714u128));
+    assert_eq!(attr_amount(&res, "refund"), Uint128::from(9_868_286u128));
+    assert_eq!(
+        attr_amount(&res, "return_amount"),
+        Uint128::from(10_000_000u128)
+    );
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "asset0000".to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: "addr0000".to_string(),
+                    amount: Uint128::from(10_000_000u128),
+                })
+                .unwrap(),
+                funds: vec![],
+            })),
+            SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+                to_address: "addr0000".to_string(),
+                amount: vec![Coin::new(9_868_286u128, "uusd")],
+            })),
+        ]
+    );
+
+    // the pool only takes the spent offer
+    let res: PoolResponse = query_pool(deps.as_ref()).unwrap();
+    assert_eq!(res.assets[0].amount, Uint128::from(1_010_131_714u128));
+    assert_eq!(res.assets[1].amount, Uint128::from(990_000_000u128));
+
+    // with a tax the whole attached offer is credited, only the spent part must fit in it
+    let mut deps = single_sided_pool(0, 0);
+    deps.querier.with_tax(
+        Decimal::permille(5),
+        &[(&"uusd".to_string(), &Uint128::from(1_000_000u128))],
+    );
+    let info = mock_info("addr0000", &[Coin::new(10_131_713u128, "uusd")]);
+    let res = execute(deps.as_mut(), mock_env(), info, msg(10_131_713));
+    assert_eq!(
+        res,
+        Err(ContractError::MaxOfferAmountAssertion {
+            offer_amount: "10131714".to_string(),
+            max_offer_amount: "10131713".to_string(),
+        })
+    );
+    let info = mock_info("addr0000", &[Coin::new(10_150_000u128, "uusd")]);
+    let res = execute(deps.as_mut(), mock_env(), info, msg(10_150_000)).unwrap();
+    assert_eq!(
+        attr_amount(&res, "offer_spent"),
+        Uint128::from(10_131_714u128)
+    );
+    assert_eq!(attr_amount(&res, "refund"), Uint128::from(18_286u128));
+    assert_eq!(
+        res.messages[1],
+        SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+            to_address: "addr0000".to_string(),
+            amount: vec![Coin::new(18_195u128, "uusd")],
+        }))
+    );
+
+    // a native ask arrives in full, the swap returns the tax on top
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "addr0000".to_string(),
+        amount: Uint128::from(20_000_000u128),
+        msg: to_binary(&Cw20HookMsg::SwapExactOut {
+            ask_asset: Asset {
+                info: AssetInfo::NativeToken {
+                    denom: "uusd".to_string(),
+                },
+                amount: Uint128::from(10_000_000u128),
+            },
+            to: None,
+            deadline: None,
+        })
+        .unwrap(),
+    });
+    let mut deps = single_sided_pool(0, 0);
+    deps.querier.with_tax(
+        Decimal::permille(5),
+        &[(&"uusd".to_string(), &Uint128::from(1_000_000u128))],
+    );
+    let res = execute(deps.as_mut(), mock_env(), mock_info("asset0000", &[]), msg).unwrap();
+    assert_eq!(
+        attr_amount(&res, "return_amount"),
+        Uint128::from(10_050_000u128)
+    );
+    assert_eq!(
+        attr_amount(&res, "offer_spent"),
+        Uint128::from(10_182_887u128)
+    );
+    assert_eq!(
+        res.messages[0],
+        SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+            to_address: "addr0000".to_string(),
+            amount: vec![Coin::new(10_000_000u128, "uusd")],
+        }))
+    );
+
+    // one less offer would not return the ask amount
+    let mut deps = single_sided_pool(0, 0);
+    let res: SimulationResponse = query_simulation(
+        deps.as_ref(),
+        Asset {
+            info: AssetInfo::NativeToken {
+                denom: "uusd".to_string(),
+            },
+            amount: offer_spent - Uint128::from(1u128),
+        },
+        None,
+    )
+    .unwrap();
+    assert!(res.return_amount < Uint128::from(10_000_000u128));
+
+    // cw20 offer
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "addr0000".to_string(),
+        amount: Uint128::from(20_000_000u128),
+        msg: to_binary(&Cw20HookMsg::SwapExactOut {
+            ask_asset: Asset {
+                info: AssetInfo::NativeToken {
+                    denom: "uusd".to_string(),
+                },
+                amount: Uint128::from(10_000_000u128),
+            },
+            to: None,
+            deadline: None,
+        })
+        .unwrap(),
+    });
+    let res = execute(deps.as_mut(), mock_env(), mock_info("asset0000", &[]), msg).unwrap();
+    assert_eq!(
+        attr_amount(&res, "offer_spent"),
+        Uint128::from(10_131_714u128)
+    );
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+                to_address: "addr0000".to_string(),
+                amount: vec![Coin::new(10_000_000u128, "uusd")],
+            })),
+            SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "asset0000".to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: "addr0000".to_string(),
+                    amount: Uint128::from(9_868_286u128),
+                })
+                .unwrap(),
+                funds: vec![],
+            })),
+        ]
+    );
+}
+
+#[test]
+fn observed_twap() {
+    let mut deps = mock_dependencies(&[]);
+    deps.querier
+        .with_token_balances(&[(&"asset0000".to_string(), &[])]);
+    deps.querier.with_tax(
+        Decimal::zero(),
+        &[(&"uusd".to_string(), &Uint128::from(1000000u128))],
+    );
+
+    let msg = InstantiateMsg {
+        asset_infos: [
+            AssetInfo::NativeToken {
+                denom: "uusd".to_string(),
+            },
+            AssetInfo::Token {
+                contract_addr: "asset0000".to_string(),
+            },
+        ],
+        token_code_id: 10u64,
+        asset_decimals: [6u8, 6u8],
+        lp_fee_rate: None,
+        protocol_fee_rate: None,
+        protocol_fee_collector: None,
+        pair_admin: None,
+        max_spread_cap: None,
+    };
+    let mut env = mock_env();
+    let start_time = env.block.time;
+    instantiate(deps.as_mut(), env.clone(), mock_info("addr0000", &[]), msg).unwrap();
+    set_reserves(&mut deps, [2_000_000_000, 1_000_000_000]);
+
+    let swap = |deps: &mut OwnedDeps<MockStorage, MockApi, WasmMockQuerier, TerraQuery>,
+                env: Env| {
+        let msg = ExecuteMsg::Swap {
+            offer_asset: Asset {
+                info: AssetInfo::NativeToken {
+                    denom: "uusd".to_string(),
+                },
+                amount: Uint128::from(1_000u128),
+            },
+            belief_price: None,
+            belief_price_direction: None,
+            max_spread: None,
+            to: None,
+            deadline: None,
+            to_msg: None,
+            referral: None,
+        };
+        let info = mock_info("addr0000", &[Coin::new(1_000u128, "uusd")]);
+        execute(deps.as_mut(), env, info, msg).unwrap();
+    };
+    let observed_twap = |deps: &OwnedDeps<MockStorage, MockApi, WasmMockQuerier, TerraQuery>,
+                         env: Env,
+                         window_seconds: u64| {
+        query(
+            deps.as_ref(),
+            env,
+            QueryMsg::ObservedTwap { window_seconds },
+        )
+        .map(|res| from_binary::<ObservedTwapResponse>(&res).unwrap())
+    };
+
+    // nothing is observed before the current block
+    assert_eq!(
+        observed_twap(&deps, env.clone(), 3600),
+        Err(ContractError::InsufficientPriceHistory {
+            window_seconds: 3600
+        })
+    );
+
+    // 0.5 for the first hour, 0.125 for the second
+    env.block.time = start_time.plus_seconds(3600);
+    swap(&mut deps, env.clone());
+    set_reserves(&mut deps, [4_000_000_000, 500_000_000]);
+    env.block.time = start_time.plus_seconds(7200);
+    swap(&mut deps, env.clone());
+    set_reserves(&mut deps, [1_000_000_000, 1_000_000_000]);
+
+    // and 1 for the last half hour
+    env.block.time = start_time.plus_seconds(9000);
+    let res = observed_twap(&deps, env.clone(), 1800).unwrap();
+    assert_eq!(res.price0_average, Decimal256::one());
+    assert_eq!(res.price1_average, Decimal256::one());
+    assert_eq!(res.observed_since, start_time.seconds() + 7200);
+
+    // the window starts at the oldest observation within it
+    let res = observed_twap(&deps, env.clone(), 3600).unwrap();
+    assert_eq!(res.observed_since, start_time.seconds() + 7200);
+
+    // (0.125 * 3600 + 1 * 1800) / 5400
+    let res = observed_twap(&deps, env.clone(), 5400).unwrap();
+    assert_eq!(
+        res.price0_average,
+        Decimal256::from_str("0.416666666666666666").unwrap()
+    );
+    // (8 * 3600 + 1 * 1800) / 5400
+    assert_eq!(
+        res.price1_average,
+        Decimal256::from_str("5.666666666666666666").unwrap()
+    );
+
+    // (0.5 * 3600 + 0.125 * 3600 + 1 * 1800) / 9000
+    let res = observed_twap(&deps, env.clone(), 9000).unwrap();
+    assert_eq!(res.price0_average, Decimal256::from_str("0.45").unwrap());
+    assert_eq!(res.observed_since, start_time.seconds());
+
+    // a day later the first observation is overwritten
+    env.block.time = start_time.plus_seconds(24 * 3600);
+    swap(&mut deps, env.clone());
+    let res = observed_twap(&deps, env, 30 * 24 * 3600).unwrap();
+    assert_eq!(res.observed_since, start_time.seconds() + 3600);
+}
+
+#[test]
+fn rescue_funds() {
+    let mut deps = single_sided_pool(0, 0);
+    let rescue = |asset_info: AssetInfo| ExecuteMsg::RescueFunds {
+        asset_info,
+        amount: Uint128::from(100u128),
+        to: "addr0001".to_string(),
+    };
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        rescue(AssetInfo::Token {
+            contract_addr: "asset0001".to_string(),
+        }),
+    );
+    assert_eq!(res, Err(ContractError::Unauthorized {}));
+
+    // a foreign cw20
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("owner0000", &[]),
+        rescue(AssetInfo::Token {
+            contract_addr: "asset0001".to_string(),
+        }),
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "asset0001".to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: "addr0001".to_string(),
+                amount: Uint128::from(100u128),
+            })
+            .unwrap(),
+            funds: vec![],
+        }))]
+    );
+
+    // a foreign native
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("owner0000", &[]),
+        rescue(AssetInfo::NativeToken {
+            denom: "ibc/0000".to_string(),
+        }),
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+            to_address: "addr0001".to_string(),
+            amount: vec![Coin::new(100u128, "ibc/0000")],
+        }))]
+    );
+
+    // the pool assets and the liquidity token stay
+    for asset_info in [
+        AssetInfo::NativeToken {
+            denom: "uusd".to_string(),
+        },
+        AssetInfo::Token {
+            contract_addr: "asset0000".to_string(),
+        },
+        AssetInfo::Token {
+            contract_addr: "liquidity0000".to_string(),
+        },
+    ] {
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("owner0000", &[]),
+            rescue(asset_info),
+        );
+        assert_eq!(res, Err(ContractError::PairAssetRescue {}));
+    }
+}
+
+#[test]
+fn withdraw_liquidity_to_receiver() {
+    let mut deps = single_sided_pool(0, 0);
+
+    // an operator sends the owner's LP tokens with `send_from`, refunding the owner
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "operator0000".to_string(),
+        amount: Uint128::from(100u128),
+        msg: to_binary(&Cw20HookMsg::WithdrawLiquidity {
+            min_assets: None,
+            deadline: None,
+            receiver: Some("addr0001".to_string()),
+        })
+        .unwrap(),
+    });
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("liquidity0000", &[]),
+        msg,
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+                to_address: "addr0001".to_string(),
+                amount: vec![Coin::new(100u128, "uusd")],
+            })),
+            SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "asset0000".to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: "addr0001".to_string(),
+                    amount: Uint128::from(100u128),
+                })
+                .unwrap(),
+                funds: vec![],
+            })),
+            SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "liquidity0000".to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::Burn {
+                    amount: Uint128::from(100u128),
+                })
+                .unwrap(),
+                funds: vec![],
+            })),
+        ]
+    );
+    assert_eq!(res.attributes[1], attr("sender", "operator0000"));
+    assert_eq!(res.attributes.last(), Some(&attr("receiver", "addr0001")));
+}
+
+#[test]
+fn update_liquidity_token_marketing() {
+    let mut deps = single_sided_pool(0, 0);
+    let msg = ExecuteMsg::UpdateLiquidityTokenMarketing {
+        project: None,
+        description: Some("terraswap uusd-mAAPL liquidity".to_string()),
+        marketing: Some("marketing0000".to_string()),
+        logo: Some(Logo::Url("https://terraswap.io/lp.png".to_string())),
+    };
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        msg.clone(),
+    );
+    assert_eq!(res, Err(ContractError::Unauthorized {}));
+
+    let res = execute(deps.as_mut(), mock_env(), mock_info("owner0000", &[]), msg).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "liquidity0000".to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::UpdateMarketing {
+                    project: None,
+                    description: Some("terraswap uusd-mAAPL liquidity".to_string()),
+                    marketing: Some("marketing0000".to_string()),
+                })
+                .unwrap(),
+                funds: vec![],
+            })),
+            SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "liquidity0000".to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::UploadLogo(Logo::Url(
+                    "https://terraswap.io/lp.png".to_string()
+                )))
+                .unwrap(),
+                funds: vec![],
+            })),
+        ]
+    );
+
+    // only the logo
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("owner0000", &[]),
+        ExecuteMsg::UpdateLiquidityTokenMarketing {
+            project: None,
+            description: None,
+            marketing: None,
+            logo: Some(Logo::Url("https://terraswap.io/lp.png".to_string())),
+        },
+    )
+    .unwrap();
+    assert_eq!(res.messages.len(), 1);
+}
+
+#[test]
+fn fee_tiers() {
+    let mut deps = single_sided_pool(0, 0);
+    let uusd = |amount: u128| Asset {
+        info: AssetInfo::NativeToken {
+            denom: "uusd".to_string(),
+        },
+        amount: Uint128::from(amount),
+    };
+    let token = |amount: u128| Asset {
+        info: AssetInfo::Token {
+            contract_addr: "asset0000".to_string(),
+        },
+        amount: Uint128::from(amount),
+    };
+
+    // only the factory owner can update the tiers, which must be monotonic
+    let tiers_msg = |fee_tiers: Vec<(u128, u64)>| ExecuteMsg::UpdateFeeTiers {
+        fee_tiers: fee_tiers
+            .into_iter()
+            .map(|(threshold, permille)| (Uint128::from(threshold), Decimal::permille(permille)))
+            .collect(),
+    };
+    let info = mock_info("addr0000", &[]);
+    let res = execute(deps.as_mut(), mock_env(), info, tiers_msg(vec![(500, 5)]));
+    assert_eq!(res, Err(ContractError::Unauthorized {}));
+
+    let info = mock_info("owner0000", &[]);
+    for fee_tiers in [
+        vec![(500, 2)],
+        vec![(0, 5)],
+        vec![(500, 5), (500, 7)],
+        vec![(1_000, 7), (500, 5)],
+        vec![(500, 7), (1_000, 5)],
+    ] {
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            tiers_msg(fee_tiers),
+        );
+        assert_eq!(res, Err(ContractError::InvalidFeeTiers {}));
+    }
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        tiers_msg(vec![(500, 11)]),
+    );
+    assert_eq!(res, Err(ContractError::FeeRateTooHigh {}));
+
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        tiers_msg(vec![(500, 5), (1_000, 10)]),
+    )
+    .unwrap();
+
+    // up to 5% of the offer reserve pays 0.3%, above it 0.5% and above 10% 1%
+    for (offer_amount, commission_amount) in [
+        (50_000_000u128, 142_858u128),
+        (50_000_001u128, 238_096u128),
+        (100_000_000u128, 454_546u128),
+        (100_000_001u128, 909_091u128),
+    ] {
+        let res: SimulationResponse =
+            query_simulation(deps.as_ref(), uusd(offer_amount), None).unwrap();
+        assert_eq!(res.commission_amount, Uint128::from(commission_amount));
+
+        // the reverse simulation charges the tier of the offer it returns, which
+        // may be a cheaper offer in a lower tier just below the threshold
+        let reverse: ReverseSimulationResponse =
+            query_reverse_simulation(deps.as_ref(), token(res.return_amount.u128()), None).unwrap();
+        assert!(reverse.offer_amount.u128() <= offer_amount);
+        let forward: SimulationResponse =
+            query_simulation(deps.as_ref(), uusd(reverse.offer_amount.u128()), None).unwrap();
+        assert!(forward.return_amount >= res.return_amount);
+        assert_eq!(forward.commission_amount, reverse.commission_amount);
+    }
+
+    // the swap charges the simulated commission
+    let info = mock_info("addr0000", &[Coin::new(50_000_001u128, "uusd")]);
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::Swap {
+            offer_asset: uusd(50_000_001),
+            belief_price: None,
+            belief_price_direction: None,
+            max_spread: None,
+            to: None,
+            deadline: None,
+            to_msg: None,
+            referral: None,
+        },
+    )
+    .unwrap();
+    assert!(res
+        .attributes
+        .contains(&attr("commission_amount", "238096")));
+}
+
+#[test]
+fn simulate_provide_liquidity() {
+    let assets = |uusd_amount: u128, token_amount: u128| {
+        vec![
+            Asset {
+                info: AssetInfo::NativeToken {
+                    denom: "uusd".to_string(),
+                },
+                amount: Uint128::from(uusd_amount),
+            },
+            Asset {
+                info: AssetInfo::Token {
+                    contract_addr: "asset0000".to_string(),
+                },
+                amount: Uint128::from(token_amount),
+            },
+        ]
+    };
+
+    // the simulation predicts the share, the reserves and the refunds of the provision
+    let assert_simulation =
+        |deps: &mut OwnedDeps<MockStorage, MockApi, WasmMockQuerier, TerraQuery>,
+         uusd_amount: u128,
+         token_amount: u128| {
+            let res: SimulateProvideLiquidityResponse = from_binary(
+                &query(
+                    deps.as_ref(),
+                    mock_env(),
+                    QueryMsg::SimulateProvideLiquidity {
+                        assets: assets(uusd_amount, token_amount),
+                        slippage_tolerance: None,
+                    },
+                )
+                .unwrap(),
+            )
+            .unwrap();
+
+            let reserves = RESERVES.load(deps.as_ref().storage).unwrap();
+            let assets = assets(uusd_amount, token_amount);
+            let info = mock_info("addr0000", &[Coin::new(uusd_amount, "uusd")]);
+            let execute_res = execute(
+                deps.as_mut(),
+                mock_env(),
+                info,
+                ExecuteMsg::ProvideLiquidity {
+                    assets: [assets[0].clone(), assets[1].clone()],
+                    receiver: None,
+                    deadline: None,
+                    slippage_tolerance: None,
+                    min_lp_to_receive: None,
+                },
+            )
+            .unwrap();
+            assert!(execute_res
+                .attributes
+                .contains(&attr("share", res.lp_minted.to_string())));
+            assert!(execute_res.attributes.contains(&attr(
+                "refund_assets",
+                format!("{}, {}", res.refund_amounts[0], res.refund_amounts[1])
+            )));
+            let new_reserves = RESERVES.load(deps.as_ref().storage).unwrap();
+            for i in 0..2 {
+                assert_eq!(new_reserves[i], reserves[i] + res.used_amounts[i].amount);
+            }
+
+            res
+        };
+
+    // balanced
+    let mut deps = single_sided_pool(0, 0);
+    let res = assert_simulation(&mut deps, 1_000_000, 1_000_000);
+    assert_eq!(res.lp_minted, Uint128::from(1_000_000u128));
+    assert_eq!(res.used_amounts, assets(1_000_000, 1_000_000));
+    assert_eq!(res.refund_amounts, assets(0, 0));
+
+    // unbalanced, the excess uusd is refunded
+    let mut deps = single_sided_pool(0, 0);
+    let res = assert_simulation(&mut deps, 3_000_000, 1_000_000);
+    assert_eq!(res.lp_minted, Uint128::from(1_000_000u128));
+    assert_eq!(res.used_amounts, assets(1_000_000, 1_000_000));
+    assert_eq!(res.refund_amounts, assets(2_000_000, 0));
+
+    // the slippage tolerance is checked as in the execution
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::SimulateProvideLiquidity {
+            assets: assets(3_000_000, 1_000_000),
+            slippage_tolerance: Some(Decimal::percent(10)),
+        },
+    );
+    assert_eq!(res, Err(ContractError::MaxSlippageAssertion {}));
+
+    // the first provision locks the minimum liquidity
+    let mut deps = single_sided_pool(0, 0);
+    deps.querier.with_token_balances(&[(
+        &"liquidity0000".to_string(),
+        &[(&MOCK_CONTRACT_ADDR.to_string(), &Uint128::zero())],
+    )]);
+    set_reserves(&mut deps, [0, 0]);
+    let res = assert_simulation(&mut deps, 4_000_000, 1_000_000);
+    assert_eq!(res.lp_minted, Uint128::from(1_999_000u128));
+    assert_eq!(res.used_amounts, assets(4_000_000, 1_000_000));
+    assert_eq!(res.refund_amounts, assets(0, 0));
+}
+
+#[test]
+fn migrate_liquidity_token() {
+    let mut deps = single_sided_pool(0, 0);
+    let migrate_msg = |liquidity_token_code_id: Option<u64>| MigrateMsg {
+        update_liquidity_token: true,
+        liquidity_token_code_id,
+    };
+    let migrate_reply = Reply {
+        id: 2,
+        result: SubMsgResult::Ok(SubMsgResponse {
+            events: vec![],
+            data: None,
+        }),
+    };
+    let reset_version =
+        |deps: &mut OwnedDeps<MockStorage, MockApi, WasmMockQuerier, TerraQuery>| {
+            set_contract_version(deps.as_mut().storage, "crates.io:terraswap-pair", "0.1.1")
+                .unwrap()
+        };
+
+    // the pair must be the admin of the liquidity token
+    reset_version(&mut deps);
+    deps.querier.with_contract_admin("liquidity0000", None);
+    let res = migrate(deps.as_mut(), mock_env(), migrate_msg(Some(3)));
+    assert_eq!(res, Err(ContractError::LiquidityTokenAdminRequired {}));
+
+    deps.querier
+        .with_contract_admin("liquidity0000", Some(MOCK_CONTRACT_ADDR.to_string()));
+    deps.querier
+        .with_token_minter("liquidity0000", MOCK_CONTRACT_ADDR);
+
+    // the factory token code id is used by default
+    reset_version(&mut deps);
+    let res = migrate(deps.as_mut(), mock_env(), migrate_msg(None)).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::reply_on_success(
+            WasmMsg::Migrate {
+                contract_addr: "liquidity0000".to_string(),
+                new_code_id: 2,
+                msg: to_binary(&Empty {}).unwrap(),
+            },
+            2,
+        )]
+    );
+    reply(deps.as_mut(), mock_env(), migrate_reply.clone()).unwrap();
+
+    // a migration changing the minter or the supply is aborted
+    reset_version(&mut deps);
+    let res = migrate(deps.as_mut(), mock_env(), migrate_msg(Some(3))).unwrap();
+    assert_eq!(
+        res.messages[0].msg,
+        CosmosMsg::Wasm(WasmMsg::Migrate {
+            contract_addr: "liquidity0000".to_string(),
+            new_code_id: 3,
+            msg: to_binary(&Empty {}).unwrap(),
+        })
+    );
+    deps.querier.with_token_minter("liquidity0000", "addr0000");
+    let res = reply(deps.as_mut(), mock_env(), migrate_reply.clone());
+    assert_eq!(res, Err(ContractError::LiquidityTokenChanged {}));
+
+    deps.querier
+        .with_token_minter("liquidity0000", MOCK_CONTRACT_ADDR);
+    reset_version(&mut deps);
+    migrate(deps.as_mut(), mock_env(), migrate_msg(Some(3))).unwrap();
+    deps.querier.with_token_balances(&[(
+        &"liquidity0000".to_string(),
+        &[(&MOCK_CONTRACT_ADDR.to_string(), &Uint128::zero())],
+    )]);
+    let res = reply(deps.as_mut(), mock_env(), migrate_reply);
+    assert_eq!(res, Err(ContractError::LiquidityTokenChanged {}));
+}
+
+#[test]
+fn swap_with_referral() {
+    let uusd = |amount: u128| Asset {
+        info: AssetInfo::NativeToken {
+            denom: "uusd".to_string(),
+        },
+        amount: Uint128::from(amount),
+    };
+    let token = |amount: u128| Asset {
+        info: AssetInfo::Token {
+            contract_addr: "asset0000".to_string(),
+        },
+        amount: Uint128::from(amount),
+    };
+    let referral = |bps: u16| {
+        Some(Referral {
+            address: "referral0000".to_string(),
+            bps,
+        })
+    };
+    let swap_msg = |referral: Option<Referral>| ExecuteMsg::Swap {
+        offer_asset: uusd(10_000_000),
+        belief_price: None,
+        belief_price_direction: None,
+        max_spread: None,
+        to: None,
+        deadline: None,
+        to_msg: None,
+        referral,
+    };
+    let info = mock_info("addr0000", &[Coin::new(10_000_000u128, "uusd")]);
+
+    // the cut is capped by the factory, 50 bps by default
+    let mut deps = single_sided_pool(0, 0);
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        swap_msg(referral(51)),
+    );
+    assert_eq!(
+        res,
+        Err(ContractError::ReferralBpsTooHigh {
+            max_referral_bps: 50
+        })
+    );
+    let res = query_simulation(deps.as_ref(), uusd(10_000_000), referral(51));
+    assert_eq!(
+        res,
+        Err(ContractError::ReferralBpsTooHigh {
+            max_referral_bps: 50
+        })
+    );
+
+    // token ask, the simulation reports the return after the cut
+    let plain = query_simulation(deps.as_ref(), uusd(10_000_000), None).unwrap();
+    let simulation = query_simulation(deps.as_ref(), uusd(10_000_000), referral(50)).unwrap();
+    let referral_amount = plain.return_amount.multiply_ratio(50u128, 10_000u128);
+    assert_eq!(
+        simulation.return_amount,
+        plain.return_amount - referral_amount
+    );
+
+    // the reverse simulation of the return after the cut finds the same offer
+    let reverse = query_reverse_simulation(
+        deps.as_ref(),
+        token(simulation.return_amount.u128()),
+        referral(50),
+    )
+    .unwrap();
+    assert_eq!(reverse.offer_amount, Uint128::from(10_000_000u128));
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        swap_msg(referral(50)),
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "asset0000".to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: "addr0000".to_string(),
+                    amount: simulation.return_amount,
+                })
+                .unwrap(),
+                funds: vec![],
+            })),
+            SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "asset0000".to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: "referral0000".to_string(),
+                    amount: referral_amount,
+                })
+                .unwrap(),
+                funds: vec![],
+            })),
+        ]
+    );
+    assert!(res
+        .attributes
+        .contains(&attr("referral_amount", referral_amount.to_string())));
+    assert!(res
+        .attributes
+        .contains(&attr("return_amount", plain.return_amount.to_string())));
+    assert!(res.attributes.contains(&attr(
+        "net_return_amount",
+        simulation.return_amount.to_string()
+    )));
+
+    // the whole return leaves the pool
+    let res: PoolResponse = query_pool(deps.as_ref()).unwrap();
+    assert_eq!(
+        res.assets[1].amount,
+        Uint128::from(1_000_000_000u128) - plain.return_amount
+    );
+
+    // a zero cut sends nothing to the referral
+    let mut deps = single_sided_pool(0, 0);
+    let res = execute(deps.as_mut(), mock_env(), info, swap_msg(referral(0))).unwrap();
+    assert_eq!(res.messages.len(), 1);
+    assert!(res.attributes.contains(&attr("referral_amount", "0")));
+    assert!(res
+        .attributes
+        .contains(&attr("return_amount", plain.return_amount.to_string())));
+
+    // native ask through the cw20 hook
+    let mut deps = single_sided_pool(0, 0);
+    let simulation = query_simulation(deps.as_ref(), token(10_000_000), referral(30)).unwrap();
+    let plain = query_simulation(deps.as_ref(), token(10_000_000), None).unwrap();
+    let referral_amount = plain.return_amount - simulation.return_amount;
+    assert_eq!(
+        referral_amount,
+        plain.return_amount.multiply_ratio(30u128, 10_000u128)
+    );
+
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "addr0000".to_string(),
+        amount: Uint128::from(10_000_000u128),
+        msg: to_binary(&Cw20HookMsg::Swap {
+            belief_price: None,
+            belief_price_direction: None,
+            max_spread: None,
+            to: None,
+            deadline: None,
+            to_msg: None,
+            referral: referral(30),
+        })
+        .unwrap(),
+    });
+    let res = execute(deps.as_mut(), mock_env(), mock_info("asset0000", &[]), msg).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+                to_address: "addr0000".to_string(),
+                amount: vec![Coin::new(simulation.return_amount.u128(), "uusd")],
+            })),
+            SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+                to_address: "referral0000".to_string(),
+                amount: vec![Coin::new(referral_amount.u128(), "uusd")],
+            })),
+        ]
+    );
+}
+
+#[test]
+fn simulation_batch() {
+    let deps = single_sided_pool(0, 0);
+    let uusd = AssetInfo::NativeToken {
+        denom: "uusd".to_string(),
+    };
+    let token = AssetInfo::Token {
+        contract_addr: "asset0000".to_string(),
+    };
+    let amounts: Vec<Uint128> = [0u128, 1, 1_000, 1_000_000, 100_000_000, 500_000_000]
+        .into_iter()
+        .map(Uint128::from)
+        .collect();
+
+    for (offer_info, ask_info) in [(uusd.clone(), token.clone()), (token.clone(), uusd.clone())] {
+        let res: SimulationBatchResponse = from_binary(
+            &query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::SimulationBatch {
+                    offer_asset_info: offer_info.clone(),
+                    amounts: amounts.clone(),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        let simulations: Vec<SimulationResponse> = amounts
+            .iter()
+            .map(|amount| {
+                let offer_asset = Asset {
+                    info: offer_info.clone(),
+                    amount: *amount,
+                };
+                query_simulation(deps.as_ref(), offer_asset, None).unwrap()
+            })
+            .collect();
+        assert_eq!(res.simulations, simulations);
+
+        let res: ReverseSimulationBatchResponse = from_binary(
+            &query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::ReverseSimulationBatch {
+                    ask_asset_info: ask_info.clone(),
+                    amounts: amounts.clone(),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        let simulations: Vec<ReverseSimulationResponse> = amounts
+            .iter()
+            .map(|amount| {
+                let ask_asset = Asset {
+                    info: ask_info.clone(),
+                    amount: *amount,
+                };
+                query_reverse_simulation(deps.as_ref(), ask_asset, None).unwrap()
+            })
+            .collect();
+        assert_eq!(res.simulations, simulations);
+    }
+
+    // at most 50 amounts, of an asset of the pair
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::SimulationBatch {
+            offer_asset_info: uusd.clone(),
+            amounts: vec![Uint128::one(); 50],
+        },
+    );
+    assert!(res.is_ok());
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::ReverseSimulationBatch {
+            ask_asset_info: uusd,
+            amounts: vec![Uint128::one(); 51],
+        },
+    );
+    assert_eq!(res, Err(ContractError::SimulationBatchTooLarge { max: 50 }));
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::SimulationBatch {
+            offer_asset_info: AssetInfo::NativeToken {
+                denom: "ukrw".to_string(),
+            },
+            amounts: vec![Uint128::one()],
+        },
+    );
+    assert_eq!(res, Err(ContractError::AssetMismatch {}));
+}
+
+#[test]
+fn swap_without_liquidity() {
+    let uusd = |amount: u128| Asset {
+        info: AssetInfo::NativeToken {
+            denom: "uusd".to_string(),
+        },
+        amount: Uint128::from(amount),
+    };
+    let token = |amount: u128| Asset {
+        info: AssetInfo::Token {
+            contract_addr: "asset0000".to_string(),
+        },
+        amount: Uint128::from(amount),
+    };
+
+    // a fresh pair has no reserves, a drained one can be left with one
+    for reserves in [None, Some([0, 1_000]), Some([1_000, 0])] {
+        let mut deps = single_sided_pool(10_000, 0);
+        match reserves {
+            Some(reserves) => set_reserves(&mut deps, reserves),
+            None => RESERVES.remove(deps.as_mut().storage),
+        }
+
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("addr0000", &[Coin::new(10_000u128, "uusd")]),
+            ExecuteMsg::Swap {
+                offer_asset: uusd(10_000),
+                belief_price: None,
+                belief_price_direction: None,
+                max_spread: None,
+                to: None,
+                deadline: None,
+                to_msg: None,
+                referral: None,
+            },
+        );
+        assert_eq!(res, Err(ContractError::NoLiquidity {}));
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("addr0000", &[Coin::new(10_000u128, "uusd")]),
+            ExecuteMsg::SwapExactOut {
+                ask_asset: token(100),
+                max_offer_amount: Uint128::from(10_000u128),
+                to: None,
+                deadline: None,
+            },
+        );
+        assert_eq!(res, Err(ContractError::NoLiquidity {}));
+
+        let res = query_simulation(deps.as_ref(), token(10_000), None);
+        assert_eq!(res, Err(ContractError::NoLiquidity {}));
+        let res = query_reverse_simulation(deps.as_ref(), uusd(100), None);
+        assert_eq!(res, Err(ContractError::NoLiquidity {}));
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::SimulationBatch {
+                offer_asset_info: uusd(0).info,
+                amounts: vec![Uint128::from(10_000u128)],
+            },
+        );
+        assert_eq!(res, Err(ContractError::NoLiquidity {}));
+    }
+}
+
+#[test]
+fn commit_reveal_swap() {
+    let env_at = |height: u64| {
+        let mut env = mock_env();
+        env.block.height = height;
+        env
+    };
+    let commit_height = mock_env().block.height;
+    let params = CommitSwapParams {
+        offer_asset: Asset {
+            info: AssetInfo::NativeToken {
+                denom: "uusd".to_string(),
+            },
+            amount: Uint128::from(10_000u128),
+        },
+        belief_price: None,
+        max_spread: None,
+        to: None,
+    };
+    let hash = commit_swap_hash(&params, "salt", &Addr::unchecked("addr0000")).unwrap();
+    let info = mock_info("addr0000", &[Coin::new(10_000u128, "uusd")]);
+    let reveal = |salt: &str| ExecuteMsg::RevealSwap {
+        params: params.clone(),
+        salt: salt.to_string(),
+    };
+
+    let mut deps = single_sided_pool(10_000, 0);
+    let res: CommitConfig =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::CommitConfig {}).unwrap()).unwrap();
+    assert_eq!(
+        res,
+        CommitConfig {
+            min_delay_blocks: 2,
+            expiry_blocks: 100,
+        }
+    );
+
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        ExecuteMsg::CommitSwap { hash: hash.clone() },
+    )
+    .unwrap();
+    let res: Option<CommitmentResponse> = from_binary(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Commitment {
+                address: "addr0000".to_string(),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        res,
+        Some(CommitmentResponse {
+            hash: hash.clone(),
+            height: commit_height,
+        })
+    );
+
+    // not before the delay, only with the committed salt and by the committer
+    let res = execute(
+        deps.as_mut(),
+        env_at(commit_height + 1),
+        info.clone(),
+        reveal("salt"),
+    );
+    assert_eq!(
+        res,
+        Err(ContractError::CommitmentTooEarly {
+            reveal_height: commit_height + 2
+        })
+    );
+    let res = execute(
+        deps.as_mut(),
+        env_at(commit_height + 2),
+        info.clone(),
+        reveal("wrong"),
+    );
+    assert_eq!(res, Err(ContractError::CommitmentMismatch {}));
+    let res = execute(
+        deps.as_mut(),
+        env_at(commit_height + 2),
+        mock_info("addr0001", &[Coin::new(10_000u128, "uusd")]),
+        reveal("salt"),
+    );
+    assert_eq!(res, Err(ContractError::CommitmentNotFound {}));
+
+    // the swap runs at the reserves of the reveal
+    let simulation = query_simulation(deps.as_ref(), params.offer_asset.clone(), None).unwrap();
+    let res = execute(
+        deps.as_mut(),
+        env_at(commit_height + 2),
+        info.clone(),
+        reveal("salt"),
+    )
+    .unwrap();
+    assert!(res.attributes.contains(&attr("commitment", hash.clone())));
+    assert!(res
+        .attributes
+        .contains(&attr("return_amount", simulation.return_amount.to_string())));
+
+    // a commitment is revealed once
+    let res = execute(
+        deps.as_mut(),
+        env_at(commit_height + 3),
+        info.clone(),
+        reveal("salt"),
+    );
+    assert_eq!(res, Err(ContractError::CommitmentNotFound {}));
+
+    // expired commitments can not be revealed
+    let mut deps = single_sided_pool(10_000, 0);
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        ExecuteMsg::CommitSwap { hash: hash.clone() },
+    )
+    .unwrap();
+    let res = execute(
+        deps.as_mut(),
+        env_at(commit_height + 101),
+        info.clone(),
+        reveal("salt"),
+    );
+    assert_eq!(res, Err(ContractError::CommitmentExpired {}));
+
+    // the committer can cancel
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        ExecuteMsg::CancelCommitment {},
+    )
+    .unwrap();
+    let res = execute(
+        deps.as_mut(),
+        env_at(commit_height + 2),
+        info,
+        reveal("salt"),
+    );
+    assert_eq!(res, Err(ContractError::CommitmentNotFound {}));
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        ExecuteMsg::CancelCommitment {},
+    );
+    assert_eq!(res, Err(ContractError::CommitmentNotFound {}));
+
+    // token offers are revealed through the cw20 hook with the committed amount
+    let mut deps = single_sided_pool(0, 10_000);
+    let params = CommitSwapParams {
+        offer_asset: Asset {
+            info: AssetInfo::Token {
+                contract_addr: "asset0000".to_string(),
+            },
+            amount: Uint128::from(10_000u128),
+        },
+        belief_price: None,
+        max_spread: None,
+        to: Some("addr0001".to_string()),
+    };
+    let hash = commit_swap_hash(&params, "salt", &Addr::unchecked("addr0000")).unwrap();
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        ExecuteMsg::CommitSwap { hash },
+    )
+    .unwrap();
+    let receive = |amount: u128| {
+        ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: "addr0000".to_string(),
+            amount: Uint128::from(amount),
+            msg: to_binary(&Cw20HookMsg::RevealSwap {
+                params: params.clone(),
+                salt: "salt".to_string(),
+            })
+            .unwrap(),
+        })
+    };
+    let res = execute(
+        deps.as_mut(),
+        env_at(commit_height + 2),
+        mock_info("asset0000", &[]),
+        receive(9_999),
+    );
+    assert_eq!(res, Err(ContractError::AssetMismatch {}));
+    let simulation = query_simulation(deps.as_ref(), params.offer_asset.clone(), None).unwrap();
+    let res = execute(
+        deps.as_mut(),
+        env_at(commit_height + 2),
+        mock_info("asset0000", &[]),
+        receive(10_000),
+    )
+    .unwrap();
+    assert!(res.attributes.contains(&attr("receiver", "addr0001")));
+    assert!(res
+        .attributes
+        .contains(&attr("return_amount", simulation.return_amount.to_string())));
+
+    // the reveal window is set by the pair admin
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        ExecuteMsg::UpdateCommitConfig {
+            min_delay_blocks: 1,
+            expiry_blocks: 10,
+        },
+    );
+    assert_eq!(res, Err(ContractError::Unauthorized {}));
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("owner0000", &[]),
+        ExecuteMsg::UpdateCommitConfig {
+            min_delay_blocks: 0,
+            expiry_blocks: 10,
+        },
+    );
+    assert_eq!(res, Err(ContractError::InvalidCommitConfig {}));
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("owner0000", &[]),
+        ExecuteMsg::UpdateCommitConfig {
+            min_delay_blocks: 1,
+            expiry_blocks: 10,
+        },
+    )
+    .unwrap();
+    let res: CommitConfig =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::CommitConfig {}).unwrap()).unwrap();
+    assert_eq!(
+        res,
+        CommitConfig {
+            min_delay_blocks: 1,
+            expiry_blocks: 10,
+        }
+    );
+}
+
+#[test]
+fn belief_price_direction() {
+    let swap = |belief_price: &str,
+                belief_price_direction: Option<BeliefPriceDirection>,
+                max_spread: &str| {
+        let mut deps = single_sided_pool(10_000_000, 0);
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("addr0000", &[Coin::new(10_000_000u128, "uusd")]),
+            ExecuteMsg::Swap {
+                offer_asset: Asset {
+                    info: AssetInfo::NativeToken {
+                        denom: "uusd".to_string(),
+                    },
+                    amount: Uint128::from(10_000_000u128),
+                },
+                belief_price: Some(Decimal::from_str(belief_price).unwrap()),
+                belief_price_direction,
+                max_spread: Some(Decimal::from_str(max_spread).unwrap()),
+                to: None,
+                deadline: None,
+                to_msg: None,
+                referral: None,
+            },
+        )
+    };
+
+    // 1.25 is read as offer per ask unless told otherwise
+    let res = swap("1.25", None, "0.2").unwrap();
+    assert!(res.attributes.contains(&attr("expected_return", "8000000")));
+    let res = swap("1.25", Some(BeliefPriceDirection::OfferPerAsk), "0.2").unwrap();
+    assert!(res.attributes.contains(&attr("expected_return", "8000000")));
+
+    // the same economic price in both directions gives the same protection
+    for (belief_price, direction) in [
+        ("0.8", BeliefPriceDirection::OfferPerAsk),
+        ("1.25", BeliefPriceDirection::AskPerOffer),
+    ] {
+        let res = swap(belief_price, Some(direction.clone()), "0.2");
+        assert_eq!(res, Err(ContractError::MaxSpreadAssertion {}));
+        let res = swap(belief_price, Some(direction), "0.25").unwrap();
+        assert!(res
+            .attributes
+            .contains(&attr("expected_return", "12500000")));
+    }
+
+    let res = swap("0", None, "0.2");
+    assert_eq!(res, Err(ContractError::ZeroBeliefPrice {}));
+    let res = swap("0", Some(BeliefPriceDirection::AskPerOffer), "0.2");
+    assert_eq!(res, Err(ContractError::ZeroBeliefPrice {}));
+}
+
+#[test]
+fn pair_admin_authorization() {
+    let mut deps = single_sided_pool(0, 0);
+
+    // the factory owner appoints the admin, exposed by the config query
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        ExecuteMsg::UpdateAdmin {
+            admin: Some("admin0000".to_string()),
+        },
+    );
+    assert_eq!(res, Err(ContractError::Unauthorized {}));
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("owner0000", &[]),
+        ExecuteMsg::UpdateAdmin {
+            admin: Some("admin0000".to_string()),
+        },
+    )
+    .unwrap();
+    let config: ConfigResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
+    assert_eq!(config.pair_admin, Some(Addr::unchecked("admin0000")));
+
+    let msgs = vec![
+        ExecuteMsg::UpdateFeeConfig {
+            lp_fee_rate: Decimal::permille(2),
+            protocol_fee_rate: Decimal::zero(),
+            protocol_fee_collector: None,
+        },
+        ExecuteMsg::UpdateFeeTiers {
+            fee_tiers: vec![(Uint128::from(500u128), Decimal::permille(5))],
+        },
+        ExecuteMsg::SetPoolStatus {
+            swaps_paused: true,
+            deposits_paused: false,
+        },
+        ExecuteMsg::RescueFunds {
+            asset_info: AssetInfo::Token {
+                contract_addr: "asset0001".to_string(),
+            },
+            amount: Uint128::from(100u128),
+            to: "addr0001".to_string(),
+        },
+        ExecuteMsg::UpdateAdmin {
+            admin: Some("admin0000".to_string()),
+        },
+    ];
+    for msg in msgs {
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("addr0000", &[]),
+            msg.clone(),
+        );
+        assert_eq!(res, Err(ContractError::Unauthorized {}));
+
+        for sender in ["admin0000", "owner0000"] {
+            execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info(sender, &[]),
+                msg.clone(),
+            )
+            .unwrap();
+        }
+    }
+
+    // the admin hands over, then the factory owner removes the admin
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("admin0000", &[]),
+        ExecuteMsg::UpdateAdmin {
+            admin: Some("admin0001".to_string()),
+        },
+    )
+    .unwrap();
+    let msg = ExecuteMsg::SetPoolStatus {
+        swaps_paused: false,
+        deposits_paused: false,
+    };
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("admin0000", &[]),
+        msg.clone(),
+    );
+    assert_eq!(res, Err(ContractError::Unauthorized {}));
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("admin0001", &[]),
+        msg.clone(),
+    )
+    .unwrap();
+
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("owner0000", &[]),
+        ExecuteMsg::UpdateAdmin { admin: None },
+    )
+    .unwrap();
+    let res = execute(deps.as_mut(), mock_env(), mock_info("admin0001", &[]), msg);
+    assert_eq!(res, Err(ContractError::Unauthorized {}));
+    let config: ConfigResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
+    assert_eq!(config.pair_admin, None);
+}
+
+#[test]
+fn fee_stats() {
+    let mut deps = single_sided_pool(0, 0);
+    let fee_stats = |deps: Deps<TerraQuery>| -> FeeStatsResponse {
+        from_binary(&query(deps, mock_env(), QueryMsg::FeeStats {}).unwrap()).unwrap()
+    };
+    let commission = |res: &Response<TerraMsg>| -> Uint128 {
+        let attr = res
+            .attributes
+            .iter()
+            .find(|attr| attr.key == "commission_amount")
+            .unwrap();
+        Uint128::from_str(&attr.value).unwrap()
+    };
+
+    // nothing is tracked before the first swap
+    let stats = fee_stats(deps.as_ref());
+    assert_eq!(stats.tracked_since, None);
+    assert_eq!(stats.cumulative_fees[0].amount, Uint128::zero());
+    assert_eq!(stats.cumulative_fees[1].amount, Uint128::zero());
+
+    let mut env = mock_env();
+    let started = env.block.time.seconds();
+    let mut expected = [Uint128::zero(), Uint128::zero()];
+    for offer in [1_000_000u128, 2_500_000, 7_000_000] {
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("addr0000", &[Coin::new(offer, "uusd")]),
+            ExecuteMsg::Swap {
+                offer_asset: Asset {
+                    info: AssetInfo::NativeToken {
+                        denom: "uusd".to_string(),
+                    },
+                    amount: Uint128::from(offer),
+                },
+                belief_price: None,
+                belief_price_direction: None,
+                max_spread: None,
+                to: None,
+                deadline: None,
+                to_msg: None,
+                referral: None,
+            },
+        )
+        .unwrap();
+        expected[1] += commission(&res);
+
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("asset0000", &[]),
+            ExecuteMsg::Receive(Cw20ReceiveMsg {
+                sender: "addr0000".to_string(),
+                amount: Uint128::from(offer),
+                msg: to_binary(&Cw20HookMsg::Swap {
+                    belief_price: None,
+                    belief_price_direction: None,
+                    max_spread: None,
+                    to: None,
+                    deadline: None,
+                    to_msg: None,
+                    referral: None,
+                })
+                .unwrap(),
+            }),
+        )
+        .unwrap();
+        expected[0] += commission(&res);
+        env.block.time = env.block.time.plus_seconds(60);
+    }
+
+    let stats = fee_stats(deps.as_ref());
+    assert!(!expected[0].is_zero() && !expected[1].is_zero());
+    assert_eq!(stats.cumulative_fees[0].amount, expected[0]);
+    assert_eq!(stats.cumulative_fees[1].amount, expected[1]);
+    assert_eq!(
+        stats.cumulative_fees[0].info,
+        AssetInfo::NativeToken {
+            denom: "uusd".to_string(),
+        }
+    );
+    assert_eq!(stats.tracked_since, Some(started));
+    let pool: PoolResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Pool {}).unwrap()).unwrap();
+    assert_eq!(stats.reserves, pool.assets);
+
+    // the counters saturate instead of overflowing
+    FEE_TRACKER
+        .save(
+            deps.as_mut().storage,
+            &FeeTracker {
+                cumulative_fees: [Uint128::MAX - Uint128::from(1u128), Uint128::zero()],
+                tracked_since: started,
+            },
+        )
+        .unwrap();
+    track_commission(
+        deps.as_mut().storage,
+        0,
+        Uint128::from(10u128),
+        started + 600,
+    )
+    .unwrap();
+    let stats = fee_stats(deps.as_ref());
+    assert_eq!(stats.cumulative_fees[0].amount, Uint128::MAX);
+    assert_eq!(stats.tracked_since, Some(started));
+}
+
+#[test]
+fn native_funds_match_declared_assets() {
+    let uusd = |amount: u128| Asset {
+        info: AssetInfo::NativeToken {
+            denom: "uusd".to_string(),
+        },
+        amount: Uint128::from(amount),
+    };
+    let token = |amount: u128| Asset {
+        info: AssetInfo::Token {
+            contract_addr: "asset0000".to_string(),
+        },
+        amount: Uint128::from(amount),
+    };
+    let mismatch = |declared: u128, sent: u128| {
+        Err(ContractError::NativeFundsMismatch {
+            denom: "uusd".to_string(),
+            declared: Uint128::from(declared),
+            sent: Uint128::from(sent),
+        })
+    };
+    let unexpected = Err(ContractError::UnexpectedFunds {
+        denom: "uluna".to_string(),
+    });
+
+    let provide = |funds: &[Coin]| {
+        let mut deps = single_sided_pool(0, 0);
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("addr0000", funds),
+            ExecuteMsg::ProvideLiquidity {
+                assets: [uusd(1_000), token(1_000)],
+                receiver: None,
+                deadline: None,
+                slippage_tolerance: None,
+                min_lp_to_receive: None,
+            },
+        )
+        .map(|_| ())
+    };
+    assert_eq!(
+        provide(&[Coin::new(1_001u128, "uusd")]),
+        mismatch(1_000, 1_001)
+    );
+    assert_eq!(provide(&[Coin::new(999u128, "uusd")]), mismatch(1_000, 999));
+    assert_eq!(provide(&[]), mismatch(1_000, 0));
+    assert_eq!(
+        provide(&[Coin::new(1_000u128, "uusd"), Coin::new(1u128, "uluna")]),
+        unexpected
+    );
+    // coins of the same denom are summed
+    assert_eq!(
+        provide(&[Coin::new(600u128, "uusd"), Coin::new(600u128, "uusd")]),
+        mismatch(1_000, 1_200)
+    );
+    assert_eq!(provide(&[Coin::new(1_000u128, "uusd")]), Ok(()));
+
+    let swap = |funds: &[Coin]| {
+        let mut deps = single_sided_pool(0, 0);
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("addr0000", funds),
+            ExecuteMsg::Swap {
+                offer_asset: uusd(1_000),
+                belief_price: None,
+                belief_price_direction: None,
+                max_spread: None,
+                to: None,
+                deadline: None,
+                to_msg: None,
+                referral: None,
+            },
+        )
+        .map(|_| ())
+    };
+    assert_eq!(
+        swap(&[Coin::new(1_001u128, "uusd")]),
+        mismatch(1_000, 1_001)
+    );
+    assert_eq!(swap(&[Coin::new(999u128, "uusd")]), mismatch(1_000, 999));
+    assert_eq!(
+        swap(&[Coin::new(1_000u128, "uusd"), Coin::new(1u128, "uluna")]),
+        unexpected
+    );
+    assert_eq!(swap(&[Coin::new(1u128, "uluna")]), unexpected);
+    assert_eq!(swap(&[Coin::new(1_000u128, "uusd")]), Ok(()));
 }
@@ -2,50 +2,93 @@
 use cosmwasm_std::entry_point;
 
 use cosmwasm_std::{
-    from_binary, to_binary, Addr, Api, Binary, Coin, CosmosMsg, Deps, DepsMut, Env, MessageInfo,
-    QueryRequest, Response, StdError, StdResult, Uint128, WasmMsg, WasmQuery,
+    from_binary, to_binary, Addr, Api, Binary, Coin, CosmosMsg, Decimal, Decimal256, Deps, DepsMut,
+    Empty, Env, Event, IbcMsg, IbcTimeout, MessageInfo, QuerierWrapper, Reply, Response, StdResult,
+    Storage, SubMsg, Uint128, Uint256, WasmMsg,
 };
-use cw2::set_contract_version;
+use cw2::{get_contract_version, set_contract_version};
 
-use crate::operations::execute_swap_operation;
-use crate::querier::{compute_reverse_tax, compute_tax};
-use crate::state::{Config, CONFIG};
+use crate::error::ContractError;
+use crate::migration::migrate_from_v0_1_0;
+use crate::operations::{
+    execute_swap_operation, record_hop_return, record_swap_receipt, write_swap_receipt,
+    FINAL_HOP_REPLY_ID, HOP_REPLY_ID,
+};
+use crate::querier::query_wrapper_fee_rate;
+use crate::state::{
+    deduct_transfer_fee, is_dex_enabled, read_dex_msg_format, read_ibc_origin,
+    read_pending_unwinds, read_transfer_fee_rate, read_wrapper, read_wrapper_contract, Config,
+    Wrapper, ACCOUNT_LIMITS, ACCOUNT_SWAP_VOLUMES, CONFIG, DEX_ENABLED, DEX_MSG_FORMATS,
+    HOP_RETURN, IBC_DENOMS, KNOWN_DEXES, PENDING_HOP, PENDING_UNWINDS, PRICE_GUARDS,
+    ROUTE_INITIATOR, ROUTE_NONCE, SWAP_RECEIPTS, TOKEN_TRANSFER_FEES, UNWIND_NONCE, WRAPPERS,
+};
 
 use classic_bindings::{SwapResponse, TerraMsg, TerraQuerier, TerraQuery};
 
 use classic_terraswap::asset::{Asset, AssetInfo, PairInfo};
-use classic_terraswap::pair::{QueryMsg as PairQueryMsg, SimulationResponse};
-use classic_terraswap::querier::{query_pair_info, reverse_simulate};
+use classic_terraswap::factory::{QueryMsg as FactoryQueryMsg, RouterBootstrapResponse};
+use classic_terraswap::querier::{
+    compute_reverse_tax, compute_tax, query_pair_info, query_pair_info_from_pair, query_pool,
+    reverse_simulate_with_tax, simulate_in_format,
+};
 use classic_terraswap::router::{
-    ConfigResponse, Cw20HookMsg, ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg,
-    SimulateSwapOperationsResponse, SwapOperation,
+    assert_operations, AccountLimits, CheckSwapViabilityResponse, ConfigResponse, Cw20HookMsg,
+    DexStatus, EstimateRouteGasResponse, ExecuteMsg, FeeSide, GasConfig, GuardConfig,
+    IbcDenomResponse, IbcOrigin, InstantiateMsg, LastSwapReceiptResponse, MigrateMsg, MsgFormat,
+    PendingUnwind, PendingUnwindsResponse, QueryMsg, ReferenceSource, RouteTax, RoutingFee,
+    SimulateRouteTaxResponse, SimulateSwapOperationsResponse, SwapOperation, TaxResponse,
+    WrapperInfo, WrapperResponse,
 };
 use classic_terraswap::util::assert_deadline;
 use cw20::Cw20ReceiveMsg;
-use std::collections::HashMap;
+use sha2::{Digest, Sha256};
 
 // version info for migration info
 const CONTRACT_NAME: &str = "crates.io:terraswap-router";
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
+const SECONDS_PER_DAY: u64 = 86400;
+/// An unwound return is refunded by the origin chain when not received within the timeout
+const IBC_TRANSFER_TIMEOUT_SECONDS: u64 = 600;
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
     deps: DepsMut<TerraQuery>,
     _env: Env,
-    _info: MessageInfo,
+    info: MessageInfo,
     msg: InstantiateMsg,
-) -> StdResult<Response<TerraMsg>> {
+) -> Result<Response<TerraMsg>, ContractError> {
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
 
+    for factory in [
+        &msg.terraswap_factory,
+        &msg.loop_factory,
+        &msg.astroport_factory,
+    ] {
+        check_factory(
+            &deps.querier,
+            factory,
+            msg.unchecked,
+            msg.validate_factories,
+        )?;
+    }
+
     CONFIG.save(
         deps.storage,
         &Config {
+            owner: deps.api.addr_canonicalize(info.sender.as_str())?,
             terraswap_factory: deps.api.addr_canonicalize(&msg.terraswap_factory)?,
             loop_factory: deps.api.addr_canonicalize(&msg.loop_factory)?,
             astroport_factory: deps.api.addr_canonicalize(&msg.astroport_factory)?,
+            routing_fee: None,
+            gas_config: GasConfig::default(),
         },
     )?;
 
+    for dex in KNOWN_DEXES {
+        DEX_ENABLED.save(deps.storage, dex, &true)?;
+    }
+
     Ok(Response::default())
 }
 
@@ -55,30 +98,50 @@ pub fn execute(
     env: Env,
     info: MessageInfo,
     msg: ExecuteMsg,
-) -> StdResult<Response<TerraMsg>> {
+) -> Result<Response<TerraMsg>, ContractError> {
     match msg {
         ExecuteMsg::Receive(msg) => receive_cw20(deps, env, info, msg),
         ExecuteMsg::ExecuteSwapOperations {
             operations,
             minimum_receive,
+            minimum_receive_price,
             to,
             deadline,
+            offer_amount,
+            auto_unwind,
+            unwind_receiver,
         } => {
             let api = deps.api;
+            let (offer_amount, exact_offer) = offer_amount_of(
+                deps.as_ref(),
+                &info.sender,
+                &operations,
+                &info.funds,
+                offer_amount,
+            )?;
+            let minimum_receive =
+                minimum_receive_of(offer_amount, minimum_receive, minimum_receive_price)?;
+            let unwind_receiver = unwind_receiver_of(auto_unwind, unwind_receiver, &to)?;
             execute_swap_operations(
                 deps,
                 env,
                 info.sender,
+                offer_amount,
+                exact_offer,
                 operations,
                 minimum_receive,
                 optional_addr_validate(api, to)?,
                 deadline,
+                unwind_receiver,
             )
         }
         ExecuteMsg::ExecuteSwapOperation {
             operation,
             to,
             deadline,
+            offer_amount,
+            route_id,
+            hop_index,
         } => {
             let api = deps.api;
             execute_swap_operation(
@@ -88,8 +151,22 @@ pub fn execute(
                 operation,
                 optional_addr_validate(api, to)?.map(|v| v.to_string()),
                 deadline,
+                offer_amount,
+                route_id.zip(hop_index),
             )
         }
+        ExecuteMsg::DistributeAskAsset {
+            asset_info,
+            receiver,
+        } => {
+            let receiver = deps.api.addr_validate(&receiver)?;
+            distribute_ask_asset(deps, env, info, asset_info, receiver)
+        }
+        ExecuteMsg::UnwindAskAsset {
+            ibc_denom,
+            unwind_receiver,
+            minimum_receive,
+        } => unwind_ask_asset(deps, env, info, ibc_denom, unwind_receiver, minimum_receive),
         ExecuteMsg::AssertMinimumReceive {
             asset_info,
             prev_balance,
@@ -102,7 +179,429 @@ pub fn execute(
             minimum_receive,
             deps.api.addr_validate(&receiver)?,
         ),
+        ExecuteMsg::UpdateWrapper {
+            native_denom,
+            wrapper,
+        } => update_wrapper(deps, info, native_denom, wrapper),
+        ExecuteMsg::UpdateIbcDenom { ibc_denom, origin } => {
+            update_ibc_denom(deps, info, ibc_denom, origin)
+        }
+        ExecuteMsg::SettleUnwind { id, refund } => settle_unwind(deps, info, id, refund),
+        ExecuteMsg::UpdateAccountLimits { account_limits } => {
+            update_account_limits(deps, info, account_limits)
+        }
+        ExecuteMsg::UpdateRoutingFee { routing_fee } => update_routing_fee(deps, info, routing_fee),
+        ExecuteMsg::SetDexEnabled { dex, enabled } => set_dex_enabled(deps, info, dex, enabled),
+        ExecuteMsg::SetDexMsgFormat { dex, msg_format } => {
+            set_dex_msg_format(deps, info, dex, msg_format)
+        }
+        ExecuteMsg::SetPriceGuard {
+            offer_asset_info,
+            ask_asset_info,
+            guard,
+        } => set_price_guard(deps, info, offer_asset_info, ask_asset_info, guard),
+        ExecuteMsg::SetTokenTransferFee { token, bps } => {
+            set_token_transfer_fee(deps, info, token, bps)
+        }
+        ExecuteMsg::UpdateGasConfig { gas_config } => update_gas_config(deps, info, gas_config),
+        ExecuteMsg::UpdateConfig {
+            terraswap_factory,
+            loop_factory,
+            astroport_factory,
+            unchecked,
+            validate_factories,
+        } => update_config(
+            deps,
+            info,
+            [terraswap_factory, loop_factory, astroport_factory],
+            unchecked,
+            validate_factories,
+        ),
+    }
+}
+
+// Only owner can execute it
+pub fn update_config(
+    deps: DepsMut<TerraQuery>,
+    info: MessageInfo,
+    factories: [Option<String>; 3],
+    unchecked: bool,
+    validate_factories: bool,
+) -> Result<Response<TerraMsg>, ContractError> {
+    let mut config: Config = CONFIG.load(deps.storage)?;
+
+    // permission check
+    if deps.api.addr_canonicalize(info.sender.as_str())? != config.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let [terraswap_factory, loop_factory, astroport_factory] = factories;
+    for (factory, stored) in [
+        (terraswap_factory, &mut config.terraswap_factory),
+        (loop_factory, &mut config.loop_factory),
+        (astroport_factory, &mut config.astroport_factory),
+    ] {
+        if let Some(factory) = factory {
+            check_factory(&deps.querier, &factory, unchecked, validate_factories)?;
+            *stored = deps.api.addr_canonicalize(&factory)?;
+        }
+    }
+
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new().add_attribute("action", "update_config"))
+}
+
+// Only owner can execute it
+pub fn update_wrapper(
+    deps: DepsMut<TerraQuery>,
+    info: MessageInfo,
+    native_denom: String,
+    wrapper: Option<WrapperInfo>,
+) -> Result<Response<TerraMsg>, ContractError> {
+    let config: Config = CONFIG.load(deps.storage)?;
+
+    // permission check
+    if deps.api.addr_canonicalize(info.sender.as_str())? != config.owner {
+        return Err(ContractError::Unauthorized {});
     }
+
+    let (wrapper_contract, wrapped_token) = match wrapper {
+        Some(wrapper) => {
+            let wrapper = Wrapper {
+                contract: deps.api.addr_validate(&wrapper.wrapper_contract)?,
+                wrapped_token: deps.api.addr_validate(&wrapper.wrapped_token)?,
+            };
+            WRAPPERS.save(deps.storage, &native_denom, &wrapper)?;
+            (
+                wrapper.contract.to_string(),
+                wrapper.wrapped_token.to_string(),
+            )
+        }
+        None => {
+            WRAPPERS.remove(deps.storage, &native_denom);
+            (String::new(), String::new())
+        }
+    };
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "update_wrapper"),
+        ("native_denom", &native_denom),
+        ("wrapper_contract", &wrapper_contract),
+        ("wrapped_token", &wrapped_token),
+    ]))
+}
+
+// Only owner can execute it
+pub fn update_ibc_denom(
+    deps: DepsMut<TerraQuery>,
+    info: MessageInfo,
+    ibc_denom: String,
+    origin: Option<IbcOrigin>,
+) -> Result<Response<TerraMsg>, ContractError> {
+    let config: Config = CONFIG.load(deps.storage)?;
+
+    // permission check
+    if deps.api.addr_canonicalize(info.sender.as_str())? != config.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let denom_info = AssetInfo::NativeToken {
+        denom: ibc_denom.clone(),
+    };
+    if !ibc_denom.starts_with("ibc/") || denom_info.validate(deps.api).is_err() {
+        return Err(ContractError::InvalidIbcDenom { denom: ibc_denom });
+    }
+
+    let (channel_id, origin_denom) = match origin {
+        Some(origin) => {
+            if origin.channel_id.is_empty() || origin.origin_denom.is_empty() {
+                return Err(ContractError::InvalidIbcOrigin {});
+            }
+            IBC_DENOMS.save(deps.storage, &ibc_denom, &origin)?;
+            (origin.channel_id, origin.origin_denom)
+        }
+        None => {
+            IBC_DENOMS.remove(deps.storage, &ibc_denom);
+            (String::new(), String::new())
+        }
+    };
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "update_ibc_denom"),
+        ("ibc_denom", &ibc_denom),
+        ("channel_id", &channel_id),
+        ("origin_denom", &origin_denom),
+    ]))
+}
+
+// Only owner can execute it
+pub fn update_account_limits(
+    deps: DepsMut<TerraQuery>,
+    info: MessageInfo,
+    account_limits: Option<AccountLimits>,
+) -> Result<Response<TerraMsg>, ContractError> {
+    let config: Config = CONFIG.load(deps.storage)?;
+
+    // permission check
+    if deps.api.addr_canonicalize(info.sender.as_str())? != config.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    match account_limits {
+        Some(account_limits) => ACCOUNT_LIMITS.save(deps.storage, &account_limits)?,
+        None => ACCOUNT_LIMITS.remove(deps.storage),
+    }
+
+    Ok(Response::new().add_attribute("action", "update_account_limits"))
+}
+
+// Only owner can execute it
+pub fn update_routing_fee(
+    deps: DepsMut<TerraQuery>,
+    info: MessageInfo,
+    routing_fee: Option<RoutingFee>,
+) -> Result<Response<TerraMsg>, ContractError> {
+    let mut config: Config = CONFIG.load(deps.storage)?;
+
+    // permission check
+    if deps.api.addr_canonicalize(info.sender.as_str())? != config.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    if let Some(routing_fee) = &routing_fee {
+        if routing_fee.rate >= Decimal::one() {
+            return Err(ContractError::InvalidRoutingFeeRate {});
+        }
+
+        deps.api.addr_validate(&routing_fee.collector)?;
+    }
+
+    config.routing_fee = routing_fee;
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new().add_attribute("action", "update_routing_fee"))
+}
+
+// Only owner can execute it
+pub fn set_token_transfer_fee(
+    deps: DepsMut<TerraQuery>,
+    info: MessageInfo,
+    token: String,
+    bps: u16,
+) -> Result<Response<TerraMsg>, ContractError> {
+    let config: Config = CONFIG.load(deps.storage)?;
+
+    // permission check
+    if deps.api.addr_canonicalize(info.sender.as_str())? != config.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    if bps >= 10_000 {
+        return Err(ContractError::InvalidTransferFeeBps {});
+    }
+
+    let token = deps.api.addr_validate(&token)?;
+    if bps == 0 {
+        TOKEN_TRANSFER_FEES.remove(deps.storage, &token);
+    } else {
+        TOKEN_TRANSFER_FEES.save(deps.storage, &token, &bps)?;
+    }
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "set_token_transfer_fee"),
+        ("token", token.as_str()),
+        ("bps", &bps.to_string()),
+    ]))
+}
+
+// Only owner can execute it
+pub fn set_price_guard(
+    deps: DepsMut<TerraQuery>,
+    info: MessageInfo,
+    offer_asset_info: AssetInfo,
+    ask_asset_info: AssetInfo,
+    guard: Option<GuardConfig>,
+) -> Result<Response<TerraMsg>, ContractError> {
+    let config: Config = CONFIG.load(deps.storage)?;
+
+    // permission check
+    if deps.api.addr_canonicalize(info.sender.as_str())? != config.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    if offer_asset_info.equal(&ask_asset_info) {
+        return Err(ContractError::InvalidPriceGuard {});
+    }
+
+    let offer_asset = offer_asset_info.to_string();
+    let ask_asset = ask_asset_info.to_string();
+
+    // a pool has one guard, whichever direction it is set in
+    PRICE_GUARDS.remove(deps.storage, (&ask_asset, &offer_asset));
+    match &guard {
+        Some(guard) => {
+            if guard.max_deviation.is_zero() {
+                return Err(ContractError::InvalidPriceGuard {});
+            }
+
+            match &guard.reference {
+                ReferenceSource::Pair { contract_addr } => {
+                    let pair_info = query_pair_info_from_pair(
+                        &deps.querier,
+                        deps.api.addr_validate(contract_addr)?,
+                    )?;
+                    let holds = |asset_info: &AssetInfo| {
+                        pair_info.asset_infos.iter().any(|a| a.equal(asset_info))
+                    };
+                    if !holds(&offer_asset_info) || !holds(&ask_asset_info) {
+                        return Err(ContractError::InvalidPriceGuard {});
+                    }
+                }
+                ReferenceSource::Constant { price } => {
+                    if price.is_zero() {
+                        return Err(ContractError::InvalidPriceGuard {});
+                    }
+                }
+            }
+
+            PRICE_GUARDS.save(deps.storage, (&offer_asset, &ask_asset), guard)?;
+        }
+        None => PRICE_GUARDS.remove(deps.storage, (&offer_asset, &ask_asset)),
+    }
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "set_price_guard"),
+        ("offer_asset", &offer_asset),
+        ("ask_asset", &ask_asset),
+        (
+            "max_deviation",
+            &guard.map_or_else(
+                || "none".to_string(),
+                |guard| guard.max_deviation.to_string(),
+            ),
+        ),
+    ]))
+}
+
+// Only owner can execute it
+pub fn update_gas_config(
+    deps: DepsMut<TerraQuery>,
+    info: MessageInfo,
+    gas_config: GasConfig,
+) -> Result<Response<TerraMsg>, ContractError> {
+    let mut config: Config = CONFIG.load(deps.storage)?;
+
+    // permission check
+    if deps.api.addr_canonicalize(info.sender.as_str())? != config.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    config.gas_config = gas_config;
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new().add_attribute("action", "update_gas_config"))
+}
+
+// Only owner can execute it
+pub fn set_dex_enabled(
+    deps: DepsMut<TerraQuery>,
+    info: MessageInfo,
+    dex: String,
+    enabled: bool,
+) -> Result<Response<TerraMsg>, ContractError> {
+    let config: Config = CONFIG.load(deps.storage)?;
+
+    // permission check
+    if deps.api.addr_canonicalize(info.sender.as_str())? != config.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    if !KNOWN_DEXES.contains(&dex.as_str()) {
+        return Err(ContractError::UnknownDex { dex });
+    }
+
+    DEX_ENABLED.save(deps.storage, &dex, &enabled)?;
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "set_dex_enabled"),
+        ("dex", &dex),
+        ("enabled", &enabled.to_string()),
+    ]))
+}
+
+pub fn set_dex_msg_format(
+    deps: DepsMut<TerraQuery>,
+    info: MessageInfo,
+    dex: String,
+    msg_format: MsgFormat,
+) -> Result<Response<TerraMsg>, ContractError> {
+    let config: Config = CONFIG.load(deps.storage)?;
+
+    // permission check
+    if deps.api.addr_canonicalize(info.sender.as_str())? != config.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    if !KNOWN_DEXES.contains(&dex.as_str()) {
+        return Err(ContractError::UnknownDex { dex });
+    }
+
+    DEX_MSG_FORMATS.save(deps.storage, &dex, &msg_format)?;
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "set_dex_msg_format"),
+        ("dex", &dex),
+        ("msg_format", &msg_format.to_string()),
+    ]))
+}
+
+/// Sanity-checks a factory address at instantiate and on `UpdateConfig`, with
+/// `validate_factory` when `validate_factories` and else with `assert_factory`
+/// unless `unchecked`
+fn check_factory(
+    querier: &QuerierWrapper<TerraQuery>,
+    factory: &str,
+    unchecked: bool,
+    validate_factories: bool,
+) -> Result<(), ContractError> {
+    if validate_factories {
+        validate_factory(querier, factory)
+    } else if !unchecked {
+        assert_factory(querier, factory)
+    } else {
+        Ok(())
+    }
+}
+
+/// Checks the address answers the factory `Config` query, to catch mistyped
+/// factory addresses before the first swap through them
+fn assert_factory(
+    querier: &QuerierWrapper<TerraQuery>,
+    factory: &str,
+) -> Result<(), ContractError> {
+    querier
+        .query_wasm_smart::<Empty>(factory, &FactoryQueryMsg::Config {})
+        .map_err(|_| ContractError::InvalidFactory {
+            factory: factory.to_string(),
+        })?;
+
+    Ok(())
+}
+
+/// Checks the address answers the factory `RouterBootstrap` query, which the
+/// contracts answering only the older factory queries do not
+fn validate_factory(
+    querier: &QuerierWrapper<TerraQuery>,
+    factory: &str,
+) -> Result<(), ContractError> {
+    querier
+        .query_wasm_smart::<RouterBootstrapResponse>(factory, &FactoryQueryMsg::RouterBootstrap {})
+        .map_err(|_| ContractError::FactoryValidationFailed {
+            factory: factory.to_string(),
+        })?;
+
+    Ok(())
 }
 
 fn optional_addr_validate(api: &dyn Api, addr: Option<String>) -> StdResult<Option<Addr>> {
@@ -120,70 +619,304 @@ pub fn receive_cw20(
     env: Env,
     _info: MessageInfo,
     cw20_msg: Cw20ReceiveMsg,
-) -> StdResult<Response<TerraMsg>> {
+) -> Result<Response<TerraMsg>, ContractError> {
     let sender = deps.api.addr_validate(&cw20_msg.sender)?;
     match from_binary(&cw20_msg.msg)? {
         Cw20HookMsg::ExecuteSwapOperations {
             operations,
             minimum_receive,
+            minimum_receive_price,
             to,
             deadline,
+            auto_unwind,
+            unwind_receiver,
         } => {
             let api = deps.api;
+            let minimum_receive =
+                minimum_receive_of(cw20_msg.amount, minimum_receive, minimum_receive_price)?;
+            let unwind_receiver = unwind_receiver_of(auto_unwind, unwind_receiver, &to)?;
             execute_swap_operations(
                 deps,
                 env,
                 sender,
+                cw20_msg.amount,
+                false,
                 operations,
                 minimum_receive,
                 optional_addr_validate(api, to)?,
                 deadline,
+                unwind_receiver,
             )
         }
     }
 }
 
+/// The minimum receive of a route offering `offer_amount`, given as an amount or as a
+/// price of the ask asset per offer asset, rounded up to hold the price
+fn minimum_receive_of(
+    offer_amount: Uint128,
+    minimum_receive: Option<Uint128>,
+    minimum_receive_price: Option<Decimal>,
+) -> Result<Option<Uint128>, ContractError> {
+    match (minimum_receive, minimum_receive_price) {
+        (Some(_), Some(_)) => Err(ContractError::MinimumReceiveConflict {}),
+        (None, Some(price)) => Ok(Some(offer_amount.checked_mul_ceil(price)?)),
+        (minimum_receive, None) => Ok(minimum_receive),
+    }
+}
+
+/// The receiver on the origin chain of an `auto_unwind` route, which is not sent to `to`
+fn unwind_receiver_of(
+    auto_unwind: bool,
+    unwind_receiver: Option<String>,
+    to: &Option<String>,
+) -> Result<Option<String>, ContractError> {
+    if !auto_unwind {
+        return Ok(None);
+    }
+
+    match unwind_receiver {
+        Some(unwind_receiver) if !unwind_receiver.is_empty() && to.is_none() => {
+            Ok(Some(unwind_receiver))
+        }
+        _ => Err(ContractError::InvalidUnwind {}),
+    }
+}
+
+/// The offer amount of a route and whether it was given explicitly. An explicit
+/// `offer_amount` must be attached, only the owner offers what the router already holds
+fn offer_amount_of(
+    deps: Deps<TerraQuery>,
+    sender: &Addr,
+    operations: &[SwapOperation],
+    funds: &[Coin],
+    offer_amount: Option<Uint128>,
+) -> Result<(Uint128, bool), ContractError> {
+    let sent = native_offer_amount(operations, funds);
+    let offer_amount = match offer_amount {
+        Some(offer_amount) => offer_amount,
+        None => return Ok((sent, false)),
+    };
+
+    if sent != offer_amount {
+        let config: Config = CONFIG.load(deps.storage)?;
+        if deps.api.addr_canonicalize(sender.as_str())? != config.owner {
+            return Err(ContractError::OfferAmountMismatch { offer_amount, sent });
+        }
+    }
+
+    Ok((offer_amount, true))
+}
+
+/// Returns the amount of the first offer denom attached to the message
+fn native_offer_amount(operations: &[SwapOperation], funds: &[Coin]) -> Uint128 {
+    match operations.first().map(|op| op.get_offer_asset_info()) {
+        Some(AssetInfo::NativeToken { denom }) => funds
+            .iter()
+            .find(|coin| coin.denom == denom)
+            .map(|coin| coin.amount)
+            .unwrap_or_default(),
+        _ => Uint128::zero(),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn execute_swap_operations(
     deps: DepsMut<TerraQuery>,
     env: Env,
     sender: Addr,
+    offer_amount: Uint128,
+    exact_offer: bool,
+    operations: Vec<SwapOperation>,
+    minimum_receive: Option<Uint128>,
+    to: Option<Addr>,
+    deadline: Option<u64>,
+    unwind_receiver: Option<String>,
+) -> Result<Response<TerraMsg>, ContractError> {
+    let (route_id, nonce) = next_route_id(deps.storage, &env, &sender)?;
+    let offer_asset_info = operations.first().map(|op| op.get_offer_asset_info());
+    let operations_len = operations.len();
+
+    let messages = build_swap_messages(
+        deps.as_ref(),
+        &env,
+        sender.clone(),
+        offer_amount,
+        exact_offer,
+        &route_id,
+        operations,
+        minimum_receive,
+        to,
+        deadline,
+        unwind_receiver,
+    )?;
+
+    // the operations are validated while building the messages
+    let offer_asset_info = offer_asset_info.unwrap();
+    ROUTE_NONCE.save(deps.storage, &(env.block.height, nonce))?;
+    ROUTE_INITIATOR.save(deps.storage, &sender)?;
+
+    if let Some(account_limits) = ACCOUNT_LIMITS.may_load(deps.storage)? {
+        assert_account_limits(
+            deps,
+            &env,
+            account_limits,
+            &sender,
+            offer_asset_info.clone(),
+            offer_amount,
+        )?;
+    }
+
+    Ok(Response::new().add_messages(messages).add_event(
+        Event::new("terraswap_router_swap").add_attributes(vec![
+            ("route_id", route_id),
+            ("hop_count", operations_len.to_string()),
+            ("offer_asset", offer_asset_info.to_string()),
+            ("offer_amount", offer_amount.to_string()),
+        ]),
+    ))
+}
+
+/// Build the messages of the swap operations without dispatching them,
+/// shared by `execute_swap_operations` and the `BuildSwapMessages` query
+#[allow(clippy::too_many_arguments)]
+fn build_swap_messages(
+    deps: Deps<TerraQuery>,
+    env: &Env,
+    sender: Addr,
+    offer_amount: Uint128,
+    exact_offer: bool,
+    route_id: &str,
     operations: Vec<SwapOperation>,
     minimum_receive: Option<Uint128>,
     to: Option<Addr>,
     deadline: Option<u64>,
-) -> StdResult<Response<TerraMsg>> {
+    unwind_receiver: Option<String>,
+) -> Result<Vec<CosmosMsg<TerraMsg>>, ContractError> {
     assert_deadline(env.block.time.seconds(), deadline)?;
     let operations_len = operations.len();
     if operations_len == 0 {
-        return Err(StdError::generic_err("must provide operations"));
+        return Err(ContractError::EmptyOperations {});
     }
 
     // Assert the operations are properly set
-    assert_operations(&operations)?;
+    let target_asset_info = assert_operations(deps.api, &operations)?.into_inner();
+    assert_dexes_enabled(deps.storage, &operations)?;
+    if let Some(err) = check_price_guards(deps, &operations)? {
+        return Err(err);
+    }
+
+    if offer_amount.is_zero() {
+        return Err(ContractError::ZeroOfferAmount {});
+    }
+
+    // Assert the router holds the exact offer amount up front
+    let offer_asset_info = operations[0].get_offer_asset_info();
+    if exact_offer {
+        let balance =
+            offer_asset_info.query_pool(&deps.querier, deps.api, env.contract.address.clone())?;
+        if balance < offer_amount {
+            return Err(ContractError::InsufficientOfferBalance {
+                offer_amount,
+                balance,
+            });
+        }
+    }
 
+    let config: Config = CONFIG.load(deps.storage)?;
     let to = if let Some(to) = to { to } else { sender };
-    let target_asset_info = operations.last().unwrap().get_target_asset_info();
+
+    // an unwound route ends in the router, which transfers the return to the origin chain
+    let unwind = match unwind_receiver {
+        Some(unwind_receiver) => {
+            let ibc_denom = target_asset_info.to_string();
+            if !IBC_DENOMS.has(deps.storage, &ibc_denom) || !target_asset_info.is_native_token() {
+                return Err(ContractError::UnregisteredIbcDenom { denom: ibc_denom });
+            }
+            Some((ibc_denom, unwind_receiver))
+        }
+        None => None,
+    };
+
+    let mut messages: Vec<CosmosMsg<TerraMsg>> = vec![];
+
+    // Peel the routing fee off the offer asset before the first hop,
+    // the last hop sends the ask asset back to the router when it is charged on the ask side
+    let mut ask_side_fee = false;
+    let mut first_hop_amount = offer_amount;
+    if let Some(routing_fee) = config.routing_fee {
+        match routing_fee.fee_side {
+            FeeSide::Offer => {
+                let fee_amount = offer_amount * routing_fee.rate;
+                first_hop_amount = offer_amount.checked_sub(fee_amount)?;
+                if !fee_amount.is_zero() {
+                    messages.push(
+                        Asset {
+                            info: offer_asset_info,
+                            amount: fee_amount,
+                        }
+                        .into_msg(
+                            &deps.querier,
+                            deps.api.addr_validate(&routing_fee.collector)?,
+                        )?,
+                    );
+                }
+            }
+            FeeSide::Ask => ask_side_fee = true,
+        }
+    }
 
     let mut operation_index = 0;
-    let mut messages: Vec<CosmosMsg<TerraMsg>> = operations
-        .into_iter()
-        .map(|op| {
-            operation_index += 1;
-            Ok(CosmosMsg::Wasm(WasmMsg::Execute {
-                contract_addr: env.contract.address.to_string(),
-                funds: vec![],
-                msg: to_binary(&ExecuteMsg::ExecuteSwapOperation {
-                    operation: op,
-                    to: if operation_index == operations_len {
-                        Some(to.to_string())
-                    } else {
-                        None
-                    },
-                    deadline: None,
-                })?,
-            }))
-        })
-        .collect::<StdResult<Vec<CosmosMsg<TerraMsg>>>>()?;
+    for op in operations.into_iter() {
+        operation_index += 1;
+        messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: env.contract.address.to_string(),
+            funds: vec![],
+            msg: to_binary(&ExecuteMsg::ExecuteSwapOperation {
+                operation: op,
+                to: if operation_index == operations_len && !ask_side_fee && unwind.is_none() {
+                    Some(to.to_string())
+                } else {
+                    None
+                },
+                deadline: None,
+                // the first hop offers the amount of the route, each next hop
+                // the return of the previous one
+                offer_amount: if operation_index == 1 {
+                    Some(first_hop_amount)
+                } else {
+                    None
+                },
+                route_id: Some(route_id.to_string()),
+                hop_index: Some(operation_index as u32),
+            })?,
+        }));
+    }
+
+    // the minimum receive is asserted before the return leaves the chain
+    if let Some((ibc_denom, unwind_receiver)) = unwind {
+        messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: env.contract.address.to_string(),
+            funds: vec![],
+            msg: to_binary(&ExecuteMsg::UnwindAskAsset {
+                ibc_denom,
+                unwind_receiver,
+                minimum_receive,
+            })?,
+        }));
+        return Ok(messages);
+    }
+
+    if ask_side_fee {
+        messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: env.contract.address.to_string(),
+            funds: vec![],
+            msg: to_binary(&ExecuteMsg::DistributeAskAsset {
+                asset_info: target_asset_info.clone(),
+                receiver: to.to_string(),
+            })?,
+        }));
+    }
 
     // Execute minimum amount assertion
     if let Some(minimum_receive) = minimum_receive {
@@ -201,7 +934,274 @@ pub fn execute_swap_operations(
         }))
     }
 
-    Ok(Response::new().add_messages(messages))
+    Ok(messages)
+}
+
+/// Route id is the hash of the sender, the block height
+/// and the nonce of the route in the block
+fn next_route_id(storage: &dyn Storage, env: &Env, sender: &Addr) -> StdResult<(String, u64)> {
+    let nonce = match ROUTE_NONCE.may_load(storage)? {
+        Some((height, nonce)) if height == env.block.height => nonce + 1,
+        _ => 0,
+    };
+
+    let mut hasher = Sha256::new();
+    hasher.update(sender.as_bytes());
+    hasher.update(env.block.height.to_be_bytes());
+    hasher.update(nonce.to_be_bytes());
+
+    Ok((format!("{:x}", hasher.finalize()), nonce))
+}
+
+/// Split the whole ask asset balance of the router
+/// into the routing fee and the return amount of the receiver
+fn distribute_ask_asset(
+    deps: DepsMut<TerraQuery>,
+    env: Env,
+    info: MessageInfo,
+    asset_info: AssetInfo,
+    receiver: Addr,
+) -> Result<Response<TerraMsg>, ContractError> {
+    if env.contract.address != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let config: Config = CONFIG.load(deps.storage)?;
+    let routing_fee = config
+        .routing_fee
+        .ok_or(ContractError::RoutingFeeNotSet {})?;
+
+    // the return of the last hop, or the whole balance for a route without one
+    let amount = match HOP_RETURN.may_load(deps.storage)? {
+        Some(amount) => amount,
+        None => asset_info.query_pool(&deps.querier, deps.api, env.contract.address)?,
+    };
+    HOP_RETURN.remove(deps.storage);
+    let fee_amount = amount * routing_fee.rate;
+    let return_amount = amount.checked_sub(fee_amount)?;
+
+    let mut messages: Vec<CosmosMsg<TerraMsg>> = vec![];
+    if !fee_amount.is_zero() {
+        messages.push(
+            Asset {
+                info: asset_info.clone(),
+                amount: fee_amount,
+            }
+            .into_msg(
+                &deps.querier,
+                deps.api.addr_validate(&routing_fee.collector)?,
+            )?,
+        );
+    }
+
+    let mut response = Response::new().add_messages(messages);
+    if !return_amount.is_zero() {
+        let msg = Asset {
+            info: asset_info.clone(),
+            amount: return_amount,
+        }
+        .into_msg(&deps.querier, receiver.clone())?;
+
+        // the route receipt is the increase of the receiver balance
+        if ROUTE_INITIATOR.exists(deps.storage) {
+            let balance = asset_info.query_pool(&deps.querier, deps.api, receiver.clone())?;
+            PENDING_HOP.save(deps.storage, &(asset_info, receiver, balance))?;
+            response = response.add_submessage(SubMsg::reply_on_success(msg, FINAL_HOP_REPLY_ID));
+        } else {
+            response = response.add_message(msg);
+        }
+    }
+
+    Ok(response.add_attributes(vec![
+        ("action", "distribute_ask_asset"),
+        ("fee_amount", &fee_amount.to_string()),
+        ("return_amount", &return_amount.to_string()),
+    ]))
+}
+
+/// Transfer the return of the last hop, less the ask side routing fee,
+/// back to the origin chain of `ibc_denom`
+fn unwind_ask_asset(
+    deps: DepsMut<TerraQuery>,
+    env: Env,
+    info: MessageInfo,
+    ibc_denom: String,
+    unwind_receiver: String,
+    minimum_receive: Option<Uint128>,
+) -> Result<Response<TerraMsg>, ContractError> {
+    if env.contract.address != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let origin = read_ibc_origin(deps.storage, &ibc_denom)?;
+    let asset_info = AssetInfo::NativeToken {
+        denom: ibc_denom.clone(),
+    };
+
+    // the return of the last hop, or the whole balance for a route without one
+    let amount = match HOP_RETURN.may_load(deps.storage)? {
+        Some(amount) => amount,
+        None => asset_info.query_pool(&deps.querier, deps.api, env.contract.address.clone())?,
+    };
+    HOP_RETURN.remove(deps.storage);
+
+    let config: Config = CONFIG.load(deps.storage)?;
+    let mut messages: Vec<CosmosMsg<TerraMsg>> = vec![];
+    let mut fee_amount = Uint128::zero();
+    if let Some(routing_fee) = config.routing_fee {
+        if routing_fee.fee_side == FeeSide::Ask {
+            fee_amount = amount * routing_fee.rate;
+        }
+        if !fee_amount.is_zero() {
+            messages.push(
+                Asset {
+                    info: asset_info.clone(),
+                    amount: fee_amount,
+                }
+                .into_msg(
+                    &deps.querier,
+                    deps.api.addr_validate(&routing_fee.collector)?,
+                )?,
+            );
+        }
+    }
+
+    let transfer = Asset {
+        info: asset_info.clone(),
+        amount: amount.checked_sub(fee_amount)?,
+    }
+    .deduct_tax(&deps.querier)?;
+    if let Some(minimum_receive) = minimum_receive {
+        if transfer.amount < minimum_receive {
+            return Err(ContractError::MinimumReceiveAssertion {
+                expected: minimum_receive,
+                actual: transfer.amount,
+            });
+        }
+    }
+
+    // a timed out or failed transfer is refunded to the router, not to the initiator,
+    // so the transfer is kept until the owner settles it
+    let return_amount = transfer.amount;
+    let initiator = ROUTE_INITIATOR.load(deps.storage)?;
+    let mut unwind_id = String::new();
+    if !return_amount.is_zero() {
+        let timeout = env.block.time.plus_seconds(IBC_TRANSFER_TIMEOUT_SECONDS);
+        let id = UNWIND_NONCE.may_load(deps.storage)?.unwrap_or_default() + 1;
+        UNWIND_NONCE.save(deps.storage, &id)?;
+        PENDING_UNWINDS.save(
+            deps.storage,
+            id,
+            &PendingUnwind {
+                id,
+                initiator: initiator.to_string(),
+                unwind_receiver: unwind_receiver.clone(),
+                channel_id: origin.channel_id.clone(),
+                amount: transfer.clone(),
+                timeout: timeout.seconds(),
+            },
+        )?;
+        unwind_id = id.to_string();
+
+        messages.push(CosmosMsg::Ibc(IbcMsg::Transfer {
+            channel_id: origin.channel_id.clone(),
+            to_address: unwind_receiver.clone(),
+            amount: transfer,
+            timeout: IbcTimeout::with_timestamp(timeout),
+        }));
+    }
+    write_swap_receipt(deps, &env, asset_info, return_amount)?;
+
+    Ok(Response::new().add_messages(messages).add_attributes(vec![
+        ("action", "unwind_ask_asset"),
+        ("unwind_id", &unwind_id),
+        ("channel_id", &origin.channel_id),
+        ("origin_denom", &origin.origin_denom),
+        ("unwind_receiver", &unwind_receiver),
+        ("fee_amount", &fee_amount.to_string()),
+        ("return_amount", &return_amount.to_string()),
+    ]))
+}
+
+// Only owner can execute it
+pub fn settle_unwind(
+    deps: DepsMut<TerraQuery>,
+    info: MessageInfo,
+    id: u64,
+    refund: bool,
+) -> Result<Response<TerraMsg>, ContractError> {
+    let config: Config = CONFIG.load(deps.storage)?;
+
+    // permission check
+    if deps.api.addr_canonicalize(info.sender.as_str())? != config.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let unwind = PENDING_UNWINDS
+        .may_load(deps.storage, id)?
+        .ok_or(ContractError::UnknownUnwind { id })?;
+    PENDING_UNWINDS.remove(deps.storage, id);
+
+    let mut response = Response::new();
+    if refund {
+        response = response.add_message(
+            Asset {
+                info: AssetInfo::NativeToken {
+                    denom: unwind.amount.denom,
+                },
+                amount: unwind.amount.amount,
+            }
+            .into_msg(&deps.querier, deps.api.addr_validate(&unwind.initiator)?)?,
+        );
+    }
+
+    Ok(response.add_attributes(vec![
+        ("action", "settle_unwind"),
+        ("unwind_id", &id.to_string()),
+        ("refund", &refund.to_string()),
+    ]))
+}
+
+fn assert_account_limits(
+    deps: DepsMut<TerraQuery>,
+    env: &Env,
+    account_limits: AccountLimits,
+    sender: &Addr,
+    offer_asset_info: AssetInfo,
+    offer_amount: Uint128,
+) -> Result<(), ContractError> {
+    // Convert the offer amount into the limit asset
+    let amount = if offer_asset_info.equal(&account_limits.asset) {
+        offer_amount
+    } else {
+        let config: Config = CONFIG.load(deps.storage)?;
+        let terraswap_factory = deps.api.addr_humanize(&config.terraswap_factory)?;
+        simulate_return_amount(
+            deps.as_ref(),
+            terraswap_factory,
+            &read_dex_msg_format(deps.storage, "terraswap")?,
+            offer_amount,
+            offer_asset_info,
+            account_limits.asset,
+            &mut RouteTaxes::default(),
+        )?
+    };
+
+    let day = env.block.time.seconds() / SECONDS_PER_DAY;
+    let swapped_amount = ACCOUNT_SWAP_VOLUMES
+        .may_load(deps.storage, (sender, day))?
+        .unwrap_or_default();
+
+    let remaining = account_limits
+        .max_amount_per_day
+        .saturating_sub(swapped_amount);
+    if amount > remaining {
+        return Err(ContractError::DailySwapLimitExceeded { remaining });
+    }
+
+    ACCOUNT_SWAP_VOLUMES.save(deps.storage, (sender, day), &(swapped_amount + amount))?;
+
+    Ok(())
 }
 
 fn assert_minimum_receive(
@@ -210,40 +1210,127 @@ fn assert_minimum_receive(
     prev_balance: Uint128,
     minium_receive: Uint128,
     receiver: Addr,
-) -> StdResult<Response<TerraMsg>> {
+) -> Result<Response<TerraMsg>, ContractError> {
     let receiver_balance = asset_info.query_pool(&deps.querier, deps.api, receiver)?;
     let swap_amount = receiver_balance.checked_sub(prev_balance)?;
 
     if swap_amount < minium_receive {
-        return Err(StdError::generic_err(format!(
-            "assertion failed; minimum receive amount: {}, swap amount: {}",
-            minium_receive, swap_amount
-        )));
+        return Err(ContractError::MinimumReceiveAssertion {
+            expected: minium_receive,
+            actual: swap_amount,
+        });
     }
 
     Ok(Response::default())
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps<TerraQuery>, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
+pub fn query(deps: Deps<TerraQuery>, env: Env, msg: QueryMsg) -> Result<Binary, ContractError> {
     match msg {
-        QueryMsg::Config {} => to_binary(&query_config(deps)?),
+        QueryMsg::Config {} => Ok(to_binary(&query_config(deps)?)?),
         QueryMsg::SimulateSwapOperations {
             offer_amount,
             operations,
-        } => to_binary(&simulate_swap_operations(deps, offer_amount, operations)?),
+        } => Ok(to_binary(&simulate_swap_operations(
+            deps,
+            offer_amount,
+            operations,
+        )?)?),
+        QueryMsg::SimulateRouteTax {
+            offer_amount,
+            operations,
+        } => Ok(to_binary(&simulate_route_tax(
+            deps,
+            offer_amount,
+            operations,
+        )?)?),
         QueryMsg::ReverseSimulateSwapOperations {
             ask_amount,
             operations,
-        } => to_binary(&reverse_simulate_swap_operations(
+        } => Ok(to_binary(&reverse_simulate_swap_operations(
             deps, ask_amount, operations,
-        )?),
+        )?)?),
+        QueryMsg::Wrapper { native_denom } => Ok(to_binary(&query_wrapper(deps, native_denom)?)?),
+        QueryMsg::IbcDenom { ibc_denom } => Ok(to_binary(&query_ibc_denom(deps, ibc_denom)?)?),
+        QueryMsg::PendingUnwinds { start_after, limit } => {
+            Ok(to_binary(&PendingUnwindsResponse {
+                unwinds: read_pending_unwinds(deps.storage, start_after, limit)?,
+            })?)
+        }
+        QueryMsg::BuildSwapMessages {
+            sender,
+            operations,
+            minimum_receive,
+            to,
+            deadline,
+            offer_amount,
+            funds,
+        } => Ok(to_binary(&query_build_swap_messages(
+            deps,
+            env,
+            deps.api.addr_validate(&sender)?,
+            operations,
+            minimum_receive,
+            optional_addr_validate(deps.api, to)?,
+            deadline,
+            offer_amount,
+            funds,
+        )?)?),
+        QueryMsg::CheckSwapViability {
+            operations,
+            offer_amount,
+            belief_price,
+            max_spread,
+        } => Ok(to_binary(&query_check_swap_viability(
+            deps,
+            operations,
+            offer_amount,
+            belief_price,
+            max_spread,
+        )?)?),
+        QueryMsg::ComputeTax { asset } => Ok(to_binary(&query_compute_tax(deps, asset)?)?),
+        QueryMsg::ComputeReverseTax { asset } => {
+            Ok(to_binary(&query_compute_reverse_tax(deps, asset)?)?)
+        }
+        QueryMsg::EstimateRouteGas { operations } => {
+            Ok(to_binary(&query_estimate_route_gas(deps, operations)?)?)
+        }
+        QueryMsg::LastSwapReceipt { account } => {
+            Ok(to_binary(&query_last_swap_receipt(deps, env, account)?)?)
+        }
     }
 }
 
+/// The receipt of the last route of `account`, receipts of previous blocks are not returned
+fn query_last_swap_receipt(
+    deps: Deps<TerraQuery>,
+    env: Env,
+    account: String,
+) -> StdResult<LastSwapReceiptResponse> {
+    let account = deps.api.addr_validate(&account)?;
+    let receipt = SWAP_RECEIPTS
+        .may_load(deps.storage, &account)?
+        .filter(|receipt| receipt.block_height == env.block.height);
+
+    Ok(LastSwapReceiptResponse { receipt })
+}
+
+pub fn query_estimate_route_gas(
+    deps: Deps<TerraQuery>,
+    operations: Vec<SwapOperation>,
+) -> StdResult<EstimateRouteGasResponse> {
+    assert_operations(deps.api, &operations)?;
+
+    let config = CONFIG.load(deps.storage)?;
+    Ok(EstimateRouteGasResponse {
+        gas: config.gas_config.estimate(&operations),
+    })
+}
+
 pub fn query_config(deps: Deps<TerraQuery>) -> StdResult<ConfigResponse> {
     let state = CONFIG.load(deps.storage)?;
     let resp = ConfigResponse {
+        owner: deps.api.addr_humanize(&state.owner)?.to_string(),
         terraswap_factory: deps
             .api
             .addr_humanize(&state.terraswap_factory)?
@@ -253,28 +1340,261 @@ pub fn query_config(deps: Deps<TerraQuery>) -> StdResult<ConfigResponse> {
             .api
             .addr_humanize(&state.astroport_factory)?
             .to_string(),
+        account_limits: ACCOUNT_LIMITS.may_load(deps.storage)?,
+        routing_fee: state.routing_fee,
+        gas_config: state.gas_config,
+        dexes: KNOWN_DEXES
+            .iter()
+            .map(|dex| {
+                Ok(DexStatus {
+                    dex: dex.to_string(),
+                    enabled: is_dex_enabled(deps.storage, dex)?,
+                    msg_format: read_dex_msg_format(deps.storage, dex)?,
+                })
+            })
+            .collect::<StdResult<Vec<DexStatus>>>()?,
     };
 
     Ok(resp)
 }
 
+#[allow(clippy::too_many_arguments)]
+pub fn query_build_swap_messages(
+    deps: Deps<TerraQuery>,
+    env: Env,
+    sender: Addr,
+    operations: Vec<SwapOperation>,
+    minimum_receive: Option<Uint128>,
+    to: Option<Addr>,
+    deadline: Option<u64>,
+    offer_amount: Option<Uint128>,
+    funds: Vec<Coin>,
+) -> Result<Vec<CosmosMsg<TerraMsg>>, ContractError> {
+    let (offer_amount, exact_offer) =
+        offer_amount_of(deps, &sender, &operations, &funds, offer_amount)?;
+    let (route_id, _) = next_route_id(deps.storage, &env, &sender)?;
+
+    build_swap_messages(
+        deps,
+        &env,
+        sender,
+        offer_amount,
+        exact_offer,
+        &route_id,
+        operations,
+        minimum_receive,
+        to,
+        deadline,
+        None,
+    )
+}
+
+/// Simulates the route and applies the spread assertion of the pair contract
+/// to its return, with the offer and return amounts in their base units
+pub fn query_check_swap_viability(
+    deps: Deps<TerraQuery>,
+    operations: Vec<SwapOperation>,
+    offer_amount: Uint128,
+    belief_price: Decimal,
+    max_spread: Decimal,
+) -> Result<CheckSwapViabilityResponse, ContractError> {
+    if belief_price.is_zero() {
+        return Err(ContractError::InvalidBeliefPrice {});
+    }
+
+    let expected_return = simulate_swap_operations(deps, offer_amount, operations)?.amount;
+
+    // the pair rejects the swap when
+    // (belief_return - return_amount) / belief_return > max_spread
+    // with the ratio floored to 18 decimals
+    let belief_return: Uint256 =
+        Uint256::from(offer_amount) * (Decimal256::one() / Decimal256::from(belief_price));
+    let required_return: Uint128 = if belief_return.is_zero() {
+        Uint128::zero()
+    } else {
+        let fraction = Uint256::from(10u128.pow(Decimal256::DECIMAL_PLACES));
+        let max_spread_amount = (belief_return
+            * (Uint256::from(max_spread.atomics()) + Uint256::one())
+            - Uint256::one())
+            / fraction;
+
+        belief_return.saturating_sub(max_spread_amount).try_into()?
+    };
+
+    Ok(CheckSwapViabilityResponse {
+        viable: expected_return >= required_return,
+        expected_return,
+        required_return,
+    })
+}
+
+/// Returns the tax deducted from the asset, cw20 tokens are not taxed
+pub fn query_compute_tax(deps: Deps<TerraQuery>, asset: Asset) -> StdResult<TaxResponse> {
+    let tax_amount = match asset.info {
+        AssetInfo::NativeToken { denom } => compute_tax(&deps.querier, asset.amount, denom)?,
+        AssetInfo::Token { .. } => Uint128::zero(),
+    };
+
+    Ok(TaxResponse {
+        tax_amount,
+        pre_tax_amount: asset.amount,
+        post_tax_amount: asset.amount.checked_sub(tax_amount)?,
+    })
+}
+
+/// Returns the tax added on top of the asset, cw20 tokens are not taxed
+pub fn query_compute_reverse_tax(deps: Deps<TerraQuery>, asset: Asset) -> StdResult<TaxResponse> {
+    let tax_amount = match asset.info {
+        AssetInfo::NativeToken { denom } => {
+            compute_reverse_tax(&deps.querier, asset.amount, denom)?
+        }
+        AssetInfo::Token { .. } => Uint128::zero(),
+    };
+
+    Ok(TaxResponse {
+        tax_amount,
+        pre_tax_amount: asset.amount.checked_add(tax_amount)?,
+        post_tax_amount: asset.amount,
+    })
+}
+
+pub fn query_wrapper(deps: Deps<TerraQuery>, native_denom: String) -> StdResult<WrapperResponse> {
+    let wrapper = read_wrapper(deps.storage, &native_denom)?;
+
+    Ok(WrapperResponse {
+        native_denom,
+        wrapper_contract: wrapper.contract.to_string(),
+        wrapped_token: wrapper.wrapped_token.to_string(),
+    })
+}
+
+pub fn query_ibc_denom(
+    deps: Deps<TerraQuery>,
+    ibc_denom: String,
+) -> Result<IbcDenomResponse, ContractError> {
+    let origin = read_ibc_origin(deps.storage, &ibc_denom)?;
+
+    Ok(IbcDenomResponse {
+        ibc_denom,
+        channel_id: origin.channel_id,
+        origin_denom: origin.origin_denom,
+    })
+}
+
 fn simulate_swap_operations(
     deps: Deps<TerraQuery>,
     offer_amount: Uint128,
     operations: Vec<SwapOperation>,
-) -> StdResult<SimulateSwapOperationsResponse> {
+) -> Result<SimulateSwapOperationsResponse, ContractError> {
+    let price_guard_tripped = check_price_guards(deps, &operations)?.is_some();
+    let amount = simulate_route(deps, offer_amount, operations, &mut RouteTaxes::default())?;
+
+    Ok(SimulateSwapOperationsResponse {
+        amount,
+        price_guard_tripped,
+    })
+}
+
+/// Simulates the route like `simulate_swap_operations`, decomposing the burn tax
+/// it deducts by hop and denom
+fn simulate_route_tax(
+    deps: Deps<TerraQuery>,
+    offer_amount: Uint128,
+    operations: Vec<SwapOperation>,
+) -> Result<SimulateRouteTaxResponse, ContractError> {
+    let mut taxes = RouteTaxes::default();
+    let amount = simulate_route(deps, offer_amount, operations, &mut taxes)?;
+
+    let mut total: Vec<Coin> = vec![];
+    for tax in taxes.taxes.iter() {
+        match total.iter_mut().find(|coin| coin.denom == tax.denom) {
+            Some(coin) => coin.amount = coin.amount.checked_add(tax.tax_amount)?,
+            None => total.push(Coin {
+                denom: tax.denom.clone(),
+                amount: tax.tax_amount,
+            }),
+        }
+    }
+    total.sort_by(|a, b| a.denom.cmp(&b.denom));
+
+    Ok(SimulateRouteTaxResponse {
+        amount,
+        taxes: taxes.taxes,
+        total,
+    })
+}
+
+/// The burn tax deducted along a simulated route
+#[derive(Default)]
+struct RouteTaxes {
+    /// The hop being simulated, counted from 1 as in the hop events
+    hop_index: u32,
+    taxes: Vec<RouteTax>,
+}
+
+impl RouteTaxes {
+    /// Deducts the tax of sending `amount` of `asset_info`, recorded against the current hop
+    fn deduct(
+        &mut self,
+        querier: &QuerierWrapper<TerraQuery>,
+        asset_info: &AssetInfo,
+        amount: Uint128,
+    ) -> StdResult<Uint128> {
+        let denom = match asset_info {
+            AssetInfo::NativeToken { denom } => denom,
+            AssetInfo::Token { .. } => return Ok(amount),
+        };
+
+        let tax_amount = compute_tax(querier, amount, denom.clone())?;
+        if !tax_amount.is_zero() {
+            self.taxes.push(RouteTax {
+                hop_index: self.hop_index,
+                denom: denom.clone(),
+                tax_amount,
+            });
+        }
+
+        Ok(amount.checked_sub(tax_amount)?)
+    }
+}
+
+/// Simulates the return of the route, recording every tax deduction in `taxes`
+fn simulate_route(
+    deps: Deps<TerraQuery>,
+    offer_amount: Uint128,
+    operations: Vec<SwapOperation>,
+    taxes: &mut RouteTaxes,
+) -> Result<Uint128, ContractError> {
     let config: Config = CONFIG.load(deps.storage)?;
     let terra_querier = TerraQuerier::new(&deps.querier);
 
     let operations_len = operations.len();
     if operations_len == 0 {
-        return Err(StdError::generic_err("must provide operations"));
+        return Err(ContractError::EmptyOperations {});
     }
 
+    assert_dexes_enabled(deps.storage, &operations)?;
+
+    let target_asset_info = operations.last().unwrap().get_target_asset_info();
+    let routing_fee = config.routing_fee.clone();
+    let ask_side_fee = matches!(&routing_fee, Some(fee) if fee.fee_side == FeeSide::Ask);
+
     let mut operation_index = 0;
     let mut offer_amount = offer_amount;
+
+    // Deduct the offer side routing fee before the first hop
+    if let Some(RoutingFee {
+        rate,
+        fee_side: FeeSide::Offer,
+        ..
+    }) = routing_fee
+    {
+        offer_amount = offer_amount.checked_sub(offer_amount * rate)?;
+    }
+
     for operation in operations.into_iter() {
         operation_index += 1;
+        taxes.hop_index = operation_index as u32;
 
         offer_amount = match operation {
             SwapOperation::NativeSwap {
@@ -283,12 +1603,14 @@ fn simulate_swap_operations(
             } => {
                 // Deduct tax before query simulation
                 // because last swap is swap_send
-                if operation_index == operations_len {
-                    offer_amount = offer_amount.checked_sub(compute_tax(
+                if operation_index == operations_len && !ask_side_fee {
+                    offer_amount = taxes.deduct(
                         &deps.querier,
+                        &AssetInfo::NativeToken {
+                            denom: offer_denom.clone(),
+                        },
                         offer_amount,
-                        offer_denom.clone(),
-                    )?)?;
+                    )?;
                 }
 
                 let res: SwapResponse = terra_querier.query_swap(
@@ -309,11 +1631,12 @@ fn simulate_swap_operations(
                 simulate_return_amount(
                     deps,
                     terraswap_factory,
+                    &read_dex_msg_format(deps.storage, "terraswap")?,
                     offer_amount,
                     offer_asset_info,
                     ask_asset_info,
-                )
-                .unwrap()
+                    taxes,
+                )?
             }
             SwapOperation::Loop {
                 offer_asset_info,
@@ -323,11 +1646,12 @@ fn simulate_swap_operations(
                 simulate_return_amount(
                     deps,
                     loop_factory,
+                    &read_dex_msg_format(deps.storage, "loop")?,
                     offer_amount,
                     offer_asset_info,
                     ask_asset_info,
-                )
-                .unwrap()
+                    taxes,
+                )?
             }
             SwapOperation::Astroport {
                 offer_asset_info,
@@ -337,43 +1661,99 @@ fn simulate_swap_operations(
                 simulate_return_amount(
                     deps,
                     astroport_factory,
+                    &read_dex_msg_format(deps.storage, "astroport")?,
                     offer_amount,
                     offer_asset_info,
                     ask_asset_info,
-                )
-                .unwrap()
+                    taxes,
+                )?
+            }
+            SwapOperation::Wrap {
+                native_denom,
+                wrapped_token,
+            } => {
+                // Deduct tax of the native token sent to the wrapper
+                let offer_amount = taxes.deduct(
+                    &deps.querier,
+                    &AssetInfo::NativeToken {
+                        denom: native_denom.clone(),
+                    },
+                    offer_amount,
+                )?;
+
+                simulate_wrapper_amount(deps, &native_denom, &wrapped_token, offer_amount)?
+            }
+            SwapOperation::Unwrap {
+                wrapped_token,
+                native_denom,
+            } => {
+                let return_amount =
+                    simulate_wrapper_amount(deps, &native_denom, &wrapped_token, offer_amount)?;
+
+                // Deduct tax of the native token released by the wrapper
+                taxes.deduct(
+                    &deps.querier,
+                    &AssetInfo::NativeToken {
+                        denom: native_denom,
+                    },
+                    return_amount,
+                )?
             }
         }
     }
 
-    Ok(SimulateSwapOperationsResponse {
-        amount: offer_amount,
-    })
+    // Deduct the ask side routing fee and the tax of the transfer to the receiver
+    if let Some(RoutingFee {
+        rate,
+        fee_side: FeeSide::Ask,
+        ..
+    }) = routing_fee
+    {
+        offer_amount = offer_amount.checked_sub(offer_amount * rate)?;
+        offer_amount = taxes.deduct(&deps.querier, &target_asset_info, offer_amount)?;
+    }
+
+    Ok(offer_amount)
 }
 
 fn reverse_simulate_swap_operations(
     deps: Deps<TerraQuery>,
     ask_amount: Uint128,
     operations: Vec<SwapOperation>,
-) -> StdResult<SimulateSwapOperationsResponse> {
+) -> Result<SimulateSwapOperationsResponse, ContractError> {
     let config: Config = CONFIG.load(deps.storage)?;
 
     let operations_len = operations.len();
     if operations_len == 0 {
-        return Err(StdError::generic_err("must provide operations"));
+        return Err(ContractError::EmptyOperations {});
     }
 
+    assert_dexes_enabled(deps.storage, &operations)?;
+    let price_guard_tripped = check_price_guards(deps, &operations)?.is_some();
+
+    let target_asset_info = operations.last().unwrap().get_target_asset_info();
     let mut ask_amount = ask_amount;
+
+    // Add the ask side routing fee and the tax of the transfer to the receiver
+    if let Some(RoutingFee {
+        rate,
+        fee_side: FeeSide::Ask,
+        ..
+    }) = config.routing_fee
+    {
+        if let AssetInfo::NativeToken { denom } = target_asset_info {
+            ask_amount =
+                ask_amount.checked_add(compute_reverse_tax(&deps.querier, ask_amount, denom)?)?;
+        }
+        ask_amount = reverse_deduct_rate(ask_amount, rate)?;
+    }
+
     for operation in operations.into_iter().rev() {
         ask_amount = match operation {
             SwapOperation::NativeSwap {
                 offer_denom: _,
                 ask_denom: _,
-            } => {
-                return Err(StdError::generic_err(
-                    "reverse simulation of native_swap is not supported yet",
-                ))
-            }
+            } => return Err(ContractError::ReverseNativeSwapUnsupported {}),
             SwapOperation::TerraSwap {
                 offer_asset_info,
                 ask_asset_info,
@@ -383,11 +1763,11 @@ fn reverse_simulate_swap_operations(
                 reverse_simulate_return_amount(
                     deps,
                     terraswap_factory,
+                    &read_dex_msg_format(deps.storage, "terraswap")?,
                     ask_amount,
                     offer_asset_info,
                     ask_asset_info,
-                )
-                .unwrap()
+                )?
             }
             SwapOperation::Loop {
                 offer_asset_info,
@@ -398,11 +1778,11 @@ fn reverse_simulate_swap_operations(
                 reverse_simulate_return_amount(
                     deps,
                     loop_factory,
+                    &read_dex_msg_format(deps.storage, "loop")?,
                     ask_amount,
                     offer_asset_info,
                     ask_asset_info,
-                )
-                .unwrap()
+                )?
             }
             SwapOperation::Astroport {
                 offer_asset_info,
@@ -413,24 +1793,73 @@ fn reverse_simulate_swap_operations(
                 reverse_simulate_return_amount(
                     deps,
                     astroport_factory,
+                    &read_dex_msg_format(deps.storage, "astroport")?,
                     ask_amount,
                     offer_asset_info,
                     ask_asset_info,
-                )
-                .unwrap()
+                )?
+            }
+            SwapOperation::Wrap {
+                native_denom,
+                wrapped_token,
+            } => {
+                let offer_amount = reverse_simulate_wrapper_amount(
+                    deps,
+                    &native_denom,
+                    &wrapped_token,
+                    ask_amount,
+                )?;
+
+                // Add tax of the native token sent to the wrapper
+                offer_amount.checked_add(compute_reverse_tax(
+                    &deps.querier,
+                    offer_amount,
+                    native_denom,
+                )?)?
+            }
+            SwapOperation::Unwrap {
+                wrapped_token,
+                native_denom,
+            } => {
+                // Add tax of the native token released by the wrapper
+                let ask_amount = ask_amount.checked_add(compute_reverse_tax(
+                    &deps.querier,
+                    ask_amount,
+                    native_denom.clone(),
+                )?)?;
+
+                reverse_simulate_wrapper_amount(deps, &native_denom, &wrapped_token, ask_amount)?
             }
         }
     }
 
-    Ok(SimulateSwapOperationsResponse { amount: ask_amount })
+    // Add the offer side routing fee taken before the first hop
+    if let Some(RoutingFee {
+        rate,
+        fee_side: FeeSide::Offer,
+        ..
+    }) = config.routing_fee
+    {
+        ask_amount = reverse_deduct_rate(ask_amount, rate)?;
+    }
+
+    Ok(SimulateSwapOperationsResponse {
+        amount: ask_amount,
+        price_guard_tripped,
+    })
 }
 
+/// Simulates a pair hop with the tax of sending the offer asset to the pair
+/// and the tax of sending the return on, recorded in `taxes`, and the transfer
+/// fee the ask token takes from the return
 fn simulate_return_amount(
     deps: Deps<TerraQuery>,
     factory: Addr,
-    mut offer_amount: Uint128,
+    msg_format: &MsgFormat,
+    offer_amount: Uint128,
     offer_asset_info: AssetInfo,
     ask_asset_info: AssetInfo,
+    taxes: &mut RouteTaxes,
 ) -> StdResult<Uint128> {
     let pair_info: PairInfo = query_pair_info(
         &deps.querier,
@@ -438,208 +1867,242 @@ fn simulate_return_amount(
         &[offer_asset_info.clone(), ask_asset_info.clone()],
     )?;
 
-    // Deduct tax before querying simulation
-    if let AssetInfo::NativeToken { denom } = offer_asset_info.clone() {
-        offer_amount =
-            offer_amount.checked_sub(compute_tax(&deps.querier, offer_amount, denom)?)?;
-    }
-
-    let mut res: SimulationResponse =
-        deps.querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
-            contract_addr: pair_info.contract_addr,
-            msg: to_binary(&PairQueryMsg::Simulation {
-                offer_asset: Asset {
-                    info: offer_asset_info,
-                    amount: offer_amount,
-                },
-            })?,
-        }))?;
-
-    // Deduct tax after querying simulation
-    if let AssetInfo::NativeToken { denom } = ask_asset_info {
-        res.return_amount =
-            res.return_amount
-                .checked_sub(compute_tax(&deps.querier, res.return_amount, denom)?)?;
-    }
+    let offer_amount = taxes.deduct(&deps.querier, &offer_asset_info, offer_amount)?;
+    let res = simulate_in_format(
+        &deps.querier,
+        Addr::unchecked(&pair_info.contract_addr),
+        &Asset {
+            info: offer_asset_info,
+            amount: offer_amount,
+        },
+        &ask_asset_info,
+        msg_format,
+    )?;
 
-    Ok(res.return_amount)
+    let return_amount = deduct_transfer_fee(deps.storage, &ask_asset_info, res.return_amount)?;
+    taxes.deduct(&deps.querier, &ask_asset_info, return_amount)
 }
 
 fn reverse_simulate_return_amount(
     deps: Deps<TerraQuery>,
     factory: Addr,
+    msg_format: &MsgFormat,
     ask_amount: Uint128,
     offer_asset_info: AssetInfo,
     ask_asset_info: AssetInfo,
-) -> StdResult<Uint128> {
+) -> Result<Uint128, ContractError> {
     let pair_info: PairInfo = query_pair_info(
         &deps.querier,
         factory,
-        &[offer_asset_info.clone(), ask_asset_info.clone()],
+        &[offer_asset_info, ask_asset_info.clone()],
     )?;
 
-    let mut res = reverse_simulate(
+    // the return must still cover the ask amount after the transfer fee
+    let ask_amount = reverse_deduct_rate(
+        ask_amount,
+        read_transfer_fee_rate(deps.storage, &ask_asset_info)?,
+    )?;
+    let res = reverse_simulate_with_tax(
         &deps.querier,
-        Addr::unchecked(pair_info.contract_addr),
+        &pair_info,
+        msg_format,
         &Asset {
-            amount: ask_amount,
             info: ask_asset_info,
+            amount: ask_amount,
         },
+        true,
+        true,
     )?;
 
-    // Add tax after querying simulation
-    if let AssetInfo::NativeToken { denom } = offer_asset_info {
-        res.offer_amount = res.offer_amount.checked_add(compute_reverse_tax(
-            &deps.querier,
-            res.offer_amount,
-            denom,
-        )?)?;
+    Ok(res.offer_amount)
+}
+
+/// Wrapping and unwrapping are 1:1 exchanges minus the fee of the wrapper
+fn simulate_wrapper_amount(
+    deps: Deps<TerraQuery>,
+    native_denom: &str,
+    wrapped_token: &str,
+    offer_amount: Uint128,
+) -> Result<Uint128, ContractError> {
+    let wrapper_contract = read_wrapper_contract(deps.storage, native_denom, wrapped_token)?;
+    let fee_rate = query_wrapper_fee_rate(&deps.querier, wrapper_contract)?;
+
+    Ok(offer_amount.checked_sub(offer_amount * fee_rate)?)
+}
+
+fn reverse_simulate_wrapper_amount(
+    deps: Deps<TerraQuery>,
+    native_denom: &str,
+    wrapped_token: &str,
+    ask_amount: Uint128,
+) -> Result<Uint128, ContractError> {
+    let wrapper_contract = read_wrapper_contract(deps.storage, native_denom, wrapped_token)?;
+    let fee_rate = query_wrapper_fee_rate(&deps.querier, wrapper_contract)?;
+    if fee_rate >= Decimal::one() {
+        return Err(ContractError::InvalidWrapperFeeRate {});
     }
 
-    Ok(res.offer_amount)
+    reverse_deduct_rate(ask_amount, fee_rate)
 }
 
-fn assert_operations(operations: &[SwapOperation]) -> StdResult<()> {
-    let mut ask_asset_map: HashMap<String, bool> = HashMap::new();
-    for operation in operations.iter() {
-        let (offer_asset, ask_asset) = match operation {
-            SwapOperation::NativeSwap {
-                offer_denom,
-                ask_denom,
-            } => (
-                AssetInfo::NativeToken {
-                    denom: offer_denom.clone(),
-                },
-                AssetInfo::NativeToken {
-                    denom: ask_denom.clone(),
-                },
-            ),
-            SwapOperation::TerraSwap {
-                offer_asset_info,
-                ask_asset_info,
-            }
-            | SwapOperation::Loop {
-                offer_asset_info,
-                ask_asset_info,
-            }
-            | SwapOperation::Astroport {
-                offer_asset_info,
-                ask_asset_info,
-            } => (offer_asset_info.clone(), ask_asset_info.clone()),
+/// Returns the amount which still covers `amount` after deducting `amount * rate`,
+/// floor(amount / (1 - rate)) always covers the deduction
+fn reverse_deduct_rate(amount: Uint128, rate: Decimal) -> Result<Uint128, ContractError> {
+    if rate >= Decimal::one() {
+        return Err(ContractError::InvalidRate {});
+    }
+
+    Ok(amount.multiply_ratio(Decimal::one().atomics(), (Decimal::one() - rate).atomics()))
+}
+
+/// Returns the error of the first pool of the route deviating from the reference
+/// of its price guard, checked before the route moves the prices
+fn check_price_guards(
+    deps: Deps<TerraQuery>,
+    operations: &[SwapOperation],
+) -> Result<Option<ContractError>, ContractError> {
+    let config: Config = CONFIG.load(deps.storage)?;
+    for operation in operations {
+        let factory = match operation {
+            SwapOperation::TerraSwap { .. } => &config.terraswap_factory,
+            SwapOperation::Loop { .. } => &config.loop_factory,
+            SwapOperation::Astroport { .. } => &config.astroport_factory,
+            _ => continue,
         };
+        let offer_asset_info = operation.get_offer_asset_info();
+        let ask_asset_info = operation.get_target_asset_info();
 
-        ask_asset_map.remove(&offer_asset.to_string());
-        ask_asset_map.insert(ask_asset.to_string(), true);
-    }
+        for (base, quote) in [
+            (&offer_asset_info, &ask_asset_info),
+            (&ask_asset_info, &offer_asset_info),
+        ] {
+            let guard = match PRICE_GUARDS
+                .may_load(deps.storage, (&base.to_string(), &quote.to_string()))?
+            {
+                Some(guard) => guard,
+                None => continue,
+            };
+
+            let pair_info = query_pair_info(
+                &deps.querier,
+                deps.api.addr_humanize(factory)?,
+                &[offer_asset_info.clone(), ask_asset_info.clone()],
+            )?;
+            let price = query_spot_price(deps, &pair_info.contract_addr, base, quote)?;
+            let reference = match guard.reference {
+                ReferenceSource::Pair { contract_addr } => {
+                    query_spot_price(deps, &contract_addr, base, quote)?
+                }
+                ReferenceSource::Constant { price } => price,
+            };
 
-    if ask_asset_map.keys().len() != 1 {
-        return Err(StdError::generic_err(
-            "invalid operations; multiple output token",
-        ));
+            let deviation = Decimal::checked_from_ratio(
+                price.abs_diff(reference).atomics(),
+                reference.atomics(),
+            )?;
+            if deviation > guard.max_deviation {
+                return Ok(Some(ContractError::PriceGuardTripped {
+                    offer_asset: base.to_string(),
+                    ask_asset: quote.to_string(),
+                    deviation,
+                    max_deviation: guard.max_deviation,
+                }));
+            }
+        }
     }
 
-    Ok(())
+    Ok(None)
 }
 
-#[test]
-fn test_invalid_operations() {
-    // empty error
-    assert!(assert_operations(&[]).is_err());
+/// The spot price of `quote` per `base` in the reserves of the pair
+fn query_spot_price(
+    deps: Deps<TerraQuery>,
+    pair_contract: &str,
+    base: &AssetInfo,
+    quote: &AssetInfo,
+) -> Result<Decimal, ContractError> {
+    let pool = query_pool(&deps.querier, Addr::unchecked(pair_contract))?;
+    let reserve = |asset_info: &AssetInfo| {
+        pool.assets
+            .iter()
+            .find(|asset| asset.info.equal(asset_info))
+            .map(|asset| asset.amount)
+            .ok_or_else(|| ContractError::AssetNotInPair {
+                asset: asset_info.to_string(),
+                pair: pair_contract.to_string(),
+            })
+    };
 
-    // uluna output
-    assert!(assert_operations(&vec![
-        SwapOperation::NativeSwap {
-            offer_denom: "uusd".to_string(),
-            ask_denom: "uluna".to_string(),
-        },
-        SwapOperation::TerraSwap {
-            offer_asset_info: AssetInfo::NativeToken {
-                denom: "ukrw".to_string(),
-            },
-            ask_asset_info: AssetInfo::Token {
-                contract_addr: "asset0001".to_string(),
-            },
-        },
-        SwapOperation::TerraSwap {
-            offer_asset_info: AssetInfo::Token {
-                contract_addr: "asset0001".to_string(),
-            },
-            ask_asset_info: AssetInfo::NativeToken {
-                denom: "uluna".to_string(),
-            },
+    let base_reserve = reserve(base)?;
+    if base_reserve.is_zero() {
+        return Err(ContractError::NoLiquidity {
+            pair: pair_contract.to_string(),
+        });
+    }
+
+    Ok(Decimal::checked_from_ratio(reserve(quote)?, base_reserve)?)
+}
+
+fn assert_dexes_enabled(
+    storage: &dyn Storage,
+    operations: &[SwapOperation],
+) -> Result<(), ContractError> {
+    for dex in operations
+        .iter()
+        .map(|op| op.dex_name())
+        .filter(|dex| KNOWN_DEXES.contains(dex))
+    {
+        if !is_dex_enabled(storage, dex)? {
+            return Err(ContractError::DexDisabled {
+                dex: dex.to_string(),
+            });
         }
-    ])
-    .is_ok());
+    }
 
-    // asset0002 output
-    assert!(assert_operations(&vec![
-        SwapOperation::NativeSwap {
-            offer_denom: "uusd".to_string(),
-            ask_denom: "uluna".to_string(),
-        },
-        SwapOperation::TerraSwap {
-            offer_asset_info: AssetInfo::NativeToken {
-                denom: "ukrw".to_string(),
-            },
-            ask_asset_info: AssetInfo::Token {
-                contract_addr: "asset0001".to_string(),
-            },
-        },
-        SwapOperation::TerraSwap {
-            offer_asset_info: AssetInfo::Token {
-                contract_addr: "asset0001".to_string(),
-            },
-            ask_asset_info: AssetInfo::NativeToken {
-                denom: "uluna".to_string(),
-            },
-        },
-        SwapOperation::TerraSwap {
-            offer_asset_info: AssetInfo::NativeToken {
-                denom: "uluna".to_string(),
-            },
-            ask_asset_info: AssetInfo::Token {
-                contract_addr: "asset0002".to_string(),
-            },
-        },
-    ])
-    .is_ok());
-
-    // multiple output token types error
-    assert!(assert_operations(&vec![
-        SwapOperation::NativeSwap {
-            offer_denom: "uusd".to_string(),
-            ask_denom: "ukrw".to_string(),
-        },
-        SwapOperation::TerraSwap {
-            offer_asset_info: AssetInfo::NativeToken {
-                denom: "ukrw".to_string(),
-            },
-            ask_asset_info: AssetInfo::Token {
-                contract_addr: "asset0001".to_string(),
-            },
-        },
-        SwapOperation::TerraSwap {
-            offer_asset_info: AssetInfo::Token {
-                contract_addr: "asset0001".to_string(),
-            },
-            ask_asset_info: AssetInfo::NativeToken {
-                denom: "uaud".to_string(),
-            },
-        },
-        SwapOperation::TerraSwap {
-            offer_asset_info: AssetInfo::NativeToken {
-                denom: "uluna".to_string(),
-            },
-            ask_asset_info: AssetInfo::Token {
-                contract_addr: "asset0002".to_string(),
-            },
-        },
-    ])
-    .is_err());
+    Ok(())
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn reply(
+    deps: DepsMut<TerraQuery>,
+    env: Env,
+    msg: Reply,
+) -> Result<Response<TerraMsg>, ContractError> {
+    match msg.id {
+        HOP_REPLY_ID => record_hop_return(deps),
+        FINAL_HOP_REPLY_ID => record_swap_receipt(deps, env),
+        _ => Err(ContractError::InvalidReply {}),
+    }
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> StdResult<Response> {
+pub fn migrate(
+    mut deps: DepsMut<TerraQuery>,
+    env: Env,
+    _msg: MigrateMsg,
+) -> Result<Response<TerraMsg>, ContractError> {
+    let prev_version = get_contract_version(deps.storage)?;
+    if prev_version.contract != CONTRACT_NAME {
+        return Err(ContractError::InvalidContract {});
+    }
+
+    // dispatch to the upgrade of the stored version,
+    // each upgrade rewrites the state into the next version
+    match prev_version.version.as_str() {
+        CONTRACT_VERSION => return Ok(Response::default()),
+        "0.1.0" => migrate_from_v0_1_0(deps.branch(), &env)?,
+        version => {
+            return Err(ContractError::UnsupportedMigration {
+                version: version.to_string(),
+            })
+        }
+    }
+
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
-    Ok(Response::default())
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "migrate"),
+        ("from_version", prev_version.version.as_str()),
+        ("to_version", CONTRACT_VERSION),
+    ]))
 }
@@ -0,0 +1,153 @@
+use classic_terraswap::util::DeadlineError;
+use cosmwasm_std::{
+    CheckedFromRatioError, CheckedMultiplyFractionError, ConversionOverflowError, Decimal,
+    OverflowError, StdError, Uint128,
+};
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum ContractError {
+    #[error("{0}")]
+    Std(#[from] StdError),
+
+    #[error("{0}")]
+    Deadline(#[from] DeadlineError),
+
+    #[error("unauthorized")]
+    Unauthorized {},
+
+    #[error("address {factory} does not respond to factory Config query")]
+    InvalidFactory { factory: String },
+
+    #[error("address {factory} does not respond to factory RouterBootstrap query")]
+    FactoryValidationFailed { factory: String },
+
+    #[error("offer amount must not be zero")]
+    ZeroOfferAmount {},
+
+    #[error("invalid reply id")]
+    InvalidReply {},
+
+    #[error("must provide operations")]
+    EmptyOperations {},
+
+    #[error("unknown dex {dex}")]
+    UnknownDex { dex: String },
+
+    #[error("dex {dex} is disabled")]
+    DexDisabled { dex: String },
+
+    #[error("invalid routing fee rate")]
+    InvalidRoutingFeeRate {},
+
+    #[error("routing fee is not set")]
+    RoutingFeeNotSet {},
+
+    #[error("insufficient offer balance; offer amount: {offer_amount}, balance: {balance}")]
+    InsufficientOfferBalance {
+        offer_amount: Uint128,
+        balance: Uint128,
+    },
+
+    #[error("offer amount {offer_amount} does not match the attached amount {sent}")]
+    OfferAmountMismatch {
+        offer_amount: Uint128,
+        sent: Uint128,
+    },
+
+    #[error("daily swap limit exceeded; remaining allowance: {remaining}")]
+    DailySwapLimitExceeded { remaining: Uint128 },
+
+    #[error("assertion failed; minimum receive amount: {expected}, swap amount: {actual}")]
+    MinimumReceiveAssertion { expected: Uint128, actual: Uint128 },
+
+    #[error("minimum_receive and minimum_receive_price can't be both given")]
+    MinimumReceiveConflict {},
+
+    #[error("{denom} is not a registered ibc denom")]
+    UnregisteredIbcDenom { denom: String },
+
+    #[error("invalid ibc denom: {denom}")]
+    InvalidIbcDenom { denom: String },
+
+    #[error("channel_id and origin_denom must be given")]
+    InvalidIbcOrigin {},
+
+    #[error("no pending unwind {id}")]
+    UnknownUnwind { id: u64 },
+
+    #[error("auto_unwind needs an unwind_receiver and can't be given with to")]
+    InvalidUnwind {},
+
+    #[error("invalid belief price")]
+    InvalidBeliefPrice {},
+
+    #[error("reverse simulation of native_swap is not supported yet")]
+    ReverseNativeSwapUnsupported {},
+
+    #[error("invalid wrapper fee rate")]
+    InvalidWrapperFeeRate {},
+
+    #[error("{wrapped_token} is not the wrapped token registered for {native_denom}")]
+    WrapperMismatch {
+        native_denom: String,
+        wrapped_token: String,
+    },
+
+    #[error("invalid rate")]
+    InvalidRate {},
+
+    #[error("price guard needs distinct assets, a positive max deviation and reference price, and a reference pair of the assets")]
+    InvalidPriceGuard {},
+
+    #[error("price of {ask_asset} per {offer_asset} deviates {deviation} from the reference, more than {max_deviation}")]
+    PriceGuardTripped {
+        offer_asset: String,
+        ask_asset: String,
+        deviation: Decimal,
+        max_deviation: Decimal,
+    },
+
+    #[error("{asset} is not an asset of the pair {pair}")]
+    AssetNotInPair { asset: String, pair: String },
+
+    #[error("pair {pair} has no liquidity")]
+    NoLiquidity { pair: String },
+
+    #[error("token transfer fee must be less than 10000 bps")]
+    InvalidTransferFeeBps {},
+
+    #[error("contract admin is required to set the owner")]
+    AdminRequired {},
+
+    #[error("invalid contract")]
+    InvalidContract {},
+
+    #[error("unsupported migration from version {version}")]
+    UnsupportedMigration { version: String },
+}
+
+/// Overflows keep the message they had as a `StdError`
+impl From<OverflowError> for ContractError {
+    fn from(err: OverflowError) -> Self {
+        ContractError::Std(err.into())
+    }
+}
+
+impl From<ConversionOverflowError> for ContractError {
+    fn from(err: ConversionOverflowError) -> Self {
+        ContractError::Std(err.into())
+    }
+}
+
+impl From<CheckedFromRatioError> for ContractError {
+    fn from(err: CheckedFromRatioError) -> Self {
+        ContractError::Std(StdError::generic_err(err.to_string()))
+    }
+}
+
+impl From<CheckedMultiplyFractionError> for ContractError {
+    fn from(err: CheckedMultiplyFractionError) -> Self {
+        ContractError::Std(StdError::generic_err(err.to_string()))
+    }
+}
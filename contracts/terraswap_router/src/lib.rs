@@ -1,6 +1,8 @@
 pub mod contract;
 pub mod state;
 
+mod error;
+mod migration;
 mod operations;
 mod querier;
 
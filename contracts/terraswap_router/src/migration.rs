@@ -0,0 +1,50 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use classic_bindings::TerraQuery;
+use classic_terraswap::router::GasConfig;
+use cosmwasm_std::{CanonicalAddr, DepsMut, Env};
+use cw_storage_plus::Item;
+
+use crate::error::ContractError;
+use crate::state::{Config, CONFIG, DEX_ENABLED, KNOWN_DEXES};
+
+/// Config of 0.1.0 which has no owner
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct ConfigV0_1_0 {
+    pub terraswap_factory: CanonicalAddr,
+    pub loop_factory: CanonicalAddr,
+    pub astroport_factory: CanonicalAddr,
+}
+
+const CONFIG_V0_1_0: Item<ConfigV0_1_0> = Item::new("config");
+
+/// 0.1.0 has no owner, the contract admin becomes the owner
+/// and every known dex is enabled
+pub fn migrate_from_v0_1_0(deps: DepsMut<TerraQuery>, env: &Env) -> Result<(), ContractError> {
+    let legacy_config = CONFIG_V0_1_0.load(deps.storage)?;
+
+    let admin = deps
+        .querier
+        .query_wasm_contract_info(env.contract.address.to_string())?
+        .admin
+        .ok_or(ContractError::AdminRequired {})?;
+
+    CONFIG.save(
+        deps.storage,
+        &Config {
+            owner: deps.api.addr_canonicalize(&admin)?,
+            terraswap_factory: legacy_config.terraswap_factory,
+            loop_factory: legacy_config.loop_factory,
+            astroport_factory: legacy_config.astroport_factory,
+            routing_fee: None,
+            gas_config: GasConfig::default(),
+        },
+    )?;
+
+    for dex in KNOWN_DEXES {
+        DEX_ENABLED.save(deps.storage, dex, &true)?;
+    }
+
+    Ok(())
+}
@@ -1,24 +1,38 @@
 use std::str::FromStr;
 
 use cosmwasm_std::{
-    to_binary, Addr, Coin, CosmosMsg, Decimal, Deps, DepsMut, Env, MessageInfo, Response, StdError,
-    StdResult, WasmMsg,
+    to_binary, Addr, CanonicalAddr, Coin, CosmosMsg, Decimal, Deps, DepsMut, Env, Event,
+    MessageInfo, Response, SubMsg, Uint128, WasmMsg,
 };
 
-use crate::querier::compute_tax;
-use crate::state::{Config, CONFIG};
+use crate::error::ContractError;
+use crate::state::{
+    read_dex_msg_format, read_wrapper_contract, Config, CONFIG, HOP_RETURN, PENDING_HOP,
+    ROUTE_INITIATOR, SWAP_RECEIPTS,
+};
 
 use classic_bindings::{TerraMsg, TerraQuery};
 
 use classic_terraswap::asset::{Asset, AssetInfo, PairInfo};
 use classic_terraswap::pair::ExecuteMsg as PairExecuteMsg;
-use classic_terraswap::querier::{query_balance, query_pair_info, query_token_balance};
-use classic_terraswap::router::SwapOperation;
+use classic_terraswap::querier::{compute_tax, query_pair_info};
+use classic_terraswap::router::{
+    AstroportCw20HookMsg, AstroportPairExecuteMsg, MsgFormat, SwapOperation, SwapReceipt,
+    WrapperCw20HookMsg, WrapperExecuteMsg,
+};
 use classic_terraswap::util::assert_deadline;
 use cw20::Cw20ExecuteMsg;
 
+/// The reply id of a hop keeping its return in the router
+pub const HOP_REPLY_ID: u64 = 1;
+/// The reply id of the last hop of a route, sending its return to the receiver
+pub const FINAL_HOP_REPLY_ID: u64 = 2;
+
 /// Execute swap operation
-/// swap all offer asset to ask asset
+/// swap `offer_amount` or, when it is not given, the return of the previous hop to ask asset.
+/// A hop keeping its return in the router replies with the return for the next hop,
+/// the last hop of a route with the receipt of the route
+#[allow(clippy::too_many_arguments)]
 pub fn execute_swap_operation(
     deps: DepsMut<TerraQuery>,
     env: Env,
@@ -26,20 +40,47 @@ pub fn execute_swap_operation(
     operation: SwapOperation,
     to: Option<String>,
     deadline: Option<u64>,
-) -> StdResult<Response<TerraMsg>> {
+    offer_amount: Option<Uint128>,
+    route_hop: Option<(String, u32)>,
+) -> Result<Response<TerraMsg>, ContractError> {
     if env.contract.address != info.sender {
-        return Err(StdError::generic_err("unauthorized"));
+        return Err(ContractError::Unauthorized {});
     }
 
     assert_deadline(env.block.time.seconds(), deadline)?;
 
+    let hop_event = route_hop.map(|(route_id, hop_index)| {
+        Event::new("terraswap_router_hop").add_attributes(vec![
+            ("route_id", route_id),
+            ("hop_index", hop_index.to_string()),
+            ("offer_asset", operation.get_offer_asset_info().to_string()),
+            ("ask_asset", operation.get_target_asset_info().to_string()),
+        ])
+    });
+
+    let dex = operation.dex_name();
+    let ask_asset_info = operation.get_target_asset_info();
+    let keeps_return = to.is_none();
+    let receiver = to.clone();
+    let offer_amount = match offer_amount {
+        Some(offer_amount) => Some(offer_amount),
+        None => HOP_RETURN.may_load(deps.storage)?,
+    };
+    HOP_RETURN.remove(deps.storage);
+
     let messages: Vec<CosmosMsg<TerraMsg>> = match operation {
         SwapOperation::NativeSwap {
             offer_denom,
             ask_denom,
         } => {
-            let amount =
-                query_balance(&deps.querier, env.contract.address, offer_denom.to_string())?;
+            let amount = offer_amount_or_balance(
+                deps.as_ref(),
+                &env,
+                &AssetInfo::NativeToken {
+                    denom: offer_denom.clone(),
+                },
+                offer_amount,
+            )?;
             if let Some(to) = to {
                 // if the operation is last, and requires send
                 // deduct tax from the offer_coin
@@ -67,34 +108,16 @@ pub fn execute_swap_operation(
             offer_asset_info,
             ask_asset_info,
         } => {
-            let config: Config = CONFIG.load(deps.as_ref().storage)?;
-            let terraswap_factory = deps.api.addr_humanize(&config.terraswap_factory)?;
-            let pair_info: PairInfo = query_pair_info(
-                &deps.querier,
-                terraswap_factory,
-                &[offer_asset_info.clone(), ask_asset_info],
-            )?;
-
-            let amount = match offer_asset_info.clone() {
-                AssetInfo::NativeToken { denom } => {
-                    query_balance(&deps.querier, env.contract.address, denom)?
-                }
-                AssetInfo::Token { contract_addr } => query_token_balance(
-                    &deps.querier,
-                    deps.api.addr_validate(contract_addr.as_str())?,
-                    env.contract.address,
-                )?,
-            };
-            let offer_asset: Asset = Asset {
-                info: offer_asset_info,
-                amount,
-            };
-
-            vec![asset_into_swap_msg(
+            let config: Config = CONFIG.load(deps.storage)?;
+            vec![pair_swap_msg(
                 deps.as_ref(),
-                Addr::unchecked(pair_info.contract_addr),
-                offer_asset,
+                &env,
+                dex,
+                config.terraswap_factory,
                 None,
+                offer_asset_info,
+                ask_asset_info,
+                offer_amount,
                 to,
             )?]
         }
@@ -102,34 +125,16 @@ pub fn execute_swap_operation(
             offer_asset_info,
             ask_asset_info,
         } => {
-            let config: Config = CONFIG.load(deps.as_ref().storage)?;
-            let loop_factory = deps.api.addr_humanize(&config.loop_factory)?;
-            let pair_info: PairInfo = query_pair_info(
-                &deps.querier,
-                loop_factory,
-                &[offer_asset_info.clone(), ask_asset_info],
-            )?;
-
-            let amount = match offer_asset_info.clone() {
-                AssetInfo::NativeToken { denom } => {
-                    query_balance(&deps.querier, env.contract.address, denom)?
-                }
-                AssetInfo::Token { contract_addr } => query_token_balance(
-                    &deps.querier,
-                    deps.api.addr_validate(contract_addr.as_str())?,
-                    env.contract.address,
-                )?,
-            };
-            let offer_asset: Asset = Asset {
-                info: offer_asset_info,
-                amount,
-            };
-
-            vec![asset_into_swap_msg(
+            let config: Config = CONFIG.load(deps.storage)?;
+            vec![pair_swap_msg(
                 deps.as_ref(),
-                Addr::unchecked(pair_info.contract_addr),
-                offer_asset,
+                &env,
+                dex,
+                config.loop_factory,
                 None,
+                offer_asset_info,
+                ask_asset_info,
+                offer_amount,
                 to,
             )?]
         }
@@ -137,49 +142,224 @@ pub fn execute_swap_operation(
             offer_asset_info,
             ask_asset_info,
         } => {
-            let config: Config = CONFIG.load(deps.as_ref().storage)?;
-            let astroport_factory = deps.api.addr_humanize(&config.astroport_factory)?;
-            let pair_info: PairInfo = query_pair_info(
-                &deps.querier,
-                astroport_factory,
-                &[offer_asset_info.clone(), ask_asset_info],
-            )?;
-
-            let amount = match offer_asset_info.clone() {
-                AssetInfo::NativeToken { denom } => {
-                    query_balance(&deps.querier, env.contract.address, denom)?
-                }
-                AssetInfo::Token { contract_addr } => query_token_balance(
-                    &deps.querier,
-                    deps.api.addr_validate(contract_addr.as_str())?,
-                    env.contract.address,
-                )?,
-            };
-            let offer_asset: Asset = Asset {
-                info: offer_asset_info,
-                amount,
-            };
-
-            vec![asset_into_swap_msg(
+            let config: Config = CONFIG.load(deps.storage)?;
+            vec![pair_swap_msg(
                 deps.as_ref(),
-                Addr::unchecked(pair_info.contract_addr),
-                offer_asset,
+                &env,
+                dex,
+                config.astroport_factory,
                 Some(Decimal::from_str("0.5")?),
+                offer_asset_info,
+                ask_asset_info,
+                offer_amount,
                 to,
             )?]
         }
+        SwapOperation::Wrap {
+            native_denom,
+            wrapped_token,
+        } => {
+            let wrapper_contract =
+                read_wrapper_contract(deps.storage, &native_denom, &wrapped_token)?;
+            let amount = offer_amount_or_balance(
+                deps.as_ref(),
+                &env,
+                &AssetInfo::NativeToken {
+                    denom: native_denom.clone(),
+                },
+                offer_amount,
+            )?;
+
+            // deduct tax from the native token sent to the wrapper
+            let amount =
+                amount.checked_sub(compute_tax(&deps.querier, amount, native_denom.clone())?)?;
+
+            vec![CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: wrapper_contract.to_string(),
+                funds: vec![Coin {
+                    denom: native_denom,
+                    amount,
+                }],
+                msg: to_binary(&WrapperExecuteMsg::Deposit { recipient: to })?,
+            })]
+        }
+        SwapOperation::Unwrap {
+            wrapped_token,
+            native_denom,
+        } => {
+            let wrapper_contract =
+                read_wrapper_contract(deps.storage, &native_denom, &wrapped_token)?;
+            let amount = offer_amount_or_balance(
+                deps.as_ref(),
+                &env,
+                &AssetInfo::Token {
+                    contract_addr: wrapped_token.clone(),
+                },
+                offer_amount,
+            )?;
+
+            vec![CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: wrapped_token,
+                funds: vec![],
+                msg: to_binary(&Cw20ExecuteMsg::Send {
+                    contract: wrapper_contract.to_string(),
+                    amount,
+                    msg: to_binary(&WrapperCw20HookMsg::Withdraw { recipient: to })?,
+                })?,
+            })]
+        }
+    };
+
+    let reply_id = if keeps_return {
+        Some(HOP_REPLY_ID)
+    } else if ROUTE_INITIATOR.exists(deps.storage) {
+        Some(FINAL_HOP_REPLY_ID)
+    } else {
+        None
     };
 
-    Ok(Response::new().add_messages(messages))
+    let mut response = Response::new();
+    if let Some(reply_id) = reply_id {
+        let receiver = match receiver {
+            Some(receiver) => deps.api.addr_validate(&receiver)?,
+            None => env.contract.address,
+        };
+        let balance = ask_asset_info.query_pool(&deps.querier, deps.api, receiver.clone())?;
+        PENDING_HOP.save(deps.storage, &(ask_asset_info, receiver, balance))?;
+        // the reply of the last message follows the whole hop
+        let last = messages.len().saturating_sub(1);
+        response = response.add_submessages(messages.into_iter().enumerate().map(|(i, msg)| {
+            if i == last {
+                SubMsg::reply_on_success(msg, reply_id)
+            } else {
+                SubMsg::new(msg)
+            }
+        }));
+    } else {
+        response = response.add_messages(messages);
+    }
+    if let Some(hop_event) = hop_event {
+        response = response.add_event(hop_event);
+    }
+
+    Ok(response)
+}
+
+/// The swap message of a hop through the pair of `factory`
+#[allow(clippy::too_many_arguments)]
+fn pair_swap_msg(
+    deps: Deps<TerraQuery>,
+    env: &Env,
+    dex: &str,
+    factory: CanonicalAddr,
+    max_spread: Option<Decimal>,
+    offer_asset_info: AssetInfo,
+    ask_asset_info: AssetInfo,
+    offer_amount: Option<Uint128>,
+    to: Option<String>,
+) -> Result<CosmosMsg<TerraMsg>, ContractError> {
+    let pair_info: PairInfo = query_pair_info(
+        &deps.querier,
+        deps.api.addr_humanize(&factory)?,
+        &[offer_asset_info.clone(), ask_asset_info.clone()],
+    )?;
+
+    let amount = offer_amount_or_balance(deps, env, &offer_asset_info, offer_amount)?;
+    let offer_asset: Asset = Asset {
+        info: offer_asset_info,
+        amount,
+    };
+
+    asset_into_swap_msg(
+        deps,
+        Addr::unchecked(pair_info.contract_addr),
+        &read_dex_msg_format(deps.storage, dex)?,
+        offer_asset,
+        ask_asset_info,
+        max_spread,
+        to,
+    )
+}
+
+/// The ask asset and the return of the replying hop, the increase of the receiver balance
+fn pending_hop_return(deps: DepsMut<TerraQuery>) -> Result<(AssetInfo, Uint128), ContractError> {
+    let (ask_asset_info, receiver, prev_balance) = PENDING_HOP.load(deps.storage)?;
+    PENDING_HOP.remove(deps.storage);
+
+    let balance = ask_asset_info.query_pool(&deps.querier, deps.api, receiver)?;
+    Ok((ask_asset_info, balance.checked_sub(prev_balance)?))
+}
+
+/// Records the return of the replying hop for the next hop
+pub fn record_hop_return(
+    mut deps: DepsMut<TerraQuery>,
+) -> Result<Response<TerraMsg>, ContractError> {
+    let (_, return_amount) = pending_hop_return(deps.branch())?;
+    HOP_RETURN.save(deps.storage, &return_amount)?;
+
+    Ok(Response::new().add_attribute("hop_return", return_amount.to_string()))
+}
+
+/// Records the return of the last hop as the receipt of the route
+pub fn record_swap_receipt(
+    mut deps: DepsMut<TerraQuery>,
+    env: Env,
+) -> Result<Response<TerraMsg>, ContractError> {
+    let (target_asset, amount) = pending_hop_return(deps.branch())?;
+    write_swap_receipt(deps, &env, target_asset, amount)?;
+
+    Ok(Response::new().add_attribute("swap_receipt", amount.to_string()))
 }
 
+/// Writes the receipt of the route being executed for its initiator
+pub fn write_swap_receipt(
+    deps: DepsMut<TerraQuery>,
+    env: &Env,
+    target_asset: AssetInfo,
+    amount: Uint128,
+) -> Result<(), ContractError> {
+    let initiator = ROUTE_INITIATOR.load(deps.storage)?;
+    ROUTE_INITIATOR.remove(deps.storage);
+
+    SWAP_RECEIPTS.save(
+        deps.storage,
+        &initiator,
+        &SwapReceipt {
+            target_asset,
+            amount,
+            block_height: env.block.height,
+        },
+    )?;
+    Ok(())
+}
+
+/// Returns the explicit offer amount or the whole router balance of the offer asset
+fn offer_amount_or_balance(
+    deps: Deps<TerraQuery>,
+    env: &Env,
+    offer_asset_info: &AssetInfo,
+    offer_amount: Option<Uint128>,
+) -> Result<Uint128, ContractError> {
+    match offer_amount {
+        Some(offer_amount) => Ok(offer_amount),
+        None => Ok(offer_asset_info.query_pool(
+            &deps.querier,
+            deps.api,
+            env.contract.address.clone(),
+        )?),
+    }
+}
+
+/// Builds the swap of `offer_asset` for `ask_asset_info` in the message format of the pair
 pub fn asset_into_swap_msg(
     deps: Deps<TerraQuery>,
     pair_contract: Addr,
+    msg_format: &MsgFormat,
     offer_asset: Asset,
+    ask_asset_info: AssetInfo,
     max_spread: Option<Decimal>,
     to: Option<String>,
-) -> StdResult<CosmosMsg<TerraMsg>> {
+) -> Result<CosmosMsg<TerraMsg>, ContractError> {
     match offer_asset.info.clone() {
         AssetInfo::NativeToken { denom } => {
             // deduct tax first
@@ -188,20 +368,33 @@ pub fn asset_into_swap_msg(
                 offer_asset.amount,
                 denom.clone(),
             )?)?;
+            let offer_asset = Asset {
+                amount,
+                ..offer_asset
+            };
 
             Ok(CosmosMsg::Wasm(WasmMsg::Execute {
                 contract_addr: pair_contract.to_string(),
                 funds: vec![Coin { denom, amount }],
-                msg: to_binary(&PairExecuteMsg::Swap {
-                    offer_asset: Asset {
-                        amount,
-                        ..offer_asset
-                    },
-                    belief_price: None,
-                    max_spread,
-                    to,
-                    deadline: None,
-                })?,
+                msg: match msg_format {
+                    MsgFormat::Classic => to_binary(&PairExecuteMsg::Swap {
+                        offer_asset,
+                        belief_price: None,
+                        belief_price_direction: None,
+                        max_spread,
+                        to,
+                        deadline: None,
+                        to_msg: None,
+                        referral: None,
+                    })?,
+                    MsgFormat::AstroportV2 => to_binary(&AstroportPairExecuteMsg::Swap {
+                        offer_asset,
+                        ask_asset_info: Some(ask_asset_info),
+                        belief_price: None,
+                        max_spread,
+                        to,
+                    })?,
+                },
             }))
         }
         AssetInfo::Token { contract_addr } => Ok(CosmosMsg::Wasm(WasmMsg::Execute {
@@ -210,13 +403,24 @@ pub fn asset_into_swap_msg(
             msg: to_binary(&Cw20ExecuteMsg::Send {
                 contract: pair_contract.to_string(),
                 amount: offer_asset.amount,
-                msg: to_binary(&PairExecuteMsg::Swap {
-                    offer_asset,
-                    belief_price: None,
-                    max_spread,
-                    to,
-                    deadline: None,
-                })?,
+                msg: match msg_format {
+                    MsgFormat::Classic => to_binary(&PairExecuteMsg::Swap {
+                        offer_asset,
+                        belief_price: None,
+                        belief_price_direction: None,
+                        max_spread,
+                        to,
+                        deadline: None,
+                        to_msg: None,
+                        referral: None,
+                    })?,
+                    MsgFormat::AstroportV2 => to_binary(&AstroportCw20HookMsg::Swap {
+                        ask_asset_info: Some(ask_asset_info),
+                        belief_price: None,
+                        max_spread,
+                        to,
+                    })?,
+                },
             })?,
         })),
     }
@@ -1,41 +1,16 @@
-use cosmwasm_std::{Decimal, Decimal256, QuerierWrapper, StdResult, Uint128, Uint256};
-use std::ops::Mul;
+use cosmwasm_std::{to_binary, Addr, Decimal, QuerierWrapper, QueryRequest, StdResult, WasmQuery};
 
-use classic_bindings::{TerraQuerier, TerraQuery};
+use classic_bindings::TerraQuery;
+use classic_terraswap::router::{WrapperFeeResponse, WrapperQueryMsg};
 
-static DECIMAL_FRACTION: Uint128 = Uint128::new(1_000_000_000_000_000_000u128);
-
-pub fn compute_tax(
+pub fn query_wrapper_fee_rate(
     querier: &QuerierWrapper<TerraQuery>,
-    amount: Uint128,
-    denom: String,
-) -> StdResult<Uint128> {
-    let terra_querier = TerraQuerier::new(querier);
-    let tax_rate: Decimal = (terra_querier.query_tax_rate()?).rate;
-    let tax_cap: Uint128 = (terra_querier.query_tax_cap(denom)?).cap;
-    Ok(std::cmp::min(
-        amount.checked_sub(amount.multiply_ratio(
-            DECIMAL_FRACTION,
-            DECIMAL_FRACTION * tax_rate + DECIMAL_FRACTION,
-        ))?,
-        tax_cap,
-    ))
-}
-
-pub fn compute_reverse_tax(
-    querier: &QuerierWrapper<TerraQuery>,
-    amount: Uint128,
-    denom: String,
-) -> StdResult<Uint128> {
-    let terra_querier = TerraQuerier::new(querier);
-    let tax_rate: Decimal = (terra_querier.query_tax_rate()?).rate;
-    let tax_cap: Uint128 = (terra_querier.query_tax_cap(denom)?).cap;
-
-    let tax: Uint128 = (std::cmp::min(
-        Uint256::from(amount).mul(Decimal256::one() + Decimal256::from(tax_rate)),
-        Uint256::from(amount + tax_cap),
-    ) - Uint256::from(amount))
-    .try_into()?;
+    wrapper_contract: Addr,
+) -> StdResult<Decimal> {
+    let res: WrapperFeeResponse = querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
+        contract_addr: wrapper_contract.to_string(),
+        msg: to_binary(&WrapperQueryMsg::Fee {})?,
+    }))?;
 
-    Ok(tax)
+    Ok(res.fee_rate)
 }
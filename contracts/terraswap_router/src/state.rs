@@ -1,14 +1,162 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use cosmwasm_std::CanonicalAddr;
-use cw_storage_plus::Item;
+use classic_terraswap::asset::AssetInfo;
+use classic_terraswap::router::{
+    AccountLimits, GasConfig, GuardConfig, IbcOrigin, MsgFormat, PendingUnwind, RoutingFee,
+    SwapReceipt,
+};
+use cosmwasm_std::{Addr, CanonicalAddr, Decimal, StdError, StdResult, Storage, Uint128};
+use cw_storage_plus::{Bound, Item, Map};
+
+use crate::error::ContractError;
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 pub struct Config {
+    pub owner: CanonicalAddr,
     pub terraswap_factory: CanonicalAddr,
     pub loop_factory: CanonicalAddr,
     pub astroport_factory: CanonicalAddr,
+    pub routing_fee: Option<RoutingFee>,
+    #[serde(default)]
+    pub gas_config: GasConfig,
 }
 
 pub const CONFIG: Item<Config> = Item::new("config");
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct Wrapper {
+    pub contract: Addr,
+    pub wrapped_token: Addr,
+}
+
+// key : native denom / value: wrapper contract and the token it mints
+pub const WRAPPERS: Map<&str, Wrapper> = Map::new("wrappers");
+
+pub fn read_wrapper(storage: &dyn Storage, native_denom: &str) -> StdResult<Wrapper> {
+    WRAPPERS
+        .may_load(storage, native_denom)?
+        .ok_or_else(|| StdError::generic_err(format!("no wrapper registered for {}", native_denom)))
+}
+
+/// Returns the wrapper contract of `native_denom`, only when it mints `wrapped_token`
+pub fn read_wrapper_contract(
+    storage: &dyn Storage,
+    native_denom: &str,
+    wrapped_token: &str,
+) -> Result<Addr, ContractError> {
+    let wrapper = read_wrapper(storage, native_denom)?;
+    if wrapper.wrapped_token != wrapped_token {
+        return Err(ContractError::WrapperMismatch {
+            native_denom: native_denom.to_string(),
+            wrapped_token: wrapped_token.to_string(),
+        });
+    }
+
+    Ok(wrapper.contract)
+}
+
+// key : ibc denom / value: channel and denom of the origin chain
+pub const IBC_DENOMS: Map<&str, IbcOrigin> = Map::new("ibc_denoms");
+
+pub fn read_ibc_origin(storage: &dyn Storage, ibc_denom: &str) -> Result<IbcOrigin, ContractError> {
+    IBC_DENOMS
+        .may_load(storage, ibc_denom)?
+        .ok_or_else(|| ContractError::UnregisteredIbcDenom {
+            denom: ibc_denom.to_string(),
+        })
+}
+
+// the id of the last unwind transfer
+pub const UNWIND_NONCE: Item<u64> = Item::new("unwind_nonce");
+
+// key : unwind id / value: unwind transfer not settled yet
+pub const PENDING_UNWINDS: Map<u64, PendingUnwind> = Map::new("pending_unwinds");
+
+const MAX_LIMIT: u32 = 30;
+const DEFAULT_LIMIT: u32 = 10;
+
+pub fn read_pending_unwinds(
+    storage: &dyn Storage,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<Vec<PendingUnwind>> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    PENDING_UNWINDS
+        .range(
+            storage,
+            start_after.map(Bound::exclusive),
+            None,
+            cosmwasm_std::Order::Ascending,
+        )
+        .take(limit)
+        .map(|item| item.map(|(_, unwind)| unwind))
+        .collect()
+}
+
+// key : cw20 token / value: fee the token takes from every transfer, in bps
+pub const TOKEN_TRANSFER_FEES: Map<&Addr, u16> = Map::new("token_transfer_fees");
+
+/// The transfer fee registered for `asset_info`, natives and unregistered tokens have none
+pub fn read_transfer_fee_rate(storage: &dyn Storage, asset_info: &AssetInfo) -> StdResult<Decimal> {
+    let bps = match asset_info {
+        AssetInfo::Token { contract_addr } => TOKEN_TRANSFER_FEES
+            .may_load(storage, &Addr::unchecked(contract_addr))?
+            .unwrap_or_default(),
+        AssetInfo::NativeToken { .. } => 0,
+    };
+
+    Ok(Decimal::from_ratio(bps, 10_000u16))
+}
+
+/// Deducts the transfer fee of `asset_info` from `amount`, as the recipient of a transfer receives
+pub fn deduct_transfer_fee(
+    storage: &dyn Storage,
+    asset_info: &AssetInfo,
+    amount: Uint128,
+) -> StdResult<Uint128> {
+    let rate = read_transfer_fee_rate(storage, asset_info)?;
+    Ok(amount.checked_sub(amount * rate)?)
+}
+
+// key : (offer asset, ask asset) / value: guard of the pools of the assets
+pub const PRICE_GUARDS: Map<(&str, &str), GuardConfig> = Map::new("price_guards");
+
+// the daily swap cap of each account, absent while the cap is disabled
+// so a route reads a single missing key
+pub const ACCOUNT_LIMITS: Item<AccountLimits> = Item::new("account_limits");
+
+// key : (account, day) / value: offer amount swapped in the limit asset
+pub const ACCOUNT_SWAP_VOLUMES: Map<(&Addr, u64), Uint128> = Map::new("account_swap_volumes");
+
+// (block height, nonce of the last route executed in the block)
+pub const ROUTE_NONCE: Item<(u64, u64)> = Item::new("route_nonce");
+
+// (ask asset, receiver, balance of the receiver) of the hop waiting for its reply
+pub const PENDING_HOP: Item<(AssetInfo, Addr, Uint128)> = Item::new("pending_hop");
+
+// the return of the last hop, offered by the next hop of the route
+pub const HOP_RETURN: Item<Uint128> = Item::new("hop_return");
+
+// the initiator of the route being executed, who gets the receipt of the route
+pub const ROUTE_INITIATOR: Item<Addr> = Item::new("route_initiator");
+
+// key : initiator / value: the output of the last route of the initiator,
+// overwritten by the next route of the initiator
+pub const SWAP_RECEIPTS: Map<&Addr, SwapReceipt> = Map::new("swap_receipts");
+
+pub const KNOWN_DEXES: [&str; 3] = ["terraswap", "loop", "astroport"];
+
+// key : dex / value: whether routing through the dex is enabled
+pub const DEX_ENABLED: Map<&str, bool> = Map::new("dex_enabled");
+
+pub fn is_dex_enabled(storage: &dyn Storage, dex: &str) -> StdResult<bool> {
+    Ok(DEX_ENABLED.may_load(storage, dex)?.unwrap_or(true))
+}
+
+// key : dex / value: format of the swap messages and simulation queries of its pairs
+pub const DEX_MSG_FORMATS: Map<&str, MsgFormat> = Map::new("dex_msg_formats");
+
+pub fn read_dex_msg_format(storage: &dyn Storage, dex: &str) -> StdResult<MsgFormat> {
+    Ok(DEX_MSG_FORMATS.may_load(storage, dex)?.unwrap_or_default())
+}
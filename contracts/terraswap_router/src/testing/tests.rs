@@ -1,19 +1,33 @@
 use cosmwasm_std::testing::{mock_env, mock_info, MOCK_CONTRACT_ADDR};
 use cosmwasm_std::{
-    coin, from_binary, to_binary, Coin, CosmosMsg, Decimal, StdError, SubMsg, Uint128, WasmMsg,
+    attr, coin, from_binary, to_binary, Addr, Api, BankMsg, Binary, CanonicalAddr, Coin, CosmosMsg,
+    Decimal, Deps, DepsMut, Env, Event, IbcMsg, IbcTimeout, Reply, ReplyOn, Response, StdError,
+    SubMsg, SubMsgResponse, SubMsgResult, Timestamp, Uint128, WasmMsg,
 };
 
-use crate::contract::{execute, instantiate, query};
+use crate::contract::{execute, instantiate, migrate, query, reply};
+use crate::error::ContractError;
+use crate::operations::{FINAL_HOP_REPLY_ID, HOP_REPLY_ID};
+use crate::state::ROUTE_INITIATOR;
+use classic_bindings::TerraQuery;
 use classic_terraswap::mock_querier::mock_dependencies;
+use cw2::{get_contract_version, set_contract_version};
+use cw_storage_plus::Item;
 
 use classic_bindings::TerraMsg;
 use classic_terraswap::asset::{Asset, AssetInfo, PairInfo};
 use classic_terraswap::pair::ExecuteMsg as PairExecuteMsg;
 use classic_terraswap::router::{
-    ConfigResponse, Cw20HookMsg, ExecuteMsg, InstantiateMsg, QueryMsg,
-    SimulateSwapOperationsResponse, SwapOperation,
+    AccountLimits, CheckSwapViabilityResponse, ConfigResponse, Cw20HookMsg, DexStatus,
+    EstimateRouteGasResponse, ExecuteMsg, FeeSide, GasConfig, GuardConfig, IbcDenomResponse,
+    IbcOrigin, InstantiateMsg, LastSwapReceiptResponse, MigrateMsg, MsgFormat, PendingUnwind,
+    PendingUnwindsResponse, QueryMsg, ReferenceSource, RouteTax, RoutingFee,
+    SimulateRouteTaxResponse, SimulateSwapOperationsResponse, SwapOperation, SwapReceipt,
+    TaxResponse, WrapperCw20HookMsg, WrapperExecuteMsg, WrapperInfo, WrapperResponse,
 };
+use classic_terraswap::util::DeadlineError;
 use cw20::{Cw20ExecuteMsg, Cw20ReceiveMsg};
+use std::str::FromStr;
 
 #[test]
 fn proper_initialization() {
@@ -23,6 +37,8 @@ fn proper_initialization() {
         terraswap_factory: "terraswapfactory".to_string(),
         loop_factory: "loopfactory".to_string(),
         astroport_factory: "astroportfactory".to_string(),
+        unchecked: false,
+        validate_factories: false,
     };
 
     let info = mock_info("addr0000", &[]);
@@ -33,9 +49,156 @@ fn proper_initialization() {
     // it worked, let's query the state
     let config: ConfigResponse =
         from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
+    assert_eq!("addr0000", config.owner.as_str());
     assert_eq!("terraswapfactory", config.terraswap_factory.as_str());
     assert_eq!("loopfactory", config.loop_factory.as_str());
     assert_eq!("astroportfactory", config.astroport_factory.as_str());
+    assert_eq!(config.gas_config, GasConfig::default());
+}
+
+#[test]
+fn instantiate_with_invalid_factory() {
+    let mut deps = mock_dependencies(&[]);
+    deps.querier.with_unresponsive_contracts(&["loopfactory"]);
+
+    let msg = InstantiateMsg {
+        terraswap_factory: "terraswapfactory".to_string(),
+        loop_factory: "loopfactory".to_string(),
+        astroport_factory: "astroportfactory".to_string(),
+        unchecked: false,
+        validate_factories: false,
+    };
+
+    let info = mock_info("addr0000", &[]);
+    let res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg.clone());
+    match res {
+        Err(err) => assert_eq!(
+            err,
+            ContractError::InvalidFactory {
+                factory: "loopfactory".to_string()
+            }
+        ),
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+
+    // the check is skipped for factories deployed in the same transaction
+    let msg = InstantiateMsg {
+        unchecked: true,
+        ..msg
+    };
+    let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+}
+
+#[test]
+fn instantiate_with_factory_validation() {
+    let mut deps = mock_dependencies(&[]);
+    deps.querier
+        .with_factories(&["terraswapfactory", "loopfactory"]);
+
+    // astroportfactory answers the Config query but is not a factory
+    let msg = InstantiateMsg {
+        terraswap_factory: "terraswapfactory".to_string(),
+        loop_factory: "loopfactory".to_string(),
+        astroport_factory: "astroportfactory".to_string(),
+        unchecked: false,
+        validate_factories: true,
+    };
+    let info = mock_info("addr0000", &[]);
+    let res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg.clone());
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::FactoryValidationFailed {
+            factory: "astroportfactory".to_string()
+        }
+    );
+
+    // the validation is not skipped by unchecked
+    deps.querier
+        .with_unresponsive_contracts(&["astroportfactory"]);
+    let res = instantiate(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        InstantiateMsg {
+            unchecked: true,
+            ..msg.clone()
+        },
+    );
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::FactoryValidationFailed {
+            factory: "astroportfactory".to_string()
+        }
+    );
+
+    deps.querier.with_unresponsive_contracts(&[]);
+    deps.querier
+        .with_factories(&["terraswapfactory", "loopfactory", "astroportfactory"]);
+    instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+}
+
+#[test]
+fn update_config() {
+    let mut deps = mock_dependencies(&[]);
+    default_instantiate(deps.as_mut());
+    deps.querier
+        .with_unresponsive_contracts(&["newloopfactory"]);
+
+    let msg = |unchecked: bool, validate_factories: bool| ExecuteMsg::UpdateConfig {
+        terraswap_factory: None,
+        loop_factory: Some("newloopfactory".to_string()),
+        astroport_factory: None,
+        unchecked,
+        validate_factories,
+    };
+
+    // only owner can update the config
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0001", &[]),
+        msg(false, false),
+    );
+    assert_eq!(res.unwrap_err(), ContractError::Unauthorized {});
+
+    // the factories are checked like at instantiate
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        msg(false, false),
+    );
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::InvalidFactory {
+            factory: "newloopfactory".to_string()
+        }
+    );
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        msg(true, true),
+    );
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::FactoryValidationFailed {
+            factory: "newloopfactory".to_string()
+        }
+    );
+
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        msg(true, false),
+    )
+    .unwrap();
+    let config: ConfigResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
+    assert_eq!("terraswapfactory", config.terraswap_factory.as_str());
+    assert_eq!("newloopfactory", config.loop_factory.as_str());
+    assert_eq!("astroportfactory", config.astroport_factory.as_str());
 }
 
 #[test]
@@ -50,6 +213,8 @@ fn execute_swap_operations() {
         terraswap_factory: "terraswapfactory".to_string(),
         loop_factory: "loopfactory".to_string(),
         astroport_factory: "astroportfactory".to_string(),
+        unchecked: false,
+        validate_factories: false,
     };
 
     let info = mock_info("addr0000", &[]);
@@ -60,14 +225,18 @@ fn execute_swap_operations() {
     let msg = ExecuteMsg::ExecuteSwapOperations {
         operations: vec![],
         minimum_receive: None,
+        minimum_receive_price: None,
         to: None,
         deadline: None,
+        offer_amount: None,
+        auto_unwind: false,
+        unwind_receiver: None,
     };
 
     let info = mock_info("addr0000", &[]);
     let res = execute(deps.as_mut(), mock_env(), info, msg);
     match res {
-        Err(StdError::GenericErr { msg, .. }) => assert_eq!(msg, "must provide operations"),
+        Err(err) => assert_eq!(err, ContractError::EmptyOperations {}),
         _ => panic!("DO NOT ENTER HERE"),
     }
 
@@ -103,12 +272,17 @@ fn execute_swap_operations() {
             },
         ],
         minimum_receive: Some(Uint128::from(1000000u128)),
+        minimum_receive_price: None,
         to: None,
         deadline: None,
+        offer_amount: None,
+        auto_unwind: false,
+        unwind_receiver: None,
     };
 
-    let info = mock_info("addr0000", &[]);
+    let info = mock_info("addr0000", &[coin(1000000, "uusd")]);
     let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    let route_id = route_id_of(&res);
     assert_eq!(
         res.messages,
         vec![
@@ -122,6 +296,9 @@ fn execute_swap_operations() {
                     },
                     to: None,
                     deadline: None,
+                    offer_amount: Some(Uint128::from(1000000u128)),
+                    route_id: Some(route_id.clone()),
+                    hop_index: Some(1),
                 })
                 .unwrap(),
             })),
@@ -139,6 +316,9 @@ fn execute_swap_operations() {
                     },
                     to: None,
                     deadline: None,
+                    offer_amount: None,
+                    route_id: Some(route_id.clone()),
+                    hop_index: Some(2),
                 })
                 .unwrap(),
             })),
@@ -156,6 +336,9 @@ fn execute_swap_operations() {
                     },
                     to: None,
                     deadline: None,
+                    offer_amount: None,
+                    route_id: Some(route_id.clone()),
+                    hop_index: Some(3),
                 })
                 .unwrap(),
             })),
@@ -173,6 +356,9 @@ fn execute_swap_operations() {
                     },
                     to: Some("addr0000".to_string()),
                     deadline: None,
+                    offer_amount: None,
+                    route_id: Some(route_id.clone()),
+                    hop_index: Some(4),
                 })
                 .unwrap(),
             })),
@@ -227,14 +413,18 @@ fn execute_swap_operations() {
                 },
             ],
             minimum_receive: None,
+            minimum_receive_price: None,
             to: Some("addr0002".to_string()),
             deadline: None,
+            auto_unwind: false,
+            unwind_receiver: None,
         })
         .unwrap(),
     });
 
     let info = mock_info("asset0000", &[]);
     let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    let route_id = route_id_of(&res);
     assert_eq!(
         res.messages,
         vec![
@@ -248,6 +438,9 @@ fn execute_swap_operations() {
                     },
                     to: None,
                     deadline: None,
+                    offer_amount: Some(Uint128::from(1000000u128)),
+                    route_id: Some(route_id.clone()),
+                    hop_index: Some(1),
                 })
                 .unwrap(),
             })),
@@ -265,6 +458,9 @@ fn execute_swap_operations() {
                     },
                     to: None,
                     deadline: None,
+                    offer_amount: None,
+                    route_id: Some(route_id.clone()),
+                    hop_index: Some(2),
                 })
                 .unwrap(),
             })),
@@ -282,6 +478,9 @@ fn execute_swap_operations() {
                     },
                     to: None,
                     deadline: None,
+                    offer_amount: None,
+                    route_id: Some(route_id.clone()),
+                    hop_index: Some(3),
                 })
                 .unwrap(),
             })),
@@ -299,6 +498,9 @@ fn execute_swap_operations() {
                     },
                     to: Some("addr0002".to_string()),
                     deadline: None,
+                    offer_amount: None,
+                    route_id: Some(route_id.clone()),
+                    hop_index: Some(4),
                 })
                 .unwrap(),
             }))
@@ -306,6 +508,45 @@ fn execute_swap_operations() {
     );
 }
 
+#[test]
+fn execute_swap_operations_with_deadline() {
+    let mut deps = mock_dependencies(&[]);
+    default_instantiate(deps.as_mut());
+
+    let env = mock_env();
+    let now = env.block.time.seconds();
+    let msg = |deadline: u64| ExecuteMsg::ExecuteSwapOperations {
+        operations: vec![SwapOperation::TerraSwap {
+            offer_asset_info: AssetInfo::NativeToken {
+                denom: "ukrw".to_string(),
+            },
+            ask_asset_info: AssetInfo::Token {
+                contract_addr: "asset0001".to_string(),
+            },
+        }],
+        minimum_receive: None,
+        minimum_receive_price: None,
+        to: None,
+        deadline: Some(deadline),
+        offer_amount: None,
+        auto_unwind: false,
+        unwind_receiver: None,
+    };
+
+    // a deadline at the current block time is still met
+    let info = mock_info("addr0000", &[coin(1000000, "ukrw")]);
+    execute(deps.as_mut(), env.clone(), info.clone(), msg(now)).unwrap();
+
+    let err = execute(deps.as_mut(), env, info, msg(now - 1)).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::Deadline(DeadlineError {
+            deadline: now - 1,
+            block_time: now
+        })
+    );
+}
+
 #[test]
 fn execute_swap_operation() {
     let mut deps = mock_dependencies(&[]);
@@ -313,6 +554,8 @@ fn execute_swap_operation() {
         terraswap_factory: "terraswapfactory".to_string(),
         loop_factory: "loopfactory".to_string(),
         astroport_factory: "astroportfactory".to_string(),
+        unchecked: false,
+        validate_factories: false,
     };
 
     let info = mock_info("addr0000", &[]);
@@ -335,6 +578,7 @@ fn execute_swap_operation() {
                 contract_addr: "pair0000".to_string(),
                 liquidity_token: "liquidity0000".to_string(),
                 asset_decimals: [6u8, 6u8],
+                created_at: None,
             },
         )],
         &[("uusd".to_string(), 6u8)],
@@ -359,11 +603,14 @@ fn execute_swap_operation() {
         },
         to: None,
         deadline: None,
+        offer_amount: None,
+        route_id: None,
+        hop_index: None,
     };
     let info = mock_info("addr0000", &[]);
     let res = execute(deps.as_mut(), mock_env(), info, msg.clone());
     match res {
-        Err(StdError::GenericErr { msg, .. }) => assert_eq!(msg, "unauthorized"),
+        Err(err) => assert_eq!(err, ContractError::Unauthorized {}),
         _ => panic!("DO NOT ENTER HERE"),
     }
 
@@ -371,13 +618,16 @@ fn execute_swap_operation() {
     let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
     assert_eq!(
         res.messages,
-        vec![SubMsg::new(TerraMsg::create_swap_msg(
-            Coin {
-                denom: "uusd".to_string(),
-                amount: Uint128::from(1000000u128),
-            },
-            "uluna".to_string()
-        ))],
+        vec![SubMsg::reply_on_success(
+            TerraMsg::create_swap_msg(
+                Coin {
+                    denom: "uusd".to_string(),
+                    amount: Uint128::from(1000000u128),
+                },
+                "uluna".to_string()
+            ),
+            HOP_REPLY_ID
+        )],
     );
 
     // optional to address
@@ -389,6 +639,9 @@ fn execute_swap_operation() {
         },
         to: Some("addr0000".to_string()),
         deadline: None,
+        offer_amount: None,
+        route_id: None,
+        hop_index: None,
     };
     let info = mock_info(MOCK_CONTRACT_ADDR, &[]);
     let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
@@ -418,6 +671,7 @@ fn execute_swap_operation() {
                 contract_addr: "pair0000".to_string(),
                 liquidity_token: "liquidity0000".to_string(),
                 asset_decimals: [6u8, 6u8],
+                created_at: None,
             },
         )],
         &[("uusd".to_string(), 6u8)],
@@ -438,6 +692,9 @@ fn execute_swap_operation() {
         },
         to: Some("addr0000".to_string()),
         deadline: None,
+        offer_amount: None,
+        route_id: None,
+        hop_index: None,
     };
 
     let info = mock_info(MOCK_CONTRACT_ADDR, &[]);
@@ -458,9 +715,12 @@ fn execute_swap_operation() {
                         amount: Uint128::from(1000000u128),
                     },
                     belief_price: None,
+                    belief_price_direction: None,
                     max_spread: None,
                     to: Some("addr0000".to_string()),
                     deadline: None,
+                    to_msg: None,
+                    referral: None,
                 })
                 .unwrap()
             })
@@ -477,6 +737,8 @@ fn query_buy_with_routes() {
         terraswap_factory: "terraswapfactory".to_string(),
         loop_factory: "loopfactory".to_string(),
         astroport_factory: "astroportfactory".to_string(),
+        unchecked: false,
+        validate_factories: false,
     };
 
     let info = mock_info("addr0000", &[]);
@@ -535,6 +797,7 @@ fn query_buy_with_routes() {
                     contract_addr: "pair0000".to_string(),
                     liquidity_token: "liquidity0000".to_string(),
                     asset_decimals: [6u8, 6u8],
+                    created_at: None,
                 },
             ),
             (
@@ -551,6 +814,7 @@ fn query_buy_with_routes() {
                     contract_addr: "pair0001".to_string(),
                     liquidity_token: "liquidity0001".to_string(),
                     asset_decimals: [6u8, 6u8],
+                    created_at: None,
                 },
             ),
         ],
@@ -562,7 +826,8 @@ fn query_buy_with_routes() {
     assert_eq!(
         res,
         SimulateSwapOperationsResponse {
-            amount: Uint128::from(952380u128)
+            amount: Uint128::from(952380u128),
+            price_guard_tripped: false,
         }
     );
 }
@@ -575,6 +840,8 @@ fn query_reverse_routes_with_from_native() {
         terraswap_factory: "terraswapfactory".to_string(),
         loop_factory: "loopfactory".to_string(),
         astroport_factory: "astrofactory".to_string(),
+        unchecked: false,
+        validate_factories: false,
     };
 
     let target_amount = 952380u128;
@@ -635,6 +902,7 @@ fn query_reverse_routes_with_from_native() {
                         },
                     ],
                     asset_decimals: [8u8, 6u8],
+                    created_at: None,
                 },
             ),
             (
@@ -651,6 +919,7 @@ fn query_reverse_routes_with_from_native() {
                         },
                     ],
                     asset_decimals: [8u8, 6u8],
+                    created_at: None,
                 },
             ),
         ],
@@ -664,10 +933,15 @@ fn query_reverse_routes_with_from_native() {
         res,
         SimulateSwapOperationsResponse {
             amount: Uint128::from(999999u128),
+            price_guard_tripped: false,
         }
     );
 
     let offer_amount = res.amount;
+    deps.querier.with_token_balances(&[(
+        &"asset0000".to_string(),
+        &[(&MOCK_CONTRACT_ADDR.to_string(), &Uint128::zero())],
+    )]);
 
     let msg = ExecuteMsg::ExecuteSwapOperation {
         operation: SwapOperation::TerraSwap {
@@ -680,11 +954,14 @@ fn query_reverse_routes_with_from_native() {
         },
         to: None,
         deadline: None,
+        offer_amount: None,
+        route_id: None,
+        hop_index: None,
     };
     let info = mock_info("addr0", &[coin(offer_amount.u128(), "ukrw")]);
     let res = execute(deps.as_mut(), mock_env(), info, msg.clone());
     match res {
-        Err(StdError::GenericErr { msg, .. }) => assert_eq!(msg, "unauthorized"),
+        Err(err) => assert_eq!(err, ContractError::Unauthorized {}),
         _ => panic!("DO NOT ENTER HERE"),
     }
 
@@ -693,23 +970,29 @@ fn query_reverse_routes_with_from_native() {
 
     assert_eq!(
         res.messages,
-        vec![SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
-            contract_addr: "pair0000".to_string(),
-            funds: vec![coin(target_amount, "ukrw")],
-            msg: to_binary(&PairExecuteMsg::Swap {
-                offer_asset: Asset {
-                    info: AssetInfo::NativeToken {
-                        denom: "ukrw".to_string(),
+        vec![SubMsg::reply_on_success(
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "pair0000".to_string(),
+                funds: vec![coin(target_amount, "ukrw")],
+                msg: to_binary(&PairExecuteMsg::Swap {
+                    offer_asset: Asset {
+                        info: AssetInfo::NativeToken {
+                            denom: "ukrw".to_string(),
+                        },
+                        amount: Uint128::from(target_amount),
                     },
-                    amount: Uint128::from(target_amount),
-                },
-                belief_price: None,
-                max_spread: None,
-                to: None,
-                deadline: None,
-            })
-            .unwrap(),
-        })),],
+                    belief_price: None,
+                    belief_price_direction: None,
+                    max_spread: None,
+                    to: None,
+                    deadline: None,
+                    to_msg: None,
+                    referral: None,
+                })
+                .unwrap(),
+            }),
+            HOP_REPLY_ID
+        )],
     );
 }
 
@@ -721,6 +1004,8 @@ fn query_reverse_routes_with_to_native() {
         terraswap_factory: "terraswapfactory".to_string(),
         loop_factory: "loopfactory".to_string(),
         astroport_factory: "astrofactory".to_string(),
+        unchecked: false,
+        validate_factories: false,
     };
 
     let target_amount = 1000000u128;
@@ -778,6 +1063,7 @@ fn query_reverse_routes_with_to_native() {
                         },
                     ],
                     asset_decimals: [8u8, 6u8],
+                    created_at: None,
                 },
             ),
             (
@@ -794,6 +1080,7 @@ fn query_reverse_routes_with_to_native() {
                         },
                     ],
                     asset_decimals: [8u8, 6u8],
+                    created_at: None,
                 },
             ),
         ],
@@ -803,10 +1090,12 @@ fn query_reverse_routes_with_to_native() {
     let res: SimulateSwapOperationsResponse =
         from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
 
+    // target amount + 50000 (tax)
     assert_eq!(
         res,
         SimulateSwapOperationsResponse {
-            amount: Uint128::from(target_amount),
+            amount: Uint128::from(1050000u128),
+            price_guard_tripped: false,
         }
     );
 
@@ -825,14 +1114,18 @@ fn query_reverse_routes_with_to_native() {
                 },
             }],
             minimum_receive: None,
+            minimum_receive_price: None,
             to: None,
             deadline: None,
+            auto_unwind: false,
+            unwind_receiver: None,
         })
         .unwrap(),
     });
     let info = mock_info("addr0", &[]);
     let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
 
+    let route_id = route_id_of(&res);
     assert_eq!(
         res.messages,
         vec![SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
@@ -849,6 +1142,9 @@ fn query_reverse_routes_with_to_native() {
                 },
                 to: Some("addr0".to_string()),
                 deadline: None,
+                offer_amount: Some(offer_amount),
+                route_id: Some(route_id.clone()),
+                hop_index: Some(1),
             })
             .unwrap(),
         })),],
@@ -865,6 +1161,9 @@ fn query_reverse_routes_with_to_native() {
         },
         to: None,
         deadline: None,
+        offer_amount: None,
+        route_id: None,
+        hop_index: None,
     };
 
     let info = mock_info(MOCK_CONTRACT_ADDR, &[]);
@@ -872,28 +1171,34 @@ fn query_reverse_routes_with_to_native() {
 
     assert_eq!(
         res.messages,
-        vec![SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
-            contract_addr: "asset0000".to_string(),
-            funds: vec![],
-            msg: to_binary(&Cw20ExecuteMsg::Send {
-                contract: "pair0000".to_string(),
-                amount: Uint128::from(target_amount),
-                msg: to_binary(&PairExecuteMsg::Swap {
-                    offer_asset: Asset {
-                        info: AssetInfo::Token {
-                            contract_addr: "asset0000".to_string(),
+        vec![SubMsg::reply_on_success(
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "asset0000".to_string(),
+                funds: vec![],
+                msg: to_binary(&Cw20ExecuteMsg::Send {
+                    contract: "pair0000".to_string(),
+                    amount: Uint128::from(target_amount),
+                    msg: to_binary(&PairExecuteMsg::Swap {
+                        offer_asset: Asset {
+                            info: AssetInfo::Token {
+                                contract_addr: "asset0000".to_string(),
+                            },
+                            amount: Uint128::from(target_amount),
                         },
-                        amount: Uint128::from(target_amount),
-                    },
-                    belief_price: None,
-                    max_spread: None,
-                    to: None,
-                    deadline: None,
+                        belief_price: None,
+                        belief_price_direction: None,
+                        max_spread: None,
+                        to: None,
+                        deadline: None,
+                        to_msg: None,
+                        referral: None,
+                    })
+                    .unwrap(),
                 })
                 .unwrap(),
-            })
-            .unwrap(),
-        }))],
+            }),
+            HOP_REPLY_ID
+        )],
     );
 }
 
@@ -932,9 +1237,12 @@ fn assert_minimum_receive_native_token() {
     };
     let res = execute(deps.as_mut(), mock_env(), info, msg);
     match res {
-        Err(StdError::GenericErr { msg, .. }) => assert_eq!(
-            msg,
-            "assertion failed; minimum receive amount: 1000001, swap amount: 1000000"
+        Err(err) => assert_eq!(
+            err,
+            ContractError::MinimumReceiveAssertion {
+                expected: Uint128::from(1000001u128),
+                actual: Uint128::from(1000000u128)
+            }
         ),
         _ => panic!("DO NOT ENTER HERE"),
     }
@@ -971,10 +1279,3381 @@ fn assert_minimum_receive_token() {
     };
     let res = execute(deps.as_mut(), mock_env(), info, msg);
     match res {
-        Err(StdError::GenericErr { msg, .. }) => assert_eq!(
-            msg,
-            "assertion failed; minimum receive amount: 1000001, swap amount: 1000000"
+        Err(err) => assert_eq!(
+            err,
+            ContractError::MinimumReceiveAssertion {
+                expected: Uint128::from(1000001u128),
+                actual: Uint128::from(1000000u128)
+            }
         ),
         _ => panic!("DO NOT ENTER HERE"),
     }
 }
+
+fn default_instantiate(deps: DepsMut<TerraQuery>) {
+    let msg = InstantiateMsg {
+        terraswap_factory: "terraswapfactory".to_string(),
+        loop_factory: "loopfactory".to_string(),
+        astroport_factory: "astroportfactory".to_string(),
+        unchecked: false,
+        validate_factories: false,
+    };
+
+    let info = mock_info("addr0000", &[]);
+    let _res = instantiate(deps, mock_env(), info, msg).unwrap();
+}
+
+#[test]
+fn update_wrapper() {
+    let mut deps = mock_dependencies(&[]);
+    default_instantiate(deps.as_mut());
+
+    let msg = ExecuteMsg::UpdateWrapper {
+        native_denom: "uluna".to_string(),
+        wrapper: Some(WrapperInfo {
+            wrapper_contract: "wrapper0000".to_string(),
+            wrapped_token: "wluna".to_string(),
+        }),
+    };
+
+    // only owner can register the wrapper
+    let info = mock_info("addr0001", &[]);
+    let res = execute(deps.as_mut(), mock_env(), info, msg.clone());
+    match res {
+        Err(err) => assert_eq!(err, ContractError::Unauthorized {}),
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+
+    let info = mock_info("addr0000", &[]);
+    let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    let res: WrapperResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Wrapper {
+                native_denom: "uluna".to_string(),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        res,
+        WrapperResponse {
+            native_denom: "uluna".to_string(),
+            wrapper_contract: "wrapper0000".to_string(),
+            wrapped_token: "wluna".to_string(),
+        }
+    );
+
+    // remove the wrapper
+    let msg = ExecuteMsg::UpdateWrapper {
+        native_denom: "uluna".to_string(),
+        wrapper: None,
+    };
+    let info = mock_info("addr0000", &[]);
+    let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::Wrapper {
+            native_denom: "uluna".to_string(),
+        },
+    );
+    match res {
+        Err(ContractError::Std(StdError::GenericErr { msg, .. })) => {
+            assert_eq!(msg, "no wrapper registered for uluna")
+        }
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+}
+
+#[test]
+fn execute_wrap_and_unwrap_operation() {
+    let mut deps = mock_dependencies(&[]);
+    default_instantiate(deps.as_mut());
+
+    deps.querier.with_tax(
+        Decimal::percent(5),
+        &[(&"uluna".to_string(), &Uint128::from(1000000u128))],
+    );
+    deps.querier.with_balance(&[(
+        &MOCK_CONTRACT_ADDR.to_string(),
+        vec![coin(1000000u128, "uluna")],
+    )]);
+    deps.querier.with_token_balances(&[(
+        &"wluna".to_string(),
+        &[(&MOCK_CONTRACT_ADDR.to_string(), &Uint128::from(1000000u128))],
+    )]);
+
+    let wrap_msg = ExecuteMsg::ExecuteSwapOperation {
+        operation: SwapOperation::Wrap {
+            native_denom: "uluna".to_string(),
+            wrapped_token: "wluna".to_string(),
+        },
+        to: None,
+        deadline: None,
+        offer_amount: None,
+        route_id: None,
+        hop_index: None,
+    };
+
+    // wrapper is not registered yet
+    let info = mock_info(MOCK_CONTRACT_ADDR, &[]);
+    let res = execute(deps.as_mut(), mock_env(), info, wrap_msg.clone());
+    match res {
+        Err(ContractError::Std(StdError::GenericErr { msg, .. })) => {
+            assert_eq!(msg, "no wrapper registered for uluna")
+        }
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+
+    let info = mock_info("addr0000", &[]);
+    let _res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::UpdateWrapper {
+            native_denom: "uluna".to_string(),
+            wrapper: Some(WrapperInfo {
+                wrapper_contract: "wrapper0000".to_string(),
+                wrapped_token: "wluna".to_string(),
+            }),
+        },
+    )
+    .unwrap();
+
+    // the wrapped token of the operation must be the one minted by the wrapper
+    let info = mock_info(MOCK_CONTRACT_ADDR, &[]);
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::ExecuteSwapOperation {
+            operation: SwapOperation::Unwrap {
+                wrapped_token: "fakeluna".to_string(),
+                native_denom: "uluna".to_string(),
+            },
+            to: None,
+            deadline: None,
+            offer_amount: None,
+            route_id: None,
+            hop_index: None,
+        },
+    );
+    assert_eq!(
+        res,
+        Err(ContractError::WrapperMismatch {
+            native_denom: "uluna".to_string(),
+            wrapped_token: "fakeluna".to_string(),
+        })
+    );
+
+    let info = mock_info(MOCK_CONTRACT_ADDR, &[]);
+    let res = execute(deps.as_mut(), mock_env(), info, wrap_msg).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::reply_on_success(
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "wrapper0000".to_string(),
+                funds: vec![coin(952380u128, "uluna")], // deduct tax
+                msg: to_binary(&WrapperExecuteMsg::Deposit { recipient: None }).unwrap(),
+            }),
+            HOP_REPLY_ID
+        )]
+    );
+
+    let msg = ExecuteMsg::ExecuteSwapOperation {
+        operation: SwapOperation::Unwrap {
+            wrapped_token: "wluna".to_string(),
+            native_denom: "uluna".to_string(),
+        },
+        to: Some("addr0000".to_string()),
+        deadline: None,
+        offer_amount: None,
+        route_id: None,
+        hop_index: None,
+    };
+    let info = mock_info(MOCK_CONTRACT_ADDR, &[]);
+    let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "wluna".to_string(),
+            funds: vec![],
+            msg: to_binary(&Cw20ExecuteMsg::Send {
+                contract: "wrapper0000".to_string(),
+                amount: Uint128::from(1000000u128),
+                msg: to_binary(&WrapperCw20HookMsg::Withdraw {
+                    recipient: Some("addr0000".to_string()),
+                })
+                .unwrap(),
+            })
+            .unwrap(),
+        }))]
+    );
+}
+
+#[test]
+fn query_routes_with_wrapper() {
+    let mut deps = mock_dependencies(&[]);
+    default_instantiate(deps.as_mut());
+
+    let info = mock_info("addr0000", &[]);
+    let _res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::UpdateWrapper {
+            native_denom: "uluna".to_string(),
+            wrapper: Some(WrapperInfo {
+                wrapper_contract: "wrapper0000".to_string(),
+                wrapped_token: "wluna".to_string(),
+            }),
+        },
+    )
+    .unwrap();
+
+    // set tax rate as 5% and wrapper fee as 0.1%
+    deps.querier.with_tax(
+        Decimal::percent(5),
+        &[(&"uluna".to_string(), &Uint128::from(1000000u128))],
+    );
+    deps.querier
+        .with_wrapper_fees(&[(&"wrapper0000".to_string(), &Decimal::permille(1))]);
+    deps.querier.with_terraswap_factory(
+        &[(
+            &"wlunaasset0000".to_string(),
+            &PairInfo {
+                asset_infos: [
+                    AssetInfo::Token {
+                        contract_addr: "wluna".to_string(),
+                    },
+                    AssetInfo::Token {
+                        contract_addr: "asset0000".to_string(),
+                    },
+                ],
+                contract_addr: "pair0000".to_string(),
+                liquidity_token: "liquidity0000".to_string(),
+                asset_decimals: [6u8, 6u8],
+                created_at: None,
+            },
+        )],
+        &[("uluna".to_string(), 6u8)],
+    );
+
+    let msg = QueryMsg::SimulateSwapOperations {
+        offer_amount: Uint128::from(1000000u128),
+        operations: vec![
+            SwapOperation::Wrap {
+                native_denom: "uluna".to_string(),
+                wrapped_token: "wluna".to_string(),
+            },
+            SwapOperation::TerraSwap {
+                offer_asset_info: AssetInfo::Token {
+                    contract_addr: "wluna".to_string(),
+                },
+                ask_asset_info: AssetInfo::Token {
+                    contract_addr: "asset0000".to_string(),
+                },
+            },
+        ],
+    };
+
+    // 1000000 - 47620 (tax) - 952 (wrapper fee)
+    let res: SimulateSwapOperationsResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+    assert_eq!(res.amount, Uint128::from(951428u128));
+
+    let operations = vec![
+        SwapOperation::TerraSwap {
+            offer_asset_info: AssetInfo::Token {
+                contract_addr: "asset0000".to_string(),
+            },
+            ask_asset_info: AssetInfo::Token {
+                contract_addr: "wluna".to_string(),
+            },
+        },
+        SwapOperation::Unwrap {
+            wrapped_token: "wluna".to_string(),
+            native_denom: "uluna".to_string(),
+        },
+    ];
+
+    // (1000000 + 50000 (tax)) / (1 - 0.001)
+    let res: SimulateSwapOperationsResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::ReverseSimulateSwapOperations {
+                ask_amount: Uint128::from(1000000u128),
+                operations: operations.clone(),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(res.amount, Uint128::from(1051051u128));
+
+    // the reverse simulated amount is enough to receive the ask amount
+    let res: SimulateSwapOperationsResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::SimulateSwapOperations {
+                offer_amount: Uint128::from(1051051u128),
+                operations,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(res.amount, Uint128::from(1000000u128));
+}
+
+#[test]
+fn update_account_limits() {
+    let mut deps = mock_dependencies(&[]);
+    default_instantiate(deps.as_mut());
+
+    let account_limits = AccountLimits {
+        asset: AssetInfo::NativeToken {
+            denom: "uusd".to_string(),
+        },
+        max_amount_per_day: Uint128::from(1000000u128),
+    };
+    let msg = ExecuteMsg::UpdateAccountLimits {
+        account_limits: Some(account_limits.clone()),
+    };
+
+    // only owner can update the limits
+    let info = mock_info("addr0001", &[]);
+    let res = execute(deps.as_mut(), mock_env(), info, msg.clone());
+    match res {
+        Err(err) => assert_eq!(err, ContractError::Unauthorized {}),
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+
+    let info = mock_info("addr0000", &[]);
+    let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    let config: ConfigResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
+    assert_eq!(config.account_limits, Some(account_limits));
+
+    // disable the limits
+    let info = mock_info("addr0000", &[]);
+    let _res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::UpdateAccountLimits {
+            account_limits: None,
+        },
+    )
+    .unwrap();
+
+    let config: ConfigResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
+    assert_eq!(config.account_limits, None);
+}
+
+fn enable_account_limits(deps: DepsMut<TerraQuery>) {
+    let info = mock_info("addr0000", &[]);
+    let _res = execute(
+        deps,
+        mock_env(),
+        info,
+        ExecuteMsg::UpdateAccountLimits {
+            account_limits: Some(AccountLimits {
+                asset: AssetInfo::NativeToken {
+                    denom: "uusd".to_string(),
+                },
+                max_amount_per_day: Uint128::from(1000000u128),
+            }),
+        },
+    )
+    .unwrap();
+}
+
+fn native_swap_operations() -> ExecuteMsg {
+    ExecuteMsg::ExecuteSwapOperations {
+        operations: vec![SwapOperation::NativeSwap {
+            offer_denom: "uusd".to_string(),
+            ask_denom: "ukrw".to_string(),
+        }],
+        minimum_receive: None,
+        minimum_receive_price: None,
+        to: None,
+        deadline: None,
+        offer_amount: None,
+        auto_unwind: false,
+        unwind_receiver: None,
+    }
+}
+
+#[test]
+fn account_limits_exact_cap() {
+    let mut deps = mock_dependencies(&[]);
+    default_instantiate(deps.as_mut());
+    enable_account_limits(deps.as_mut());
+
+    deps.querier.with_tax(
+        Decimal::zero(),
+        &[(&"uusd".to_string(), &Uint128::from(1000000u128))],
+    );
+    deps.querier.with_terraswap_factory(
+        &[(
+            &"asset0000uusd".to_string(),
+            &PairInfo {
+                asset_infos: [
+                    AssetInfo::Token {
+                        contract_addr: "asset0000".to_string(),
+                    },
+                    AssetInfo::NativeToken {
+                        denom: "uusd".to_string(),
+                    },
+                ],
+                contract_addr: "pair0000".to_string(),
+                liquidity_token: "liquidity0000".to_string(),
+                asset_decimals: [6u8, 6u8],
+                created_at: None,
+            },
+        )],
+        &[("uusd".to_string(), 6u8)],
+    );
+
+    let info = mock_info("addr0000", &[coin(600000u128, "uusd")]);
+    let _res = execute(deps.as_mut(), mock_env(), info, native_swap_operations()).unwrap();
+
+    // cw20 offer is converted into uusd through the terraswap pair
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "addr0000".to_string(),
+        amount: Uint128::from(400000u128),
+        msg: to_binary(&Cw20HookMsg::ExecuteSwapOperations {
+            operations: vec![SwapOperation::TerraSwap {
+                offer_asset_info: AssetInfo::Token {
+                    contract_addr: "asset0000".to_string(),
+                },
+                ask_asset_info: AssetInfo::NativeToken {
+                    denom: "uusd".to_string(),
+                },
+            }],
+            minimum_receive: None,
+            minimum_receive_price: None,
+            to: None,
+            deadline: None,
+            auto_unwind: false,
+            unwind_receiver: None,
+        })
+        .unwrap(),
+    });
+    let info = mock_info("asset0000", &[]);
+    let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    // the cap is reached
+    let info = mock_info("addr0000", &[coin(1u128, "uusd")]);
+    let res = execute(deps.as_mut(), mock_env(), info, native_swap_operations());
+    match res {
+        Err(err) => assert_eq!(
+            err,
+            ContractError::DailySwapLimitExceeded {
+                remaining: Uint128::from(0u128)
+            }
+        ),
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+
+    // other accounts have their own allowance
+    let info = mock_info("addr0001", &[coin(1000000u128, "uusd")]);
+    let _res = execute(deps.as_mut(), mock_env(), info, native_swap_operations()).unwrap();
+}
+
+#[test]
+fn account_limits_rollover() {
+    let mut deps = mock_dependencies(&[]);
+    default_instantiate(deps.as_mut());
+    enable_account_limits(deps.as_mut());
+
+    let mut env = mock_env();
+    env.block.time = Timestamp::from_seconds(86400 * 19000 + 86399);
+
+    let info = mock_info("addr0000", &[coin(700000u128, "uusd")]);
+    let _res = execute(deps.as_mut(), env.clone(), info, native_swap_operations()).unwrap();
+
+    let info = mock_info("addr0000", &[coin(300001u128, "uusd")]);
+    let res = execute(deps.as_mut(), env.clone(), info, native_swap_operations());
+    match res {
+        Err(err) => assert_eq!(
+            err,
+            ContractError::DailySwapLimitExceeded {
+                remaining: Uint128::from(300000u128)
+            }
+        ),
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+
+    // midnight resets the allowance
+    env.block.time = env.block.time.plus_seconds(1);
+    let info = mock_info("addr0000", &[coin(1000000u128, "uusd")]);
+    let _res = execute(deps.as_mut(), env, info, native_swap_operations()).unwrap();
+}
+
+fn set_routing_fee(deps: DepsMut<TerraQuery>, fee_side: FeeSide) {
+    let info = mock_info("addr0000", &[]);
+    let _res = execute(
+        deps,
+        mock_env(),
+        info,
+        ExecuteMsg::UpdateRoutingFee {
+            routing_fee: Some(RoutingFee {
+                rate: Decimal::percent(1),
+                collector: "collector".to_string(),
+                fee_side,
+            }),
+        },
+    )
+    .unwrap();
+}
+
+#[test]
+fn update_routing_fee() {
+    let mut deps = mock_dependencies(&[]);
+    default_instantiate(deps.as_mut());
+
+    let routing_fee = RoutingFee {
+        rate: Decimal::percent(1),
+        collector: "collector".to_string(),
+        fee_side: FeeSide::Offer,
+    };
+
+    // only owner can update the routing fee
+    let info = mock_info("addr0001", &[]);
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::UpdateRoutingFee {
+            routing_fee: Some(routing_fee.clone()),
+        },
+    );
+    match res {
+        Err(err) => assert_eq!(err, ContractError::Unauthorized {}),
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+
+    let info = mock_info("addr0000", &[]);
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::UpdateRoutingFee {
+            routing_fee: Some(RoutingFee {
+                rate: Decimal::one(),
+                ..routing_fee.clone()
+            }),
+        },
+    );
+    match res {
+        Err(err) => assert_eq!(err, ContractError::InvalidRoutingFeeRate {}),
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+
+    let info = mock_info("addr0000", &[]);
+    let _res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::UpdateRoutingFee {
+            routing_fee: Some(routing_fee.clone()),
+        },
+    )
+    .unwrap();
+
+    let config: ConfigResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
+    assert_eq!(config.routing_fee, Some(routing_fee));
+}
+
+#[test]
+fn estimate_route_gas() {
+    let mut deps = mock_dependencies(&[]);
+    default_instantiate(deps.as_mut());
+
+    let operations = vec![
+        SwapOperation::native_swap("ukrw", "uusd"),
+        SwapOperation::terraswap(
+            AssetInfo::NativeToken {
+                denom: "uusd".to_string(),
+            },
+            AssetInfo::Token {
+                contract_addr: "asset0001".to_string(),
+            },
+        ),
+        SwapOperation::Astroport {
+            offer_asset_info: AssetInfo::Token {
+                contract_addr: "asset0001".to_string(),
+            },
+            ask_asset_info: AssetInfo::NativeToken {
+                denom: "uluna".to_string(),
+            },
+        },
+    ];
+
+    // base + native swap + terraswap hop + astroport hop + assertion
+    let res: EstimateRouteGasResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::EstimateRouteGas {
+                operations: operations.clone(),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(res.gas, 150_000 + 150_000 + 250_000 + 300_000 + 50_000);
+
+    let gas_config = GasConfig {
+        base: 1000,
+        terraswap_hop: 200,
+        loop_hop: 300,
+        astroport_hop: 400,
+        native_swap: 50,
+        wrapper_hop: 70,
+        assertion: 5,
+    };
+
+    // only owner can update the gas config
+    let info = mock_info("addr0001", &[]);
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::UpdateGasConfig {
+            gas_config: gas_config.clone(),
+        },
+    );
+    match res {
+        Err(err) => assert_eq!(err, ContractError::Unauthorized {}),
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+
+    let info = mock_info("addr0000", &[]);
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::UpdateGasConfig {
+            gas_config: gas_config.clone(),
+        },
+    )
+    .unwrap();
+    assert_eq!(res.attributes, vec![attr("action", "update_gas_config")]);
+
+    let config: ConfigResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
+    assert_eq!(config.gas_config, gas_config);
+
+    let res: EstimateRouteGasResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::EstimateRouteGas { operations },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(res.gas, 1000 + 50 + 200 + 400 + 5);
+
+    // an empty route is rejected as on execution
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::EstimateRouteGas { operations: vec![] },
+    );
+    assert!(res.is_err());
+}
+
+#[test]
+fn execute_swap_operations_with_routing_fee() {
+    let mut deps = mock_dependencies(&[]);
+    default_instantiate(deps.as_mut());
+    set_routing_fee(deps.as_mut(), FeeSide::Offer);
+
+    deps.querier.with_tax(
+        Decimal::percent(5),
+        &[(&"uusd".to_string(), &Uint128::from(1000000u128))],
+    );
+    deps.querier.with_token_balances(&[(
+        &"asset0000".to_string(),
+        &[(&"addr0000".to_string(), &Uint128::from(1000000u128))],
+    )]);
+
+    let operations = vec![SwapOperation::TerraSwap {
+        offer_asset_info: AssetInfo::NativeToken {
+            denom: "uusd".to_string(),
+        },
+        ask_asset_info: AssetInfo::Token {
+            contract_addr: "asset0000".to_string(),
+        },
+    }];
+    let msg = ExecuteMsg::ExecuteSwapOperations {
+        operations: operations.clone(),
+        minimum_receive: Some(Uint128::from(900000u128)),
+        minimum_receive_price: None,
+        to: None,
+        deadline: None,
+        offer_amount: None,
+        auto_unwind: false,
+        unwind_receiver: None,
+    };
+
+    // fee first, then route, then assertion
+    let info = mock_info("addr0000", &[coin(1000000u128, "uusd")]);
+    let res = execute(deps.as_mut(), mock_env(), info, msg.clone()).unwrap();
+    let route_id = route_id_of(&res);
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+                to_address: "collector".to_string(),
+                amount: vec![coin(9523u128, "uusd")], // 10000 - tax
+            })),
+            SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: MOCK_CONTRACT_ADDR.to_string(),
+                funds: vec![],
+                msg: to_binary(&ExecuteMsg::ExecuteSwapOperation {
+                    operation: operations[0].clone(),
+                    to: Some("addr0000".to_string()),
+                    deadline: None,
+                    offer_amount: Some(Uint128::from(990000u128)),
+                    route_id: Some(route_id.clone()),
+                    hop_index: Some(1),
+                })
+                .unwrap(),
+            })),
+            SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: MOCK_CONTRACT_ADDR.to_string(),
+                funds: vec![],
+                msg: to_binary(&ExecuteMsg::AssertMinimumReceive {
+                    asset_info: AssetInfo::Token {
+                        contract_addr: "asset0000".to_string(),
+                    },
+                    prev_balance: Uint128::from(1000000u128),
+                    minimum_receive: Uint128::from(900000u128),
+                    receiver: "addr0000".to_string(),
+                })
+                .unwrap(),
+            })),
+        ]
+    );
+
+    // route, then fee, then assertion
+    set_routing_fee(deps.as_mut(), FeeSide::Ask);
+    let info = mock_info("addr0000", &[coin(1000000u128, "uusd")]);
+    let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    let route_id = route_id_of(&res);
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: MOCK_CONTRACT_ADDR.to_string(),
+                funds: vec![],
+                msg: to_binary(&ExecuteMsg::ExecuteSwapOperation {
+                    operation: operations[0].clone(),
+                    to: None,
+                    deadline: None,
+                    offer_amount: Some(Uint128::from(1000000u128)),
+                    route_id: Some(route_id.clone()),
+                    hop_index: Some(1),
+                })
+                .unwrap(),
+            })),
+            SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: MOCK_CONTRACT_ADDR.to_string(),
+                funds: vec![],
+                msg: to_binary(&ExecuteMsg::DistributeAskAsset {
+                    asset_info: AssetInfo::Token {
+                        contract_addr: "asset0000".to_string(),
+                    },
+                    receiver: "addr0000".to_string(),
+                })
+                .unwrap(),
+            })),
+            SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: MOCK_CONTRACT_ADDR.to_string(),
+                funds: vec![],
+                msg: to_binary(&ExecuteMsg::AssertMinimumReceive {
+                    asset_info: AssetInfo::Token {
+                        contract_addr: "asset0000".to_string(),
+                    },
+                    prev_balance: Uint128::from(1000000u128),
+                    minimum_receive: Uint128::from(900000u128),
+                    receiver: "addr0000".to_string(),
+                })
+                .unwrap(),
+            })),
+        ]
+    );
+
+    // cw20 offer asset is transferred to the collector
+    set_routing_fee(deps.as_mut(), FeeSide::Offer);
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: "addr0000".to_string(),
+        amount: Uint128::from(1000000u128),
+        msg: to_binary(&Cw20HookMsg::ExecuteSwapOperations {
+            operations: vec![SwapOperation::TerraSwap {
+                offer_asset_info: AssetInfo::Token {
+                    contract_addr: "asset0000".to_string(),
+                },
+                ask_asset_info: AssetInfo::NativeToken {
+                    denom: "uusd".to_string(),
+                },
+            }],
+            minimum_receive: None,
+            minimum_receive_price: None,
+            to: None,
+            deadline: None,
+            auto_unwind: false,
+            unwind_receiver: None,
+        })
+        .unwrap(),
+    });
+    let info = mock_info("asset0000", &[]);
+    let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    assert_eq!(
+        res.messages[0],
+        SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "asset0000".to_string(),
+            funds: vec![],
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: "collector".to_string(),
+                amount: Uint128::from(10000u128),
+            })
+            .unwrap(),
+        }))
+    );
+}
+
+#[test]
+fn distribute_ask_asset() {
+    let mut deps = mock_dependencies(&[]);
+    default_instantiate(deps.as_mut());
+    set_routing_fee(deps.as_mut(), FeeSide::Ask);
+
+    deps.querier.with_token_balances(&[(
+        &"asset0000".to_string(),
+        &[(&MOCK_CONTRACT_ADDR.to_string(), &Uint128::from(1000000u128))],
+    )]);
+
+    let msg = ExecuteMsg::DistributeAskAsset {
+        asset_info: AssetInfo::Token {
+            contract_addr: "asset0000".to_string(),
+        },
+        receiver: "addr0000".to_string(),
+    };
+
+    // internal use only
+    let info = mock_info("addr0000", &[]);
+    let res = execute(deps.as_mut(), mock_env(), info, msg.clone());
+    match res {
+        Err(err) => assert_eq!(err, ContractError::Unauthorized {}),
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+
+    let info = mock_info(MOCK_CONTRACT_ADDR, &[]);
+    let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "asset0000".to_string(),
+                funds: vec![],
+                msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: "collector".to_string(),
+                    amount: Uint128::from(10000u128),
+                })
+                .unwrap(),
+            })),
+            SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "asset0000".to_string(),
+                funds: vec![],
+                msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: "addr0000".to_string(),
+                    amount: Uint128::from(990000u128),
+                })
+                .unwrap(),
+            })),
+        ]
+    );
+}
+
+#[test]
+fn query_routes_with_routing_fee() {
+    let mut deps = mock_dependencies(&[]);
+    default_instantiate(deps.as_mut());
+
+    deps.querier.with_tax(
+        Decimal::percent(5),
+        &[(&"uusd".to_string(), &Uint128::from(1000000u128))],
+    );
+    deps.querier.with_terraswap_factory(
+        &[(
+            &"asset0000uusd".to_string(),
+            &PairInfo {
+                asset_infos: [
+                    AssetInfo::Token {
+                        contract_addr: "asset0000".to_string(),
+                    },
+                    AssetInfo::NativeToken {
+                        denom: "uusd".to_string(),
+                    },
+                ],
+                contract_addr: "pair0000".to_string(),
+                liquidity_token: "liquidity0000".to_string(),
+                asset_decimals: [6u8, 6u8],
+                created_at: None,
+            },
+        )],
+        &[("uusd".to_string(), 6u8)],
+    );
+
+    let operations = vec![SwapOperation::TerraSwap {
+        offer_asset_info: AssetInfo::Token {
+            contract_addr: "asset0000".to_string(),
+        },
+        ask_asset_info: AssetInfo::NativeToken {
+            denom: "uusd".to_string(),
+        },
+    }];
+    let simulate = |deps: Deps<TerraQuery>, offer_amount: u128| {
+        let res: SimulateSwapOperationsResponse = from_binary(
+            &query(
+                deps,
+                mock_env(),
+                QueryMsg::SimulateSwapOperations {
+                    offer_amount: Uint128::from(offer_amount),
+                    operations: operations.clone(),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        res.amount
+    };
+    let reverse_simulate = |deps: Deps<TerraQuery>, ask_amount: u128| {
+        let res: SimulateSwapOperationsResponse = from_binary(
+            &query(
+                deps,
+                mock_env(),
+                QueryMsg::ReverseSimulateSwapOperations {
+                    ask_amount: Uint128::from(ask_amount),
+                    operations: operations.clone(),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        res.amount
+    };
+
+    // 1000000 - 10000 (fee) - 47143 (tax)
+    set_routing_fee(deps.as_mut(), FeeSide::Offer);
+    assert_eq!(
+        simulate(deps.as_ref(), 1000000u128),
+        Uint128::from(942857u128)
+    );
+    // (942857 + 47142 (tax)) / (1 - 0.01)
+    let offer_amount = reverse_simulate(deps.as_ref(), 942857u128);
+    assert_eq!(offer_amount, Uint128::from(999998u128));
+    assert_eq!(
+        simulate(deps.as_ref(), offer_amount.u128()),
+        Uint128::from(942856u128)
+    );
+
+    // 1000000 - 47620 (tax) - 9523 (fee) - 44898 (tax on the transfer to the receiver)
+    set_routing_fee(deps.as_mut(), FeeSide::Ask);
+    assert_eq!(
+        simulate(deps.as_ref(), 1000000u128),
+        Uint128::from(897959u128)
+    );
+    // ((897959 + 44896 (tax)) / (1 - 0.01)) + 47618 (tax)
+    let offer_amount = reverse_simulate(deps.as_ref(), 897959u128);
+    assert_eq!(offer_amount, Uint128::from(999997u128));
+    assert_eq!(
+        simulate(deps.as_ref(), offer_amount.u128()),
+        Uint128::from(897957u128)
+    );
+}
+
+#[test]
+fn execute_swap_operations_with_offer_amount() {
+    let mut deps = mock_dependencies(&[coin(1000000u128, "uusd")]);
+    default_instantiate(deps.as_mut());
+
+    let operations = vec![
+        SwapOperation::NativeSwap {
+            offer_denom: "uusd".to_string(),
+            ask_denom: "ukrw".to_string(),
+        },
+        SwapOperation::TerraSwap {
+            offer_asset_info: AssetInfo::NativeToken {
+                denom: "ukrw".to_string(),
+            },
+            ask_asset_info: AssetInfo::Token {
+                contract_addr: "asset0001".to_string(),
+            },
+        },
+    ];
+
+    // the router balance is not enough
+    let msg = ExecuteMsg::ExecuteSwapOperations {
+        operations: operations.clone(),
+        minimum_receive: None,
+        minimum_receive_price: None,
+        to: None,
+        deadline: None,
+        offer_amount: Some(Uint128::from(1000001u128)),
+        auto_unwind: false,
+        unwind_receiver: None,
+    };
+    let info = mock_info("addr0000", &[]);
+    let res = execute(deps.as_mut(), mock_env(), info, msg);
+    match res {
+        Err(err) => assert_eq!(
+            err,
+            ContractError::InsufficientOfferBalance {
+                offer_amount: Uint128::from(1000001u128),
+                balance: Uint128::from(1000000u128)
+            }
+        ),
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+
+    // other senders must attach the offer amount, the router balance is not theirs
+    let offer = |offer_amount: u128| ExecuteMsg::ExecuteSwapOperations {
+        operations: operations.clone(),
+        minimum_receive: None,
+        minimum_receive_price: None,
+        to: None,
+        deadline: None,
+        offer_amount: Some(Uint128::from(offer_amount)),
+        auto_unwind: false,
+        unwind_receiver: None,
+    };
+    for funds in [vec![], vec![coin(399999u128, "uusd")]] {
+        let sent = funds.first().map(|coin| coin.amount).unwrap_or_default();
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("addr0001", &funds),
+            offer(400000),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::OfferAmountMismatch {
+                offer_amount: Uint128::from(400000u128),
+                sent,
+            }
+        );
+    }
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0001", &[coin(400000u128, "uusd")]),
+        offer(400000),
+    )
+    .unwrap();
+
+    // only the first hop swaps the exact offer amount
+    let msg = ExecuteMsg::ExecuteSwapOperations {
+        operations: operations.clone(),
+        minimum_receive: None,
+        minimum_receive_price: None,
+        to: None,
+        deadline: None,
+        offer_amount: Some(Uint128::from(400000u128)),
+        auto_unwind: false,
+        unwind_receiver: None,
+    };
+    let info = mock_info("addr0000", &[]);
+    let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    let route_id = route_id_of(&res);
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: MOCK_CONTRACT_ADDR.to_string(),
+                funds: vec![],
+                msg: to_binary(&ExecuteMsg::ExecuteSwapOperation {
+                    operation: operations[0].clone(),
+                    to: None,
+                    deadline: None,
+                    offer_amount: Some(Uint128::from(400000u128)),
+                    route_id: Some(route_id.clone()),
+                    hop_index: Some(1),
+                })
+                .unwrap(),
+            })),
+            SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: MOCK_CONTRACT_ADDR.to_string(),
+                funds: vec![],
+                msg: to_binary(&ExecuteMsg::ExecuteSwapOperation {
+                    operation: operations[1].clone(),
+                    to: Some("addr0000".to_string()),
+                    deadline: None,
+                    offer_amount: None,
+                    route_id: Some(route_id.clone()),
+                    hop_index: Some(2),
+                })
+                .unwrap(),
+            })),
+        ]
+    );
+
+    // the offer side routing fee is peeled off the exact offer amount
+    set_routing_fee(deps.as_mut(), FeeSide::Offer);
+    deps.querier.with_tax(
+        Decimal::zero(),
+        &[(&"uusd".to_string(), &Uint128::from(1000000u128))],
+    );
+    let msg = ExecuteMsg::ExecuteSwapOperations {
+        operations: operations.clone(),
+        minimum_receive: None,
+        minimum_receive_price: None,
+        to: None,
+        deadline: None,
+        offer_amount: Some(Uint128::from(400000u128)),
+        auto_unwind: false,
+        unwind_receiver: None,
+    };
+    let info = mock_info("addr0000", &[]);
+    let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    let route_id = route_id_of(&res);
+    assert_eq!(
+        res.messages[0..2],
+        vec![
+            SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+                to_address: "collector".to_string(),
+                amount: vec![coin(4000u128, "uusd")],
+            })),
+            SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: MOCK_CONTRACT_ADDR.to_string(),
+                funds: vec![],
+                msg: to_binary(&ExecuteMsg::ExecuteSwapOperation {
+                    operation: operations[0].clone(),
+                    to: None,
+                    deadline: None,
+                    offer_amount: Some(Uint128::from(396000u128)),
+                    route_id: Some(route_id.clone()),
+                    hop_index: Some(1),
+                })
+                .unwrap(),
+            })),
+        ]
+    );
+}
+
+#[test]
+fn execute_swap_operation_with_offer_amount() {
+    let mut deps = mock_dependencies(&[]);
+    default_instantiate(deps.as_mut());
+
+    deps.querier.with_terraswap_factory(
+        &[(
+            &"uusdasset0000".to_string(),
+            &PairInfo {
+                asset_infos: [
+                    AssetInfo::NativeToken {
+                        denom: "uusd".to_string(),
+                    },
+                    AssetInfo::Token {
+                        contract_addr: "asset0000".to_string(),
+                    },
+                ],
+                contract_addr: "pair0000".to_string(),
+                liquidity_token: "liquidity0000".to_string(),
+                asset_decimals: [6u8, 6u8],
+                created_at: None,
+            },
+        )],
+        &[("uusd".to_string(), 6u8)],
+    );
+    deps.querier.with_tax(
+        Decimal::percent(5),
+        &[(&"uusd".to_string(), &Uint128::from(1000000u128))],
+    );
+    deps.querier.with_balance(&[(
+        &MOCK_CONTRACT_ADDR.to_string(),
+        vec![coin(1000000u128, "uusd")],
+    )]);
+
+    deps.querier.with_token_balances(&[(
+        &"asset0000".to_string(),
+        &[(&MOCK_CONTRACT_ADDR.to_string(), &Uint128::zero())],
+    )]);
+
+    // swap a part of the router balance
+    let msg = ExecuteMsg::ExecuteSwapOperation {
+        operation: SwapOperation::TerraSwap {
+            offer_asset_info: AssetInfo::NativeToken {
+                denom: "uusd".to_string(),
+            },
+            ask_asset_info: AssetInfo::Token {
+                contract_addr: "asset0000".to_string(),
+            },
+        },
+        to: None,
+        deadline: None,
+        offer_amount: Some(Uint128::from(420000u128)),
+        route_id: None,
+        hop_index: None,
+    };
+    let info = mock_info(MOCK_CONTRACT_ADDR, &[]);
+    let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::reply_on_success(
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "pair0000".to_string(),
+                funds: vec![coin(400000u128, "uusd")], // deduct tax
+                msg: to_binary(&PairExecuteMsg::Swap {
+                    offer_asset: Asset {
+                        info: AssetInfo::NativeToken {
+                            denom: "uusd".to_string(),
+                        },
+                        amount: Uint128::from(400000u128),
+                    },
+                    belief_price: None,
+                    belief_price_direction: None,
+                    max_spread: None,
+                    to: None,
+                    deadline: None,
+                    to_msg: None,
+                    referral: None,
+                })
+                .unwrap(),
+            }),
+            HOP_REPLY_ID
+        )]
+    );
+}
+
+#[test]
+fn hop_return_feeds_next_hop() {
+    let mut deps = mock_dependencies(&[]);
+    default_instantiate(deps.as_mut());
+
+    deps.querier.with_terraswap_factory(
+        &[(
+            &"uusdasset0000".to_string(),
+            &PairInfo {
+                asset_infos: [
+                    AssetInfo::NativeToken {
+                        denom: "uusd".to_string(),
+                    },
+                    AssetInfo::Token {
+                        contract_addr: "asset0000".to_string(),
+                    },
+                ],
+                contract_addr: "pair0000".to_string(),
+                liquidity_token: "liquidity0000".to_string(),
+                asset_decimals: [6u8, 6u8],
+                created_at: None,
+            },
+        )],
+        &[("uusd".to_string(), 6u8)],
+    );
+    deps.querier.with_balance(&[(
+        &MOCK_CONTRACT_ADDR.to_string(),
+        vec![coin(1000000u128, "uusd")],
+    )]);
+
+    // the router already holds 500000 of the intermediate asset
+    deps.querier.with_token_balances(&[(
+        &"asset0000".to_string(),
+        &[(&MOCK_CONTRACT_ADDR.to_string(), &Uint128::from(500000u128))],
+    )]);
+
+    let info = mock_info(MOCK_CONTRACT_ADDR, &[]);
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        ExecuteMsg::ExecuteSwapOperation {
+            operation: SwapOperation::TerraSwap {
+                offer_asset_info: AssetInfo::NativeToken {
+                    denom: "uusd".to_string(),
+                },
+                ask_asset_info: AssetInfo::Token {
+                    contract_addr: "asset0000".to_string(),
+                },
+            },
+            to: None,
+            deadline: None,
+            offer_amount: Some(Uint128::from(400000u128)),
+            route_id: None,
+            hop_index: None,
+        },
+    )
+    .unwrap();
+
+    // the hop returns 300000
+    deps.querier.with_token_balances(&[(
+        &"asset0000".to_string(),
+        &[(&MOCK_CONTRACT_ADDR.to_string(), &Uint128::from(800000u128))],
+    )]);
+    let hop_reply = |id: u64| Reply {
+        id,
+        result: SubMsgResult::Ok(SubMsgResponse {
+            events: vec![],
+            data: None,
+        }),
+    };
+    let err = reply(deps.as_mut(), mock_env(), hop_reply(99)).unwrap_err();
+    assert_eq!(err, ContractError::InvalidReply {});
+
+    let res = reply(deps.as_mut(), mock_env(), hop_reply(HOP_REPLY_ID)).unwrap();
+    assert_eq!(res.attributes, vec![attr("hop_return", "300000")]);
+
+    // the next hop offers the return, not the whole balance
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::ExecuteSwapOperation {
+            operation: SwapOperation::TerraSwap {
+                offer_asset_info: AssetInfo::Token {
+                    contract_addr: "asset0000".to_string(),
+                },
+                ask_asset_info: AssetInfo::NativeToken {
+                    denom: "uusd".to_string(),
+                },
+            },
+            to: Some("addr0000".to_string()),
+            deadline: None,
+            offer_amount: None,
+            route_id: None,
+            hop_index: None,
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "asset0000".to_string(),
+            funds: vec![],
+            msg: to_binary(&Cw20ExecuteMsg::Send {
+                contract: "pair0000".to_string(),
+                amount: Uint128::from(300000u128),
+                msg: to_binary(&PairExecuteMsg::Swap {
+                    offer_asset: Asset {
+                        info: AssetInfo::Token {
+                            contract_addr: "asset0000".to_string(),
+                        },
+                        amount: Uint128::from(300000u128),
+                    },
+                    belief_price: None,
+                    belief_price_direction: None,
+                    max_spread: None,
+                    to: Some("addr0000".to_string()),
+                    deadline: None,
+                    to_msg: None,
+                    referral: None,
+                })
+                .unwrap(),
+            })
+            .unwrap(),
+        }))]
+    );
+}
+
+#[test]
+fn swap_receipts() {
+    let mut deps = mock_dependencies(&[]);
+    default_instantiate(deps.as_mut());
+
+    deps.querier.with_terraswap_factory(
+        &[(
+            &"uusdasset0000".to_string(),
+            &PairInfo {
+                asset_infos: [
+                    AssetInfo::NativeToken {
+                        denom: "uusd".to_string(),
+                    },
+                    AssetInfo::Token {
+                        contract_addr: "asset0000".to_string(),
+                    },
+                ],
+                contract_addr: "pair0000".to_string(),
+                liquidity_token: "liquidity0000".to_string(),
+                asset_decimals: [6u8, 6u8],
+                created_at: None,
+            },
+        )],
+        &[("uusd".to_string(), 6u8)],
+    );
+    deps.querier.with_token_balances(&[(
+        &"asset0000".to_string(),
+        &[(&"addr0000".to_string(), &Uint128::from(100u128))],
+    )]);
+    let operation = SwapOperation::TerraSwap {
+        offer_asset_info: AssetInfo::NativeToken {
+            denom: "uusd".to_string(),
+        },
+        ask_asset_info: AssetInfo::Token {
+            contract_addr: "asset0000".to_string(),
+        },
+    };
+
+    // the route of addr0000 is started, then its last hop replies
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[coin(1000000u128, "uusd")]),
+        ExecuteMsg::ExecuteSwapOperations {
+            operations: vec![operation.clone()],
+            minimum_receive: None,
+            minimum_receive_price: None,
+            to: None,
+            deadline: None,
+            offer_amount: None,
+            auto_unwind: false,
+            unwind_receiver: None,
+        },
+    )
+    .unwrap();
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(MOCK_CONTRACT_ADDR, &[]),
+        ExecuteMsg::ExecuteSwapOperation {
+            operation,
+            to: Some("addr0000".to_string()),
+            deadline: None,
+            offer_amount: Some(Uint128::from(1000000u128)),
+            route_id: None,
+            hop_index: None,
+        },
+    )
+    .unwrap();
+    assert_eq!(res.messages[0].id, FINAL_HOP_REPLY_ID);
+    assert_eq!(res.messages[0].reply_on, ReplyOn::Success);
+
+    deps.querier.with_token_balances(&[(
+        &"asset0000".to_string(),
+        &[(&"addr0000".to_string(), &Uint128::from(800u128))],
+    )]);
+    let res = reply(
+        deps.as_mut(),
+        mock_env(),
+        Reply {
+            id: FINAL_HOP_REPLY_ID,
+            result: SubMsgResult::Ok(SubMsgResponse {
+                events: vec![],
+                data: None,
+            }),
+        },
+    )
+    .unwrap();
+    assert_eq!(res.attributes, vec![attr("swap_receipt", "700")]);
+
+    let last_receipt = |deps: Deps<TerraQuery>, env: Env| {
+        from_binary::<LastSwapReceiptResponse>(
+            &query(
+                deps,
+                env,
+                QueryMsg::LastSwapReceipt {
+                    account: "addr0000".to_string(),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap()
+        .receipt
+    };
+    let receipt = SwapReceipt {
+        target_asset: AssetInfo::Token {
+            contract_addr: "asset0000".to_string(),
+        },
+        amount: Uint128::from(700u128),
+        block_height: mock_env().block.height,
+    };
+    assert_eq!(
+        last_receipt(deps.as_ref(), mock_env()),
+        Some(receipt.clone())
+    );
+
+    // the receipt is not returned in the next block
+    let mut env = mock_env();
+    env.block.height += 1;
+    assert_eq!(last_receipt(deps.as_ref(), env), None);
+}
+
+fn route_id_of(res: &Response<TerraMsg>) -> String {
+    res.events
+        .iter()
+        .find(|event| event.ty == "terraswap_router_swap")
+        .and_then(|event| event.attributes.iter().find(|attr| attr.key == "route_id"))
+        .map(|attr| attr.value.clone())
+        .unwrap()
+}
+
+#[test]
+fn route_events() {
+    let mut deps = mock_dependencies(&[]);
+    default_instantiate(deps.as_mut());
+
+    let operations = vec![
+        SwapOperation::NativeSwap {
+            offer_denom: "uusd".to_string(),
+            ask_denom: "ukrw".to_string(),
+        },
+        SwapOperation::TerraSwap {
+            offer_asset_info: AssetInfo::NativeToken {
+                denom: "ukrw".to_string(),
+            },
+            ask_asset_info: AssetInfo::Token {
+                contract_addr: "asset0001".to_string(),
+            },
+        },
+    ];
+    let swap_msg = ExecuteMsg::ExecuteSwapOperations {
+        operations: operations.clone(),
+        minimum_receive: None,
+        minimum_receive_price: None,
+        to: None,
+        deadline: None,
+        offer_amount: None,
+        auto_unwind: false,
+        unwind_receiver: None,
+    };
+
+    let info = mock_info("addr0000", &[coin(1000000u128, "uusd")]);
+    let res = execute(deps.as_mut(), mock_env(), info, swap_msg.clone()).unwrap();
+    let route_id = route_id_of(&res);
+    assert_eq!(
+        res.events,
+        vec![Event::new("terraswap_router_swap").add_attributes(vec![
+            ("route_id", route_id.as_str()),
+            ("hop_count", "2"),
+            ("offer_asset", "uusd"),
+            ("offer_amount", "1000000"),
+        ])]
+    );
+
+    // every hop emits the same route id with its hop index
+    deps.querier.with_balance(&[(
+        &MOCK_CONTRACT_ADDR.to_string(),
+        vec![coin(1000000u128, "uusd")],
+    )]);
+    let msg = ExecuteMsg::ExecuteSwapOperation {
+        operation: operations[0].clone(),
+        to: None,
+        deadline: None,
+        offer_amount: None,
+        route_id: Some(route_id.clone()),
+        hop_index: Some(1),
+    };
+    let info = mock_info(MOCK_CONTRACT_ADDR, &[]);
+    let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    assert_eq!(
+        res.events,
+        vec![Event::new("terraswap_router_hop").add_attributes(vec![
+            ("route_id", route_id.as_str()),
+            ("hop_index", "1"),
+            ("offer_asset", "uusd"),
+            ("ask_asset", "ukrw"),
+        ])]
+    );
+
+    // another route in the same block has a different id
+    let info = mock_info("addr0000", &[coin(1000000u128, "uusd")]);
+    let res = execute(deps.as_mut(), mock_env(), info, swap_msg.clone()).unwrap();
+    assert_ne!(route_id_of(&res), route_id);
+
+    // the nonce is reset in the next block
+    let mut env = mock_env();
+    env.block.height += 1;
+    let info = mock_info("addr0000", &[coin(1000000u128, "uusd")]);
+    let res = execute(deps.as_mut(), env, info, swap_msg).unwrap();
+    assert_ne!(route_id_of(&res), route_id);
+}
+
+#[test]
+fn migrate_with_invalid_contract_name() {
+    let mut deps = mock_dependencies(&[]);
+    default_instantiate(deps.as_mut());
+    set_contract_version(deps.as_mut().storage, "crates.io:terraswap-pair", "0.2.0").unwrap();
+
+    let res = migrate(deps.as_mut(), mock_env(), MigrateMsg {});
+    match res {
+        Err(err) => assert_eq!(err, ContractError::InvalidContract {}),
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+}
+
+#[test]
+fn migrate_same_version() {
+    let mut deps = mock_dependencies(&[]);
+    default_instantiate(deps.as_mut());
+    let version = get_contract_version(deps.as_ref().storage).unwrap();
+
+    let res = migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+    assert_eq!(res, Response::default());
+    assert_eq!(
+        get_contract_version(deps.as_ref().storage).unwrap(),
+        version
+    );
+
+    // unknown versions are rejected
+    set_contract_version(deps.as_mut().storage, version.contract, "0.0.1").unwrap();
+    let res = migrate(deps.as_mut(), mock_env(), MigrateMsg {});
+    match res {
+        Err(err) => assert_eq!(
+            err,
+            ContractError::UnsupportedMigration {
+                version: "0.0.1".to_string()
+            }
+        ),
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+}
+
+#[test]
+fn migrate_from_v0_1_0() {
+    let mut deps = mock_dependencies(&[]);
+
+    #[derive(serde::Serialize, serde::Deserialize)]
+    struct ConfigV0_1_0 {
+        terraswap_factory: CanonicalAddr,
+        loop_factory: CanonicalAddr,
+        astroport_factory: CanonicalAddr,
+    }
+
+    let api = deps.api;
+    Item::new("config")
+        .save(
+            deps.as_mut().storage,
+            &ConfigV0_1_0 {
+                terraswap_factory: api.addr_canonicalize("terraswapfactory").unwrap(),
+                loop_factory: api.addr_canonicalize("loopfactory").unwrap(),
+                astroport_factory: api.addr_canonicalize("astroportfactory").unwrap(),
+            },
+        )
+        .unwrap();
+    set_contract_version(deps.as_mut().storage, "crates.io:terraswap-router", "0.1.0").unwrap();
+
+    // the owner is taken from the contract admin
+    let res = migrate(deps.as_mut(), mock_env(), MigrateMsg {});
+    assert!(res.is_err());
+
+    deps.querier
+        .with_contract_admin(MOCK_CONTRACT_ADDR, Some("admin0000".to_string()));
+    let _res = migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+
+    let config: ConfigResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
+    assert_eq!("admin0000", config.owner.as_str());
+    assert_eq!("terraswapfactory", config.terraswap_factory.as_str());
+    assert_eq!("loopfactory", config.loop_factory.as_str());
+    assert_eq!("astroportfactory", config.astroport_factory.as_str());
+    assert!(config.dexes.iter().all(|dex| dex.enabled));
+    assert_eq!(
+        get_contract_version(deps.as_ref().storage).unwrap().version,
+        env!("CARGO_PKG_VERSION")
+    );
+}
+
+#[test]
+fn build_swap_messages() {
+    let mut deps = mock_dependencies(&[coin(1000000u128, "uusd")]);
+    default_instantiate(deps.as_mut());
+    deps.querier.with_tax(
+        Decimal::percent(5),
+        &[(&"uusd".to_string(), &Uint128::from(1000000u128))],
+    );
+    deps.querier.with_token_balances(&[(
+        &"asset0001".to_string(),
+        &[(&"addr0001".to_string(), &Uint128::from(1000000u128))],
+    )]);
+
+    let operations = vec![
+        SwapOperation::NativeSwap {
+            offer_denom: "uusd".to_string(),
+            ask_denom: "ukrw".to_string(),
+        },
+        SwapOperation::TerraSwap {
+            offer_asset_info: AssetInfo::NativeToken {
+                denom: "ukrw".to_string(),
+            },
+            ask_asset_info: AssetInfo::Token {
+                contract_addr: "asset0001".to_string(),
+            },
+        },
+    ];
+
+    let assert_same_messages =
+        |deps: DepsMut<TerraQuery>, offer_amount: Option<Uint128>, funds: Vec<Coin>| {
+            let built: Vec<CosmosMsg<TerraMsg>> = from_binary(
+                &query(
+                    deps.as_ref(),
+                    mock_env(),
+                    QueryMsg::BuildSwapMessages {
+                        sender: "addr0000".to_string(),
+                        operations: operations.clone(),
+                        minimum_receive: Some(Uint128::from(1u128)),
+                        to: Some("addr0001".to_string()),
+                        deadline: None,
+                        offer_amount,
+                        funds: funds.clone(),
+                    },
+                )
+                .unwrap(),
+            )
+            .unwrap();
+
+            let info = mock_info("addr0000", &funds);
+            let res = execute(
+                deps,
+                mock_env(),
+                info,
+                ExecuteMsg::ExecuteSwapOperations {
+                    operations: operations.clone(),
+                    minimum_receive: Some(Uint128::from(1u128)),
+                    minimum_receive_price: None,
+                    to: Some("addr0001".to_string()),
+                    deadline: None,
+                    offer_amount,
+                    auto_unwind: false,
+                    unwind_receiver: None,
+                },
+            )
+            .unwrap();
+
+            assert_eq!(
+                built,
+                res.messages
+                    .into_iter()
+                    .map(|msg| msg.msg)
+                    .collect::<Vec<CosmosMsg<TerraMsg>>>()
+            );
+        };
+
+    assert_same_messages(deps.as_mut(), None, vec![coin(1000000u128, "uusd")]);
+
+    set_routing_fee(deps.as_mut(), FeeSide::Offer);
+    assert_same_messages(deps.as_mut(), None, vec![coin(1000000u128, "uusd")]);
+    assert_same_messages(deps.as_mut(), Some(Uint128::from(500000u128)), vec![]);
+
+    set_routing_fee(deps.as_mut(), FeeSide::Ask);
+    assert_same_messages(deps.as_mut(), None, vec![coin(1000000u128, "uusd")]);
+
+    // nothing is executed by the query
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::BuildSwapMessages {
+            sender: "addr0000".to_string(),
+            operations: operations.clone(),
+            minimum_receive: None,
+            to: None,
+            deadline: None,
+            offer_amount: Some(Uint128::from(1000001u128)),
+            funds: vec![],
+        },
+    );
+    match res {
+        Err(err) => assert_eq!(
+            err,
+            ContractError::InsufficientOfferBalance {
+                offer_amount: Uint128::from(1000001u128),
+                balance: Uint128::from(1000000u128)
+            }
+        ),
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+}
+
+#[test]
+fn set_dex_enabled() {
+    let mut deps = mock_dependencies(&[]);
+    default_instantiate(deps.as_mut());
+
+    let msg = ExecuteMsg::SetDexEnabled {
+        dex: "loop".to_string(),
+        enabled: false,
+    };
+
+    // only owner can switch the dex
+    let info = mock_info("addr0001", &[]);
+    let res = execute(deps.as_mut(), mock_env(), info, msg.clone());
+    match res {
+        Err(err) => assert_eq!(err, ContractError::Unauthorized {}),
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+
+    let info = mock_info("addr0000", &[]);
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::SetDexEnabled {
+            dex: "unknown".to_string(),
+            enabled: false,
+        },
+    );
+    match res {
+        Err(err) => assert_eq!(
+            err,
+            ContractError::UnknownDex {
+                dex: "unknown".to_string()
+            }
+        ),
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+
+    let info = mock_info("addr0000", &[]);
+    let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    let config: ConfigResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
+    assert_eq!(
+        config.dexes,
+        vec![
+            DexStatus {
+                dex: "terraswap".to_string(),
+                enabled: true,
+                msg_format: MsgFormat::Classic,
+            },
+            DexStatus {
+                dex: "loop".to_string(),
+                enabled: false,
+                msg_format: MsgFormat::Classic,
+            },
+            DexStatus {
+                dex: "astroport".to_string(),
+                enabled: true,
+                msg_format: MsgFormat::Classic,
+            },
+        ]
+    );
+}
+
+#[test]
+fn dex_msg_format() {
+    let mut deps = mock_dependencies(&[]);
+    default_instantiate(deps.as_mut());
+    deps.querier.with_terraswap_factory(
+        &[(
+            &"uusdasset0000".to_string(),
+            &PairInfo {
+                asset_infos: [
+                    AssetInfo::NativeToken {
+                        denom: "uusd".to_string(),
+                    },
+                    AssetInfo::Token {
+                        contract_addr: "asset0000".to_string(),
+                    },
+                ],
+                contract_addr: "pair0000".to_string(),
+                liquidity_token: "liquidity0000".to_string(),
+                asset_decimals: [6u8, 6u8],
+                created_at: None,
+            },
+        )],
+        &[("uusd".to_string(), 6u8)],
+    );
+
+    let msg = ExecuteMsg::SetDexMsgFormat {
+        dex: "astroport".to_string(),
+        msg_format: MsgFormat::AstroportV2,
+    };
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0001", &[]),
+        msg.clone(),
+    );
+    assert_eq!(res.unwrap_err(), ContractError::Unauthorized {});
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        ExecuteMsg::SetDexMsgFormat {
+            dex: "unknown".to_string(),
+            msg_format: MsgFormat::AstroportV2,
+        },
+    );
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::UnknownDex {
+            dex: "unknown".to_string()
+        }
+    );
+    let res = execute(deps.as_mut(), mock_env(), mock_info("addr0000", &[]), msg).unwrap();
+    assert_eq!(
+        res.attributes,
+        vec![
+            attr("action", "set_dex_msg_format"),
+            attr("dex", "astroport"),
+            attr("msg_format", "astroport_v2"),
+        ]
+    );
+
+    let config: ConfigResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
+    let msg_formats: Vec<MsgFormat> = config.dexes.into_iter().map(|d| d.msg_format).collect();
+    assert_eq!(
+        msg_formats,
+        vec![
+            MsgFormat::Classic,
+            MsgFormat::Classic,
+            MsgFormat::AstroportV2
+        ]
+    );
+
+    deps.querier.with_token_balances(&[(
+        &"asset0000".to_string(),
+        &[(&MOCK_CONTRACT_ADDR.to_string(), &Uint128::zero())],
+    )]);
+
+    let uusd = AssetInfo::NativeToken {
+        denom: "uusd".to_string(),
+    };
+    let token = AssetInfo::Token {
+        contract_addr: "asset0000".to_string(),
+    };
+    let mut swap_msg = |operation: SwapOperation| {
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(MOCK_CONTRACT_ADDR, &[]),
+            ExecuteMsg::ExecuteSwapOperation {
+                operation,
+                to: None,
+                deadline: None,
+                offer_amount: Some(Uint128::from(1000u128)),
+                route_id: None,
+                hop_index: None,
+            },
+        )
+        .unwrap();
+        match &res.messages[0].msg {
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr, msg, ..
+            }) => (contract_addr.to_string(), msg.clone()),
+            _ => panic!("DO NOT ENTER HERE"),
+        }
+    };
+
+    // terraswap keeps the classic format
+    let (contract_addr, msg) = swap_msg(SwapOperation::TerraSwap {
+        offer_asset_info: uusd.clone(),
+        ask_asset_info: token.clone(),
+    });
+    assert_eq!(contract_addr, "pair0000");
+    assert_eq!(
+        msg,
+        Binary::from(
+            br#"{"swap":{"offer_asset":{"info":{"native_token":{"denom":"uusd"}},"amount":"1000"},"belief_price":null,"belief_price_direction":null,"max_spread":null,"to":null,"deadline":null,"to_msg":null,"referral":null}}"#
+                .to_vec()
+        )
+    );
+
+    let (contract_addr, msg) = swap_msg(SwapOperation::Astroport {
+        offer_asset_info: uusd.clone(),
+        ask_asset_info: token.clone(),
+    });
+    assert_eq!(contract_addr, "pair0000");
+    assert_eq!(
+        msg,
+        Binary::from(
+            br#"{"swap":{"offer_asset":{"info":{"native_token":{"denom":"uusd"}},"amount":"1000"},"ask_asset_info":{"token":{"contract_addr":"asset0000"}},"belief_price":null,"max_spread":"0.5","to":null}}"#
+                .to_vec()
+        )
+    );
+
+    let (contract_addr, msg) = swap_msg(SwapOperation::Astroport {
+        offer_asset_info: token.clone(),
+        ask_asset_info: uusd.clone(),
+    });
+    assert_eq!(contract_addr, "asset0000");
+    assert_eq!(
+        msg,
+        to_binary(&Cw20ExecuteMsg::Send {
+            contract: "pair0000".to_string(),
+            amount: Uint128::from(1000u128),
+            msg: Binary::from(
+                br#"{"swap":{"ask_asset_info":{"native_token":{"denom":"uusd"}},"belief_price":null,"max_spread":"0.5","to":null}}"#
+                    .to_vec()
+            ),
+        })
+        .unwrap()
+    );
+
+    // simulations query astroport pairs in the same format
+    let res: SimulateSwapOperationsResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::SimulateSwapOperations {
+                offer_amount: Uint128::from(1000u128),
+                operations: vec![SwapOperation::Astroport {
+                    offer_asset_info: token,
+                    ask_asset_info: uusd,
+                }],
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(res.amount, Uint128::from(1000u128));
+}
+
+#[test]
+fn route_through_disabled_dex() {
+    let mut deps = mock_dependencies(&[]);
+    default_instantiate(deps.as_mut());
+
+    let info = mock_info("addr0000", &[]);
+    let _res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::SetDexEnabled {
+            dex: "loop".to_string(),
+            enabled: false,
+        },
+    )
+    .unwrap();
+
+    let operations = vec![
+        SwapOperation::TerraSwap {
+            offer_asset_info: AssetInfo::NativeToken {
+                denom: "uusd".to_string(),
+            },
+            ask_asset_info: AssetInfo::Token {
+                contract_addr: "asset0000".to_string(),
+            },
+        },
+        SwapOperation::Loop {
+            offer_asset_info: AssetInfo::Token {
+                contract_addr: "asset0000".to_string(),
+            },
+            ask_asset_info: AssetInfo::Token {
+                contract_addr: "asset0001".to_string(),
+            },
+        },
+        SwapOperation::Astroport {
+            offer_asset_info: AssetInfo::Token {
+                contract_addr: "asset0001".to_string(),
+            },
+            ask_asset_info: AssetInfo::NativeToken {
+                denom: "uluna".to_string(),
+            },
+        },
+    ];
+
+    let info = mock_info("addr0000", &[coin(1000000u128, "uusd")]);
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::ExecuteSwapOperations {
+            operations: operations.clone(),
+            minimum_receive: None,
+            minimum_receive_price: None,
+            to: None,
+            deadline: None,
+            offer_amount: None,
+            auto_unwind: false,
+            unwind_receiver: None,
+        },
+    );
+    match res {
+        Err(err) => assert_eq!(
+            err,
+            ContractError::DexDisabled {
+                dex: "loop".to_string()
+            }
+        ),
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::SimulateSwapOperations {
+            offer_amount: Uint128::from(1000000u128),
+            operations: operations.clone(),
+        },
+    );
+    match res {
+        Err(err) => assert_eq!(
+            err,
+            ContractError::DexDisabled {
+                dex: "loop".to_string()
+            }
+        ),
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::ReverseSimulateSwapOperations {
+            ask_amount: Uint128::from(1000000u128),
+            operations: operations.clone(),
+        },
+    );
+    match res {
+        Err(err) => assert_eq!(
+            err,
+            ContractError::DexDisabled {
+                dex: "loop".to_string()
+            }
+        ),
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+
+    // enabling the dex again allows the route
+    let info = mock_info("addr0000", &[]);
+    let _res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::SetDexEnabled {
+            dex: "loop".to_string(),
+            enabled: true,
+        },
+    )
+    .unwrap();
+
+    let info = mock_info("addr0000", &[coin(1000000u128, "uusd")]);
+    let _res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::ExecuteSwapOperations {
+            operations,
+            minimum_receive: None,
+            minimum_receive_price: None,
+            to: None,
+            deadline: None,
+            offer_amount: None,
+            auto_unwind: false,
+            unwind_receiver: None,
+        },
+    )
+    .unwrap();
+}
+
+#[test]
+fn reverse_simulation_covers_ask_side_tax() {
+    let mut deps = mock_dependencies(&[]);
+    default_instantiate(deps.as_mut());
+
+    // set tax rate as 0.5% with a cap of 10000
+    deps.querier.with_tax(
+        Decimal::permille(5),
+        &[(&"uusd".to_string(), &Uint128::from(10000u128))],
+    );
+    deps.querier.with_terraswap_factory(
+        &[(
+            &"asset0000uusd".to_string(),
+            &PairInfo {
+                asset_infos: [
+                    AssetInfo::Token {
+                        contract_addr: "asset0000".to_string(),
+                    },
+                    AssetInfo::NativeToken {
+                        denom: "uusd".to_string(),
+                    },
+                ],
+                contract_addr: "pair0000".to_string(),
+                liquidity_token: "liquidity0000".to_string(),
+                asset_decimals: [6u8, 6u8],
+                created_at: None,
+            },
+        )],
+        &[("uusd".to_string(), 6u8)],
+    );
+
+    let operations = vec![SwapOperation::TerraSwap {
+        offer_asset_info: AssetInfo::Token {
+            contract_addr: "asset0000".to_string(),
+        },
+        ask_asset_info: AssetInfo::NativeToken {
+            denom: "uusd".to_string(),
+        },
+    }];
+
+    // 1000000 is taxed by the rate, 10000000 by the cap
+    for (ask_amount, expected_offer_amount) in
+        [(1000000u128, 1005000u128), (10000000u128, 10010000u128)]
+    {
+        let res: SimulateSwapOperationsResponse = from_binary(
+            &query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::ReverseSimulateSwapOperations {
+                    ask_amount: Uint128::from(ask_amount),
+                    operations: operations.clone(),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(res.amount, Uint128::from(expected_offer_amount));
+
+        let res: SimulateSwapOperationsResponse = from_binary(
+            &query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::SimulateSwapOperations {
+                    offer_amount: res.amount,
+                    operations: operations.clone(),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(res.amount, Uint128::from(ask_amount));
+    }
+}
+
+#[test]
+fn check_swap_viability() {
+    let mut deps = mock_dependencies(&[]);
+    default_instantiate(deps.as_mut());
+
+    deps.querier.with_terraswap_factory(
+        &[(
+            &"asset0000asset0001".to_string(),
+            &PairInfo {
+                asset_infos: [
+                    AssetInfo::Token {
+                        contract_addr: "asset0000".to_string(),
+                    },
+                    AssetInfo::Token {
+                        contract_addr: "asset0001".to_string(),
+                    },
+                ],
+                contract_addr: "pair0000".to_string(),
+                liquidity_token: "liquidity0000".to_string(),
+                asset_decimals: [6u8, 6u8],
+                created_at: None,
+            },
+        )],
+        &[],
+    );
+
+    let operations = vec![SwapOperation::TerraSwap {
+        offer_asset_info: AssetInfo::Token {
+            contract_addr: "asset0000".to_string(),
+        },
+        ask_asset_info: AssetInfo::Token {
+            contract_addr: "asset0001".to_string(),
+        },
+    }];
+
+    // the mock pair returns the offer amount, 1000000
+    for (belief_price, max_spread, viable, required_return) in [
+        ("1", "0.01", true, 990000u128),
+        // spread of exactly max_spread is accepted
+        ("0.5", "0.5", true, 1000000u128),
+        ("0.5", "0.499999999999999999", false, 1000001u128),
+        // 1 / 0.3 is floored to 18 decimals and the belief return to 3333333
+        ("0.3", "0.7", true, 1000000u128),
+        ("0.3", "0.69", false, 1033334u128),
+    ] {
+        let res: CheckSwapViabilityResponse = from_binary(
+            &query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::CheckSwapViability {
+                    operations: operations.clone(),
+                    offer_amount: Uint128::from(1000000u128),
+                    belief_price: Decimal::from_str(belief_price).unwrap(),
+                    max_spread: Decimal::from_str(max_spread).unwrap(),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            res,
+            CheckSwapViabilityResponse {
+                viable,
+                expected_return: Uint128::from(1000000u128),
+                required_return: Uint128::from(required_return),
+            }
+        );
+    }
+
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::CheckSwapViability {
+            operations,
+            offer_amount: Uint128::from(1000000u128),
+            belief_price: Decimal::zero(),
+            max_spread: Decimal::percent(1),
+        },
+    );
+    match res {
+        Err(err) => assert_eq!(err, ContractError::InvalidBeliefPrice {}),
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+}
+
+#[test]
+fn compute_tax_queries() {
+    let mut deps = mock_dependencies(&[]);
+    default_instantiate(deps.as_mut());
+
+    // set tax rate as 1% with a cap of 10000
+    deps.querier.with_tax(
+        Decimal::percent(1),
+        &[(&"uusd".to_string(), &Uint128::from(10000u128))],
+    );
+
+    let query_tax = |deps: Deps<TerraQuery>, reverse: bool, info: AssetInfo, amount: u128| {
+        let asset = Asset {
+            info,
+            amount: Uint128::from(amount),
+        };
+        let msg = if reverse {
+            QueryMsg::ComputeReverseTax { asset }
+        } else {
+            QueryMsg::ComputeTax { asset }
+        };
+        from_binary::<TaxResponse>(&query(deps, mock_env(), msg).unwrap()).unwrap()
+    };
+    let uusd = AssetInfo::NativeToken {
+        denom: "uusd".to_string(),
+    };
+
+    // 1010000 is taxed exactly the cap
+    for (amount, tax_amount) in [
+        (1000000u128, 9901u128),
+        (1010000u128, 10000u128),
+        (2000000u128, 10000u128),
+    ] {
+        assert_eq!(
+            query_tax(deps.as_ref(), false, uusd.clone(), amount),
+            TaxResponse {
+                tax_amount: Uint128::from(tax_amount),
+                pre_tax_amount: Uint128::from(amount),
+                post_tax_amount: Uint128::from(amount - tax_amount),
+            }
+        );
+    }
+
+    // 1000000 is received after paying exactly the cap
+    for (amount, tax_amount) in [
+        (999999u128, 9999u128),
+        (1000000u128, 10000u128),
+        (2000000u128, 10000u128),
+    ] {
+        assert_eq!(
+            query_tax(deps.as_ref(), true, uusd.clone(), amount),
+            TaxResponse {
+                tax_amount: Uint128::from(tax_amount),
+                pre_tax_amount: Uint128::from(amount + tax_amount),
+                post_tax_amount: Uint128::from(amount),
+            }
+        );
+    }
+
+    // cw20 tokens are not taxed
+    let token = AssetInfo::Token {
+        contract_addr: "asset0000".to_string(),
+    };
+    for reverse in [false, true] {
+        assert_eq!(
+            query_tax(deps.as_ref(), reverse, token.clone(), 1000000u128),
+            TaxResponse {
+                tax_amount: Uint128::zero(),
+                pre_tax_amount: Uint128::from(1000000u128),
+                post_tax_amount: Uint128::from(1000000u128),
+            }
+        );
+    }
+
+    // zero tax rate
+    deps.querier.with_tax(
+        Decimal::zero(),
+        &[(&"uusd".to_string(), &Uint128::from(10000u128))],
+    );
+    for reverse in [false, true] {
+        assert_eq!(
+            query_tax(deps.as_ref(), reverse, uusd.clone(), 1000000u128),
+            TaxResponse {
+                tax_amount: Uint128::zero(),
+                pre_tax_amount: Uint128::from(1000000u128),
+                post_tax_amount: Uint128::from(1000000u128),
+            }
+        );
+    }
+}
+
+#[test]
+fn tax_rate_change_between_simulation_and_execution() {
+    let mut deps = mock_dependencies(&[coin(1000000u128, "uusd")]);
+    default_instantiate(deps.as_mut());
+    deps.querier.with_terraswap_factory(
+        &[(
+            &"uusdasset0000".to_string(),
+            &PairInfo {
+                asset_infos: [
+                    AssetInfo::NativeToken {
+                        denom: "uusd".to_string(),
+                    },
+                    AssetInfo::Token {
+                        contract_addr: "asset0000".to_string(),
+                    },
+                ],
+                contract_addr: "pair0000".to_string(),
+                liquidity_token: "liquidity0000".to_string(),
+                asset_decimals: [6u8, 6u8],
+                created_at: None,
+            },
+        )],
+        &[("uusd".to_string(), 6u8)],
+    );
+
+    let operation = SwapOperation::TerraSwap {
+        offer_asset_info: AssetInfo::NativeToken {
+            denom: "uusd".to_string(),
+        },
+        ask_asset_info: AssetInfo::Token {
+            contract_addr: "asset0000".to_string(),
+        },
+    };
+    let simulate = |deps: Deps<TerraQuery>| {
+        from_binary::<SimulateSwapOperationsResponse>(
+            &query(
+                deps,
+                mock_env(),
+                QueryMsg::SimulateSwapOperations {
+                    offer_amount: Uint128::from(1000000u128),
+                    operations: vec![operation.clone()],
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap()
+        .amount
+    };
+
+    // the route is simulated without tax
+    let minimum_receive = simulate(deps.as_ref());
+    assert_eq!(minimum_receive, Uint128::from(1000000u128));
+
+    // governance sets a tax of 1% on uusd before the route executes
+    deps.querier.with_tax_rate(Decimal::percent(1));
+    deps.querier
+        .with_tax_cap("uusd", Uint128::from(1000000u128));
+    assert_eq!(simulate(deps.as_ref()), Uint128::from(990099u128));
+
+    let info = mock_info(MOCK_CONTRACT_ADDR, &[]);
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::ExecuteSwapOperation {
+            operation: operation.clone(),
+            to: Some("addr0000".to_string()),
+            deadline: None,
+            offer_amount: None,
+            route_id: None,
+            hop_index: None,
+        },
+    )
+    .unwrap();
+    match &res.messages[0].msg {
+        CosmosMsg::Wasm(WasmMsg::Execute { funds, .. }) => {
+            assert_eq!(funds, &vec![coin(990099u128, "uusd")])
+        }
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+
+    // the swap returns less than the simulated minimum
+    deps.querier.with_token_balances(&[(
+        &"asset0000".to_string(),
+        &[(&"addr0000".to_string(), &Uint128::from(990099u128))],
+    )]);
+    let info = mock_info(MOCK_CONTRACT_ADDR, &[]);
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::AssertMinimumReceive {
+            asset_info: AssetInfo::Token {
+                contract_addr: "asset0000".to_string(),
+            },
+            prev_balance: Uint128::zero(),
+            minimum_receive,
+            receiver: "addr0000".to_string(),
+        },
+    );
+    assert_eq!(
+        res,
+        Err(ContractError::MinimumReceiveAssertion {
+            expected: minimum_receive,
+            actual: Uint128::from(990099u128),
+        })
+    );
+}
+
+#[test]
+fn execute_swap_operations_with_minimum_receive_price() {
+    let mut deps = mock_dependencies(&[]);
+    default_instantiate(deps.as_mut());
+
+    let operations = vec![SwapOperation::TerraSwap {
+        offer_asset_info: AssetInfo::Token {
+            contract_addr: "asset0000".to_string(),
+        },
+        ask_asset_info: AssetInfo::NativeToken {
+            denom: "uusd".to_string(),
+        },
+    }];
+    let hook = |minimum_receive: Option<Uint128>| {
+        ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: "addr0000".to_string(),
+            amount: Uint128::from(1234567u128),
+            msg: to_binary(&Cw20HookMsg::ExecuteSwapOperations {
+                operations: operations.clone(),
+                minimum_receive,
+                minimum_receive_price: Some(Decimal::percent(50)),
+                to: None,
+                deadline: None,
+                auto_unwind: false,
+                unwind_receiver: None,
+            })
+            .unwrap(),
+        })
+    };
+
+    // the offer amount is the amount of the cw20 hook, the minimum is rounded up
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("asset0000", &[]),
+        hook(None),
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages.last().unwrap(),
+        &SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: MOCK_CONTRACT_ADDR.to_string(),
+            funds: vec![],
+            msg: to_binary(&ExecuteMsg::AssertMinimumReceive {
+                asset_info: AssetInfo::NativeToken {
+                    denom: "uusd".to_string(),
+                },
+                prev_balance: Uint128::zero(),
+                minimum_receive: Uint128::from(617284u128),
+                receiver: "addr0000".to_string(),
+            })
+            .unwrap(),
+        }))
+    );
+
+    // both forms are refused
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("asset0000", &[]),
+        hook(Some(Uint128::from(617284u128))),
+    );
+    match res {
+        Err(err) => assert_eq!(err, ContractError::MinimumReceiveConflict {}),
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+
+    // a native offer is priced from the attached funds
+    let msg = ExecuteMsg::ExecuteSwapOperations {
+        operations: vec![SwapOperation::NativeSwap {
+            offer_denom: "uusd".to_string(),
+            ask_denom: "ukrw".to_string(),
+        }],
+        minimum_receive: None,
+        minimum_receive_price: Some(Decimal::from_str("1200").unwrap()),
+        to: None,
+        deadline: None,
+        offer_amount: None,
+        auto_unwind: false,
+        unwind_receiver: None,
+    };
+    let info = mock_info("addr0000", &[coin(1000u128, "uusd")]);
+    let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    assert_eq!(
+        res.messages.last().unwrap(),
+        &SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: MOCK_CONTRACT_ADDR.to_string(),
+            funds: vec![],
+            msg: to_binary(&ExecuteMsg::AssertMinimumReceive {
+                asset_info: AssetInfo::NativeToken {
+                    denom: "ukrw".to_string(),
+                },
+                prev_balance: Uint128::zero(),
+                minimum_receive: Uint128::from(1200000u128),
+                receiver: "addr0000".to_string(),
+            })
+            .unwrap(),
+        }))
+    );
+}
+
+#[test]
+fn simulate_route_tax() {
+    let mut deps = mock_dependencies(&[]);
+    default_instantiate(deps.as_mut());
+    let uusd = AssetInfo::NativeToken {
+        denom: "uusd".to_string(),
+    };
+    let uluna = AssetInfo::NativeToken {
+        denom: "uluna".to_string(),
+    };
+    let token = AssetInfo::Token {
+        contract_addr: "asset0000".to_string(),
+    };
+    let pair_info = |asset_infos: [AssetInfo; 2], contract_addr: &str| PairInfo {
+        asset_infos,
+        contract_addr: contract_addr.to_string(),
+        liquidity_token: "liquidity0000".to_string(),
+        asset_decimals: [6u8, 6u8],
+        created_at: None,
+    };
+    deps.querier.with_terraswap_factory(
+        &[
+            (
+                &"uusdasset0000".to_string(),
+                &pair_info([uusd.clone(), token.clone()], "pair0000"),
+            ),
+            (
+                &"asset0000uluna".to_string(),
+                &pair_info([token.clone(), uluna.clone()], "pair0001"),
+            ),
+        ],
+        &[("uusd".to_string(), 6u8), ("uluna".to_string(), 6u8)],
+    );
+    deps.querier.with_tax(
+        Decimal::percent(1),
+        &[
+            (&"uusd".to_string(), &Uint128::from(1000000u128)),
+            (&"uluna".to_string(), &Uint128::from(5000u128)),
+        ],
+    );
+
+    // uusd is taxed into the first pair, uluna out of the second pair and into the market swap
+    let operations = vec![
+        SwapOperation::terraswap(uusd, token.clone()),
+        SwapOperation::terraswap(token, uluna),
+        SwapOperation::native_swap("uluna", "ukrw"),
+    ];
+    let res: SimulateRouteTaxResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::SimulateRouteTax {
+                offer_amount: Uint128::from(1000000u128),
+                operations: operations.clone(),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        res,
+        SimulateRouteTaxResponse {
+            amount: Uint128::from(980099u128),
+            taxes: vec![
+                RouteTax {
+                    hop_index: 1,
+                    denom: "uusd".to_string(),
+                    tax_amount: Uint128::from(9901u128),
+                },
+                RouteTax {
+                    hop_index: 2,
+                    denom: "uluna".to_string(),
+                    tax_amount: Uint128::from(5000u128),
+                },
+                RouteTax {
+                    hop_index: 3,
+                    denom: "uluna".to_string(),
+                    tax_amount: Uint128::from(5000u128),
+                },
+            ],
+            total: vec![coin(10000u128, "uluna"), coin(9901u128, "uusd")],
+        }
+    );
+
+    // the same return as the simulation
+    let simulation: SimulateSwapOperationsResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::SimulateSwapOperations {
+                offer_amount: Uint128::from(1000000u128),
+                operations,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(simulation.amount, res.amount);
+}
+
+#[test]
+fn token_transfer_fee() {
+    let mut deps = mock_dependencies(&[]);
+    default_instantiate(deps.as_mut());
+    let uusd = AssetInfo::NativeToken {
+        denom: "uusd".to_string(),
+    };
+    let uluna = AssetInfo::NativeToken {
+        denom: "uluna".to_string(),
+    };
+    let token = AssetInfo::Token {
+        contract_addr: "asset0000".to_string(),
+    };
+    let pair_info = |asset_infos: [AssetInfo; 2], contract_addr: &str| PairInfo {
+        asset_infos,
+        contract_addr: contract_addr.to_string(),
+        liquidity_token: "liquidity0000".to_string(),
+        asset_decimals: [6u8, 6u8],
+        created_at: None,
+    };
+    deps.querier.with_terraswap_factory(
+        &[
+            (
+                &"ulunaasset0000".to_string(),
+                &pair_info([uluna.clone(), token.clone()], "pair0000"),
+            ),
+            (
+                &"asset0000uusd".to_string(),
+                &pair_info([token.clone(), uusd.clone()], "pair0001"),
+            ),
+        ],
+        &[("uusd".to_string(), 6u8), ("uluna".to_string(), 6u8)],
+    );
+    deps.querier.with_token_balances(&[(
+        &"asset0000".to_string(),
+        &[(&"addr0000".to_string(), &Uint128::from(990000u128))],
+    )]);
+
+    // only owner can register a transfer fee, below 100%
+    let msg = |bps: u16| ExecuteMsg::SetTokenTransferFee {
+        token: "asset0000".to_string(),
+        bps,
+    };
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0001", &[]),
+        msg(100),
+    );
+    assert_eq!(res, Err(ContractError::Unauthorized {}));
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        msg(10000),
+    );
+    assert_eq!(res, Err(ContractError::InvalidTransferFeeBps {}));
+
+    let simulate = |deps: Deps<TerraQuery>| -> Uint128 {
+        let res: SimulateSwapOperationsResponse = from_binary(
+            &query(
+                deps,
+                mock_env(),
+                QueryMsg::SimulateSwapOperations {
+                    offer_amount: Uint128::from(1000000u128),
+                    operations: vec![
+                        SwapOperation::terraswap(uluna.clone(), token.clone()),
+                        SwapOperation::terraswap(token.clone(), uusd.clone()),
+                    ],
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        res.amount
+    };
+    let assert_msg = |minimum_receive: u128| ExecuteMsg::AssertMinimumReceive {
+        asset_info: token.clone(),
+        prev_balance: Uint128::zero(),
+        minimum_receive: Uint128::from(minimum_receive),
+        receiver: "addr0000".to_string(),
+    };
+
+    // unregistered tokens are quoted and asserted as they are
+    assert_eq!(simulate(deps.as_ref()), Uint128::from(1000000u128));
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        assert_msg(1000000),
+    );
+    assert_eq!(
+        res,
+        Err(ContractError::MinimumReceiveAssertion {
+            expected: Uint128::from(1000000u128),
+            actual: Uint128::from(990000u128),
+        })
+    );
+
+    // a 1% fee is taken from the token returned by the first hop
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        msg(100),
+    )
+    .unwrap();
+    assert!(res.attributes.contains(&attr("bps", "100")));
+    assert_eq!(simulate(deps.as_ref()), Uint128::from(990000u128));
+
+    let res: SimulateSwapOperationsResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::ReverseSimulateSwapOperations {
+                ask_amount: Uint128::from(990000u128),
+                operations: vec![
+                    SwapOperation::terraswap(uluna.clone(), token.clone()),
+                    SwapOperation::terraswap(token.clone(), uusd.clone()),
+                ],
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(res.amount, Uint128::from(1000000u128));
+
+    // the assertion still asks the whole minimum, the fee is only quoted by the simulations
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        assert_msg(1000000),
+    );
+    assert_eq!(
+        res,
+        Err(ContractError::MinimumReceiveAssertion {
+            expected: Uint128::from(1000000u128),
+            actual: Uint128::from(990000u128),
+        })
+    );
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        assert_msg(990000),
+    )
+    .unwrap();
+
+    // 0 removes the fee
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        msg(0),
+    )
+    .unwrap();
+    assert_eq!(simulate(deps.as_ref()), Uint128::from(1000000u128));
+}
+
+#[test]
+fn price_guard() {
+    let mut deps = mock_dependencies(&[]);
+    default_instantiate(deps.as_mut());
+    let uluna = AssetInfo::NativeToken {
+        denom: "uluna".to_string(),
+    };
+    let uusd = AssetInfo::NativeToken {
+        denom: "uusd".to_string(),
+    };
+    let token = AssetInfo::Token {
+        contract_addr: "asset0000".to_string(),
+    };
+    let pair_info = |asset_infos: [AssetInfo; 2], contract_addr: &str| PairInfo {
+        asset_infos,
+        contract_addr: contract_addr.to_string(),
+        liquidity_token: "liquidity0000".to_string(),
+        asset_decimals: [6u8, 6u8],
+        created_at: None,
+    };
+    let pool = |uluna_amount: u128, token_amount: u128| {
+        [
+            Asset {
+                info: uluna.clone(),
+                amount: Uint128::from(uluna_amount),
+            },
+            Asset {
+                info: token.clone(),
+                amount: Uint128::from(token_amount),
+            },
+        ]
+    };
+    deps.querier.with_terraswap_factory(
+        &[
+            (
+                &"ulunaasset0000".to_string(),
+                &pair_info([uluna.clone(), token.clone()], "pair0000"),
+            ),
+            (
+                &"asset0000uluna".to_string(),
+                &pair_info([uluna.clone(), token.clone()], "pair0000"),
+            ),
+        ],
+        &[("uluna".to_string(), 6u8)],
+    );
+    deps.querier.with_pair_info(
+        "pair0001",
+        &pair_info([uluna.clone(), token.clone()], "pair0001"),
+    );
+    deps.querier
+        .with_pair_info("pair0002", &pair_info([uluna.clone(), uusd], "pair0002"));
+    deps.querier
+        .with_pair_pool("pair0000", pool(1000000, 2000000));
+    deps.querier
+        .with_pair_pool("pair0001", pool(1000000, 1500000));
+
+    let set_guard =
+        |reference: ReferenceSource, max_deviation: Decimal| ExecuteMsg::SetPriceGuard {
+            offer_asset_info: uluna.clone(),
+            ask_asset_info: token.clone(),
+            guard: Some(GuardConfig {
+                reference,
+                max_deviation,
+            }),
+        };
+    let constant = |price: u64| ReferenceSource::Constant {
+        price: Decimal::from_ratio(price, 10u64),
+    };
+
+    // only owner can set a guard, with a reference of the assets
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0001", &[]),
+        set_guard(constant(20), Decimal::percent(10)),
+    );
+    assert_eq!(res, Err(ContractError::Unauthorized {}));
+    for msg in [
+        set_guard(constant(20), Decimal::zero()),
+        set_guard(constant(0), Decimal::percent(10)),
+        set_guard(
+            ReferenceSource::Pair {
+                contract_addr: "pair0002".to_string(),
+            },
+            Decimal::percent(10),
+        ),
+    ] {
+        let res = execute(deps.as_mut(), mock_env(), mock_info("addr0000", &[]), msg);
+        assert_eq!(res, Err(ContractError::InvalidPriceGuard {}));
+    }
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        set_guard(constant(20), Decimal::percent(10)),
+    )
+    .unwrap();
+
+    let swap = |operation: SwapOperation| ExecuteMsg::ExecuteSwapOperations {
+        operations: vec![operation],
+        minimum_receive: None,
+        minimum_receive_price: None,
+        to: None,
+        deadline: None,
+        offer_amount: None,
+        auto_unwind: false,
+        unwind_receiver: None,
+    };
+    let simulate = |deps: Deps<TerraQuery>, operation: SwapOperation| {
+        let res: SimulateSwapOperationsResponse = from_binary(
+            &query(
+                deps,
+                mock_env(),
+                QueryMsg::SimulateSwapOperations {
+                    offer_amount: Uint128::from(1000u128),
+                    operations: vec![operation],
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        res.price_guard_tripped
+    };
+    let forward = SwapOperation::terraswap(uluna.clone(), token.clone());
+    let backward = SwapOperation::terraswap(token.clone(), uluna.clone());
+
+    // the pool is at the reference price
+    assert!(!simulate(deps.as_ref(), forward.clone()));
+    let info = mock_info("addr0000", &[coin(1000, "uluna")]);
+    execute(deps.as_mut(), mock_env(), info, swap(forward.clone())).unwrap();
+
+    // the manipulated pool deviates 25% in both directions
+    deps.querier
+        .with_pair_pool("pair0000", pool(1000000, 1500000));
+    assert!(simulate(deps.as_ref(), forward.clone()));
+    assert!(simulate(deps.as_ref(), backward));
+    let info = mock_info("addr0000", &[coin(1000, "uluna")]);
+    let res = execute(deps.as_mut(), mock_env(), info, swap(forward.clone()));
+    assert_eq!(
+        res,
+        Err(ContractError::PriceGuardTripped {
+            offer_asset: "uluna".to_string(),
+            ask_asset: "asset0000".to_string(),
+            deviation: Decimal::percent(25),
+            max_deviation: Decimal::percent(10),
+        })
+    );
+
+    // a reference pair moved as much does not trip the guard
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        set_guard(
+            ReferenceSource::Pair {
+                contract_addr: "pair0001".to_string(),
+            },
+            Decimal::percent(10),
+        ),
+    )
+    .unwrap();
+    assert!(!simulate(deps.as_ref(), forward.clone()));
+
+    // removing the guard lifts it
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        ExecuteMsg::SetPriceGuard {
+            offer_asset_info: token.clone(),
+            ask_asset_info: uluna.clone(),
+            guard: None,
+        },
+    )
+    .unwrap();
+    deps.querier.with_pair_pool("pair0000", pool(1000000, 100));
+    assert!(!simulate(deps.as_ref(), forward));
+}
+
+#[test]
+fn auto_unwind() {
+    let ibc_denom = "ibc/0471F1C4E7AFD3F07702BEF6DC365268D64570F7C1FDC98EA6098DD6DE59817B";
+    let mut deps = mock_dependencies(&[]);
+    default_instantiate(deps.as_mut());
+
+    let register = |origin: Option<IbcOrigin>| ExecuteMsg::UpdateIbcDenom {
+        ibc_denom: ibc_denom.to_string(),
+        origin,
+    };
+    let origin = IbcOrigin {
+        channel_id: "channel-1".to_string(),
+        origin_denom: "uosmo".to_string(),
+    };
+
+    // only the owner registers ibc denoms
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0001", &[]),
+        register(Some(origin.clone())),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        ExecuteMsg::UpdateIbcDenom {
+            ibc_denom: "uusd".to_string(),
+            origin: Some(origin.clone()),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidIbcDenom {
+            denom: "uusd".to_string()
+        }
+    );
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        register(Some(IbcOrigin {
+            channel_id: String::new(),
+            origin_denom: "uosmo".to_string(),
+        })),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::InvalidIbcOrigin {});
+
+    let swap = |auto_unwind: bool, unwind_receiver: Option<&str>, to: Option<&str>| {
+        ExecuteMsg::ExecuteSwapOperations {
+            operations: vec![SwapOperation::native_swap("uusd", ibc_denom)],
+            minimum_receive: Some(Uint128::from(900u128)),
+            minimum_receive_price: None,
+            to: to.map(|to| to.to_string()),
+            deadline: None,
+            offer_amount: None,
+            auto_unwind,
+            unwind_receiver: unwind_receiver.map(|receiver| receiver.to_string()),
+        }
+    };
+    let funds = [coin(1000u128, "uusd")];
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &funds),
+        swap(true, Some("osmo1receiver"), None),
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::UnregisteredIbcDenom {
+            denom: ibc_denom.to_string()
+        }
+    );
+
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        register(Some(origin.clone())),
+    )
+    .unwrap();
+    let res: IbcDenomResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::IbcDenom {
+                ibc_denom: ibc_denom.to_string(),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        res,
+        IbcDenomResponse {
+            ibc_denom: ibc_denom.to_string(),
+            channel_id: "channel-1".to_string(),
+            origin_denom: "uosmo".to_string(),
+        }
+    );
+
+    // the unwind receiver is required, and the return is not sent to `to`
+    for msg in [
+        swap(true, None, None),
+        swap(true, Some(""), None),
+        swap(true, Some("osmo1receiver"), Some("addr0001")),
+    ] {
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("addr0000", &funds),
+            msg,
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::InvalidUnwind {});
+    }
+
+    // the last hop keeps its return for the unwind, which asserts the minimum receive
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &funds),
+        swap(true, Some("osmo1receiver"), None),
+    )
+    .unwrap();
+    assert_eq!(res.messages.len(), 2);
+    assert_eq!(
+        res.messages[1],
+        SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: MOCK_CONTRACT_ADDR.to_string(),
+            funds: vec![],
+            msg: to_binary(&ExecuteMsg::UnwindAskAsset {
+                ibc_denom: ibc_denom.to_string(),
+                unwind_receiver: "osmo1receiver".to_string(),
+                minimum_receive: Some(Uint128::from(900u128)),
+            })
+            .unwrap(),
+        }))
+    );
+    match &res.messages[0].msg {
+        CosmosMsg::Wasm(WasmMsg::Execute { msg, .. }) => match from_binary(msg).unwrap() {
+            ExecuteMsg::ExecuteSwapOperation { to, .. } => assert_eq!(to, None),
+            _ => panic!("DO NOT ENTER HERE"),
+        },
+        _ => panic!("DO NOT ENTER HERE"),
+    }
+
+    // the router transfers the return less the ask side routing fee to the origin chain
+    set_routing_fee(deps.as_mut(), FeeSide::Ask);
+    deps.querier.with_balance(&[(
+        &MOCK_CONTRACT_ADDR.to_string(),
+        vec![coin(1000u128, ibc_denom)],
+    )]);
+    let unwind = |minimum_receive: u128| ExecuteMsg::UnwindAskAsset {
+        ibc_denom: ibc_denom.to_string(),
+        unwind_receiver: "osmo1receiver".to_string(),
+        minimum_receive: Some(Uint128::from(minimum_receive)),
+    };
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        unwind(900),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(MOCK_CONTRACT_ADDR, &[]),
+        unwind(991),
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::MinimumReceiveAssertion {
+            expected: Uint128::from(991u128),
+            actual: Uint128::from(990u128),
+        }
+    );
+
+    let env = mock_env();
+    ROUTE_INITIATOR
+        .save(deps.as_mut().storage, &Addr::unchecked("addr0000"))
+        .unwrap();
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(MOCK_CONTRACT_ADDR, &[]),
+        unwind(990),
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+                to_address: "collector".to_string(),
+                amount: vec![coin(10u128, ibc_denom)],
+            })),
+            SubMsg::new(CosmosMsg::Ibc(IbcMsg::Transfer {
+                channel_id: "channel-1".to_string(),
+                to_address: "osmo1receiver".to_string(),
+                amount: coin(990u128, ibc_denom),
+                timeout: IbcTimeout::with_timestamp(env.block.time.plus_seconds(600)),
+            })),
+        ]
+    );
+    assert_eq!(
+        res.attributes,
+        vec![
+            attr("action", "unwind_ask_asset"),
+            attr("unwind_id", "1"),
+            attr("channel_id", "channel-1"),
+            attr("origin_denom", "uosmo"),
+            attr("unwind_receiver", "osmo1receiver"),
+            attr("fee_amount", "10"),
+            attr("return_amount", "990"),
+        ]
+    );
+
+    // the transfer is pending until the owner settles it, a refund returns it to the initiator
+    let pending = PendingUnwind {
+        id: 1,
+        initiator: "addr0000".to_string(),
+        unwind_receiver: "osmo1receiver".to_string(),
+        channel_id: "channel-1".to_string(),
+        amount: coin(990u128, ibc_denom),
+        timeout: env.block.time.plus_seconds(600).seconds(),
+    };
+    let res: PendingUnwindsResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::PendingUnwinds {
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(res.unwinds, vec![pending]);
+
+    let settle = ExecuteMsg::SettleUnwind {
+        id: 1,
+        refund: true,
+    };
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0001", &[]),
+        settle.clone(),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        settle.clone(),
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+            to_address: "addr0000".to_string(),
+            amount: vec![coin(990u128, ibc_denom)],
+        }))]
+    );
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        settle,
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::UnknownUnwind { id: 1 });
+
+    // a removed denom can't be unwound
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &[]),
+        register(None),
+    )
+    .unwrap();
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("addr0000", &funds),
+        swap(true, Some("osmo1receiver"), None),
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::UnregisteredIbcDenom {
+            denom: ibc_denom.to_string()
+        }
+    );
+}
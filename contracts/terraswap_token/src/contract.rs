@@ -1,62 +1,57 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
-use cosmwasm_std::{Binary, Deps, DepsMut, Env, MessageInfo, Response, StdError, StdResult};
+use cosmwasm_std::{Addr, Binary, Deps, DepsMut, Env, MessageInfo, Order, Response, StdResult};
 
 use cw2::set_contract_version;
+use cw20::MarketingInfoResponse;
 use cw20_base::{
-    contract::{create_accounts, execute as cw20_execute, query as cw20_query},
-    msg::{ExecuteMsg, QueryMsg},
-    state::{MinterData, TokenInfo, TOKEN_INFO},
+    contract::{execute as cw20_execute, instantiate as cw20_instantiate, query as cw20_query},
+    msg::{ExecuteMsg, InstantiateMarketingInfo, InstantiateMsg as Cw20InstantiateMsg, QueryMsg},
+    state::{ALLOWANCES, ALLOWANCES_SPENDER, MARKETING_INFO, TOKEN_INFO},
     ContractError,
 };
+use cw_storage_plus::{Bound, Item};
 
-use classic_terraswap::token::InstantiateMsg;
+use classic_terraswap::token::{InstantiateMsg, MigrateMsg};
 
 // version info for migration info
 const CONTRACT_NAME: &str = "crates.io:terraswap-token";
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
+/// The last owner and spender indexed by spender in a migration
+const ALLOWANCE_INDEX_CURSOR: Item<(Addr, Addr)> = Item::new("allowance_index_cursor");
+/// The most allowances a migration indexes by spender
+const ALLOWANCE_INDEX_LIMIT: usize = 100;
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
     mut deps: DepsMut,
-    _env: Env,
-    _info: MessageInfo,
+    env: Env,
+    info: MessageInfo,
     msg: InstantiateMsg,
 ) -> Result<Response, ContractError> {
+    // cw20-base validates and stores the token and marketing info
+    let res = cw20_instantiate(
+        deps.branch(),
+        env,
+        info,
+        Cw20InstantiateMsg {
+            name: msg.name,
+            symbol: msg.symbol,
+            decimals: msg.decimals,
+            initial_balances: msg.initial_balances,
+            mint: msg.mint,
+            marketing: msg.marketing.map(|marketing| InstantiateMarketingInfo {
+                project: marketing.project,
+                description: marketing.description,
+                marketing: marketing.marketing,
+                logo: marketing.logo,
+            }),
+        },
+    )?;
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
-    // check valid token info
-    msg.validate()?;
-
-    // create initial accounts
-    let total_supply = create_accounts(&mut deps, &msg.initial_balances)?;
-
-    if let Some(limit) = msg.get_cap() {
-        if total_supply > limit {
-            return Err(ContractError::Std(StdError::generic_err(
-                "Initial supply greater than cap",
-            )));
-        }
-    }
-
-    let mint = match msg.mint {
-        Some(m) => Some(MinterData {
-            minter: deps.api.addr_validate(&m.minter)?,
-            cap: m.cap,
-        }),
-        None => None,
-    };
 
-    // store token info
-    let data = TokenInfo {
-        name: msg.name,
-        symbol: msg.symbol,
-        decimals: msg.decimals,
-        total_supply,
-        mint,
-    };
-
-    TOKEN_INFO.save(deps.storage, &data)?;
-    Ok(Response::default())
+    Ok(res)
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
@@ -73,3 +68,49 @@ pub fn execute(
 pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
     cw20_query(deps, env, msg)
 }
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    // tokens from before the marketing info are marketed by their minter,
+    // the pair of a liquidity token; balances and allowances are kept as they are
+    if MARKETING_INFO.may_load(deps.storage)?.is_none() {
+        if let Some(mint) = TOKEN_INFO.load(deps.storage)?.mint {
+            MARKETING_INFO.save(
+                deps.storage,
+                &MarketingInfoResponse {
+                    project: None,
+                    description: None,
+                    marketing: Some(mint.minter),
+                    logo: None,
+                },
+            )?;
+        }
+    }
+
+    // tokens from before the spender index answer `all_spender_allowances` once
+    // their allowances are indexed by spender, in batches repeated by migrating again
+    let cursor = ALLOWANCE_INDEX_CURSOR.may_load(deps.storage)?;
+    let start = cursor
+        .as_ref()
+        .map(|(owner, spender)| Bound::exclusive((owner, spender)));
+    let allowances = ALLOWANCES
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(ALLOWANCE_INDEX_LIMIT)
+        .collect::<StdResult<Vec<_>>>()?;
+    for ((owner, spender), allowance) in allowances.iter() {
+        ALLOWANCES_SPENDER.save(deps.storage, (spender, owner), allowance)?;
+    }
+    if let Some((last, _)) = allowances.last() {
+        ALLOWANCE_INDEX_CURSOR.save(deps.storage, last)?;
+    }
+
+    Ok(Response::new().add_attributes(vec![
+        ("allowances_indexed", allowances.len().to_string()),
+        (
+            "complete",
+            (allowances.len() < ALLOWANCE_INDEX_LIMIT).to_string(),
+        ),
+    ]))
+}
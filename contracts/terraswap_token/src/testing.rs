@@ -0,0 +1,457 @@
+use crate::contract::{execute, instantiate, migrate, query};
+use classic_terraswap::token::{InstantiateMarketingInfo, InstantiateMsg, MigrateMsg};
+use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+use cosmwasm_std::{attr, from_binary, to_binary, Addr, Deps, DepsMut, SubMsg, Uint128};
+use cw20::{
+    AllAllowancesResponse, AllSpenderAllowancesResponse, AllowanceInfo, BalanceResponse, Cw20Coin,
+    Cw20ReceiveMsg, Expiration, Logo, LogoInfo, MarketingInfoResponse, MinterResponse,
+    SpenderAllowanceInfo, TokenInfoResponse,
+};
+use cw20_base::msg::{ExecuteMsg, QueryMsg};
+use cw20_base::state::ALLOWANCES_SPENDER;
+use cw20_base::ContractError;
+
+fn liquidity_token_msg(marketing: Option<InstantiateMarketingInfo>) -> InstantiateMsg {
+    InstantiateMsg {
+        name: "terraswap liquidity token".to_string(),
+        symbol: "uLP-uluna-uu".to_string(),
+        decimals: 6,
+        initial_balances: vec![Cw20Coin {
+            address: "addr0000".to_string(),
+            amount: Uint128::from(100u128),
+        }],
+        mint: Some(MinterResponse {
+            minter: "pair0000".to_string(),
+            cap: None,
+        }),
+        marketing,
+    }
+}
+
+fn query_marketing_info(deps: Deps) -> MarketingInfoResponse {
+    from_binary(&query(deps, mock_env(), QueryMsg::MarketingInfo {}).unwrap()).unwrap()
+}
+
+fn query_balance(deps: Deps, address: &str) -> Uint128 {
+    let res: BalanceResponse = from_binary(
+        &query(
+            deps,
+            mock_env(),
+            QueryMsg::Balance {
+                address: address.to_string(),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    res.balance
+}
+
+#[test]
+fn instantiate_with_marketing() {
+    let mut deps = mock_dependencies();
+
+    let msg = liquidity_token_msg(Some(InstantiateMarketingInfo {
+        project: Some("terraswap".to_string()),
+        description: None,
+        marketing: Some("pair0000".to_string()),
+        logo: Some(Logo::Url("https://terraswap.io/lp.png".to_string())),
+    }));
+    instantiate(deps.as_mut(), mock_env(), mock_info("pair0000", &[]), msg).unwrap();
+
+    assert_eq!(
+        query_marketing_info(deps.as_ref()),
+        MarketingInfoResponse {
+            project: Some("terraswap".to_string()),
+            description: None,
+            marketing: Some(Addr::unchecked("pair0000")),
+            logo: Some(LogoInfo::Url("https://terraswap.io/lp.png".to_string())),
+        }
+    );
+
+    // the marketing admin hands the marketing over
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("pair0000", &[]),
+        ExecuteMsg::UpdateMarketing {
+            project: None,
+            description: Some("liquidity of uluna-uusd".to_string()),
+            marketing: Some("admin0000".to_string()),
+        },
+    )
+    .unwrap();
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("pair0000", &[]),
+        ExecuteMsg::UploadLogo(Logo::Url("https://terraswap.io/other.png".to_string())),
+    );
+    match res {
+        Err(ContractError::Unauthorized {}) => (),
+        _ => panic!("Must return unauthorized error"),
+    }
+
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("admin0000", &[]),
+        ExecuteMsg::UploadLogo(Logo::Url("https://terraswap.io/other.png".to_string())),
+    )
+    .unwrap();
+
+    let marketing_info = query_marketing_info(deps.as_ref());
+    assert_eq!(
+        marketing_info.description,
+        Some("liquidity of uluna-uusd".to_string())
+    );
+    assert_eq!(marketing_info.marketing, Some(Addr::unchecked("admin0000")));
+    assert_eq!(
+        marketing_info.logo,
+        Some(LogoInfo::Url("https://terraswap.io/other.png".to_string()))
+    );
+}
+
+#[test]
+fn instantiate_invalid_symbol() {
+    let mut deps = mock_dependencies();
+
+    let mut msg = liquidity_token_msg(None);
+    msg.symbol = "uLP-uluna-uusd".to_string();
+    let res = instantiate(deps.as_mut(), mock_env(), mock_info("pair0000", &[]), msg);
+    match res {
+        Err(ContractError::Std(err)) => assert_eq!(
+            err.to_string(),
+            "Generic error: Ticker symbol is not in expected format [a-zA-Z\\-]{3,12}"
+        ),
+        _ => panic!("Must return invalid symbol error"),
+    }
+}
+
+#[test]
+fn migrate_keeps_balances() {
+    let mut deps = mock_dependencies();
+
+    // a liquidity token from before the marketing info
+    instantiate(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("pair0000", &[]),
+        liquidity_token_msg(None),
+    )
+    .unwrap();
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("pair0000", &[]),
+        ExecuteMsg::Mint {
+            recipient: "addr0001".to_string(),
+            amount: Uint128::from(50u128),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        query_marketing_info(deps.as_ref()),
+        MarketingInfoResponse::default()
+    );
+
+    migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+
+    assert_eq!(
+        query_balance(deps.as_ref(), "addr0000"),
+        Uint128::from(100u128)
+    );
+    assert_eq!(
+        query_balance(deps.as_ref(), "addr0001"),
+        Uint128::from(50u128)
+    );
+    let token_info: TokenInfoResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::TokenInfo {}).unwrap()).unwrap();
+    assert_eq!(token_info.total_supply, Uint128::from(150u128));
+
+    // the pair markets the token
+    assert_eq!(
+        query_marketing_info(deps.as_ref()).marketing,
+        Some(Addr::unchecked("pair0000"))
+    );
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("pair0000", &[]),
+        ExecuteMsg::UpdateMarketing {
+            project: Some("terraswap".to_string()),
+            description: None,
+            marketing: None,
+        },
+    )
+    .unwrap();
+}
+
+#[test]
+fn migrate_keeps_marketing_info() {
+    let mut deps = mock_dependencies();
+
+    instantiate(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("pair0000", &[]),
+        liquidity_token_msg(Some(InstantiateMarketingInfo {
+            project: None,
+            description: None,
+            marketing: Some("admin0000".to_string()),
+            logo: None,
+        })),
+    )
+    .unwrap();
+
+    migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+
+    assert_eq!(
+        query_marketing_info(deps.as_ref()).marketing,
+        Some(Addr::unchecked("admin0000"))
+    );
+}
+
+fn approve(deps: DepsMut, owner: &str, spender: &str, amount: u128, expires: Expiration) {
+    execute(
+        deps,
+        mock_env(),
+        mock_info(owner, &[]),
+        ExecuteMsg::IncreaseAllowance {
+            spender: spender.to_string(),
+            amount: Uint128::from(amount),
+            expires: Some(expires),
+        },
+    )
+    .unwrap();
+}
+
+#[test]
+fn operator_allowances() {
+    let mut deps = mock_dependencies();
+    instantiate(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("pair0000", &[]),
+        liquidity_token_msg(None),
+    )
+    .unwrap();
+
+    let mut env = mock_env();
+    let expires = Expiration::AtHeight(env.block.height + 10);
+    approve(deps.as_mut(), "addr0000", "operator0000", 60, expires);
+    approve(
+        deps.as_mut(),
+        "addr0000",
+        "operator0001",
+        5,
+        Expiration::Never {},
+    );
+
+    // the operator moves, burns and sends the owner's tokens within the allowance
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("operator0000", &[]),
+        ExecuteMsg::TransferFrom {
+            owner: "addr0000".to_string(),
+            recipient: "addr0001".to_string(),
+            amount: Uint128::from(10u128),
+        },
+    )
+    .unwrap();
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("operator0000", &[]),
+        ExecuteMsg::BurnFrom {
+            owner: "addr0000".to_string(),
+            amount: Uint128::from(20u128),
+        },
+    )
+    .unwrap();
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("operator0000", &[]),
+        ExecuteMsg::SendFrom {
+            owner: "addr0000".to_string(),
+            contract: "pair0000".to_string(),
+            amount: Uint128::from(20u128),
+            msg: to_binary("withdraw").unwrap(),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(
+            Cw20ReceiveMsg {
+                sender: "operator0000".to_string(),
+                amount: Uint128::from(20u128),
+                msg: to_binary("withdraw").unwrap(),
+            }
+            .into_cosmos_msg("pair0000")
+            .unwrap()
+        )]
+    );
+
+    assert_eq!(
+        query_balance(deps.as_ref(), "addr0000"),
+        Uint128::from(50u128)
+    );
+    assert_eq!(
+        query_balance(deps.as_ref(), "pair0000"),
+        Uint128::from(20u128)
+    );
+    let token_info: TokenInfoResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::TokenInfo {}).unwrap()).unwrap();
+    assert_eq!(token_info.total_supply, Uint128::from(80u128));
+
+    // both indexes of the allowances
+    let res: AllAllowancesResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::AllAllowances {
+                owner: "addr0000".to_string(),
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        res.allowances,
+        vec![
+            AllowanceInfo {
+                spender: "operator0000".to_string(),
+                allowance: Uint128::from(10u128),
+                expires,
+            },
+            AllowanceInfo {
+                spender: "operator0001".to_string(),
+                allowance: Uint128::from(5u128),
+                expires: Expiration::Never {},
+            },
+        ]
+    );
+    let res: AllSpenderAllowancesResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::AllSpenderAllowances {
+                spender: "operator0000".to_string(),
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        res.allowances,
+        vec![SpenderAllowanceInfo {
+            owner: "addr0000".to_string(),
+            allowance: Uint128::from(10u128),
+            expires,
+        }]
+    );
+
+    // beyond the allowance
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("operator0001", &[]),
+        ExecuteMsg::BurnFrom {
+            owner: "addr0000".to_string(),
+            amount: Uint128::from(6u128),
+        },
+    );
+    assert!(matches!(res, Err(ContractError::Std(_))));
+
+    // the allowance expired
+    env.block.height += 10;
+    let res = execute(
+        deps.as_mut(),
+        env,
+        mock_info("operator0000", &[]),
+        ExecuteMsg::BurnFrom {
+            owner: "addr0000".to_string(),
+            amount: Uint128::from(1u128),
+        },
+    );
+    assert!(matches!(res, Err(ContractError::Expired {})));
+}
+
+#[test]
+fn migrate_indexes_allowances_by_spender() {
+    let mut deps = mock_dependencies();
+    instantiate(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("pair0000", &[]),
+        liquidity_token_msg(None),
+    )
+    .unwrap();
+    let operator = |i: usize| format!("operator{:04}", i);
+    for i in 0..101 {
+        approve(
+            deps.as_mut(),
+            "addr0000",
+            &operator(i),
+            60,
+            Expiration::Never {},
+        );
+
+        // a token from before the spender index
+        ALLOWANCES_SPENDER.remove(
+            deps.as_mut().storage,
+            (&Addr::unchecked(operator(i)), &Addr::unchecked("addr0000")),
+        );
+    }
+    let query_spender_allowances = |deps: Deps, spender: String| {
+        from_binary::<AllSpenderAllowancesResponse>(
+            &query(
+                deps,
+                mock_env(),
+                QueryMsg::AllSpenderAllowances {
+                    spender,
+                    start_after: None,
+                    limit: None,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap()
+        .allowances
+    };
+
+    // the allowances are indexed in batches of 100
+    let res = migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+    assert_eq!(
+        res.attributes,
+        vec![attr("allowances_indexed", "100"), attr("complete", "false")]
+    );
+    assert_eq!(
+        query_spender_allowances(deps.as_ref(), operator(0)),
+        vec![SpenderAllowanceInfo {
+            owner: "addr0000".to_string(),
+            allowance: Uint128::from(60u128),
+            expires: Expiration::Never {},
+        }]
+    );
+    assert!(query_spender_allowances(deps.as_ref(), operator(100)).is_empty());
+
+    // migrating again continues after the cursor
+    let res = migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+    assert_eq!(
+        res.attributes,
+        vec![attr("allowances_indexed", "1"), attr("complete", "true")]
+    );
+    assert_eq!(
+        query_spender_allowances(deps.as_ref(), operator(100)).len(),
+        1
+    );
+}
@@ -4,6 +4,8 @@ use std::fs::create_dir_all;
 use cosmwasm_schema::{export_schema, remove_schemas, schema_for};
 
 use classic_terraswap::asset::{Asset, AssetInfo, PairInfo};
+use classic_terraswap::pair::{OfferComputation, SwapComputation};
+use classic_terraswap::router::SwapOperation;
 
 fn main() {
     let mut out_dir = current_dir().unwrap();
@@ -14,4 +16,7 @@ fn main() {
     export_schema(&schema_for!(PairInfo), &out_dir);
     export_schema(&schema_for!(Asset), &out_dir);
     export_schema(&schema_for!(AssetInfo), &out_dir);
+    export_schema(&schema_for!(SwapOperation), &out_dir);
+    export_schema(&schema_for!(SwapComputation), &out_dir);
+    export_schema(&schema_for!(OfferComputation), &out_dir);
 }
@@ -1,11 +1,14 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::fmt;
+use std::str::FromStr;
 
-use crate::querier::{query_balance, query_native_decimals, query_token_balance, query_token_info};
-use classic_bindings::{TerraMsg, TerraQuerier, TerraQuery};
+use crate::querier::{
+    compute_tax, query_balance, query_native_decimals, query_token_balance, query_token_info,
+};
+use classic_bindings::{TerraMsg, TerraQuery};
 use cosmwasm_std::{
-    to_binary, Addr, Api, BankMsg, CanonicalAddr, Coin, CosmosMsg, Decimal, MessageInfo,
+    to_binary, Addr, Api, BankMsg, Binary, CanonicalAddr, Coin, CosmosMsg, MessageInfo,
     QuerierWrapper, StdError, StdResult, SubMsg, Uint128, WasmMsg,
 };
 use cw20::Cw20ExecuteMsg;
@@ -22,26 +25,65 @@ impl fmt::Display for Asset {
     }
 }
 
-static DECIMAL_FRACTION: Uint128 = Uint128::new(1_000_000_000_000_000_000u128);
+/// Parses the `Display` form of an asset, the amount followed by the asset info,
+/// optionally separated by a colon like `500000:terra1...`
+impl FromStr for Asset {
+    type Err = StdError;
+
+    fn from_str(s: &str) -> StdResult<Self> {
+        let info_start = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+        let (amount, info) = s.split_at(info_start);
+        if amount.is_empty() {
+            return Err(StdError::parse_err(
+                "Asset",
+                format!("missing amount in {}", s),
+            ));
+        }
+        let info = info.strip_prefix(':').unwrap_or(info);
+
+        Ok(Asset {
+            info: info.parse()?,
+            amount: Uint128::from_str(amount)?,
+        })
+    }
+}
 
 impl Asset {
     pub fn is_native_token(&self) -> bool {
         self.info.is_native_token()
     }
 
+    /// Fails with both asset infos unless the assets are of the same asset info
+    pub fn assert_same_info(&self, other: &Asset) -> StdResult<()> {
+        if self.info == other.info {
+            Ok(())
+        } else {
+            Err(StdError::generic_err(format!(
+                "asset info mismatch: {} and {}",
+                self.info, other.info
+            )))
+        }
+    }
+
+    pub fn checked_add(&self, other: &Asset) -> StdResult<Asset> {
+        self.assert_same_info(other)?;
+        Ok(Asset {
+            info: self.info.clone(),
+            amount: self.amount.checked_add(other.amount)?,
+        })
+    }
+
+    pub fn checked_sub(&self, other: &Asset) -> StdResult<Asset> {
+        self.assert_same_info(other)?;
+        Ok(Asset {
+            info: self.info.clone(),
+            amount: self.amount.checked_sub(other.amount)?,
+        })
+    }
+
     pub fn compute_tax(&self, querier: &QuerierWrapper<TerraQuery>) -> StdResult<Uint128> {
-        let amount = self.amount;
         if let AssetInfo::NativeToken { denom } = &self.info {
-            let terra_querier = TerraQuerier::new(querier);
-            let tax_rate: Decimal = (terra_querier.query_tax_rate()?).rate;
-            let tax_cap: Uint128 = (terra_querier.query_tax_cap(denom.to_string())?).cap;
-            Ok(std::cmp::min(
-                amount.checked_sub(amount.multiply_ratio(
-                    DECIMAL_FRACTION,
-                    DECIMAL_FRACTION * tax_rate + DECIMAL_FRACTION,
-                ))?,
-                tax_cap,
-            ))
+            compute_tax(querier, self.amount, denom.to_string())
         } else {
             Ok(Uint128::zero())
         }
@@ -63,21 +105,90 @@ impl Asset {
         self,
         querier: &QuerierWrapper<TerraQuery>,
         recipient: Addr,
+    ) -> StdResult<CosmosMsg<TerraMsg>> {
+        self.into_taxed_send_msg(querier, recipient, None)
+    }
+
+    /// Sends the asset to a contract with `msg` attached, a cw20 `Send` for tokens
+    /// and an execute of `msg` with the funds attached for native tokens
+    pub fn into_send_msg(
+        self,
+        querier: &QuerierWrapper<TerraQuery>,
+        contract: Addr,
+        msg: Binary,
+    ) -> StdResult<CosmosMsg<TerraMsg>> {
+        self.into_taxed_send_msg(querier, contract, Some(msg))
+    }
+
+    /// Sends the asset to `recipient`, with `msg` attached when given: a cw20 `Send` for
+    /// tokens and an execute of `msg` with the funds attached for native tokens.
+    /// Native tokens are sent in full, see `into_taxed_send_msg`
+    pub fn into_untaxed_send_msg(
+        self,
+        recipient: Addr,
+        msg: Option<Binary>,
     ) -> StdResult<CosmosMsg<TerraMsg>> {
         let amount = self.amount;
+        self.send_msg(recipient, msg, amount)
+    }
+
+    /// Sends the asset like `into_untaxed_send_msg`, with the tax deducted from native tokens
+    pub fn into_taxed_send_msg(
+        self,
+        querier: &QuerierWrapper<TerraQuery>,
+        recipient: Addr,
+        msg: Option<Binary>,
+    ) -> StdResult<CosmosMsg<TerraMsg>> {
+        let amount = if self.is_native_token() {
+            self.deduct_tax(querier)?.amount
+        } else {
+            self.amount
+        };
+        self.send_msg(recipient, msg, amount)
+    }
 
-        match &self.info {
-            AssetInfo::Token { contract_addr } => Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+    fn send_msg(
+        &self,
+        recipient: Addr,
+        msg: Option<Binary>,
+        native_amount: Uint128,
+    ) -> StdResult<CosmosMsg<TerraMsg>> {
+        match (&self.info, msg) {
+            (AssetInfo::Token { contract_addr }, Some(msg)) => {
+                Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+                    contract_addr: contract_addr.to_string(),
+                    msg: to_binary(&Cw20ExecuteMsg::Send {
+                        contract: recipient.to_string(),
+                        amount: self.amount,
+                        msg,
+                    })?,
+                    funds: vec![],
+                }))
+            }
+            (AssetInfo::Token { contract_addr }, None) => Ok(CosmosMsg::Wasm(WasmMsg::Execute {
                 contract_addr: contract_addr.to_string(),
                 msg: to_binary(&Cw20ExecuteMsg::Transfer {
                     recipient: recipient.to_string(),
-                    amount,
+                    amount: self.amount,
                 })?,
                 funds: vec![],
             })),
-            AssetInfo::NativeToken { .. } => Ok(CosmosMsg::Bank(BankMsg::Send {
+            (AssetInfo::NativeToken { denom }, Some(msg)) => {
+                Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+                    contract_addr: recipient.to_string(),
+                    msg,
+                    funds: vec![Coin {
+                        denom: denom.to_string(),
+                        amount: native_amount,
+                    }],
+                }))
+            }
+            (AssetInfo::NativeToken { denom }, None) => Ok(CosmosMsg::Bank(BankMsg::Send {
                 to_address: recipient.to_string(),
-                amount: vec![self.deduct_tax(querier)?],
+                amount: vec![Coin {
+                    denom: denom.to_string(),
+                    amount: native_amount,
+                }],
             })),
         }
     }
@@ -137,6 +248,43 @@ pub enum AssetInfo {
     NativeToken { denom: String },
 }
 
+/// Parses a bech32 address as a token and anything else as a denom,
+/// a token can also be given explicitly as `cw20:terra1...`
+impl FromStr for AssetInfo {
+    type Err = StdError;
+
+    fn from_str(s: &str) -> StdResult<Self> {
+        if let Some(contract_addr) = s.strip_prefix("cw20:") {
+            if !is_bech32(contract_addr) {
+                return Err(StdError::parse_err(
+                    "AssetInfo",
+                    format!("invalid bech32 contract address: {}", contract_addr),
+                ));
+            }
+            return Ok(AssetInfo::Token {
+                contract_addr: contract_addr.to_string(),
+            });
+        }
+
+        if looks_like_bech32(s) {
+            if !is_bech32(s) {
+                return Err(StdError::parse_err(
+                    "AssetInfo",
+                    format!("invalid bech32 contract address: {}", s),
+                ));
+            }
+            return Ok(AssetInfo::Token {
+                contract_addr: s.to_string(),
+            });
+        }
+
+        validate_denom(s)?;
+        Ok(AssetInfo::NativeToken {
+            denom: s.to_string(),
+        })
+    }
+}
+
 impl fmt::Display for AssetInfo {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -164,6 +312,34 @@ impl AssetInfo {
             AssetInfo::Token { .. } => false,
         }
     }
+
+    /// Checks the format of a denom, or validates a contract address
+    pub fn validate(&self, api: &dyn Api) -> StdResult<AssetInfoValidated> {
+        match self {
+            AssetInfo::NativeToken { denom } => {
+                validate_denom(denom)?;
+                Ok(AssetInfoValidated(self.clone()))
+            }
+            AssetInfo::Token { contract_addr } => Ok(AssetInfoValidated(AssetInfo::Token {
+                contract_addr: api.addr_validate(contract_addr)?.to_string(),
+            })),
+        }
+    }
+
+    pub fn as_native_denom(&self) -> Option<&str> {
+        match self {
+            AssetInfo::NativeToken { denom } => Some(denom),
+            AssetInfo::Token { .. } => None,
+        }
+    }
+
+    pub fn as_contract_addr(&self) -> Option<&str> {
+        match self {
+            AssetInfo::Token { contract_addr } => Some(contract_addr),
+            AssetInfo::NativeToken { .. } => None,
+        }
+    }
+
     pub fn query_pool(
         &self,
         querier: &QuerierWrapper<TerraQuery>,
@@ -218,6 +394,26 @@ impl AssetInfo {
     }
 }
 
+/// An asset info checked by `AssetInfo::validate`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AssetInfoValidated(AssetInfo);
+
+impl AssetInfoValidated {
+    pub fn inner(&self) -> &AssetInfo {
+        &self.0
+    }
+
+    pub fn into_inner(self) -> AssetInfo {
+        self.0
+    }
+}
+
+impl From<AssetInfoValidated> for AssetInfo {
+    fn from(asset_info: AssetInfoValidated) -> Self {
+        asset_info.0
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 pub struct AssetRaw {
     pub info: AssetInfoRaw,
@@ -287,6 +483,14 @@ impl AssetInfoRaw {
     }
 }
 
+/// The key a pair is registered under, the same for either order of its assets
+pub fn pair_key(asset_infos: &[AssetInfoRaw; 2]) -> Vec<u8> {
+    let mut asset_infos = asset_infos.to_vec();
+    asset_infos.sort_by(|a, b| a.as_bytes().cmp(b.as_bytes()));
+
+    [asset_infos[0].as_bytes(), asset_infos[1].as_bytes()].concat()
+}
+
 // We define a custom struct for each query response
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 pub struct PairInfo {
@@ -294,6 +498,16 @@ pub struct PairInfo {
     pub contract_addr: String,
     pub liquidity_token: String,
     pub asset_decimals: [u8; 2],
+    /// The block the factory registered the pair at, unknown for pairs registered before
+    #[serde(default)]
+    pub created_at: Option<PairCreation>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct PairCreation {
+    pub height: u64,
+    /// Block time in seconds
+    pub time: u64,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
@@ -301,7 +515,15 @@ pub struct PairInfoRaw {
     pub asset_infos: [AssetInfoRaw; 2],
     pub contract_addr: CanonicalAddr,
     pub liquidity_token: CanonicalAddr,
+    /// Pairs stored before the decimals were recorded are read with 6 decimals
+    #[serde(default = "default_asset_decimals")]
     pub asset_decimals: [u8; 2],
+    #[serde(default)]
+    pub created_at: Option<PairCreation>,
+}
+
+fn default_asset_decimals() -> [u8; 2] {
+    [6u8, 6u8]
 }
 
 impl PairInfoRaw {
@@ -314,6 +536,7 @@ impl PairInfoRaw {
                 self.asset_infos[1].to_normal(api)?,
             ],
             asset_decimals: self.asset_decimals,
+            created_at: self.created_at.clone(),
         })
     }
 
@@ -337,3 +560,85 @@ impl PairInfoRaw {
         ])
     }
 }
+
+/// Denoms are 3 to 128 characters, starting with a letter,
+/// and IBC denoms are the hex hash of their trace
+fn validate_denom(denom: &str) -> StdResult<()> {
+    if denom.is_empty() {
+        return Err(StdError::parse_err("AssetInfo", "empty denom"));
+    }
+    if denom.len() < 3 || denom.len() > 128 {
+        return Err(StdError::parse_err(
+            "AssetInfo",
+            format!("denom must be 3 to 128 characters: {}", denom),
+        ));
+    }
+    if !denom.starts_with(|c: char| c.is_ascii_alphabetic())
+        || !denom
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "/:._-".contains(c))
+    {
+        return Err(StdError::parse_err(
+            "AssetInfo",
+            format!("invalid denom: {}", denom),
+        ));
+    }
+    if let Some(hash) = denom.strip_prefix("ibc/") {
+        if hash.len() != 64 || !hash.chars().all(|c| matches!(c, '0'..='9' | 'A'..='F')) {
+            return Err(StdError::parse_err(
+                "AssetInfo",
+                format!("invalid ibc denom: {}", denom),
+            ));
+        }
+    }
+    Ok(())
+}
+
+const BECH32_CHARSET: &str = "qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+/// A lowercase human readable part and a data part of at least the checksum,
+/// whether or not the checksum is valid
+fn looks_like_bech32(s: &str) -> bool {
+    match s.rsplit_once('1') {
+        Some((hrp, data)) => {
+            !hrp.is_empty()
+                && hrp.chars().all(|c| c.is_ascii_lowercase())
+                && data.len() > 6
+                && data.chars().all(|c| BECH32_CHARSET.contains(c))
+        }
+        None => false,
+    }
+}
+
+fn is_bech32(s: &str) -> bool {
+    if !looks_like_bech32(s) {
+        return false;
+    }
+    let (hrp, data) = s.rsplit_once('1').unwrap();
+    let mut values = bech32_hrp_expand(hrp);
+    values.extend(data.chars().map(|c| BECH32_CHARSET.find(c).unwrap() as u8));
+    bech32_polymod(&values) == 1
+}
+
+pub(crate) fn bech32_hrp_expand(hrp: &str) -> Vec<u8> {
+    let mut values: Vec<u8> = hrp.bytes().map(|b| b >> 5).collect();
+    values.push(0);
+    values.extend(hrp.bytes().map(|b| b & 31));
+    values
+}
+
+/// The BIP-173 checksum of 5-bit values
+pub(crate) fn bech32_polymod(values: &[u8]) -> u32 {
+    const GENERATOR: [u32; 5] = [0x3b6a57b2, 0x26508e6d, 0x1ea119fa, 0x3d4233dd, 0x2a1462b3];
+    let mut checksum = 1u32;
+    for value in values {
+        let top = checksum >> 25;
+        checksum = ((checksum & 0x1ffffff) << 5) ^ *value as u32;
+        for (i, generator) in GENERATOR.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                checksum ^= generator;
+            }
+        }
+    }
+    checksum
+}
@@ -1,7 +1,21 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+use cosmwasm_schema::QueryResponses;
+
 use crate::asset::{Asset, AssetInfo, PairInfo};
+use cosmwasm_std::{Binary, Decimal};
+
+/// Referral cut pairs accept by default, in bps of the swap return
+pub const DEFAULT_MAX_REFERRAL_BPS: u16 = 50;
+/// Upper bound of the referral cap the owner can set
+pub const MAX_REFERRAL_BPS_LIMIT: u16 = 1_000;
+/// The most decimals the asset of a pair can be declared with
+pub const MAX_ASSET_DECIMALS: u8 = 18;
+
+pub fn default_max_referral_bps() -> u16 {
+    DEFAULT_MAX_REFERRAL_BPS
+}
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 pub struct InstantiateMsg {
@@ -18,10 +32,29 @@ pub enum ExecuteMsg {
         owner: Option<String>,
         token_code_id: Option<u64>,
         pair_code_id: Option<u64>,
+        /// The referral cut pair swaps accept, in bps of the return
+        max_referral_bps: Option<u16>,
+        /// Restricts pair creation to the owner and the creators
+        permissioned: Option<bool>,
     },
     /// CreatePair instantiates pair contract
     CreatePair {
         assets: [Asset; 2],
+        /// Lets the factory owner create a pair of native denoms without registered
+        /// decimals, which are read as 6
+        allow_unregistered_decimals: Option<bool>,
+        /// Fees, admin and spread cap the pair is instantiated with
+        pair_params: Option<PairInitParams>,
+    },
+    /// CreatePairAndProvide instantiates a pair and provides `assets` to it in the same
+    /// transaction. The native assets are sent along and the factory transfers the
+    /// cw20 assets from the sender, which need an allowance to the factory
+    CreatePairAndProvide {
+        asset_infos: [AssetInfo; 2],
+        assets: [Asset; 2],
+        slippage_tolerance: Option<Decimal>,
+        /// The receiver of the liquidity tokens, the sender by default
+        receiver: Option<String>,
     },
     AddNativeTokenDecimals {
         denom: String,
@@ -31,22 +64,138 @@ pub enum ExecuteMsg {
         contract: String,
         code_id: Option<u64>,
     },
+    /// MigratePairs migrates up to `limit` registered pairs after `start_after` with `msg`.
+    /// Pairs the factory is not the admin of are skipped
+    MigratePairs {
+        new_code_id: u64,
+        start_after: Option<[AssetInfo; 2]>,
+        limit: u32,
+        msg: Binary,
+    },
+    /// UpdatePairsAdmin hands the wasm admin of up to `limit` registered pairs after
+    /// `start_after` over to `new_admin`. Pairs the factory is not the admin of are skipped
+    UpdatePairsAdmin {
+        new_admin: String,
+        start_after: Option<[AssetInfo; 2]>,
+        limit: u32,
+    },
+    /// UpdatePairDecimals corrects the decimals of a pair, in the order of `asset_infos`
+    UpdatePairDecimals {
+        asset_infos: [AssetInfo; 2],
+        asset_decimals: [u8; 2],
+    },
+    /// DeregisterPair removes a pair from the registry, the pair contract keeps running
+    DeregisterPair {
+        asset_infos: [AssetInfo; 2],
+    },
+    /// UpdateCreators adds and removes the addresses allowed to create pairs
+    /// while the factory is permissioned
+    UpdateCreators {
+        add: Vec<String>,
+        remove: Vec<String>,
+    },
+    /// UpdateAssetDenylist adds and removes the assets no new pair can be created with
+    UpdateAssetDenylist {
+        add: Vec<AssetInfo>,
+        remove: Vec<AssetInfo>,
+    },
+    /// UpdatePairCreationFee sets the native fee `create_pair` charges and its collector.
+    /// `None` removes the fee
+    UpdatePairCreationFee {
+        pair_creation_fee: Option<Asset>,
+        fee_collector: Option<String>,
+    },
+    /// UpdatePairFeeBounds sets the total fee rates other creators than the owner can
+    /// create pairs with. `None` leaves the fees of new pairs to the owner
+    UpdatePairFeeBounds {
+        pair_fee_bounds: Option<PairFeeBounds>,
+    },
+    /// BuildAssetIndex indexes the next `limit` registered pairs by their assets and
+    /// liquidity tokens, for the pairs registered before the indexes existed
+    BuildAssetIndex {
+        limit: Option<u32>,
+    },
+    /// ReportDuplicatePairs lists the pairs among the next `limit` registered pairs after the
+    /// raw key `start_after`, which are stored under a key other than the sorted key of their assets
+    ReportDuplicatePairs {
+        start_after: Option<Binary>,
+        limit: Option<u32>,
+    },
+    /// ProposeNewOwner proposes `owner` as the new owner, who can claim the ownership
+    /// within `expires_in` seconds, at most a week. It replaces the previous proposal
+    ProposeNewOwner {
+        owner: String,
+        expires_in: u64,
+    },
+    /// DropOwnershipProposal removes the pending ownership proposal
+    DropOwnershipProposal {},
+    /// ClaimOwnership makes the proposed owner the owner
+    ClaimOwnership {},
+    /// CountPairs counts the next `limit` pairs registered before the pair count existed
+    CountPairs {
+        limit: Option<u32>,
+    },
+    /// RefreshPair rewrites the liquidity token and the decimals of a registered pair
+    /// from the pair contract
+    RefreshPair {
+        asset_infos: [AssetInfo; 2],
+    },
+    /// RepairRegistry rewrites the liquidity token and the assets of the next `limit`
+    /// registered pairs after the raw key `start_after`, which differ from the pair contracts
+    RepairRegistry {
+        start_after: Option<Binary>,
+        limit: Option<u32>,
+    },
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema, QueryResponses)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
+    #[returns(ConfigResponse)]
     Config {},
-    Pair {
-        asset_infos: [AssetInfo; 2],
-    },
+    #[returns(PairInfo)]
+    Pair { asset_infos: [AssetInfo; 2] },
+    /// Pairs lists the registered pairs by their sorted asset infos, ascending by default
+    #[returns(PairsResponse)]
     Pairs {
         start_after: Option<[AssetInfo; 2]>,
         limit: Option<u32>,
+        order: Option<OrderBy>,
     },
-    NativeTokenDecimals {
-        denom: String,
+    #[returns(PairInfo)]
+    PairByLpToken { lp_token: String },
+    #[returns(PairsResponse)]
+    PairsByAsset {
+        asset_info: AssetInfo,
+        start_after: Option<[AssetInfo; 2]>,
+        limit: Option<u32>,
+    },
+    #[returns(NativeTokenDecimalsResponse)]
+    NativeTokenDecimals { denom: String },
+    #[returns(CreatorsResponse)]
+    Creators {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    #[returns(AssetDenylistResponse)]
+    AssetDenylist {
+        start_after: Option<AssetInfo>,
+        limit: Option<u32>,
     },
+    /// PredictPairAddress returns the address a pair of `asset_infos` is created at
+    /// with the current pair code id
+    #[returns(PredictPairAddressResponse)]
+    PredictPairAddress { asset_infos: [AssetInfo; 2] },
+    /// Stats returns the number of registered pairs and the recently created pairs
+    #[returns(StatsResponse)]
+    Stats {},
+    /// ValidatePair compares a registered pair with the pair contract and its liquidity token
+    #[returns(ValidatePairResponse)]
+    ValidatePair { asset_infos: [AssetInfo; 2] },
+    /// RouterBootstrap returns the configuration a router or tool needs to start
+    /// against this factory, including the factory's own address
+    #[returns(RouterBootstrapResponse)]
+    RouterBootstrap {},
 }
 
 // We define a custom struct for each query response
@@ -55,6 +204,43 @@ pub struct ConfigResponse {
     pub owner: String,
     pub pair_code_id: u64,
     pub token_code_id: u64,
+    #[serde(default = "default_max_referral_bps")]
+    pub max_referral_bps: u16,
+    #[serde(default)]
+    pub permissioned: bool,
+    #[serde(default)]
+    pub pair_creation_fee: Option<Asset>,
+    #[serde(default)]
+    pub fee_collector: Option<String>,
+    /// The proposed owner, until the ownership is claimed
+    #[serde(default)]
+    pub pending_owner: Option<String>,
+    #[serde(default)]
+    pub pair_fee_bounds: Option<PairFeeBounds>,
+}
+
+/// Params a pair is instantiated with instead of its defaults
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct PairInitParams {
+    pub fee_config: Option<PairFeeParams>,
+    /// Only the owner can set the admin of a new pair
+    pub admin: Option<String>,
+    pub max_spread_cap: Option<Decimal>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct PairFeeParams {
+    pub lp_fee_rate: Decimal,
+    pub protocol_fee_rate: Decimal,
+    /// Only the owner can set the protocol fee collector of a new pair
+    pub protocol_fee_collector: Option<String>,
+}
+
+/// Range of the total fee rate of the pairs created by other creators than the owner
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct PairFeeBounds {
+    pub min_fee_rate: Decimal,
+    pub max_fee_rate: Decimal,
 }
 
 /// We currently take no arguments for migrations
@@ -62,6 +248,13 @@ pub struct ConfigResponse {
 pub struct MigrateMsg {}
 
 // We define a custom struct for each query response
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderBy {
+    Ascending,
+    Descending,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 pub struct PairsResponse {
     pub pairs: Vec<PairInfo>,
@@ -71,3 +264,60 @@ pub struct PairsResponse {
 pub struct NativeTokenDecimalsResponse {
     pub decimals: u8,
 }
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct CreatorsResponse {
+    pub creators: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct AssetDenylistResponse {
+    pub assets: Vec<AssetInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct PredictPairAddressResponse {
+    pub contract_addr: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct ValidatePairResponse {
+    pub registered: PairInfo,
+    pub live_matches: bool,
+    /// The differences of the registered pair from the live one
+    pub mismatches: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct StatsResponse {
+    pub pair_count: u64,
+    /// Whether the pairs registered before the pair count existed are counted
+    pub pair_count_complete: bool,
+    /// The most recently created pairs, the latest first
+    pub recent_pairs: Vec<RecentPairResponse>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct RecentPairResponse {
+    pub contract_addr: String,
+    pub created_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct RouterBootstrapResponse {
+    /// The address of the factory answering the query
+    pub factory: String,
+    pub pair_code_id: u64,
+    pub token_code_id: u64,
+    /// The registered decimals of the native denoms
+    pub native_token_decimals: Vec<NativeTokenDecimalsItem>,
+    pub pair_count: u64,
+    /// Whether the pairs registered before the pair count existed are counted
+    pub pair_count_complete: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct NativeTokenDecimalsItem {
+    pub denom: String,
+    pub decimals: u8,
+}
@@ -1,18 +1,26 @@
 use cosmwasm_std::testing::{MockApi, MockQuerier, MockStorage, MOCK_CONTRACT_ADDR};
 use cosmwasm_std::{
-    from_binary, from_slice, to_binary, Coin, ContractResult, Decimal, OwnedDeps, Querier,
-    QuerierResult, QueryRequest, SystemError, SystemResult, Uint128, WasmQuery,
+    from_binary, from_slice, to_binary, CodeInfoResponse, Coin, ContractInfoResponse,
+    ContractResult, Decimal, HexBinary, OwnedDeps, Querier, QuerierResult, QueryRequest,
+    SystemError, SystemResult, Uint128, WasmQuery,
 };
 use std::collections::HashMap;
 use std::marker::PhantomData;
 use std::panic;
 
-use crate::asset::{AssetInfo, PairInfo};
-use crate::factory::{NativeTokenDecimalsResponse, QueryMsg as FactoryQueryMsg};
+use crate::asset::{Asset, AssetInfo, PairInfo};
+use crate::factory::{
+    ConfigResponse as FactoryConfigResponse, NativeTokenDecimalsItem, NativeTokenDecimalsResponse,
+    QueryMsg as FactoryQueryMsg, RouterBootstrapResponse,
+};
 use crate::pair::QueryMsg as PairQueryMsg;
-use crate::pair::{ReverseSimulationResponse, SimulationResponse};
+use crate::pair::{PoolResponse, ReverseSimulationResponse, SimulationResponse};
+use crate::router::{WrapperFeeResponse, WrapperQueryMsg};
 use classic_bindings::{SwapResponse, TaxCapResponse, TaxRateResponse, TerraQuery};
-use cw20::{BalanceResponse as Cw20BalanceResponse, Cw20QueryMsg, TokenInfoResponse};
+use cw20::{
+    AllowanceResponse, BalanceResponse as Cw20BalanceResponse, Cw20QueryMsg, Expiration,
+    MinterResponse, TokenInfoResponse,
+};
 
 use std::iter::FromIterator;
 
@@ -37,6 +45,15 @@ pub struct WasmMockQuerier {
     token_querier: TokenQuerier,
     tax_querier: TaxQuerier,
     terraswap_factory_querier: TerraswapFactoryQuerier,
+    wrapper_querier: WrapperQuerier,
+    unresponsive_contracts: Vec<String>,
+    token_minters: HashMap<String, String>,
+    // keyed by the token, the owner and the spender
+    token_allowances: HashMap<(String, String, String), Uint128>,
+    pair_infos: HashMap<String, PairInfo>,
+    pair_pools: HashMap<String, [Asset; 2]>,
+    // the contracts answering the factory RouterBootstrap query
+    factories: Vec<String>,
 }
 
 #[derive(Clone, Default)]
@@ -128,6 +145,23 @@ pub(crate) fn native_token_decimals_to_map(
     native_token_decimals_map
 }
 
+#[derive(Clone, Default)]
+pub struct WrapperQuerier {
+    // wrapper contract address => fee rate
+    fees: HashMap<String, Decimal>,
+}
+
+impl WrapperQuerier {
+    pub fn new(fees: &[(&String, &Decimal)]) -> Self {
+        let mut fees_map: HashMap<String, Decimal> = HashMap::new();
+        for (wrapper, fee_rate) in fees.iter() {
+            fees_map.insert(wrapper.to_string(), **fee_rate);
+        }
+
+        WrapperQuerier { fees: fees_map }
+    }
+}
+
 impl Querier for WasmMockQuerier {
     fn raw_query(&self, bin_request: &[u8]) -> QuerierResult {
         // MockQuerier doesn't support Custom, so we ignore it completely here
@@ -175,7 +209,53 @@ impl WasmMockQuerier {
                 }
                 _ => panic!("DO NOT ENTER HERE"),
             },
+            QueryRequest::Wasm(WasmQuery::Smart { contract_addr, .. })
+                if self.unresponsive_contracts.contains(contract_addr) =>
+            {
+                SystemResult::Err(SystemError::NoSuchContract {
+                    addr: contract_addr.to_string(),
+                })
+            }
             QueryRequest::Wasm(WasmQuery::Smart { contract_addr, msg }) => match from_binary(msg) {
+                Ok(FactoryQueryMsg::Config {}) => {
+                    SystemResult::Ok(ContractResult::from(to_binary(&FactoryConfigResponse {
+                        owner: "owner0000".to_string(),
+                        pair_code_id: 1,
+                        token_code_id: 2,
+                        max_referral_bps: 50,
+                        permissioned: false,
+                        pair_creation_fee: None,
+                        fee_collector: None,
+                        pending_owner: None,
+                        pair_fee_bounds: None,
+                    })))
+                }
+                Ok(FactoryQueryMsg::RouterBootstrap {})
+                    if self.factories.contains(contract_addr) =>
+                {
+                    let mut native_token_decimals: Vec<NativeTokenDecimalsItem> = self
+                        .terraswap_factory_querier
+                        .native_token_decimals
+                        .iter()
+                        .map(|(denom, decimals)| NativeTokenDecimalsItem {
+                            denom: denom.to_string(),
+                            decimals: *decimals,
+                        })
+                        .collect();
+                    native_token_decimals.sort_by(|a, b| a.denom.cmp(&b.denom));
+
+                    SystemResult::Ok(ContractResult::from(to_binary(&RouterBootstrapResponse {
+                        factory: contract_addr.to_string(),
+                        pair_code_id: 1,
+                        token_code_id: 2,
+                        native_token_decimals,
+                        pair_count: self.terraswap_factory_querier.pairs.len() as u64,
+                        pair_count_complete: true,
+                    })))
+                }
+                Ok(FactoryQueryMsg::RouterBootstrap {}) => SystemResult::Ok(ContractResult::Err(
+                    "Error parsing into type: unknown variant `router_bootstrap`".to_string(),
+                )),
                 Ok(FactoryQueryMsg::Pair { asset_infos }) => {
                     let key = [asset_infos[0].to_string(), asset_infos[1].to_string()].join("");
                     let mut sort_key: Vec<char> = key.chars().collect();
@@ -211,6 +291,11 @@ impl WasmMockQuerier {
                     }
                 }
                 _ => match from_binary(msg) {
+                    Ok(PairQueryMsg::Pair {}) if self.pair_infos.contains_key(contract_addr) => {
+                        SystemResult::Ok(ContractResult::from(to_binary(
+                            &self.pair_infos[contract_addr],
+                        )))
+                    }
                     Ok(PairQueryMsg::Pair {}) => {
                         SystemResult::Ok(ContractResult::from(to_binary(&PairInfo {
                             asset_infos: [
@@ -224,90 +309,152 @@ impl WasmMockQuerier {
                             asset_decimals: [6u8, 6u8],
                             contract_addr: "pair0000".to_string(),
                             liquidity_token: "liquidity0000".to_string(),
+                            created_at: None,
+                        })))
+                    }
+                    Ok(PairQueryMsg::Pool {}) if self.pair_pools.contains_key(contract_addr) => {
+                        SystemResult::Ok(ContractResult::from(to_binary(&PoolResponse {
+                            assets: self.pair_pools[contract_addr].clone(),
+                            total_share: Uint128::zero(),
                         })))
                     }
-                    Ok(PairQueryMsg::Simulation { offer_asset }) => {
+                    Ok(PairQueryMsg::Simulation { offer_asset, .. }) => {
                         SystemResult::Ok(ContractResult::from(to_binary(&SimulationResponse {
                             return_amount: offer_asset.amount,
                             commission_amount: Uint128::zero(),
                             spread_amount: Uint128::zero(),
+                            exceeds_trade_limit: false,
                         })))
                     }
-                    Ok(PairQueryMsg::ReverseSimulation { ask_asset }) => SystemResult::Ok(
+                    Ok(PairQueryMsg::ReverseSimulation { ask_asset, .. }) => SystemResult::Ok(
                         ContractResult::from(to_binary(&ReverseSimulationResponse {
                             offer_amount: ask_asset.amount,
                             commission_amount: Uint128::zero(),
                             spread_amount: Uint128::zero(),
+                            exceeds_trade_limit: false,
                         })),
                     ),
-                    _ => match from_binary(msg).unwrap() {
-                        Cw20QueryMsg::TokenInfo {} => {
-                            let balances: &HashMap<String, Uint128> =
-                                match self.token_querier.balances.get(contract_addr) {
-                                    Some(balances) => balances,
+                    _ => match from_binary(msg) {
+                        Ok(WrapperQueryMsg::Fee {}) => {
+                            match self.wrapper_querier.fees.get(contract_addr) {
+                                Some(fee_rate) => SystemResult::Ok(ContractResult::from(
+                                    to_binary(&WrapperFeeResponse {
+                                        fee_rate: *fee_rate,
+                                    }),
+                                )),
+                                None => SystemResult::Err(SystemError::InvalidRequest {
+                                    error: format!(
+                                        "No wrapper info exists for the contract {}",
+                                        contract_addr
+                                    ),
+                                    request: msg.as_slice().into(),
+                                }),
+                            }
+                        }
+                        _ => match from_binary(msg).unwrap() {
+                            Cw20QueryMsg::TokenInfo {} => {
+                                let balances: &HashMap<String, Uint128> =
+                                    match self.token_querier.balances.get(contract_addr) {
+                                        Some(balances) => balances,
+                                        None => {
+                                            return SystemResult::Err(SystemError::InvalidRequest {
+                                                error: format!(
+                                                    "No balance info exists for the contract {}",
+                                                    contract_addr
+                                                ),
+                                                request: msg.as_slice().into(),
+                                            })
+                                        }
+                                    };
+
+                                let mut total_supply = Uint128::zero();
+
+                                for balance in balances {
+                                    total_supply += *balance.1;
+                                }
+
+                                SystemResult::Ok(ContractResult::Ok(
+                                    to_binary(&TokenInfoResponse {
+                                        name: "mAAPL".to_string(),
+                                        symbol: "mAAPL".to_string(),
+                                        decimals: 8,
+                                        total_supply,
+                                    })
+                                    .unwrap(),
+                                ))
+                            }
+                            Cw20QueryMsg::Balance { address } => {
+                                let balances: &HashMap<String, Uint128> =
+                                    match self.token_querier.balances.get(contract_addr) {
+                                        Some(balances) => balances,
+                                        None => {
+                                            return SystemResult::Err(SystemError::InvalidRequest {
+                                                error: format!(
+                                                    "No balance info exists for the contract {}",
+                                                    contract_addr
+                                                ),
+                                                request: msg.as_slice().into(),
+                                            })
+                                        }
+                                    };
+
+                                let balance = match balances.get(&address) {
+                                    Some(v) => *v,
                                     None => {
-                                        return SystemResult::Err(SystemError::InvalidRequest {
-                                            error: format!(
-                                                "No balance info exists for the contract {}",
-                                                contract_addr
-                                            ),
-                                            request: msg.as_slice().into(),
-                                        })
+                                        return SystemResult::Ok(ContractResult::Ok(
+                                            to_binary(&Cw20BalanceResponse {
+                                                balance: Uint128::zero(),
+                                            })
+                                            .unwrap(),
+                                        ));
                                     }
                                 };
 
-                            let mut total_supply = Uint128::zero();
-
-                            for balance in balances {
-                                total_supply += *balance.1;
+                                SystemResult::Ok(ContractResult::Ok(
+                                    to_binary(&Cw20BalanceResponse { balance }).unwrap(),
+                                ))
                             }
 
-                            SystemResult::Ok(ContractResult::Ok(
-                                to_binary(&TokenInfoResponse {
-                                    name: "mAAPL".to_string(),
-                                    symbol: "mAAPL".to_string(),
-                                    decimals: 8,
-                                    total_supply,
-                                })
-                                .unwrap(),
-                            ))
-                        }
-                        Cw20QueryMsg::Balance { address } => {
-                            let balances: &HashMap<String, Uint128> =
-                                match self.token_querier.balances.get(contract_addr) {
-                                    Some(balances) => balances,
-                                    None => {
-                                        return SystemResult::Err(SystemError::InvalidRequest {
-                                            error: format!(
-                                                "No balance info exists for the contract {}",
-                                                contract_addr
-                                            ),
-                                            request: msg.as_slice().into(),
-                                        })
-                                    }
-                                };
+                            Cw20QueryMsg::Allowance { owner, spender } => {
+                                let allowance = self
+                                    .token_allowances
+                                    .get(&(contract_addr.to_string(), owner, spender))
+                                    .copied()
+                                    .unwrap_or_default();
+                                SystemResult::Ok(ContractResult::Ok(
+                                    to_binary(&AllowanceResponse {
+                                        allowance,
+                                        expires: Expiration::Never {},
+                                    })
+                                    .unwrap(),
+                                ))
+                            }
 
-                            let balance = match balances.get(&address) {
-                                Some(v) => *v,
-                                None => {
-                                    return SystemResult::Ok(ContractResult::Ok(
-                                        to_binary(&Cw20BalanceResponse {
-                                            balance: Uint128::zero(),
-                                        })
+                            Cw20QueryMsg::Minter {} => {
+                                match self.token_minters.get(contract_addr) {
+                                    Some(minter) => SystemResult::Ok(ContractResult::Ok(
+                                        to_binary(&Some(MinterResponse {
+                                            minter: minter.to_string(),
+                                            cap: None,
+                                        }))
                                         .unwrap(),
-                                    ));
+                                    )),
+                                    None => panic!("DO NOT ENTER HERE"),
                                 }
-                            };
-
-                            SystemResult::Ok(ContractResult::Ok(
-                                to_binary(&Cw20BalanceResponse { balance }).unwrap(),
-                            ))
-                        }
+                            }
 
-                        _ => panic!("DO NOT ENTER HERE"),
+                            _ => panic!("DO NOT ENTER HERE"),
+                        },
                     },
                 },
             },
+            QueryRequest::Wasm(WasmQuery::CodeInfo { code_id }) => {
+                let mut res = CodeInfoResponse::default();
+                res.code_id = *code_id;
+                res.creator = "creator".to_string();
+                res.checksum = HexBinary::from(vec![*code_id as u8; 32]);
+                SystemResult::Ok(ContractResult::Ok(to_binary(&res).unwrap()))
+            }
             _ => self.base.handle_query(request),
         }
     }
@@ -320,6 +467,13 @@ impl WasmMockQuerier {
             token_querier: TokenQuerier::default(),
             tax_querier: TaxQuerier::default(),
             terraswap_factory_querier: TerraswapFactoryQuerier::default(),
+            wrapper_querier: WrapperQuerier::default(),
+            unresponsive_contracts: vec![],
+            token_minters: HashMap::new(),
+            token_allowances: HashMap::new(),
+            pair_infos: HashMap::new(),
+            pair_pools: HashMap::new(),
+            factories: vec![],
         }
     }
 
@@ -328,11 +482,56 @@ impl WasmMockQuerier {
         self.token_querier = TokenQuerier::new(balances);
     }
 
+    // configure the minter answered by the minter query of the token
+    pub fn with_token_minter(&mut self, contract_addr: &str, minter: &str) {
+        self.token_minters
+            .insert(contract_addr.to_string(), minter.to_string());
+    }
+
+    // configure the allowance of `spender` over the tokens of `owner`
+    pub fn with_token_allowance(
+        &mut self,
+        contract_addr: &str,
+        owner: &str,
+        spender: &str,
+        allowance: Uint128,
+    ) {
+        self.token_allowances.insert(
+            (
+                contract_addr.to_string(),
+                owner.to_string(),
+                spender.to_string(),
+            ),
+            allowance,
+        );
+    }
+
     // configure the token owner mock querier
+    // configure the pair info answered by the pair query of the pair
+    pub fn with_pair_info(&mut self, contract_addr: &str, pair_info: &PairInfo) {
+        self.pair_infos
+            .insert(contract_addr.to_string(), pair_info.clone());
+    }
+
+    // configure the reserves answered by the pool query of the pair
+    pub fn with_pair_pool(&mut self, contract_addr: &str, assets: [Asset; 2]) {
+        self.pair_pools.insert(contract_addr.to_string(), assets);
+    }
+
     pub fn with_tax(&mut self, rate: Decimal, caps: &[(&String, &Uint128)]) {
         self.tax_querier = TaxQuerier::new(rate, caps);
     }
 
+    // change the tax rate, keeping the caps, e.g. between two executes
+    pub fn with_tax_rate(&mut self, rate: Decimal) {
+        self.tax_querier.rate = rate;
+    }
+
+    // change the tax cap of `denom`, keeping the rate and the other caps
+    pub fn with_tax_cap(&mut self, denom: &str, cap: Uint128) {
+        self.tax_querier.caps.insert(denom.to_string(), cap);
+    }
+
     // configure the terraswap pair
     pub fn with_terraswap_factory(
         &mut self,
@@ -342,6 +541,50 @@ impl WasmMockQuerier {
         self.terraswap_factory_querier = TerraswapFactoryQuerier::new(pairs, native_token_decimals);
     }
 
+    // configure the wrapper contracts with their fee rates
+    pub fn with_wrapper_fees(&mut self, fees: &[(&String, &Decimal)]) {
+        self.wrapper_querier = WrapperQuerier::new(fees);
+    }
+
+    // configure the admin answered by the contract info query of the contract
+    pub fn with_contract_admin(&mut self, contract_addr: &str, admin: Option<String>) {
+        self.with_contract_admins(&[(contract_addr, admin)]);
+    }
+
+    pub fn with_contract_admins(&mut self, admins: &[(&str, Option<String>)]) {
+        let admins: HashMap<String, Option<String>> = admins
+            .iter()
+            .map(|(contract_addr, admin)| (contract_addr.to_string(), admin.clone()))
+            .collect();
+        self.base.update_wasm(move |request| match request {
+            WasmQuery::ContractInfo { contract_addr } if admins.contains_key(contract_addr) => {
+                let mut res = ContractInfoResponse::default();
+                res.code_id = 1;
+                res.creator = "creator".to_string();
+                res.admin = admins[contract_addr].clone();
+                SystemResult::Ok(ContractResult::Ok(to_binary(&res).unwrap()))
+            }
+            WasmQuery::ContractInfo { contract_addr }
+            | WasmQuery::Smart { contract_addr, .. }
+            | WasmQuery::Raw { contract_addr, .. } => {
+                SystemResult::Err(SystemError::NoSuchContract {
+                    addr: contract_addr.clone(),
+                })
+            }
+            _ => SystemResult::Err(SystemError::Unknown {}),
+        });
+    }
+
+    // configure the contracts answering the factory RouterBootstrap query
+    pub fn with_factories(&mut self, factories: &[&str]) {
+        self.factories = factories.iter().map(|f| f.to_string()).collect();
+    }
+
+    // configure the contracts failing every smart query
+    pub fn with_unresponsive_contracts(&mut self, contracts: &[&str]) {
+        self.unresponsive_contracts = contracts.iter().map(|c| c.to_string()).collect();
+    }
+
     pub fn with_balance(&mut self, balances: &[(&String, Vec<Coin>)]) {
         for (addr, balance) in balances {
             self.base.update_balance(addr.to_string(), balance.clone());
@@ -1,10 +1,12 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use crate::asset::{Asset, AssetInfo};
+use cosmwasm_schema::QueryResponses;
 
-use cosmwasm_std::{Decimal, Uint128};
-use cw20::Cw20ReceiveMsg;
+use crate::asset::{Asset, AssetInfo, PairInfo};
+
+use cosmwasm_std::{Addr, Binary, Decimal, Decimal256, StdError, StdResult, Uint128, Uint256};
+use cw20::{Cw20ReceiveMsg, Logo};
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 pub struct InstantiateMsg {
@@ -13,6 +15,17 @@ pub struct InstantiateMsg {
     /// Token contract code id for initialization
     pub token_code_id: u64,
     pub asset_decimals: [u8; 2],
+    /// Commission rate kept in the pool, 0.3% when not given
+    pub lp_fee_rate: Option<Decimal>,
+    /// Commission rate sent to the protocol fee collector, zero when not given
+    pub protocol_fee_rate: Option<Decimal>,
+    pub protocol_fee_collector: Option<String>,
+    /// Admin allowed to update the fees, pause the pool and rescue funds
+    /// besides the factory owner
+    pub pair_admin: Option<String>,
+    /// The highest spread swaps accept, uncapped when not given
+    #[serde(default)]
+    pub max_spread_cap: Option<Decimal>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -25,14 +38,127 @@ pub enum ExecuteMsg {
         receiver: Option<String>,
         deadline: Option<u64>,
         slippage_tolerance: Option<Decimal>,
+        /// Minimum LP tokens to mint to the receiver
+        min_lp_to_receive: Option<Uint128>,
     },
     /// Swap an offer asset to the other
     Swap {
         offer_asset: Asset,
         belief_price: Option<Decimal>,
+        /// How `belief_price` is quoted, offer per ask by default
+        belief_price_direction: Option<BeliefPriceDirection>,
         max_spread: Option<Decimal>,
         to: Option<String>,
         deadline: Option<u64>,
+        /// Delivers the return to the `to` contract with this message attached
+        to_msg: Option<Binary>,
+        /// Sends a cut of the return to a referral
+        referral: Option<Referral>,
+    },
+    /// ProvideLiquiditySingle swaps the optimal part of a native `asset` deposit
+    /// to the other asset in the pool and provides both sides
+    ProvideLiquiditySingle {
+        asset: Asset,
+        min_lp_to_receive: Option<Uint128>,
+        deadline: Option<u64>,
+    },
+    /// UpdateFeeConfig replaces the commission rates, only the factory owner
+    /// or the pair admin can execute it
+    UpdateFeeConfig {
+        lp_fee_rate: Decimal,
+        protocol_fee_rate: Decimal,
+        protocol_fee_collector: Option<String>,
+    },
+    /// UpdateAdmin replaces the pair admin, only the pair admin or the factory owner
+    /// can execute it. `None` removes the admin.
+    UpdateAdmin {
+        admin: Option<String>,
+    },
+    /// UpdateFeeTiers replaces the commission tiers, only the factory owner
+    /// or the pair admin can execute it.
+    /// Each tier is an offer threshold in bps of the offer reserve and the total commission
+    /// rate of swaps offering more, sorted by threshold.
+    UpdateFeeTiers {
+        fee_tiers: Vec<(Uint128, Decimal)>,
+    },
+    /// UpdateMaxSpreadCap bounds the max spread of every swap, only the factory owner
+    /// can execute it. `None` removes the cap.
+    UpdateMaxSpreadCap {
+        max_spread_cap: Option<Decimal>,
+    },
+    /// UpdateMaxTradeRatio bounds the return of a single swap to this fraction of
+    /// the ask reserve. Only the factory owner or the pair admin can execute it.
+    /// `None` removes the limit.
+    UpdateMaxTradeRatio {
+        max_trade_ratio: Option<Decimal>,
+    },
+    /// SetPoolStatus pauses swaps and deposits, withdrawals stay open.
+    /// Only the factory owner or the pair admin can execute it
+    SetPoolStatus {
+        swaps_paused: bool,
+        deposits_paused: bool,
+    },
+    /// Skim sends the balances above the reserves to `to`, the sender by default
+    Skim {
+        to: Option<String>,
+    },
+    /// Sync sets the reserves to the balances
+    Sync {},
+    /// Sends the accrued protocol fees to the protocol fee collector
+    CollectProtocolFees {},
+    /// Corrects the decimals of the pair assets
+    UpdateAssetDecimals {
+        asset_decimals: [u8; 2],
+    },
+    /// Provides the tokens escrowed with `deposit_for_liquidity` and the attached native funds
+    ProvideLiquidityFromDeposits {
+        slippage_tolerance: Option<Decimal>,
+        min_lp_to_receive: Option<Uint128>,
+    },
+    /// Sends the tokens escrowed with `deposit_for_liquidity` back
+    RefundDeposits {},
+    /// Sends an asset which is not part of the pair, sent to it by mistake.
+    /// Only the factory owner or the pair admin can execute it
+    RescueFunds {
+        asset_info: AssetInfo,
+        amount: Uint128,
+        to: String,
+    },
+    /// Buy exactly `ask_asset` with up to `max_offer_amount` of the native offer asset,
+    /// the rest is refunded
+    SwapExactOut {
+        ask_asset: Asset,
+        max_offer_amount: Uint128,
+        to: Option<String>,
+        deadline: Option<u64>,
+    },
+    /// CommitSwap stores the hash of a swap to reveal later, replacing the
+    /// commitment of the sender
+    CommitSwap {
+        hash: String,
+    },
+    /// RevealSwap executes the committed swap of the sender with the native offer
+    /// attached, within the reveal window of the commitment
+    RevealSwap {
+        params: CommitSwapParams,
+        salt: String,
+    },
+    /// CancelCommitment removes the commitment of the sender
+    CancelCommitment {},
+    /// UpdateCommitConfig sets the reveal window of commitments, only the factory owner
+    /// or the pair admin can execute it
+    UpdateCommitConfig {
+        min_delay_blocks: u64,
+        expiry_blocks: u64,
+    },
+    /// UpdateLiquidityTokenMarketing updates the marketing info and logo of the liquidity
+    /// token, only the factory owner or the pair admin can execute it.
+    /// `marketing` hands the marketing of the token over to another address
+    UpdateLiquidityTokenMarketing {
+        project: Option<String>,
+        description: Option<String>,
+        marketing: Option<String>,
+        logo: Option<Logo>,
     },
 }
 
@@ -42,23 +168,250 @@ pub enum Cw20HookMsg {
     /// Sell a given amount of asset
     Swap {
         belief_price: Option<Decimal>,
+        /// How `belief_price` is quoted, offer per ask by default
+        belief_price_direction: Option<BeliefPriceDirection>,
         max_spread: Option<Decimal>,
         to: Option<String>,
         deadline: Option<u64>,
+        /// Delivers the return to the `to` contract with this message attached
+        to_msg: Option<Binary>,
+        /// Sends a cut of the return to a referral
+        referral: Option<Referral>,
+    },
+    /// Buy exactly `ask_asset` with up to the sent amount, the rest is refunded
+    SwapExactOut {
+        ask_asset: Asset,
+        to: Option<String>,
+        deadline: Option<u64>,
+    },
+    /// Reveal the committed swap of the sender, offering the sent amount
+    RevealSwap {
+        params: CommitSwapParams,
+        salt: String,
+    },
+    /// Escrow the sent token for `provide_liquidity_from_deposits`
+    DepositForLiquidity {},
+    /// Provide liquidity with the sent token only
+    ProvideLiquiditySingle {
+        min_lp_to_receive: Option<Uint128>,
+        deadline: Option<u64>,
     },
     WithdrawLiquidity {
-        min_assets: Option<[Asset; 2]>,
+        /// Minimum refund of up to two pool assets, the others are unconstrained
+        min_assets: Option<Vec<Asset>>,
+        deadline: Option<u64>,
+        /// Receives the refund instead of the sender, the owner of an operator's `send_from`
+        receiver: Option<String>,
+    },
+    /// Withdraw liquidity into `ask_asset_info` only, the other refund is swapped
+    /// through the pool
+    WithdrawLiquiditySingle {
+        ask_asset_info: AssetInfo,
+        min_receive: Option<Uint128>,
         deadline: Option<u64>,
+        /// Receives the refund instead of the sender
+        receiver: Option<String>,
     },
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema, QueryResponses)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
+    #[returns(PairInfo)]
     Pair {},
+    #[returns(PoolResponse)]
     Pool {},
-    Simulation { offer_asset: Asset },
-    ReverseSimulation { ask_asset: Asset },
+    #[returns(Vec<Asset>)]
+    Share { amount: Uint128 },
+    #[returns([Asset; 2])]
+    Deposits { address: String },
+    #[returns(SimulationResponse)]
+    Simulation {
+        offer_asset: Asset,
+        referral: Option<Referral>,
+    },
+    #[returns(ReverseSimulationResponse)]
+    ReverseSimulation {
+        ask_asset: Asset,
+        referral: Option<Referral>,
+    },
+    #[returns(Option<CommitmentResponse>)]
+    Commitment { address: String },
+    #[returns(CommitConfig)]
+    CommitConfig {},
+    #[returns(SimulationBatchResponse)]
+    SimulationBatch {
+        offer_asset_info: AssetInfo,
+        amounts: Vec<Uint128>,
+    },
+    #[returns(ReverseSimulationBatchResponse)]
+    ReverseSimulationBatch {
+        ask_asset_info: AssetInfo,
+        amounts: Vec<Uint128>,
+    },
+    #[returns(FeeConfig)]
+    FeeConfig {},
+    #[returns(ConfigResponse)]
+    Config {},
+    #[returns(PoolStatus)]
+    PoolStatus {},
+    #[returns(CumulativePricesResponse)]
+    CumulativePrices {},
+    #[returns(ObservedTwapResponse)]
+    ObservedTwap { window_seconds: u64 },
+    #[returns(FeeStatsResponse)]
+    FeeStats {},
+    #[returns(SimulateProvideLiquiditySingleResponse)]
+    SimulateProvideLiquiditySingle { asset: Asset },
+    #[returns(SimulateProvideLiquidityResponse)]
+    SimulateProvideLiquidity {
+        assets: Vec<Asset>,
+        slippage_tolerance: Option<Decimal>,
+    },
+}
+
+/// SimulateProvideLiquidityResponse returns the LP tokens minted for a provision and
+/// the parts of the assets taken into the pools and refunded
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct SimulateProvideLiquidityResponse {
+    pub lp_minted: Uint128,
+    pub used_amounts: Vec<Asset>,
+    pub refund_amounts: Vec<Asset>,
+}
+
+/// SimulateProvideLiquiditySingleResponse returns the LP tokens minted for a
+/// single-sided deposit, the part of the deposit swapped to the other asset and
+/// the spread ratio of that swap
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct SimulateProvideLiquiditySingleResponse {
+    pub lp_amount: Uint128,
+    pub swap_amount: Uint128,
+    pub price_impact: Decimal,
+}
+
+/// ConfigResponse returns the factory of the pair and the limits of swaps
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct ConfigResponse {
+    pub factory: Addr,
+    pub pair_admin: Option<Addr>,
+    pub max_spread_cap: Option<Decimal>,
+    pub max_trade_ratio: Option<Decimal>,
+}
+
+/// CommitSwapParams are the swap hidden by a commitment. The commitment is the
+/// hex encoded sha256 of the JSON params, the salt and the sender address
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct CommitSwapParams {
+    pub offer_asset: Asset,
+    pub belief_price: Option<Decimal>,
+    pub max_spread: Option<Decimal>,
+    pub to: Option<String>,
+}
+
+/// CommitConfig bounds the reveal of a commitment, in blocks after its commit
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct CommitConfig {
+    pub min_delay_blocks: u64,
+    pub expiry_blocks: u64,
+}
+
+impl Default for CommitConfig {
+    fn default() -> Self {
+        CommitConfig {
+            min_delay_blocks: 2,
+            expiry_blocks: 100,
+        }
+    }
+}
+
+/// CommitmentResponse returns the committed hash and the height of the commit
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct CommitmentResponse {
+    pub hash: String,
+    pub height: u64,
+}
+
+/// PoolStatus tells which actions of the pool are paused
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema, Default)]
+pub struct PoolStatus {
+    pub swaps_paused: bool,
+    pub deposits_paused: bool,
+}
+
+/// CumulativePricesResponse returns the price accumulators up to the current block
+/// with the current reserves. The accumulators are time weighted sums of the prices
+/// scaled by 10^18 and wrap on overflow; price0 is the price of asset 0 in asset 1.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct CumulativePricesResponse {
+    pub assets: [Asset; 2],
+    pub price0_cumulative_last: Uint256,
+    pub price1_cumulative_last: Uint256,
+    pub block_time_last: u64,
+}
+
+/// FeeStatsResponse returns the commission the pair charged since `tracked_since`, the
+/// block time of the first tracked swap, none before it, with the current reserves.
+/// The fees saturate at the max amount.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct FeeStatsResponse {
+    pub cumulative_fees: [Asset; 2],
+    pub tracked_since: Option<u64>,
+    pub reserves: [Asset; 2],
+}
+
+/// ObservedTwapResponse returns the time weighted average prices since the oldest
+/// price observation within the window; price0 is the price of asset 0 in asset 1.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct ObservedTwapResponse {
+    pub price0_average: Decimal256,
+    pub price1_average: Decimal256,
+    pub observed_since: u64,
+}
+
+/// FeeConfig splits the swap commission between the pool and the protocol
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct FeeConfig {
+    pub lp_fee_rate: Decimal,
+    pub protocol_fee_rate: Decimal,
+    pub protocol_fee_collector: Option<Addr>,
+    /// Offer thresholds in bps of the offer reserve with the total commission rate
+    /// charged above them, sorted by threshold
+    #[serde(default)]
+    pub fee_tiers: Vec<(Uint128, Decimal)>,
+}
+
+impl FeeConfig {
+    pub fn total_fee_rate(&self) -> Decimal {
+        self.lp_fee_rate + self.protocol_fee_rate
+    }
+
+    /// The commission rate of a swap offering `offer_amount` to `offer_pool`, the rate of
+    /// the highest tier whose threshold the offer exceeds or the total fee rate
+    pub fn commission_rate(&self, offer_pool: Uint128, offer_amount: Uint128) -> Decimal {
+        let offer_bps = Uint256::from(offer_amount) * Uint256::from(10_000u128);
+        self.fee_tiers
+            .iter()
+            .rev()
+            .find(|(threshold, _)| {
+                offer_bps > Uint256::from(*threshold) * Uint256::from(offer_pool)
+            })
+            .map_or_else(
+                || self.total_fee_rate(),
+                |(_, commission_rate)| *commission_rate,
+            )
+    }
+}
+
+impl Default for FeeConfig {
+    /// 0.3% commission, all of it kept in the pool
+    fn default() -> Self {
+        FeeConfig {
+            lp_fee_rate: Decimal::permille(3),
+            protocol_fee_rate: Decimal::zero(),
+            protocol_fee_collector: None,
+            fee_tiers: vec![],
+        }
+    }
 }
 
 // We define a custom struct for each query response
@@ -68,22 +421,206 @@ pub struct PoolResponse {
     pub total_share: Uint128,
 }
 
+/// BeliefPriceDirection tells how a belief price is quoted, in whole units
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BeliefPriceDirection {
+    /// Offer asset per ask asset
+    OfferPerAsk,
+    /// Ask asset per offer asset
+    AskPerOffer,
+}
+
+/// Referral takes `bps` of the return of a swap for `address`,
+/// up to the `max_referral_bps` of the factory
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct Referral {
+    pub address: String,
+    pub bps: u16,
+}
+
 /// SimulationResponse returns swap simulation response
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 pub struct SimulationResponse {
     pub return_amount: Uint128,
     pub spread_amount: Uint128,
     pub commission_amount: Uint128,
+    /// Whether the swap returns more than the max trade ratio of the ask reserve
+    /// and would be rejected
+    #[serde(default)]
+    pub exceeds_trade_limit: bool,
 }
 
-/// ReverseSimulationResponse returns reverse swap simulation response
+/// ReverseSimulationResponse returns reverse swap simulation response.
+/// `offer_amount` is the least offer whose simulated return is at least the ask amount,
+/// and the spread and commission are the ones the simulation of that offer reports.
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 pub struct ReverseSimulationResponse {
     pub offer_amount: Uint128,
     pub spread_amount: Uint128,
     pub commission_amount: Uint128,
+    /// Whether the ask amount is more than the max trade ratio of the ask reserve
+    /// and the swap would be rejected
+    #[serde(default)]
+    pub exceeds_trade_limit: bool,
+}
+
+/// SimulationBatchResponse returns the simulations of the batch amounts, in order,
+/// against the same reserves
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct SimulationBatchResponse {
+    pub simulations: Vec<SimulationResponse>,
+}
+
+/// ReverseSimulationBatchResponse returns the reverse simulations of the batch
+/// amounts, in order, against the same reserves
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct ReverseSimulationBatchResponse {
+    pub simulations: Vec<ReverseSimulationResponse>,
 }
 
-/// We currently take no arguments for migrations
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
-pub struct MigrateMsg {}
+pub struct MigrateMsg {
+    /// Also migrates the liquidity token, which must have the pair as its admin,
+    /// to `liquidity_token_code_id` or the token code id of the factory
+    #[serde(default)]
+    pub update_liquidity_token: bool,
+    #[serde(default)]
+    pub liquidity_token_code_id: Option<u64>,
+}
+
+/// The outcome of swapping an offer amount in a constant product pool
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct SwapComputation {
+    /// Sent to the trader, after the commission
+    pub return_amount: Uint128,
+    /// Lost to the price impact against the pool price before the swap
+    pub spread_amount: Uint128,
+    /// Kept by the pool out of the return
+    pub commission_amount: Uint128,
+}
+
+/// The least offer which returns at least an ask amount, with what `compute_swap` charges on it
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct OfferComputation {
+    pub offer_amount: Uint128,
+    pub spread_amount: Uint128,
+    pub commission_amount: Uint128,
+}
+
+/// Swaps `offer_amount` against the pools, `return + spread + commission` adds up to
+/// the offer valued at the pool price `Decimal256::from_ratio(ask_pool, offer_pool)`
+pub fn compute_swap(
+    offer_pool: Uint128,
+    ask_pool: Uint128,
+    offer_amount: Uint128,
+    commission_rate: Decimal,
+) -> StdResult<SwapComputation> {
+    if offer_pool.is_zero() {
+        return Err(StdError::generic_err("offer pool is empty"));
+    }
+    let commission_rate: Decimal256 = commission_rate.into();
+    if commission_rate > Decimal256::one() {
+        return Err(StdError::generic_err("commission rate exceeds one"));
+    }
+
+    let offer_pool: Uint256 = offer_pool.into();
+    let ask_pool: Uint256 = ask_pool.into();
+    let offer_amount: Uint256 = offer_amount.into();
+
+    // offer => ask
+    // ask_amount = (ask_pool - cp / (offer_pool + offer_amount)) * (1 - commission_rate)
+    let return_amount = ask_pool
+        .checked_mul(offer_amount)?
+        .checked_div(offer_pool.checked_add(offer_amount)?)?;
+
+    // calculate spread & commission
+    let price = Decimal256::checked_from_ratio(ask_pool, offer_pool)
+        .map_err(|err| StdError::generic_err(err.to_string()))?;
+    let spread_amount = offer_amount
+        .checked_multiply_ratio(price.atomics(), Decimal256::one().atomics())
+        .map_err(|err| StdError::generic_err(err.to_string()))?
+        .checked_sub(return_amount)?;
+    let commission_amount = compute_commission(return_amount, commission_rate)?;
+
+    // commission will be absorbed to pool
+    let return_amount = return_amount.checked_sub(commission_amount)?;
+
+    Ok(SwapComputation {
+        return_amount: return_amount.try_into()?,
+        spread_amount: spread_amount.try_into()?,
+        commission_amount: commission_amount.try_into()?,
+    })
+}
+
+/// Commission on the return of a swap, rounded up in favor of the pool
+fn compute_commission(return_amount: Uint256, commission_rate: Decimal256) -> StdResult<Uint256> {
+    let mut commission_amount: Uint256 = return_amount * commission_rate;
+    if !commission_rate.is_zero()
+        && return_amount != (commission_amount * (Decimal256::one() / commission_rate))
+    {
+        commission_amount += Uint256::one();
+    }
+
+    // never more than the return itself
+    Ok(std::cmp::min(commission_amount, return_amount))
+}
+
+/// The exact inverse of `compute_swap`, returns the least offer amount whose
+/// return is at least `ask_amount`, with the spread and commission `compute_swap`
+/// charges on that offer.
+pub fn compute_offer_amount(
+    offer_pool: Uint128,
+    ask_pool: Uint128,
+    ask_amount: Uint128,
+    commission_rate: Decimal,
+) -> StdResult<OfferComputation> {
+    let offer_pool_256: Uint256 = offer_pool.into();
+    let ask_pool_256: Uint256 = ask_pool.into();
+    let ask_amount_256: Uint256 = ask_amount.into();
+    let commission_rate_256: Decimal256 = commission_rate.into();
+    if commission_rate_256 >= Decimal256::one() {
+        return Err(StdError::generic_err(
+            "commission rate must be less than one",
+        ));
+    }
+
+    // the least return before the commission deduction that leaves `ask_amount`,
+    // starting from ask_amount / (1 - commission_rate) rounded up
+    let return_after_commission = |amount: Uint256| -> StdResult<Uint256> {
+        Ok(amount - compute_commission(amount, commission_rate_256)?)
+    };
+    let one_minus_commission = Decimal256::one() - commission_rate_256;
+    let mut before_commission_deduction =
+        ask_amount_256.multiply_ratio(Decimal256::one().atomics(), one_minus_commission.atomics());
+    if before_commission_deduction >= ask_pool_256 {
+        return Err(StdError::generic_err("Ask amount exceeds the pool"));
+    }
+    while return_after_commission(before_commission_deduction)? < ask_amount_256 {
+        before_commission_deduction += Uint256::one();
+    }
+    while !before_commission_deduction.is_zero()
+        && return_after_commission(before_commission_deduction - Uint256::one())? >= ask_amount_256
+    {
+        before_commission_deduction -= Uint256::one();
+    }
+
+    if before_commission_deduction >= ask_pool_256 {
+        return Err(StdError::generic_err("Ask amount exceeds the pool"));
+    }
+
+    // the least offer with floor(ask_pool * offer / (offer_pool + offer)) >= before_commission_deduction,
+    // offer = ceil(before_commission_deduction * offer_pool / (ask_pool - before_commission_deduction))
+    let numerator = before_commission_deduction * offer_pool_256;
+    let denominator = ask_pool_256 - before_commission_deduction;
+    let offer_amount: Uint128 =
+        ((numerator + denominator - Uint256::one()) / denominator).try_into()?;
+
+    let swap = compute_swap(offer_pool, ask_pool, offer_amount, commission_rate)?;
+
+    Ok(OfferComputation {
+        offer_amount,
+        spread_amount: swap.spread_amount,
+        commission_amount: swap.commission_amount,
+    })
+}
@@ -1,14 +1,59 @@
 use crate::asset::{Asset, AssetInfo, PairInfo};
 use crate::factory::{NativeTokenDecimalsResponse, QueryMsg as FactoryQueryMsg};
-use crate::pair::{QueryMsg as PairQueryMsg, ReverseSimulationResponse, SimulationResponse};
+use crate::pair::{
+    PoolResponse, QueryMsg as PairQueryMsg, ReverseSimulationResponse, SimulationResponse,
+};
+use crate::router::{AstroportPairQueryMsg, MsgFormat};
 
-use classic_bindings::TerraQuery;
+use classic_bindings::{TerraQuerier, TerraQuery};
 use cosmwasm_std::{
-    to_binary, Addr, AllBalanceResponse, BalanceResponse, BankQuery, Coin, QuerierWrapper,
-    QueryRequest, StdResult, Uint128, WasmQuery,
+    to_binary, Addr, AllBalanceResponse, Api, BalanceResponse, BankQuery, Coin, Decimal,
+    Decimal256, QuerierWrapper, QueryRequest, StdError, StdResult, Uint128, Uint256, WasmQuery,
+};
+use std::ops::Mul;
+
+use cw20::{
+    AllowanceResponse, BalanceResponse as Cw20BalanceResponse, Cw20QueryMsg, TokenInfoResponse,
 };
 
-use cw20::{BalanceResponse as Cw20BalanceResponse, Cw20QueryMsg, TokenInfoResponse};
+static DECIMAL_FRACTION: Uint128 = Uint128::new(1_000_000_000_000_000_000u128);
+
+/// Returns the burn tax charged on a native transfer of `amount`
+pub fn compute_tax(
+    querier: &QuerierWrapper<TerraQuery>,
+    amount: Uint128,
+    denom: String,
+) -> StdResult<Uint128> {
+    let terra_querier = TerraQuerier::new(querier);
+    let tax_rate: Decimal = (terra_querier.query_tax_rate()?).rate;
+    let tax_cap: Uint128 = (terra_querier.query_tax_cap(denom)?).cap;
+    Ok(std::cmp::min(
+        amount.checked_sub(amount.multiply_ratio(
+            DECIMAL_FRACTION,
+            DECIMAL_FRACTION * tax_rate + DECIMAL_FRACTION,
+        ))?,
+        tax_cap,
+    ))
+}
+
+/// Returns the burn tax to add on top of `amount` so that `amount` is left after the transfer
+pub fn compute_reverse_tax(
+    querier: &QuerierWrapper<TerraQuery>,
+    amount: Uint128,
+    denom: String,
+) -> StdResult<Uint128> {
+    let terra_querier = TerraQuerier::new(querier);
+    let tax_rate: Decimal = (terra_querier.query_tax_rate()?).rate;
+    let tax_cap: Uint128 = (terra_querier.query_tax_cap(denom)?).cap;
+
+    let tax: Uint128 = (std::cmp::min(
+        Uint256::from(amount).mul(Decimal256::one() + Decimal256::from(tax_rate)),
+        Uint256::from(amount + tax_cap),
+    ) - Uint256::from(amount))
+    .try_into()?;
+
+    Ok(tax)
+}
 
 pub fn query_balance(
     querier: &QuerierWrapper<TerraQuery>,
@@ -51,6 +96,59 @@ pub fn query_token_balance(
     Ok(res.balance)
 }
 
+/// Balances of `account` in each of `assets`, in input order. Native denoms share a single
+/// bank `AllBalances` query and missing ones read as zero; tokens are queried one by one.
+pub fn query_balances(
+    querier: &QuerierWrapper<TerraQuery>,
+    api: &dyn Api,
+    account: Addr,
+    assets: &[AssetInfo],
+) -> StdResult<Vec<Asset>> {
+    let native_balances = if assets.iter().any(|info| info.is_native_token()) {
+        query_all_balances(querier, account.clone())?
+    } else {
+        vec![]
+    };
+
+    assets
+        .iter()
+        .map(|info| {
+            let amount = match info {
+                AssetInfo::NativeToken { denom } => native_balances
+                    .iter()
+                    .find(|coin| &coin.denom == denom)
+                    .map(|coin| coin.amount)
+                    .unwrap_or_default(),
+                AssetInfo::Token { contract_addr } => query_token_balance(
+                    querier,
+                    api.addr_validate(contract_addr)?,
+                    account.clone(),
+                )?,
+            };
+
+            Ok(Asset {
+                info: info.clone(),
+                amount,
+            })
+        })
+        .collect()
+}
+
+pub fn query_token_allowance(
+    querier: &QuerierWrapper<TerraQuery>,
+    contract_addr: Addr,
+    owner: Addr,
+    spender: Addr,
+) -> StdResult<AllowanceResponse> {
+    querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
+        contract_addr: contract_addr.to_string(),
+        msg: to_binary(&Cw20QueryMsg::Allowance {
+            owner: owner.to_string(),
+            spender: spender.to_string(),
+        })?,
+    }))
+}
+
 pub fn query_token_info(
     querier: &QuerierWrapper<TerraQuery>,
     contract_addr: Addr,
@@ -89,6 +187,16 @@ pub fn query_pair_info(
     }))
 }
 
+pub fn query_pool(
+    querier: &QuerierWrapper<TerraQuery>,
+    pair_contract: Addr,
+) -> StdResult<PoolResponse> {
+    querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
+        contract_addr: pair_contract.to_string(),
+        msg: to_binary(&PairQueryMsg::Pool {})?,
+    }))
+}
+
 pub fn simulate(
     querier: &QuerierWrapper<TerraQuery>,
     pair_contract: Addr,
@@ -98,6 +206,7 @@ pub fn simulate(
         contract_addr: pair_contract.to_string(),
         msg: to_binary(&PairQueryMsg::Simulation {
             offer_asset: offer_asset.clone(),
+            referral: None,
         })?,
     }))
 }
@@ -111,18 +220,159 @@ pub fn reverse_simulate(
         contract_addr: pair_contract.to_string(),
         msg: to_binary(&PairQueryMsg::ReverseSimulation {
             ask_asset: ask_asset.clone(),
+            referral: None,
         })?,
     }))
 }
 
+/// Simulates swapping `offer_asset` for `ask_asset_info` with the query of `msg_format`
+pub fn simulate_in_format(
+    querier: &QuerierWrapper<TerraQuery>,
+    pair_contract: Addr,
+    offer_asset: &Asset,
+    ask_asset_info: &AssetInfo,
+    msg_format: &MsgFormat,
+) -> StdResult<SimulationResponse> {
+    match msg_format {
+        MsgFormat::Classic => simulate(querier, pair_contract, offer_asset),
+        MsgFormat::AstroportV2 => querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
+            contract_addr: pair_contract.to_string(),
+            msg: to_binary(&AstroportPairQueryMsg::Simulation {
+                offer_asset: offer_asset.clone(),
+                ask_asset_info: Some(ask_asset_info.clone()),
+            })?,
+        })),
+    }
+}
+
+/// Reverse simulates receiving `ask_asset` for `offer_asset_info` with the query of `msg_format`
+pub fn reverse_simulate_in_format(
+    querier: &QuerierWrapper<TerraQuery>,
+    pair_contract: Addr,
+    offer_asset_info: &AssetInfo,
+    ask_asset: &Asset,
+    msg_format: &MsgFormat,
+) -> StdResult<ReverseSimulationResponse> {
+    match msg_format {
+        MsgFormat::Classic => reverse_simulate(querier, pair_contract, ask_asset),
+        MsgFormat::AstroportV2 => querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
+            contract_addr: pair_contract.to_string(),
+            msg: to_binary(&AstroportPairQueryMsg::ReverseSimulation {
+                offer_asset_info: Some(offer_asset_info.clone()),
+                ask_asset: ask_asset.clone(),
+            })?,
+        })),
+    }
+}
+
+/// Simulates swapping `offer_asset` in `pair` with the query of `msg_format`, optionally deducting the tax of sending
+/// the offer asset to the pair and the tax of sending the returned asset on
+pub fn simulate_with_tax(
+    querier: &QuerierWrapper<TerraQuery>,
+    pair: &PairInfo,
+    msg_format: &MsgFormat,
+    offer_asset: &Asset,
+    apply_offer_tax: bool,
+    apply_ask_tax: bool,
+) -> StdResult<SimulationResponse> {
+    let ask_asset_info = counter_asset_info(pair, &offer_asset.info)?;
+
+    let mut offer_asset = offer_asset.clone();
+    if apply_offer_tax {
+        offer_asset.amount = offer_asset
+            .amount
+            .checked_sub(offer_asset.compute_tax(querier)?)?;
+    }
+
+    let mut res = simulate_in_format(
+        querier,
+        Addr::unchecked(&pair.contract_addr),
+        &offer_asset,
+        &ask_asset_info,
+        msg_format,
+    )?;
+
+    if apply_ask_tax {
+        let return_asset = Asset {
+            info: ask_asset_info,
+            amount: res.return_amount,
+        };
+        res.return_amount = res
+            .return_amount
+            .checked_sub(return_asset.compute_tax(querier)?)?;
+    }
+
+    Ok(res)
+}
+
+/// Reverse simulates receiving `ask_asset` from `pair` with the query of `msg_format`, optionally adding the tax of sending
+/// the offer asset to the pair and the tax of sending the returned asset on
+pub fn reverse_simulate_with_tax(
+    querier: &QuerierWrapper<TerraQuery>,
+    pair: &PairInfo,
+    msg_format: &MsgFormat,
+    ask_asset: &Asset,
+    apply_offer_tax: bool,
+    apply_ask_tax: bool,
+) -> StdResult<ReverseSimulationResponse> {
+    let offer_asset_info = counter_asset_info(pair, &ask_asset.info)?;
+
+    // the pair must return enough to cover the tax on delivery
+    let mut ask_asset = ask_asset.clone();
+    if apply_ask_tax {
+        if let AssetInfo::NativeToken { denom } = &ask_asset.info {
+            ask_asset.amount = ask_asset.amount.checked_add(compute_reverse_tax(
+                querier,
+                ask_asset.amount,
+                denom.to_string(),
+            )?)?;
+        }
+    }
+
+    let mut res = reverse_simulate_in_format(
+        querier,
+        Addr::unchecked(&pair.contract_addr),
+        &offer_asset_info,
+        &ask_asset,
+        msg_format,
+    )?;
+
+    if apply_offer_tax {
+        if let AssetInfo::NativeToken { denom } = offer_asset_info {
+            res.offer_amount = res.offer_amount.checked_add(compute_reverse_tax(
+                querier,
+                res.offer_amount,
+                denom,
+            )?)?;
+        }
+    }
+
+    Ok(res)
+}
+
+/// The asset of `pair` on the other side of `asset_info`
+fn counter_asset_info(pair: &PairInfo, asset_info: &AssetInfo) -> StdResult<AssetInfo> {
+    if pair.asset_infos[0] == *asset_info {
+        Ok(pair.asset_infos[1].clone())
+    } else if pair.asset_infos[1] == *asset_info {
+        Ok(pair.asset_infos[0].clone())
+    } else {
+        Err(StdError::generic_err(format!(
+            "{} is not an asset of the pair {}",
+            asset_info, pair.contract_addr
+        )))
+    }
+}
+
+/// Reads the pair info from the pair contract itself, without the factory which created it
 pub fn query_pair_info_from_pair(
     querier: &QuerierWrapper<TerraQuery>,
     pair_contract: Addr,
 ) -> StdResult<PairInfo> {
-    let pair_info: PairInfo = querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
-        contract_addr: pair_contract.to_string(),
-        msg: to_binary(&PairQueryMsg::Pair {})?,
-    }))?;
-
-    Ok(pair_info)
+    querier
+        .query(&QueryRequest::Wasm(WasmQuery::Smart {
+            contract_addr: pair_contract.to_string(),
+            msg: to_binary(&PairQueryMsg::Pair {})?,
+        }))
+        .map_err(|err| StdError::generic_err(format!("{} is not a pair: {}", pair_contract, err)))
 }
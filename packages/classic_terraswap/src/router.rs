@@ -1,16 +1,31 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use cosmwasm_std::Uint128;
-use cw20::Cw20ReceiveMsg;
+use cosmwasm_schema::QueryResponses;
 
-use crate::asset::AssetInfo;
+use classic_bindings::TerraMsg;
+use cosmwasm_std::{
+    to_binary, Api, Coin, CosmosMsg, Decimal, StdError, StdResult, Uint128, WasmMsg,
+};
+use cw20::{Cw20ExecuteMsg, Cw20ReceiveMsg};
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::asset::{Asset, AssetInfo, AssetInfoValidated};
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 pub struct InstantiateMsg {
     pub terraswap_factory: String,
     pub loop_factory: String,
     pub astroport_factory: String,
+    /// skips the factory `Config` query sanity check, for factories
+    /// instantiated in the same transaction
+    #[serde(default)]
+    pub unchecked: bool,
+    /// checks that every factory answers the factory `RouterBootstrap` query,
+    /// instead of the `Config` query
+    #[serde(default)]
+    pub validate_factories: bool,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
@@ -32,9 +47,97 @@ pub enum SwapOperation {
         offer_asset_info: AssetInfo,
         ask_asset_info: AssetInfo,
     },
+    /// Wrap a native token into its cw20 representation
+    /// through the wrapper contract registered for the denom
+    Wrap {
+        native_denom: String,
+        wrapped_token: String,
+    },
+    /// Unwrap a cw20 wrapped token back into the native token
+    /// through the wrapper contract registered for the denom
+    Unwrap {
+        wrapped_token: String,
+        native_denom: String,
+    },
 }
 
 impl SwapOperation {
+    pub fn native_swap(offer_denom: impl Into<String>, ask_denom: impl Into<String>) -> Self {
+        SwapOperation::NativeSwap {
+            offer_denom: offer_denom.into(),
+            ask_denom: ask_denom.into(),
+        }
+    }
+
+    pub fn terraswap(offer_asset_info: AssetInfo, ask_asset_info: AssetInfo) -> Self {
+        SwapOperation::TerraSwap {
+            offer_asset_info,
+            ask_asset_info,
+        }
+    }
+
+    pub fn loop_swap(offer_asset_info: AssetInfo, ask_asset_info: AssetInfo) -> Self {
+        SwapOperation::Loop {
+            offer_asset_info,
+            ask_asset_info,
+        }
+    }
+
+    pub fn astroport(offer_asset_info: AssetInfo, ask_asset_info: AssetInfo) -> Self {
+        SwapOperation::Astroport {
+            offer_asset_info,
+            ask_asset_info,
+        }
+    }
+
+    pub fn wrap(native_denom: impl Into<String>, wrapped_token: impl Into<String>) -> Self {
+        SwapOperation::Wrap {
+            native_denom: native_denom.into(),
+            wrapped_token: wrapped_token.into(),
+        }
+    }
+
+    pub fn unwrap(wrapped_token: impl Into<String>, native_denom: impl Into<String>) -> Self {
+        SwapOperation::Unwrap {
+            wrapped_token: wrapped_token.into(),
+            native_denom: native_denom.into(),
+        }
+    }
+
+    /// Returns the name of the dex or the module executing the operation
+    pub fn dex_name(&self) -> &'static str {
+        match self {
+            SwapOperation::NativeSwap { .. } => "native",
+            SwapOperation::TerraSwap { .. } => "terraswap",
+            SwapOperation::Loop { .. } => "loop",
+            SwapOperation::Astroport { .. } => "astroport",
+            SwapOperation::Wrap { .. } | SwapOperation::Unwrap { .. } => "wrapper",
+        }
+    }
+
+    pub fn get_offer_asset_info(&self) -> AssetInfo {
+        match self {
+            SwapOperation::NativeSwap { offer_denom, .. } => AssetInfo::NativeToken {
+                denom: offer_denom.clone(),
+            },
+            SwapOperation::TerraSwap {
+                offer_asset_info, ..
+            }
+            | SwapOperation::Loop {
+                offer_asset_info, ..
+            }
+            | SwapOperation::Astroport {
+                offer_asset_info, ..
+            } => offer_asset_info.clone(),
+            SwapOperation::Wrap { native_denom, .. } => AssetInfo::NativeToken {
+                denom: native_denom.clone(),
+            },
+            SwapOperation::Unwrap { wrapped_token, .. } => AssetInfo::Token {
+                contract_addr: wrapped_token.clone(),
+            },
+        }
+    }
+
     pub fn get_target_asset_info(&self) -> AssetInfo {
         match self {
             SwapOperation::NativeSwap { ask_denom, .. } => AssetInfo::NativeToken {
@@ -43,28 +146,406 @@ impl SwapOperation {
             SwapOperation::TerraSwap { ask_asset_info, .. }
             | SwapOperation::Loop { ask_asset_info, .. }
             | SwapOperation::Astroport { ask_asset_info, .. } => ask_asset_info.clone(),
+            SwapOperation::Wrap { wrapped_token, .. } => AssetInfo::Token {
+                contract_addr: wrapped_token.clone(),
+            },
+            SwapOperation::Unwrap { native_denom, .. } => AssetInfo::NativeToken {
+                denom: native_denom.clone(),
+            },
         }
     }
 }
 
+/// Checks the operations form one continuous route with a single output asset,
+/// each hop offering what the previous hop returned, and returns the validated output asset
+pub fn assert_operations(
+    api: &dyn Api,
+    operations: &[SwapOperation],
+) -> StdResult<AssetInfoValidated> {
+    let mut ask_assets: HashSet<String> = HashSet::new();
+    let mut target_asset = None;
+    for (i, operation) in operations.iter().enumerate() {
+        let offer_asset = operation.get_offer_asset_info();
+        let ask_asset = operation.get_target_asset_info();
+        offer_asset.validate(api)?;
+        target_asset = Some(ask_asset.validate(api)?);
+
+        if i > 0 {
+            let prev_ask_asset = operations[i - 1].get_target_asset_info();
+            if prev_ask_asset != offer_asset {
+                return Err(StdError::generic_err(format!(
+                    "invalid operations; hop {} offers {} but the previous hop returns {}",
+                    i, offer_asset, prev_ask_asset
+                )));
+            }
+        }
+
+        ask_assets.remove(&offer_asset.to_string());
+        ask_assets.insert(ask_asset.to_string());
+    }
+
+    match target_asset {
+        Some(target_asset) if ask_assets.len() == 1 => Ok(target_asset),
+        _ => Err(StdError::generic_err(
+            "invalid operations; multiple output token",
+        )),
+    }
+}
+
+/// Builds the message of a multi-hop swap, checking the route with `assert_operations`
+/// like the router does
+///
+/// ```
+/// use classic_terraswap::asset::AssetInfo;
+/// use classic_terraswap::router::RouteBuilder;
+/// use cosmwasm_std::testing::MockApi;
+/// use cosmwasm_std::Uint128;
+///
+/// let route = RouteBuilder::new()
+///     .native_hop("uluna", "uusd")
+///     .terraswap_hop(
+///         AssetInfo::NativeToken { denom: "uusd".to_string() },
+///         AssetInfo::Token { contract_addr: "asset0000".to_string() },
+///     )
+///     .minimum_receive(Uint128::from(100u128))
+///     .build(&MockApi::default(), "router0000", Uint128::from(1000u128))
+///     .unwrap();
+/// ```
+///
+/// Market swaps only take denoms, a cw20 can not be routed through them:
+///
+/// ```compile_fail
+/// use classic_terraswap::asset::AssetInfo;
+/// use classic_terraswap::router::RouteBuilder;
+///
+/// RouteBuilder::new().native_hop(
+///     AssetInfo::Token { contract_addr: "asset0000".to_string() },
+///     "uusd",
+/// );
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RouteBuilder {
+    operations: Vec<SwapOperation>,
+    minimum_receive: Option<Uint128>,
+    minimum_receive_price: Option<Decimal>,
+    to: Option<String>,
+    deadline: Option<u64>,
+    unwind_receiver: Option<String>,
+}
+
+impl RouteBuilder {
+    pub fn new() -> Self {
+        RouteBuilder::default()
+    }
+
+    pub fn hop(mut self, operation: SwapOperation) -> Self {
+        self.operations.push(operation);
+        self
+    }
+
+    pub fn native_hop(self, offer_denom: impl Into<String>, ask_denom: impl Into<String>) -> Self {
+        self.hop(SwapOperation::native_swap(offer_denom, ask_denom))
+    }
+
+    pub fn terraswap_hop(self, offer_asset_info: AssetInfo, ask_asset_info: AssetInfo) -> Self {
+        self.hop(SwapOperation::terraswap(offer_asset_info, ask_asset_info))
+    }
+
+    pub fn loop_hop(self, offer_asset_info: AssetInfo, ask_asset_info: AssetInfo) -> Self {
+        self.hop(SwapOperation::loop_swap(offer_asset_info, ask_asset_info))
+    }
+
+    pub fn astroport_hop(self, offer_asset_info: AssetInfo, ask_asset_info: AssetInfo) -> Self {
+        self.hop(SwapOperation::astroport(offer_asset_info, ask_asset_info))
+    }
+
+    pub fn wrap_hop(
+        self,
+        native_denom: impl Into<String>,
+        wrapped_token: impl Into<String>,
+    ) -> Self {
+        self.hop(SwapOperation::wrap(native_denom, wrapped_token))
+    }
+
+    pub fn unwrap_hop(
+        self,
+        wrapped_token: impl Into<String>,
+        native_denom: impl Into<String>,
+    ) -> Self {
+        self.hop(SwapOperation::unwrap(wrapped_token, native_denom))
+    }
+
+    pub fn minimum_receive(mut self, minimum_receive: Uint128) -> Self {
+        self.minimum_receive = Some(minimum_receive);
+        self
+    }
+
+    /// Asks at least `price` of the last ask asset per offer asset,
+    /// instead of a `minimum_receive` amount
+    pub fn minimum_receive_price(mut self, price: Decimal) -> Self {
+        self.minimum_receive_price = Some(price);
+        self
+    }
+
+    pub fn to(mut self, to: impl Into<String>) -> Self {
+        self.to = Some(to.into());
+        self
+    }
+
+    pub fn deadline(mut self, deadline: u64) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Sends the return, a registered IBC denom, back to its origin chain
+    /// to `unwind_receiver` with `auto_unwind`
+    pub fn auto_unwind(mut self, unwind_receiver: impl Into<String>) -> Self {
+        self.unwind_receiver = Some(unwind_receiver.into());
+        self
+    }
+
+    /// The hops added so far, e.g. to simulate the route before building it
+    pub fn operations(&self) -> &[SwapOperation] {
+        &self.operations
+    }
+
+    /// Swaps `offer_amount` of the first hop's offer asset through `router`
+    pub fn build(
+        self,
+        api: &dyn Api,
+        router: impl Into<String>,
+        offer_amount: Uint128,
+    ) -> StdResult<RouteMsg> {
+        if self.operations.is_empty() {
+            return Err(StdError::generic_err("must provide operations"));
+        }
+        assert_operations(api, &self.operations)?;
+        if self.minimum_receive.is_some() && self.minimum_receive_price.is_some() {
+            return Err(StdError::generic_err(
+                "minimum_receive and minimum_receive_price can't be both given",
+            ));
+        }
+
+        let router = router.into();
+        match self.operations[0].get_offer_asset_info() {
+            AssetInfo::NativeToken { denom } => Ok(RouteMsg::Execute {
+                router,
+                msg: ExecuteMsg::ExecuteSwapOperations {
+                    operations: self.operations,
+                    minimum_receive: self.minimum_receive,
+                    minimum_receive_price: self.minimum_receive_price,
+                    to: self.to,
+                    deadline: self.deadline,
+                    offer_amount: Some(offer_amount),
+                    auto_unwind: self.unwind_receiver.is_some(),
+                    unwind_receiver: self.unwind_receiver,
+                },
+                funds: vec![Coin {
+                    denom,
+                    amount: offer_amount,
+                }],
+            }),
+            AssetInfo::Token { contract_addr } => {
+                let hook = Cw20HookMsg::ExecuteSwapOperations {
+                    operations: self.operations,
+                    minimum_receive: self.minimum_receive,
+                    minimum_receive_price: self.minimum_receive_price,
+                    to: self.to,
+                    deadline: self.deadline,
+                    auto_unwind: self.unwind_receiver.is_some(),
+                    unwind_receiver: self.unwind_receiver,
+                };
+                let send = Cw20ExecuteMsg::Send {
+                    contract: router,
+                    amount: offer_amount,
+                    msg: to_binary(&hook)?,
+                };
+
+                Ok(RouteMsg::Cw20Send {
+                    token: contract_addr,
+                    hook,
+                    send,
+                })
+            }
+        }
+    }
+}
+
+/// A swap built by `RouteBuilder`
+#[derive(Clone, Debug, PartialEq)]
+pub enum RouteMsg {
+    /// The native offer asset is attached as `funds` of `msg` to the router
+    Execute {
+        router: String,
+        msg: ExecuteMsg,
+        funds: Vec<Coin>,
+    },
+    /// The cw20 offer asset is sent to the router by `send` on `token`, carrying `hook`
+    Cw20Send {
+        token: String,
+        hook: Cw20HookMsg,
+        send: Cw20ExecuteMsg,
+    },
+}
+
+impl RouteMsg {
+    pub fn into_cosmos_msg<T>(self) -> StdResult<CosmosMsg<T>> {
+        Ok(match self {
+            RouteMsg::Execute { router, msg, funds } => CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: router,
+                msg: to_binary(&msg)?,
+                funds,
+            }),
+            RouteMsg::Cw20Send { token, send, .. } => CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: token,
+                msg: to_binary(&send)?,
+                funds: vec![],
+            }),
+        })
+    }
+}
+
+/// AccountLimits caps the daily offer amount of each account,
+/// measured in `asset` through a single-hop terraswap simulation
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct AccountLimits {
+    pub asset: AssetInfo,
+    pub max_amount_per_day: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FeeSide {
+    /// The fee is taken from the offer asset before the first hop
+    Offer,
+    /// The fee is taken from the ask asset after the last hop
+    Ask,
+}
+
+/// RoutingFee is charged on every `execute_swap_operations` and sent to the collector
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct RoutingFee {
+    pub rate: Decimal,
+    pub collector: String,
+    pub fee_side: FeeSide,
+}
+
+/// ReferenceSource is the price a guarded hop is held against
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReferenceSource {
+    /// The spot price of another pair of the same assets, a TWAP pair or a deeper pool
+    Pair { contract_addr: String },
+    /// A fixed price, in ask asset per offer asset of the guard
+    Constant { price: Decimal },
+}
+
+/// GuardConfig halts routing through a pool whose spot price deviates from
+/// the reference by more than `max_deviation`, as a fraction of the reference
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct GuardConfig {
+    pub reference: ReferenceSource,
+    pub max_deviation: Decimal,
+}
+
+/// GasConfig holds the gas `EstimateRouteGas` counts for the route and each of its operations
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct GasConfig {
+    /// Gas of the transaction calling the router, counted once per route
+    pub base: u64,
+    pub terraswap_hop: u64,
+    pub loop_hop: u64,
+    pub astroport_hop: u64,
+    pub native_swap: u64,
+    /// Gas of a `Wrap` or `Unwrap` operation
+    pub wrapper_hop: u64,
+    /// Gas of the minimum receive assertion closing the route
+    pub assertion: u64,
+}
+
+impl Default for GasConfig {
+    fn default() -> Self {
+        GasConfig {
+            base: 150_000,
+            terraswap_hop: 250_000,
+            loop_hop: 250_000,
+            astroport_hop: 300_000,
+            native_swap: 150_000,
+            wrapper_hop: 150_000,
+            assertion: 50_000,
+        }
+    }
+}
+
+impl GasConfig {
+    /// Returns the gas of `operations` followed by the minimum receive assertion
+    pub fn estimate(&self, operations: &[SwapOperation]) -> u64 {
+        operations
+            .iter()
+            .map(|operation| match operation {
+                SwapOperation::NativeSwap { .. } => self.native_swap,
+                SwapOperation::TerraSwap { .. } => self.terraswap_hop,
+                SwapOperation::Loop { .. } => self.loop_hop,
+                SwapOperation::Astroport { .. } => self.astroport_hop,
+                SwapOperation::Wrap { .. } | SwapOperation::Unwrap { .. } => self.wrapper_hop,
+            })
+            .fold(
+                self.base.saturating_add(self.assertion),
+                u64::saturating_add,
+            )
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum ExecuteMsg {
     Receive(Cw20ReceiveMsg),
     /// Execute multiple BuyOperation
+    /// `offer_amount` makes the first hop swap exactly that amount out of the router balance
+    /// instead of the whole balance of the offer asset. It must equal the attached funds of
+    /// the offer denom, only the owner may offer funds the router already holds.
+    /// `minimum_receive_price` asks at least that much of the ask asset per offer asset,
+    /// it can't be given with `minimum_receive`.
+    /// `auto_unwind` transfers the return, a registered IBC denom, back to its origin chain
+    /// to `unwind_receiver` instead of sending it to `to`
     ExecuteSwapOperations {
         operations: Vec<SwapOperation>,
         minimum_receive: Option<Uint128>,
+        #[serde(default)]
+        minimum_receive_price: Option<Decimal>,
         to: Option<String>,
         deadline: Option<u64>,
+        offer_amount: Option<Uint128>,
+        #[serde(default)]
+        auto_unwind: bool,
+        #[serde(default)]
+        unwind_receiver: Option<String>,
     },
 
     /// Internal use
-    /// Swap all offer tokens to ask token
+    /// Swap `offer_amount` or all offer tokens to ask token
     ExecuteSwapOperation {
         operation: SwapOperation,
         to: Option<String>,
         deadline: Option<u64>,
+        offer_amount: Option<Uint128>,
+        /// Route id and hop index emitted in the `terraswap_router_hop` event
+        route_id: Option<String>,
+        hop_index: Option<u32>,
+    },
+    /// Internal use
+    /// Send the routing fee of the ask asset to the collector and the rest to the receiver
+    DistributeAskAsset {
+        asset_info: AssetInfo,
+        receiver: String,
+    },
+    /// Internal use
+    /// Send the return of the route, less the routing fee of the ask asset,
+    /// back to the origin chain of `ibc_denom`
+    UnwindAskAsset {
+        ibc_denom: String,
+        unwind_receiver: String,
+        minimum_receive: Option<Uint128>,
     },
     /// Internal use
     /// Check the swap amount is exceed minimum_receive
@@ -74,45 +555,373 @@ pub enum ExecuteMsg {
         minimum_receive: Uint128,
         receiver: String,
     },
+    /// UpdateWrapper registers or removes (with `None`) the wrapper contract of a native denom
+    /// and the cw20 token it mints
+    UpdateWrapper {
+        native_denom: String,
+        wrapper: Option<WrapperInfo>,
+    },
+    /// UpdateIbcDenom registers or removes (with `None`) the channel an IBC denom is
+    /// transferred back to its origin chain through
+    UpdateIbcDenom {
+        ibc_denom: String,
+        origin: Option<IbcOrigin>,
+    },
+    /// SettleUnwind closes a pending unwind transfer once its outcome is known, sending
+    /// the amount back to the initiator when the transfer was refunded to the router
+    SettleUnwind {
+        id: u64,
+        refund: bool,
+    },
+    /// UpdateAccountLimits enables or disables (with `None`) the per-account daily swap cap
+    UpdateAccountLimits {
+        account_limits: Option<AccountLimits>,
+    },
+    /// UpdateRoutingFee sets or removes (with `None`) the routing fee
+    UpdateRoutingFee {
+        routing_fee: Option<RoutingFee>,
+    },
+    /// SetDexEnabled enables or disables routing through `terraswap`, `loop` or `astroport`
+    SetDexEnabled {
+        dex: String,
+        enabled: bool,
+    },
+    /// SetDexMsgFormat sets the format of the swap messages and simulation queries
+    /// sent to the pairs of `terraswap`, `loop` or `astroport`
+    SetDexMsgFormat {
+        dex: String,
+        msg_format: MsgFormat,
+    },
+    /// SetTokenTransferFee registers the fee, in bps, a cw20 token takes from every
+    /// transfer. Simulations and the minimum receive assertion account for it, 0 removes it
+    SetTokenTransferFee {
+        token: String,
+        bps: u16,
+    },
+    /// SetPriceGuard guards the pools of `offer_asset_info` and `ask_asset_info`, in both
+    /// directions, against price deviations from a reference. `None` removes the guard
+    SetPriceGuard {
+        offer_asset_info: AssetInfo,
+        ask_asset_info: AssetInfo,
+        guard: Option<GuardConfig>,
+    },
+    /// UpdateGasConfig sets the gas `EstimateRouteGas` counts by operation
+    UpdateGasConfig {
+        gas_config: GasConfig,
+    },
+    /// UpdateConfig replaces the given factories, sanity-checked like at instantiate
+    UpdateConfig {
+        terraswap_factory: Option<String>,
+        loop_factory: Option<String>,
+        astroport_factory: Option<String>,
+        #[serde(default)]
+        unchecked: bool,
+        #[serde(default)]
+        validate_factories: bool,
+    },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum Cw20HookMsg {
+    /// `minimum_receive_price` asks at least that much of the ask asset per token sent
     ExecuteSwapOperations {
         operations: Vec<SwapOperation>,
         minimum_receive: Option<Uint128>,
+        #[serde(default)]
+        minimum_receive_price: Option<Decimal>,
         to: Option<String>,
         deadline: Option<u64>,
+        #[serde(default)]
+        auto_unwind: bool,
+        #[serde(default)]
+        unwind_receiver: Option<String>,
     },
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema, QueryResponses)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
+    #[returns(ConfigResponse)]
     Config {},
+    #[returns(SimulateSwapOperationsResponse)]
     SimulateSwapOperations {
         offer_amount: Uint128,
         operations: Vec<SwapOperation>,
     },
+    /// SimulateRouteTax simulates the route as `SimulateSwapOperations` and returns
+    /// the burn tax it deducts by hop and denom
+    #[returns(SimulateRouteTaxResponse)]
+    SimulateRouteTax {
+        offer_amount: Uint128,
+        operations: Vec<SwapOperation>,
+    },
+    #[returns(SimulateSwapOperationsResponse)]
     ReverseSimulateSwapOperations {
         ask_amount: Uint128,
         operations: Vec<SwapOperation>,
     },
+    #[returns(WrapperResponse)]
+    Wrapper { native_denom: String },
+    #[returns(IbcDenomResponse)]
+    IbcDenom { ibc_denom: String },
+    /// PendingUnwinds returns the unwind transfers not settled yet, by id
+    #[returns(PendingUnwindsResponse)]
+    PendingUnwinds {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    /// BuildSwapMessages returns the messages `ExecuteSwapOperations` would emit
+    /// for `sender` attaching `funds`, serialized as `Vec<CosmosMsg<TerraMsg>>`
+    #[returns(Vec<CosmosMsg<TerraMsg>>)]
+    BuildSwapMessages {
+        sender: String,
+        operations: Vec<SwapOperation>,
+        minimum_receive: Option<Uint128>,
+        to: Option<String>,
+        deadline: Option<u64>,
+        offer_amount: Option<Uint128>,
+        #[serde(default)]
+        funds: Vec<Coin>,
+    },
+    /// CheckSwapViability simulates the route and checks the return against
+    /// `belief_price` and `max_spread` the same way the pair contract does
+    #[returns(CheckSwapViabilityResponse)]
+    CheckSwapViability {
+        operations: Vec<SwapOperation>,
+        offer_amount: Uint128,
+        belief_price: Decimal,
+        max_spread: Decimal,
+    },
+    /// ComputeTax returns the tax deducted from `asset` when it is sent
+    #[returns(TaxResponse)]
+    ComputeTax { asset: Asset },
+    /// ComputeReverseTax returns the tax to add on top of `asset` so that
+    /// `asset` is received after tax
+    #[returns(TaxResponse)]
+    ComputeReverseTax { asset: Asset },
+    /// EstimateRouteGas returns the gas of executing `operations` with the gas config,
+    /// to be multiplied by the wallet's adjustment
+    #[returns(EstimateRouteGasResponse)]
+    EstimateRouteGas { operations: Vec<SwapOperation> },
+    /// LastSwapReceipt returns the output of the last route `account` initiated
+    /// in the current block
+    #[returns(LastSwapReceiptResponse)]
+    LastSwapReceipt { account: String },
 }
 
 // We define a custom struct for each query response
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 pub struct ConfigResponse {
+    pub owner: String,
     pub terraswap_factory: String,
     pub loop_factory: String,
     pub astroport_factory: String,
+    pub account_limits: Option<AccountLimits>,
+    pub routing_fee: Option<RoutingFee>,
+    pub gas_config: GasConfig,
+    pub dexes: Vec<DexStatus>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct EstimateRouteGasResponse {
+    pub gas: u64,
+}
+
+/// The output of a route, received by the receiver of the route
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct SwapReceipt {
+    pub target_asset: AssetInfo,
+    pub amount: Uint128,
+    pub block_height: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct LastSwapReceiptResponse {
+    /// None when the account initiated no route in the current block
+    pub receipt: Option<SwapReceipt>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct DexStatus {
+    pub dex: String,
+    pub enabled: bool,
+    #[serde(default)]
+    pub msg_format: MsgFormat,
+}
+
+/// The shape of the swap messages and simulation queries a dex's pairs accept
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum MsgFormat {
+    /// The messages of the TerraSwap pair
+    #[default]
+    Classic,
+    /// The messages of the Astroport v2 pair, which name the ask asset
+    AstroportV2,
+}
+
+impl fmt::Display for MsgFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MsgFormat::Classic => write!(f, "classic"),
+            MsgFormat::AstroportV2 => write!(f, "astroport_v2"),
+        }
+    }
 }
 
 // We define a custom struct for each query response
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 pub struct SimulateSwapOperationsResponse {
     pub amount: Uint128,
+    /// Whether a pool of the route deviates from the reference of its price guard,
+    /// the route would be rejected
+    #[serde(default)]
+    pub price_guard_tripped: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct CheckSwapViabilityResponse {
+    pub viable: bool,
+    pub expected_return: Uint128,
+    pub required_return: Uint128,
+}
+
+/// A tax deduction of a route, `hop_index` counts from 1
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct RouteTax {
+    pub hop_index: u32,
+    pub denom: String,
+    pub tax_amount: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct SimulateRouteTaxResponse {
+    /// The return of the route, as simulated by `SimulateSwapOperations`
+    pub amount: Uint128,
+    /// Every tax deduction in the order of the route
+    pub taxes: Vec<RouteTax>,
+    /// The taxes summed by denom
+    pub total: Vec<Coin>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct TaxResponse {
+    pub tax_amount: Uint128,
+    pub pre_tax_amount: Uint128,
+    pub post_tax_amount: Uint128,
+}
+
+/// WrapperInfo is the wrapper contract of a native denom and the cw20 token it mints
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct WrapperInfo {
+    pub wrapper_contract: String,
+    pub wrapped_token: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct WrapperResponse {
+    pub native_denom: String,
+    pub wrapper_contract: String,
+    pub wrapped_token: String,
+}
+
+/// IbcOrigin is the channel an IBC denom came through and its denom on the origin chain
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct IbcOrigin {
+    pub channel_id: String,
+    pub origin_denom: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct IbcDenomResponse {
+    pub ibc_denom: String,
+    pub channel_id: String,
+    pub origin_denom: String,
+}
+
+/// An unwind transfer sent by the router, refunded to the router when it times out or fails
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct PendingUnwind {
+    pub id: u64,
+    pub initiator: String,
+    pub unwind_receiver: String,
+    pub channel_id: String,
+    pub amount: Coin,
+    /// Block time, in seconds, the transfer times out at
+    pub timeout: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct PendingUnwindsResponse {
+    pub unwinds: Vec<PendingUnwind>,
+}
+
+/// WrapperExecuteMsg is the interface of a wrapper contract
+/// which mints the cw20 representation of a native token
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum WrapperExecuteMsg {
+    /// Deposit the attached native token and mint the wrapped token to the recipient
+    Deposit { recipient: Option<String> },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum WrapperCw20HookMsg {
+    /// Burn the sent wrapped token and release the native token to the recipient
+    Withdraw { recipient: Option<String> },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum WrapperQueryMsg {
+    Fee {},
+}
+
+/// WrapperFeeResponse returns the rate the wrapper deducts on deposit and withdrawal
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct WrapperFeeResponse {
+    pub fee_rate: Decimal,
+}
+
+/// AstroportPairExecuteMsg is the swap interface of an Astroport v2 pair
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AstroportPairExecuteMsg {
+    Swap {
+        offer_asset: Asset,
+        ask_asset_info: Option<AssetInfo>,
+        belief_price: Option<Decimal>,
+        max_spread: Option<Decimal>,
+        to: Option<String>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AstroportCw20HookMsg {
+    Swap {
+        ask_asset_info: Option<AssetInfo>,
+        belief_price: Option<Decimal>,
+        max_spread: Option<Decimal>,
+        to: Option<String>,
+    },
+}
+
+/// AstroportPairQueryMsg holds the simulation queries of an Astroport v2 pair,
+/// answered with the responses of the TerraSwap pair
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AstroportPairQueryMsg {
+    Simulation {
+        offer_asset: Asset,
+        ask_asset_info: Option<AssetInfo>,
+    },
+    ReverseSimulation {
+        offer_asset_info: Option<AssetInfo>,
+        ask_asset: Asset,
+    },
 }
 
 /// We currently take no arguments for migrations
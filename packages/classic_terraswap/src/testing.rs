@@ -1,15 +1,26 @@
-use crate::asset::{Asset, AssetInfo, AssetInfoRaw, AssetRaw, PairInfo};
+use crate::asset::{
+    bech32_hrp_expand, bech32_polymod, pair_key, Asset, AssetInfo, AssetInfoRaw, AssetRaw, PairInfo,
+};
 use crate::mock_querier::mock_dependencies;
+use crate::pair::{compute_offer_amount, compute_swap, FeeConfig};
 use crate::querier::{
-    query_all_balances, query_balance, query_pair_info, query_token_balance, query_token_info,
+    compute_reverse_tax, compute_tax, query_all_balances, query_balance, query_balances,
+    query_pair_info, query_pair_info_from_pair, query_token_balance, query_token_info,
+    reverse_simulate_with_tax, simulate_with_tax,
+};
+use crate::router::{
+    assert_operations, Cw20HookMsg, ExecuteMsg as RouterExecuteMsg, MsgFormat, RouteBuilder,
+    RouteMsg, SwapOperation,
 };
 
-use cosmwasm_std::testing::MOCK_CONTRACT_ADDR;
+use classic_bindings::TerraQuery;
+use cosmwasm_std::testing::{MockApi, MOCK_CONTRACT_ADDR};
 use cosmwasm_std::{
-    coin, to_binary, Addr, Api, BankMsg, Coin, CosmosMsg, Decimal, MessageInfo, StdError, Uint128,
-    WasmMsg,
+    coin, to_binary, Addr, Api, BankMsg, Binary, Coin, CosmosMsg, Decimal, Decimal256, MessageInfo,
+    QuerierWrapper, StdError, Uint128, Uint256, Uint512, WasmMsg,
 };
 use cw20::Cw20ExecuteMsg;
+use std::str::FromStr;
 
 #[test]
 fn token_balance_querier() {
@@ -77,6 +88,77 @@ fn all_balances_querier() {
     );
 }
 
+#[test]
+fn balances_querier() {
+    let mut deps = mock_dependencies(&[coin(200, "uusd"), coin(300, "ukrw")]);
+    deps.querier.with_token_balances(&[
+        (
+            &"asset0000".to_string(),
+            &[(&MOCK_CONTRACT_ADDR.to_string(), &Uint128::from(123u128))],
+        ),
+        (&"asset0001".to_string(), &[]),
+    ]);
+
+    let assets = vec![
+        AssetInfo::Token {
+            contract_addr: "asset0000".to_string(),
+        },
+        AssetInfo::NativeToken {
+            denom: "ukrw".to_string(),
+        },
+        AssetInfo::NativeToken {
+            denom: "uluna".to_string(),
+        },
+        AssetInfo::Token {
+            contract_addr: "asset0001".to_string(),
+        },
+        AssetInfo::NativeToken {
+            denom: "uusd".to_string(),
+        },
+    ];
+
+    let balances = query_balances(
+        &deps.as_ref().querier,
+        deps.as_ref().api,
+        Addr::unchecked(MOCK_CONTRACT_ADDR),
+        &assets,
+    )
+    .unwrap();
+    assert_eq!(
+        balances
+            .iter()
+            .map(|asset| (asset.info.clone(), asset.amount.u128()))
+            .collect::<Vec<_>>(),
+        assets
+            .iter()
+            .cloned()
+            .zip([123, 300, 0, 0, 200])
+            .collect::<Vec<_>>()
+    );
+
+    // an account holding none of the assets reads as all zero
+    let balances = query_balances(
+        &deps.as_ref().querier,
+        deps.as_ref().api,
+        Addr::unchecked("addr0000"),
+        &assets,
+    )
+    .unwrap();
+    assert_eq!(balances.len(), assets.len());
+    assert!(balances.iter().all(|asset| asset.amount.is_zero()));
+
+    assert_eq!(
+        query_balances(
+            &deps.as_ref().querier,
+            deps.as_ref().api,
+            Addr::unchecked("addr0000"),
+            &[],
+        )
+        .unwrap(),
+        vec![]
+    );
+}
+
 #[test]
 fn supply_querier() {
     let mut deps = mock_dependencies(&[]);
@@ -120,6 +202,10 @@ fn test_asset_info() {
 
     assert!(native_token_info.is_native_token());
     assert!(!token_info.is_native_token());
+    assert_eq!(native_token_info.as_native_denom(), Some("uusd"));
+    assert_eq!(native_token_info.as_contract_addr(), None);
+    assert_eq!(token_info.as_native_denom(), None);
+    assert_eq!(token_info.as_contract_addr(), Some("asset0000"));
 
     let mut deps = mock_dependencies(&[Coin {
         denom: "uusd".to_string(),
@@ -157,6 +243,58 @@ fn test_asset_info() {
     );
 }
 
+#[test]
+fn test_asset_info_validate() {
+    let deps = mock_dependencies(&[]);
+    let native = |denom: &str| AssetInfo::NativeToken {
+        denom: denom.to_string(),
+    };
+    let token = |contract_addr: &str| AssetInfo::Token {
+        contract_addr: contract_addr.to_string(),
+    };
+
+    for denom in [
+        "uluna",
+        "ibc/0471F1C4E7AFD3F07702BEF6DC365268D64570F7C1FDC98EA6098DD6DE59817B",
+        "factory/terra1x46rqay4d3cssq8gxxvqz8xt6nwlz4td20k38v/ubtc",
+    ] {
+        let validated = native(denom).validate(deps.as_ref().api).unwrap();
+        assert_eq!(validated.inner(), &native(denom));
+        assert_eq!(AssetInfo::from(validated), native(denom));
+    }
+    assert_eq!(
+        token("asset0000")
+            .validate(deps.as_ref().api)
+            .unwrap()
+            .into_inner(),
+        token("asset0000")
+    );
+
+    assert_eq!(
+        native("").validate(deps.as_ref().api),
+        Err(StdError::parse_err("AssetInfo", "empty denom"))
+    );
+    for denom in [
+        "ibc/0471f1c4e7afd3f07702bef6dc365268d64570f7c1fdc98ea6098dd6de59817b",
+        "ibc/0000",
+        "ibc/",
+    ] {
+        assert_eq!(
+            native(denom).validate(deps.as_ref().api),
+            Err(StdError::parse_err(
+                "AssetInfo",
+                format!("invalid ibc denom: {}", denom)
+            ))
+        );
+    }
+    assert_eq!(
+        native("u$luna").validate(deps.as_ref().api),
+        Err(StdError::parse_err("AssetInfo", "invalid denom: u$luna"))
+    );
+    assert!(token("ASSET0000").validate(deps.as_ref().api).is_err());
+    assert!(token("").validate(deps.as_ref().api).is_err());
+}
+
 #[test]
 fn test_asset() {
     let mut deps = mock_dependencies(&[Coin {
@@ -243,6 +381,370 @@ fn test_asset() {
     );
 }
 
+#[test]
+fn test_asset_into_send_msg() {
+    let mut deps = mock_dependencies(&[]);
+    deps.querier.with_tax(
+        Decimal::percent(1),
+        &[(&"uusd".to_string(), &Uint128::from(1000000u128))],
+    );
+
+    let token_asset = Asset {
+        amount: Uint128::from(123123u128),
+        info: AssetInfo::Token {
+            contract_addr: "asset0000".to_string(),
+        },
+    };
+    let native_token_asset = Asset {
+        amount: Uint128::from(123123u128),
+        info: AssetInfo::NativeToken {
+            denom: "uusd".to_string(),
+        },
+    };
+    let hook = Some(Binary::from(b"{\"hook\":{}}".to_vec()));
+    let token_transfer = CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: "asset0000".to_string(),
+        msg: to_binary(&Cw20ExecuteMsg::Transfer {
+            recipient: "addr0000".to_string(),
+            amount: Uint128::from(123123u128),
+        })
+        .unwrap(),
+        funds: vec![],
+    });
+    let token_send = CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: "asset0000".to_string(),
+        msg: to_binary(&Cw20ExecuteMsg::Send {
+            contract: "addr0000".to_string(),
+            amount: Uint128::from(123123u128),
+            msg: hook.clone().unwrap(),
+        })
+        .unwrap(),
+        funds: vec![],
+    });
+    let bank_send = |amount: u128| {
+        CosmosMsg::Bank(BankMsg::Send {
+            to_address: "addr0000".to_string(),
+            amount: vec![coin(amount, "uusd")],
+        })
+    };
+    let execute_with_funds = |amount: u128| {
+        CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "addr0000".to_string(),
+            msg: hook.clone().unwrap(),
+            funds: vec![coin(amount, "uusd")],
+        })
+    };
+
+    // the full amount without the tax
+    assert_eq!(
+        token_asset
+            .clone()
+            .into_untaxed_send_msg(Addr::unchecked("addr0000"), None),
+        Ok(token_transfer.clone())
+    );
+    assert_eq!(
+        token_asset
+            .clone()
+            .into_untaxed_send_msg(Addr::unchecked("addr0000"), hook.clone()),
+        Ok(token_send.clone())
+    );
+    assert_eq!(
+        native_token_asset
+            .clone()
+            .into_untaxed_send_msg(Addr::unchecked("addr0000"), None),
+        Ok(bank_send(123123))
+    );
+    assert_eq!(
+        native_token_asset
+            .clone()
+            .into_untaxed_send_msg(Addr::unchecked("addr0000"), hook.clone()),
+        Ok(execute_with_funds(123123))
+    );
+
+    // the tax is only deducted from native tokens
+    let querier = deps.as_ref().querier;
+    assert_eq!(
+        token_asset
+            .clone()
+            .into_taxed_send_msg(&querier, Addr::unchecked("addr0000"), None),
+        Ok(token_transfer)
+    );
+    assert_eq!(
+        token_asset.into_taxed_send_msg(&querier, Addr::unchecked("addr0000"), hook.clone()),
+        Ok(token_send)
+    );
+    assert_eq!(
+        native_token_asset
+            .clone()
+            .into_taxed_send_msg(&querier, Addr::unchecked("addr0000"), None),
+        Ok(bank_send(121903))
+    );
+    assert_eq!(
+        native_token_asset.clone().into_taxed_send_msg(
+            &querier,
+            Addr::unchecked("addr0000"),
+            hook.clone()
+        ),
+        Ok(execute_with_funds(121903))
+    );
+
+    // into_send_msg always attaches the msg and deducts the tax
+    assert_eq!(
+        native_token_asset.into_send_msg(
+            &querier,
+            Addr::unchecked("addr0000"),
+            hook.clone().unwrap()
+        ),
+        Ok(execute_with_funds(121903))
+    );
+}
+
+#[test]
+fn test_asset_checked_arithmetic() {
+    let native = |denom: &str, amount: u128| Asset {
+        info: AssetInfo::NativeToken {
+            denom: denom.to_string(),
+        },
+        amount: Uint128::from(amount),
+    };
+    let token = |contract_addr: &str, amount: u128| Asset {
+        info: AssetInfo::Token {
+            contract_addr: contract_addr.to_string(),
+        },
+        amount: Uint128::from(amount),
+    };
+
+    assert_eq!(
+        native("uusd", 100).checked_add(&native("uusd", 23)),
+        Ok(native("uusd", 123))
+    );
+    assert_eq!(
+        native("uusd", 100).checked_sub(&native("uusd", 23)),
+        Ok(native("uusd", 77))
+    );
+    assert_eq!(
+        token("asset0000", 100).checked_add(&token("asset0000", 23)),
+        Ok(token("asset0000", 123))
+    );
+    assert_eq!(
+        token("asset0000", 100).checked_sub(&token("asset0000", 100)),
+        Ok(token("asset0000", 0))
+    );
+
+    // the amounts are checked
+    assert!(matches!(
+        native("uusd", 1).checked_sub(&native("uusd", 2)),
+        Err(StdError::Overflow { .. })
+    ));
+    assert!(matches!(
+        token("asset0000", u128::MAX).checked_add(&token("asset0000", 1)),
+        Err(StdError::Overflow { .. })
+    ));
+
+    // the infos must match
+    assert_eq!(
+        native("uusd", 1).assert_same_info(&native("uusd", 2)),
+        Ok(())
+    );
+    assert_eq!(
+        native("uusd", 1).assert_same_info(&native("uluna", 1)),
+        Err(StdError::generic_err("asset info mismatch: uusd and uluna"))
+    );
+    assert_eq!(
+        native("uusd", 1).checked_add(&token("asset0000", 1)),
+        Err(StdError::generic_err(
+            "asset info mismatch: uusd and asset0000"
+        ))
+    );
+    assert_eq!(
+        token("asset0000", 2).checked_sub(&token("asset0001", 1)),
+        Err(StdError::generic_err(
+            "asset info mismatch: asset0000 and asset0001"
+        ))
+    );
+}
+
+// a valid bech32 string of 5-bit data values
+fn bech32_encode(hrp: &str, data: &[u8]) -> String {
+    const CHARSET: &[u8] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+    let mut values = bech32_hrp_expand(hrp);
+    values.extend_from_slice(data);
+    values.extend_from_slice(&[0u8; 6]);
+    let polymod = bech32_polymod(&values) ^ 1;
+    let checksum = (0..6).map(|i| ((polymod >> (5 * (5 - i))) & 31) as u8);
+
+    let data: String = data
+        .iter()
+        .copied()
+        .chain(checksum)
+        .map(|value| CHARSET[value as usize] as char)
+        .collect();
+    format!("{}1{}", hrp, data)
+}
+
+#[test]
+fn test_asset_info_from_str() {
+    let contract_addr = bech32_encode("terra", &[7u8; 32]);
+
+    assert_eq!(
+        AssetInfo::from_str("uluna"),
+        Ok(AssetInfo::NativeToken {
+            denom: "uluna".to_string()
+        })
+    );
+    assert_eq!(
+        AssetInfo::from_str("ibc/0471F1C4E7AFD3F07702BEF6DC365268D64570F7C1FDC98EA6098DD6DE59817B"),
+        Ok(AssetInfo::NativeToken {
+            denom: "ibc/0471F1C4E7AFD3F07702BEF6DC365268D64570F7C1FDC98EA6098DD6DE59817B"
+                .to_string()
+        })
+    );
+    assert_eq!(
+        AssetInfo::from_str(&contract_addr),
+        Ok(AssetInfo::Token {
+            contract_addr: contract_addr.clone()
+        })
+    );
+    assert_eq!(
+        AssetInfo::from_str(&format!("cw20:{}", contract_addr)),
+        Ok(AssetInfo::Token {
+            contract_addr: contract_addr.clone()
+        })
+    );
+
+    // a changed character breaks the checksum
+    let mut broken_addr = contract_addr.clone();
+    let last = broken_addr.pop().unwrap();
+    broken_addr.push(if last == 'q' { 'p' } else { 'q' });
+    assert_eq!(
+        AssetInfo::from_str(&broken_addr),
+        Err(StdError::parse_err(
+            "AssetInfo",
+            format!("invalid bech32 contract address: {}", broken_addr)
+        ))
+    );
+    assert_eq!(
+        AssetInfo::from_str("cw20:asset0000"),
+        Err(StdError::parse_err(
+            "AssetInfo",
+            "invalid bech32 contract address: asset0000"
+        ))
+    );
+    assert_eq!(
+        AssetInfo::from_str(""),
+        Err(StdError::parse_err("AssetInfo", "empty denom"))
+    );
+    assert_eq!(
+        AssetInfo::from_str("ab"),
+        Err(StdError::parse_err(
+            "AssetInfo",
+            "denom must be 3 to 128 characters: ab"
+        ))
+    );
+    assert_eq!(
+        AssetInfo::from_str("9uluna"),
+        Err(StdError::parse_err("AssetInfo", "invalid denom: 9uluna"))
+    );
+    assert_eq!(
+        AssetInfo::from_str("u luna"),
+        Err(StdError::parse_err("AssetInfo", "invalid denom: u luna"))
+    );
+}
+
+#[test]
+fn test_asset_from_str() {
+    let contract_addr = bech32_encode("terra", &[1u8; 32]);
+    let token = |amount: u128| Asset {
+        info: AssetInfo::Token {
+            contract_addr: contract_addr.clone(),
+        },
+        amount: Uint128::from(amount),
+    };
+
+    assert_eq!(
+        Asset::from_str("1000000uluna"),
+        Ok(Asset {
+            info: AssetInfo::NativeToken {
+                denom: "uluna".to_string()
+            },
+            amount: Uint128::from(1000000u128),
+        })
+    );
+    assert_eq!(
+        Asset::from_str(&format!("500000:{}", contract_addr)),
+        Ok(token(500000))
+    );
+    assert_eq!(
+        Asset::from_str(&format!("500000{}", contract_addr)),
+        Ok(token(500000))
+    );
+    assert_eq!(
+        Asset::from_str(&format!("0:cw20:{}", contract_addr)),
+        Ok(token(0))
+    );
+
+    assert_eq!(
+        Asset::from_str("uluna"),
+        Err(StdError::parse_err("Asset", "missing amount in uluna"))
+    );
+    assert_eq!(
+        Asset::from_str("1000000"),
+        Err(StdError::parse_err("AssetInfo", "empty denom"))
+    );
+    assert_eq!(
+        Asset::from_str("1000000:"),
+        Err(StdError::parse_err("AssetInfo", "empty denom"))
+    );
+    assert!(Asset::from_str("340282366920938463463374607431768211456uluna").is_err());
+}
+
+#[test]
+fn test_asset_string_round_trip() {
+    // xorshift, so the random values are the same in every run
+    let mut seed = 0x2545f4914f6cdd1du64;
+    let mut next = move || {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        seed
+    };
+    let denom_chars: Vec<char> = ('a'..='z')
+        .chain('A'..='Z')
+        .chain('0'..='9')
+        .chain("/:._-".chars())
+        .collect();
+
+    for _ in 0..500 {
+        let info = if next() % 2 == 0 {
+            let len = 3 + (next() % 126) as usize;
+            let denom: String = (0..len)
+                .map(|i| {
+                    // denoms start with a letter
+                    let chars = if i == 0 { 52 } else { denom_chars.len() };
+                    denom_chars[(next() % chars as u64) as usize]
+                })
+                .collect();
+            AssetInfo::NativeToken { denom }
+        } else {
+            let hrp = ["terra", "cosmos", "osmo"][(next() % 3) as usize];
+            // 20 byte accounts and 32 byte contracts
+            let len = if next() % 2 == 0 { 32 } else { 52 };
+            let data: Vec<u8> = (0..len).map(|_| (next() % 32) as u8).collect();
+            AssetInfo::Token {
+                contract_addr: bech32_encode(hrp, &data),
+            }
+        };
+        let amount = Uint128::from(((next() as u128) << 64 | next() as u128) >> (next() % 128));
+        let asset = Asset { info, amount };
+
+        assert_eq!(
+            AssetInfo::from_str(&asset.info.to_string()),
+            Ok(asset.info.clone())
+        );
+        assert_eq!(Asset::from_str(&asset.to_string()), Ok(asset));
+    }
+}
+
 #[test]
 fn test_assert_sent_native_token_balance() {
     // zero asset
@@ -385,6 +887,70 @@ fn test_asset_info_raw_equal() {
     }));
 }
 
+#[test]
+fn test_pair_key() {
+    let deps = mock_dependencies(&[]);
+    let native_asset_info_raw = AssetInfoRaw::NativeToken {
+        denom: "uluna".to_string(),
+    };
+    let token_asset_info_raw = AssetInfoRaw::Token {
+        contract_addr: deps.api.addr_canonicalize("contract0000").unwrap(),
+    };
+
+    assert_eq!(
+        pair_key(&[native_asset_info_raw.clone(), token_asset_info_raw.clone()]),
+        pair_key(&[token_asset_info_raw, native_asset_info_raw]),
+    );
+}
+
+#[test]
+fn test_pair_key_of_asset_infos() {
+    let deps = mock_dependencies(&[]);
+    let api = deps.as_ref().api;
+    let native = |denom: &str| AssetInfo::NativeToken {
+        denom: denom.to_string(),
+    };
+    let token = |contract_addr: &str| AssetInfo::Token {
+        contract_addr: contract_addr.to_string(),
+    };
+    let key =
+        |a: &AssetInfo, b: &AssetInfo| pair_key(&[a.to_raw(api).unwrap(), b.to_raw(api).unwrap()]);
+    let ibc = "ibc/0471F1C4E7AFD3F07702BEF6DC365268D64570F7C1FDC98EA6098DD6DE59817B";
+
+    let asset_infos = [
+        native("uluna"),
+        native("uusd"),
+        native(ibc),
+        token("asset0000"),
+        token("asset0001"),
+    ];
+    let mut keys = vec![];
+    for (i, a) in asset_infos.iter().enumerate() {
+        for b in asset_infos[i + 1..].iter() {
+            // the order of the assets does not matter
+            assert_eq!(key(b, a), key(a, b));
+
+            // the canonical bytes of the assets, sorted
+            let mut raw = [a.to_raw(api).unwrap(), b.to_raw(api).unwrap()];
+            raw.sort_by(|a, b| a.as_bytes().cmp(b.as_bytes()));
+            assert_eq!(key(a, b), [raw[0].as_bytes(), raw[1].as_bytes()].concat());
+
+            keys.push(key(a, b));
+        }
+    }
+
+    // every pair has its own key
+    let count = keys.len();
+    keys.sort();
+    keys.dedup();
+    assert_eq!(keys.len(), count);
+
+    // a token keys by its canonical address
+    let key = key(&native("uusd"), &token("asset0000"));
+    let canonical = api.addr_canonicalize("asset0000").unwrap();
+    assert!(key.ends_with(canonical.as_slice()) || key.starts_with(canonical.as_slice()));
+}
+
 #[test]
 fn query_terraswap_pair_contract() {
     let mut deps = mock_dependencies(&[]);
@@ -404,6 +970,7 @@ fn query_terraswap_pair_contract() {
                 contract_addr: "pair0000".to_string(),
                 liquidity_token: "liquidity0000".to_string(),
                 asset_decimals: [6u8, 6u8],
+                created_at: None,
             },
         )],
         &[("uusd".to_string(), 6u8)],
@@ -426,3 +993,809 @@ fn query_terraswap_pair_contract() {
     assert_eq!(pair_info.contract_addr, Addr::unchecked("pair0000"),);
     assert_eq!(pair_info.liquidity_token, Addr::unchecked("liquidity0000"),);
 }
+
+#[test]
+fn query_pair_info_from_pair_contract() {
+    let mut deps = mock_dependencies(&[]);
+
+    let pair_info = PairInfo {
+        asset_infos: [
+            AssetInfo::Token {
+                contract_addr: "asset0000".to_string(),
+            },
+            AssetInfo::NativeToken {
+                denom: "uusd".to_string(),
+            },
+        ],
+        contract_addr: "pair0001".to_string(),
+        liquidity_token: "liquidity0001".to_string(),
+        asset_decimals: [8u8, 6u8],
+        created_at: None,
+    };
+    deps.querier.with_pair_info("pair0001", &pair_info);
+
+    assert_eq!(
+        query_pair_info_from_pair(&deps.as_ref().querier, Addr::unchecked("pair0001")),
+        Ok(pair_info)
+    );
+
+    // a contract which does not answer the pair query
+    deps.querier.with_unresponsive_contracts(&["token0000"]);
+    assert_eq!(
+        query_pair_info_from_pair(&deps.as_ref().querier, Addr::unchecked("token0000")),
+        Err(StdError::generic_err(
+            "token0000 is not a pair: Generic error: Querier system error: No such contract: token0000"
+        ))
+    );
+}
+
+#[test]
+fn compute_tax_with_cap() {
+    let mut deps = mock_dependencies(&[]);
+    deps.querier.with_tax(
+        Decimal::percent(1),
+        &[
+            (&"uusd".to_string(), &Uint128::from(1000000u128)),
+            (&"ukrw".to_string(), &Uint128::from(10u128)),
+        ],
+    );
+    let querier = &deps.as_ref().querier;
+
+    // 1000 - floor(1000 / 1.01)
+    assert_eq!(
+        compute_tax(querier, Uint128::from(1000u128), "uusd".to_string()).unwrap(),
+        Uint128::from(10u128)
+    );
+    assert_eq!(
+        compute_tax(querier, Uint128::from(5000u128), "uusd".to_string()).unwrap(),
+        Uint128::from(50u128)
+    );
+    assert_eq!(
+        compute_tax(querier, Uint128::from(5000u128), "ukrw".to_string()).unwrap(),
+        Uint128::from(10u128)
+    );
+
+    // 1000 * 0.01 on top
+    assert_eq!(
+        compute_reverse_tax(querier, Uint128::from(1000u128), "uusd".to_string()).unwrap(),
+        Uint128::from(10u128)
+    );
+    assert_eq!(
+        compute_reverse_tax(querier, Uint128::from(5000u128), "uusd".to_string()).unwrap(),
+        Uint128::from(50u128)
+    );
+    assert_eq!(
+        compute_reverse_tax(querier, Uint128::from(5000u128), "ukrw".to_string()).unwrap(),
+        Uint128::from(10u128)
+    );
+}
+
+#[test]
+fn compute_tax_after_tax_changes() {
+    let mut deps = mock_dependencies(&[]);
+    let tax = |querier: &QuerierWrapper<TerraQuery>| {
+        compute_tax(querier, Uint128::from(5000u128), "uusd".to_string()).unwrap()
+    };
+
+    // no rate is set, nothing is taxed
+    assert_eq!(tax(&deps.as_ref().querier), Uint128::zero());
+
+    // a denom without a cap is not taxed
+    deps.querier.with_tax_rate(Decimal::percent(1));
+    assert_eq!(tax(&deps.as_ref().querier), Uint128::zero());
+
+    // 5000 - floor(5000 / 1.01)
+    deps.querier
+        .with_tax_cap("uusd", Uint128::from(1000000u128));
+    assert_eq!(tax(&deps.as_ref().querier), Uint128::from(50u128));
+
+    // the rate changes and the cap is kept
+    deps.querier.with_tax_rate(Decimal::percent(2));
+    assert_eq!(tax(&deps.as_ref().querier), Uint128::from(99u128));
+
+    // the cap is hit
+    deps.querier.with_tax_cap("uusd", Uint128::from(20u128));
+    assert_eq!(tax(&deps.as_ref().querier), Uint128::from(20u128));
+    assert_eq!(
+        compute_reverse_tax(
+            &deps.as_ref().querier,
+            Uint128::from(5000u128),
+            "uusd".to_string()
+        )
+        .unwrap(),
+        Uint128::from(20u128)
+    );
+}
+
+#[test]
+fn simulate_with_tax_queries() {
+    let mut deps = mock_dependencies(&[]);
+    deps.querier.with_tax(
+        Decimal::percent(1),
+        &[
+            (&"uusd".to_string(), &Uint128::from(1000000u128)),
+            (&"ukrw".to_string(), &Uint128::from(10u128)),
+        ],
+    );
+    let querier = &deps.as_ref().querier;
+
+    // the mock pair returns the offer amount 1:1
+    let native_pair = PairInfo {
+        asset_infos: [
+            AssetInfo::NativeToken {
+                denom: "uusd".to_string(),
+            },
+            AssetInfo::NativeToken {
+                denom: "ukrw".to_string(),
+            },
+        ],
+        contract_addr: "pair0000".to_string(),
+        liquidity_token: "liquidity0000".to_string(),
+        asset_decimals: [6u8, 6u8],
+        created_at: None,
+    };
+    let token_pair = PairInfo {
+        asset_infos: [
+            AssetInfo::NativeToken {
+                denom: "uusd".to_string(),
+            },
+            AssetInfo::Token {
+                contract_addr: "asset0000".to_string(),
+            },
+        ],
+        contract_addr: "pair0001".to_string(),
+        liquidity_token: "liquidity0001".to_string(),
+        asset_decimals: [6u8, 6u8],
+        created_at: None,
+    };
+
+    let uusd = |amount: u128| Asset {
+        info: AssetInfo::NativeToken {
+            denom: "uusd".to_string(),
+        },
+        amount: Uint128::from(amount),
+    };
+    let ukrw = |amount: u128| Asset {
+        info: AssetInfo::NativeToken {
+            denom: "ukrw".to_string(),
+        },
+        amount: Uint128::from(amount),
+    };
+    let token = |amount: u128| Asset {
+        info: AssetInfo::Token {
+            contract_addr: "asset0000".to_string(),
+        },
+        amount: Uint128::from(amount),
+    };
+
+    let simulate = |pair: &PairInfo, offer: Asset, offer_tax: bool, ask_tax: bool| {
+        simulate_with_tax(
+            querier,
+            pair,
+            &MsgFormat::Classic,
+            &offer,
+            offer_tax,
+            ask_tax,
+        )
+        .unwrap()
+        .return_amount
+        .u128()
+    };
+    // 1000 uusd - 10 offer tax, then 990 ukrw - 10 capped ask tax
+    assert_eq!(simulate(&native_pair, uusd(1000), true, true), 980);
+    assert_eq!(simulate(&native_pair, uusd(1000), true, false), 990);
+    assert_eq!(simulate(&native_pair, uusd(1000), false, true), 990);
+    assert_eq!(simulate(&native_pair, uusd(1000), false, false), 1000);
+    // tokens are never taxed
+    assert_eq!(simulate(&token_pair, token(1000), true, true), 990);
+    assert_eq!(simulate(&token_pair, uusd(5000), true, true), 4950);
+
+    let reverse_simulate = |pair: &PairInfo, ask: Asset, offer_tax: bool, ask_tax: bool| {
+        reverse_simulate_with_tax(querier, pair, &MsgFormat::Classic, &ask, offer_tax, ask_tax)
+            .unwrap()
+            .offer_amount
+            .u128()
+    };
+    // 1000 ukrw + 10 ask tax, then 1010 uusd + 10 offer tax
+    assert_eq!(reverse_simulate(&native_pair, ukrw(1000), true, true), 1020);
+    assert_eq!(
+        reverse_simulate(&native_pair, ukrw(1000), true, false),
+        1010
+    );
+    assert_eq!(
+        reverse_simulate(&native_pair, ukrw(1000), false, true),
+        1010
+    );
+    assert_eq!(
+        reverse_simulate(&native_pair, ukrw(1000), false, false),
+        1000
+    );
+    assert_eq!(reverse_simulate(&token_pair, token(1000), true, true), 1010);
+    assert_eq!(reverse_simulate(&token_pair, uusd(5000), true, true), 5050);
+
+    assert_eq!(
+        simulate_with_tax(
+            querier,
+            &token_pair,
+            &MsgFormat::Classic,
+            &ukrw(1000),
+            true,
+            true
+        ),
+        Err(StdError::generic_err(
+            "ukrw is not an asset of the pair pair0001"
+        ))
+    );
+    assert_eq!(
+        reverse_simulate_with_tax(
+            querier,
+            &native_pair,
+            &MsgFormat::Classic,
+            &token(1000),
+            true,
+            true
+        ),
+        Err(StdError::generic_err(
+            "asset0000 is not an asset of the pair pair0000"
+        ))
+    );
+}
+
+#[test]
+fn test_swap_operation() {
+    let uusd = AssetInfo::NativeToken {
+        denom: "uusd".to_string(),
+    };
+    let token = AssetInfo::Token {
+        contract_addr: "asset0000".to_string(),
+    };
+
+    let operations = [
+        (
+            SwapOperation::native_swap("uluna", "uusd"),
+            SwapOperation::NativeSwap {
+                offer_denom: "uluna".to_string(),
+                ask_denom: "uusd".to_string(),
+            },
+            "native",
+        ),
+        (
+            SwapOperation::terraswap(uusd.clone(), token.clone()),
+            SwapOperation::TerraSwap {
+                offer_asset_info: uusd.clone(),
+                ask_asset_info: token.clone(),
+            },
+            "terraswap",
+        ),
+        (
+            SwapOperation::loop_swap(uusd.clone(), token.clone()),
+            SwapOperation::Loop {
+                offer_asset_info: uusd.clone(),
+                ask_asset_info: token.clone(),
+            },
+            "loop",
+        ),
+        (
+            SwapOperation::astroport(uusd.clone(), token.clone()),
+            SwapOperation::Astroport {
+                offer_asset_info: uusd.clone(),
+                ask_asset_info: token.clone(),
+            },
+            "astroport",
+        ),
+    ];
+
+    for (operation, expected, dex_name) in operations.iter() {
+        assert_eq!(operation, expected);
+        assert_eq!(operation.dex_name(), *dex_name);
+    }
+
+    assert_eq!(operations[0].0.get_offer_asset_info().to_string(), "uluna");
+    assert_eq!(operations[0].0.get_target_asset_info(), uusd);
+    assert_eq!(operations[1].0.get_offer_asset_info(), uusd);
+    assert_eq!(operations[1].0.get_target_asset_info(), token);
+
+    let wrap = SwapOperation::wrap("uusd", "asset0000");
+    assert_eq!(wrap.dex_name(), "wrapper");
+    assert_eq!(wrap.get_offer_asset_info(), uusd);
+    assert_eq!(wrap.get_target_asset_info(), token);
+
+    let unwrap = SwapOperation::unwrap("asset0000", "uusd");
+    assert_eq!(unwrap.dex_name(), "wrapper");
+    assert_eq!(unwrap.get_offer_asset_info(), token);
+    assert_eq!(unwrap.get_target_asset_info(), uusd);
+}
+
+#[test]
+#[allow(clippy::useless_vec)]
+fn test_assert_operations() {
+    let api = MockApi::default();
+
+    // empty error
+    assert!(assert_operations(&api, &[]).is_err());
+
+    // uluna output
+    assert_eq!(
+        assert_operations(
+            &api,
+            &vec![
+                SwapOperation::NativeSwap {
+                    offer_denom: "uusd".to_string(),
+                    ask_denom: "ukrw".to_string(),
+                },
+                SwapOperation::TerraSwap {
+                    offer_asset_info: AssetInfo::NativeToken {
+                        denom: "ukrw".to_string(),
+                    },
+                    ask_asset_info: AssetInfo::Token {
+                        contract_addr: "asset0001".to_string(),
+                    },
+                },
+                SwapOperation::TerraSwap {
+                    offer_asset_info: AssetInfo::Token {
+                        contract_addr: "asset0001".to_string(),
+                    },
+                    ask_asset_info: AssetInfo::NativeToken {
+                        denom: "uluna".to_string(),
+                    },
+                }
+            ]
+        ),
+        Ok(AssetInfo::NativeToken {
+            denom: "uluna".to_string(),
+        }
+        .validate(&api)
+        .unwrap())
+    );
+
+    // asset0002 output
+    assert!(assert_operations(
+        &api,
+        &vec![
+            SwapOperation::NativeSwap {
+                offer_denom: "uusd".to_string(),
+                ask_denom: "ukrw".to_string(),
+            },
+            SwapOperation::TerraSwap {
+                offer_asset_info: AssetInfo::NativeToken {
+                    denom: "ukrw".to_string(),
+                },
+                ask_asset_info: AssetInfo::Token {
+                    contract_addr: "asset0001".to_string(),
+                },
+            },
+            SwapOperation::TerraSwap {
+                offer_asset_info: AssetInfo::Token {
+                    contract_addr: "asset0001".to_string(),
+                },
+                ask_asset_info: AssetInfo::NativeToken {
+                    denom: "uluna".to_string(),
+                },
+            },
+            SwapOperation::TerraSwap {
+                offer_asset_info: AssetInfo::NativeToken {
+                    denom: "uluna".to_string(),
+                },
+                ask_asset_info: AssetInfo::Token {
+                    contract_addr: "asset0002".to_string(),
+                },
+            },
+        ]
+    )
+    .is_ok());
+
+    // wrap and unwrap output
+    assert!(assert_operations(
+        &api,
+        &vec![
+            SwapOperation::Wrap {
+                native_denom: "uluna".to_string(),
+                wrapped_token: "wluna".to_string(),
+            },
+            SwapOperation::TerraSwap {
+                offer_asset_info: AssetInfo::Token {
+                    contract_addr: "wluna".to_string(),
+                },
+                ask_asset_info: AssetInfo::Token {
+                    contract_addr: "asset0001".to_string(),
+                },
+            },
+            SwapOperation::TerraSwap {
+                offer_asset_info: AssetInfo::Token {
+                    contract_addr: "asset0001".to_string(),
+                },
+                ask_asset_info: AssetInfo::Token {
+                    contract_addr: "wluna".to_string(),
+                },
+            },
+            SwapOperation::Unwrap {
+                wrapped_token: "wluna".to_string(),
+                native_denom: "uluna".to_string(),
+            },
+        ]
+    )
+    .is_ok());
+
+    // discontinuous route error
+    assert_eq!(
+        assert_operations(
+            &api,
+            &vec![
+                SwapOperation::NativeSwap {
+                    offer_denom: "uusd".to_string(),
+                    ask_denom: "ukrw".to_string(),
+                },
+                SwapOperation::TerraSwap {
+                    offer_asset_info: AssetInfo::NativeToken {
+                        denom: "ukrw".to_string(),
+                    },
+                    ask_asset_info: AssetInfo::Token {
+                        contract_addr: "asset0001".to_string(),
+                    },
+                },
+                SwapOperation::TerraSwap {
+                    offer_asset_info: AssetInfo::Token {
+                        contract_addr: "asset0001".to_string(),
+                    },
+                    ask_asset_info: AssetInfo::NativeToken {
+                        denom: "uaud".to_string(),
+                    },
+                },
+                SwapOperation::TerraSwap {
+                    offer_asset_info: AssetInfo::NativeToken {
+                        denom: "uluna".to_string(),
+                    },
+                    ask_asset_info: AssetInfo::Token {
+                        contract_addr: "asset0002".to_string(),
+                    },
+                },
+            ]
+        ),
+        Err(StdError::generic_err(
+            "invalid operations; hop 3 offers uluna but the previous hop returns uaud"
+        ))
+    );
+
+    // the assets are validated
+    assert_eq!(
+        assert_operations(
+            &api,
+            &[SwapOperation::TerraSwap {
+                offer_asset_info: AssetInfo::NativeToken {
+                    denom: "".to_string(),
+                },
+                ask_asset_info: AssetInfo::Token {
+                    contract_addr: "asset0001".to_string(),
+                },
+            }]
+        ),
+        Err(StdError::parse_err("AssetInfo", "empty denom"))
+    );
+    assert!(assert_operations(
+        &api,
+        &[SwapOperation::TerraSwap {
+            offer_asset_info: AssetInfo::NativeToken {
+                denom: "uluna".to_string(),
+            },
+            ask_asset_info: AssetInfo::Token {
+                contract_addr: "ASSET0001".to_string(),
+            },
+        }]
+    )
+    .is_err());
+}
+
+#[test]
+fn test_route_builder() {
+    let api = MockApi::default();
+    let uusd = AssetInfo::NativeToken {
+        denom: "uusd".to_string(),
+    };
+    let token = AssetInfo::Token {
+        contract_addr: "asset0000".to_string(),
+    };
+    let ukrw = AssetInfo::NativeToken {
+        denom: "ukrw".to_string(),
+    };
+
+    // a native offer is attached to the router message
+    let route = RouteBuilder::new()
+        .native_hop("uluna", "uusd")
+        .terraswap_hop(uusd.clone(), token.clone())
+        .minimum_receive(Uint128::from(100u128))
+        .to("addr0001")
+        .deadline(1000)
+        .build(&api, "router0000", Uint128::from(1000u128))
+        .unwrap();
+    let msg = RouterExecuteMsg::ExecuteSwapOperations {
+        operations: vec![
+            SwapOperation::native_swap("uluna", "uusd"),
+            SwapOperation::terraswap(uusd.clone(), token.clone()),
+        ],
+        minimum_receive: Some(Uint128::from(100u128)),
+        minimum_receive_price: None,
+        to: Some("addr0001".to_string()),
+        deadline: Some(1000),
+        offer_amount: Some(Uint128::from(1000u128)),
+        auto_unwind: false,
+        unwind_receiver: None,
+    };
+    assert_eq!(
+        route,
+        RouteMsg::Execute {
+            router: "router0000".to_string(),
+            msg: msg.clone(),
+            funds: vec![coin(1000, "uluna")],
+        }
+    );
+    assert_eq!(
+        route.into_cosmos_msg::<cosmwasm_std::Empty>().unwrap(),
+        CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "router0000".to_string(),
+            msg: to_binary(&msg).unwrap(),
+            funds: vec![coin(1000, "uluna")],
+        })
+    );
+
+    // a cw20 offer is sent to the router with the hook
+    let route = RouteBuilder::new()
+        .astroport_hop(token.clone(), uusd.clone())
+        .loop_hop(uusd.clone(), ukrw.clone())
+        .build(&api, "router0000", Uint128::from(1000u128))
+        .unwrap();
+    let hook = Cw20HookMsg::ExecuteSwapOperations {
+        operations: vec![
+            SwapOperation::astroport(token.clone(), uusd.clone()),
+            SwapOperation::loop_swap(uusd.clone(), ukrw.clone()),
+        ],
+        minimum_receive: None,
+        minimum_receive_price: None,
+        to: None,
+        deadline: None,
+        auto_unwind: false,
+        unwind_receiver: None,
+    };
+    let send = Cw20ExecuteMsg::Send {
+        contract: "router0000".to_string(),
+        amount: Uint128::from(1000u128),
+        msg: to_binary(&hook).unwrap(),
+    };
+    assert_eq!(
+        route,
+        RouteMsg::Cw20Send {
+            token: "asset0000".to_string(),
+            hook,
+            send: send.clone(),
+        }
+    );
+    assert_eq!(
+        route.into_cosmos_msg::<cosmwasm_std::Empty>().unwrap(),
+        CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "asset0000".to_string(),
+            msg: to_binary(&send).unwrap(),
+            funds: vec![],
+        })
+    );
+
+    // broken routes are rejected when built
+    assert_eq!(
+        RouteBuilder::new().build(&api, "router0000", Uint128::from(1000u128)),
+        Err(StdError::generic_err("must provide operations"))
+    );
+    assert_eq!(
+        RouteBuilder::new()
+            .native_hop("uluna", "uusd")
+            .terraswap_hop(ukrw, token.clone())
+            .build(&api, "router0000", Uint128::from(1000u128)),
+        Err(StdError::generic_err(
+            "invalid operations; hop 1 offers ukrw but the previous hop returns uusd"
+        ))
+    );
+    assert_eq!(
+        RouteBuilder::new()
+            .wrap_hop("uluna", "wluna")
+            .unwrap_hop("wluna", "")
+            .build(&api, "router0000", Uint128::from(1000u128)),
+        Err(StdError::parse_err("AssetInfo", "empty denom"))
+    );
+    assert_eq!(
+        RouteBuilder::new()
+            .native_hop("uluna", "uusd")
+            .minimum_receive(Uint128::from(100u128))
+            .minimum_receive_price(Decimal::percent(10))
+            .build(&api, "router0000", Uint128::from(1000u128)),
+        Err(StdError::generic_err(
+            "minimum_receive and minimum_receive_price can't be both given"
+        ))
+    );
+}
+
+#[test]
+fn test_compute_swap_with_huge_pool_variance() {
+    let offer_pool = Uint128::from(395451850234u128);
+    let ask_pool = Uint128::from(317u128);
+
+    assert_eq!(
+        compute_swap(
+            offer_pool,
+            ask_pool,
+            Uint128::from(1u128),
+            FeeConfig::default().total_fee_rate()
+        )
+        .unwrap()
+        .return_amount,
+        Uint128::zero()
+    );
+}
+
+#[test]
+fn test_compute_offer_amount_inverts_compute_swap() {
+    let commission_rates = [
+        Decimal::zero(),
+        FeeConfig::default().total_fee_rate(),
+        Decimal::percent(1),
+    ];
+    let pools = [
+        (1_000u128, 1_000u128),
+        (1_000_000u128, 3_000_000u128),
+        (3_000_000u128, 1_000_000u128),
+        (123_456_789_000u128, 987_654_321u128),
+        (10_000_000_000_000u128, 10_000_000_000_000u128),
+        (395451850234u128, 317u128),
+    ];
+
+    for commission_rate in commission_rates {
+        for (offer_pool, ask_pool) in pools {
+            let mut ask_amount = 1u128;
+            while ask_amount < ask_pool / 2 {
+                let offer_amount = compute_offer_amount(
+                    offer_pool.into(),
+                    ask_pool.into(),
+                    ask_amount.into(),
+                    commission_rate,
+                )
+                .unwrap()
+                .offer_amount;
+
+                // forward(reverse(x)) >= x and the offer is the least one
+                let return_amount = compute_swap(
+                    offer_pool.into(),
+                    ask_pool.into(),
+                    offer_amount,
+                    commission_rate,
+                )
+                .unwrap()
+                .return_amount;
+                assert!(return_amount.u128() >= ask_amount);
+                let less_return_amount = compute_swap(
+                    offer_pool.into(),
+                    ask_pool.into(),
+                    offer_amount - Uint128::from(1u8),
+                    commission_rate,
+                )
+                .unwrap()
+                .return_amount;
+                assert!(less_return_amount.u128() < ask_amount);
+
+                // within 1 unit unless an offer unit buys more than one ask unit
+                if offer_pool >= ask_pool {
+                    assert!(return_amount.u128() - ask_amount <= 1);
+                }
+
+                ask_amount = ask_amount * 3 + 1;
+            }
+        }
+    }
+}
+
+#[test]
+fn test_compute_swap_properties() {
+    // xorshift, so the random values are the same in every run
+    let mut seed = 0x9e3779b97f4a7c15u64;
+    let mut next = move || {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        seed
+    };
+    // amounts of every magnitude up to u128::MAX
+    let amount = |next: &mut dyn FnMut() -> u64| match next() % 8 {
+        0 => 0u128,
+        1 => u128::MAX,
+        _ => (((next() as u128) << 64) | next() as u128) >> (next() % 128),
+    };
+
+    let mut swaps = 0;
+    for i in 0..3000 {
+        let offer_pool = Uint128::from(amount(&mut next));
+        let ask_pool = Uint128::from(amount(&mut next));
+        let offer_amount = Uint128::from(amount(&mut next));
+        let ask_amount = Uint128::from(amount(&mut next) >> (next() % 128));
+        // up to 1.2, past the valid range
+        let commission_rate = Decimal::from_atomics((next() % 1_200) as u128, 3).unwrap();
+        let commission_rate = if i % 10 == 0 {
+            FeeConfig::default().total_fee_rate()
+        } else {
+            commission_rate
+        };
+
+        // never panics, invalid input is an error
+        let swap = compute_swap(offer_pool, ask_pool, offer_amount, commission_rate);
+        if offer_pool.is_zero() || commission_rate > Decimal::one() {
+            assert!(swap.is_err());
+        }
+        if let Ok(swap) = swap {
+            swaps += 1;
+
+            // return + spread + commission is the offer at the pool price
+            let price = Decimal256::from_ratio(ask_pool, offer_pool);
+            let value = Uint512::from(Uint256::from(offer_amount)) * Uint512::from(price.atomics())
+                / Uint512::from(Decimal256::one().atomics());
+            assert_eq!(
+                Uint512::from(swap.return_amount.u128())
+                    + Uint512::from(swap.spread_amount.u128())
+                    + Uint512::from(swap.commission_amount.u128()),
+                value
+            );
+            assert!(swap.return_amount + swap.commission_amount <= ask_pool);
+        }
+
+        let offer = compute_offer_amount(offer_pool, ask_pool, ask_amount, commission_rate);
+        if let Ok(offer) = offer {
+            let swap =
+                compute_swap(offer_pool, ask_pool, offer.offer_amount, commission_rate).unwrap();
+            assert!(swap.return_amount >= ask_amount);
+            assert_eq!(swap.spread_amount, offer.spread_amount);
+            assert_eq!(swap.commission_amount, offer.commission_amount);
+        }
+    }
+    assert!(swaps > 1000);
+
+    assert_eq!(
+        compute_swap(
+            Uint128::zero(),
+            Uint128::from(100u128),
+            Uint128::from(100u128),
+            Decimal::zero()
+        ),
+        Err(StdError::generic_err("offer pool is empty"))
+    );
+    assert_eq!(
+        compute_offer_amount(
+            Uint128::from(100u128),
+            Uint128::from(100u128),
+            Uint128::from(10u128),
+            Decimal::one()
+        ),
+        Err(StdError::generic_err(
+            "commission rate must be less than one"
+        ))
+    );
+    assert_eq!(
+        compute_offer_amount(
+            Uint128::from(100u128),
+            Uint128::from(100u128),
+            Uint128::from(100u128),
+            Decimal::zero()
+        ),
+        Err(StdError::generic_err("Ask amount exceeds the pool"))
+    );
+}
+
+#[test]
+fn test_query_responses() {
+    use cosmwasm_schema::QueryResponses;
+
+    // every query has a response type, named after its variant
+    let factory = crate::factory::QueryMsg::response_schemas().unwrap();
+    assert_eq!(factory.len(), 12);
+    assert!(factory.contains_key("validate_pair"));
+
+    let pair = crate::pair::QueryMsg::response_schemas().unwrap();
+    assert_eq!(pair.len(), 18);
+    assert!(pair.contains_key("simulate_provide_liquidity"));
+
+    let router = crate::router::QueryMsg::response_schemas().unwrap();
+    assert_eq!(router.len(), 13);
+    assert!(router.contains_key("build_swap_messages"));
+}
@@ -2,18 +2,36 @@ use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use cosmwasm_std::{StdError, StdResult, Uint128};
-use cw20::{Cw20Coin, MinterResponse};
+use cw20::{Cw20Coin, Logo, MinterResponse};
+
+/// The most characters of a cw20 symbol
+const MAX_SYMBOL_LENGTH: usize = 12;
 
 /// TokenContract InstantiateMsg
-#[derive(Serialize, Deserialize, JsonSchema)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct InstantiateMsg {
     pub name: String,
     pub symbol: String,
     pub decimals: u8,
     pub initial_balances: Vec<Cw20Coin>,
     pub mint: Option<MinterResponse>,
+    #[serde(default)]
+    pub marketing: Option<InstantiateMarketingInfo>,
+}
+
+/// The marketing info of the token, `marketing` is the address which can update it
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct InstantiateMarketingInfo {
+    pub project: Option<String>,
+    pub description: Option<String>,
+    pub marketing: Option<String>,
+    pub logo: Option<Logo>,
 }
 
+/// TokenContract MigrateMsg
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MigrateMsg {}
+
 impl InstantiateMsg {
     pub fn get_cap(&self) -> Option<Uint128> {
         self.mint.as_ref().and_then(|v| v.cap)
@@ -38,6 +56,16 @@ impl InstantiateMsg {
     }
 }
 
+/// The liquidity token symbol `uLP-<asset0>-<asset1>` of the asset labels, the native
+/// denoms or token symbols, cut to the characters and length a cw20 symbol allows
+pub fn liquidity_token_symbol(asset_labels: [&str; 2]) -> String {
+    format!("uLP-{}-{}", asset_labels[0], asset_labels[1])
+        .chars()
+        .filter(|c| c.is_ascii_alphabetic() || *c == '-')
+        .take(MAX_SYMBOL_LENGTH)
+        .collect()
+}
+
 fn is_valid_name(name: &str) -> bool {
     let bytes = name.as_bytes();
     if bytes.len() < 3 || bytes.len() > 50 {
@@ -48,7 +76,7 @@ fn is_valid_name(name: &str) -> bool {
 
 fn is_valid_symbol(symbol: &str) -> bool {
     let bytes = symbol.as_bytes();
-    if bytes.len() < 3 || bytes.len() > 12 {
+    if bytes.len() < 3 || bytes.len() > MAX_SYMBOL_LENGTH {
         return false;
     }
     for byte in bytes.iter() {
@@ -74,6 +102,7 @@ mod test {
             }),
             name: "test_token".to_string(),
             symbol: "TNT".to_string(),
+            marketing: None,
         };
 
         assert_eq!(msg.get_cap(), Some(Uint128::from(1u128)))
@@ -90,6 +119,7 @@ mod test {
             }),
             name: "test_token".to_string(),
             symbol: "TNT".to_string(),
+            marketing: None,
         };
 
         assert_eq!(valid_msg.validate(), Ok(()));
@@ -103,6 +133,7 @@ mod test {
             }),
             name: "a".to_string(),
             symbol: "TNT".to_string(),
+            marketing: None,
         };
 
         assert_eq!(
@@ -121,6 +152,7 @@ mod test {
             }),
             name: "test_token".to_string(),
             symbol: "TN".to_string(),
+            marketing: None,
         };
 
         assert_eq!(
@@ -139,6 +171,7 @@ mod test {
             }),
             name: "test_token".to_string(),
             symbol: "TNT".to_string(),
+            marketing: None,
         };
 
         assert_eq!(
@@ -146,4 +179,22 @@ mod test {
             Err(StdError::generic_err("Decimals must not exceed 18"))
         );
     }
+
+    #[test]
+    fn liquidity_token_symbol_of_assets() {
+        assert_eq!(liquidity_token_symbol(["uluna", "uusd"]), "uLP-uluna-uu");
+        assert_eq!(liquidity_token_symbol(["uusd", "ANC"]), "uLP-uusd-ANC");
+        assert_eq!(
+            liquidity_token_symbol(["ibc/27394FB092D2ECCD", "ukrw"]),
+            "uLP-ibcFBDEC"
+        );
+
+        for symbol in [
+            liquidity_token_symbol(["uluna", "uusd"]),
+            liquidity_token_symbol(["a", "b"]),
+            liquidity_token_symbol(["ibc/27394FB092D2ECCD", "ukrw"]),
+        ] {
+            assert!(is_valid_symbol(&symbol), "{}", symbol);
+        }
+    }
 }
@@ -1,11 +1,50 @@
 use classic_bindings::TerraQuery;
 use cosmwasm_std::{DepsMut, StdError, StdResult};
 use cw2::{get_contract_version, set_contract_version};
+use std::fmt;
 
-pub fn assert_deadline(blocktime: u64, deadline: Option<u64>) -> StdResult<()> {
+/// Returned when a message is executed after its deadline
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeadlineError {
+    pub deadline: u64,
+    pub block_time: u64,
+}
+
+impl fmt::Display for DeadlineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Expired deadline: deadline {}, block time {}",
+            self.deadline, self.block_time
+        )
+    }
+}
+
+impl std::error::Error for DeadlineError {}
+
+impl From<DeadlineError> for StdError {
+    fn from(err: DeadlineError) -> Self {
+        StdError::generic_err(err.to_string())
+    }
+}
+
+/// A deadline is met up to and including its own second
+pub fn assert_deadline(blocktime: u64, deadline: Option<u64>) -> Result<(), DeadlineError> {
+    assert_deadline_with_grace(blocktime, deadline, 0)
+}
+
+/// Tolerates `grace_seconds` past the deadline for clock skew between the signer and the chain
+pub fn assert_deadline_with_grace(
+    blocktime: u64,
+    deadline: Option<u64>,
+    grace_seconds: u64,
+) -> Result<(), DeadlineError> {
     if let Some(deadline) = deadline {
-        if blocktime >= deadline {
-            return Err(StdError::generic_err("Expired deadline"));
+        if blocktime > deadline.saturating_add(grace_seconds) {
+            return Err(DeadlineError {
+                deadline,
+                block_time: blocktime,
+            });
         }
     }
 
@@ -43,13 +82,38 @@ fn test_assert_deadline_with_normal() {
 #[test]
 fn test_assert_deadline_with_expired() {
     let err = assert_deadline(10u64, Some(5u64)).unwrap_err();
-    assert_eq!(err, StdError::generic_err("Expired deadline"))
+    assert_eq!(
+        err,
+        DeadlineError {
+            deadline: 5,
+            block_time: 10
+        }
+    );
+    assert_eq!(
+        StdError::from(err),
+        StdError::generic_err("Expired deadline: deadline 5, block time 10")
+    );
 }
 
 #[test]
 fn test_assert_deadline_with_same() {
-    let err = assert_deadline(10u64, Some(10u64)).unwrap_err();
-    assert_eq!(err, StdError::generic_err("Expired deadline"))
+    assert_deadline(10u64, Some(10u64)).unwrap();
+    assert_deadline(11u64, Some(10u64)).unwrap_err();
+}
+
+#[test]
+fn test_assert_deadline_with_grace() {
+    assert_deadline_with_grace(10u64, Some(10u64), 0).unwrap();
+    assert_deadline_with_grace(13u64, Some(10u64), 3).unwrap();
+    assert_eq!(
+        assert_deadline_with_grace(14u64, Some(10u64), 3),
+        Err(DeadlineError {
+            deadline: 10,
+            block_time: 14
+        })
+    );
+    assert_deadline_with_grace(u64::MAX, Some(10u64), u64::MAX).unwrap();
+    assert_deadline_with_grace(100u64, None, 0).unwrap();
 }
 
 #[test]
@@ -0,0 +1,8 @@
+pub mod suite;
+pub mod terra;
+
+pub use suite::Suite;
+pub use terra::TerraModule;
+
+#[cfg(test)]
+mod testing;
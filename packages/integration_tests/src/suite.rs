@@ -0,0 +1,404 @@
+use anyhow::Result as AnyResult;
+use classic_bindings::{TerraMsg, TerraQuery};
+use classic_terraswap::asset::{Asset, AssetInfo, PairInfo};
+use classic_terraswap::factory::{
+    ExecuteMsg as FactoryExecuteMsg, InstantiateMsg as FactoryInstantiateMsg,
+    QueryMsg as FactoryQueryMsg,
+};
+use classic_terraswap::router::{
+    InstantiateMsg as RouterInstantiateMsg, LastSwapReceiptResponse, QueryMsg as RouterQueryMsg,
+    RouteBuilder,
+};
+use classic_terraswap::token::InstantiateMsg as TokenInstantiateMsg;
+use cosmwasm_std::testing::{MockApi, MockStorage};
+use cosmwasm_std::{
+    coin, to_binary, Addr, Binary, Coin, CosmosMsg, Decimal, Deps, DepsMut, Empty, Env,
+    MessageInfo, Response, StdError, StdResult, Uint128, WasmMsg,
+};
+use cw20::{BalanceResponse, Cw20Coin, Cw20ExecuteMsg, Cw20QueryMsg};
+use cw_multi_test::{
+    App, AppBuilder, AppResponse, BankKeeper, BankSudo, Contract, ContractWrapper, Executor,
+    SudoMsg, WasmKeeper,
+};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::terra::TerraModule;
+
+pub type TerraApp =
+    App<BankKeeper, MockApi, MockStorage, TerraModule, WasmKeeper<TerraMsg, TerraQuery>>;
+
+pub fn factory_contract() -> Box<dyn Contract<TerraMsg, TerraQuery>> {
+    Box::new(
+        ContractWrapper::new(
+            terraswap_factory::contract::execute,
+            terraswap_factory::contract::instantiate,
+            terraswap_factory::contract::query,
+        )
+        .with_reply(terraswap_factory::contract::reply),
+    )
+}
+
+pub fn pair_contract() -> Box<dyn Contract<TerraMsg, TerraQuery>> {
+    Box::new(
+        ContractWrapper::new(
+            terraswap_pair::contract::execute,
+            terraswap_pair::contract::instantiate,
+            terraswap_pair::contract::query,
+        )
+        .with_reply(terraswap_pair::contract::reply),
+    )
+}
+
+pub fn router_contract() -> Box<dyn Contract<TerraMsg, TerraQuery>> {
+    Box::new(
+        ContractWrapper::new(
+            terraswap_router::contract::execute,
+            terraswap_router::contract::instantiate,
+            terraswap_router::contract::query,
+        )
+        .with_reply(terraswap_router::contract::reply),
+    )
+}
+
+pub fn token_contract() -> Box<dyn Contract<TerraMsg, TerraQuery>> {
+    Box::new(ContractWrapper::new_with_empty(
+        terraswap_token::contract::execute,
+        terraswap_token::contract::instantiate,
+        terraswap_token::contract::query,
+    ))
+}
+
+/// A token burning 1% of every `transfer`, as the fee-on-transfer cw20s of Classic
+pub fn fee_token_contract() -> Box<dyn Contract<TerraMsg, TerraQuery>> {
+    Box::new(ContractWrapper::new_with_empty(
+        fee_token_execute,
+        terraswap_token::contract::instantiate,
+        terraswap_token::contract::query,
+    ))
+}
+
+fn fee_token_execute(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: Cw20ExecuteMsg,
+) -> Result<Response, cw20_base::ContractError> {
+    match msg {
+        Cw20ExecuteMsg::Transfer { recipient, amount } => {
+            let fee = amount.multiply_ratio(1u128, 100u128);
+            let res = cw20_base::contract::execute_transfer(
+                deps.branch(),
+                env.clone(),
+                info.clone(),
+                recipient,
+                amount - fee,
+            )?;
+            if fee.is_zero() {
+                return Ok(res);
+            }
+
+            let burn = cw20_base::contract::execute_burn(deps, env, info, fee)?;
+            Ok(res.add_attributes(burn.attributes))
+        }
+        msg => terraswap_token::contract::execute(deps, env, info, msg),
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReceiptReaderMsg {
+    /// Executes `msg` on `router` with the sent funds, then reads the receipt of the route
+    Route { router: String, msg: Binary },
+    /// Reads the receipt of the last route of the contract into the `receipt_amount` attribute
+    ReadReceipt { router: String },
+}
+
+/// A contract continuing after a route in the same transaction, as a liquidation bot
+pub fn receipt_reader_contract() -> Box<dyn Contract<TerraMsg, TerraQuery>> {
+    Box::new(ContractWrapper::new_with_empty(
+        receipt_reader_execute,
+        |_: DepsMut, _: Env, _: MessageInfo, _: Empty| -> StdResult<Response> {
+            Ok(Response::new())
+        },
+        |_: Deps, _: Env, _: Empty| -> StdResult<Binary> {
+            Err(StdError::generic_err("no queries"))
+        },
+    ))
+}
+
+fn receipt_reader_execute(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: ReceiptReaderMsg,
+) -> StdResult<Response> {
+    match msg {
+        ReceiptReaderMsg::Route { router, msg } => Ok(Response::new()
+            .add_message(WasmMsg::Execute {
+                contract_addr: router.clone(),
+                msg,
+                funds: info.funds,
+            })
+            .add_message(WasmMsg::Execute {
+                contract_addr: env.contract.address.to_string(),
+                msg: to_binary(&ReceiptReaderMsg::ReadReceipt { router })?,
+                funds: vec![],
+            })),
+        ReceiptReaderMsg::ReadReceipt { router } => {
+            let res: LastSwapReceiptResponse = deps.querier.query_wasm_smart(
+                router,
+                &RouterQueryMsg::LastSwapReceipt {
+                    account: env.contract.address.to_string(),
+                },
+            )?;
+            let receipt = res
+                .receipt
+                .ok_or_else(|| StdError::generic_err("no swap receipt"))?;
+
+            Ok(Response::new().add_attribute("receipt_amount", receipt.amount))
+        }
+    }
+}
+
+/// Suite deploys the factory and the router in an app with the terra module,
+/// to run routes end to end without a chain
+pub struct Suite {
+    pub app: TerraApp,
+    pub owner: Addr,
+    pub factory: Addr,
+    pub router: Addr,
+    token_code_id: u64,
+}
+
+impl Default for Suite {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Suite {
+    pub fn new() -> Self {
+        let mut app = AppBuilder::new_custom()
+            .with_custom(TerraModule::default())
+            .build(|_, _, _| {});
+        let owner = Addr::unchecked("owner");
+
+        let token_code_id = app.store_code(token_contract());
+        let pair_code_id = app.store_code(pair_contract());
+        let factory_code_id = app.store_code(factory_contract());
+        let router_code_id = app.store_code(router_contract());
+
+        let factory = app
+            .instantiate_contract(
+                factory_code_id,
+                owner.clone(),
+                &FactoryInstantiateMsg {
+                    pair_code_id,
+                    token_code_id,
+                },
+                &[],
+                "factory",
+                Some(owner.to_string()),
+            )
+            .unwrap();
+
+        let router = app
+            .instantiate_contract(
+                router_code_id,
+                owner.clone(),
+                &RouterInstantiateMsg {
+                    terraswap_factory: factory.to_string(),
+                    loop_factory: factory.to_string(),
+                    astroport_factory: factory.to_string(),
+                    unchecked: false,
+                    validate_factories: false,
+                },
+                &[],
+                "router",
+                Some(owner.to_string()),
+            )
+            .unwrap();
+
+        Suite {
+            app,
+            owner,
+            factory,
+            router,
+            token_code_id,
+        }
+    }
+
+    /// Sets the tax rate and caps of the terra module
+    pub fn with_tax(mut self, rate: Decimal, caps: &[(&str, u128)]) -> Self {
+        self.app
+            .init_modules(|router, _, _| router.custom.set_tax(rate, caps));
+        self
+    }
+
+    /// Sets the market swap rate of the terra module
+    pub fn with_swap_rate(mut self, offer_denom: &str, ask_denom: &str, rate: Decimal) -> Self {
+        self.app
+            .init_modules(|router, _, _| router.custom.set_swap_rate(offer_denom, ask_denom, rate));
+        self
+    }
+
+    /// Mints `coins` to `address`
+    pub fn fund(&mut self, address: &Addr, coins: &[Coin]) {
+        self.app
+            .sudo(SudoMsg::Bank(BankSudo::Mint {
+                to_address: address.to_string(),
+                amount: coins.to_vec(),
+            }))
+            .unwrap();
+    }
+
+    /// Returns a user holding `coins`
+    pub fn create_user(&mut self, name: &str, coins: &[Coin]) -> Addr {
+        let user = Addr::unchecked(name);
+        if !coins.is_empty() {
+            self.fund(&user, coins);
+        }
+
+        user
+    }
+
+    /// Instantiates a token of 6 decimals with the initial balances
+    pub fn create_token(&mut self, symbol: &str, balances: &[(&Addr, u128)]) -> Addr {
+        self.instantiate_token(self.token_code_id, symbol, balances)
+    }
+
+    /// Instantiates a token of 6 decimals with the initial balances, which takes
+    /// a 1% fee from every transfer
+    pub fn create_fee_token(&mut self, symbol: &str, balances: &[(&Addr, u128)]) -> Addr {
+        let code_id = self.app.store_code(fee_token_contract());
+        self.instantiate_token(code_id, symbol, balances)
+    }
+
+    fn instantiate_token(
+        &mut self,
+        code_id: u64,
+        symbol: &str,
+        balances: &[(&Addr, u128)],
+    ) -> Addr {
+        self.app
+            .instantiate_contract(
+                code_id,
+                self.owner.clone(),
+                &TokenInstantiateMsg {
+                    name: format!("{} token", symbol),
+                    symbol: symbol.to_string(),
+                    decimals: 6,
+                    initial_balances: balances
+                        .iter()
+                        .map(|(address, amount)| Cw20Coin {
+                            address: address.to_string(),
+                            amount: Uint128::from(*amount),
+                        })
+                        .collect(),
+                    mint: None,
+                    marketing: None,
+                },
+                &[],
+                symbol,
+                None,
+            )
+            .unwrap()
+    }
+
+    /// Creates the pair of `assets` through the factory, which provides `assets` from
+    /// the owner. The native assets are minted to the owner, who must hold the tokens.
+    pub fn create_pair(&mut self, assets: [Asset; 2]) -> PairInfo {
+        let owner = self.owner.clone();
+        let mut funds = vec![];
+        for asset in assets.iter() {
+            match &asset.info {
+                AssetInfo::NativeToken { denom } => funds.push(coin(asset.amount.u128(), denom)),
+                AssetInfo::Token { contract_addr } => {
+                    self.app
+                        .execute_contract(
+                            owner.clone(),
+                            Addr::unchecked(contract_addr),
+                            &Cw20ExecuteMsg::IncreaseAllowance {
+                                spender: self.factory.to_string(),
+                                amount: asset.amount,
+                                expires: None,
+                            },
+                            &[],
+                        )
+                        .unwrap();
+                }
+            }
+        }
+        funds.sort_by(|a, b| a.denom.cmp(&b.denom));
+        if !funds.is_empty() {
+            self.fund(&owner, &funds);
+        }
+
+        self.app
+            .execute_contract(
+                owner,
+                self.factory.clone(),
+                &FactoryExecuteMsg::CreatePair {
+                    assets: assets.clone(),
+                    allow_unregistered_decimals: Some(true),
+                    pair_params: None,
+                },
+                &funds,
+            )
+            .unwrap();
+
+        self.app
+            .wrap()
+            .query_wasm_smart(
+                &self.factory,
+                &FactoryQueryMsg::Pair {
+                    asset_infos: [assets[0].info.clone(), assets[1].info.clone()],
+                },
+            )
+            .unwrap()
+    }
+
+    /// Builds the message swapping `offer_amount` through the router along `route`
+    pub fn route_msg(&self, route: RouteBuilder, offer_amount: u128) -> CosmosMsg<TerraMsg> {
+        route
+            .build(
+                &MockApi::default(),
+                self.router.as_str(),
+                Uint128::from(offer_amount),
+            )
+            .and_then(|route| route.into_cosmos_msg())
+            .unwrap()
+    }
+
+    /// Executes `msg` as `sender`
+    pub fn execute(&mut self, sender: &Addr, msg: CosmosMsg<TerraMsg>) -> AnyResult<AppResponse> {
+        self.app.execute(sender.clone(), msg)
+    }
+
+    pub fn balance(&self, asset_info: &AssetInfo, address: &Addr) -> Uint128 {
+        match asset_info {
+            AssetInfo::NativeToken { denom } => {
+                self.app
+                    .wrap()
+                    .query_balance(address, denom)
+                    .unwrap()
+                    .amount
+            }
+            AssetInfo::Token { contract_addr } => {
+                let res: BalanceResponse = self
+                    .app
+                    .wrap()
+                    .query_wasm_smart(
+                        contract_addr,
+                        &Cw20QueryMsg::Balance {
+                            address: address.to_string(),
+                        },
+                    )
+                    .unwrap();
+                res.balance
+            }
+        }
+    }
+}
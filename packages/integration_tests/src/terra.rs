@@ -0,0 +1,176 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, bail, Result as AnyResult};
+use classic_bindings::{
+    ExchangeRateItem, ExchangeRatesResponse, SwapResponse, TaxCapResponse, TaxRateResponse,
+    TerraMsg, TerraQuery,
+};
+use cosmwasm_std::{
+    coin, to_binary, Addr, Api, BankMsg, Binary, BlockInfo, Coin, CustomQuery, Decimal, Empty,
+    Event, Querier, Storage, Uint128,
+};
+use cw_multi_test::{AppResponse, BankSudo, CosmosRouter, Module, SudoMsg};
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+
+/// TerraModule stands in for the market and treasury modules of Terra Classic.
+///
+/// Market swaps burn the offer coin and mint the ask coin at a fixed rate, and the
+/// treasury answers with the configured tax rate and caps. The bank does not charge
+/// the tax on transfers, the contracts still deduct it from what they send.
+#[derive(Default)]
+pub struct TerraModule {
+    tax_rate: Decimal,
+    tax_caps: HashMap<String, Uint128>,
+    swap_rates: HashMap<(String, String), Decimal>,
+}
+
+impl TerraModule {
+    /// Sets the tax rate and the caps; denoms without a cap are not taxed
+    pub fn set_tax(&mut self, rate: Decimal, caps: &[(&str, u128)]) {
+        self.tax_rate = rate;
+        self.tax_caps = caps
+            .iter()
+            .map(|(denom, cap)| (denom.to_string(), Uint128::from(*cap)))
+            .collect();
+    }
+
+    /// Sets the amount of `ask_denom` a market swap returns for one `offer_denom`
+    pub fn set_swap_rate(&mut self, offer_denom: &str, ask_denom: &str, rate: Decimal) {
+        self.swap_rates
+            .insert((offer_denom.to_string(), ask_denom.to_string()), rate);
+    }
+
+    fn swap(&self, offer_coin: &Coin, ask_denom: &str) -> AnyResult<Coin> {
+        let rate = self
+            .swap_rates
+            .get(&(offer_coin.denom.clone(), ask_denom.to_string()))
+            .ok_or_else(|| anyhow!("no swap rate from {} to {}", offer_coin.denom, ask_denom))?;
+
+        Ok(coin((offer_coin.amount * *rate).u128(), ask_denom))
+    }
+}
+
+impl Module for TerraModule {
+    type ExecT = TerraMsg;
+    type QueryT = TerraQuery;
+    type SudoT = Empty;
+
+    fn execute<ExecC, QueryC>(
+        &self,
+        api: &dyn Api,
+        storage: &mut dyn Storage,
+        router: &dyn CosmosRouter<ExecC = ExecC, QueryC = QueryC>,
+        block: &BlockInfo,
+        sender: Addr,
+        msg: TerraMsg,
+    ) -> AnyResult<AppResponse>
+    where
+        ExecC: std::fmt::Debug + Clone + PartialEq + JsonSchema + DeserializeOwned + 'static,
+        QueryC: CustomQuery + DeserializeOwned + 'static,
+    {
+        let (to_address, offer_coin, ask_denom) = match msg {
+            TerraMsg::Swap {
+                offer_coin,
+                ask_denom,
+            } => (sender.to_string(), offer_coin, ask_denom),
+            TerraMsg::SwapSend {
+                to_address,
+                offer_coin,
+                ask_denom,
+            } => (to_address, offer_coin, ask_denom),
+        };
+
+        let receive = self.swap(&offer_coin, &ask_denom)?;
+        if receive.amount.is_zero() {
+            bail!("swap returns nothing for {}", offer_coin);
+        }
+
+        // the offer coin leaves the supply and the ask coin enters it
+        router.execute(
+            api,
+            storage,
+            block,
+            sender.clone(),
+            BankMsg::Burn {
+                amount: vec![offer_coin.clone()],
+            }
+            .into(),
+        )?;
+        router.sudo(
+            api,
+            storage,
+            block,
+            SudoMsg::Bank(BankSudo::Mint {
+                to_address: to_address.clone(),
+                amount: vec![receive.clone()],
+            }),
+        )?;
+
+        Ok(AppResponse {
+            events: vec![Event::new("swap").add_attributes(vec![
+                ("trader", sender.to_string()),
+                ("recipient", to_address),
+                ("offer", offer_coin.to_string()),
+                ("swap_coin", receive.to_string()),
+            ])],
+            data: None,
+        })
+    }
+
+    fn sudo<ExecC, QueryC>(
+        &self,
+        _api: &dyn Api,
+        _storage: &mut dyn Storage,
+        _router: &dyn CosmosRouter<ExecC = ExecC, QueryC = QueryC>,
+        _block: &BlockInfo,
+        _msg: Empty,
+    ) -> AnyResult<AppResponse>
+    where
+        ExecC: std::fmt::Debug + Clone + PartialEq + JsonSchema + DeserializeOwned + 'static,
+        QueryC: CustomQuery + DeserializeOwned + 'static,
+    {
+        bail!("sudo is not supported by the terra module")
+    }
+
+    fn query(
+        &self,
+        _api: &dyn Api,
+        _storage: &dyn Storage,
+        _querier: &dyn Querier,
+        _block: &BlockInfo,
+        request: TerraQuery,
+    ) -> AnyResult<Binary> {
+        Ok(match request {
+            TerraQuery::Swap {
+                offer_coin,
+                ask_denom,
+            } => to_binary(&SwapResponse {
+                receive: self.swap(&offer_coin, &ask_denom)?,
+            })?,
+            TerraQuery::TaxRate {} => to_binary(&TaxRateResponse {
+                rate: self.tax_rate,
+            })?,
+            TerraQuery::TaxCap { denom } => to_binary(&TaxCapResponse {
+                cap: self.tax_caps.get(&denom).copied().unwrap_or_default(),
+            })?,
+            TerraQuery::ExchangeRates {
+                base_denom,
+                quote_denoms,
+            } => to_binary(&ExchangeRatesResponse {
+                exchange_rates: quote_denoms
+                    .into_iter()
+                    .filter_map(|quote_denom| {
+                        self.swap_rates
+                            .get(&(base_denom.clone(), quote_denom.clone()))
+                            .map(|rate| ExchangeRateItem {
+                                quote_denom,
+                                exchange_rate: *rate,
+                            })
+                    })
+                    .collect(),
+                base_denom,
+            })?,
+        })
+    }
+}
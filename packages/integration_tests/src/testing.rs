@@ -0,0 +1,758 @@
+use std::str::FromStr;
+
+use classic_terraswap::asset::{Asset, AssetInfo};
+use classic_terraswap::pair::{
+    Cw20HookMsg, ExecuteMsg as PairExecuteMsg, PoolResponse, QueryMsg as PairQueryMsg,
+};
+use classic_terraswap::router::{
+    ExecuteMsg as RouterExecuteMsg, GuardConfig, InstantiateMsg as RouterInstantiateMsg,
+    LastSwapReceiptResponse, QueryMsg as RouterQueryMsg, ReferenceSource, RouteBuilder,
+    SimulateSwapOperationsResponse, SwapOperation, SwapReceipt,
+};
+use cosmwasm_std::{coin, to_binary, Addr, CosmosMsg, Decimal, Empty, StdResult, Uint128, WasmMsg};
+use cw20::{Cw20ExecuteMsg, Cw20QueryMsg, Expiration, MarketingInfoResponse, TokenInfoResponse};
+use cw_multi_test::Executor;
+use proptest::prelude::*;
+
+use crate::suite::{receipt_reader_contract, router_contract, ReceiptReaderMsg, Suite};
+
+fn native(denom: &str) -> AssetInfo {
+    AssetInfo::NativeToken {
+        denom: denom.to_string(),
+    }
+}
+
+fn token(contract_addr: &Addr) -> AssetInfo {
+    AssetInfo::Token {
+        contract_addr: contract_addr.to_string(),
+    }
+}
+
+fn asset(info: &AssetInfo, amount: u128) -> Asset {
+    Asset {
+        info: info.clone(),
+        amount: Uint128::from(amount),
+    }
+}
+
+/// Deploys the pairs uluna-ASSET and ASSET-uusd, and a market swap of uusd to ukrw
+fn setup() -> (Suite, AssetInfo) {
+    let mut suite = Suite::new().with_swap_rate("uusd", "ukrw", Decimal::from_str("1200").unwrap());
+    let owner = suite.owner.clone();
+    let asset_token = token(&suite.create_token("ASSET", &[(&owner, 10_000_000_000)]));
+
+    suite.create_pair([
+        asset(&native("uluna"), 1_000_000_000),
+        asset(&asset_token, 2_000_000_000),
+    ]);
+    suite.create_pair([
+        asset(&asset_token, 2_000_000_000),
+        asset(&native("uusd"), 5_000_000_000),
+    ]);
+
+    (suite, asset_token)
+}
+
+fn try_simulate(
+    suite: &Suite,
+    offer_amount: u128,
+    operations: &[SwapOperation],
+) -> StdResult<Uint128> {
+    let res: SimulateSwapOperationsResponse = suite.app.wrap().query_wasm_smart(
+        &suite.router,
+        &RouterQueryMsg::SimulateSwapOperations {
+            offer_amount: Uint128::from(offer_amount),
+            operations: operations.to_vec(),
+        },
+    )?;
+
+    Ok(res.amount)
+}
+
+fn simulate(suite: &Suite, offer_amount: u128, operations: &[SwapOperation]) -> Uint128 {
+    try_simulate(suite, offer_amount, operations).unwrap()
+}
+
+fn luna_to_krw(asset_token: &AssetInfo) -> RouteBuilder {
+    RouteBuilder::new()
+        .terraswap_hop(native("uluna"), asset_token.clone())
+        .terraswap_hop(asset_token.clone(), native("uusd"))
+        .native_hop("uusd", "ukrw")
+}
+
+#[test]
+fn multi_hop_swap() {
+    let (mut suite, asset_token) = setup();
+    let user = suite.create_user("user0000", &[coin(1_000_000, "uluna")]);
+
+    let route = luna_to_krw(&asset_token);
+    let expected = simulate(&suite, 1_000_000, route.operations());
+    assert!(!expected.is_zero());
+
+    let msg = suite.route_msg(route, 1_000_000);
+    suite.execute(&user, msg).unwrap();
+
+    assert_eq!(suite.balance(&native("ukrw"), &user), expected);
+    assert_eq!(suite.balance(&native("uluna"), &user), Uint128::zero());
+
+    // the router keeps nothing of the route
+    let router = suite.router.clone();
+    for info in [native("uluna"), asset_token, native("uusd"), native("ukrw")] {
+        assert_eq!(suite.balance(&info, &router), Uint128::zero());
+    }
+}
+
+#[test]
+fn multi_hop_swap_with_tax() {
+    let (suite, asset_token) = setup();
+    let untaxed = simulate(&suite, 1_000_000, luna_to_krw(&asset_token).operations());
+    let mut suite = suite.with_tax(
+        Decimal::from_str("0.01").unwrap(),
+        &[("uusd", 1_000_000), ("ukrw", 1_000_000)],
+    );
+    let user = suite.create_user("user0000", &[coin(1_000_000, "uluna")]);
+
+    let route = luna_to_krw(&asset_token);
+    let expected = simulate(&suite, 1_000_000, route.operations());
+    assert!(expected < untaxed);
+
+    let msg = suite.route_msg(route, 1_000_000);
+    suite.execute(&user, msg).unwrap();
+
+    assert_eq!(suite.balance(&native("ukrw"), &user), expected);
+}
+
+#[test]
+fn router_balances_stay_out_of_routes() {
+    let (mut suite, asset_token) = setup();
+    let owner = suite.owner.clone();
+    let router = suite.router.clone();
+    let user = suite.create_user("user0000", &[coin(1_000_000, "uluna")]);
+
+    // the router holds the intermediate and the ask asset of the route
+    suite.fund(
+        &router,
+        &[coin(5_000_000, "uluna"), coin(7_000_000, "uusd")],
+    );
+    if let AssetInfo::Token { contract_addr } = &asset_token {
+        suite
+            .app
+            .execute_contract(
+                owner,
+                Addr::unchecked(contract_addr),
+                &Cw20ExecuteMsg::Transfer {
+                    recipient: router.to_string(),
+                    amount: Uint128::from(3_000_000u128),
+                },
+                &[],
+            )
+            .unwrap();
+    }
+
+    let route = RouteBuilder::new()
+        .terraswap_hop(native("uluna"), asset_token.clone())
+        .terraswap_hop(asset_token.clone(), native("uusd"));
+    let expected = simulate(&suite, 1_000_000, route.operations());
+
+    let msg = suite.route_msg(route, 1_000_000);
+    suite.execute(&user, msg).unwrap();
+
+    // every hop offers the return of the previous one only
+    assert_eq!(suite.balance(&native("uusd"), &user), expected);
+    assert_eq!(
+        suite.balance(&native("uluna"), &router),
+        Uint128::from(5_000_000u128)
+    );
+    assert_eq!(
+        suite.balance(&asset_token, &router),
+        Uint128::from(3_000_000u128)
+    );
+    assert_eq!(
+        suite.balance(&native("uusd"), &router),
+        Uint128::from(7_000_000u128)
+    );
+}
+
+#[test]
+fn swap_receipt_read_in_the_same_transaction() {
+    let (mut suite, asset_token) = setup();
+    let owner = suite.owner.clone();
+    let code_id = suite.app.store_code(receipt_reader_contract());
+    let reader = suite
+        .app
+        .instantiate_contract(code_id, owner, &Empty {}, &[], "reader", None)
+        .unwrap();
+    let user = suite.create_user("user0000", &[coin(1_000_000, "uluna")]);
+
+    let route = luna_to_krw(&asset_token);
+    let expected = simulate(&suite, 1_000_000, route.operations());
+    let msg = match suite.route_msg(route, 1_000_000) {
+        CosmosMsg::Wasm(WasmMsg::Execute { msg, .. }) => msg,
+        _ => panic!("DO NOT ENTER HERE"),
+    };
+
+    // the reader routes for itself and reads the receipt in its next message
+    let res = suite
+        .app
+        .execute_contract(
+            user,
+            reader.clone(),
+            &ReceiptReaderMsg::Route {
+                router: suite.router.to_string(),
+                msg,
+            },
+            &[coin(1_000_000, "uluna")],
+        )
+        .unwrap();
+    let receipt_amount = res
+        .events
+        .iter()
+        .flat_map(|event| event.attributes.iter())
+        .find(|attr| attr.key == "receipt_amount")
+        .map(|attr| attr.value.clone());
+    assert_eq!(receipt_amount, Some(expected.to_string()));
+    assert_eq!(suite.balance(&native("ukrw"), &reader), expected);
+
+    let receipt = |suite: &Suite| {
+        let res: LastSwapReceiptResponse = suite
+            .app
+            .wrap()
+            .query_wasm_smart(
+                &suite.router,
+                &RouterQueryMsg::LastSwapReceipt {
+                    account: reader.to_string(),
+                },
+            )
+            .unwrap();
+        res.receipt
+    };
+    assert_eq!(
+        receipt(&suite),
+        Some(SwapReceipt {
+            target_asset: native("ukrw"),
+            amount: expected,
+            block_height: suite.app.block_info().height,
+        })
+    );
+
+    // the receipt is gone in the next block
+    suite.app.update_block(|block| block.height += 1);
+    assert_eq!(receipt(&suite), None);
+}
+
+#[test]
+fn minimum_receive_failure() {
+    let (mut suite, asset_token) = setup();
+    let user = suite.create_user("user0000", &[coin(1_000_000, "uluna")]);
+
+    let route = luna_to_krw(&asset_token);
+    let expected = simulate(&suite, 1_000_000, route.operations());
+
+    let msg = suite.route_msg(route.minimum_receive(expected + Uint128::one()), 1_000_000);
+    let err = suite.execute(&user, msg).unwrap_err();
+    assert_eq!(
+        err.root_cause().to_string(),
+        format!(
+            "assertion failed; minimum receive amount: {}, swap amount: {}",
+            expected + Uint128::one(),
+            expected
+        )
+    );
+
+    // the whole route is reverted
+    assert_eq!(
+        suite.balance(&native("uluna"), &user),
+        Uint128::from(1_000_000u128)
+    );
+    assert_eq!(suite.balance(&native("ukrw"), &user), Uint128::zero());
+}
+
+#[test]
+fn cw20_initiated_swap() {
+    let (mut suite, asset_token) = setup();
+    let owner = suite.owner.clone();
+    let user = suite.create_user("user0000", &[]);
+    let receiver = suite.create_user("receiver", &[]);
+
+    // hand some of the owner's tokens to the user
+    let AssetInfo::Token { contract_addr } = &asset_token else {
+        unreachable!()
+    };
+    suite
+        .app
+        .execute_contract(
+            owner,
+            Addr::unchecked(contract_addr),
+            &Cw20ExecuteMsg::Transfer {
+                recipient: user.to_string(),
+                amount: Uint128::from(1_000_000u128),
+            },
+            &[],
+        )
+        .unwrap();
+
+    let route = RouteBuilder::new()
+        .terraswap_hop(asset_token.clone(), native("uluna"))
+        .to(receiver.to_string());
+    let expected = simulate(&suite, 1_000_000, route.operations());
+
+    let msg = suite.route_msg(route, 1_000_000);
+    suite.execute(&user, msg).unwrap();
+
+    assert_eq!(suite.balance(&asset_token, &user), Uint128::zero());
+    assert_eq!(suite.balance(&native("uluna"), &receiver), expected);
+}
+
+#[test]
+fn cw20_swap_with_minimum_receive_price() {
+    let (mut suite, asset_token) = setup();
+    let owner = suite.owner.clone();
+    let route = || RouteBuilder::new().terraswap_hop(asset_token.clone(), native("uluna"));
+
+    // the price of the simulation holds, a price above it does not
+    let expected = simulate(&suite, 1_000_000, route().operations());
+    let price = Decimal::from_ratio(expected, 1_000_000u128);
+    let above = price + Decimal::from_ratio(1u128, 1_000_000u128);
+
+    let msg = suite.route_msg(route().minimum_receive_price(above), 1_000_000);
+    let err = suite.execute(&owner, msg).unwrap_err();
+    assert_eq!(
+        err.root_cause().to_string(),
+        format!(
+            "assertion failed; minimum receive amount: {}, swap amount: {}",
+            expected + Uint128::one(),
+            expected
+        )
+    );
+
+    let msg = suite.route_msg(route().minimum_receive_price(price), 1_000_000);
+    suite.execute(&owner, msg).unwrap();
+    assert_eq!(suite.balance(&native("uluna"), &owner), expected);
+}
+
+#[test]
+fn route_to_fee_on_transfer_token() {
+    let mut suite = Suite::new();
+    let owner = suite.owner.clone();
+    let user = suite.create_user("user", &[coin(2_000_000, "uusd")]);
+    let asset_token = token(&suite.create_token("ASSET", &[(&owner, 1_000_000_000)]));
+    let fee_token = token(&suite.create_fee_token("FEE", &[(&owner, 1_000_000_000)]));
+    suite.create_pair([
+        asset(&native("uusd"), 1_000_000_000),
+        asset(&asset_token, 500_000_000),
+    ]);
+    suite.create_pair([
+        asset(&asset_token, 500_000_000),
+        asset(&fee_token, 1_000_000_000),
+    ]);
+    let route = || {
+        RouteBuilder::new()
+            .terraswap_hop(native("uusd"), asset_token.clone())
+            .terraswap_hop(asset_token.clone(), fee_token.clone())
+    };
+    let set_fee = |suite: &mut Suite, bps: u16| {
+        suite
+            .app
+            .execute_contract(
+                owner.clone(),
+                suite.router.clone(),
+                &RouterExecuteMsg::SetTokenTransferFee {
+                    token: fee_token.to_string(),
+                    bps,
+                },
+                &[],
+            )
+            .unwrap();
+    };
+
+    // the receiver gets 1% less than the pair returns, so the quote is not met
+    let quoted = simulate(&suite, 1_000_000, route().operations());
+    let msg = suite.route_msg(route().minimum_receive(quoted), 1_000_000);
+    let err = suite.execute(&user, msg).unwrap_err();
+    let received = quoted - quoted * Decimal::percent(1);
+    assert_eq!(
+        err.root_cause().to_string(),
+        format!(
+            "assertion failed; minimum receive amount: {}, swap amount: {}",
+            quoted, received
+        )
+    );
+
+    // once registered the quote is what arrives
+    set_fee(&mut suite, 100);
+    assert_eq!(simulate(&suite, 1_000_000, route().operations()), received);
+    let msg = suite.route_msg(route().minimum_receive(received), 1_000_000);
+    suite.execute(&user, msg).unwrap();
+    assert_eq!(suite.balance(&fee_token, &user), received);
+
+    // a minimum quoted without the fee is not met
+    set_fee(&mut suite, 0);
+    let quoted = simulate(&suite, 1_000_000, route().operations());
+    set_fee(&mut suite, 100);
+    let msg = suite.route_msg(route().minimum_receive(quoted), 1_000_000);
+    let err = suite.execute(&user, msg).unwrap_err();
+    assert_eq!(
+        err.root_cause().to_string(),
+        format!(
+            "assertion failed; minimum receive amount: {}, swap amount: {}",
+            quoted,
+            quoted - quoted * Decimal::percent(1)
+        )
+    );
+}
+
+#[test]
+fn price_guard_halts_manipulated_pool() {
+    let (mut suite, asset_token) = setup();
+    let owner = suite.owner.clone();
+    let whale = suite.create_user("whale", &[coin(500_000_000, "uluna")]);
+    let user = suite.create_user("user", &[coin(1_000_000, "uluna")]);
+    let route = || RouteBuilder::new().terraswap_hop(native("uluna"), asset_token.clone());
+
+    // the uluna-ASSET pool starts at 2 ASSET per uluna
+    suite
+        .app
+        .execute_contract(
+            owner,
+            suite.router.clone(),
+            &RouterExecuteMsg::SetPriceGuard {
+                offer_asset_info: native("uluna"),
+                ask_asset_info: asset_token.clone(),
+                guard: Some(GuardConfig {
+                    reference: ReferenceSource::Constant {
+                        price: Decimal::from_ratio(2u128, 1u128),
+                    },
+                    max_deviation: Decimal::percent(10),
+                }),
+            },
+            &[],
+        )
+        .unwrap();
+    let msg = suite.route_msg(route(), 1_000);
+    suite.execute(&user, msg).unwrap();
+
+    // a dump of uluna takes the pool more than 10% off the reference, which
+    // halts the routes through it from then on
+    let msg = suite.route_msg(route(), 500_000_000);
+    suite.execute(&whale, msg).unwrap();
+    let msg = suite.route_msg(route(), 1_000);
+    let err = suite.execute(&user, msg).unwrap_err();
+    assert!(err
+        .root_cause()
+        .to_string()
+        .starts_with(&format!("price of {} per uluna deviates", asset_token)));
+}
+
+#[test]
+fn router_validates_factories() {
+    let mut suite = Suite::new();
+    let owner = suite.owner.clone();
+    let not_factory = suite.create_token("ASSET", &[]);
+    let router_code_id = suite.app.store_code(router_contract());
+    let msg = |factory: &Addr| RouterInstantiateMsg {
+        terraswap_factory: factory.to_string(),
+        loop_factory: factory.to_string(),
+        astroport_factory: factory.to_string(),
+        unchecked: false,
+        validate_factories: true,
+    };
+
+    let err = suite
+        .app
+        .instantiate_contract(
+            router_code_id,
+            owner.clone(),
+            &msg(&not_factory),
+            &[],
+            "router",
+            None,
+        )
+        .unwrap_err();
+    assert_eq!(
+        err.root_cause().to_string(),
+        format!(
+            "address {} does not respond to factory RouterBootstrap query",
+            not_factory
+        )
+    );
+
+    let factory = suite.factory.clone();
+    suite
+        .app
+        .instantiate_contract(router_code_id, owner, &msg(&factory), &[], "router", None)
+        .unwrap();
+}
+
+#[test]
+fn liquidity_token_marketing() {
+    let mut suite = Suite::new();
+    let owner = suite.owner.clone();
+    let asset_token = token(&suite.create_token("ASSET", &[(&owner, 1_000_000)]));
+    let pair = suite.create_pair([
+        asset(&native("uluna"), 1_000_000),
+        asset(&asset_token, 1_000_000),
+    ]);
+
+    let token_info: TokenInfoResponse = suite
+        .app
+        .wrap()
+        .query_wasm_smart(&pair.liquidity_token, &Cw20QueryMsg::TokenInfo {})
+        .unwrap();
+    assert_eq!(token_info.symbol, "uLP-uluna-AS");
+
+    let marketing_info: MarketingInfoResponse = suite
+        .app
+        .wrap()
+        .query_wasm_smart(&pair.liquidity_token, &Cw20QueryMsg::MarketingInfo {})
+        .unwrap();
+    assert_eq!(
+        marketing_info.marketing,
+        Some(Addr::unchecked(&pair.contract_addr))
+    );
+}
+
+#[test]
+fn operator_withdraws_liquidity() {
+    let mut suite = Suite::new();
+    let owner = suite.owner.clone();
+    let operator = suite.create_user("operator", &[]);
+    let asset_token = token(&suite.create_token("ASSET", &[(&owner, 1_000_000)]));
+    let pair = suite.create_pair([
+        asset(&native("uluna"), 1_000_000),
+        asset(&asset_token, 1_000_000),
+    ]);
+    let liquidity_token = token(&Addr::unchecked(&pair.liquidity_token));
+    let shares = suite.balance(&liquidity_token, &owner);
+
+    // the owner approves the operator until the next block
+    let expires = Expiration::AtHeight(suite.app.block_info().height + 1);
+    suite
+        .app
+        .execute_contract(
+            owner.clone(),
+            Addr::unchecked(&pair.liquidity_token),
+            &Cw20ExecuteMsg::IncreaseAllowance {
+                spender: operator.to_string(),
+                amount: Uint128::from(200_000u128),
+                expires: Some(expires),
+            },
+            &[],
+        )
+        .unwrap();
+
+    let withdraw = |amount: u128| Cw20ExecuteMsg::SendFrom {
+        owner: owner.to_string(),
+        contract: pair.contract_addr.clone(),
+        amount: Uint128::from(amount),
+        msg: to_binary(&Cw20HookMsg::WithdrawLiquidity {
+            min_assets: None,
+            deadline: None,
+            receiver: Some(owner.to_string()),
+        })
+        .unwrap(),
+    };
+    suite
+        .app
+        .execute_contract(
+            operator.clone(),
+            Addr::unchecked(&pair.liquidity_token),
+            &withdraw(100_000),
+            &[],
+        )
+        .unwrap();
+
+    // the share is burnt and the refund goes to the owner
+    assert_eq!(
+        suite.balance(&liquidity_token, &owner),
+        shares - Uint128::from(100_000u128)
+    );
+    assert_eq!(
+        suite.balance(&native("uluna"), &owner),
+        Uint128::from(100_000u128)
+    );
+    assert_eq!(
+        suite.balance(&asset_token, &owner),
+        Uint128::from(100_000u128)
+    );
+    assert_eq!(suite.balance(&native("uluna"), &operator), Uint128::zero());
+    let token_info: TokenInfoResponse = suite
+        .app
+        .wrap()
+        .query_wasm_smart(&pair.liquidity_token, &Cw20QueryMsg::TokenInfo {})
+        .unwrap();
+    assert_eq!(token_info.total_supply, Uint128::from(900_000u128));
+
+    // the allowance expires
+    suite.app.update_block(|block| block.height += 1);
+    let err = suite
+        .app
+        .execute_contract(
+            operator,
+            Addr::unchecked(&pair.liquidity_token),
+            &withdraw(100_000),
+            &[],
+        )
+        .unwrap_err();
+    assert_eq!(err.root_cause().to_string(), "Allowance is expired");
+}
+
+#[test]
+fn pool_reports_lp_supply() {
+    let mut suite = Suite::new();
+    let owner = suite.owner.clone();
+    let asset_token = token(&suite.create_token("ASSET", &[(&owner, 2_000_000)]));
+    let pair = suite.create_pair([
+        asset(&native("uluna"), 1_000_000),
+        asset(&asset_token, 1_000_000),
+    ]);
+
+    let assert_total_share = |suite: &Suite, expected: u128| {
+        let pool: PoolResponse = suite
+            .app
+            .wrap()
+            .query_wasm_smart(&pair.contract_addr, &PairQueryMsg::Pool {})
+            .unwrap();
+        let token_info: TokenInfoResponse = suite
+            .app
+            .wrap()
+            .query_wasm_smart(&pair.liquidity_token, &Cw20QueryMsg::TokenInfo {})
+            .unwrap();
+        assert_eq!(pool.total_share, token_info.total_supply);
+        assert_eq!(pool.total_share, Uint128::from(expected));
+    };
+    assert_total_share(&suite, 1_000_000);
+
+    // a provision mints to the supply
+    suite
+        .app
+        .execute_contract(
+            owner.clone(),
+            Addr::unchecked(asset_token.to_string()),
+            &Cw20ExecuteMsg::IncreaseAllowance {
+                spender: pair.contract_addr.clone(),
+                amount: Uint128::from(500_000u128),
+                expires: None,
+            },
+            &[],
+        )
+        .unwrap();
+    suite.fund(&owner, &[coin(500_000, "uluna")]);
+    suite
+        .app
+        .execute_contract(
+            owner.clone(),
+            Addr::unchecked(&pair.contract_addr),
+            &PairExecuteMsg::ProvideLiquidity {
+                assets: [
+                    asset(&native("uluna"), 500_000),
+                    asset(&asset_token, 500_000),
+                ],
+                receiver: None,
+                deadline: None,
+                slippage_tolerance: None,
+                min_lp_to_receive: None,
+            },
+            &[coin(500_000, "uluna")],
+        )
+        .unwrap();
+    assert_total_share(&suite, 1_500_000);
+
+    // a withdrawal burns from it
+    suite
+        .app
+        .execute_contract(
+            owner,
+            Addr::unchecked(&pair.liquidity_token),
+            &Cw20ExecuteMsg::Send {
+                contract: pair.contract_addr.clone(),
+                amount: Uint128::from(300_000u128),
+                msg: to_binary(&Cw20HookMsg::WithdrawLiquidity {
+                    min_assets: None,
+                    deadline: None,
+                    receiver: None,
+                })
+                .unwrap(),
+            },
+            &[],
+        )
+        .unwrap();
+    assert_total_share(&suite, 1_200_000);
+}
+
+/// Deploys a route of `hops` terraswap hops over `reserves`, alternating native and
+/// token assets from uluna, with every native denom taxed at `tax_rate` up to `tax_cap`
+fn setup_route(
+    hops: usize,
+    reserves: &[(u128, u128)],
+    tax_rate: Decimal,
+    tax_cap: u128,
+) -> (Suite, Vec<AssetInfo>) {
+    let mut suite = Suite::new().with_tax(
+        tax_rate,
+        &[("uluna", tax_cap), ("uusd", tax_cap), ("ukrw", tax_cap)],
+    );
+    let owner = suite.owner.clone();
+    let assets = [
+        native("uluna"),
+        token(&suite.create_token("ASSETA", &[(&owner, u64::MAX.into())])),
+        native("uusd"),
+        token(&suite.create_token("ASSETB", &[(&owner, u64::MAX.into())])),
+        native("ukrw"),
+    ];
+
+    for (i, (offer_reserve, ask_reserve)) in reserves.iter().take(hops).enumerate() {
+        suite.create_pair([
+            asset(&assets[i], *offer_reserve),
+            asset(&assets[i + 1], *ask_reserve),
+        ]);
+    }
+
+    (suite, assets[..=hops].to_vec())
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    /// Executing a route delivers at least its simulation, so a minimum receive of
+    /// exactly the simulated amount never fails
+    #[test]
+    fn simulation_execution_parity(
+        hops in 1usize..=4,
+        reserves in prop::collection::vec(
+            (1_000u128..1_000_000_000_000, 1_000u128..1_000_000_000_000),
+            4,
+        ),
+        offer_amount in 1u128..10_000_000_000,
+        tax_permille in 0u64..=20,
+        tax_cap in 0u128..10_000_000,
+        market_rate in prop::option::of(1u64..100_000),
+    ) {
+        let (mut suite, mut assets) =
+            setup_route(hops, &reserves, Decimal::permille(tax_permille), tax_cap);
+        let mut route = assets
+            .windows(2)
+            .fold(RouteBuilder::new(), |route, infos| {
+                route.terraswap_hop(infos[0].clone(), infos[1].clone())
+            });
+
+        // a route ending in a native denom may finish with a market swap
+        if let (Some(rate), AssetInfo::NativeToken { denom }) = (market_rate, assets[hops].clone()) {
+            let ask_denom = if denom == "uusd" { "ukrw" } else { "uusd" };
+            suite = suite.with_swap_rate(&denom, ask_denom, Decimal::permille(rate));
+            route = route.native_hop(denom, ask_denom);
+            assets.push(native(ask_denom));
+        }
+
+        let simulated = try_simulate(&suite, offer_amount, route.operations());
+        prop_assume!(matches!(simulated, Ok(amount) if !amount.is_zero()));
+        let simulated = simulated.unwrap();
+
+        let user = suite.create_user("user0000", &[coin(offer_amount, "uluna")]);
+        let msg = suite.route_msg(route.minimum_receive(simulated), offer_amount);
+        let res = suite.execute(&user, msg);
+        prop_assert!(res.is_ok(), "simulated {} but failed: {:?}", simulated, res.unwrap_err());
+
+        let executed = suite.balance(assets.last().unwrap(), &user);
+        prop_assert!(executed >= simulated, "simulated {} but executed {}", simulated, executed);
+    }
+}